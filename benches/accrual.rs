@@ -0,0 +1,90 @@
+//! The accrual walk behind `get_liquidity_position_balances`, on the positions in
+//! `tests/fixtures` (hand-built so far, see `tests/golden.rs`): the math alone, the math
+//! over a long stretch without a bookkeeping update, and the whole call with its account
+//! reads served from memory.
+//!
+//! ```sh
+//! cargo bench --bench accrual
+//! ```
+
+use std::{hint::black_box, path::Path};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use twob_market_making::{core::position_balances, state::fixture::PositionFixture};
+
+fn fixtures() -> Vec<(String, PositionFixture)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures = std::fs::read_dir(&dir)
+        .expect("reading tests/fixtures")
+        .map(|entry| entry.expect("reading tests/fixtures").path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            (
+                name,
+                PositionFixture::load(&path).expect("loading a fixture"),
+            )
+        })
+        .collect::<Vec<_>>();
+    fixtures.sort_by(|a, b| a.0.cmp(&b.0));
+    fixtures
+}
+
+fn math(c: &mut Criterion) {
+    let mut group = c.benchmark_group("position_balances");
+    for (name, fixture) in fixtures() {
+        let accounts = fixture.accounts().expect("decoding a fixture");
+        group.bench_function(BenchmarkId::from_parameter(&name), |b| {
+            b.iter(|| {
+                position_balances(
+                    black_box(&accounts.position),
+                    &accounts.bookkeeping,
+                    &accounts.market,
+                    &accounts.exits,
+                    black_box(fixture.current_slot),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn long_walk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("position_balances_behind");
+    let (_, fixture) = fixtures().into_iter().next().expect("a fixture");
+    let accounts = fixture.accounts().expect("decoding a fixture");
+    let window = accounts.market.end_slot_interval * twob_market_making::ARRAY_LENGTH;
+    for windows in [10, 100, 1_000] {
+        let current_slot = fixture.current_slot + windows * window;
+        group.bench_function(BenchmarkId::from_parameter(windows), |b| {
+            b.iter(|| {
+                position_balances(
+                    &accounts.position,
+                    &accounts.bookkeeping,
+                    &accounts.market,
+                    &accounts.exits,
+                    black_box(current_slot),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn replay(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("starting a runtime");
+    let mut group = c.benchmark_group("get_liquidity_position_balances");
+    for (name, fixture) in fixtures() {
+        group.bench_function(BenchmarkId::from_parameter(&name), |b| {
+            b.to_async(&runtime)
+                .iter(|| async { fixture.replay().await.expect("replaying a fixture") })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, math, long_walk, replay);
+criterion_main!(benches);
@@ -0,0 +1,6 @@
+fn main() {
+    println!("cargo:rerun-if-changed=proto/control.proto");
+
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/control.proto").expect("failed to compile control.proto");
+}
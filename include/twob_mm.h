@@ -0,0 +1,71 @@
+/*
+ * C interface to the TwoB position math of twob-market-making, built with
+ * `--no-default-features --features ffi`. See src/ffi.rs.
+ *
+ * Accounts are passed as the raw data an RPC returns. Every function returns a
+ * TWOB_* status and writes its result only on TWOB_OK.
+ */
+#ifndef TWOB_MM_H
+#define TWOB_MM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TWOB_OK 0
+#define TWOB_NULL_ARGUMENT 1
+#define TWOB_INVALID_ACCOUNT 2
+#define TWOB_STALE_SLOT 3
+#define TWOB_OVERFLOW 4
+
+/* A position's balances, in the tokens' smallest units. */
+typedef struct TwobBalances {
+    uint64_t base_balance;
+    uint64_t quote_balance;
+    uint64_t base_debt;
+    uint64_t quote_debt;
+} TwobBalances;
+
+/* The data of the exits account at `index`. */
+typedef struct TwobExits {
+    uint64_t index;
+    const uint8_t *data;
+    size_t len;
+} TwobExits;
+
+/* Slots until each side runs dry; draining_* is 0 for a side not being drained. */
+typedef struct TwobRunway {
+    uint8_t draining_base;
+    uint64_t base_slots;
+    uint8_t draining_quote;
+    uint64_t quote_slots;
+} TwobRunway;
+
+/* The first and last index of the exits accounts twob_position_balances reads. */
+int32_t twob_exits_window(const uint8_t *bookkeeping, size_t bookkeeping_len,
+                          const uint8_t *market, size_t market_len,
+                          uint64_t current_slot, uint64_t *first, uint64_t *last);
+
+/*
+ * The position's balances at current_slot; a later slot projects them forward. An
+ * exits account left out is read as having no exits.
+ */
+int32_t twob_position_balances(const uint8_t *position, size_t position_len,
+                               const uint8_t *bookkeeping, size_t bookkeeping_len,
+                               const uint8_t *market, size_t market_len,
+                               const TwobExits *exits, size_t exits_len,
+                               uint64_t current_slot, TwobBalances *out);
+
+/* Slots until the position, holding balances, runs dry on each side. */
+int32_t twob_runway(const uint8_t *position, size_t position_len,
+                    const uint8_t *market, size_t market_len,
+                    const TwobBalances *balances, TwobRunway *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TWOB_MM_H */
@@ -0,0 +1,154 @@
+//! Account data as the program stores it: the account's 8-byte discriminator, then the
+//! account in Borsh. [`serialize_account`] and [`deserialize_account`] round-trip every
+//! TwoB account type through that layout, and [`AccountKind::of`] tells which type a
+//! blob of account data holds, for indexers, geyser plugins and test fixtures that take
+//! this crate as the definition of the layout.
+
+use anchor_lang::{AccountDeserialize, AccountSerialize, Discriminator};
+
+pub use crate::twob_anchor::accounts::{
+    Bookkeeping, Exits, LiquidityPosition, Market, Prices, ProgramConfig, TradePosition,
+};
+
+pub const BOOKKEEPING_DISCRIMINATOR: &[u8] = Bookkeeping::DISCRIMINATOR;
+pub const EXITS_DISCRIMINATOR: &[u8] = Exits::DISCRIMINATOR;
+pub const LIQUIDITY_POSITION_DISCRIMINATOR: &[u8] = LiquidityPosition::DISCRIMINATOR;
+pub const MARKET_DISCRIMINATOR: &[u8] = Market::DISCRIMINATOR;
+pub const PRICES_DISCRIMINATOR: &[u8] = Prices::DISCRIMINATOR;
+pub const PROGRAM_CONFIG_DISCRIMINATOR: &[u8] = ProgramConfig::DISCRIMINATOR;
+pub const TRADE_POSITION_DISCRIMINATOR: &[u8] = TradePosition::DISCRIMINATOR;
+
+/// The TwoB program's account types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccountKind {
+    Bookkeeping,
+    Exits,
+    LiquidityPosition,
+    Market,
+    Prices,
+    ProgramConfig,
+    TradePosition,
+}
+
+impl AccountKind {
+    pub const ALL: [Self; 7] = [
+        Self::Bookkeeping,
+        Self::Exits,
+        Self::LiquidityPosition,
+        Self::Market,
+        Self::Prices,
+        Self::ProgramConfig,
+        Self::TradePosition,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bookkeeping => "Bookkeeping",
+            Self::Exits => "Exits",
+            Self::LiquidityPosition => "LiquidityPosition",
+            Self::Market => "Market",
+            Self::Prices => "Prices",
+            Self::ProgramConfig => "ProgramConfig",
+            Self::TradePosition => "TradePosition",
+        }
+    }
+
+    pub fn discriminator(self) -> &'static [u8] {
+        match self {
+            Self::Bookkeeping => BOOKKEEPING_DISCRIMINATOR,
+            Self::Exits => EXITS_DISCRIMINATOR,
+            Self::LiquidityPosition => LIQUIDITY_POSITION_DISCRIMINATOR,
+            Self::Market => MARKET_DISCRIMINATOR,
+            Self::Prices => PRICES_DISCRIMINATOR,
+            Self::ProgramConfig => PROGRAM_CONFIG_DISCRIMINATOR,
+            Self::TradePosition => TRADE_POSITION_DISCRIMINATOR,
+        }
+    }
+
+    /// The type of the account `data` holds, from its discriminator.
+    pub fn of(data: &[u8]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| data.starts_with(kind.discriminator()))
+    }
+}
+
+/// A TwoB account type: one [`serialize_account`] and [`deserialize_account`] take.
+pub trait TwobAccount: AccountSerialize + AccountDeserialize + Discriminator {
+    const KIND: AccountKind;
+}
+
+macro_rules! twob_accounts {
+    ($($account:ident),* $(,)?) => {
+        $(impl TwobAccount for $account {
+            const KIND: AccountKind = AccountKind::$account;
+        })*
+    };
+}
+
+twob_accounts!(
+    Bookkeeping,
+    Exits,
+    LiquidityPosition,
+    Market,
+    Prices,
+    ProgramConfig,
+    TradePosition,
+);
+
+/// `account` as the program stores it: discriminator, then Borsh.
+pub fn serialize_account<T: TwobAccount>(account: &T) -> Vec<u8> {
+    let mut data = Vec::new();
+    account
+        .try_serialize(&mut data)
+        .expect("serializing into a Vec doesn't fail");
+    data
+}
+
+/// The `T` in account `data`, checking its discriminator. Bytes after the account, such
+/// as the padding of an account allocated larger than it needs, are ignored.
+pub fn deserialize_account<T: TwobAccount>(mut data: &[u8]) -> anchor_lang::Result<T> {
+    T::try_deserialize(&mut data)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+
+    #[test]
+    fn round_trips_accounts_and_tells_them_apart() {
+        let market = Market {
+            id: 7,
+            base_mint: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+            start_slot: 100,
+            base_flow: 1 << 70,
+            quote_flow: 3,
+            end_slot_interval: 10,
+            open_positions: 2,
+            accumulated_base_fees: 5,
+            accumulated_quote_fees: 6,
+            fee_bps: 30,
+            unhealthy_liquidity_fee_bps: 100,
+            is_paused: 0,
+            bump: 254,
+        };
+        let data = serialize_account(&market);
+        assert!(data.starts_with(MARKET_DISCRIMINATOR));
+        assert_eq!(AccountKind::of(&data), Some(AccountKind::Market));
+        let decoded: Market = deserialize_account(&data).unwrap();
+        assert_eq!(serialize_account(&decoded), data);
+
+        assert!(deserialize_account::<Bookkeeping>(&data).is_err());
+        assert_eq!(AccountKind::of(&[0; 8]), None);
+        let discriminators = AccountKind::ALL.map(AccountKind::discriminator);
+        assert!(
+            discriminators
+                .iter()
+                .enumerate()
+                .all(|(i, d)| !discriminators[i + 1..].contains(d))
+        );
+    }
+}
@@ -3,7 +3,32 @@
 //! This module provides:
 //! - PDA (Program Derived Address) resolution utilities
 //! - Helper functions for account derivation
+//! - The account discriminators and serialization helpers ([`codec`])
 
+pub mod codec;
 pub mod resolvers;
 
+pub use codec::*;
 pub use resolvers::*;
+
+/// A market with no flows, fees or positions and windows of 10 slot intervals, for tests
+/// to override what they need of.
+#[cfg(test)]
+pub(crate) fn test_market() -> Market {
+    Market {
+        id: 1,
+        base_mint: anchor_lang::prelude::Pubkey::new_unique(),
+        quote_mint: anchor_lang::prelude::Pubkey::new_unique(),
+        start_slot: 0,
+        base_flow: 0,
+        quote_flow: 0,
+        end_slot_interval: 10,
+        open_positions: 0,
+        accumulated_base_fees: 0,
+        accumulated_quote_fees: 0,
+        fee_bps: 0,
+        unhealthy_liquidity_fee_bps: 0,
+        is_paused: 0,
+        bump: 255,
+    }
+}
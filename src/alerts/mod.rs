@@ -0,0 +1,443 @@
+//! Operator alerts for the events that need a human: a position stopped (or failed to),
+//! debt showing up, a circuit breaker or risk limit tripping, a stale price feed, an
+//! unreachable RPC or a bot gone silent. Informational notices such as fills go through the
+//! same sinks.
+//!
+//! Bots raise alerts through an [`Alerter`], which logs every alert, drops repeats of the
+//! same kind for the same market inside a throttle window (informational notices are never
+//! dropped), and fans the rest out to the configured [`AlertSink`]s in the background so a
+//! slow webhook never delays a stop.
+
+pub mod webhook;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::future::{BoxFuture, join_all};
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::config::{env_var, inherit};
+
+pub use webhook::{DiscordSink, SlackSink, TelegramSink, WebhookSink};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    StopExecuted,
+    StopFailed,
+    DebtDetected,
+    CircuitBreakerTripped,
+    FeedStale,
+    RpcDown,
+    /// The RPC endpoint's slot trails the cluster's.
+    RpcLagging,
+    RiskLimitBreached,
+    BotSilent,
+    ArbitrageDetected,
+    MarketUnhealthy,
+    /// The payer's SOL is below the fee floor; only stops are being sent.
+    FeeBalanceLow,
+    /// The day's fee budget is spent; only stops are being sent.
+    FeeBudgetExhausted,
+    /// A position's flows weren't what was just sent, e.g. a competing update won.
+    FlowMismatch,
+    Fill,
+    /// The day's summary of a position's PnL and activity.
+    DailyReport,
+    /// The bot handed its position over to a new signing keypair.
+    KeyRotated,
+    /// A key rotation stopped partway; the bot is paused until an operator steps in.
+    KeyRotationFailed,
+}
+
+impl AlertKind {
+    pub fn severity(self) -> Severity {
+        match self {
+            AlertKind::Fill | AlertKind::DailyReport | AlertKind::KeyRotated => Severity::Info,
+            AlertKind::StopExecuted
+            | AlertKind::FeedStale
+            | AlertKind::ArbitrageDetected
+            | AlertKind::MarketUnhealthy
+            | AlertKind::FlowMismatch => Severity::Warning,
+            AlertKind::StopFailed
+            | AlertKind::DebtDetected
+            | AlertKind::CircuitBreakerTripped
+            | AlertKind::RpcDown
+            | AlertKind::RpcLagging
+            | AlertKind::RiskLimitBreached
+            | AlertKind::BotSilent
+            | AlertKind::FeeBalanceLow
+            | AlertKind::FeeBudgetExhausted
+            | AlertKind::KeyRotationFailed => Severity::Critical,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertKind::StopExecuted => "stop_executed",
+            AlertKind::StopFailed => "stop_failed",
+            AlertKind::DebtDetected => "debt_detected",
+            AlertKind::CircuitBreakerTripped => "circuit_breaker_tripped",
+            AlertKind::FeedStale => "feed_stale",
+            AlertKind::RpcDown => "rpc_down",
+            AlertKind::RpcLagging => "rpc_lagging",
+            AlertKind::RiskLimitBreached => "risk_limit_breached",
+            AlertKind::BotSilent => "bot_silent",
+            AlertKind::ArbitrageDetected => "arbitrage_detected",
+            AlertKind::MarketUnhealthy => "market_unhealthy",
+            AlertKind::FeeBalanceLow => "fee_balance_low",
+            AlertKind::FeeBudgetExhausted => "fee_budget_exhausted",
+            AlertKind::FlowMismatch => "flow_mismatch",
+            AlertKind::Fill => "fill",
+            AlertKind::DailyReport => "daily_report",
+            AlertKind::KeyRotated => "key_rotated",
+            AlertKind::KeyRotationFailed => "key_rotation_failed",
+        }
+    }
+}
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Critical => "CRITICAL",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub severity: Severity,
+    pub bot: String,
+    pub market_id: Option<u64>,
+    pub message: String,
+}
+
+impl Alert {
+    /// One-line human-readable rendering used by the chat sinks.
+    pub fn text(&self) -> String {
+        let scope = match self.market_id {
+            Some(market_id) => format!("{} market {}", self.bot, market_id),
+            None => self.bot.clone(),
+        };
+        format!(
+            "[{}] {} {}: {}",
+            self.severity, scope, self.kind, self.message
+        )
+    }
+}
+
+/// A destination for alerts. Implementations should fail fast; the [`Alerter`] logs
+/// failures and never retries.
+pub trait AlertSink: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AlertConfig {
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub discord_webhook_url: Option<String>,
+    pub slack_webhook_url: Option<String>,
+    /// Receives the alert as JSON.
+    pub webhook_url: Option<String>,
+    /// Repeats of the same kind for the same market inside this window are dropped.
+    pub min_interval: Duration,
+}
+
+impl AlertConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str| {
+            env_var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        let telegram_bot_token = var("ALERT_TELEGRAM_BOT_TOKEN");
+        let telegram_chat_id = var("ALERT_TELEGRAM_CHAT_ID");
+        if telegram_bot_token.is_some() != telegram_chat_id.is_some() {
+            anyhow::bail!(
+                "ALERT_TELEGRAM_BOT_TOKEN and ALERT_TELEGRAM_CHAT_ID must be set together"
+            );
+        }
+
+        let min_interval_secs = env_var("ALERT_MIN_INTERVAL_SECS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse::<u64>()?;
+
+        Ok(Self {
+            telegram_bot_token,
+            telegram_chat_id,
+            discord_webhook_url: var("ALERT_DISCORD_WEBHOOK_URL"),
+            slack_webhook_url: var("ALERT_SLACK_WEBHOOK_URL"),
+            webhook_url: var("ALERT_WEBHOOK_URL"),
+            min_interval: Duration::from_secs(min_interval_secs),
+        })
+    }
+
+    /// Whether any sink is configured, so alerts reach someone beyond the logs.
+    pub fn has_sinks(&self) -> bool {
+        self.telegram_bot_token.is_some()
+            || self.discord_webhook_url.is_some()
+            || self.slack_webhook_url.is_some()
+            || self.webhook_url.is_some()
+    }
+}
+
+/// Fans alerts out to every sink. Cheap to clone; clones share the throttle state.
+#[derive(Clone)]
+pub struct Alerter {
+    bot: String,
+    sinks: Arc<Vec<Box<dyn AlertSink>>>,
+    throttle: Arc<Mutex<Throttle>>,
+}
+
+impl Alerter {
+    pub fn new(
+        bot: impl Into<String>,
+        sinks: Vec<Box<dyn AlertSink>>,
+        min_interval: Duration,
+    ) -> Self {
+        Self {
+            bot: bot.into(),
+            sinks: Arc::new(sinks),
+            throttle: Arc::new(Mutex::new(Throttle::new(min_interval))),
+        }
+    }
+
+    /// Build the sinks enabled in `config`.
+    pub fn from_config(bot: impl Into<String>, config: &AlertConfig) -> anyhow::Result<Self> {
+        let http_client = webhook::http_client()?;
+        let mut sinks: Vec<Box<dyn AlertSink>> = Vec::new();
+        if let (Some(token), Some(chat_id)) = (&config.telegram_bot_token, &config.telegram_chat_id)
+        {
+            sinks.push(Box::new(TelegramSink::new(
+                http_client.clone(),
+                token.clone(),
+                chat_id.clone(),
+            )));
+        }
+        if let Some(url) = &config.discord_webhook_url {
+            sinks.push(Box::new(DiscordSink::new(http_client.clone(), url.clone())));
+        }
+        if let Some(url) = &config.slack_webhook_url {
+            sinks.push(Box::new(SlackSink::new(http_client.clone(), url.clone())));
+        }
+        if let Some(url) = &config.webhook_url {
+            sinks.push(Box::new(WebhookSink::new(http_client.clone(), url.clone())));
+        }
+        Ok(Self::new(bot, sinks, config.min_interval))
+    }
+
+    /// Raise an alert. Returns immediately; delivery happens on a background task.
+    pub fn notify(&self, kind: AlertKind, market_id: Option<u64>, message: impl Into<String>) {
+        let alert = Alert {
+            kind,
+            severity: kind.severity(),
+            bot: self.bot.clone(),
+            market_id,
+            message: message.into(),
+        };
+
+        let allowed = alert.severity == Severity::Info
+            || self
+                .throttle
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .allow(kind, market_id, Instant::now());
+        if !allowed {
+            info!(
+                event.name = "alert_throttled",
+                alert.kind = %kind,
+                market.id = market_id,
+                monotonic_counter.alerts_throttled_total = 1_u64,
+            );
+            return;
+        }
+
+        match alert.severity {
+            Severity::Info => info!(
+                event.name = "alert_raised",
+                alert.kind = %kind,
+                alert.severity = %alert.severity,
+                market.id = market_id,
+                monotonic_counter.alerts_total = 1_u64,
+                "{}",
+                alert.message
+            ),
+            Severity::Warning => warn!(
+                event.name = "alert_raised",
+                alert.kind = %kind,
+                alert.severity = %alert.severity,
+                market.id = market_id,
+                monotonic_counter.alerts_total = 1_u64,
+                "{}",
+                alert.message
+            ),
+            Severity::Critical => error!(
+                event.name = "alert_raised",
+                alert.kind = %kind,
+                alert.severity = %alert.severity,
+                market.id = market_id,
+                monotonic_counter.alerts_total = 1_u64,
+                "{}",
+                alert.message
+            ),
+        }
+
+        if self.sinks.is_empty() {
+            return;
+        }
+        let sinks = self.sinks.clone();
+        tokio::spawn(inherit(async move {
+            let results = join_all(sinks.iter().map(|sink| sink.send(&alert))).await;
+            for (sink, result) in sinks.iter().zip(results) {
+                if let Err(error) = result {
+                    warn!(
+                        event.name = "alert_sink_failed",
+                        alert.kind = %alert.kind,
+                        alert.sink = sink.name(),
+                        ?error,
+                    );
+                }
+            }
+        }));
+    }
+}
+
+/// Remembers when each (kind, market) last went out.
+#[derive(Debug)]
+struct Throttle {
+    min_interval: Duration,
+    last_sent: HashMap<(AlertKind, Option<u64>), Instant>,
+}
+
+impl Throttle {
+    fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last_sent: HashMap::new(),
+        }
+    }
+
+    fn allow(&mut self, kind: AlertKind, market_id: Option<u64>, now: Instant) -> bool {
+        let key = (kind, market_id);
+        if let Some(last) = self.last_sent.get(&key)
+            && now.duration_since(*last) < self.min_interval
+        {
+            return false;
+        }
+        self.last_sent.insert(key, now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn throttle_drops_repeats_per_kind_and_market() {
+        let mut throttle = Throttle::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(throttle.allow(AlertKind::RpcDown, Some(1), start));
+        assert!(!throttle.allow(AlertKind::RpcDown, Some(1), start + Duration::from_secs(30)));
+        assert!(throttle.allow(AlertKind::RpcDown, Some(2), start));
+        assert!(throttle.allow(AlertKind::FeedStale, Some(1), start));
+        assert!(throttle.allow(AlertKind::RpcDown, Some(1), start + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn text_names_bot_market_and_kind() {
+        let alert = Alert {
+            kind: AlertKind::StopExecuted,
+            severity: AlertKind::StopExecuted.severity(),
+            bot: "inventory-flow".to_string(),
+            market_id: Some(3),
+            message: "position stopped".to_string(),
+        };
+        assert_eq!(
+            alert.text(),
+            "[WARNING] inventory-flow market 3 stop_executed: position stopped"
+        );
+    }
+
+    struct CountingSink(Arc<AtomicUsize>);
+
+    impl AlertSink for CountingSink {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn send<'a>(&'a self, _alert: &'a Alert) -> BoxFuture<'a, anyhow::Result<()>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn notify_delivers_unthrottled_alerts_to_every_sink() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let alerter = Alerter::new(
+            "oracle-flow",
+            vec![
+                Box::new(CountingSink(sent.clone())),
+                Box::new(CountingSink(sent.clone())),
+            ],
+            Duration::from_secs(60),
+        );
+
+        alerter.notify(AlertKind::DebtDetected, Some(1), "quote debt 5");
+        alerter.notify(AlertKind::DebtDetected, Some(1), "quote debt 6");
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn notify_never_throttles_informational_alerts() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let alerter = Alerter::new(
+            "fill-notifier",
+            vec![Box::new(CountingSink(sent.clone()))],
+            Duration::from_secs(60),
+        );
+
+        alerter.notify(AlertKind::Fill, Some(1), "sold 1 base");
+        alerter.notify(AlertKind::Fill, Some(1), "sold 2 base");
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+    }
+}
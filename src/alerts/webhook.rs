@@ -0,0 +1,152 @@
+//! Built-in HTTP sinks: Telegram bot messages, Discord and Slack incoming webhooks, and a
+//! generic webhook receiving the alert as JSON.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::future::BoxFuture;
+use serde_json::json;
+
+use super::{Alert, AlertSink};
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) fn http_client() -> anyhow::Result<reqwest::Client> {
+    Ok(reqwest::Client::builder().timeout(SEND_TIMEOUT).build()?)
+}
+
+async fn post_json(
+    client: &reqwest::Client,
+    url: &str,
+    body: &serde_json::Value,
+) -> anyhow::Result<()> {
+    client
+        .post(url)
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()
+        .context("alert webhook rejected the request")?;
+    Ok(())
+}
+
+pub struct TelegramSink {
+    client: reqwest::Client,
+    bot_token: String,
+    chat_id: String,
+}
+
+impl TelegramSink {
+    pub fn new(client: reqwest::Client, bot_token: String, chat_id: String) -> Self {
+        Self {
+            client,
+            bot_token,
+            chat_id,
+        }
+    }
+}
+
+impl AlertSink for TelegramSink {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+            post_json(
+                &self.client,
+                &url,
+                &json!({ "chat_id": self.chat_id, "text": alert.text() }),
+            )
+            .await
+        })
+    }
+}
+
+pub struct DiscordSink {
+    client: reqwest::Client,
+    webhook_url: String,
+}
+
+impl DiscordSink {
+    pub fn new(client: reqwest::Client, webhook_url: String) -> Self {
+        Self {
+            client,
+            webhook_url,
+        }
+    }
+}
+
+impl AlertSink for DiscordSink {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            post_json(
+                &self.client,
+                &self.webhook_url,
+                &json!({ "content": alert.text() }),
+            )
+            .await
+        })
+    }
+}
+
+pub struct SlackSink {
+    client: reqwest::Client,
+    webhook_url: String,
+}
+
+impl SlackSink {
+    pub fn new(client: reqwest::Client, webhook_url: String) -> Self {
+        Self {
+            client,
+            webhook_url,
+        }
+    }
+}
+
+impl AlertSink for SlackSink {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            post_json(
+                &self.client,
+                &self.webhook_url,
+                &json!({ "text": alert.text() }),
+            )
+            .await
+        })
+    }
+}
+
+/// Posts the [`Alert`] itself as JSON, for PagerDuty-style relays and custom receivers.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(client: reqwest::Client, url: String) -> Self {
+        Self { client, url }
+    }
+}
+
+impl AlertSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let body = serde_json::to_value(alert)?;
+            post_json(&self.client, &self.url, &body).await
+        })
+    }
+}
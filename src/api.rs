@@ -0,0 +1,364 @@
+//! Read-only JSON API over market and position state, for monitoring and UIs.
+//!
+//! Enabled with the `api` feature. Each request fetches fresh on-chain state through the
+//! library fetchers; nothing is cached.
+
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
+
+use anchor_client::{Client, ClientError};
+use anchor_lang::prelude::Pubkey;
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::{
+    AccountResolver, BOOKKEEPING_PRECISION_FACTOR, ProgramPayer, TwobError,
+    fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances,
+    portfolio::{Portfolio, fetch_portfolio},
+    twob_anchor::{
+        self,
+        accounts::{Bookkeeping, LiquidityPosition, Market},
+    },
+};
+
+type ApiClient = Arc<Client<ProgramPayer>>;
+
+/// Serve the API on `addr` until the task is dropped.
+pub async fn serve(addr: SocketAddr, client: ApiClient) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(event.name = "api_server_started", api.addr = %addr);
+    axum::serve(listener, router(client)).await?;
+    Ok(())
+}
+
+pub fn router(client: ApiClient) -> Router {
+    Router::new()
+        .route("/markets", get(list_markets))
+        .route("/markets/{id}", get(get_market))
+        .route("/positions/{authority}", get(list_positions))
+        .route("/balances/{market_id}/{authority}", get(get_balances))
+        .route("/portfolio/{authority}", get(get_portfolio))
+        .with_state(client)
+}
+
+#[derive(Debug, Serialize)]
+pub struct MarketView {
+    pub address: String,
+    pub id: u64,
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub start_slot: u64,
+    /// u128 flows are rendered as strings so JSON consumers don't lose precision.
+    pub base_flow: String,
+    pub quote_flow: String,
+    pub end_slot_interval: u64,
+    pub open_positions: u64,
+    pub accumulated_base_fees: u64,
+    pub accumulated_quote_fees: u64,
+    pub fee_bps: u8,
+    pub unhealthy_liquidity_fee_bps: u8,
+    pub is_paused: bool,
+}
+
+impl MarketView {
+    fn new(address: Pubkey, market: &Market) -> Self {
+        Self {
+            address: address.to_string(),
+            id: market.id,
+            base_mint: market.base_mint.to_string(),
+            quote_mint: market.quote_mint.to_string(),
+            start_slot: market.start_slot,
+            base_flow: market.base_flow.to_string(),
+            quote_flow: market.quote_flow.to_string(),
+            end_slot_interval: market.end_slot_interval,
+            open_positions: market.open_positions,
+            accumulated_base_fees: market.accumulated_base_fees,
+            accumulated_quote_fees: market.accumulated_quote_fees,
+            fee_bps: market.fee_bps,
+            unhealthy_liquidity_fee_bps: market.unhealthy_liquidity_fee_bps,
+            is_paused: market.is_paused != 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BookkeepingView {
+    pub base_per_quote: String,
+    pub quote_per_base: String,
+    pub slots_without_trade: u64,
+    pub last_update_slot: u64,
+}
+
+impl From<&Bookkeeping> for BookkeepingView {
+    fn from(bookkeeping: &Bookkeeping) -> Self {
+        Self {
+            base_per_quote: bookkeeping.base_per_quote.to_string(),
+            quote_per_base: bookkeeping.quote_per_base.to_string(),
+            slots_without_trade: bookkeeping.slots_without_trade,
+            last_update_slot: bookkeeping.last_update_slot,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MarketDetailView {
+    pub market: MarketView,
+    pub bookkeeping: BookkeepingView,
+    pub current_slot: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PositionView {
+    pub address: String,
+    pub market_id: u64,
+    pub authority: String,
+    /// Balances as of the position's last on-chain update, in raw token units.
+    pub base_balance: u64,
+    pub quote_balance: u64,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+    pub base_debt: u64,
+    pub quote_debt: u64,
+    pub last_update_slot: u64,
+}
+
+impl PositionView {
+    fn new(address: Pubkey, market_id: u64, position: &LiquidityPosition) -> Self {
+        Self {
+            address: address.to_string(),
+            market_id,
+            authority: position.authority.to_string(),
+            base_balance: (position.base_balance / BOOKKEEPING_PRECISION_FACTOR) as u64,
+            quote_balance: (position.quote_balance / BOOKKEEPING_PRECISION_FACTOR) as u64,
+            base_flow: position.base_flow_u64,
+            quote_flow: position.quote_flow_u64,
+            base_debt: position.base_debt,
+            quote_debt: position.quote_debt,
+            last_update_slot: position.last_update_slot,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalancesView {
+    pub market_id: u64,
+    pub authority: String,
+    pub current_slot: u64,
+    pub base_balance: u64,
+    pub quote_balance: u64,
+    pub base_debt: u64,
+    pub quote_debt: u64,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+}
+
+pub enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::Internal(error)
+    }
+}
+
+impl From<ClientError> for ApiError {
+    fn from(error: ClientError) -> Self {
+        Self::Internal(error.into())
+    }
+}
+
+impl From<TwobError> for ApiError {
+    fn from(error: TwobError) -> Self {
+        match error {
+            TwobError::AccountNotFound { pubkey } => {
+                Self::NotFound(format!("account {} not found", pubkey))
+            }
+            error => Self::Internal(error.into()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Self::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            Self::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            Self::Internal(error) => {
+                error!(event.name = "api_request_failed", ?error);
+                (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+            }
+        };
+        (status, Json(serde_json::json!({ "error": message }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+async fn list_markets(State(client): State<ApiClient>) -> ApiResult<Vec<MarketView>> {
+    let program = client.program(twob_anchor::ID)?;
+    let mut markets: Vec<MarketView> = program
+        .accounts::<Market>(vec![])
+        .await?
+        .iter()
+        .map(|(address, market)| MarketView::new(*address, market))
+        .collect();
+    markets.sort_by_key(|market| market.id);
+    Ok(Json(markets))
+}
+
+async fn get_market(
+    State(client): State<ApiClient>,
+    Path(id): Path<u64>,
+) -> ApiResult<MarketDetailView> {
+    let program = client.program(twob_anchor::ID)?;
+    let state = fetch_market_state(&program, id)
+        .await
+        .map_err(|_| ApiError::NotFound(format!("market {} not found", id)))?;
+    let address = AccountResolver::new(twob_anchor::ID)
+        .market_pda(id)
+        .address();
+
+    Ok(Json(MarketDetailView {
+        market: MarketView::new(address, &state.market),
+        bookkeeping: BookkeepingView::from(&state.bookkeeping),
+        current_slot: state.current_slot,
+    }))
+}
+
+/// Liquidity positions held by `authority` across all markets.
+async fn list_positions(
+    State(client): State<ApiClient>,
+    Path(authority): Path<String>,
+) -> ApiResult<Vec<PositionView>> {
+    let authority = parse_pubkey(&authority)?;
+    let program = client.program(twob_anchor::ID)?;
+    let resolver = AccountResolver::new(twob_anchor::ID);
+
+    let mut markets = program.accounts::<Market>(vec![]).await?;
+    markets.sort_by_key(|(_, market)| market.id);
+
+    let mut positions = Vec::new();
+    for (market_address, market) in markets {
+        let address = resolver
+            .liquidity_position_pda(&market_address, &authority)
+            .address();
+        // A missing account just means no position in this market.
+        if let Ok(position) = program.account::<LiquidityPosition>(address).await {
+            positions.push(PositionView::new(address, market.id, &position));
+        }
+    }
+
+    Ok(Json(positions))
+}
+
+/// Balances accrued up to the current slot, as the bots compute them.
+async fn get_balances(
+    State(client): State<ApiClient>,
+    Path((market_id, authority)): Path<(u64, String)>,
+) -> ApiResult<BalancesView> {
+    let authority = parse_pubkey(&authority)?;
+    let program = client.program(twob_anchor::ID)?;
+    let state = fetch_market_state(&program, market_id)
+        .await
+        .map_err(|_| ApiError::NotFound(format!("market {} not found", market_id)))?;
+    let position = fetch_liquidity_position(&program, market_id, &authority)
+        .await
+        .map_err(|_| {
+            ApiError::NotFound(format!(
+                "no liquidity position for {} in market {}",
+                authority, market_id
+            ))
+        })?;
+
+    let balances = get_liquidity_position_balances(
+        &program,
+        position,
+        state.bookkeeping,
+        state.market,
+        state.current_slot,
+    )
+    .await?;
+
+    Ok(Json(BalancesView {
+        market_id,
+        authority: authority.to_string(),
+        current_slot: state.current_slot,
+        base_balance: balances.base_balance,
+        quote_balance: balances.quote_balance,
+        base_debt: balances.base_debt,
+        quote_debt: balances.quote_debt,
+        base_flow: position.base_flow_u64,
+        quote_flow: position.quote_flow_u64,
+    }))
+}
+
+/// Everything `authority` holds across all markets, valued at each market's flow price.
+async fn get_portfolio(
+    State(client): State<ApiClient>,
+    Path(authority): Path<String>,
+) -> ApiResult<Portfolio> {
+    let authority = parse_pubkey(&authority)?;
+    let program = client.program(twob_anchor::ID)?;
+    let mut market_ids = program
+        .accounts::<Market>(vec![])
+        .await?
+        .into_iter()
+        .map(|(_, market)| market.id)
+        .collect::<Vec<_>>();
+    market_ids.sort_unstable();
+
+    let portfolio = fetch_portfolio(&program, &[authority], &market_ids, &BTreeMap::new()).await?;
+    Ok(Json(portfolio))
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, ApiError> {
+    value
+        .parse()
+        .map_err(|_| ApiError::BadRequest(format!("invalid pubkey `{}`", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn market_view_renders_u128_flows_as_strings() {
+        let market = Market {
+            id: 7,
+            base_mint: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+            start_slot: 1,
+            base_flow: u128::MAX,
+            quote_flow: 2,
+            end_slot_interval: 10,
+            open_positions: 3,
+            accumulated_base_fees: 4,
+            accumulated_quote_fees: 5,
+            fee_bps: 6,
+            unhealthy_liquidity_fee_bps: 7,
+            is_paused: 1,
+            bump: 255,
+        };
+
+        let json = serde_json::to_value(MarketView::new(Pubkey::new_unique(), &market)).unwrap();
+        assert_eq!(json["base_flow"], u128::MAX.to_string());
+        assert_eq!(json["is_paused"], true);
+    }
+
+    #[test]
+    fn rejects_invalid_pubkeys() {
+        assert!(matches!(
+            parse_pubkey("not-a-key"),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! An append-only record of what the bots decided and sent, for compliance and incident
+//! review.
+//!
+//! With `AUDIT_LOG_PATH` set, every strategy decision and every transaction the
+//! [`TxSender`](crate::tx::TxSender) sends is appended to that file as one JSON object per
+//! line: when, at which slot, what action, the inputs it was taken on, and the resulting
+//! signature and status. Unlike the tracing output, the records have a fixed shape, aren't
+//! sampled or filtered by level, and are synced to disk as they are written. The file is
+//! never truncated or rewritten; rotate it from outside.
+//!
+//! Writing is best effort: a record that can't be written is logged and the action goes
+//! ahead anyway.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{config::env_var, event_bus::BusEvent, reporting::Reporting};
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    /// The slot the action was decided at or landed in, where known.
+    pub slot: Option<u64>,
+    /// What wrote the record: `strategy` or `tx`.
+    pub source: &'static str,
+    /// e.g. `update_flows` from a strategy, `update_liquidity_flows` from the sender.
+    pub action: String,
+    pub market_id: Option<u64>,
+    /// What the action was taken on, e.g. balances for a decision or flows for a send.
+    pub inputs: serde_json::Value,
+    pub signature: Option<String>,
+    /// e.g. `decided`, `sent`, `confirmed`, `failed`.
+    pub status: String,
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(source: &'static str, action: &str, status: &str) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            slot: None,
+            source,
+            action: action.to_string(),
+            market_id: None,
+            inputs: serde_json::Value::Null,
+            signature: None,
+            status: status.to_string(),
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// The log `AUDIT_LOG_PATH` names; `None` when unset or when it can't be opened.
+    pub fn from_env() -> Option<Self> {
+        let path = env_var("AUDIT_LOG_PATH")
+            .ok()
+            .filter(|value| !value.trim().is_empty())?;
+        Self::open(path.trim())
+            .inspect_err(|error| warn!(event.name = "audit_log_open_failed", ?error))
+            .ok()
+    }
+
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Opening audit log {}", path.display()))?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `record` and sync it to disk.
+    pub fn write(&self, record: &AuditRecord) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // One write per record keeps concurrent writers' lines whole.
+        file.write_all(&line)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Append `record` to the audit log of the bot `reporting` belongs to, if it has one, and
+/// publish it as an action on its event bus.
+pub fn record(reporting: &Reporting, record: AuditRecord) {
+    reporting.publish(BusEvent::from(&record));
+    let Some(log) = &reporting.audit_log else {
+        return;
+    };
+    if let Err(error) = log.write(&record) {
+        warn!(
+            event.name = "audit_log_write_failed",
+            audit.path = %log.path().display(),
+            audit.action = %record.action,
+            ?error,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn records_are_appended_one_per_line() {
+        let path = std::env::temp_dir().join(format!("twob-audit-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let log = AuditLog::open(&path).unwrap();
+        let sent = AuditRecord {
+            market_id: Some(1),
+            signature: Some("sig".to_string()),
+            inputs: serde_json::json!({ "base_flow": 5 }),
+            ..AuditRecord::new("tx", "update_liquidity_flows", "sent")
+        };
+        log.write(&sent).unwrap();
+        log.write(&AuditRecord {
+            slot: Some(42),
+            ..AuditRecord::new("tx", "update_liquidity_flows", "confirmed")
+        })
+        .unwrap();
+        // Reopening appends rather than truncating.
+        drop(log);
+        AuditLog::open(&path)
+            .unwrap()
+            .write(&AuditRecord::new("strategy", "stop", "decided"))
+            .unwrap();
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["inputs"]["base_flow"], 5);
+        assert_eq!(lines[0]["signature"], "sig");
+        assert_eq!(lines[1]["slot"], 42);
+        assert_eq!(lines[2]["source"], "strategy");
+    }
+}
@@ -0,0 +1,169 @@
+//! Historical price points replayed by the backtester.
+//!
+//! Each `Prices` account stores cumulative bookkeeping snapshots for `ARRAY_LENGTH`
+//! consecutive end-slot boundaries. Flattening those into [`PricePoint`]s gives a slot
+//! series from which a liquidity position's accrual can be recomputed for any flows.
+
+use std::{fs, path::Path};
+
+use anchor_client::Program;
+use anyhow::{Context, ensure};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    ARRAY_LENGTH, AccountResolver, ProgramPayer,
+    twob_anchor::{
+        self,
+        accounts::{Market, Prices},
+    },
+};
+
+/// Cumulative bookkeeping values at one end-slot boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PricePoint {
+    pub slot: u64,
+    pub base_per_quote: u128,
+    pub quote_per_base: u128,
+    pub slots_without_trades: u64,
+    /// External reference price (quote per base, UI units) recorded alongside, if any.
+    #[serde(default)]
+    pub oracle_price: Option<f64>,
+}
+
+/// Fetch the `Prices` accounts covering `start_slot..=end_slot` and flatten them into points.
+///
+/// Indices whose `Prices` account no longer exists (closed or never created) are skipped.
+pub async fn fetch_price_points(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    start_slot: u64,
+    end_slot: u64,
+) -> anyhow::Result<Vec<PricePoint>> {
+    ensure!(
+        start_slot < end_slot,
+        "backtest start slot {} must be before end slot {}",
+        start_slot,
+        end_slot
+    );
+
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let market_pda = resolver.market_pda(market_id);
+    let market = program.account::<Market>(market_pda.address()).await?;
+
+    let start_index = start_slot / ARRAY_LENGTH / market.end_slot_interval;
+    let end_index = end_slot / ARRAY_LENGTH / market.end_slot_interval;
+
+    let mut points = Vec::new();
+    for index in start_index..=end_index {
+        let prices_pda = resolver.prices_pda(&market_pda.address(), index);
+        let prices = match program.account::<Prices>(prices_pda.address()).await {
+            Ok(prices) => prices,
+            Err(error) => {
+                warn!(
+                    event.name = "backtest_prices_account_missing",
+                    market.id = market_id,
+                    prices.index = index,
+                    prices.address = %prices_pda.address(),
+                    ?error,
+                );
+                continue;
+            }
+        };
+
+        points.extend(
+            price_points_from_account(&prices, index, market.end_slot_interval)
+                .into_iter()
+                .filter(|point| (start_slot..=end_slot).contains(&point.slot)),
+        );
+    }
+
+    Ok(points)
+}
+
+/// Flatten one `Prices` account into points, skipping boundaries that were never written.
+pub fn price_points_from_account(
+    prices: &Prices,
+    index: u64,
+    end_slot_interval: u64,
+) -> Vec<PricePoint> {
+    (0..ARRAY_LENGTH)
+        .filter_map(|i| {
+            let base_per_quote = prices.base_per_quote_snapshot[i as usize];
+            let quote_per_base = prices.quote_per_base_snapshot[i as usize];
+            if base_per_quote == 0 && quote_per_base == 0 {
+                return None;
+            }
+
+            Some(PricePoint {
+                slot: (index * ARRAY_LENGTH + i) * end_slot_interval,
+                base_per_quote,
+                quote_per_base,
+                slots_without_trades: prices.slots_without_trades_snapshot[i as usize],
+                oracle_price: None,
+            })
+        })
+        .collect()
+}
+
+pub fn load_price_points(path: impl AsRef<Path>) -> anyhow::Result<Vec<PricePoint>> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read price points from {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse price points from {}", path.display()))
+}
+
+pub fn save_price_points(path: impl AsRef<Path>, points: &[PricePoint]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let contents = serde_json::to_string_pretty(points)?;
+    fs::write(path, contents)
+        .with_context(|| format!("Failed to write price points to {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::prelude::Pubkey;
+
+    #[test]
+    fn flattens_prices_account_and_skips_unwritten_slots() {
+        let mut prices = Prices {
+            owner: Pubkey::new_unique(),
+            base_per_quote_snapshot: [0; 10],
+            quote_per_base_snapshot: [0; 10],
+            slots_without_trades_snapshot: [0; 10],
+            open_positions: 0,
+            index: 3,
+            bump: 255,
+        };
+        prices.base_per_quote_snapshot[0] = 10;
+        prices.quote_per_base_snapshot[0] = 20;
+        prices.base_per_quote_snapshot[2] = 30;
+        prices.quote_per_base_snapshot[2] = 40;
+        prices.slots_without_trades_snapshot[2] = 5;
+
+        let points = price_points_from_account(&prices, 3, 4);
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].slot, 120);
+        assert_eq!(points[1].slot, 128);
+        assert_eq!(points[1].base_per_quote, 30);
+        assert_eq!(points[1].slots_without_trades, 5);
+    }
+
+    #[test]
+    fn price_points_round_trip_through_json() {
+        let points = vec![PricePoint {
+            slot: 1,
+            base_per_quote: u128::from(u64::MAX) * 1_000,
+            quote_per_base: 2,
+            slots_without_trades: 3,
+            oracle_price: Some(84.5),
+        }];
+
+        let json = serde_json::to_string(&points).unwrap();
+        let parsed: Vec<PricePoint> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, points);
+    }
+}
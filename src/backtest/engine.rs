@@ -0,0 +1,599 @@
+//! Replays a single liquidity position against a historical price series.
+//!
+//! Balances are accrued with the same formulas `get_liquidity_position_balances` uses, with
+//! the `Prices` snapshots standing in for the bookkeeping. The position is assumed to be
+//! small relative to the market, so changing its flows does not move the recorded prices.
+
+use anyhow::ensure;
+use serde::Serialize;
+
+use crate::{BOOKKEEPING_PRECISION_FACTOR, LiquidityPositionBalances, backtest::PricePoint};
+
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    /// Initial deposit in raw base token units.
+    pub initial_base: u64,
+    /// Initial deposit in raw quote token units.
+    pub initial_quote: u64,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+    /// Minimum slots between two strategy decisions.
+    pub decision_interval_slots: u64,
+    /// Charged for every simulated transaction (flow update or stop).
+    pub fee_per_update_lamports: u64,
+}
+
+/// What a strategy sees when it is asked for new flows.
+#[derive(Debug, Clone, Copy)]
+pub struct StepContext<'a> {
+    pub slot: u64,
+    pub balances: LiquidityPositionBalances,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+    /// Quote per base in UI units realised by the market over the last interval.
+    pub market_price: Option<f64>,
+    pub oracle_price: Option<f64>,
+    pub config: &'a BacktestConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FlowUpdate {
+    pub base_flow: u64,
+    pub quote_flow: u64,
+}
+
+pub trait BacktestStrategy {
+    fn name(&self) -> &str;
+
+    /// Return new flows, or `None` to keep the current ones.
+    fn decide(&mut self, context: &StepContext<'_>) -> Option<FlowUpdate>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DebtEvent {
+    pub slot: u64,
+    pub base_debt: u64,
+    pub quote_debt: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestReport {
+    pub strategy: String,
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub steps: usize,
+    pub flow_updates: usize,
+    pub fee_spend_lamports: u64,
+    pub debt_events: Vec<DebtEvent>,
+    pub start_price: Option<f64>,
+    pub end_price: Option<f64>,
+    pub start_value: Option<f64>,
+    pub end_value: Option<f64>,
+    /// Value of the initial deposit had it been held untouched until the end price.
+    pub hold_value: Option<f64>,
+    pub pnl: Option<f64>,
+    pub pnl_vs_hold: Option<f64>,
+    pub final_base_balance: u64,
+    pub final_quote_balance: u64,
+}
+
+/// Position state kept in bookkeeping precision, like the on-chain account.
+#[derive(Debug, Clone, Copy)]
+struct SimulatedPosition {
+    base_balance: u128,
+    quote_balance: u128,
+    base_flow: u64,
+    quote_flow: u64,
+}
+
+impl SimulatedPosition {
+    fn new(base: u64, quote: u64) -> Self {
+        Self {
+            base_balance: u128::from(base) * BOOKKEEPING_PRECISION_FACTOR,
+            quote_balance: u128::from(quote) * BOOKKEEPING_PRECISION_FACTOR,
+            base_flow: 0,
+            quote_flow: 0,
+        }
+    }
+
+    fn is_flowing(&self) -> bool {
+        self.base_flow > 0 || self.quote_flow > 0
+    }
+
+    /// Advance the position from `from` to `to`, returning the debt if it ran dry.
+    fn accrue(&mut self, from: &PricePoint, to: &PricePoint) -> Option<DebtEvent> {
+        let active_slots = u128::from(active_slots(from, to));
+
+        let base_outflow = BOOKKEEPING_PRECISION_FACTOR
+            .saturating_mul(active_slots)
+            .saturating_mul(u128::from(self.base_flow));
+        let quote_outflow = BOOKKEEPING_PRECISION_FACTOR
+            .saturating_mul(active_slots)
+            .saturating_mul(u128::from(self.quote_flow));
+        let base_inflow = to
+            .base_per_quote
+            .saturating_sub(from.base_per_quote)
+            .saturating_mul(u128::from(self.quote_flow));
+        let quote_inflow = to
+            .quote_per_base
+            .saturating_sub(from.quote_per_base)
+            .saturating_mul(u128::from(self.base_flow));
+
+        let base_total = self.base_balance.saturating_add(base_inflow);
+        let quote_total = self.quote_balance.saturating_add(quote_inflow);
+        let base_debt = base_outflow.saturating_sub(base_total) / BOOKKEEPING_PRECISION_FACTOR;
+        let quote_debt = quote_outflow.saturating_sub(quote_total) / BOOKKEEPING_PRECISION_FACTOR;
+
+        self.base_balance = base_total.saturating_sub(base_outflow);
+        self.quote_balance = quote_total.saturating_sub(quote_outflow);
+
+        (base_debt > 0 || quote_debt > 0).then_some(DebtEvent {
+            slot: to.slot,
+            base_debt: base_debt as u64,
+            quote_debt: quote_debt as u64,
+        })
+    }
+
+    fn apply(&mut self, change: &PositionChange) {
+        match *change {
+            PositionChange::Flows(update) => {
+                self.base_flow = update.base_flow;
+                self.quote_flow = update.quote_flow;
+            }
+            PositionChange::Deposit { base, quote } => {
+                self.base_balance = self
+                    .base_balance
+                    .saturating_add(u128::from(base) * BOOKKEEPING_PRECISION_FACTOR);
+                self.quote_balance = self
+                    .quote_balance
+                    .saturating_add(u128::from(quote) * BOOKKEEPING_PRECISION_FACTOR);
+            }
+            PositionChange::Withdraw { base, quote } => {
+                self.base_balance = self
+                    .base_balance
+                    .saturating_sub(u128::from(base) * BOOKKEEPING_PRECISION_FACTOR);
+                self.quote_balance = self
+                    .quote_balance
+                    .saturating_sub(u128::from(quote) * BOOKKEEPING_PRECISION_FACTOR);
+            }
+            PositionChange::Close => *self = Self::new(0, 0),
+        }
+    }
+
+    fn balances(&self) -> LiquidityPositionBalances {
+        LiquidityPositionBalances {
+            base_balance: (self.base_balance / BOOKKEEPING_PRECISION_FACTOR) as u64,
+            quote_balance: (self.quote_balance / BOOKKEEPING_PRECISION_FACTOR) as u64,
+            base_debt: 0,
+            quote_debt: 0,
+        }
+    }
+}
+
+fn active_slots(from: &PricePoint, to: &PricePoint) -> u64 {
+    let elapsed = to.slot.saturating_sub(from.slot);
+    let inactive = to
+        .slots_without_trades
+        .saturating_sub(from.slots_without_trades);
+    elapsed.saturating_sub(inactive)
+}
+
+/// Average quote per base (UI units) the market traded at between two points.
+pub fn implied_price(
+    from: &PricePoint,
+    to: &PricePoint,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Option<f64> {
+    let active_slots = active_slots(from, to);
+    let quote_per_base_delta = to.quote_per_base.checked_sub(from.quote_per_base)?;
+    if active_slots == 0 || quote_per_base_delta == 0 {
+        return None;
+    }
+
+    let native =
+        quote_per_base_delta as f64 / BOOKKEEPING_PRECISION_FACTOR as f64 / active_slots as f64;
+    let price = native * 10f64.powi(i32::from(base_token_decimals))
+        / 10f64.powi(i32::from(quote_token_decimals));
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+fn value_quote(balances: &LiquidityPositionBalances, price: f64, config: &BacktestConfig) -> f64 {
+    let base = balances.base_balance as f64 / 10f64.powi(i32::from(config.base_token_decimals));
+    let quote = balances.quote_balance as f64 / 10f64.powi(i32::from(config.quote_token_decimals));
+    base.mul_add(price, quote)
+}
+
+/// Run `strategy` over an ordered price series.
+///
+/// The strategy is consulted at the first point and then at most once per
+/// `decision_interval_slots`. A position that runs into debt is stopped for the rest of
+/// the run, mirroring what `public_stop_liquidity_position` would do on-chain.
+pub fn run_backtest(
+    points: &[PricePoint],
+    strategy: &mut dyn BacktestStrategy,
+    config: &BacktestConfig,
+) -> anyhow::Result<BacktestReport> {
+    ensure!(
+        points.len() >= 2,
+        "backtest needs at least two price points, got {}",
+        points.len()
+    );
+    ensure!(
+        points.windows(2).all(|pair| pair[0].slot < pair[1].slot),
+        "backtest price points must be strictly ordered by slot"
+    );
+
+    let base_decimals = config.base_token_decimals;
+    let quote_decimals = config.quote_token_decimals;
+
+    let mut position = SimulatedPosition::new(config.initial_base, config.initial_quote);
+    let mut stopped = false;
+    let mut last_decision_slot: Option<u64> = None;
+    let mut flow_updates = 0;
+    let mut fee_spend_lamports = 0u64;
+    let mut debt_events = Vec::new();
+
+    let start_price = implied_price(&points[0], &points[1], base_decimals, quote_decimals);
+    let mut market_price = start_price;
+
+    for pair in points.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+
+        let decision_due = last_decision_slot
+            .is_none_or(|slot| from.slot.saturating_sub(slot) >= config.decision_interval_slots);
+        if !stopped && decision_due {
+            last_decision_slot = Some(from.slot);
+            let context = StepContext {
+                slot: from.slot,
+                balances: position.balances(),
+                base_flow: position.base_flow,
+                quote_flow: position.quote_flow,
+                market_price,
+                oracle_price: from.oracle_price,
+                config,
+            };
+
+            let update = strategy.decide(&context).filter(|update| {
+                (update.base_flow, update.quote_flow) != (position.base_flow, position.quote_flow)
+            });
+            if let Some(update) = update {
+                position.base_flow = update.base_flow;
+                position.quote_flow = update.quote_flow;
+                flow_updates += 1;
+                fee_spend_lamports =
+                    fee_spend_lamports.saturating_add(config.fee_per_update_lamports);
+            }
+        }
+
+        if let Some(price) = implied_price(from, to, base_decimals, quote_decimals) {
+            market_price = Some(price);
+        }
+
+        if let Some(event) = position.accrue(from, to) {
+            debt_events.push(event);
+            if position.is_flowing() {
+                position.base_flow = 0;
+                position.quote_flow = 0;
+                fee_spend_lamports =
+                    fee_spend_lamports.saturating_add(config.fee_per_update_lamports);
+            }
+            stopped = true;
+        }
+    }
+
+    let initial = LiquidityPositionBalances {
+        base_balance: config.initial_base,
+        quote_balance: config.initial_quote,
+        base_debt: 0,
+        quote_debt: 0,
+    };
+    let final_balances = position.balances();
+    let end_price = market_price;
+
+    let start_value = start_price.map(|price| value_quote(&initial, price, config));
+    let end_value = end_price.map(|price| value_quote(&final_balances, price, config));
+    let hold_value = end_price.map(|price| value_quote(&initial, price, config));
+
+    Ok(BacktestReport {
+        strategy: strategy.name().to_string(),
+        start_slot: points[0].slot,
+        end_slot: points[points.len() - 1].slot,
+        steps: points.len() - 1,
+        flow_updates,
+        fee_spend_lamports,
+        debt_events,
+        start_price,
+        end_price,
+        start_value,
+        end_value,
+        hold_value,
+        pnl: start_value.zip(end_value).map(|(start, end)| end - start),
+        pnl_vs_hold: hold_value.zip(end_value).map(|(hold, end)| end - hold),
+        final_base_balance: final_balances.base_balance,
+        final_quote_balance: final_balances.quote_balance,
+    })
+}
+
+/// Balances of a replayed position right after accruing up to `slot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BalancePoint {
+    pub slot: u64,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+    pub base_balance: u64,
+    pub quote_balance: u64,
+    pub base_debt: u64,
+    pub quote_debt: u64,
+}
+
+/// Replay a recorded flow schedule over an ordered price series, starting from
+/// `initial_base`/`initial_quote` at the first point.
+///
+/// `schedule` holds `(slot, flows)` pairs ordered by slot; each takes effect from the first
+/// point at or after its slot. Unlike [`run_backtest`] the position is never stopped, so
+/// debt shows up exactly as the recorded flows would have produced it.
+pub fn replay_balances(
+    points: &[PricePoint],
+    schedule: &[(u64, FlowUpdate)],
+    initial_base: u64,
+    initial_quote: u64,
+) -> Vec<BalancePoint> {
+    let changes = std::iter::once((
+        0,
+        PositionChange::Deposit {
+            base: initial_base,
+            quote: initial_quote,
+        },
+    ))
+    .chain(
+        schedule
+            .iter()
+            .map(|(slot, update)| (*slot, PositionChange::Flows(*update))),
+    )
+    .collect::<Vec<_>>();
+    replay_position(points, &changes)
+}
+
+/// Something the position's authority (or a public stop) did to a liquidity position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PositionChange {
+    Flows(FlowUpdate),
+    /// Raw token units added to the balances (`provide_liquidity`, `add_liquidity`).
+    Deposit {
+        base: u64,
+        quote: u64,
+    },
+    /// Raw token units taken out of the balances (`withdraw_liquidity`).
+    Withdraw {
+        base: u64,
+        quote: u64,
+    },
+    /// The position was closed; balances and flows drop to zero.
+    Close,
+}
+
+/// Replay a position's full history, starting empty, over an ordered price series.
+///
+/// `changes` holds `(slot, change)` pairs ordered by slot; like [`replay_balances`] each
+/// takes effect from the first point at or after its slot, so the result is exact only at
+/// end-slot boundaries. Withdrawals larger than the replayed balance saturate at zero.
+pub fn replay_position(
+    points: &[PricePoint],
+    changes: &[(u64, PositionChange)],
+) -> Vec<BalancePoint> {
+    let mut position = SimulatedPosition::new(0, 0);
+    let mut pending = changes.iter().peekable();
+    let mut out = Vec::with_capacity(points.len().saturating_sub(1));
+
+    for pair in points.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        while let Some((_, change)) = pending.next_if(|(slot, _)| *slot <= from.slot) {
+            position.apply(change);
+        }
+
+        let debt = position.accrue(from, to);
+        let balances = position.balances();
+        out.push(BalancePoint {
+            slot: to.slot,
+            base_flow: position.base_flow,
+            quote_flow: position.quote_flow,
+            base_balance: balances.base_balance,
+            quote_balance: balances.quote_balance,
+            base_debt: debt.map_or(0, |event| event.base_debt),
+            quote_debt: debt.map_or(0, |event| event.quote_debt),
+        });
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P: u128 = BOOKKEEPING_PRECISION_FACTOR;
+
+    /// Points every 10 slots for a market trading at a constant native quote-per-base ratio.
+    fn constant_market(steps: u64, quote_per_base_native: u128) -> Vec<PricePoint> {
+        (0..=steps)
+            .map(|i| PricePoint {
+                slot: i * 10,
+                base_per_quote: P * u128::from(i * 10) / quote_per_base_native,
+                quote_per_base: P * quote_per_base_native * u128::from(i * 10),
+                slots_without_trades: 0,
+                oracle_price: None,
+            })
+            .collect()
+    }
+
+    fn config() -> BacktestConfig {
+        BacktestConfig {
+            initial_base: 1_000,
+            initial_quote: 2_000,
+            base_token_decimals: 0,
+            quote_token_decimals: 0,
+            decision_interval_slots: 0,
+            fee_per_update_lamports: 5_000,
+        }
+    }
+
+    struct FixedFlows(FlowUpdate);
+
+    impl BacktestStrategy for FixedFlows {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn decide(&mut self, _context: &StepContext<'_>) -> Option<FlowUpdate> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn implied_price_uses_active_slots_and_decimals() {
+        let from = PricePoint {
+            slot: 0,
+            base_per_quote: 0,
+            quote_per_base: 0,
+            slots_without_trades: 0,
+            oracle_price: None,
+        };
+        let to = PricePoint {
+            slot: 20,
+            quote_per_base: P * 84 * 10,
+            slots_without_trades: 10,
+            ..from
+        };
+
+        assert_eq!(implied_price(&from, &to, 0, 0), Some(84.0));
+        let ui = implied_price(&from, &to, 9, 6).unwrap();
+        assert!((ui - 84_000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn balanced_flows_swap_inventory_at_market_price() {
+        let points = constant_market(10, 2);
+        let mut strategy = FixedFlows(FlowUpdate {
+            base_flow: 1,
+            quote_flow: 2,
+        });
+
+        let report = run_backtest(&points, &mut strategy, &config()).unwrap();
+
+        // 100 slots selling 1 base/slot for 2 quote, and 2 quote/slot for 1 base.
+        assert_eq!(report.final_base_balance, 1_000);
+        assert_eq!(report.final_quote_balance, 2_000);
+        assert_eq!(report.flow_updates, 1);
+        assert_eq!(report.fee_spend_lamports, 5_000);
+        assert!(report.debt_events.is_empty());
+        assert_eq!(report.start_price, Some(2.0));
+        assert!((report.pnl.unwrap()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn debt_stops_the_position() {
+        let points = constant_market(10, 2);
+        let mut strategy = FixedFlows(FlowUpdate {
+            base_flow: 200,
+            quote_flow: 0,
+        });
+
+        let report = run_backtest(&points, &mut strategy, &config()).unwrap();
+
+        assert_eq!(report.debt_events.len(), 1);
+        assert_eq!(report.debt_events[0].slot, 10);
+        assert_eq!(report.debt_events[0].base_debt, 1_000);
+        // One update to start flowing, one stop.
+        assert_eq!(report.fee_spend_lamports, 10_000);
+        assert_eq!(report.final_base_balance, 0);
+    }
+
+    #[test]
+    fn rejects_unordered_points() {
+        let mut points = constant_market(3, 2);
+        points.swap(1, 2);
+        let mut strategy = FixedFlows(FlowUpdate {
+            base_flow: 1,
+            quote_flow: 1,
+        });
+
+        assert!(run_backtest(&points, &mut strategy, &config()).is_err());
+        assert!(run_backtest(&points[..1], &mut strategy, &config()).is_err());
+    }
+
+    #[test]
+    fn replay_applies_scheduled_flows_without_stopping() {
+        let points = constant_market(3, 2);
+        let schedule = [
+            (
+                0,
+                FlowUpdate {
+                    base_flow: 1,
+                    quote_flow: 2,
+                },
+            ),
+            (
+                10,
+                FlowUpdate {
+                    base_flow: 60,
+                    quote_flow: 0,
+                },
+            ),
+        ];
+
+        let replayed = replay_balances(&points, &schedule, 1_000, 2_000);
+
+        assert_eq!(replayed.len(), 3);
+        assert_eq!(replayed[0].base_balance, 1_000);
+        assert_eq!(replayed[0].quote_balance, 2_000);
+        // 10 slots selling 60 base/slot for 2 quote each.
+        assert_eq!(replayed[1].base_flow, 60);
+        assert_eq!(replayed[1].base_balance, 400);
+        assert_eq!(replayed[1].quote_balance, 3_200);
+        assert_eq!(replayed[2].base_balance, 0);
+        assert_eq!(replayed[2].base_debt, 200);
+    }
+
+    #[test]
+    fn replay_position_applies_deposits_withdrawals_and_close() {
+        let points = constant_market(4, 2);
+        let changes = [
+            (
+                0,
+                PositionChange::Deposit {
+                    base: 1_000,
+                    quote: 0,
+                },
+            ),
+            (
+                0,
+                PositionChange::Flows(FlowUpdate {
+                    base_flow: 10,
+                    quote_flow: 0,
+                }),
+            ),
+            (
+                10,
+                PositionChange::Withdraw {
+                    base: 400,
+                    quote: 100,
+                },
+            ),
+            (25, PositionChange::Close),
+        ];
+
+        let replayed = replay_position(&points, &changes);
+
+        assert_eq!(replayed.len(), 4);
+        assert_eq!(replayed[0].base_balance, 900);
+        assert_eq!(replayed[0].quote_balance, 200);
+        assert_eq!(replayed[1].base_balance, 400);
+        assert_eq!(replayed[1].quote_balance, 300);
+        assert_eq!(replayed[2].base_balance, 300);
+        // The close at slot 25 takes effect from the point at slot 30.
+        assert_eq!(replayed[3].base_balance, 0);
+        assert_eq!(replayed[3].quote_balance, 0);
+        assert_eq!(replayed[3].base_flow, 0);
+    }
+}
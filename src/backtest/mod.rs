@@ -0,0 +1,16 @@
+//! Offline replay of flow strategies against recorded or synthetic market prices.
+
+mod data;
+mod engine;
+mod strategy;
+mod synthetic;
+
+pub use data::{
+    PricePoint, fetch_price_points, load_price_points, price_points_from_account, save_price_points,
+};
+pub use engine::{
+    BacktestConfig, BacktestReport, BacktestStrategy, BalancePoint, DebtEvent, FlowUpdate,
+    PositionChange, StepContext, implied_price, replay_balances, replay_position, run_backtest,
+};
+pub use strategy::{InventoryFlowStrategy, OracleFlowStrategy};
+pub use synthetic::{MarketEvent, PricePath, SyntheticMarket};
@@ -0,0 +1,147 @@
+//! Backtest ports of the `inventory-flow` and `oracle-flow` quoting rules.
+
+use crate::{
+    backtest::{BacktestStrategy, FlowUpdate, StepContext},
+    quote::{
+        blended_quote_price, compute_target_flows, liquidity_position_price, sanitize_weight,
+        should_update_quote,
+    },
+};
+
+/// Stream `balance / flow_divisor` of each side, like the `inventory-flow` bin.
+#[derive(Debug, Clone)]
+pub struct InventoryFlowStrategy {
+    pub flow_divisor: u64,
+}
+
+impl BacktestStrategy for InventoryFlowStrategy {
+    fn name(&self) -> &str {
+        "inventory-flow"
+    }
+
+    fn decide(&mut self, context: &StepContext<'_>) -> Option<FlowUpdate> {
+        if self.flow_divisor == 0 {
+            return None;
+        }
+
+        Some(FlowUpdate {
+            base_flow: context.balances.base_balance / self.flow_divisor,
+            quote_flow: context.balances.quote_balance / self.flow_divisor,
+        })
+    }
+}
+
+/// Quote around a blend of oracle and inventory price, like the `oracle-flow` bin.
+///
+/// Falls back to the market's realised price when the series carries no oracle price.
+#[derive(Debug, Clone)]
+pub struct OracleFlowStrategy {
+    pub optimal_quote_weight: f64,
+    pub quote_update_threshold_bps: u64,
+}
+
+impl BacktestStrategy for OracleFlowStrategy {
+    fn name(&self) -> &str {
+        "oracle-flow"
+    }
+
+    fn decide(&mut self, context: &StepContext<'_>) -> Option<FlowUpdate> {
+        let base_decimals = context.config.base_token_decimals;
+        let quote_decimals = context.config.quote_token_decimals;
+
+        let reference_price = context.oracle_price.or(context.market_price)?;
+        let inventory_price =
+            liquidity_position_price(&context.balances, base_decimals, quote_decimals)?;
+        let target_price = blended_quote_price(
+            reference_price,
+            inventory_price,
+            sanitize_weight(self.optimal_quote_weight),
+        );
+        let optimal = compute_target_flows(
+            &context.balances,
+            target_price,
+            inventory_price,
+            base_decimals,
+            quote_decimals,
+        )?;
+
+        should_update_quote(
+            context.base_flow,
+            context.quote_flow,
+            &optimal,
+            self.quote_update_threshold_bps,
+        )
+        .then_some(FlowUpdate {
+            base_flow: optimal.base_flow,
+            quote_flow: optimal.quote_flow,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LiquidityPositionBalances, backtest::BacktestConfig};
+
+    fn config() -> BacktestConfig {
+        BacktestConfig {
+            initial_base: 0,
+            initial_quote: 0,
+            base_token_decimals: 9,
+            quote_token_decimals: 6,
+            decision_interval_slots: 0,
+            fee_per_update_lamports: 0,
+        }
+    }
+
+    fn context<'a>(config: &'a BacktestConfig, oracle_price: Option<f64>) -> StepContext<'a> {
+        StepContext {
+            slot: 0,
+            balances: LiquidityPositionBalances {
+                base_balance: 1_000_000_000,
+                quote_balance: 100_000_000,
+                base_debt: 0,
+                quote_debt: 0,
+            },
+            base_flow: 0,
+            quote_flow: 0,
+            market_price: Some(100.0),
+            oracle_price,
+            config,
+        }
+    }
+
+    #[test]
+    fn inventory_flow_divides_balances() {
+        let config = config();
+        let mut strategy = InventoryFlowStrategy { flow_divisor: 5 };
+
+        let update = strategy.decide(&context(&config, None)).unwrap();
+        assert_eq!(update.base_flow, 200_000_000);
+        assert_eq!(update.quote_flow, 20_000_000);
+        assert!(
+            InventoryFlowStrategy { flow_divisor: 0 }
+                .decide(&context(&config, None))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn oracle_flow_skips_updates_within_threshold() {
+        let config = config();
+        let mut strategy = OracleFlowStrategy {
+            optimal_quote_weight: 0.0,
+            quote_update_threshold_bps: 50,
+        };
+
+        let first = strategy.decide(&context(&config, Some(110.0))).unwrap();
+        // Oracle above inventory price: quote side is limiting and fully streamed.
+        assert_eq!(first.quote_flow, 100_000_000);
+        assert!(first.base_flow < 1_000_000_000);
+
+        let mut current = context(&config, Some(110.0));
+        current.base_flow = first.base_flow;
+        current.quote_flow = first.quote_flow;
+        assert!(strategy.decide(&current).is_none());
+    }
+}
@@ -0,0 +1,367 @@
+//! Deterministic synthetic price series for exercising strategies without chain data.
+//!
+//! Paths are generated in UI price units and converted into the cumulative bookkeeping
+//! values a `Prices` account would have recorded, so the output plugs straight into
+//! [`run_backtest`](crate::backtest::run_backtest).
+
+use anyhow::ensure;
+
+use crate::{BOOKKEEPING_PRECISION_FACTOR, backtest::PricePoint};
+
+/// How the price moves from one step to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PricePath {
+    /// Geometric Brownian motion with per-step drift and volatility.
+    Gbm { drift: f64, volatility: f64 },
+    /// GBM plus jumps of `±jump_size` (fractional) occurring with `jump_probability` per step.
+    JumpDiffusion {
+        drift: f64,
+        volatility: f64,
+        jump_probability: f64,
+        jump_size: f64,
+    },
+    /// Noise-free geometric trend, e.g. `0.001` for +0.1% per step.
+    Trend { change_per_step: f64 },
+}
+
+/// Disruptions layered on top of the price path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarketEvent {
+    /// Price drops by `drop_fraction` at `at_step` and recovers linearly over `recovery_steps`.
+    FlashCrash {
+        at_step: u64,
+        drop_fraction: f64,
+        recovery_steps: u64,
+    },
+    /// No trades for `steps` steps starting at `from_step`.
+    ///
+    /// The program only matches when both sides stream, so a one-sided market records
+    /// the same thing as an empty one: slots without trades and flat cumulative prices.
+    Halt { from_step: u64, steps: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticMarket {
+    pub seed: u64,
+    pub start_slot: u64,
+    pub steps: u64,
+    pub slots_per_step: u64,
+    /// Quote per base in UI units at the first step.
+    pub start_price: f64,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+    pub path: PricePath,
+    pub events: Vec<MarketEvent>,
+    /// Record the (crash-adjusted) path price as the oracle price on every point.
+    pub with_oracle_price: bool,
+}
+
+impl SyntheticMarket {
+    pub fn new(seed: u64, steps: u64, start_price: f64, path: PricePath) -> Self {
+        Self {
+            seed,
+            start_slot: 0,
+            steps,
+            slots_per_step: 100,
+            start_price,
+            base_token_decimals: 9,
+            quote_token_decimals: 6,
+            path,
+            events: Vec::new(),
+            with_oracle_price: true,
+        }
+    }
+
+    /// A calm GBM market with a 30% flash crash halfway through.
+    pub fn flash_crash(seed: u64, steps: u64, start_price: f64) -> Self {
+        let mut market = Self::new(
+            seed,
+            steps,
+            start_price,
+            PricePath::Gbm {
+                drift: 0.0,
+                volatility: 0.002,
+            },
+        );
+        market.events.push(MarketEvent::FlashCrash {
+            at_step: steps / 2,
+            drop_fraction: 0.3,
+            recovery_steps: (steps / 10).max(1),
+        });
+        market
+    }
+
+    /// Counter-flow disappears for the middle third of the run.
+    pub fn one_sided_flow(seed: u64, steps: u64, start_price: f64) -> Self {
+        let mut market = Self::new(
+            seed,
+            steps,
+            start_price,
+            PricePath::Gbm {
+                drift: 0.0,
+                volatility: 0.002,
+            },
+        );
+        market.events.push(MarketEvent::Halt {
+            from_step: steps / 3,
+            steps: steps / 3,
+        });
+        market
+    }
+
+    /// Nobody trades at all.
+    pub fn zero_flow(seed: u64, steps: u64, start_price: f64) -> Self {
+        let mut market = Self::new(
+            seed,
+            steps,
+            start_price,
+            PricePath::Trend {
+                change_per_step: 0.0,
+            },
+        );
+        market.events.push(MarketEvent::Halt {
+            from_step: 0,
+            steps,
+        });
+        market
+    }
+
+    /// Generate `steps + 1` points; identical inputs always yield identical series.
+    pub fn generate(&self) -> anyhow::Result<Vec<PricePoint>> {
+        ensure!(self.steps > 0, "synthetic market needs at least one step");
+        ensure!(
+            self.slots_per_step > 0,
+            "synthetic market needs a positive slots_per_step"
+        );
+        ensure!(
+            self.start_price.is_finite() && self.start_price > 0.0,
+            "synthetic market start price must be positive, got {}",
+            self.start_price
+        );
+
+        let mut rng = SplitMix64::new(self.seed);
+        let base_scale = 10f64.powi(i32::from(self.base_token_decimals));
+        let quote_scale = 10f64.powi(i32::from(self.quote_token_decimals));
+        let precision = BOOKKEEPING_PRECISION_FACTOR as f64;
+
+        let mut path_price = self.start_price;
+        let mut point = PricePoint {
+            slot: self.start_slot,
+            base_per_quote: 0,
+            quote_per_base: 0,
+            slots_without_trades: 0,
+            oracle_price: self.with_oracle_price.then_some(path_price),
+        };
+        let mut points = Vec::with_capacity(self.steps as usize + 1);
+        points.push(point);
+
+        for step in 0..self.steps {
+            let traded_price = path_price * self.crash_multiplier(step);
+            let slots = self.slots_per_step;
+
+            if self.is_halted(step) {
+                point.slots_without_trades += slots;
+            } else {
+                let quote_per_base_native = traded_price * quote_scale / base_scale;
+                point.quote_per_base = point
+                    .quote_per_base
+                    .saturating_add((precision * quote_per_base_native * slots as f64) as u128);
+                point.base_per_quote = point
+                    .base_per_quote
+                    .saturating_add((precision / quote_per_base_native * slots as f64) as u128);
+            }
+
+            path_price = self.next_price(path_price, &mut rng);
+            point.slot += slots;
+            point.oracle_price = self
+                .with_oracle_price
+                .then_some(path_price * self.crash_multiplier(step + 1));
+            points.push(point);
+        }
+
+        Ok(points)
+    }
+
+    fn next_price(&self, price: f64, rng: &mut SplitMix64) -> f64 {
+        let next = match self.path {
+            PricePath::Gbm { drift, volatility } => {
+                price * gbm_factor(drift, volatility, rng.next_normal())
+            }
+            PricePath::JumpDiffusion {
+                drift,
+                volatility,
+                jump_probability,
+                jump_size,
+            } => {
+                let mut next = price * gbm_factor(drift, volatility, rng.next_normal());
+                if rng.next_f64() < jump_probability {
+                    let sign = if rng.next_f64() < 0.5 { -1.0 } else { 1.0 };
+                    next *= 1.0 + sign * jump_size;
+                }
+                next
+            }
+            PricePath::Trend { change_per_step } => price * (1.0 + change_per_step),
+        };
+
+        // Keep the path strictly positive so the bookkeeping conversion stays defined.
+        if next.is_finite() && next > 0.0 {
+            next
+        } else {
+            price
+        }
+    }
+
+    fn crash_multiplier(&self, step: u64) -> f64 {
+        self.events
+            .iter()
+            .filter_map(|event| match *event {
+                MarketEvent::FlashCrash {
+                    at_step,
+                    drop_fraction,
+                    recovery_steps,
+                } if step >= at_step && step <= at_step + recovery_steps => {
+                    let recovered = (step - at_step) as f64 / recovery_steps.max(1) as f64;
+                    Some(1.0 - drop_fraction.clamp(0.0, 0.99) * (1.0 - recovered))
+                }
+                _ => None,
+            })
+            .product()
+    }
+
+    fn is_halted(&self, step: u64) -> bool {
+        self.events.iter().any(|event| match *event {
+            MarketEvent::Halt { from_step, steps } => {
+                (from_step..from_step + steps).contains(&step)
+            }
+            MarketEvent::FlashCrash { .. } => false,
+        })
+    }
+}
+
+fn gbm_factor(drift: f64, volatility: f64, shock: f64) -> f64 {
+    (drift - volatility * volatility / 2.0 + volatility * shock).exp()
+}
+
+/// Small, dependency-free PRNG; stable across platforms and crate upgrades.
+#[derive(Debug, Clone)]
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal via Box-Muller.
+    fn next_normal(&mut self) -> f64 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::{BacktestConfig, InventoryFlowStrategy, implied_price, run_backtest};
+
+    fn backtest_config() -> BacktestConfig {
+        BacktestConfig {
+            initial_base: 1_000_000_000,
+            initial_quote: 100_000_000,
+            base_token_decimals: 9,
+            quote_token_decimals: 6,
+            decision_interval_slots: 0,
+            fee_per_update_lamports: 5_000,
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_series() {
+        let path = PricePath::JumpDiffusion {
+            drift: 0.0,
+            volatility: 0.01,
+            jump_probability: 0.1,
+            jump_size: 0.05,
+        };
+        let a = SyntheticMarket::new(7, 50, 100.0, path).generate().unwrap();
+        let b = SyntheticMarket::new(7, 50, 100.0, path).generate().unwrap();
+        let c = SyntheticMarket::new(8, 50, 100.0, path).generate().unwrap();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.len(), 51);
+    }
+
+    #[test]
+    fn trend_is_recovered_by_implied_price() {
+        let market = SyntheticMarket::new(
+            1,
+            3,
+            100.0,
+            PricePath::Trend {
+                change_per_step: 0.1,
+            },
+        );
+        let points = market.generate().unwrap();
+
+        let first = implied_price(&points[0], &points[1], 9, 6).unwrap();
+        let last = implied_price(&points[2], &points[3], 9, 6).unwrap();
+        assert!((first - 100.0).abs() < 1e-6);
+        assert!((last - 121.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn flash_crash_drops_traded_price() {
+        let market = SyntheticMarket::flash_crash(3, 20, 100.0);
+        let points = market.generate().unwrap();
+
+        let before = implied_price(&points[9], &points[10], 9, 6).unwrap();
+        let during = implied_price(&points[10], &points[11], 9, 6).unwrap();
+        assert!(during < before * 0.75);
+    }
+
+    #[test]
+    fn zero_flow_market_never_trades() {
+        let points = SyntheticMarket::zero_flow(1, 10, 100.0).generate().unwrap();
+        let last = points.last().unwrap();
+
+        assert_eq!(last.quote_per_base, 0);
+        assert_eq!(last.slots_without_trades, 1_000);
+
+        let report = run_backtest(
+            &points,
+            &mut InventoryFlowStrategy { flow_divisor: 5 },
+            &backtest_config(),
+        )
+        .unwrap();
+        assert_eq!(report.final_base_balance, 1_000_000_000);
+        assert_eq!(report.final_quote_balance, 100_000_000);
+        assert!(report.debt_events.is_empty());
+    }
+
+    #[test]
+    fn one_sided_flow_pauses_accrual() {
+        let points = SyntheticMarket::one_sided_flow(5, 30, 100.0)
+            .generate()
+            .unwrap();
+
+        assert_eq!(points[10].quote_per_base, points[20].quote_per_base);
+        assert_eq!(points[20].slots_without_trades, 1_000);
+        assert!(implied_price(&points[10], &points[11], 9, 6).is_none());
+    }
+}
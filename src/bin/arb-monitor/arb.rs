@@ -0,0 +1,74 @@
+//! Whether a twob market's flow price can be arbitraged against Jupiter.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArbDirection {
+    /// The market sells base below Jupiter's bid: buy on twob, sell on Jupiter.
+    BuyTwobSellJupiter,
+    /// The market buys base above Jupiter's ask: buy on Jupiter, sell on twob.
+    BuyJupiterSellTwob,
+}
+
+impl ArbDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ArbDirection::BuyTwobSellJupiter => "buy_twob_sell_jupiter",
+            ArbDirection::BuyJupiterSellTwob => "buy_jupiter_sell_twob",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ArbOpportunity {
+    pub direction: ArbDirection,
+    /// Gross edge before fees, in basis points of the cheaper leg.
+    pub edge_bps: f64,
+}
+
+/// Compare the market's flow price with Jupiter's bid (quote received selling base) and ask
+/// (quote paid buying base), all in UI quote per base. A spread that straddles the flow
+/// price is not arbitrageable.
+pub fn find_arbitrage(
+    flow_price: f64,
+    jupiter_bid: f64,
+    jupiter_ask: f64,
+) -> Option<ArbOpportunity> {
+    if flow_price <= 0.0 || jupiter_bid <= 0.0 || jupiter_ask <= 0.0 {
+        return None;
+    }
+
+    if jupiter_bid > flow_price {
+        return Some(ArbOpportunity {
+            direction: ArbDirection::BuyTwobSellJupiter,
+            edge_bps: (jupiter_bid - flow_price) / flow_price * 10_000.0,
+        });
+    }
+    if flow_price > jupiter_ask {
+        return Some(ArbOpportunity {
+            direction: ArbDirection::BuyJupiterSellTwob,
+            edge_bps: (flow_price - jupiter_ask) / jupiter_ask * 10_000.0,
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_both_directions_and_ignores_straddling_spreads() {
+        let cheap = find_arbitrage(99.0, 100.0, 100.2).unwrap();
+        assert_eq!(cheap.direction, ArbDirection::BuyTwobSellJupiter);
+        assert!((cheap.edge_bps - 101.0101).abs() < 1e-3);
+
+        let rich = find_arbitrage(101.0, 99.8, 100.0).unwrap();
+        assert_eq!(rich.direction, ArbDirection::BuyJupiterSellTwob);
+        assert!((rich.edge_bps - 100.0).abs() < 1e-9);
+
+        assert_eq!(find_arbitrage(100.1, 100.0, 100.2), None);
+        assert_eq!(find_arbitrage(100.0, 0.0, 100.2), None);
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use anchor_client::{Cluster, solana_sdk::commitment_config::CommitmentConfig};
+use twob_market_making::{
+    alerts::AlertConfig,
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    /// Jupiter swap API quote endpoint.
+    pub jupiter_quote_url: String,
+    pub jupiter_api_key: Option<String>,
+    /// Size of the probe quoted on Jupiter each way, in UI base units.
+    pub probe_base_amount: f64,
+    /// Flag the market once the round trip through Jupiter beats its flow price by this much.
+    pub threshold_bps: u64,
+    pub poll_interval: Duration,
+    pub alerts: AlertConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_id = env_var("MARKET_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u64>()?;
+
+        let jupiter_quote_url = env_var("ARB_JUPITER_QUOTE_URL")
+            .unwrap_or_else(|_| "https://lite-api.jup.ag/swap/v1/quote".to_string());
+
+        let jupiter_api_key = env_var("JUPITER_API_KEY")
+            .ok()
+            .filter(|value| !value.trim().is_empty());
+
+        let probe_base_amount = env_var("ARB_PROBE_BASE_AMOUNT")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<f64>()?;
+        if !probe_base_amount.is_finite() || probe_base_amount <= 0.0 {
+            anyhow::bail!(
+                "ARB_PROBE_BASE_AMOUNT must be positive, got {}",
+                probe_base_amount
+            );
+        }
+
+        let threshold_bps = env_var("ARB_THRESHOLD_BPS")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse::<u64>()?;
+
+        let poll_interval = Duration::from_secs(
+            env_var("ARB_POLL_INTERVAL_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse::<u64>()?,
+        );
+
+        let alerts = AlertConfig::from_env()?;
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            market_id,
+            jupiter_quote_url,
+            jupiter_api_key,
+            probe_base_amount,
+            threshold_bps,
+            poll_interval,
+            alerts,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
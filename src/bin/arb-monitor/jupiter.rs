@@ -0,0 +1,84 @@
+//! Read-only Jupiter swap quotes: what the aggregator would pay for an exact input today.
+
+use std::time::Instant;
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+#[derive(Debug, Serialize)]
+struct QuoteQuery {
+    #[serde(rename = "inputMint")]
+    input_mint: String,
+    #[serde(rename = "outputMint")]
+    output_mint: String,
+    amount: String,
+    #[serde(rename = "slippageBps")]
+    slippage_bps: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuoteResponse {
+    in_amount: String,
+    out_amount: String,
+}
+
+/// Raw input and output amounts of an exact-in quote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapQuote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+}
+
+pub async fn fetch_quote(
+    http_client: &reqwest::Client,
+    url: &str,
+    api_key: Option<&str>,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+) -> anyhow::Result<SwapQuote> {
+    let mut request = http_client.get(url).query(&QuoteQuery {
+        input_mint: input_mint.to_string(),
+        output_mint: output_mint.to_string(),
+        amount: amount.to_string(),
+        slippage_bps: 0,
+    });
+    if let Some(api_key) = api_key {
+        request = request.header("x-api-key", api_key);
+    }
+
+    let started_at = Instant::now();
+    let response = request
+        .send()
+        .await
+        .context("Failed to request Jupiter quote")?;
+    let status = response.status();
+    info!(
+        event.name = "jupiter_quote_http_response",
+        http.status_code = status.as_u16(),
+        histogram.jupiter_quote_latency_ms = started_at.elapsed().as_millis() as f64,
+    );
+    let body = response
+        .text()
+        .await
+        .context("Failed to read Jupiter quote response body")?;
+    if !status.is_success() {
+        anyhow::bail!("Jupiter quote failed with status {}: {}", status, body);
+    }
+
+    let quote: QuoteResponse =
+        serde_json::from_str(&body).context("Failed to parse Jupiter quote response JSON")?;
+    Ok(SwapQuote {
+        in_amount: quote
+            .in_amount
+            .parse()
+            .context("Jupiter quote inAmount is not an integer")?,
+        out_amount: quote
+            .out_amount
+            .parse()
+            .context("Jupiter quote outAmount is not an integer")?,
+    })
+}
@@ -0,0 +1,173 @@
+mod arb;
+mod config;
+mod jupiter;
+
+use anchor_client::{Client, Program, solana_sdk::pubkey::Pubkey};
+use config::Config;
+use tokio::{signal, time::sleep};
+use tracing::{info, warn};
+use twob_market_making::{
+    ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    fetch_market_state, program_payer,
+    quote::flow_price,
+    twob_anchor,
+};
+
+/// Mints and decimals of the monitored market, fixed for its lifetime.
+struct Pair {
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+    base_decimals: u8,
+    quote_decimals: u8,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    // Monitoring only reads accounts, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let http = reqwest::Client::new();
+    let alerter = Alerter::from_config("arb-monitor", &config.alerts)?;
+
+    let market = fetch_market_state(&program, config.market_id).await?.market;
+    let rpc = program.rpc();
+    let pair = Pair {
+        base_mint: market.base_mint,
+        quote_mint: market.quote_mint,
+        base_decimals: rpc.get_token_supply(&market.base_mint).await?.decimals,
+        quote_decimals: rpc.get_token_supply(&market.quote_mint).await?.decimals,
+    };
+
+    info!(
+        event.name = "arb_monitor_started",
+        market.id = config.market_id,
+        arb.threshold_bps = config.threshold_bps,
+        arb.probe_base_amount = config.probe_base_amount,
+        arb.poll_interval_secs = config.poll_interval.as_secs(),
+    );
+
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "arb_monitor_shutdown");
+                break;
+            }
+            _ = sleep(config.poll_interval) => {
+                if let Err(error) = check_market(&config, &program, &http, &pair, &alerter).await {
+                    warn!(
+                        event.name = "arb_check_failed",
+                        market.id = config.market_id,
+                        ?error,
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_market(
+    config: &Config,
+    program: &Program<ProgramPayer>,
+    http: &reqwest::Client,
+    pair: &Pair,
+    alerter: &Alerter,
+) -> anyhow::Result<()> {
+    let market = fetch_market_state(program, config.market_id).await?.market;
+    let Some(twob_price) = flow_price(
+        market.base_flow,
+        market.quote_flow,
+        pair.base_decimals,
+        pair.quote_decimals,
+    ) else {
+        info!(
+            event.name = "arb_check_skipped",
+            market.id = config.market_id,
+            reason = "market_not_flowing",
+        );
+        return Ok(());
+    };
+
+    let base_scale = 10f64.powi(i32::from(pair.base_decimals));
+    let quote_scale = 10f64.powi(i32::from(pair.quote_decimals));
+    let api_key = config.jupiter_api_key.as_deref();
+
+    let probe_base = (config.probe_base_amount * base_scale) as u64;
+    let sell = jupiter::fetch_quote(
+        http,
+        &config.jupiter_quote_url,
+        api_key,
+        &pair.base_mint,
+        &pair.quote_mint,
+        probe_base,
+    )
+    .await?;
+    // Buy back the same size, priced at the market's flow price.
+    let probe_quote = (config.probe_base_amount * twob_price * quote_scale) as u64;
+    let buy = jupiter::fetch_quote(
+        http,
+        &config.jupiter_quote_url,
+        api_key,
+        &pair.quote_mint,
+        &pair.base_mint,
+        probe_quote,
+    )
+    .await?;
+    anyhow::ensure!(
+        sell.in_amount > 0 && buy.out_amount > 0,
+        "Jupiter returned an empty quote"
+    );
+
+    let jupiter_bid = (sell.out_amount as f64 / quote_scale) / (sell.in_amount as f64 / base_scale);
+    let jupiter_ask = (buy.in_amount as f64 / quote_scale) / (buy.out_amount as f64 / base_scale);
+    let opportunity = arb::find_arbitrage(twob_price, jupiter_bid, jupiter_ask);
+    let edge_bps = opportunity.map_or(0.0, |opportunity| opportunity.edge_bps);
+
+    info!(
+        event.name = "arb_check_completed",
+        market.id = config.market_id,
+        gauge.arb_twob_price = twob_price,
+        gauge.arb_jupiter_bid = jupiter_bid,
+        gauge.arb_jupiter_ask = jupiter_ask,
+        gauge.arb_edge_bps = edge_bps,
+    );
+
+    let Some(opportunity) =
+        opportunity.filter(|opportunity| opportunity.edge_bps >= config.threshold_bps as f64)
+    else {
+        return Ok(());
+    };
+    warn!(
+        event.name = "arb_opportunity_detected",
+        market.id = config.market_id,
+        arb.direction = opportunity.direction.as_str(),
+        arb.edge_bps = opportunity.edge_bps,
+        arb.twob_price = twob_price,
+        arb.jupiter_bid = jupiter_bid,
+        arb.jupiter_ask = jupiter_ask,
+        monotonic_counter.arb_opportunities_total = 1_u64,
+    );
+    alerter.notify(
+        AlertKind::ArbitrageDetected,
+        Some(config.market_id),
+        format!(
+            "flow price {:.6} vs Jupiter bid {:.6} / ask {:.6}: {} for {:.1} bps",
+            twob_price,
+            jupiter_bid,
+            jupiter_ask,
+            opportunity.direction.as_str(),
+            opportunity.edge_bps
+        ),
+    );
+    Ok(())
+}
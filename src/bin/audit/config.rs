@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    /// Authority of the liquidity position to audit.
+    pub authority: Pubkey,
+    /// Stop walking the position's history after this many signatures.
+    pub signature_limit: usize,
+    /// Write the replayed balance trajectory here as CSV.
+    pub output_path: Option<PathBuf>,
+    /// Record the position's accounts and balances here as a regression fixture.
+    pub fixture_path: Option<PathBuf>,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_id = env_var("MARKET_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u64>()?;
+
+        let authority = env_var("AUDIT_AUTHORITY")
+            .map_err(|_| anyhow::anyhow!("AUDIT_AUTHORITY env var not set"))?
+            .parse::<Pubkey>()
+            .map_err(|e| anyhow::anyhow!("Invalid AUDIT_AUTHORITY: {}", e))?;
+
+        let signature_limit = env_var("AUDIT_SIGNATURE_LIMIT")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse::<usize>()?;
+
+        let output_path = env_var("AUDIT_OUTPUT")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(PathBuf::from);
+
+        let fixture_path = env_var("AUDIT_FIXTURE")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(PathBuf::from);
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            market_id,
+            authority,
+            signature_limit,
+            output_path,
+            fixture_path,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
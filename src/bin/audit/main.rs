@@ -0,0 +1,466 @@
+mod config;
+
+use std::{io::Write, path::Path};
+
+use anchor_client::{
+    Client,
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+};
+use anyhow::Context;
+use config::Config;
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use tracing::{info, warn};
+use twob_market_making::{
+    AccountResolver, BOOKKEEPING_PRECISION_FACTOR, LiquidityPositionBalances,
+    backtest::{BalancePoint, FlowUpdate, PositionChange, fetch_price_points, replay_position},
+    decode::TwobInstruction,
+    fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances,
+    ingest::{fetch_signatures, fetch_transaction},
+    program_payer,
+    state::fixture::PositionFixture,
+    twob_anchor,
+};
+
+/// One instruction that changed the audited position.
+#[derive(Debug, Clone, Serialize)]
+struct HistoryEntry {
+    slot: u64,
+    signature: String,
+    change: PositionChange,
+}
+
+/// Replayed balances next to the ones the program (or the lib) reports for the same slot.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Comparison {
+    label: &'static str,
+    slot: u64,
+    /// Slot of the replay point compared against, the last end-slot boundary at or before
+    /// `slot`.
+    replay_slot: Option<u64>,
+    expected: LiquidityPositionBalances,
+    replayed: LiquidityPositionBalances,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let json = match std::env::args().skip(1).collect::<Vec<_>>().as_slice() {
+        [] => false,
+        [flag] if flag == "--json" => true,
+        _ => anyhow::bail!("usage: audit [--json]"),
+    };
+    let config = Config::from_env()?;
+    // Auditing only reads accounts, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let rpc = program.rpc();
+
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let market = resolver.market_pda(config.market_id).address();
+    let position_address = resolver
+        .liquidity_position_pda(&market, &config.authority)
+        .address();
+
+    let history = position_history(&rpc, &position_address, config.signature_limit).await?;
+    let Some(first) = history.first() else {
+        anyhow::bail!(
+            "no successful twob instructions found for position {}",
+            position_address
+        );
+    };
+    info!(
+        event.name = "audit_history_loaded",
+        market.id = config.market_id,
+        position.address = %position_address,
+        audit.changes = history.len(),
+        slot.first = first.slot,
+    );
+
+    let market_state = fetch_market_state(&program, config.market_id).await?;
+    let points = fetch_price_points(
+        &program,
+        config.market_id,
+        first.slot,
+        market_state.current_slot,
+    )
+    .await?;
+    let changes = history
+        .iter()
+        .map(|entry| (entry.slot, entry.change))
+        .collect::<Vec<_>>();
+    let trajectory = replay_position(&points, &changes);
+
+    let mut comparisons = Vec::new();
+    match fetch_liquidity_position(&program, config.market_id, &config.authority).await {
+        Ok(position) => {
+            let stored = LiquidityPositionBalances {
+                base_balance: (position.base_balance / BOOKKEEPING_PRECISION_FACTOR) as u64,
+                quote_balance: (position.quote_balance / BOOKKEEPING_PRECISION_FACTOR) as u64,
+                base_debt: position.base_debt,
+                quote_debt: position.quote_debt,
+            };
+            comparisons.push(compare(
+                "stored account",
+                position.last_update_slot,
+                stored,
+                &trajectory,
+            ));
+
+            let computed = get_liquidity_position_balances(
+                &program,
+                position,
+                market_state.bookkeeping,
+                market_state.market,
+                market_state.current_slot,
+            )
+            .await?;
+            comparisons.push(compare(
+                "get_liquidity_position_balances",
+                market_state.current_slot,
+                computed,
+                &trajectory,
+            ));
+        }
+        Err(error) => warn!(
+            event.name = "audit_position_missing",
+            position.address = %position_address,
+            ?error,
+            "position account is gone; only the replayed trajectory is reported"
+        ),
+    }
+
+    if let Some(path) = &config.output_path {
+        write_trajectory(path, &trajectory)?;
+        info!(event.name = "audit_trajectory_written", audit.output = %path.display());
+    }
+    if let Some(path) = &config.fixture_path {
+        PositionFixture::capture(&program, config.market_id, &config.authority)
+            .await?
+            .save(path)?;
+        info!(event.name = "audit_fixture_written", audit.fixture = %path.display());
+    }
+
+    if json {
+        let report = serde_json::json!({
+            "market_id": config.market_id,
+            "authority": config.authority.to_string(),
+            "position": position_address.to_string(),
+            "changes": history,
+            "replay_points": trajectory.len(),
+            "comparisons": comparisons,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(
+            &config,
+            &position_address,
+            &history,
+            &trajectory,
+            &comparisons,
+        );
+    }
+    Ok(())
+}
+
+/// Every successful instruction that touched `position`, oldest first.
+async fn position_history(
+    rpc: &RpcClient,
+    position: &Pubkey,
+    limit: usize,
+) -> anyhow::Result<Vec<HistoryEntry>> {
+    let (signatures, truncated) = fetch_signatures(rpc, position, None, limit).await?;
+    if truncated {
+        warn!(
+            event.name = "audit_history_truncated",
+            audit.signature_limit = limit,
+            "the oldest changes are missing; raise AUDIT_SIGNATURE_LIMIT"
+        );
+    }
+
+    let mut history = Vec::new();
+    for entry in signatures.into_iter().rev() {
+        if entry.err.is_some() {
+            continue;
+        }
+        let signature = entry.signature.parse::<Signature>()?;
+        let transaction = fetch_transaction(rpc, &signature)
+            .await
+            .with_context(|| format!("Failed to fetch transaction {}", signature))?;
+        history.extend(
+            transaction
+                .instructions
+                .iter()
+                .filter(|indexed| indexed.accounts.contains(position))
+                .filter_map(|indexed| position_changes(&indexed.instruction))
+                .flatten()
+                .map(|change| HistoryEntry {
+                    slot: transaction.slot,
+                    signature: transaction.signature.clone(),
+                    change,
+                }),
+        );
+    }
+    Ok(history)
+}
+
+/// How an instruction changes the position it is addressed to, in the order the program
+/// applies it.
+fn position_changes(instruction: &TwobInstruction) -> Option<Vec<PositionChange>> {
+    let changes = match *instruction {
+        TwobInstruction::ProvideLiquidity {
+            base_deposit_lamports,
+            quote_deposit_lamports,
+            base_flow_u64,
+            quote_flow_u64,
+            ..
+        } => vec![
+            PositionChange::Deposit {
+                base: base_deposit_lamports,
+                quote: quote_deposit_lamports,
+            },
+            PositionChange::Flows(FlowUpdate {
+                base_flow: base_flow_u64,
+                quote_flow: quote_flow_u64,
+            }),
+        ],
+        TwobInstruction::AddLiquidity {
+            base_lamports,
+            quote_lamports,
+            ..
+        } => vec![PositionChange::Deposit {
+            base: base_lamports,
+            quote: quote_lamports,
+        }],
+        TwobInstruction::WithdrawLiquidity {
+            base_lamports,
+            quote_lamports,
+            ..
+        } => vec![PositionChange::Withdraw {
+            base: base_lamports,
+            quote: quote_lamports,
+        }],
+        TwobInstruction::UpdateLiquidityFlows {
+            base_flow_u64,
+            quote_flow_u64,
+            ..
+        } => vec![PositionChange::Flows(FlowUpdate {
+            base_flow: base_flow_u64,
+            quote_flow: quote_flow_u64,
+        })],
+        TwobInstruction::PublicStopLiquidityPosition { .. } => {
+            vec![PositionChange::Flows(FlowUpdate {
+                base_flow: 0,
+                quote_flow: 0,
+            })]
+        }
+        TwobInstruction::AuthorityCloseLiquidityPosition { .. } => vec![PositionChange::Close],
+        _ => return None,
+    };
+    Some(changes)
+}
+
+/// Compare `expected` with the replay at the last point at or before `slot`. Debt is summed
+/// over the replay since the program accumulates it on the account.
+fn compare(
+    label: &'static str,
+    slot: u64,
+    expected: LiquidityPositionBalances,
+    trajectory: &[BalancePoint],
+) -> Comparison {
+    let upto = trajectory.partition_point(|point| point.slot <= slot);
+    let replay = upto.checked_sub(1).map(|last| &trajectory[last]);
+    let (base_debt, quote_debt) =
+        trajectory[..upto]
+            .iter()
+            .fold((0u64, 0u64), |(base, quote), point| {
+                (
+                    base.saturating_add(point.base_debt),
+                    quote.saturating_add(point.quote_debt),
+                )
+            });
+
+    Comparison {
+        label,
+        slot,
+        replay_slot: replay.map(|point| point.slot),
+        expected,
+        replayed: LiquidityPositionBalances {
+            base_balance: replay.map_or(0, |point| point.base_balance),
+            quote_balance: replay.map_or(0, |point| point.quote_balance),
+            base_debt,
+            quote_debt,
+        },
+    }
+}
+
+fn write_trajectory(path: &Path, trajectory: &[BalancePoint]) -> anyhow::Result<()> {
+    let mut file = std::io::BufWriter::new(
+        std::fs::File::create(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?,
+    );
+    writeln!(
+        file,
+        "slot,base_flow,quote_flow,base_balance,quote_balance,base_debt,quote_debt"
+    )?;
+    for point in trajectory {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{}",
+            point.slot,
+            point.base_flow,
+            point.quote_flow,
+            point.base_balance,
+            point.quote_balance,
+            point.base_debt,
+            point.quote_debt
+        )?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+fn print_report(
+    config: &Config,
+    position: &Pubkey,
+    history: &[HistoryEntry],
+    trajectory: &[BalancePoint],
+    comparisons: &[Comparison],
+) {
+    let diff = |expected: u64, replayed: u64| i128::from(expected) - i128::from(replayed);
+
+    println!(
+        "Audit of position {} (authority {}) on market {}",
+        position, config.authority, config.market_id
+    );
+    println!("  changes:        {}", history.len());
+    for entry in history {
+        println!(
+            "    slot {}: {:?} ({})",
+            entry.slot, entry.change, entry.signature
+        );
+    }
+    println!("  replay points:  {}", trajectory.len());
+
+    for comparison in comparisons {
+        println!(
+            "  {} at slot {} vs replay at slot {}:",
+            comparison.label,
+            comparison.slot,
+            comparison
+                .replay_slot
+                .map_or_else(|| "n/a".to_string(), |slot| slot.to_string())
+        );
+        let rows = [
+            (
+                "base balance",
+                comparison.expected.base_balance,
+                comparison.replayed.base_balance,
+            ),
+            (
+                "quote balance",
+                comparison.expected.quote_balance,
+                comparison.replayed.quote_balance,
+            ),
+            (
+                "base debt",
+                comparison.expected.base_debt,
+                comparison.replayed.base_debt,
+            ),
+            (
+                "quote debt",
+                comparison.expected.quote_debt,
+                comparison.replayed.quote_debt,
+            ),
+        ];
+        for (name, expected, replayed) in rows {
+            println!(
+                "    {:<14} {:>22} vs {:>22} (diff {})",
+                name,
+                expected,
+                replayed,
+                diff(expected, replayed)
+            );
+        }
+    }
+
+    info!(
+        event.name = "audit_completed",
+        market.id = config.market_id,
+        position.address = %position,
+        audit.changes = history.len(),
+        audit.replay_points = trajectory.len(),
+        audit.comparisons = comparisons.len(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provide_liquidity_deposits_before_setting_flows() {
+        let changes = position_changes(&TwobInstruction::ProvideLiquidity {
+            reference_index: 7,
+            base_deposit_lamports: 1_000,
+            quote_deposit_lamports: 2_000,
+            base_flow_u64: 3,
+            quote_flow_u64: 4,
+        })
+        .unwrap();
+
+        assert_eq!(
+            changes,
+            vec![
+                PositionChange::Deposit {
+                    base: 1_000,
+                    quote: 2_000
+                },
+                PositionChange::Flows(FlowUpdate {
+                    base_flow: 3,
+                    quote_flow: 4
+                }),
+            ]
+        );
+        assert_eq!(
+            position_changes(&TwobInstruction::PublicStopLiquidityPosition { reference_index: 7 }),
+            Some(vec![PositionChange::Flows(FlowUpdate {
+                base_flow: 0,
+                quote_flow: 0
+            })])
+        );
+    }
+
+    #[test]
+    fn comparison_uses_last_replay_point_and_sums_debt() {
+        let point = |slot, base_balance, base_debt| BalancePoint {
+            slot,
+            base_flow: 1,
+            quote_flow: 0,
+            base_balance,
+            quote_balance: 0,
+            base_debt,
+            quote_debt: 0,
+        };
+        let trajectory = [point(10, 50, 0), point(20, 0, 5), point(30, 0, 10)];
+        let expected = LiquidityPositionBalances {
+            base_balance: 0,
+            quote_balance: 0,
+            base_debt: 15,
+            quote_debt: 0,
+        };
+
+        let comparison = compare("stored account", 25, expected, &trajectory);
+
+        assert_eq!(comparison.replay_slot, Some(20));
+        assert_eq!(comparison.replayed.base_balance, 0);
+        assert_eq!(comparison.replayed.base_debt, 5);
+        assert_eq!(compare("x", 5, expected, &trajectory).replay_slot, None);
+    }
+}
@@ -0,0 +1,203 @@
+use anchor_client::{Cluster, solana_sdk::commitment_config::CommitmentConfig};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrategyKind {
+    InventoryFlow,
+    OracleFlow,
+}
+
+impl std::str::FromStr for StrategyKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "inventory-flow" | "inventory" => Ok(Self::InventoryFlow),
+            "oracle-flow" | "oracle" => Ok(Self::OracleFlow),
+            other => anyhow::bail!(
+                "Invalid BACKTEST_STRATEGY `{}`; expected `inventory-flow` or `oracle-flow`",
+                other
+            ),
+        }
+    }
+}
+
+/// Synthetic market to generate instead of replaying recorded prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scenario {
+    Gbm,
+    JumpDiffusion,
+    Trend,
+    FlashCrash,
+    OneSidedFlow,
+    ZeroFlow,
+}
+
+impl std::str::FromStr for Scenario {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "gbm" => Ok(Self::Gbm),
+            "jump" | "jump-diffusion" => Ok(Self::JumpDiffusion),
+            "trend" => Ok(Self::Trend),
+            "flash-crash" => Ok(Self::FlashCrash),
+            "one-sided" | "one-sided-flow" => Ok(Self::OneSidedFlow),
+            "zero-flow" => Ok(Self::ZeroFlow),
+            other => anyhow::bail!(
+                "Invalid BACKTEST_SCENARIO `{}`; expected one of gbm, jump, trend, flash-crash, one-sided, zero-flow",
+                other
+            ),
+        }
+    }
+}
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    pub strategy: StrategyKind,
+    /// Replay points from this JSON file instead of fetching `Prices` accounts.
+    pub input_path: Option<String>,
+    /// Write the fetched points here so later runs can replay them offline.
+    pub save_path: Option<String>,
+    pub scenario: Option<Scenario>,
+    pub seed: u64,
+    pub steps: u64,
+    pub start_price: f64,
+    pub start_slot: Option<u64>,
+    pub end_slot: Option<u64>,
+    pub initial_base: u64,
+    pub initial_quote: u64,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+    pub decision_interval_slots: u64,
+    pub fee_per_update_lamports: u64,
+    pub flow_divisor: u64,
+    pub optimal_quote_weight: f64,
+    pub quote_threshold_bps: u64,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_id = env_var("MARKET_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u64>()?;
+
+        let strategy = env_var("BACKTEST_STRATEGY")
+            .unwrap_or_else(|_| "inventory-flow".to_string())
+            .parse::<StrategyKind>()?;
+
+        let input_path = env_var("BACKTEST_INPUT").ok().filter(|v| !v.is_empty());
+        let save_path = env_var("BACKTEST_SAVE_PATH").ok().filter(|v| !v.is_empty());
+
+        let scenario = env_var("BACKTEST_SCENARIO")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse::<Scenario>())
+            .transpose()?;
+
+        let seed = env_var("BACKTEST_SEED")
+            .unwrap_or_else(|_| "42".to_string())
+            .parse::<u64>()?;
+
+        let steps = env_var("BACKTEST_STEPS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse::<u64>()?;
+
+        let start_price = env_var("BACKTEST_START_PRICE")
+            .unwrap_or_else(|_| "100.0".to_string())
+            .parse::<f64>()?;
+
+        let start_slot = env_var("BACKTEST_START_SLOT")
+            .ok()
+            .map(|v| v.parse::<u64>())
+            .transpose()?;
+        let end_slot = env_var("BACKTEST_END_SLOT")
+            .ok()
+            .map(|v| v.parse::<u64>())
+            .transpose()?;
+
+        if scenario.is_none()
+            && input_path.is_none()
+            && (start_slot.is_none() || end_slot.is_none())
+        {
+            anyhow::bail!(
+                "Set BACKTEST_SCENARIO, BACKTEST_INPUT, or both BACKTEST_START_SLOT and BACKTEST_END_SLOT"
+            );
+        }
+
+        let initial_base = env_var("BACKTEST_INITIAL_BASE")
+            .unwrap_or_else(|_| "1000000000".to_string())
+            .parse::<u64>()?;
+
+        let initial_quote = env_var("BACKTEST_INITIAL_QUOTE")
+            .unwrap_or_else(|_| "100000000".to_string())
+            .parse::<u64>()?;
+
+        let base_token_decimals = env_var("BASE_TOKEN_DECIMALS")
+            .unwrap_or_else(|_| "9".to_string())
+            .parse::<u8>()?;
+
+        let quote_token_decimals = env_var("QUOTE_TOKEN_DECIMALS")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse::<u8>()?;
+
+        let decision_interval_slots = env_var("BACKTEST_DECISION_INTERVAL_SLOTS")
+            .unwrap_or_else(|_| "750".to_string())
+            .parse::<u64>()?;
+
+        let fee_per_update_lamports = env_var("BACKTEST_FEE_PER_UPDATE_LAMPORTS")
+            .unwrap_or_else(|_| "5000".to_string())
+            .parse::<u64>()?;
+
+        let flow_divisor = env_var("FLOW_DIVISOR")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u64>()?;
+
+        let optimal_quote_weight = env_var("OPTIMAL_QUOTE_WEIGHT")
+            .unwrap_or_else(|_| "0.1".to_string())
+            .parse::<f64>()?;
+
+        let quote_threshold_bps = env_var("QUOTE_THRESHOLD_BPS")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse::<u64>()?;
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            market_id,
+            strategy,
+            input_path,
+            save_path,
+            scenario,
+            seed,
+            steps,
+            start_price,
+            start_slot,
+            end_slot,
+            initial_base,
+            initial_quote,
+            base_token_decimals,
+            quote_token_decimals,
+            decision_interval_slots,
+            fee_per_update_lamports,
+            flow_divisor,
+            optimal_quote_weight,
+            quote_threshold_bps,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
@@ -0,0 +1,169 @@
+mod config;
+
+use anchor_client::{Client, solana_sdk::pubkey::Pubkey};
+use config::{Config, Scenario, StrategyKind};
+use tracing::info;
+use twob_market_making::{
+    backtest::{
+        BacktestConfig, BacktestReport, BacktestStrategy, InventoryFlowStrategy,
+        OracleFlowStrategy, PricePath, PricePoint, SyntheticMarket, fetch_price_points,
+        load_price_points, run_backtest, save_price_points,
+    },
+    program_payer, twob_anchor,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let json = match std::env::args().skip(1).collect::<Vec<_>>().as_slice() {
+        [] => false,
+        [flag] if flag == "--json" => true,
+        _ => anyhow::bail!("usage: backtest [--json]"),
+    };
+    let config = Config::from_env()?;
+    let points = load_points(&config).await?;
+    info!(
+        event.name = "backtest_points_loaded",
+        market.id = config.market_id,
+        backtest.points = points.len(),
+    );
+
+    let mut strategy: Box<dyn BacktestStrategy> = match config.strategy {
+        StrategyKind::InventoryFlow => Box::new(InventoryFlowStrategy {
+            flow_divisor: config.flow_divisor,
+        }),
+        StrategyKind::OracleFlow => Box::new(OracleFlowStrategy {
+            optimal_quote_weight: config.optimal_quote_weight,
+            quote_update_threshold_bps: config.quote_threshold_bps,
+        }),
+    };
+
+    let backtest_config = BacktestConfig {
+        initial_base: config.initial_base,
+        initial_quote: config.initial_quote,
+        base_token_decimals: config.base_token_decimals,
+        quote_token_decimals: config.quote_token_decimals,
+        decision_interval_slots: config.decision_interval_slots,
+        fee_per_update_lamports: config.fee_per_update_lamports,
+    };
+
+    let report = run_backtest(&points, strategy.as_mut(), &backtest_config)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&config, &report);
+    }
+
+    Ok(())
+}
+
+async fn load_points(config: &Config) -> anyhow::Result<Vec<PricePoint>> {
+    if let Some(scenario) = config.scenario {
+        return synthetic_market(config, scenario).generate();
+    }
+
+    if let Some(path) = &config.input_path {
+        return load_price_points(path);
+    }
+
+    let (Some(start_slot), Some(end_slot)) = (config.start_slot, config.end_slot) else {
+        anyhow::bail!(
+            "BACKTEST_START_SLOT and BACKTEST_END_SLOT are required without BACKTEST_INPUT"
+        );
+    };
+
+    // Fetching only reads accounts, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let points = fetch_price_points(&program, config.market_id, start_slot, end_slot).await?;
+
+    if let Some(path) = &config.save_path {
+        save_price_points(path, &points)?;
+        info!(event.name = "backtest_points_saved", backtest.save_path = %path);
+    }
+
+    Ok(points)
+}
+
+fn synthetic_market(config: &Config, scenario: Scenario) -> SyntheticMarket {
+    let (seed, steps, price) = (config.seed, config.steps, config.start_price);
+    let mut market = match scenario {
+        Scenario::Gbm => SyntheticMarket::new(
+            seed,
+            steps,
+            price,
+            PricePath::Gbm {
+                drift: 0.0,
+                volatility: 0.002,
+            },
+        ),
+        Scenario::JumpDiffusion => SyntheticMarket::new(
+            seed,
+            steps,
+            price,
+            PricePath::JumpDiffusion {
+                drift: 0.0,
+                volatility: 0.002,
+                jump_probability: 0.01,
+                jump_size: 0.05,
+            },
+        ),
+        Scenario::Trend => SyntheticMarket::new(
+            seed,
+            steps,
+            price,
+            PricePath::Trend {
+                change_per_step: 0.0005,
+            },
+        ),
+        Scenario::FlashCrash => SyntheticMarket::flash_crash(seed, steps, price),
+        Scenario::OneSidedFlow => SyntheticMarket::one_sided_flow(seed, steps, price),
+        Scenario::ZeroFlow => SyntheticMarket::zero_flow(seed, steps, price),
+    };
+    market.base_token_decimals = config.base_token_decimals;
+    market.quote_token_decimals = config.quote_token_decimals;
+    market
+}
+
+fn print_report(config: &Config, report: &BacktestReport) {
+    let format_opt = |value: Option<f64>| {
+        value
+            .map(|v| format!("{v:.6}"))
+            .unwrap_or_else(|| "n/a".to_string())
+    };
+
+    println!(
+        "Backtest `{}` on market {}",
+        report.strategy, config.market_id
+    );
+    println!(
+        "  slots:          {} -> {} ({} steps)",
+        report.start_slot, report.end_slot, report.steps
+    );
+    println!("  start price:    {}", format_opt(report.start_price));
+    println!("  end price:      {}", format_opt(report.end_price));
+    println!("  start value:    {}", format_opt(report.start_value));
+    println!("  end value:      {}", format_opt(report.end_value));
+    println!("  pnl:            {}", format_opt(report.pnl));
+    println!("  hold value:     {}", format_opt(report.hold_value));
+    println!("  pnl vs hold:    {}", format_opt(report.pnl_vs_hold));
+    println!("  flow updates:   {}", report.flow_updates);
+    println!("  fee spend:      {} lamports", report.fee_spend_lamports);
+    println!(
+        "  final balances: {} base / {} quote (raw)",
+        report.final_base_balance, report.final_quote_balance
+    );
+    println!("  debt events:    {}", report.debt_events.len());
+    for event in &report.debt_events {
+        println!(
+            "    slot {}: base debt {} / quote debt {}",
+            event.slot, event.base_debt, event.quote_debt
+        );
+    }
+}
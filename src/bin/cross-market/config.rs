@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair},
+};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::{JitterConfig, env_var},
+    coordinator::AllocationConfig,
+    tx::{TxSenderConfig, keypair_from_env},
+};
+
+pub struct Config {
+    pub keypair: Keypair,
+    pub rpc_url: String,
+    pub ws_url: String,
+    /// Markets quoting the same base token against different quote tokens.
+    pub market_ids: Vec<u64>,
+    pub allocation: AllocationConfig,
+    /// Half-life of the fill-rate average, in slots.
+    pub fill_half_life_slots: u64,
+    pub poll_interval: Duration,
+    pub tx: TxSenderConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+    pub jitter: JitterConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let keypair = keypair_from_env("CROSS_MARKET_KEYPAIR")?;
+
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_ids = env_var("CROSS_MARKET_IDS")
+            .map_err(|_| anyhow::anyhow!("CROSS_MARKET_IDS env var not set"))?
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if market_ids.len() < 2 {
+            anyhow::bail!("CROSS_MARKET_IDS must list at least two markets");
+        }
+
+        let allocation = AllocationConfig {
+            flow_divisor: env_var("CROSS_MARKET_FLOW_DIVISOR")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse::<u64>()?,
+            min_weight: env_var("CROSS_MARKET_MIN_WEIGHT")
+                .unwrap_or_else(|_| "0.1".to_string())
+                .parse::<f64>()?,
+            rebalance_threshold_bps: env_var("CROSS_MARKET_REBALANCE_THRESHOLD_BPS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse::<u64>()?,
+        };
+        if allocation.flow_divisor == 0 {
+            anyhow::bail!("CROSS_MARKET_FLOW_DIVISOR must be positive");
+        }
+
+        let fill_half_life_slots = env_var("CROSS_MARKET_FILL_HALF_LIFE_SLOTS")
+            .unwrap_or_else(|_| "9000".to_string())
+            .parse::<u64>()?;
+
+        let poll_interval = Duration::from_secs(
+            env_var("CROSS_MARKET_POLL_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse::<u64>()?,
+        );
+
+        Ok(Self {
+            keypair,
+            rpc_url,
+            ws_url,
+            market_ids,
+            allocation,
+            fill_half_life_slots,
+            poll_interval,
+            tx: TxSenderConfig::from_env()?,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+            jitter: JitterConfig::from_env()?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
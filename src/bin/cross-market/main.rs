@@ -0,0 +1,201 @@
+mod config;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anchor_client::{
+    Client, Program,
+    solana_sdk::{pubkey::Pubkey, signer::Signer},
+};
+use config::Config;
+use tokio::{signal, time::sleep};
+use tracing::{error, info, warn};
+use twob_market_making::{
+    MarketState, ProgramPayer,
+    coordinator::{FillRates, LegSnapshot, allocate},
+    execute_add_liquidity, execute_withdraw_liquidity, fetch_liquidity_position,
+    fetch_market_state, get_liquidity_position_balances, nearest_reference_index, program_payer,
+    strategy::{Action, execute_action},
+    twob_anchor,
+    tx::TxSender,
+};
+
+type CrossMarketProgram = Program<ProgramPayer>;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let signer = Arc::new(config.keypair.insecure_clone());
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(signer.pubkey()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let sender = TxSender::for_program(&program, signer.clone(), config.tx.clone())?;
+
+    // Pooling base across markets only makes sense if it is the same token everywhere.
+    let mut base_mint = None;
+    for &market_id in &config.market_ids {
+        let market = fetch_market_state(&program, market_id).await?.market;
+        match base_mint {
+            None => base_mint = Some(market.base_mint),
+            Some(mint) if mint != market.base_mint => anyhow::bail!(
+                "market {} trades base {} but market {} trades {}",
+                market_id,
+                market.base_mint,
+                config.market_ids[0],
+                mint
+            ),
+            Some(_) => {}
+        }
+    }
+
+    info!(
+        event.name = "cross_market_started",
+        lp.authority = %signer.pubkey(),
+        cross_market.markets = ?config.market_ids,
+        cross_market.flow_divisor = config.allocation.flow_divisor,
+        cross_market.min_weight = config.allocation.min_weight,
+        cross_market.rebalance_threshold_bps = config.allocation.rebalance_threshold_bps,
+        cross_market.fill_half_life_slots = config.fill_half_life_slots,
+    );
+
+    let mut rates = FillRates::new(config.fill_half_life_slots);
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "cross_market_shutdown");
+                break;
+            }
+            _ = sleep(config.jitter.apply(config.poll_interval)) => {
+                if let Err(error) = run_cycle(&program, &config, &mut rates, &sender).await {
+                    error!(event.name = "cross_market_cycle_failed", ?error);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_cycle(
+    program: &CrossMarketProgram,
+    config: &Config,
+    rates: &mut FillRates,
+    sender: &TxSender,
+) -> anyhow::Result<()> {
+    let mut legs = Vec::new();
+    let mut states = BTreeMap::new();
+    for &market_id in &config.market_ids {
+        let (state, leg, has_debt) = take_snapshot(program, market_id, &sender.payer()).await?;
+        if has_debt {
+            // A position in debt can't back flow; stop it and leave it out of the pool.
+            let reference_index =
+                nearest_reference_index(state.current_slot, state.market.end_slot_interval);
+            warn!(
+                event.name = "cross_market_leg_in_debt",
+                market.id = market_id
+            );
+            execute_action(
+                program,
+                market_id,
+                &Action::Stop { reference_index },
+                sender,
+            )
+            .await?;
+            continue;
+        }
+        rates.observe(leg);
+        legs.push(leg);
+        states.insert(market_id, state);
+    }
+
+    let plan = allocate(&config.allocation, &legs, rates);
+    for transfer in &plan.transfers {
+        let from = &states[&transfer.from_market_id];
+        let to = &states[&transfer.to_market_id];
+        execute_withdraw_liquidity(
+            program,
+            transfer.from_market_id,
+            transfer.amount,
+            0,
+            nearest_reference_index(from.current_slot, from.market.end_slot_interval),
+            sender,
+        )
+        .await?;
+        execute_add_liquidity(
+            program,
+            transfer.to_market_id,
+            transfer.amount,
+            0,
+            nearest_reference_index(to.current_slot, to.market.end_slot_interval),
+            sender,
+        )
+        .await?;
+        info!(
+            event.name = "cross_market_base_transferred",
+            cross_market.from_market_id = transfer.from_market_id,
+            cross_market.to_market_id = transfer.to_market_id,
+            cross_market.amount = transfer.amount,
+            monotonic_counter.cross_market_transfers_total = 1_u64,
+        );
+    }
+
+    for allocation in &plan.allocations {
+        let state = &states[&allocation.market_id];
+        let action = Action::UpdateFlows {
+            base_flow: allocation.base_flow,
+            quote_flow: allocation.quote_flow,
+            reference_index: nearest_reference_index(
+                state.current_slot,
+                state.market.end_slot_interval,
+            ),
+        };
+        execute_action(program, allocation.market_id, &action, sender).await?;
+        info!(
+            event.name = "cross_market_allocation",
+            market.id = allocation.market_id,
+            cross_market.fill_rate = rates.rate(allocation.market_id),
+            gauge.cross_market_weight = allocation.weight,
+            gauge.cross_market_target_base = allocation.target_base,
+            gauge.cross_market_base_flow = allocation.base_flow,
+            gauge.cross_market_quote_flow = allocation.quote_flow,
+        );
+    }
+
+    // Our own updates moved `last_update_slot`; fills are measured from here on.
+    for leg in &legs {
+        let (_, leg, _) = take_snapshot(program, leg.market_id, &sender.payer()).await?;
+        rates.rebase(leg);
+    }
+    Ok(())
+}
+
+async fn take_snapshot(
+    program: &CrossMarketProgram,
+    market_id: u64,
+    authority: &Pubkey,
+) -> anyhow::Result<(MarketState, LegSnapshot, bool)> {
+    let state = fetch_market_state(program, market_id).await?;
+    let position = fetch_liquidity_position(program, market_id, authority).await?;
+    let balances = get_liquidity_position_balances(
+        program,
+        position,
+        state.bookkeeping,
+        state.market,
+        state.current_slot,
+    )
+    .await?;
+    let leg = LegSnapshot {
+        market_id,
+        slot: state.current_slot,
+        last_update_slot: position.last_update_slot,
+        base: balances.base_balance.saturating_sub(balances.base_debt),
+        quote: balances.quote_balance.saturating_sub(balances.quote_debt),
+    };
+    let has_debt = balances.base_debt > 0 || balances.quote_debt > 0;
+    Ok((state, leg, has_debt))
+}
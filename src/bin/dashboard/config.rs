@@ -0,0 +1,102 @@
+use std::net::SocketAddr;
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+    dashboard::WatchTarget,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub bind_addr: SocketAddr,
+    pub targets: Vec<WatchTarget>,
+    pub refresh_interval_secs: u64,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let bind_addr = env_var("DASHBOARD_BIND_ADDR")
+            .unwrap_or_else(|_| "0.0.0.0:8090".to_string())
+            .parse::<SocketAddr>()?;
+
+        let targets = env_var("DASHBOARD_TARGETS")
+            .map_err(|_| anyhow::anyhow!("DASHBOARD_TARGETS env var not set"))?
+            .split(',')
+            .filter(|value| !value.trim().is_empty())
+            .map(parse_target)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if targets.is_empty() {
+            anyhow::bail!("DASHBOARD_TARGETS must list at least one market_id:authority pair");
+        }
+
+        let refresh_interval_secs = env_var("DASHBOARD_REFRESH_SECS")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u64>()?;
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            bind_addr,
+            targets,
+            refresh_interval_secs,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
+
+/// `market_id:authority[:price_feed_url]`
+fn parse_target(value: &str) -> anyhow::Result<WatchTarget> {
+    let mut parts = value.trim().splitn(3, ':');
+    let (Some(market_id), Some(authority)) = (parts.next(), parts.next()) else {
+        anyhow::bail!(
+            "invalid dashboard target `{value}`; expected `market_id:authority[:price_feed_url]`"
+        );
+    };
+    Ok(WatchTarget {
+        market_id: market_id.parse()?,
+        authority: authority
+            .parse::<Pubkey>()
+            .map_err(|e| anyhow::anyhow!("invalid authority in dashboard target `{value}`: {e}"))?,
+        price_feed_url: parts.next().map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_targets_with_optional_price_feed() {
+        let authority = Pubkey::new_unique();
+
+        let target = parse_target(&format!("3:{authority}")).unwrap();
+        assert_eq!(target.market_id, 3);
+        assert_eq!(target.authority, authority);
+        assert_eq!(target.price_feed_url, None);
+
+        let target = parse_target(&format!(
+            "1:{authority}:http://localhost:8080/api/v1/price/SOL/USDC"
+        ))
+        .unwrap();
+        assert_eq!(
+            target.price_feed_url.as_deref(),
+            Some("http://localhost:8080/api/v1/price/SOL/USDC")
+        );
+
+        assert!(parse_target("1").is_err());
+    }
+}
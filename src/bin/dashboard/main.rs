@@ -0,0 +1,31 @@
+mod config;
+
+use std::{sync::Arc, time::Duration};
+
+use anchor_client::{Client, solana_sdk::pubkey::Pubkey};
+use config::Config;
+use twob_market_making::program_payer;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    // The dashboard only reads state, so a placeholder payer is enough for the client.
+    let client = Arc::new(Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    ));
+
+    tokio::select! {
+        result = twob_market_making::dashboard::serve(
+            config.bind_addr,
+            client,
+            config.targets,
+            Duration::from_secs(config.refresh_interval_secs),
+        ) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
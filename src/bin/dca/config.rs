@@ -0,0 +1,109 @@
+use std::{path::PathBuf, time::Duration};
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair},
+};
+use twob_market_making::{
+    OrderSide,
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::{JitterConfig, env_var},
+    tx::{TxSenderConfig, keypair_from_env},
+};
+
+pub struct Config {
+    pub keypair: Keypair,
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    pub side: OrderSide,
+    /// Raw units of the token spent per order: quote for a buy, base for a sell.
+    pub amount: u64,
+    pub interval: Duration,
+    /// Each order trades over this many slots, rounded up to the market's end-slot interval.
+    pub order_slots: u64,
+    /// Stop after this many orders.
+    pub max_orders: Option<u64>,
+    /// Remembers when the last order went out and which positions are still open, so a
+    /// restart neither double-buys nor forgets to close.
+    pub state_file: PathBuf,
+    pub poll_interval: Duration,
+    pub tx: TxSenderConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+    pub jitter: JitterConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let keypair = keypair_from_env("DCA_KEYPAIR")?;
+
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_id = env_var("MARKET_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u64>()?;
+
+        let side = env_var("DCA_SIDE")
+            .unwrap_or_else(|_| "buy".to_string())
+            .parse::<OrderSide>()?;
+
+        let amount = env_var("DCA_AMOUNT")
+            .map_err(|_| anyhow::anyhow!("DCA_AMOUNT env var not set"))?
+            .parse::<u64>()?;
+        if amount == 0 {
+            anyhow::bail!("DCA_AMOUNT must be positive");
+        }
+
+        let interval = Duration::from_secs(
+            env_var("DCA_INTERVAL_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse::<u64>()?,
+        );
+
+        let order_slots = env_var("DCA_ORDER_SLOTS")
+            .unwrap_or_else(|_| "9000".to_string())
+            .parse::<u64>()?;
+        if order_slots == 0 {
+            anyhow::bail!("DCA_ORDER_SLOTS must be positive");
+        }
+
+        let max_orders = env_var("DCA_MAX_ORDERS")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.parse::<u64>())
+            .transpose()?;
+
+        let state_file = env_var("DCA_STATE_FILE")
+            .unwrap_or_else(|_| "dca-state.json".to_string())
+            .into();
+
+        let poll_interval = Duration::from_secs(
+            env_var("DCA_POLL_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse::<u64>()?,
+        );
+
+        Ok(Self {
+            keypair,
+            rpc_url,
+            ws_url,
+            market_id,
+            side,
+            amount,
+            interval,
+            order_slots,
+            max_orders,
+            state_file,
+            poll_interval,
+            tx: TxSenderConfig::from_env()?,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+            jitter: JitterConfig::from_env()?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
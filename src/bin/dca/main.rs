@@ -0,0 +1,167 @@
+mod config;
+mod state;
+
+use std::sync::Arc;
+
+use anchor_client::{Client, Program, solana_sdk::signer::Signer};
+use chrono::Utc;
+use config::Config;
+use state::{DcaState, OpenOrder};
+use tokio::{signal, time::sleep};
+use tracing::{info, warn};
+use twob_market_making::{
+    MarketState, ProgramPayer, execute_authority_close_position, execute_submit_order,
+    fetch_market_state, nearest_reference_index, program_payer, twob_anchor, tx::TxSender,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let payer = Arc::new(config.keypair.insecure_clone());
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(payer.pubkey()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let sender = TxSender::for_program(&program, payer, config.tx.clone())?;
+    let mut state = DcaState::load(&config.state_file)?;
+
+    info!(
+        event.name = "dca_started",
+        market.id = config.market_id,
+        dca.side = config.side.as_str(),
+        dca.amount = config.amount,
+        dca.interval_secs = config.interval.as_secs(),
+        dca.orders = state.orders,
+        dca.open_orders = state.open.len(),
+    );
+
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(
+                    event.name = "dca_shutdown",
+                    dca.open_orders = state.open.len(),
+                    "open orders keep trading and are closed on the next run"
+                );
+                break;
+            }
+            _ = sleep(config.jitter.apply(config.poll_interval)) => {
+                let market_state = match fetch_market_state(&program, config.market_id).await {
+                    Ok(market_state) => market_state,
+                    Err(error) => {
+                        warn!(event.name = "dca_state_fetch_failed", ?error);
+                        continue;
+                    }
+                };
+
+                close_matured(&program, &config, &market_state, &mut state, &sender).await;
+
+                if state.order_due(Utc::now(), config.interval, config.max_orders) {
+                    place_order(&program, &config, &market_state, &mut state, &sender).await;
+                } else if config.max_orders.is_some_and(|max| state.orders >= max)
+                    && state.open.is_empty()
+                {
+                    info!(event.name = "dca_completed", dca.orders = state.orders);
+                    break;
+                }
+
+                state.save(&config.state_file)?;
+            }
+        }
+    }
+
+    state.save(&config.state_file)
+}
+
+async fn place_order(
+    program: &Program<ProgramPayer>,
+    config: &Config,
+    market_state: &MarketState,
+    state: &mut DcaState,
+    sender: &TxSender,
+) {
+    let interval = market_state.market.end_slot_interval;
+    let end_slot = (market_state.current_slot + config.order_slots).div_ceil(interval) * interval;
+    // Trade position ids only need to be unique per authority; slots never repeat.
+    let id = market_state.current_slot;
+
+    let submitted = execute_submit_order(
+        program,
+        config.market_id,
+        id,
+        config.side,
+        config.amount,
+        end_slot,
+        nearest_reference_index(market_state.current_slot, interval),
+        sender,
+    )
+    .await;
+    match submitted {
+        Ok(()) => {
+            state.last_order_at = Some(Utc::now());
+            state.orders += 1;
+            state.open.push(OpenOrder { id, end_slot });
+            info!(
+                event.name = "dca_order_placed",
+                market.id = config.market_id,
+                dca.order_id = id,
+                dca.amount = config.amount,
+                dca.end_slot = end_slot,
+                dca.orders = state.orders,
+                monotonic_counter.dca_orders_placed_total = 1_u64,
+            );
+        }
+        Err(error) => warn!(
+            event.name = "dca_order_failed",
+            market.id = config.market_id,
+            dca.order_id = id,
+            ?error,
+        ),
+    }
+}
+
+/// Close every order that has finished trading, keeping the ones that fail to close for
+/// the next poll.
+async fn close_matured(
+    program: &Program<ProgramPayer>,
+    config: &Config,
+    market_state: &MarketState,
+    state: &mut DcaState,
+    sender: &TxSender,
+) {
+    for order in state.take_matured(market_state.current_slot) {
+        let closed = execute_authority_close_position(
+            program,
+            config.market_id,
+            order.id,
+            nearest_reference_index(
+                market_state.current_slot,
+                market_state.market.end_slot_interval,
+            ),
+            sender,
+        )
+        .await;
+        match closed {
+            Ok(()) => info!(
+                event.name = "dca_order_closed",
+                market.id = config.market_id,
+                dca.order_id = order.id,
+                monotonic_counter.dca_orders_closed_total = 1_u64,
+            ),
+            Err(error) => {
+                warn!(
+                    event.name = "dca_order_close_failed",
+                    market.id = config.market_id,
+                    dca.order_id = order.id,
+                    ?error,
+                );
+                state.open.push(order);
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! The DCA schedule's persisted state.
+
+use std::{fs, path::Path, time::Duration};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenOrder {
+    pub id: u64,
+    pub end_slot: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DcaState {
+    pub last_order_at: Option<DateTime<Utc>>,
+    pub orders: u64,
+    pub open: Vec<OpenOrder>,
+}
+
+impl DcaState {
+    /// Load the state, or start fresh if the file doesn't exist yet.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("Invalid DCA state {}", path.display()))
+    }
+
+    /// Write via a temporary file so a crash never leaves a half-written state behind.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))
+    }
+
+    pub fn order_due(
+        &self,
+        now: DateTime<Utc>,
+        interval: Duration,
+        max_orders: Option<u64>,
+    ) -> bool {
+        if max_orders.is_some_and(|max| self.orders >= max) {
+            return false;
+        }
+        self.last_order_at.is_none_or(|last| {
+            now.signed_duration_since(last)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed >= interval)
+        })
+    }
+
+    /// Remove and return the orders that ended at or before `current_slot`.
+    pub fn take_matured(&mut self, current_slot: u64) -> Vec<OpenOrder> {
+        let (matured, open) = self
+            .open
+            .iter()
+            .partition(|order| order.end_slot <= current_slot);
+        self.open = open;
+        matured
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_fall_due_once_per_interval_up_to_the_limit() {
+        let day = Duration::from_secs(86_400);
+        let now = Utc::now();
+        let mut state = DcaState::default();
+        assert!(state.order_due(now, day, Some(2)));
+
+        state.last_order_at = Some(now - chrono::Duration::hours(23));
+        state.orders = 1;
+        assert!(!state.order_due(now, day, Some(2)));
+
+        state.last_order_at = Some(now - chrono::Duration::hours(25));
+        assert!(state.order_due(now, day, Some(2)));
+        state.orders = 2;
+        assert!(!state.order_due(now, day, Some(2)));
+        assert!(state.order_due(now, day, None));
+    }
+
+    #[test]
+    fn matured_orders_are_taken_out() {
+        let mut state = DcaState {
+            open: vec![
+                OpenOrder {
+                    id: 1,
+                    end_slot: 100,
+                },
+                OpenOrder {
+                    id: 2,
+                    end_slot: 200,
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            state.take_matured(150),
+            vec![OpenOrder {
+                id: 1,
+                end_slot: 100
+            }]
+        );
+        assert_eq!(
+            state.open,
+            vec![OpenOrder {
+                id: 2,
+                end_slot: 200
+            }]
+        );
+    }
+}
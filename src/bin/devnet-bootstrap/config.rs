@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair},
+};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+    tx::TxSenderConfig,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    /// Program config authority, market creator and liquidity provider. Generated when unset.
+    pub keypair: Option<Keypair>,
+    /// Owner of the counterparty trade position. Generated when unset.
+    pub trader_keypair: Option<Keypair>,
+    /// Top each wallet up to this many lamports.
+    pub airdrop_lamports: u64,
+    /// Market to create; the first unused id from 1 when unset.
+    pub market_id: Option<u64>,
+    pub end_slot_interval: u64,
+    pub fee_bps: u8,
+    pub unhealthy_liquidity_fee_bps: u8,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    /// Minted to each wallet, in whole tokens.
+    pub mint_amount: u64,
+    /// Deposited into the liquidity position, in whole tokens.
+    pub lp_base: u64,
+    pub lp_quote: u64,
+    /// Flows are sized so the deposits last this many slots.
+    pub flow_divisor: u64,
+    /// Quote the trader spends buying base, in whole tokens.
+    pub trade_quote: u64,
+    /// The trade position runs for this many slots.
+    pub trade_slots: u64,
+    /// The environment is written here as `KEY=value` lines.
+    pub output_path: PathBuf,
+    pub tx: TxSenderConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Devnet)?;
+
+        let keypair = optional_keypair("DEVNET_BOOTSTRAP_KEYPAIR")?;
+        let trader_keypair = optional_keypair("DEVNET_BOOTSTRAP_TRADER_KEYPAIR")?;
+
+        let airdrop_sol = env_var("DEVNET_BOOTSTRAP_AIRDROP_SOL")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse::<f64>()?;
+
+        let market_id = env_var("DEVNET_BOOTSTRAP_MARKET_ID")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.trim().parse::<u64>())
+            .transpose()?;
+
+        let end_slot_interval = env_var("DEVNET_BOOTSTRAP_END_SLOT_INTERVAL")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<u64>()?;
+        if end_slot_interval == 0 {
+            anyhow::bail!("DEVNET_BOOTSTRAP_END_SLOT_INTERVAL must be positive");
+        }
+
+        let fee_bps = env_var("DEVNET_BOOTSTRAP_FEE_BPS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<u8>()?;
+
+        let unhealthy_liquidity_fee_bps = env_var("DEVNET_BOOTSTRAP_UNHEALTHY_FEE_BPS")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse::<u8>()?;
+
+        let base_decimals = env_var("DEVNET_BOOTSTRAP_BASE_DECIMALS")
+            .unwrap_or_else(|_| "9".to_string())
+            .parse::<u8>()?;
+
+        let quote_decimals = env_var("DEVNET_BOOTSTRAP_QUOTE_DECIMALS")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse::<u8>()?;
+
+        let amount = |name: &str, default: &str| {
+            env_var(name)
+                .unwrap_or_else(|_| default.to_string())
+                .parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+        };
+        let mint_amount = amount("DEVNET_BOOTSTRAP_MINT_AMOUNT", "1000000")?;
+        let lp_base = amount("DEVNET_BOOTSTRAP_LP_BASE", "1000")?;
+        let lp_quote = amount("DEVNET_BOOTSTRAP_LP_QUOTE", "150000")?;
+        let flow_divisor = amount("DEVNET_BOOTSTRAP_FLOW_DIVISOR", "100000")?;
+        let trade_quote = amount("DEVNET_BOOTSTRAP_TRADE_QUOTE", "100")?;
+        let trade_slots = amount("DEVNET_BOOTSTRAP_TRADE_SLOTS", "3000")?;
+        if flow_divisor == 0 {
+            anyhow::bail!("DEVNET_BOOTSTRAP_FLOW_DIVISOR must be positive");
+        }
+        if lp_base > mint_amount || lp_quote > mint_amount || trade_quote > mint_amount {
+            anyhow::bail!("DEVNET_BOOTSTRAP_MINT_AMOUNT must cover the LP deposit and the trade");
+        }
+
+        let output_path = PathBuf::from(
+            env_var("DEVNET_BOOTSTRAP_OUTPUT").unwrap_or_else(|_| "devnet.env".to_string()),
+        );
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            keypair,
+            trader_keypair,
+            airdrop_lamports: (airdrop_sol * 1_000_000_000.0) as u64,
+            market_id,
+            end_slot_interval,
+            fee_bps,
+            unhealthy_liquidity_fee_bps,
+            base_decimals,
+            quote_decimals,
+            mint_amount,
+            lp_base,
+            lp_quote,
+            flow_divisor,
+            trade_quote,
+            trade_slots,
+            output_path,
+            tx: TxSenderConfig::from_env()?,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
+
+fn optional_keypair(name: &str) -> anyhow::Result<Option<Keypair>> {
+    let Some(value) = env_var(name).ok().filter(|value| !value.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let keypair_bytes: Vec<u8> = serde_json::from_str(&value)?;
+    let keypair = Keypair::try_from(keypair_bytes.as_slice())
+        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))?;
+    Ok(Some(keypair))
+}
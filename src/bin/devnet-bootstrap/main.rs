@@ -0,0 +1,390 @@
+mod config;
+
+use std::{fmt::Write as _, sync::Arc, time::Duration};
+
+use anchor_client::{
+    Client, Program,
+    solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer},
+};
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    token::spl_token,
+};
+use config::Config;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use tokio::time::sleep;
+use tracing::info;
+use twob_market_making::{
+    AccountResolver, OrderSide, ProgramPayer, TokenPrograms, execute_open_position,
+    execute_submit_order, fetch_market_state, initialize_market_instruction,
+    initialize_program_config_instruction, nearest_reference_index, program_payer,
+    twob_anchor::{self, accounts::ProgramConfig, client::args},
+    tx::TxSender,
+};
+
+type BootstrapProgram = Program<ProgramPayer>;
+
+/// The two test tokens, both plain SPL Token mints.
+struct Mints {
+    base: Pubkey,
+    quote: Pubkey,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let admin = Arc::new(
+        config
+            .keypair
+            .as_ref()
+            .map(Keypair::insecure_clone)
+            .unwrap_or_else(Keypair::new),
+    );
+    let trader = Arc::new(
+        config
+            .trader_keypair
+            .as_ref()
+            .map(Keypair::insecure_clone)
+            .unwrap_or_else(Keypair::new),
+    );
+    let admin_client = Client::new_with_options(
+        config.cluster(),
+        program_payer(admin.pubkey()),
+        config.read_commitment,
+    );
+    let trader_client = Client::new_with_options(
+        config.cluster(),
+        program_payer(trader.pubkey()),
+        config.read_commitment,
+    );
+    let program = admin_client.program(twob_anchor::ID)?;
+    let trader_program = trader_client.program(twob_anchor::ID)?;
+    let rpc = program.rpc();
+    let admin_sender = TxSender::for_program(&program, admin.clone(), config.tx.clone())?;
+    let trader_sender = TxSender::for_program(&trader_program, trader.clone(), config.tx.clone())?;
+
+    info!(
+        event.name = "devnet_bootstrap_started",
+        bootstrap.rpc_url = %config.rpc_url,
+        bootstrap.admin = %admin.pubkey(),
+        bootstrap.trader = %trader.pubkey(),
+    );
+
+    for wallet in [admin.pubkey(), trader.pubkey()] {
+        fund(&rpc, &wallet, config.airdrop_lamports).await?;
+    }
+
+    let mints = create_mints(
+        &program,
+        &config,
+        &admin_sender,
+        &[admin.pubkey(), trader.pubkey()],
+    )
+    .await?;
+    ensure_program_config(&program, &admin_sender).await?;
+    let market_id = match config.market_id {
+        Some(market_id) => market_id,
+        None => next_market_id(&rpc).await?,
+    };
+    create_market(&program, &config, &mints, market_id, &admin_sender).await?;
+    seed_liquidity(&program, &config, market_id, &admin_sender).await?;
+    seed_trade(&trader_program, &config, market_id, &trader_sender).await?;
+
+    let env = render_env(&config, &mints, market_id, &admin, &trader)?;
+    std::fs::write(&config.output_path, env)?;
+    info!(
+        event.name = "devnet_bootstrap_completed",
+        market.id = market_id,
+        market.base_mint = %mints.base,
+        market.quote_mint = %mints.quote,
+        bootstrap.output = %config.output_path.display(),
+    );
+    println!(
+        "Market {} is ready; environment written to {}",
+        market_id,
+        config.output_path.display()
+    );
+    Ok(())
+}
+
+/// Airdrop `wallet` up to `target` lamports. Devnet caps a single airdrop, so large targets
+/// may need several runs.
+async fn fund(rpc: &RpcClient, wallet: &Pubkey, target: u64) -> anyhow::Result<()> {
+    let balance = rpc.get_balance(wallet).await?;
+    if balance >= target {
+        return Ok(());
+    }
+
+    let signature = rpc.request_airdrop(wallet, target - balance).await?;
+    for _ in 0..30 {
+        if rpc.confirm_transaction(&signature).await? {
+            info!(
+                event.name = "devnet_bootstrap_airdropped",
+                bootstrap.wallet = %wallet,
+                bootstrap.lamports = target - balance,
+            );
+            return Ok(());
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+    anyhow::bail!(
+        "airdrop to {} was not confirmed; the faucet may be rate limiting",
+        wallet
+    )
+}
+
+/// Create the base and quote mints with the sender's payer as mint authority, and mint
+/// `config.mint_amount` of each to every wallet in `holders`.
+async fn create_mints(
+    program: &BootstrapProgram,
+    config: &Config,
+    sender: &TxSender,
+    holders: &[Pubkey],
+) -> anyhow::Result<Mints> {
+    let rpc = program.rpc();
+    let admin = sender.payer();
+    let rent = rpc
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .await?;
+
+    let mut created = Vec::new();
+    for decimals in [config.base_decimals, config.quote_decimals] {
+        let mint = Keypair::new();
+        let mut instructions = vec![
+            solana_system_interface::instruction::create_account(
+                &admin,
+                &mint.pubkey(),
+                rent,
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &admin,
+                None,
+                decimals,
+            )?,
+        ];
+        let amount = config
+            .mint_amount
+            .saturating_mul(10u64.pow(u32::from(decimals)));
+        for holder in holders {
+            let ata = get_associated_token_address_with_program_id(
+                holder,
+                &mint.pubkey(),
+                &spl_token::ID,
+            );
+            instructions.push(create_associated_token_account_idempotent(
+                &admin,
+                holder,
+                &mint.pubkey(),
+                &spl_token::ID,
+            ));
+            instructions.push(spl_token::instruction::mint_to(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &ata,
+                &admin,
+                &[],
+                amount,
+            )?);
+        }
+
+        let mint_address = mint.pubkey();
+        sender.send_with_signers(instructions, &[&mint]).await?;
+        info!(
+            event.name = "devnet_bootstrap_mint_created",
+            bootstrap.mint = %mint_address,
+            bootstrap.decimals = decimals,
+        );
+        created.push(mint_address);
+    }
+
+    Ok(Mints {
+        base: created[0],
+        quote: created[1],
+    })
+}
+
+/// Make the sender's payer the program config authority if nobody is yet. Creating markets
+/// needs it.
+async fn ensure_program_config(
+    program: &BootstrapProgram,
+    sender: &TxSender,
+) -> anyhow::Result<()> {
+    let admin = sender.payer();
+    let program_config = AccountResolver::new(twob_anchor::ID)
+        .program_config_pda()
+        .address();
+    if let Ok(existing) = program.account::<ProgramConfig>(program_config).await {
+        if existing.authority != admin {
+            anyhow::bail!(
+                "program config {} belongs to {}; set DEVNET_BOOTSTRAP_KEYPAIR to that authority",
+                program_config,
+                existing.authority
+            );
+        }
+        return Ok(());
+    }
+
+    let instruction = initialize_program_config_instruction(admin, admin);
+    sender.send(vec![instruction]).await?;
+    info!(event.name = "devnet_bootstrap_program_config_created");
+    Ok(())
+}
+
+async fn next_market_id(rpc: &RpcClient) -> anyhow::Result<u64> {
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    for market_id in 1.. {
+        let market = resolver.market_pda(market_id).address();
+        if rpc.get_account(&market).await.is_err() {
+            return Ok(market_id);
+        }
+    }
+    unreachable!("market ids are unbounded")
+}
+
+async fn create_market(
+    program: &BootstrapProgram,
+    config: &Config,
+    mints: &Mints,
+    market_id: u64,
+    sender: &TxSender,
+) -> anyhow::Result<()> {
+    let admin = sender.payer();
+    let start_slot = program.rpc().get_slot().await?;
+
+    let (instruction, market) = initialize_market_instruction(
+        admin,
+        admin,
+        mints.base,
+        mints.quote,
+        TokenPrograms {
+            base: spl_token::ID,
+            quote: spl_token::ID,
+        },
+        args::InitializeMarket {
+            id: market_id,
+            start_slot,
+            end_slot_interval: config.end_slot_interval,
+            fee_bps: config.fee_bps,
+            unhealthy_liquidity_fee_bps: config.unhealthy_liquidity_fee_bps,
+        },
+    );
+    sender.send(vec![instruction]).await?;
+    info!(
+        event.name = "devnet_bootstrap_market_created",
+        market.id = market_id,
+        market.address = %market,
+        market.start_slot = start_slot,
+    );
+    Ok(())
+}
+
+/// Open the sender's liquidity position with flows quoting both sides.
+async fn seed_liquidity(
+    program: &BootstrapProgram,
+    config: &Config,
+    market_id: u64,
+    sender: &TxSender,
+) -> anyhow::Result<()> {
+    let state = fetch_market_state(program, market_id).await?;
+    let reference_index = nearest_reference_index(state.current_slot, config.end_slot_interval);
+    let base_deposit = config.lp_base * 10u64.pow(u32::from(config.base_decimals));
+    let quote_deposit = config.lp_quote * 10u64.pow(u32::from(config.quote_decimals));
+
+    execute_open_position(
+        program,
+        market_id,
+        args::ProvideLiquidity {
+            reference_index,
+            base_deposit_lamports: base_deposit,
+            quote_deposit_lamports: quote_deposit,
+            base_flow_u64: base_deposit / config.flow_divisor,
+            quote_flow_u64: quote_deposit / config.flow_divisor,
+        },
+        sender,
+    )
+    .await?;
+    info!(
+        event.name = "devnet_bootstrap_liquidity_provided",
+        market.id = market_id,
+        position.base = base_deposit,
+        position.quote = quote_deposit,
+    );
+    Ok(())
+}
+
+/// Open a trade position buying base against the liquidity, so there is something to fill.
+async fn seed_trade(
+    program: &BootstrapProgram,
+    config: &Config,
+    market_id: u64,
+    sender: &TxSender,
+) -> anyhow::Result<()> {
+    let current_slot = program.rpc().get_slot().await?;
+    let amount = config.trade_quote * 10u64.pow(u32::from(config.quote_decimals));
+    execute_submit_order(
+        program,
+        market_id,
+        1,
+        OrderSide::Buy,
+        amount,
+        current_slot + config.trade_slots,
+        nearest_reference_index(current_slot, config.end_slot_interval),
+        sender,
+    )
+    .await?;
+    info!(
+        event.name = "devnet_bootstrap_trade_submitted",
+        market.id = market_id,
+        order.side = OrderSide::Buy.as_str(),
+        order.amount = amount,
+        order.slots = config.trade_slots,
+    );
+    Ok(())
+}
+
+/// `KEY=value` lines pointing the bots at the new market.
+fn render_env(
+    config: &Config,
+    mints: &Mints,
+    market_id: u64,
+    admin: &Keypair,
+    trader: &Keypair,
+) -> anyhow::Result<String> {
+    let mut env = String::new();
+    writeln!(env, "RPC_URL={}", config.rpc_url)?;
+    writeln!(env, "WS_URL={}", config.ws_url)?;
+    writeln!(env, "MARKET_ID={}", market_id)?;
+    writeln!(
+        env,
+        "# base mint {}, quote mint {}",
+        mints.base, mints.quote
+    )?;
+    writeln!(env, "BASE_TOKEN_DECIMALS={}", config.base_decimals)?;
+    writeln!(env, "QUOTE_TOKEN_DECIMALS={}", config.quote_decimals)?;
+    // The admin owns the seeded liquidity position, so the flow bots run as it.
+    let admin = serde_json::to_string(&admin.to_bytes().to_vec())?;
+    for name in [
+        "DEVNET_BOOTSTRAP_KEYPAIR",
+        "ORACLE_FLOW_KEYPAIR",
+        "INVENTORY_FLOW_KEYPAIR",
+    ] {
+        writeln!(env, "{}={}", name, admin)?;
+    }
+    writeln!(
+        env,
+        "DEVNET_BOOTSTRAP_TRADER_KEYPAIR={}",
+        serde_json::to_string(&trader.to_bytes().to_vec())?
+    )?;
+    Ok(env)
+}
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Parquet,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Parquet => "parquet",
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            other => anyhow::bail!(
+                "Invalid EXPORT_FORMAT `{}`; expected `csv` or `parquet`",
+                other
+            ),
+        }
+    }
+}
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub format: Format,
+    pub output_dir: PathBuf,
+    /// Indexer database holding flow history; without it only prices are exported.
+    pub database_url: Option<String>,
+    /// Replay this liquidity position's flow history into a balances table.
+    pub authority: Option<Pubkey>,
+    /// The position's raw balances at `start_slot`, where the replay starts.
+    pub initial_base: u64,
+    pub initial_quote: u64,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_id = env_var("MARKET_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u64>()?;
+
+        let start_slot = env_var("EXPORT_START_SLOT")
+            .map_err(|_| anyhow::anyhow!("EXPORT_START_SLOT env var not set"))?
+            .parse::<u64>()?;
+        let end_slot = env_var("EXPORT_END_SLOT")
+            .map_err(|_| anyhow::anyhow!("EXPORT_END_SLOT env var not set"))?
+            .parse::<u64>()?;
+        if start_slot >= end_slot {
+            anyhow::bail!(
+                "EXPORT_START_SLOT {} must be before EXPORT_END_SLOT {}",
+                start_slot,
+                end_slot
+            );
+        }
+
+        let format = env_var("EXPORT_FORMAT")
+            .unwrap_or_else(|_| "csv".to_string())
+            .parse::<Format>()?;
+
+        let output_dir =
+            PathBuf::from(env_var("EXPORT_OUTPUT_DIR").unwrap_or_else(|_| "export".to_string()));
+
+        let database_url = env_var("EXPORT_DATABASE_URL")
+            .or_else(|_| env_var("INDEXER_DATABASE_URL"))
+            .ok()
+            .filter(|value| !value.trim().is_empty());
+
+        let authority = env_var("EXPORT_AUTHORITY")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.trim().parse::<Pubkey>())
+            .transpose()?;
+        if authority.is_some() && database_url.is_none() {
+            anyhow::bail!(
+                "EXPORT_AUTHORITY needs the position's flow history; set EXPORT_DATABASE_URL"
+            );
+        }
+
+        let initial_base = env_var("EXPORT_INITIAL_BASE")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()?;
+
+        let initial_quote = env_var("EXPORT_INITIAL_QUOTE")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()?;
+
+        let base_token_decimals = env_var("BASE_TOKEN_DECIMALS")
+            .unwrap_or_else(|_| "9".to_string())
+            .parse::<u8>()?;
+
+        let quote_token_decimals = env_var("QUOTE_TOKEN_DECIMALS")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse::<u8>()?;
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            market_id,
+            start_slot,
+            end_slot,
+            format,
+            output_dir,
+            database_url,
+            authority,
+            initial_base,
+            initial_quote,
+            base_token_decimals,
+            quote_token_decimals,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
@@ -0,0 +1,105 @@
+//! Flow history read back from the indexer database.
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::Context;
+use tokio_postgres::{Client, NoTls};
+use tracing::error;
+
+/// One recorded flow change: a market-wide `MarketUpdateEvent` (no authority) or a
+/// liquidity position's `update_liquidity_flows` call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowRecord {
+    pub slot: u64,
+    pub signature: String,
+    pub authority: Option<String>,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+}
+
+pub struct History {
+    client: Client,
+}
+
+impl History {
+    pub async fn connect(database_url: &str) -> anyhow::Result<Self> {
+        let (client, connection) = tokio_postgres::connect(database_url, NoTls)
+            .await
+            .context("Failed to connect to indexer database")?;
+        tokio::spawn(async move {
+            if let Err(error) = connection.await {
+                error!(event.name = "export_db_connection_failed", ?error);
+            }
+        });
+        Ok(Self { client })
+    }
+
+    /// Market-wide flow changes and every position's flow updates, ordered by slot.
+    pub async fn flows(
+        &self,
+        market_id: u64,
+        market: &Pubkey,
+        start_slot: u64,
+        end_slot: u64,
+    ) -> anyhow::Result<Vec<FlowRecord>> {
+        let (start, end) = (start_slot as i64, end_slot as i64);
+        let rows = self
+            .client
+            .query(
+                "SELECT slot, signature, NULL::TEXT, base_flow, quote_flow
+                   FROM twob_market_updates
+                  WHERE market_id = $1 AND slot BETWEEN $2 AND $3
+                 UNION ALL
+                 SELECT slot, signature, authority, base_flow, quote_flow
+                   FROM twob_flow_updates
+                  WHERE market = $4 AND slot BETWEEN $2 AND $3
+                 ORDER BY 1, 2",
+                &[&(market_id as i64), &start, &end, &market.to_string()],
+            )
+            .await
+            .context("Failed to query flow history")?;
+        Ok(rows.iter().map(flow_record).collect())
+    }
+
+    /// One position's flow updates up to `end_slot`, including the last one before
+    /// `start_slot` so the replay starts from the flows that were actually live.
+    pub async fn position_flows(
+        &self,
+        market: &Pubkey,
+        authority: &Pubkey,
+        start_slot: u64,
+        end_slot: u64,
+    ) -> anyhow::Result<Vec<FlowRecord>> {
+        let rows = self
+            .client
+            .query(
+                "(SELECT slot, signature, authority, base_flow, quote_flow
+                    FROM twob_flow_updates
+                   WHERE market = $1 AND authority = $2 AND slot < $3
+                   ORDER BY slot DESC LIMIT 1)
+                 UNION ALL
+                 (SELECT slot, signature, authority, base_flow, quote_flow
+                    FROM twob_flow_updates
+                   WHERE market = $1 AND authority = $2 AND slot BETWEEN $3 AND $4)
+                 ORDER BY 1, 2",
+                &[
+                    &market.to_string(),
+                    &authority.to_string(),
+                    &(start_slot as i64),
+                    &(end_slot as i64),
+                ],
+            )
+            .await
+            .context("Failed to query position flow history")?;
+        Ok(rows.iter().map(flow_record).collect())
+    }
+}
+
+fn flow_record(row: &tokio_postgres::Row) -> FlowRecord {
+    FlowRecord {
+        slot: row.get::<_, i64>(0) as u64,
+        signature: row.get(1),
+        authority: row.get(2),
+        base_flow: row.get::<_, i64>(3) as u64,
+        quote_flow: row.get::<_, i64>(4) as u64,
+    }
+}
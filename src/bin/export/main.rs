@@ -0,0 +1,237 @@
+mod config;
+mod history;
+#[cfg(feature = "parquet")]
+mod parquet_file;
+mod table;
+
+use std::{fs, path::PathBuf};
+
+use anchor_client::{Client, solana_sdk::pubkey::Pubkey};
+use anyhow::Context;
+use config::{Config, Format};
+use history::{FlowRecord, History};
+use table::{ColumnData, Table};
+use tracing::{info, warn};
+use twob_market_making::{
+    AccountResolver,
+    backtest::{
+        BalancePoint, FlowUpdate, PricePoint, fetch_price_points, implied_price, replay_balances,
+    },
+    program_payer, twob_anchor,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    #[cfg(not(feature = "parquet"))]
+    if config.format == Format::Parquet {
+        anyhow::bail!("EXPORT_FORMAT=parquet needs a build with `--features parquet`");
+    }
+
+    // Exporting only reads accounts, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let market = AccountResolver::new(twob_anchor::ID)
+        .market_pda(config.market_id)
+        .address();
+
+    fs::create_dir_all(&config.output_dir).with_context(|| {
+        format!(
+            "Failed to create output directory {}",
+            config.output_dir.display()
+        )
+    })?;
+
+    let points = fetch_price_points(
+        &program,
+        config.market_id,
+        config.start_slot,
+        config.end_slot,
+    )
+    .await?;
+    write_table(&config, &prices_table(&points, &config))?;
+
+    let Some(database_url) = &config.database_url else {
+        warn!(
+            event.name = "export_flows_skipped",
+            reason = "no_database_url",
+            "set EXPORT_DATABASE_URL to export flow history and balances"
+        );
+        return Ok(());
+    };
+    let history = History::connect(database_url).await?;
+
+    let flows = history
+        .flows(
+            config.market_id,
+            &market,
+            config.start_slot,
+            config.end_slot,
+        )
+        .await?;
+    write_table(&config, &flows_table(&flows))?;
+
+    if let Some(authority) = &config.authority {
+        let position_flows = history
+            .position_flows(&market, authority, config.start_slot, config.end_slot)
+            .await?;
+        let schedule: Vec<(u64, FlowUpdate)> = position_flows
+            .iter()
+            .map(|record| {
+                (
+                    record.slot,
+                    FlowUpdate {
+                        base_flow: record.base_flow,
+                        quote_flow: record.quote_flow,
+                    },
+                )
+            })
+            .collect();
+        let balances = replay_balances(
+            &points,
+            &schedule,
+            config.initial_base,
+            config.initial_quote,
+        );
+        write_table(&config, &balances_table(&balances))?;
+    }
+
+    Ok(())
+}
+
+fn output_path(config: &Config, table: &Table) -> PathBuf {
+    config.output_dir.join(format!(
+        "market-{}-{}-{}-{}.{}",
+        config.market_id,
+        config.start_slot,
+        config.end_slot,
+        table.name,
+        config.format.extension()
+    ))
+}
+
+fn write_table(config: &Config, table: &Table) -> anyhow::Result<()> {
+    let path = output_path(config, table);
+    match config.format {
+        Format::Csv => table.write_csv_file(&path)?,
+        #[cfg(feature = "parquet")]
+        Format::Parquet => parquet_file::write_parquet_file(table, &path)?,
+        #[cfg(not(feature = "parquet"))]
+        Format::Parquet => unreachable!("rejected at startup without the parquet feature"),
+    }
+    info!(
+        event.name = "export_table_written",
+        export.table = table.name,
+        export.rows = table.rows(),
+        export.path = %path.display(),
+    );
+    Ok(())
+}
+
+fn prices_table(points: &[PricePoint], config: &Config) -> Table {
+    let implied: Vec<Option<f64>> = std::iter::once(None)
+        .chain(points.windows(2).map(|pair| {
+            implied_price(
+                &pair[0],
+                &pair[1],
+                config.base_token_decimals,
+                config.quote_token_decimals,
+            )
+        }))
+        .take(points.len())
+        .collect();
+
+    Table::new("prices")
+        .column(
+            "slot",
+            ColumnData::U64(points.iter().map(|point| point.slot).collect()),
+        )
+        .column(
+            "base_per_quote",
+            ColumnData::Text(
+                points
+                    .iter()
+                    .map(|p| p.base_per_quote.to_string())
+                    .collect(),
+            ),
+        )
+        .column(
+            "quote_per_base",
+            ColumnData::Text(
+                points
+                    .iter()
+                    .map(|p| p.quote_per_base.to_string())
+                    .collect(),
+            ),
+        )
+        .column(
+            "slots_without_trades",
+            ColumnData::U64(points.iter().map(|p| p.slots_without_trades).collect()),
+        )
+        .column("implied_price", ColumnData::F64(implied))
+}
+
+fn flows_table(flows: &[FlowRecord]) -> Table {
+    Table::new("flows")
+        .column(
+            "slot",
+            ColumnData::U64(flows.iter().map(|f| f.slot).collect()),
+        )
+        .column(
+            "signature",
+            ColumnData::Text(flows.iter().map(|f| f.signature.clone()).collect()),
+        )
+        .column(
+            "scope",
+            ColumnData::Text(
+                flows
+                    .iter()
+                    .map(|f| {
+                        if f.authority.is_some() {
+                            "position"
+                        } else {
+                            "market"
+                        }
+                        .to_string()
+                    })
+                    .collect(),
+            ),
+        )
+        .column(
+            "authority",
+            ColumnData::Text(
+                flows
+                    .iter()
+                    .map(|f| f.authority.clone().unwrap_or_default())
+                    .collect(),
+            ),
+        )
+        .column(
+            "base_flow",
+            ColumnData::U64(flows.iter().map(|f| f.base_flow).collect()),
+        )
+        .column(
+            "quote_flow",
+            ColumnData::U64(flows.iter().map(|f| f.quote_flow).collect()),
+        )
+}
+
+fn balances_table(balances: &[BalancePoint]) -> Table {
+    let column =
+        |field: fn(&BalancePoint) -> u64| ColumnData::U64(balances.iter().map(field).collect());
+    Table::new("balances")
+        .column("slot", column(|b| b.slot))
+        .column("base_flow", column(|b| b.base_flow))
+        .column("quote_flow", column(|b| b.quote_flow))
+        .column("base_balance", column(|b| b.base_balance))
+        .column("quote_balance", column(|b| b.quote_balance))
+        .column("base_debt", column(|b| b.base_debt))
+        .column("quote_debt", column(|b| b.quote_debt))
+}
@@ -0,0 +1,47 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use anyhow::Context;
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+use crate::table::{ColumnData, Table};
+
+pub fn write_parquet_file(table: &Table, path: &Path) -> anyhow::Result<()> {
+    table.validate()?;
+
+    let mut fields = Vec::with_capacity(table.columns.len());
+    let mut arrays: Vec<ArrayRef> = Vec::with_capacity(table.columns.len());
+    for column in &table.columns {
+        let (data_type, nullable, array): (DataType, bool, ArrayRef) = match &column.data {
+            ColumnData::U64(values) => (
+                DataType::UInt64,
+                false,
+                Arc::new(UInt64Array::from(values.clone())),
+            ),
+            ColumnData::F64(values) => (
+                DataType::Float64,
+                true,
+                Arc::new(Float64Array::from(values.clone())),
+            ),
+            ColumnData::Text(values) => (
+                DataType::Utf8,
+                false,
+                Arc::new(StringArray::from(values.clone())),
+            ),
+        };
+        fields.push(Field::new(column.name, data_type, nullable));
+        arrays.push(array);
+    }
+
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?;
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
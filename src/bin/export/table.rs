@@ -0,0 +1,141 @@
+//! Column-oriented tables and their CSV encoding. Parquet encoding lives in `parquet_file.rs`.
+
+use std::{fs::File, io::Write, path::Path};
+
+use anyhow::Context;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    U64(Vec<u64>),
+    F64(Vec<Option<f64>>),
+    /// Also used for u128 bookkeeping values, which neither CSV readers nor Parquet
+    /// logical types carry losslessly as numbers.
+    Text(Vec<String>),
+}
+
+impl ColumnData {
+    fn len(&self) -> usize {
+        match self {
+            ColumnData::U64(values) => values.len(),
+            ColumnData::F64(values) => values.len(),
+            ColumnData::Text(values) => values.len(),
+        }
+    }
+
+    fn cell(&self, row: usize) -> String {
+        match self {
+            ColumnData::U64(values) => values[row].to_string(),
+            ColumnData::F64(values) => values[row].map(|v| v.to_string()).unwrap_or_default(),
+            ColumnData::Text(values) => escape_csv(&values[row]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: &'static str,
+    pub data: ColumnData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub name: &'static str,
+    pub columns: Vec<Column>,
+}
+
+impl Table {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn column(mut self, name: &'static str, data: ColumnData) -> Self {
+        self.columns.push(Column { name, data });
+        self
+    }
+
+    pub fn rows(&self) -> usize {
+        self.columns.first().map_or(0, |column| column.data.len())
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let rows = self.rows();
+        for column in &self.columns {
+            anyhow::ensure!(
+                column.data.len() == rows,
+                "{} column `{}` has {} rows, expected {}",
+                self.name,
+                column.name,
+                column.data.len(),
+                rows
+            );
+        }
+        Ok(())
+    }
+
+    pub fn write_csv(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        self.validate()?;
+        let header: Vec<&str> = self.columns.iter().map(|column| column.name).collect();
+        writeln!(writer, "{}", header.join(","))?;
+        for row in 0..self.rows() {
+            let cells: Vec<String> = self
+                .columns
+                .iter()
+                .map(|column| column.data.cell(row))
+                .collect();
+            writeln!(writer, "{}", cells.join(","))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_csv_file(&self, path: &Path) -> anyhow::Result<()> {
+        let mut file = std::io::BufWriter::new(
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+        );
+        self.write_csv(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_header_rows_and_empty_cells_for_missing_floats() {
+        let table = Table::new("prices")
+            .column("slot", ColumnData::U64(vec![10, 20]))
+            .column("price", ColumnData::F64(vec![Some(84.5), None]))
+            .column(
+                "note",
+                ColumnData::Text(vec!["plain".to_string(), "a,\"b\"".to_string()]),
+            );
+
+        let mut out = Vec::new();
+        table.write_csv(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "slot,price,note\n10,84.5,plain\n20,,\"a,\"\"b\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn rejects_ragged_columns() {
+        let table = Table::new("flows")
+            .column("slot", ColumnData::U64(vec![1, 2]))
+            .column("base_flow", ColumnData::U64(vec![1]));
+        assert!(table.validate().is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+use twob_market_making::{
+    alerts::AlertConfig,
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+    event_bus::EventBusConfig,
+};
+
+/// A liquidity position to watch for fills.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Target {
+    pub market_id: u64,
+    pub authority: Pubkey,
+}
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub targets: Vec<Target>,
+    pub poll_interval: Duration,
+    /// Fills worth less than this many UI quote units are logged but not notified.
+    pub min_quote_value: f64,
+    pub alerts: AlertConfig,
+    /// Publish every fill to Kafka or NATS.
+    pub event_bus: Option<EventBusConfig>,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let targets = env_var("FILL_TARGETS")
+            .map_err(|_| anyhow::anyhow!("FILL_TARGETS env var not set"))?
+            .split(',')
+            .filter(|value| !value.trim().is_empty())
+            .map(parse_target)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if targets.is_empty() {
+            anyhow::bail!("FILL_TARGETS must list at least one market_id:authority pair");
+        }
+
+        let poll_interval = Duration::from_secs(
+            env_var("FILL_POLL_INTERVAL_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse::<u64>()?,
+        );
+
+        let min_quote_value = env_var("FILL_MIN_QUOTE_VALUE")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<f64>()?;
+
+        let alerts = AlertConfig::from_env()?;
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            targets,
+            poll_interval,
+            min_quote_value,
+            alerts,
+            event_bus: EventBusConfig::from_env()?,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
+
+/// `market_id:authority`
+fn parse_target(value: &str) -> anyhow::Result<Target> {
+    let Some((market_id, authority)) = value.trim().split_once(':') else {
+        anyhow::bail!("invalid fill target `{value}`; expected `market_id:authority`");
+    };
+    Ok(Target {
+        market_id: market_id.parse()?,
+        authority: authority
+            .parse::<Pubkey>()
+            .map_err(|e| anyhow::anyhow!("invalid authority in fill target `{value}`: {e}"))?,
+    })
+}
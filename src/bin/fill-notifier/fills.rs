@@ -0,0 +1,160 @@
+//! Fill inference from successive snapshots of a liquidity position.
+//!
+//! While its authority leaves it alone, a position only changes by trading against the
+//! market: base flowing out buys quote, quote flowing out buys base. The net change between
+//! two snapshots is therefore the fill, and the ratio of the two sides its effective price.
+
+/// A liquidity position's balances, net of debt, at one slot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    pub slot: u64,
+    /// The position's `last_update_slot`; it moves whenever the authority touches the
+    /// position, which invalidates the comparison.
+    pub last_update_slot: u64,
+    pub base: u64,
+    pub quote: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillSide {
+    /// The position gave up base for quote.
+    SoldBase,
+    /// The position gave up quote for base.
+    BoughtBase,
+}
+
+impl FillSide {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FillSide::SoldBase => "sold",
+            FillSide::BoughtBase => "bought",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fill {
+    pub side: FillSide,
+    pub from_slot: u64,
+    pub to_slot: u64,
+    /// Raw base and quote exchanged.
+    pub base: u64,
+    pub quote: u64,
+    /// Quote per base in UI units.
+    pub price: f64,
+}
+
+/// The net fill between two snapshots of the same position, if any.
+///
+/// Returns `None` when nothing traded, when the authority changed the position in between
+/// (deposits, withdrawals and flow updates aren't fills), and when both sides moved the
+/// same way, which trading alone can't explain.
+pub fn infer_fill(
+    previous: &Snapshot,
+    next: &Snapshot,
+    base_decimals: u8,
+    quote_decimals: u8,
+) -> Option<Fill> {
+    if next.last_update_slot != previous.last_update_slot || next.slot <= previous.slot {
+        return None;
+    }
+    let (side, base, quote) = if next.base < previous.base && next.quote > previous.quote {
+        (
+            FillSide::SoldBase,
+            previous.base - next.base,
+            next.quote - previous.quote,
+        )
+    } else if next.base > previous.base && next.quote < previous.quote {
+        (
+            FillSide::BoughtBase,
+            next.base - previous.base,
+            previous.quote - next.quote,
+        )
+    } else {
+        return None;
+    };
+    let base_ui = base as f64 / 10f64.powi(i32::from(base_decimals));
+    let quote_ui = quote as f64 / 10f64.powi(i32::from(quote_decimals));
+    Some(Fill {
+        side,
+        from_slot: previous.slot,
+        to_slot: next.slot,
+        base,
+        quote,
+        price: quote_ui / base_ui,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(slot: u64, last_update_slot: u64, base: u64, quote: u64) -> Snapshot {
+        Snapshot {
+            slot,
+            last_update_slot,
+            base,
+            quote,
+        }
+    }
+
+    #[test]
+    fn infers_side_size_and_price_from_opposite_moves() {
+        let start = snapshot(100, 50, 10_000_000_000, 1_000_000_000);
+
+        let sold = infer_fill(
+            &start,
+            &snapshot(200, 50, 8_000_000_000, 1_300_000_000),
+            9,
+            6,
+        )
+        .unwrap();
+        assert_eq!(sold.side, FillSide::SoldBase);
+        assert_eq!((sold.base, sold.quote), (2_000_000_000, 300_000_000));
+        assert_eq!((sold.from_slot, sold.to_slot), (100, 200));
+        assert!((sold.price - 150.0).abs() < 1e-9);
+
+        let bought = infer_fill(
+            &start,
+            &snapshot(200, 50, 11_000_000_000, 850_000_000),
+            9,
+            6,
+        )
+        .unwrap();
+        assert_eq!(bought.side, FillSide::BoughtBase);
+        assert_eq!((bought.base, bought.quote), (1_000_000_000, 150_000_000));
+    }
+
+    #[test]
+    fn ignores_idle_positions_authority_changes_and_same_sign_moves() {
+        let start = snapshot(100, 50, 10_000_000_000, 1_000_000_000);
+
+        assert_eq!(
+            infer_fill(
+                &start,
+                &snapshot(200, 50, 10_000_000_000, 1_000_000_000),
+                9,
+                6
+            ),
+            None
+        );
+        assert_eq!(
+            infer_fill(
+                &start,
+                &snapshot(200, 150, 8_000_000_000, 1_300_000_000),
+                9,
+                6
+            ),
+            None
+        );
+        assert_eq!(
+            infer_fill(
+                &start,
+                &snapshot(200, 50, 12_000_000_000, 1_300_000_000),
+                9,
+                6
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,174 @@
+mod config;
+mod fills;
+
+use std::collections::BTreeMap;
+
+use anchor_client::{Client, Program, solana_sdk::pubkey::Pubkey};
+use config::{Config, Target};
+use fills::{Snapshot, infer_fill};
+use tokio::{signal, time::sleep};
+use tracing::{info, warn};
+use twob_market_making::{
+    ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    event_bus::{BusEvent, EventBus},
+    fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances, program_payer,
+    twob_anchor,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    // The notifier only reads accounts, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let alerter = Alerter::from_config("fill-notifier", &config.alerts)?;
+    let event_bus = config
+        .event_bus
+        .clone()
+        .map(|event_bus_config| EventBus::start(event_bus_config, "fill-notifier"));
+
+    let rpc = program.rpc();
+    let mut decimals = BTreeMap::new();
+    for target in &config.targets {
+        if decimals.contains_key(&target.market_id) {
+            continue;
+        }
+        let market = fetch_market_state(&program, target.market_id).await?.market;
+        let base_decimals = rpc.get_token_supply(&market.base_mint).await?.decimals;
+        let quote_decimals = rpc.get_token_supply(&market.quote_mint).await?.decimals;
+        decimals.insert(target.market_id, (base_decimals, quote_decimals));
+    }
+
+    info!(
+        event.name = "fill_notifier_started",
+        fill.targets = config.targets.len(),
+        fill.poll_interval_secs = config.poll_interval.as_secs(),
+        fill.min_quote_value = config.min_quote_value,
+    );
+
+    let mut snapshots: Vec<Option<Snapshot>> = vec![None; config.targets.len()];
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "fill_notifier_shutdown");
+                break;
+            }
+            _ = sleep(config.poll_interval) => {
+                for (target, previous) in config.targets.iter().zip(snapshots.iter_mut()) {
+                    let (base_decimals, quote_decimals) = decimals[&target.market_id];
+                    match take_snapshot(&program, target).await {
+                        Ok(next) => {
+                            if let Some(fill) = previous
+                                .and_then(|previous| {
+                                    infer_fill(&previous, &next, base_decimals, quote_decimals)
+                                })
+                            {
+                                report_fill(
+                                    &config,
+                                    &alerter,
+                                    event_bus.as_ref(),
+                                    target,
+                                    &fill,
+                                    quote_decimals,
+                                );
+                            }
+                            *previous = Some(next);
+                        }
+                        Err(error) => warn!(
+                            event.name = "fill_snapshot_failed",
+                            market.id = target.market_id,
+                            lp.authority = %target.authority,
+                            ?error,
+                        ),
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn take_snapshot(
+    program: &Program<ProgramPayer>,
+    target: &Target,
+) -> anyhow::Result<Snapshot> {
+    let state = fetch_market_state(program, target.market_id).await?;
+    let position = fetch_liquidity_position(program, target.market_id, &target.authority).await?;
+    let balances = get_liquidity_position_balances(
+        program,
+        position,
+        state.bookkeeping,
+        state.market,
+        state.current_slot,
+    )
+    .await?;
+    Ok(Snapshot {
+        slot: state.current_slot,
+        last_update_slot: position.last_update_slot,
+        base: balances.base_balance.saturating_sub(balances.base_debt),
+        quote: balances.quote_balance.saturating_sub(balances.quote_debt),
+    })
+}
+
+fn report_fill(
+    config: &Config,
+    alerter: &Alerter,
+    event_bus: Option<&EventBus>,
+    target: &Target,
+    fill: &fills::Fill,
+    quote_decimals: u8,
+) {
+    let quote_value = fill.quote as f64 / 10f64.powi(i32::from(quote_decimals));
+    info!(
+        event.name = "fill_detected",
+        market.id = target.market_id,
+        lp.authority = %target.authority,
+        fill.side = fill.side.as_str(),
+        fill.base = fill.base,
+        fill.quote = fill.quote,
+        fill.price = fill.price,
+        fill.from_slot = fill.from_slot,
+        fill.to_slot = fill.to_slot,
+        monotonic_counter.fills_total = 1_u64,
+        histogram.fill_quote_value = quote_value,
+        gauge.fill_price = fill.price,
+    );
+    if let Some(event_bus) = event_bus {
+        event_bus.publish(BusEvent::Fill {
+            market_id: target.market_id,
+            authority: target.authority.to_string(),
+            side: fill.side.as_str().to_string(),
+            base: fill.base,
+            quote: fill.quote,
+            price: fill.price,
+            from_slot: fill.from_slot,
+            to_slot: fill.to_slot,
+        });
+    }
+    if quote_value < config.min_quote_value {
+        return;
+    }
+    alerter.notify(
+        AlertKind::Fill,
+        Some(target.market_id),
+        format!(
+            "{} {} {} base for {} quote at {:.6} (slots {}..{})",
+            target.authority,
+            fill.side.as_str(),
+            fill.base,
+            fill.quote,
+            fill.price,
+            fill.from_slot,
+            fill.to_slot
+        ),
+    );
+}
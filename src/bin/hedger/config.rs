@@ -0,0 +1,115 @@
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+};
+
+use crate::{exchange::Venue, hedge::HedgeParams};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    pub authority: Pubkey,
+    pub base_token_decimals: u8,
+    pub venue: Venue,
+    /// Overrides the venue's production endpoint, e.g. for testnet.
+    pub api_base_url: Option<String>,
+    pub api_key: String,
+    pub api_secret: String,
+    pub symbol: String,
+    pub hedge: HedgeParams,
+    pub poll_interval_secs: u64,
+    pub dry_run: bool,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let authority = env_var("HEDGER_AUTHORITY")
+            .map_err(|_| anyhow::anyhow!("HEDGER_AUTHORITY env var not set"))?
+            .parse::<Pubkey>()
+            .map_err(|e| anyhow::anyhow!("Invalid HEDGER_AUTHORITY: {}", e))?;
+
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_id = env_var("MARKET_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u64>()?;
+
+        let base_token_decimals = env_var("BASE_TOKEN_DECIMALS")
+            .unwrap_or_else(|_| "9".to_string())
+            .parse::<u8>()?;
+
+        let venue = env_var("HEDGER_VENUE")
+            .unwrap_or_else(|_| "binance".to_string())
+            .parse::<Venue>()?;
+
+        let api_base_url = env_var("HEDGER_API_BASE_URL")
+            .ok()
+            .filter(|value| !value.trim().is_empty());
+
+        let api_key = env_var("HEDGER_API_KEY")
+            .map_err(|_| anyhow::anyhow!("HEDGER_API_KEY env var not set"))?;
+
+        let api_secret = env_var("HEDGER_API_SECRET")
+            .map_err(|_| anyhow::anyhow!("HEDGER_API_SECRET env var not set"))?;
+
+        let symbol = env_var("HEDGER_SYMBOL").unwrap_or_else(|_| "SOLUSDT".to_string());
+
+        let hedge = HedgeParams {
+            ratio: env_var("HEDGER_RATIO")
+                .unwrap_or_else(|_| "1.0".to_string())
+                .parse::<f64>()?,
+            band: env_var("HEDGER_REBALANCE_BAND")
+                .unwrap_or_else(|_| "0.5".to_string())
+                .parse::<f64>()?,
+            qty_step: env_var("HEDGER_QTY_STEP")
+                .unwrap_or_else(|_| "0.01".to_string())
+                .parse::<f64>()?,
+            min_order_qty: env_var("HEDGER_MIN_ORDER_QTY")
+                .unwrap_or_else(|_| "0.01".to_string())
+                .parse::<f64>()?,
+        };
+        if !(0.0..=1.0).contains(&hedge.ratio) {
+            anyhow::bail!("HEDGER_RATIO must be within [0, 1], got {}", hedge.ratio);
+        }
+        if hedge.qty_step <= 0.0 {
+            anyhow::bail!("HEDGER_QTY_STEP must be positive, got {}", hedge.qty_step);
+        }
+
+        let poll_interval_secs = env_var("HEDGER_POLL_INTERVAL_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<u64>()?;
+
+        let dry_run = env_var("HEDGER_DRY_RUN")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()?;
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            market_id,
+            authority,
+            base_token_decimals,
+            venue,
+            api_base_url,
+            api_key,
+            api_secret,
+            symbol,
+            hedge,
+            poll_interval_secs,
+            dry_run,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
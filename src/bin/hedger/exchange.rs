@@ -0,0 +1,313 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+const BINANCE_FUTURES_URL: &str = "https://fapi.binance.com";
+const BYBIT_URL: &str = "https://api.bybit.com";
+const RECV_WINDOW_MS: u64 = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Venue {
+    /// Binance USDⓈ-M futures.
+    Binance,
+    /// Bybit v5 linear perpetuals.
+    Bybit,
+}
+
+impl FromStr for Venue {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "binance" => Ok(Self::Binance),
+            "bybit" => Ok(Self::Bybit),
+            other => anyhow::bail!("unknown hedge venue `{other}`; expected `binance` or `bybit`"),
+        }
+    }
+}
+
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Binance => "binance",
+            Self::Bybit => "bybit",
+        })
+    }
+}
+
+/// Signed REST client for one perp symbol, assuming one-way position mode.
+pub struct PerpClient {
+    venue: Venue,
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    api_secret: String,
+    symbol: String,
+}
+
+impl PerpClient {
+    pub fn new(
+        venue: Venue,
+        base_url: Option<String>,
+        api_key: String,
+        api_secret: String,
+        symbol: String,
+    ) -> Self {
+        let base_url = base_url.unwrap_or_else(|| {
+            match venue {
+                Venue::Binance => BINANCE_FUTURES_URL,
+                Venue::Bybit => BYBIT_URL,
+            }
+            .to_string()
+        });
+        Self {
+            venue,
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            api_secret,
+            symbol,
+        }
+    }
+
+    pub fn venue(&self) -> Venue {
+        self.venue
+    }
+
+    /// Current signed position in base units; negative is short.
+    pub async fn position(&self) -> anyhow::Result<f64> {
+        match self.venue {
+            Venue::Binance => self.binance_position().await,
+            Venue::Bybit => self.bybit_position().await,
+        }
+    }
+
+    /// Place a market order for `quantity` base units (positive buys, negative sells) and
+    /// return the venue's order id.
+    pub async fn market_order(&self, quantity: f64, formatted_qty: &str) -> anyhow::Result<String> {
+        match self.venue {
+            Venue::Binance => self.binance_market_order(quantity, formatted_qty).await,
+            Venue::Bybit => self.bybit_market_order(quantity, formatted_qty).await,
+        }
+    }
+
+    async fn binance_position(&self) -> anyhow::Result<f64> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct PositionRisk {
+            symbol: String,
+            position_amt: String,
+        }
+
+        let query = self.binance_signed_query(&format!("symbol={}", self.symbol));
+        let positions: Vec<PositionRisk> = self
+            .http
+            .get(format!("{}/fapi/v2/positionRisk?{}", self.base_url, query))
+            .header("X-MBX-APIKEY", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()
+            .context("Binance positionRisk request failed")?
+            .json()
+            .await?;
+
+        positions
+            .iter()
+            .filter(|position| position.symbol == self.symbol)
+            .map(|position| {
+                position
+                    .position_amt
+                    .parse::<f64>()
+                    .with_context(|| format!("invalid positionAmt `{}`", position.position_amt))
+            })
+            .sum()
+    }
+
+    async fn binance_market_order(
+        &self,
+        quantity: f64,
+        formatted_qty: &str,
+    ) -> anyhow::Result<String> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct OrderResponse {
+            order_id: i64,
+        }
+
+        let side = if quantity > 0.0 { "BUY" } else { "SELL" };
+        let query = self.binance_signed_query(&format!(
+            "symbol={}&side={}&type=MARKET&quantity={}",
+            self.symbol, side, formatted_qty
+        ));
+        let response: OrderResponse = self
+            .http
+            .post(format!("{}/fapi/v1/order?{}", self.base_url, query))
+            .header("X-MBX-APIKEY", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()
+            .context("Binance order request failed")?
+            .json()
+            .await?;
+
+        Ok(response.order_id.to_string())
+    }
+
+    fn binance_signed_query(&self, params: &str) -> String {
+        let query = format!(
+            "{}&recvWindow={}&timestamp={}",
+            params,
+            RECV_WINDOW_MS,
+            chrono::Utc::now().timestamp_millis()
+        );
+        let signature = sign(&self.api_secret, &query);
+        format!("{}&signature={}", query, signature)
+    }
+
+    async fn bybit_position(&self) -> anyhow::Result<f64> {
+        #[derive(Deserialize)]
+        struct PositionList {
+            list: Vec<BybitPosition>,
+        }
+        #[derive(Deserialize)]
+        struct BybitPosition {
+            side: String,
+            size: String,
+        }
+
+        let query = format!("category=linear&symbol={}", self.symbol);
+        let result: PositionList = self
+            .bybit_request(
+                reqwest::Method::GET,
+                &format!("/v5/position/list?{query}"),
+                &query,
+            )
+            .await?;
+
+        result
+            .list
+            .iter()
+            .map(|position| {
+                let size = position
+                    .size
+                    .parse::<f64>()
+                    .with_context(|| format!("invalid position size `{}`", position.size))?;
+                Ok(if position.side == "Sell" { -size } else { size })
+            })
+            .sum()
+    }
+
+    async fn bybit_market_order(
+        &self,
+        quantity: f64,
+        formatted_qty: &str,
+    ) -> anyhow::Result<String> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct OrderResult {
+            order_id: String,
+        }
+
+        let body = serde_json::json!({
+            "category": "linear",
+            "symbol": self.symbol,
+            "side": if quantity > 0.0 { "Buy" } else { "Sell" },
+            "orderType": "Market",
+            "qty": formatted_qty,
+        })
+        .to_string();
+        let result: OrderResult = self
+            .bybit_request(reqwest::Method::POST, "/v5/order/create", &body)
+            .await?;
+
+        Ok(result.order_id)
+    }
+
+    /// `payload` is the query string for GETs and the JSON body for POSTs, as Bybit signs it.
+    async fn bybit_request<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        payload: &str,
+    ) -> anyhow::Result<T> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Envelope<T> {
+            ret_code: i64,
+            ret_msg: String,
+            result: Option<T>,
+        }
+
+        let timestamp = chrono::Utc::now().timestamp_millis().to_string();
+        let signature = sign(
+            &self.api_secret,
+            &format!("{}{}{}{}", timestamp, self.api_key, RECV_WINDOW_MS, payload),
+        );
+
+        let mut request = self
+            .http
+            .request(method.clone(), format!("{}{}", self.base_url, path))
+            .header("X-BAPI-API-KEY", &self.api_key)
+            .header("X-BAPI-TIMESTAMP", &timestamp)
+            .header("X-BAPI-RECV-WINDOW", RECV_WINDOW_MS.to_string())
+            .header("X-BAPI-SIGN", signature);
+        if method == reqwest::Method::POST {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(payload.to_string());
+        }
+
+        let envelope: Envelope<T> = request
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Bybit {} request failed", path))?
+            .json()
+            .await?;
+
+        if envelope.ret_code != 0 {
+            anyhow::bail!(
+                "Bybit {} returned {}: {}",
+                path,
+                envelope.ret_code,
+                envelope.ret_msg
+            );
+        }
+        envelope
+            .result
+            .with_context(|| format!("Bybit {} response has no result", path))
+    }
+}
+
+/// Hex HMAC-SHA256, the request signature both venues use.
+fn sign(secret: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_binance_documentation_example() {
+        let secret = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+        let query = "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559";
+        assert_eq!(
+            sign(secret, query),
+            "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71"
+        );
+    }
+
+    #[test]
+    fn parses_venues() {
+        assert_eq!("Binance".parse::<Venue>().unwrap(), Venue::Binance);
+        assert_eq!(" bybit ".parse::<Venue>().unwrap(), Venue::Bybit);
+        assert!("okx".parse::<Venue>().is_err());
+    }
+}
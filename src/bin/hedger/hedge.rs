@@ -0,0 +1,105 @@
+use twob_market_making::LiquidityPositionBalances;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HedgeParams {
+    /// Fraction of the LP's base exposure to offset on the perp venue.
+    pub ratio: f64,
+    /// Drift between target and actual perp position, in base units, tolerated before trading.
+    pub band: f64,
+    /// Venue lot size; orders are rounded towards zero to a multiple of it.
+    pub qty_step: f64,
+    pub min_order_qty: f64,
+}
+
+/// Base the LP is long once debt is netted off, in UI units.
+pub fn net_base_exposure(balances: &LiquidityPositionBalances, base_token_decimals: u8) -> f64 {
+    let net = balances.base_balance as f64 - balances.base_debt as f64;
+    net / 10_f64.powi(i32::from(base_token_decimals))
+}
+
+/// Signed perp order (positive buys, negative sells) that brings `current_position` back to
+/// the hedge target, or `None` while it is inside the band or too small to place.
+pub fn hedge_order(exposure: f64, current_position: f64, params: &HedgeParams) -> Option<f64> {
+    let target = -params.ratio * exposure;
+    let drift = target - current_position;
+    if drift.abs() <= params.band {
+        return None;
+    }
+
+    // Nudge away from zero so float noise (6.0 / 0.01 = 599.999..) doesn't drop a whole lot.
+    let lots = (drift / params.qty_step * (1.0 + 1e-9)).trunc();
+    let quantity = lots * params.qty_step;
+    if quantity.abs() < params.min_order_qty {
+        return None;
+    }
+    Some(quantity)
+}
+
+/// Render an order quantity with as many decimals as the lot size needs.
+pub fn format_quantity(quantity: f64, qty_step: f64) -> String {
+    let mut decimals = 0;
+    while decimals < 12 {
+        let scaled = qty_step * 10_f64.powi(decimals as i32);
+        if (scaled - scaled.round()).abs() < 1e-9 {
+            break;
+        }
+        decimals += 1;
+    }
+    format!("{:.*}", decimals, quantity.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> HedgeParams {
+        HedgeParams {
+            ratio: 1.0,
+            band: 0.5,
+            qty_step: 0.01,
+            min_order_qty: 0.01,
+        }
+    }
+
+    #[test]
+    fn exposure_nets_debt_and_scales_by_decimals() {
+        let balances = LiquidityPositionBalances {
+            base_balance: 12_500_000_000,
+            quote_balance: 0,
+            base_debt: 2_500_000_000,
+            quote_debt: 0,
+        };
+        assert_eq!(net_base_exposure(&balances, 9), 10.0);
+    }
+
+    #[test]
+    fn no_order_inside_band() {
+        assert_eq!(hedge_order(10.0, -9.6, &params()), None);
+    }
+
+    #[test]
+    fn sells_to_open_and_buys_back_when_exposure_falls() {
+        let open = hedge_order(10.0, 0.0, &params()).unwrap();
+        assert!((open + 10.0).abs() < 1e-9);
+
+        let reduce = hedge_order(4.0, -10.0, &params()).unwrap();
+        assert!((reduce - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn partial_ratio_and_lot_rounding() {
+        let params = HedgeParams {
+            ratio: 0.5,
+            band: 0.0,
+            qty_step: 0.1,
+            min_order_qty: 0.1,
+        };
+        let order = hedge_order(3.37, 0.0, &params).unwrap();
+        assert!((order + 1.6).abs() < 1e-9);
+        assert_eq!(format_quantity(order, params.qty_step), "1.6");
+
+        assert_eq!(hedge_order(0.15, 0.0, &params), None);
+        assert_eq!(format_quantity(-2.5, 0.25), "2.50");
+        assert_eq!(format_quantity(3.0, 1.0), "3");
+    }
+}
@@ -0,0 +1,142 @@
+mod config;
+mod exchange;
+mod hedge;
+
+use std::time::Duration;
+
+use anchor_client::{Client, solana_sdk::pubkey::Pubkey};
+use config::Config;
+use exchange::PerpClient;
+use hedge::{format_quantity, hedge_order, net_base_exposure};
+use tokio::{signal, sync::mpsc, time::sleep};
+use tracing::{error, info, warn};
+use twob_market_making::{
+    ProgramPayer, fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances,
+    program_payer,
+    twob_anchor::{self, events::MarketUpdateEvent},
+};
+
+type HedgerProgram = anchor_client::Program<ProgramPayer>;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+    twob_market_making::secrets::resolve_env().await?;
+
+    let config = Config::from_env()?;
+    // The hedger only reads on-chain state, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let perp = PerpClient::new(
+        config.venue,
+        config.api_base_url.clone(),
+        config.api_key.clone(),
+        config.api_secret.clone(),
+        config.symbol.clone(),
+    );
+    let interval = Duration::from_secs(config.poll_interval_secs);
+
+    info!(
+        event.name = "hedger_started",
+        market.id = config.market_id,
+        lp.authority = %config.authority,
+        hedge.venue = %config.venue,
+        hedge.symbol = %config.symbol,
+        hedge.ratio = config.hedge.ratio,
+        hedge.band = config.hedge.band,
+        hedge.dry_run = config.dry_run,
+    );
+
+    // Fills move the LP's inventory, so every market update triggers an immediate re-hedge on
+    // top of the periodic check.
+    let market_id = config.market_id;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _event_unsubscriber = program
+        .on(move |_ctx, event: MarketUpdateEvent| {
+            if event.market_id == market_id {
+                let _ = tx.send(());
+            }
+        })
+        .await?;
+
+    loop {
+        if let Err(error) = rehedge(&program, &perp, &config).await {
+            error!(event.name = "hedger_cycle_failed", ?error);
+        }
+
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "hedger_shutdown");
+                return Ok(());
+            }
+            Some(()) = rx.recv() => {
+                // Collapse a burst of updates into one re-hedge.
+                while rx.try_recv().is_ok() {}
+            }
+            _ = sleep(interval) => {}
+        }
+    }
+}
+
+async fn rehedge(
+    program: &HedgerProgram,
+    perp: &PerpClient,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let market_state = fetch_market_state(program, config.market_id).await?;
+    let position = fetch_liquidity_position(program, config.market_id, &config.authority).await?;
+    let balances = get_liquidity_position_balances(
+        program,
+        position,
+        market_state.bookkeeping,
+        market_state.market,
+        market_state.current_slot,
+    )
+    .await?;
+
+    let exposure = net_base_exposure(&balances, config.base_token_decimals);
+    let perp_position = perp.position().await?;
+
+    info!(
+        event.name = "hedger_exposure",
+        market.id = config.market_id,
+        lp.authority = %config.authority,
+        slot.current = market_state.current_slot,
+        hedge.venue = %perp.venue(),
+        gauge.hedger_net_base_exposure = exposure,
+        gauge.hedger_perp_position = perp_position,
+        gauge.hedger_residual_delta = exposure * config.hedge.ratio + perp_position,
+    );
+
+    let Some(quantity) = hedge_order(exposure, perp_position, &config.hedge) else {
+        return Ok(());
+    };
+    let formatted_qty = format_quantity(quantity, config.hedge.qty_step);
+
+    if config.dry_run {
+        warn!(
+            event.name = "hedger_order_skipped",
+            reason = "dry_run",
+            hedge.symbol = %config.symbol,
+            hedge.order_qty = quantity,
+        );
+        return Ok(());
+    }
+
+    let order_id = perp.market_order(quantity, &formatted_qty).await?;
+    info!(
+        event.name = "hedger_order_placed",
+        hedge.venue = %perp.venue(),
+        hedge.symbol = %config.symbol,
+        hedge.order_id = %order_id,
+        hedge.order_qty = quantity,
+        hedge.previous_position = perp_position,
+        monotonic_counter.hedger_orders_total = 1_u64,
+    );
+    Ok(())
+}
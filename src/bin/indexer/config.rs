@@ -0,0 +1,42 @@
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    config::env_var,
+    event_bus::EventBusConfig,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub database_url: String,
+    /// Upper bound on signatures fetched when catching up after a restart or reconnect.
+    pub backfill_limit: usize,
+    pub reconnect_delay_secs: u64,
+    /// Publish every decoded event to Kafka or NATS.
+    pub event_bus: Option<EventBusConfig>,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let database_url = env_var("INDEXER_DATABASE_URL")
+            .map_err(|_| anyhow::anyhow!("INDEXER_DATABASE_URL env var not set"))?;
+
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let backfill_limit = env_var("INDEXER_BACKFILL_LIMIT")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse::<usize>()?;
+
+        let reconnect_delay_secs = env_var("INDEXER_RECONNECT_DELAY_SECS")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u64>()?;
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            database_url,
+            backfill_limit,
+            reconnect_delay_secs,
+            event_bus: EventBusConfig::from_env()?,
+        })
+    }
+}
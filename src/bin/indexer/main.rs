@@ -0,0 +1,195 @@
+mod config;
+mod store;
+
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use config::Config;
+use futures::StreamExt;
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_types::config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+use store::Store;
+use tokio::{signal, time::sleep};
+use tracing::{error, info, warn};
+use twob_market_making::{
+    event_bus::{BusEvent, EventBus},
+    ingest::{IndexedTransaction, fetch_signatures, fetch_transaction},
+    twob_anchor,
+};
+
+const FETCH_ATTEMPTS: u32 = 5;
+const FETCH_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let rpc = RpcClient::new_with_commitment(config.rpc_url.clone(), CommitmentConfig::confirmed());
+    let mut store = Store::connect(&config.database_url).await?;
+    let event_bus = config
+        .event_bus
+        .clone()
+        .map(|event_bus_config| EventBus::start(event_bus_config, "indexer"));
+
+    info!(
+        event.name = "indexer_started",
+        program.id = %twob_anchor::ID,
+        indexer.backfill_limit = config.backfill_limit,
+    );
+
+    loop {
+        // Catch up first so nothing between the last run (or dropped socket) and now is missed.
+        if let Err(error) =
+            backfill(&rpc, &mut store, event_bus.as_ref(), config.backfill_limit).await
+        {
+            error!(event.name = "indexer_backfill_failed", ?error);
+        }
+
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "indexer_shutdown");
+                return Ok(());
+            }
+            result = follow_logs(&config, &rpc, &mut store, event_bus.as_ref()) => {
+                if let Err(error) = result {
+                    error!(event.name = "indexer_subscription_failed", ?error);
+                }
+            }
+        }
+
+        sleep(Duration::from_secs(config.reconnect_delay_secs)).await;
+    }
+}
+
+/// Index every program signature newer than the latest stored one, oldest first.
+async fn backfill(
+    rpc: &RpcClient,
+    store: &mut Store,
+    event_bus: Option<&EventBus>,
+    limit: usize,
+) -> anyhow::Result<()> {
+    let until = store
+        .latest_signature()
+        .await?
+        .map(|signature| signature.parse::<Signature>())
+        .transpose()?;
+
+    let (pending, truncated) = fetch_signatures(rpc, &twob_anchor::ID, until, limit).await?;
+    if truncated {
+        warn!(
+            event.name = "indexer_backfill_truncated",
+            indexer.backfill_limit = limit,
+        );
+    }
+
+    let total = pending.len();
+    let mut indexed = 0;
+    for entry in pending.into_iter().rev() {
+        let signature = entry.signature.parse::<Signature>()?;
+        if index_signature(rpc, store, event_bus, &signature).await? {
+            indexed += 1;
+        }
+    }
+
+    info!(
+        event.name = "indexer_backfill_completed",
+        indexer.signatures = total,
+        indexer.indexed = indexed,
+        monotonic_counter.indexer_transactions_indexed_total = indexed as u64,
+    );
+    Ok(())
+}
+
+async fn follow_logs(
+    config: &Config,
+    rpc: &RpcClient,
+    store: &mut Store,
+    event_bus: Option<&EventBus>,
+) -> anyhow::Result<()> {
+    let pubsub = PubsubClient::new(&config.ws_url).await?;
+    let (mut notifications, unsubscribe) = pubsub
+        .logs_subscribe(
+            RpcTransactionLogsFilter::Mentions(vec![twob_anchor::ID.to_string()]),
+            RpcTransactionLogsConfig {
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )
+        .await?;
+
+    info!(event.name = "indexer_subscription_started", ws.url = %config.ws_url);
+
+    while let Some(notification) = notifications.next().await {
+        let signature = notification.value.signature.parse::<Signature>()?;
+        if let Err(error) = index_signature(rpc, store, event_bus, &signature).await {
+            error!(
+                event.name = "indexer_transaction_failed",
+                tx.signature = %signature,
+                ?error,
+            );
+        }
+    }
+
+    unsubscribe().await;
+    anyhow::bail!("log subscription closed")
+}
+
+/// Fetch, decode and store one transaction, publishing its events on `event_bus`.
+/// Returns `false` if it was already indexed.
+async fn index_signature(
+    rpc: &RpcClient,
+    store: &mut Store,
+    event_bus: Option<&EventBus>,
+    signature: &Signature,
+) -> anyhow::Result<bool> {
+    let transaction = fetch_with_retry(rpc, signature).await?;
+    let inserted = store.record_transaction(&transaction).await?;
+
+    if inserted {
+        if let Some(event_bus) = event_bus {
+            for event in &transaction.events {
+                event_bus.publish(BusEvent::from_twob_event(
+                    event,
+                    Some(transaction.signature.clone()),
+                    Some(transaction.slot),
+                ));
+            }
+        }
+        info!(
+            event.name = "indexer_transaction_indexed",
+            tx.signature = %signature,
+            slot.current = transaction.slot,
+            tx.success = transaction.success,
+            indexer.instructions = transaction.instructions.len(),
+            indexer.events = transaction.events.len(),
+            monotonic_counter.indexer_transactions_indexed_total = 1_u64,
+        );
+    }
+    Ok(inserted)
+}
+
+/// Log notifications can arrive before the transaction is queryable, so retry briefly.
+async fn fetch_with_retry(
+    rpc: &RpcClient,
+    signature: &Signature,
+) -> anyhow::Result<IndexedTransaction> {
+    let mut attempt = 1;
+    loop {
+        match fetch_transaction(rpc, signature).await {
+            Ok(transaction) => return Ok(transaction),
+            Err(error) if attempt < FETCH_ATTEMPTS => {
+                warn!(
+                    event.name = "indexer_transaction_fetch_retry",
+                    tx.signature = %signature,
+                    retry.attempt = attempt,
+                    ?error,
+                );
+                attempt += 1;
+                sleep(FETCH_RETRY_DELAY).await;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
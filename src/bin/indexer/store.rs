@@ -0,0 +1,461 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::Context;
+use tokio_postgres::{Client, NoTls, Transaction};
+use tracing::error;
+use twob_market_making::{
+    decode::{TwobEvent, TwobInstruction},
+    ingest::{IndexedInstruction, IndexedTransaction},
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS twob_transactions (
+    signature TEXT PRIMARY KEY,
+    slot BIGINT NOT NULL,
+    block_time BIGINT,
+    success BOOLEAN NOT NULL,
+    indexed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS twob_transactions_slot ON twob_transactions (slot);
+
+CREATE TABLE IF NOT EXISTS twob_instructions (
+    signature TEXT NOT NULL REFERENCES twob_transactions (signature),
+    instruction_index INTEGER NOT NULL,
+    slot BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (signature, instruction_index)
+);
+
+CREATE TABLE IF NOT EXISTS twob_markets (
+    market TEXT PRIMARY KEY,
+    market_id BIGINT NOT NULL,
+    base_mint TEXT NOT NULL,
+    quote_mint TEXT NOT NULL,
+    start_slot BIGINT NOT NULL,
+    end_slot_interval BIGINT NOT NULL,
+    fee_bps SMALLINT NOT NULL,
+    unhealthy_liquidity_fee_bps SMALLINT NOT NULL,
+    created_slot BIGINT NOT NULL,
+    signature TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS twob_positions (
+    position TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    market TEXT NOT NULL,
+    authority TEXT NOT NULL,
+    opened_slot BIGINT NOT NULL,
+    opened_signature TEXT NOT NULL,
+    amount BIGINT,
+    end_slot BIGINT,
+    stopped_slot BIGINT,
+    closed_slot BIGINT
+);
+CREATE INDEX IF NOT EXISTS twob_positions_market ON twob_positions (market, kind);
+
+CREATE TABLE IF NOT EXISTS twob_flow_updates (
+    signature TEXT NOT NULL REFERENCES twob_transactions (signature),
+    instruction_index INTEGER NOT NULL,
+    slot BIGINT NOT NULL,
+    market TEXT NOT NULL,
+    position TEXT NOT NULL,
+    authority TEXT NOT NULL,
+    base_flow BIGINT NOT NULL,
+    quote_flow BIGINT NOT NULL,
+    PRIMARY KEY (signature, instruction_index)
+);
+CREATE INDEX IF NOT EXISTS twob_flow_updates_position ON twob_flow_updates (position, slot);
+
+CREATE TABLE IF NOT EXISTS twob_stops (
+    signature TEXT NOT NULL REFERENCES twob_transactions (signature),
+    instruction_index INTEGER NOT NULL,
+    slot BIGINT NOT NULL,
+    market TEXT NOT NULL,
+    position TEXT NOT NULL,
+    position_authority TEXT NOT NULL,
+    stopped_by TEXT NOT NULL,
+    PRIMARY KEY (signature, instruction_index)
+);
+
+CREATE TABLE IF NOT EXISTS twob_market_updates (
+    signature TEXT NOT NULL REFERENCES twob_transactions (signature),
+    event_index INTEGER NOT NULL,
+    slot BIGINT NOT NULL,
+    market_id BIGINT NOT NULL,
+    base_flow BIGINT NOT NULL,
+    quote_flow BIGINT NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+
+CREATE TABLE IF NOT EXISTS twob_trade_closes (
+    signature TEXT NOT NULL REFERENCES twob_transactions (signature),
+    event_index INTEGER NOT NULL,
+    slot BIGINT NOT NULL,
+    market_id BIGINT NOT NULL,
+    position_authority TEXT NOT NULL,
+    start_slot BIGINT NOT NULL,
+    end_slot BIGINT NOT NULL,
+    deposit_amount BIGINT NOT NULL,
+    swapped_amount BIGINT NOT NULL,
+    remaining_amount BIGINT NOT NULL,
+    fee_amount BIGINT NOT NULL,
+    is_buy BOOLEAN NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+";
+
+pub struct Store {
+    client: Client,
+}
+
+impl Store {
+    pub async fn connect(database_url: &str) -> anyhow::Result<Self> {
+        let (client, connection) = tokio_postgres::connect(database_url, NoTls)
+            .await
+            .context("Failed to connect to indexer database")?;
+        tokio::spawn(async move {
+            if let Err(error) = connection.await {
+                error!(event.name = "indexer_db_connection_failed", ?error);
+            }
+        });
+
+        client
+            .batch_execute(SCHEMA)
+            .await
+            .context("Failed to initialize indexer database schema")?;
+        Ok(Self { client })
+    }
+
+    /// Newest indexed signature, used as the lower bound when catching up.
+    pub async fn latest_signature(&self) -> anyhow::Result<Option<String>> {
+        let row = self
+            .client
+            .query_opt(
+                "SELECT signature FROM twob_transactions ORDER BY slot DESC, indexed_at DESC LIMIT 1",
+                &[],
+            )
+            .await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    /// Write a transaction and everything derived from it atomically.
+    ///
+    /// Returns `false` if the signature was already indexed.
+    pub async fn record_transaction(&mut self, tx: &IndexedTransaction) -> anyhow::Result<bool> {
+        let db = self.client.transaction().await?;
+
+        let inserted = db
+            .execute(
+                "INSERT INTO twob_transactions (signature, slot, block_time, success)
+                 VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (signature) DO NOTHING",
+                &[
+                    &tx.signature,
+                    &(tx.slot as i64),
+                    &tx.block_time,
+                    &tx.success,
+                ],
+            )
+            .await?;
+        if inserted == 0 {
+            db.rollback().await?;
+            return Ok(false);
+        }
+
+        for ix in &tx.instructions {
+            record_instruction(&db, tx, ix).await?;
+        }
+        for (event_index, event) in tx.events.iter().enumerate() {
+            record_event(&db, tx, event_index as i32, event).await?;
+        }
+
+        db.commit().await?;
+        Ok(true)
+    }
+}
+
+async fn record_instruction(
+    db: &Transaction<'_>,
+    tx: &IndexedTransaction,
+    ix: &IndexedInstruction,
+) -> anyhow::Result<()> {
+    let slot = tx.slot as i64;
+    let index = ix.index as i32;
+
+    db.execute(
+        "INSERT INTO twob_instructions (signature, instruction_index, slot, name)
+         VALUES ($1, $2, $3, $4)",
+        &[&tx.signature, &index, &slot, &ix.instruction.name()],
+    )
+    .await?;
+
+    match &ix.instruction {
+        TwobInstruction::InitializeMarket {
+            id,
+            start_slot,
+            end_slot_interval,
+            fee_bps,
+            unhealthy_liquidity_fee_bps,
+        } => {
+            db.execute(
+                "INSERT INTO twob_markets (
+                    market, market_id, base_mint, quote_mint, start_slot, end_slot_interval,
+                    fee_bps, unhealthy_liquidity_fee_bps, created_slot, signature
+                 ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                 ON CONFLICT (market) DO UPDATE SET
+                    market_id = EXCLUDED.market_id,
+                    start_slot = EXCLUDED.start_slot,
+                    end_slot_interval = EXCLUDED.end_slot_interval,
+                    fee_bps = EXCLUDED.fee_bps,
+                    unhealthy_liquidity_fee_bps = EXCLUDED.unhealthy_liquidity_fee_bps,
+                    created_slot = EXCLUDED.created_slot,
+                    signature = EXCLUDED.signature",
+                &[
+                    &ix.account(5)?.to_string(),
+                    &(*id as i64),
+                    &ix.account(3)?.to_string(),
+                    &ix.account(4)?.to_string(),
+                    &(*start_slot as i64),
+                    &(*end_slot_interval as i64),
+                    &i16::from(*fee_bps),
+                    &i16::from(*unhealthy_liquidity_fee_bps),
+                    &slot,
+                    &tx.signature,
+                ],
+            )
+            .await?;
+        }
+        TwobInstruction::ProvideLiquidity {
+            base_flow_u64,
+            quote_flow_u64,
+            ..
+        } => {
+            let (authority, market, position) = (ix.account(0)?, ix.account(5)?, ix.account(6)?);
+            open_position(db, tx, "liquidity", market, authority, position, None, None).await?;
+            record_flow_update(
+                db,
+                tx,
+                index,
+                market,
+                position,
+                authority,
+                *base_flow_u64,
+                *quote_flow_u64,
+            )
+            .await?;
+        }
+        TwobInstruction::UpdateLiquidityFlows {
+            base_flow_u64,
+            quote_flow_u64,
+            ..
+        } => {
+            record_flow_update(
+                db,
+                tx,
+                index,
+                ix.account(1)?,
+                ix.account(2)?,
+                ix.account(0)?,
+                *base_flow_u64,
+                *quote_flow_u64,
+            )
+            .await?;
+        }
+        TwobInstruction::PublicStopLiquidityPosition { .. } => {
+            let position = ix.account(7)?;
+            db.execute(
+                "INSERT INTO twob_stops (
+                    signature, instruction_index, slot, market, position,
+                    position_authority, stopped_by
+                 ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &tx.signature,
+                    &index,
+                    &slot,
+                    &ix.account(6)?.to_string(),
+                    &position.to_string(),
+                    &ix.account(1)?.to_string(),
+                    &ix.account(0)?.to_string(),
+                ],
+            )
+            .await?;
+            db.execute(
+                "UPDATE twob_positions SET stopped_slot = $2 WHERE position = $1",
+                &[&position.to_string(), &slot],
+            )
+            .await?;
+        }
+        TwobInstruction::AuthorityCloseLiquidityPosition { .. }
+        | TwobInstruction::AuthorityClosePosition { .. } => {
+            close_position(db, ix.account(6)?, slot).await?;
+        }
+        TwobInstruction::PublicClosePosition { .. } => {
+            close_position(db, ix.account(7)?, slot).await?;
+        }
+        TwobInstruction::SubmitOrder {
+            amount, end_slot, ..
+        } => {
+            open_position(
+                db,
+                tx,
+                "trade",
+                ix.account(3)?,
+                ix.account(0)?,
+                ix.account(4)?,
+                Some(*amount as i64),
+                Some(*end_slot as i64),
+            )
+            .await?;
+        }
+        TwobInstruction::AddLiquidity { .. }
+        | TwobInstruction::WithdrawLiquidity { .. }
+        | TwobInstruction::UpdateBooks { .. }
+        | TwobInstruction::Other { .. } => {}
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn open_position(
+    db: &Transaction<'_>,
+    tx: &IndexedTransaction,
+    kind: &str,
+    market: Pubkey,
+    authority: Pubkey,
+    position: Pubkey,
+    amount: Option<i64>,
+    end_slot: Option<i64>,
+) -> anyhow::Result<()> {
+    // Position PDAs are reused once closed, so reopening resets the lifecycle columns.
+    db.execute(
+        "INSERT INTO twob_positions (
+            position, kind, market, authority, opened_slot, opened_signature, amount, end_slot
+         ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT (position) DO UPDATE SET
+            kind = EXCLUDED.kind,
+            market = EXCLUDED.market,
+            authority = EXCLUDED.authority,
+            opened_slot = EXCLUDED.opened_slot,
+            opened_signature = EXCLUDED.opened_signature,
+            amount = EXCLUDED.amount,
+            end_slot = EXCLUDED.end_slot,
+            stopped_slot = NULL,
+            closed_slot = NULL",
+        &[
+            &position.to_string(),
+            &kind,
+            &market.to_string(),
+            &authority.to_string(),
+            &(tx.slot as i64),
+            &tx.signature,
+            &amount,
+            &end_slot,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn close_position(db: &Transaction<'_>, position: Pubkey, slot: i64) -> anyhow::Result<()> {
+    db.execute(
+        "UPDATE twob_positions SET closed_slot = $2 WHERE position = $1",
+        &[&position.to_string(), &slot],
+    )
+    .await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record_flow_update(
+    db: &Transaction<'_>,
+    tx: &IndexedTransaction,
+    index: i32,
+    market: Pubkey,
+    position: Pubkey,
+    authority: Pubkey,
+    base_flow: u64,
+    quote_flow: u64,
+) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO twob_flow_updates (
+            signature, instruction_index, slot, market, position, authority, base_flow, quote_flow
+         ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        &[
+            &tx.signature,
+            &index,
+            &(tx.slot as i64),
+            &market.to_string(),
+            &position.to_string(),
+            &authority.to_string(),
+            &(base_flow as i64),
+            &(quote_flow as i64),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn record_event(
+    db: &Transaction<'_>,
+    tx: &IndexedTransaction,
+    event_index: i32,
+    event: &TwobEvent,
+) -> anyhow::Result<()> {
+    let slot = tx.slot as i64;
+    match event {
+        TwobEvent::MarketUpdate {
+            market_id,
+            base_flow,
+            quote_flow,
+        } => {
+            db.execute(
+                "INSERT INTO twob_market_updates (
+                    signature, event_index, slot, market_id, base_flow, quote_flow
+                 ) VALUES ($1, $2, $3, $4, $5, $6)",
+                &[
+                    &tx.signature,
+                    &event_index,
+                    &slot,
+                    &(*market_id as i64),
+                    &(*base_flow as i64),
+                    &(*quote_flow as i64),
+                ],
+            )
+            .await?;
+        }
+        TwobEvent::ClosePosition {
+            position_authority,
+            market_id,
+            start_slot,
+            end_slot,
+            deposit_amount,
+            swapped_amount,
+            remaining_amount,
+            fee_amount,
+            is_buy,
+        } => {
+            db.execute(
+                "INSERT INTO twob_trade_closes (
+                    signature, event_index, slot, market_id, position_authority, start_slot,
+                    end_slot, deposit_amount, swapped_amount, remaining_amount, fee_amount, is_buy
+                 ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+                &[
+                    &tx.signature,
+                    &event_index,
+                    &slot,
+                    &(*market_id as i64),
+                    &position_authority.to_string(),
+                    &(*start_slot as i64),
+                    &(*end_slot as i64),
+                    &(*deposit_amount as i64),
+                    &(*swapped_amount as i64),
+                    &(*remaining_amount as i64),
+                    &(*fee_amount as i64),
+                    is_buy,
+                ],
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    /// Wallet whose token movements are booked.
+    pub authority: Pubkey,
+    /// Markets whose liquidity and trade positions are part of the books.
+    pub market_ids: Vec<u64>,
+    pub db_path: String,
+    /// Most new signatures booked per sync.
+    pub signature_limit: usize,
+    /// Largest difference, in raw units, `reconcile` tolerates per account and asset.
+    pub tolerance: u64,
+    /// Ticker per mint for exports; unlisted mints are named by address.
+    pub asset_symbols: BTreeMap<Pubkey, String>,
+    pub export_dir: PathBuf,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let authority = env_var("LEDGER_AUTHORITY")
+            .map_err(|_| anyhow::anyhow!("LEDGER_AUTHORITY env var not set"))?
+            .parse::<Pubkey>()
+            .map_err(|e| anyhow::anyhow!("Invalid LEDGER_AUTHORITY: {}", e))?;
+
+        let market_ids = env_var("LEDGER_MARKET_IDS")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .or_else(|| env_var("MARKET_ID").ok())
+            .unwrap_or_else(|| "1".to_string())
+            .split(',')
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.trim().parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let db_path = env_var("LEDGER_DB_PATH").unwrap_or_else(|_| "ledger.sqlite".to_string());
+
+        let signature_limit = env_var("LEDGER_SIGNATURE_LIMIT")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse::<usize>()?;
+
+        let tolerance = env_var("LEDGER_TOLERANCE")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()?;
+
+        let asset_symbols = env_var("LEDGER_ASSET_SYMBOLS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|value| !value.trim().is_empty())
+            .map(parse_asset_symbol)
+            .collect::<anyhow::Result<BTreeMap<_, _>>>()?;
+
+        let export_dir = PathBuf::from(
+            env_var("LEDGER_EXPORT_DIR").unwrap_or_else(|_| "tax-export".to_string()),
+        );
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            authority,
+            market_ids,
+            db_path,
+            signature_limit,
+            tolerance,
+            asset_symbols,
+            export_dir,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
+
+/// `mint:SYMBOL`
+fn parse_asset_symbol(value: &str) -> anyhow::Result<(Pubkey, String)> {
+    let Some((mint, symbol)) = value.trim().split_once(':') else {
+        anyhow::bail!("invalid asset symbol `{value}`; expected `mint:SYMBOL`");
+    };
+    let mint = mint
+        .parse::<Pubkey>()
+        .map_err(|e| anyhow::anyhow!("invalid mint in asset symbol `{value}`: {e}"))?;
+    Ok((mint, symbol.trim().to_string()))
+}
@@ -0,0 +1,457 @@
+mod config;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anchor_client::{Client, Program, solana_sdk::pubkey::Pubkey};
+use chrono::Datelike;
+use config::Config;
+use tracing::{info, warn};
+use twob_market_making::{
+    AccountResolver, ProgramPayer, fetch_market_state,
+    ingest::{fetch_signatures, fetch_transaction},
+    ledger::{
+        Asset, Balances, Entry, EntryKind, LedgerAccount, LedgerMarket, LedgerStore,
+        chain_balances, reconcile, settlement_entry,
+        tax::{
+            AssetInfo, TaxFormat, format_amount, realized_gains, summarize_period, tax_rows,
+            write_gains_csv, write_tax_csv,
+        },
+        transaction_entries,
+    },
+    portfolio::fetch_portfolio,
+    program_payer, twob_anchor,
+};
+
+type LedgerProgram = Program<ProgramPayer>;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let store = LedgerStore::open(&config.db_path)?;
+    // The ledger only reads accounts, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None | Some("sync") => sync(&config, &program, &store).await,
+        Some("reconcile") => match &args[1..] {
+            [] => reconcile_command(&config, &program, &store, false).await,
+            [flag] if flag == "--json" => reconcile_command(&config, &program, &store, true).await,
+            _ => anyhow::bail!("usage: ledger reconcile [--json]"),
+        },
+        Some("export") => export(&config, &program, &store, &ExportArgs::parse(&args[1..])?).await,
+        Some(other) => anyhow::bail!(
+            "unknown command `{other}`; expected `sync`, `reconcile [--json]` or `export \
+             [--from DATE] [--to DATE] [--format koinly|cointracker]`"
+        ),
+    }
+}
+
+/// What the chain holds for the authority right now.
+struct ChainState {
+    slot: u64,
+    markets: Vec<LedgerMarket>,
+    balances: Balances,
+}
+
+async fn fetch_chain_state(config: &Config, program: &LedgerProgram) -> anyhow::Result<ChainState> {
+    let rpc = program.rpc();
+    let portfolio = fetch_portfolio(
+        program,
+        &[config.authority],
+        &config.market_ids,
+        &BTreeMap::new(),
+    )
+    .await?;
+    let lamports = rpc.get_balance(&config.authority).await?;
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let markets = portfolio
+        .markets
+        .values()
+        .map(|valuation| LedgerMarket {
+            market_id: valuation.market_id,
+            address: resolver.market_pda(valuation.market_id).address(),
+            base_mint: valuation.base_mint,
+            quote_mint: valuation.quote_mint,
+        })
+        .collect();
+    Ok(ChainState {
+        slot: rpc.get_slot().await?,
+        markets,
+        balances: chain_balances(&portfolio, lamports),
+    })
+}
+
+async fn sync(config: &Config, program: &LedgerProgram, store: &LedgerStore) -> anyhow::Result<()> {
+    let owner = config.authority;
+    let rpc = program.rpc();
+    let now = chrono::Utc::now().timestamp();
+
+    if !store.is_open(&owner)? {
+        // Start the books from what the wallet holds today rather than replaying its
+        // whole history.
+        let (newest, _) = fetch_signatures(&rpc, &owner, None, 1).await?;
+        let chain = fetch_chain_state(config, program).await?;
+        let mut opening = Entry::new(EntryKind::Opening, owner, chain.slot, now, None);
+        for (&(account, asset), &amount) in &chain.balances {
+            opening.transfer(LedgerAccount::Equity, account, asset, amount);
+        }
+        store.record(
+            &owner,
+            &[opening],
+            newest.first().map(|status| status.signature.as_str()),
+        )?;
+        info!(
+            event.name = "ledger_opened",
+            ledger.authority = %owner,
+            ledger.balances = chain.balances.len(),
+            slot.current = chain.slot,
+        );
+        return Ok(());
+    }
+
+    let until = store
+        .cursor(&owner)?
+        .map(|signature| signature.parse())
+        .transpose()?;
+    let (signatures, truncated) =
+        fetch_signatures(&rpc, &owner, until, config.signature_limit).await?;
+    if truncated {
+        anyhow::bail!(
+            "more than {} signatures since the last sync; raise LEDGER_SIGNATURE_LIMIT so none \
+             are skipped",
+            config.signature_limit
+        );
+    }
+
+    let markets = fetch_chain_state(config, program).await?.markets;
+    let mut booked = 0_usize;
+    // Oldest first, so the cursor only moves past transactions that are booked.
+    for status in signatures.iter().rev() {
+        let transaction = fetch_transaction(&rpc, &status.signature.parse()?).await?;
+        let entries = transaction_entries(&owner, &transaction, &markets);
+        booked += entries.len();
+        store.record(&owner, &entries, Some(&status.signature))?;
+    }
+
+    // Positions change without the authority's transactions as they trade; book whatever
+    // the transactions don't explain as fills.
+    let chain = fetch_chain_state(config, program).await?;
+    let ledger = store.balances(&owner)?;
+    let fills = chain
+        .markets
+        .iter()
+        .flat_map(|market| {
+            [
+                (
+                    LedgerAccount::LiquidityPosition(market.market_id),
+                    market.market_id,
+                ),
+                (
+                    LedgerAccount::TradePositions(market.market_id),
+                    market.market_id,
+                ),
+            ]
+        })
+        .filter_map(|(account, market_id)| {
+            settlement_entry(
+                &owner,
+                chain.slot,
+                now,
+                account,
+                market_id,
+                &ledger,
+                &chain.balances,
+            )
+        })
+        .collect::<Vec<_>>();
+    store.record(&owner, &fills, None)?;
+
+    info!(
+        event.name = "ledger_synced",
+        ledger.authority = %owner,
+        ledger.transactions = signatures.len(),
+        ledger.entries = booked,
+        ledger.fills = fills.len(),
+        slot.current = chain.slot,
+        monotonic_counter.ledger_entries_total = (booked + fills.len()) as u64,
+    );
+    Ok(())
+}
+
+/// Compare the ledger's balances with the chain's, printed as a table or, with `json`, one
+/// JSON object; fails unless they reconcile.
+async fn reconcile_command(
+    config: &Config,
+    program: &LedgerProgram,
+    store: &LedgerStore,
+    json: bool,
+) -> anyhow::Result<()> {
+    let owner = config.authority;
+    anyhow::ensure!(
+        store.is_open(&owner)?,
+        "no ledger for {owner} yet; run `ledger sync` first"
+    );
+
+    let unbalanced = store.unbalanced_entries(&owner)?;
+    let chain = fetch_chain_state(config, program).await?;
+    let ledger = store.balances(&owner)?;
+    let discrepancies = reconcile(&ledger, &chain.balances, config.tolerance);
+
+    let keys = ledger
+        .keys()
+        .chain(chain.balances.keys())
+        .filter(|(account, _)| account.is_on_chain())
+        .collect::<BTreeSet<_>>();
+    let balance = |balances: &Balances, account: LedgerAccount, asset: Asset| {
+        balances.get(&(account, asset)).copied().unwrap_or_default()
+    };
+    if json {
+        let accounts = keys
+            .iter()
+            .map(|&&(account, asset)| {
+                serde_json::json!({
+                    "account": account.to_string(),
+                    "asset": asset.to_string(),
+                    "ledger": balance(&ledger, account, asset),
+                    "chain": balance(&chain.balances, account, asset),
+                })
+            })
+            .collect::<Vec<_>>();
+        let off = discrepancies
+            .iter()
+            .map(|discrepancy| {
+                serde_json::json!({
+                    "account": discrepancy.account.to_string(),
+                    "asset": discrepancy.asset.to_string(),
+                    "ledger": discrepancy.ledger,
+                    "chain": discrepancy.chain,
+                    "difference": discrepancy.difference(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let report = serde_json::json!({
+            "authority": owner.to_string(),
+            "slot": chain.slot,
+            "accounts": accounts,
+            "discrepancies": off,
+            "unbalanced_entries": unbalanced,
+            "reconciled": unbalanced.is_empty() && discrepancies.is_empty(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("Ledger reconciliation for {} at slot {}", owner, chain.slot);
+        for &(account, asset) in keys {
+            println!(
+                "  {:<22} {:<44} ledger {:>20}  chain {:>20}",
+                account.to_string(),
+                asset.to_string(),
+                balance(&ledger, account, asset),
+                balance(&chain.balances, account, asset)
+            );
+        }
+    }
+    for discrepancy in &discrepancies {
+        warn!(
+            event.name = "ledger_discrepancy",
+            ledger.authority = %owner,
+            ledger.account = %discrepancy.account,
+            ledger.asset = %discrepancy.asset,
+            ledger.balance = %discrepancy.ledger,
+            ledger.chain_balance = %discrepancy.chain,
+            ledger.difference = %discrepancy.difference(),
+        );
+    }
+
+    if !unbalanced.is_empty() || !discrepancies.is_empty() {
+        anyhow::bail!(
+            "ledger does not reconcile: {} unbalanced entries, {} accounts off by more than {}",
+            unbalanced.len(),
+            discrepancies.len(),
+            config.tolerance
+        );
+    }
+    if !json {
+        println!("  reconciled: every entry balances and every account matches the chain");
+    }
+    Ok(())
+}
+
+/// Reporting period and layout for `export`; dates are UTC and `to` is exclusive.
+struct ExportArgs {
+    from: i64,
+    to: i64,
+    format: TaxFormat,
+}
+
+impl ExportArgs {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let now = chrono::Utc::now();
+        let mut parsed = Self {
+            from: now
+                .date_naive()
+                .with_ordinal(1)
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map_or(0, |start| start.and_utc().timestamp()),
+            to: now.timestamp(),
+            format: TaxFormat::Koinly,
+        };
+        for pair in args.chunks(2) {
+            let [flag, value] = pair else {
+                anyhow::bail!("missing value for `{}`", pair[0]);
+            };
+            match flag.as_str() {
+                "--from" => parsed.from = parse_date(value)?,
+                "--to" => parsed.to = parse_date(value)?,
+                "--format" => parsed.format = value.parse()?,
+                other => anyhow::bail!("unknown export flag `{other}`"),
+            }
+        }
+        anyhow::ensure!(parsed.from < parsed.to, "--from must be before --to");
+        Ok(parsed)
+    }
+}
+
+fn parse_date(value: &str) -> anyhow::Result<i64> {
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|e| anyhow::anyhow!("invalid date `{value}`, expected YYYY-MM-DD: {e}"))?;
+    Ok(date
+        .and_hms_opt(0, 0, 0)
+        .map_or(0, |start| start.and_utc().timestamp()))
+}
+
+async fn export(
+    config: &Config,
+    program: &LedgerProgram,
+    store: &LedgerStore,
+    args: &ExportArgs,
+) -> anyhow::Result<()> {
+    let owner = config.authority;
+    let first_market = *config
+        .market_ids
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("no market configured"))?;
+    // Markets share their quote token, which gains are measured in.
+    let numeraire = Asset::Token(
+        fetch_market_state(program, first_market)
+            .await?
+            .market
+            .quote_mint,
+    );
+
+    // Gains need every acquisition before the period, so the whole history is replayed.
+    let entries = store.entries(&owner)?;
+    let disposals = realized_gains(&entries, numeraire);
+    let in_period = |timestamp: i64| timestamp >= args.from && timestamp < args.to;
+    let period_entries = entries
+        .iter()
+        .filter(|entry| in_period(entry.timestamp))
+        .cloned()
+        .collect::<Vec<_>>();
+    let period_disposals = disposals
+        .into_iter()
+        .filter(|disposal| in_period(disposal.timestamp))
+        .collect::<Vec<_>>();
+
+    let rpc = program.rpc();
+    let mut assets = BTreeMap::new();
+    assets.insert(
+        Asset::Sol,
+        AssetInfo {
+            symbol: "SOL".to_string(),
+            decimals: 9,
+        },
+    );
+    for entry in &entries {
+        for posting in &entry.postings {
+            let Asset::Token(mint) = posting.asset else {
+                continue;
+            };
+            if assets.contains_key(&posting.asset) {
+                continue;
+            }
+            let decimals = rpc.get_token_supply(&mint).await?.decimals;
+            let symbol = config
+                .asset_symbols
+                .get(&mint)
+                .cloned()
+                .unwrap_or_else(|| mint.to_string());
+            assets.insert(posting.asset, AssetInfo { symbol, decimals });
+        }
+    }
+
+    std::fs::create_dir_all(&config.export_dir)?;
+    let transactions_path = config.export_dir.join("transactions.csv");
+    let gains_path = config.export_dir.join("realized_gains.csv");
+    write_tax_csv(
+        std::fs::File::create(&transactions_path)?,
+        &tax_rows(&period_entries),
+        args.format,
+        &assets,
+    )?;
+    write_gains_csv(
+        std::fs::File::create(&gains_path)?,
+        &period_disposals,
+        &numeraire,
+        &assets,
+    )?;
+
+    let summary = summarize_period(&entries, &period_disposals, args.from, args.to);
+    let format_ts = |ts: i64| {
+        chrono::DateTime::from_timestamp(ts, 0)
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_else(|| ts.to_string())
+    };
+    let numeraire_info = &assets[&numeraire];
+    let numeraire_amount = |raw: i128| {
+        format!(
+            "{} {}",
+            format_amount(raw, numeraire_info.decimals),
+            numeraire_info.symbol
+        )
+    };
+    println!("Tax report for {}", owner);
+    println!(
+        "  period:            {} -> {}",
+        format_ts(args.from),
+        format_ts(args.to)
+    );
+    println!("  disposals:         {}", summary.disposals);
+    println!(
+        "  proceeds:          {}",
+        numeraire_amount(summary.proceeds)
+    );
+    println!(
+        "  cost basis:        {}",
+        numeraire_amount(summary.cost_basis)
+    );
+    println!(
+        "  realized gain:     {}",
+        numeraire_amount(summary.realized_gain)
+    );
+    for (label, totals) in [
+        ("fee expenses", &summary.fees),
+        ("inventory moved", &summary.inventory_transfers),
+    ] {
+        for (asset, amount) in totals {
+            let info = &assets[asset];
+            println!(
+                "  {:<18} {} {}",
+                format!("{label}:"),
+                format_amount(*amount, info.decimals),
+                info.symbol
+            );
+        }
+    }
+    println!("  transactions:      {}", transactions_path.display());
+    println!("  realized gains:    {}", gains_path.display());
+    Ok(())
+}
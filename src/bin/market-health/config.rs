@@ -0,0 +1,85 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use anchor_client::{Cluster, solana_sdk::commitment_config::CommitmentConfig};
+use twob_market_making::{
+    alerts::AlertConfig,
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+    health::HealthThresholds,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_ids: Vec<u64>,
+    /// Oracle price feed per market; markets without one skip the price check.
+    pub price_feed_urls: BTreeMap<u64, String>,
+    pub thresholds: HealthThresholds,
+    pub poll_interval: Duration,
+    pub alerts: AlertConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_ids = env_var("MARKET_HEALTH_IDS")
+            .or_else(|_| env_var("MARKET_ID"))
+            .unwrap_or_else(|_| "1".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if market_ids.is_empty() {
+            anyhow::bail!("MARKET_HEALTH_IDS must list at least one market");
+        }
+
+        // `<market id>=<url>` pairs, comma separated.
+        let mut price_feed_urls = BTreeMap::new();
+        for entry in env_var("MARKET_HEALTH_PRICE_FEEDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+        {
+            let (market_id, url) = entry.split_once('=').ok_or_else(|| {
+                anyhow::anyhow!("Invalid MARKET_HEALTH_PRICE_FEEDS entry `{entry}`")
+            })?;
+            price_feed_urls.insert(market_id.trim().parse::<u64>()?, url.trim().to_string());
+        }
+
+        let thresholds = HealthThresholds {
+            max_bookkeeping_age_slots: env_var("MARKET_HEALTH_MAX_BOOKKEEPING_AGE_SLOTS")
+                .unwrap_or_else(|_| "9000".to_string())
+                .parse::<u64>()?,
+            max_price_deviation_bps: env_var("MARKET_HEALTH_MAX_PRICE_DEVIATION_BPS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse::<u64>()?,
+        };
+
+        let poll_interval = Duration::from_secs(
+            env_var("MARKET_HEALTH_POLL_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse::<u64>()?,
+        );
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            market_ids,
+            price_feed_urls,
+            thresholds,
+            poll_interval,
+            alerts: AlertConfig::from_env()?,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
@@ -0,0 +1,194 @@
+mod config;
+
+use anchor_client::{Client, Program, solana_sdk::pubkey::Pubkey};
+use config::Config;
+use tokio::{signal, time::sleep};
+use tracing::{info, warn};
+use twob_market_making::{
+    ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    health::{MarketHealth, scan_market},
+    price::fetch_price,
+    program_payer, twob_anchor,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    // Scanning only reads accounts, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let http = reqwest::Client::new();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("scan") => match &args[1..] {
+            [] => scan(&program, &http, &config, false).await,
+            [flag] if flag == "--json" => scan(&program, &http, &config, true).await,
+            _ => anyhow::bail!("usage: market-health scan [--json]"),
+        },
+        None | Some("watch") => watch(&program, &http, &config).await,
+        Some(other) => {
+            anyhow::bail!("unknown command `{other}`; expected `scan [--json]` or `watch`")
+        }
+    }
+}
+
+/// One pass over the markets as a pre-flight check: prints each market's issues, or with
+/// `json` every [`MarketHealth`] as a JSON array, and fails if any market is unhealthy.
+async fn scan(
+    program: &Program<ProgramPayer>,
+    http: &reqwest::Client,
+    config: &Config,
+    json: bool,
+) -> anyhow::Result<()> {
+    let mut unhealthy = 0;
+    let mut scanned = Vec::new();
+    for &market_id in &config.market_ids {
+        let health = check_market(program, http, config, market_id).await?;
+        if !health.is_healthy() {
+            unhealthy += 1;
+        }
+        if json {
+            scanned.push(health);
+            continue;
+        }
+        let implied = health
+            .implied_price
+            .map(|price| format!("{price:.6}"))
+            .unwrap_or_else(|| "-".to_string());
+        let oracle = health
+            .oracle_price
+            .map(|price| format!("{price:.6}"))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "market {:<6} index {:<8} implied {:>14} oracle {:>14}  {}",
+            market_id,
+            health.reference_index,
+            implied,
+            oracle,
+            if health.is_healthy() {
+                "ok"
+            } else {
+                "UNHEALTHY"
+            }
+        );
+        for issue in &health.issues {
+            println!("    {issue}");
+        }
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&scanned)?);
+    }
+
+    if unhealthy > 0 {
+        anyhow::bail!(
+            "{} of {} markets are unhealthy",
+            unhealthy,
+            config.market_ids.len()
+        );
+    }
+    Ok(())
+}
+
+/// Keeper mode: scan every poll interval and alert on unhealthy markets.
+async fn watch(
+    program: &Program<ProgramPayer>,
+    http: &reqwest::Client,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let alerter = Alerter::from_config("market-health", &config.alerts)?;
+    info!(
+        event.name = "market_health_started",
+        market_health.markets = ?config.market_ids,
+        market_health.max_bookkeeping_age_slots = config.thresholds.max_bookkeeping_age_slots,
+        market_health.max_price_deviation_bps = config.thresholds.max_price_deviation_bps,
+    );
+
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "market_health_shutdown");
+                break;
+            }
+            _ = sleep(config.poll_interval) => {
+                for &market_id in &config.market_ids {
+                    match check_market(program, http, config, market_id).await {
+                        Ok(health) => report(&alerter, &health),
+                        Err(error) => {
+                            warn!(
+                                event.name = "market_health_scan_failed",
+                                market.id = market_id,
+                                ?error
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_market(
+    program: &Program<ProgramPayer>,
+    http: &reqwest::Client,
+    config: &Config,
+    market_id: u64,
+) -> anyhow::Result<MarketHealth> {
+    let oracle_price = match config.price_feed_urls.get(&market_id) {
+        Some(url) => match fetch_price(http, url).await {
+            Ok(price) => Some(price.price),
+            Err(error) => {
+                // A dead feed shouldn't hide the on-chain checks.
+                warn!(
+                    event.name = "market_health_oracle_failed",
+                    market.id = market_id,
+                    ?error
+                );
+                None
+            }
+        },
+        None => None,
+    };
+    scan_market(program, market_id, oracle_price, &config.thresholds).await
+}
+
+fn report(alerter: &Alerter, health: &MarketHealth) {
+    info!(
+        event.name = "market_health_scanned",
+        market.id = health.market_id,
+        market.reference_index = health.reference_index,
+        market_health.implied_price = ?health.implied_price,
+        market_health.oracle_price = ?health.oracle_price,
+        gauge.market_health_issues = health.issues.len() as u64,
+    );
+    if health.is_healthy() {
+        return;
+    }
+
+    let issues = health
+        .issues
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    warn!(
+        event.name = "market_unhealthy",
+        market.id = health.market_id,
+        market_health.issues = %issues,
+    );
+    alerter.notify(
+        AlertKind::MarketUnhealthy,
+        Some(health.market_id),
+        format!("market {} is unhealthy: {}", health.market_id, issues),
+    );
+}
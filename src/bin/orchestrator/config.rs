@@ -0,0 +1,211 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use serde::Deserialize;
+use twob_market_making::{
+    config::{self, env_var},
+    risk::RiskLimits,
+    supervisor::RestartPolicy,
+};
+
+pub struct Config {
+    pub instances: Vec<Instance>,
+    pub restart_policy: RestartPolicy,
+    /// Creating this file trips the global kill switch.
+    pub kill_switch_file: Option<PathBuf>,
+    /// How long the strategies get to stop after the kill switch trips before the
+    /// orchestrator exits from under them.
+    pub shutdown_grace: Duration,
+    /// Limits on every instance's positions together, in place of the instances' own.
+    pub risk_limits: RiskLimits,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Instance {
+    pub name: String,
+    /// The flow bot run, one of [`STRATEGIES`].
+    pub strategy: String,
+    /// Its command-line flags.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Settings for this instance alone, over the orchestrator's environment.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+/// The strategies the orchestrator can run.
+pub const STRATEGIES: [&str; 2] = ["oracle-flow", "inventory-flow"];
+
+#[derive(Deserialize)]
+struct InstancesFile {
+    instances: Vec<Instance>,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let instances_path = env_var("ORCHESTRATOR_CONFIG")
+            .map_err(|_| anyhow::anyhow!("ORCHESTRATOR_CONFIG env var not set"))?;
+        let text = std::fs::read_to_string(&instances_path)
+            .with_context(|| format!("Failed to read {}", instances_path))?;
+        let instances = if instances_path.ends_with(".toml") {
+            market_instances(&text, &instances_path)
+        } else {
+            parse_instances(&text)
+        }
+        .and_then(|instances| {
+            instances.iter().try_for_each(check_strategy)?;
+            Ok(instances)
+        })
+        .with_context(|| format!("Invalid orchestrator config {}", instances_path))?;
+
+        let defaults = RestartPolicy::default();
+        let restart_policy = RestartPolicy {
+            initial_backoff: Duration::from_secs(
+                env_var("ORCHESTRATOR_INITIAL_BACKOFF_SECS")
+                    .map(|value| value.parse::<u64>())
+                    .unwrap_or(Ok(defaults.initial_backoff.as_secs()))?,
+            ),
+            max_backoff: Duration::from_secs(
+                env_var("ORCHESTRATOR_MAX_BACKOFF_SECS")
+                    .map(|value| value.parse::<u64>())
+                    .unwrap_or(Ok(defaults.max_backoff.as_secs()))?,
+            ),
+            max_restarts: env_var("ORCHESTRATOR_MAX_RESTARTS")
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| value.parse::<u32>())
+                .transpose()?,
+            healthy_after: Duration::from_secs(
+                env_var("ORCHESTRATOR_HEALTHY_AFTER_SECS")
+                    .map(|value| value.parse::<u64>())
+                    .unwrap_or(Ok(defaults.healthy_after.as_secs()))?,
+            ),
+        };
+
+        let kill_switch_file = env_var("ORCHESTRATOR_KILL_SWITCH_FILE")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(PathBuf::from);
+
+        let shutdown_grace = Duration::from_secs(
+            env_var("ORCHESTRATOR_SHUTDOWN_GRACE_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse::<u64>()?,
+        );
+
+        Ok(Self {
+            instances,
+            restart_policy,
+            kill_switch_file,
+            shutdown_grace,
+            risk_limits: RiskLimits::from_env()?,
+        })
+    }
+}
+
+fn parse_instances(json: &str) -> anyhow::Result<Vec<Instance>> {
+    let file: InstancesFile = serde_json::from_str(json)?;
+    if file.instances.is_empty() {
+        anyhow::bail!("no strategy instances configured");
+    }
+    let mut names = std::collections::BTreeSet::new();
+    for instance in &file.instances {
+        if !names.insert(instance.name.as_str()) {
+            anyhow::bail!("duplicate instance name `{}`", instance.name);
+        }
+    }
+    Ok(file.instances)
+}
+
+fn check_strategy(instance: &Instance) -> anyhow::Result<()> {
+    if !STRATEGIES.contains(&instance.strategy.as_str()) {
+        anyhow::bail!(
+            "instance `{}` runs unknown strategy `{}`; expected one of {}",
+            instance.name,
+            instance.strategy,
+            STRATEGIES.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// One instance per `[market.<name>]` section of the flow bots' config file at `path`,
+/// each pointed at its section.
+fn market_instances(text: &str, path: &str) -> anyhow::Result<Vec<Instance>> {
+    let markets = config::markets(text)?;
+    if markets.is_empty() {
+        anyhow::bail!("no [market.<name>] sections configured");
+    }
+    Ok(markets
+        .into_values()
+        .map(|market| Instance {
+            env: BTreeMap::from([
+                ("CONFIG_FILE".to_string(), path.to_string()),
+                ("MARKET".to_string(), market.name.clone()),
+            ]),
+            name: market.name,
+            strategy: market.strategy,
+            args: Vec::new(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_instances_and_rejects_duplicates() {
+        let instances = parse_instances(
+            r#"{"instances": [
+                {"name": "sol-usdc", "strategy": "oracle-flow", "env": {"MARKET_ID": "1"}},
+                {"name": "bonk-usdc", "strategy": "inventory-flow"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].env["MARKET_ID"], "1");
+        assert!(instances[1].args.is_empty());
+        assert!(check_strategy(&instances[0]).is_ok());
+        assert!(
+            check_strategy(&Instance {
+                strategy: "hedger".to_string(),
+                ..instances[1].clone()
+            })
+            .is_err()
+        );
+
+        assert!(
+            parse_instances(
+                r#"{"instances": [
+                    {"name": "a", "strategy": "oracle-flow"},
+                    {"name": "a", "strategy": "inventory-flow"}
+                ]}"#,
+            )
+            .is_err()
+        );
+        assert!(parse_instances(r#"{"instances": []}"#).is_err());
+    }
+
+    #[test]
+    fn runs_one_instance_per_market_section() {
+        let instances = market_instances(
+            r#"
+                [market.sol-usdc]
+                strategy = "oracle-flow"
+                market_id = 1
+
+                [market.bonk-usdc]
+                strategy = "inventory-flow"
+                market_id = 4
+            "#,
+            "/etc/twob/markets.toml",
+        )
+        .unwrap();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].name, "bonk-usdc");
+        assert_eq!(instances[0].strategy, "inventory-flow");
+        assert_eq!(instances[0].env["MARKET"], "bonk-usdc");
+        assert_eq!(instances[0].env["CONFIG_FILE"], "/etc/twob/markets.toml");
+    }
+}
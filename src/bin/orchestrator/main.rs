@@ -0,0 +1,93 @@
+mod config;
+mod worker;
+
+use std::{path::PathBuf, time::Duration};
+
+use config::Config;
+use tokio::{
+    signal,
+    time::{sleep, timeout},
+};
+use tracing::{info, warn};
+use twob_market_making::{
+    risk::RiskEngine,
+    supervisor::{KillSwitch, Supervisor},
+};
+use worker::run_instance;
+
+const KILL_SWITCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn main() -> anyhow::Result<()> {
+    // Loaded before the runtime starts its threads, as it sets environment variables.
+    dotenv::dotenv().ok();
+    tokio::runtime::Runtime::new()?.block_on(run())
+}
+
+async fn run() -> anyhow::Result<()> {
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let mut supervisor = Supervisor::new(config.restart_policy);
+    let kill_switch = supervisor.kill_switch();
+
+    info!(
+        event.name = "orchestrator_started",
+        orchestrator.instances = config.instances.len(),
+        orchestrator.kill_switch_file = ?config.kill_switch_file,
+    );
+
+    // One engine for every instance, so their exposure is totalled against one set of
+    // limits and a breach on one market pulls the quotes on all of them.
+    let risk = RiskEngine::new(config.risk_limits);
+    for instance in config.instances {
+        let name = instance.name.clone();
+        let risk = risk.clone();
+        supervisor.spawn(name, move |kill| {
+            run_instance(instance.clone(), kill, risk.clone())
+        });
+    }
+
+    tokio::spawn({
+        let kill_switch = kill_switch.clone();
+        async move {
+            if signal::ctrl_c().await.is_ok() {
+                info!(event.name = "orchestrator_shutdown", reason = "ctrl_c");
+                kill_switch.trigger();
+            }
+        }
+    });
+    if let Some(path) = config.kill_switch_file {
+        tokio::spawn(watch_kill_switch_file(path, kill_switch.clone()));
+    }
+
+    // Once the switch trips the instances get the grace period to wind down; whatever is
+    // still running after it is dropped with the runtime.
+    let join = supervisor.join();
+    tokio::pin!(join);
+    let tripped = tokio::select! {
+        _ = &mut join => false,
+        _ = kill_switch.triggered() => true,
+    };
+    if tripped && timeout(config.shutdown_grace, join).await.is_err() {
+        warn!(
+            event.name = "orchestrator_shutdown_timed_out",
+            grace_secs = config.shutdown_grace.as_secs(),
+        );
+    }
+    info!(event.name = "orchestrator_stopped");
+    Ok(())
+}
+
+async fn watch_kill_switch_file(path: PathBuf, kill_switch: KillSwitch) {
+    loop {
+        if path.exists() {
+            warn!(
+                event.name = "orchestrator_kill_switch_tripped",
+                kill_switch.file = %path.display(),
+            );
+            kill_switch.trigger();
+            return;
+        }
+        sleep(KILL_SWITCH_POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use clap::Parser;
+use tracing::{Instrument, info, info_span};
+use twob_market_making::{
+    bots::{inventory_flow, oracle_flow},
+    config::{FlowArgs, scoped},
+    risk::RiskEngine,
+    supervisor::{KillSwitch, Launch},
+};
+
+use crate::config::Instance;
+
+/// The flags an instance's `args` give its strategy.
+#[derive(Debug, Parser)]
+struct InstanceArgs {
+    #[command(flatten)]
+    flow: FlowArgs,
+}
+
+/// Run one strategy instance to completion as a task of this process, with the
+/// orchestrator's environment under the instance's `env` as its settings. It stops when
+/// the kill switch trips; an error or a panic has the supervisor restart it. Its positions
+/// are held to `risk` together with every other instance's.
+pub async fn run_instance(
+    instance: Instance,
+    kill: KillSwitch,
+    risk: Arc<RiskEngine>,
+) -> anyhow::Result<()> {
+    let args = InstanceArgs::try_parse_from(
+        std::iter::once(instance.strategy.as_str()).chain(instance.args.iter().map(String::as_str)),
+    )
+    .with_context(|| format!("Invalid args for {}", instance.name))?;
+    let span = info_span!(
+        "orchestrator.instance",
+        instance.name = %instance.name,
+        instance.strategy = %instance.strategy,
+    );
+    let strategy = instance.strategy;
+    let run = async move {
+        info!(event.name = "orchestrator_instance_started");
+        let result = match strategy.as_str() {
+            "oracle-flow" => oracle_flow::run(args.flow, kill, Launch::Task, Some(risk)).await,
+            "inventory-flow" => {
+                inventory_flow::run(args.flow, kill, Launch::Task, Some(risk)).await
+            }
+            other => anyhow::bail!("unknown strategy `{other}`"),
+        };
+        info!(event.name = "orchestrator_instance_stopped");
+        result
+    };
+    scoped(instance.env, run).instrument(span).await
+}
@@ -0,0 +1,130 @@
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+use twob_market_making::{
+    alerts::AlertConfig,
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+    telemetry::parse_bool,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    pub authority: Pubkey,
+    pub price_feed_url: String,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+    pub db_path: String,
+    pub snapshot_interval_secs: u64,
+    /// Most of the authority's signatures fetched per cycle when recording fees.
+    pub fee_signature_limit: usize,
+    /// Whether the market's base token is SOL, so fees can be valued at its mark price.
+    pub base_is_sol: bool,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+    /// UTC hour after which `run` reports on the last 24 hours, once a day; `None` turns
+    /// the report off.
+    pub daily_report_hour: Option<u32>,
+    /// Also send the daily report to the alert sinks, not just the log.
+    pub daily_report_alert: bool,
+    pub alerts: AlertConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let authority = env_var("PNL_TRACKER_AUTHORITY")
+            .map_err(|_| anyhow::anyhow!("PNL_TRACKER_AUTHORITY env var not set"))?
+            .parse::<Pubkey>()
+            .map_err(|e| anyhow::anyhow!("Invalid PNL_TRACKER_AUTHORITY: {}", e))?;
+
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_id = env_var("MARKET_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u64>()?;
+
+        let price_feed_url = env_var("PRICE_FEED_URL").unwrap_or_else(|_| {
+            let base_url = env_var("PRICE_FEED_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:8080/api/v1/price".to_string());
+            let base_token = env_var("BASE_TOKEN").unwrap_or_else(|_| "SOL".to_string());
+            let quote_token = env_var("QUOTE_TOKEN").unwrap_or_else(|_| "USDC".to_string());
+
+            format!(
+                "{}/{}/{}",
+                base_url.trim_end_matches('/'),
+                base_token.trim(),
+                quote_token.trim(),
+            )
+        });
+
+        let base_token_decimals = env_var("BASE_TOKEN_DECIMALS")
+            .unwrap_or_else(|_| "9".to_string())
+            .parse::<u8>()?;
+
+        let quote_token_decimals = env_var("QUOTE_TOKEN_DECIMALS")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse::<u8>()?;
+
+        let db_path = env_var("PNL_DB_PATH").unwrap_or_else(|_| "pnl.sqlite".to_string());
+
+        let snapshot_interval_secs = env_var("PNL_SNAPSHOT_INTERVAL_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()?;
+
+        let fee_signature_limit = env_var("PNL_FEE_SIGNATURE_LIMIT")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse::<usize>()?;
+
+        let base_is_sol = env_var("BASE_TOKEN")
+            .unwrap_or_else(|_| "SOL".to_string())
+            .trim()
+            .eq_ignore_ascii_case("SOL");
+
+        let daily_report_hour = match env_var("PNL_DAILY_REPORT_HOUR_UTC") {
+            Ok(value) if !value.trim().is_empty() => {
+                let hour = value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|e| anyhow::anyhow!("Invalid PNL_DAILY_REPORT_HOUR_UTC: {}", e))?;
+                anyhow::ensure!(
+                    hour < 24,
+                    "PNL_DAILY_REPORT_HOUR_UTC must be 0-23, got {hour}"
+                );
+                Some(hour)
+            }
+            _ => None,
+        };
+
+        let daily_report_alert = match env_var("PNL_DAILY_REPORT_ALERT") {
+            Ok(value) if !value.trim().is_empty() => parse_bool(&value)
+                .map_err(|e| anyhow::anyhow!("Invalid PNL_DAILY_REPORT_ALERT: {}", e))?,
+            _ => false,
+        };
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            market_id,
+            authority,
+            price_feed_url,
+            base_token_decimals,
+            quote_token_decimals,
+            db_path,
+            snapshot_interval_secs,
+            fee_signature_limit,
+            base_is_sol,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+            daily_report_hour,
+            daily_report_alert,
+            alerts: AlertConfig::from_env()?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
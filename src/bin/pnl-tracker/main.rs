@@ -0,0 +1,476 @@
+mod config;
+mod pnl;
+mod store;
+
+use std::time::Duration;
+
+use anchor_client::{Client, solana_sdk::pubkey::Pubkey};
+use chrono::Timelike;
+use config::Config;
+use pnl::{DailyReport, Snapshot, summarize, total_expenses_sol, turnover_quote};
+use store::{Expense, Store};
+use tokio::{signal, time::sleep};
+use tracing::{error, info, warn};
+use twob_market_making::{
+    ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    decode::TwobInstruction,
+    fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances,
+    ingest::{IndexedTransaction, fetch_signatures, fetch_transaction},
+    price::{fetch_price, ui_price},
+    program_payer, slots_until_debt, twob_anchor,
+};
+
+type TrackerProgram = anchor_client::Program<ProgramPayer>;
+
+const DEFAULT_REPORT_HOURS: i64 = 24;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let store = Store::open(&config.db_path)?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None | Some("run") => run(&config, &store).await,
+        Some("report") => report(&config, &store, &ReportArgs::parse(&args[1..])?),
+        Some(other) => {
+            anyhow::bail!(
+                "unknown command `{other}`; expected `run` or `report [--hours N] [--json]`"
+            )
+        }
+    }
+}
+
+async fn run(config: &Config, store: &Store) -> anyhow::Result<()> {
+    // The tracker only reads state, so a placeholder payer is enough for the client.
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(Pubkey::default()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let http_client = reqwest::Client::new();
+    let interval = Duration::from_secs(config.snapshot_interval_secs);
+    let alerter = Alerter::from_config("pnl-tracker", &config.alerts)?;
+
+    info!(
+        event.name = "pnl_tracker_started",
+        market.id = config.market_id,
+        lp.authority = %config.authority,
+        pnl.db_path = %config.db_path,
+        pnl.snapshot_interval_secs = config.snapshot_interval_secs,
+    );
+
+    loop {
+        match take_snapshot(&program, &http_client, config).await {
+            Ok(snapshot) => {
+                store.insert_snapshot(&snapshot)?;
+                log_running_pnl(config, store, &snapshot)?;
+            }
+            Err(error) => error!(event.name = "pnl_snapshot_failed", ?error),
+        }
+        if let Err(error) = record_expenses(&program, config, store).await {
+            warn!(event.name = "pnl_expenses_failed", ?error);
+        }
+        if let Err(error) = send_daily_report(config, store, &alerter) {
+            warn!(event.name = "pnl_daily_report_failed", ?error);
+        }
+
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "pnl_tracker_shutdown");
+                return Ok(());
+            }
+            _ = sleep(interval) => {}
+        }
+    }
+}
+
+async fn take_snapshot(
+    program: &TrackerProgram,
+    http_client: &reqwest::Client,
+    config: &Config,
+) -> anyhow::Result<Snapshot> {
+    let market_state = fetch_market_state(program, config.market_id).await?;
+    let position = fetch_liquidity_position(program, config.market_id, &config.authority).await?;
+    let balances = get_liquidity_position_balances(
+        program,
+        position,
+        market_state.bookkeeping,
+        market_state.market,
+        market_state.current_slot,
+    )
+    .await?;
+
+    let oracle_price = match fetch_price(http_client, &config.price_feed_url).await {
+        Ok(price_data) => Some(price_data.price),
+        Err(error) => {
+            warn!(
+                event.name = "pnl_oracle_price_unavailable",
+                price.feed_url = %config.price_feed_url,
+                ?error,
+            );
+            None
+        }
+    };
+
+    Ok(Snapshot {
+        timestamp: chrono::Utc::now().timestamp(),
+        slot: market_state.current_slot,
+        market_id: config.market_id,
+        authority: config.authority.to_string(),
+        base_balance: balances.base_balance,
+        quote_balance: balances.quote_balance,
+        base_debt: balances.base_debt,
+        quote_debt: balances.quote_debt,
+        base_flow: position.base_flow_u64,
+        quote_flow: position.quote_flow_u64,
+        inventory_price: ui_price(
+            u128::from(balances.base_balance),
+            u128::from(balances.quote_balance),
+            config.base_token_decimals,
+            config.quote_token_decimals,
+        ),
+        market_price: ui_price(
+            market_state.market.base_flow,
+            market_state.market.quote_flow,
+            config.base_token_decimals,
+            config.quote_token_decimals,
+        ),
+        oracle_price,
+        runway_slots: slots_until_debt(&position, &market_state.market, &balances),
+    })
+}
+
+/// Record the cost of every transaction the authority paid for since the last scan.
+async fn record_expenses(
+    program: &TrackerProgram,
+    config: &Config,
+    store: &Store,
+) -> anyhow::Result<()> {
+    let rpc = program.rpc();
+    let authority = config.authority.to_string();
+    let until = store
+        .expense_cursor(&authority)?
+        .map(|signature| signature.parse())
+        .transpose()?;
+    let (signatures, truncated) =
+        fetch_signatures(&rpc, &config.authority, until, config.fee_signature_limit).await?;
+    if truncated {
+        warn!(
+            event.name = "pnl_expenses_truncated",
+            lp.authority = %authority,
+            pnl.fee_signature_limit = config.fee_signature_limit,
+        );
+    }
+
+    let mut recorded = 0_u64;
+    let mut lamports = 0_i64;
+    // Oldest first, so the cursor only moves past transactions that were recorded.
+    for status in signatures.iter().rev() {
+        let transaction = fetch_transaction(&rpc, &status.signature.parse()?).await?;
+        if transaction.fee_payer == config.authority {
+            let (requotes, stops) = count_flow_updates(&transaction);
+            let expense = Expense {
+                signature: transaction.signature.clone(),
+                authority: authority.clone(),
+                slot: transaction.slot,
+                timestamp: transaction
+                    .block_time
+                    .unwrap_or_else(|| chrono::Utc::now().timestamp()),
+                success: transaction.success,
+                fee_lamports: transaction.cost.fee,
+                priority_fee_lamports: transaction.cost.priority_fee,
+                rent_lamports: transaction.cost.rent,
+                requotes,
+                stops,
+            };
+            store.insert_expense(&expense)?;
+            recorded += 1;
+            lamports += transaction.cost.fee as i64 + transaction.cost.rent;
+        }
+        store.set_expense_cursor(&authority, &status.signature)?;
+    }
+
+    if recorded > 0 {
+        info!(
+            event.name = "pnl_expenses_recorded",
+            lp.authority = %authority,
+            pnl.transactions = recorded,
+            pnl.expense_lamports = lamports,
+            monotonic_counter.pnl_expense_lamports_total = lamports.max(0) as u64,
+        );
+    }
+    Ok(())
+}
+
+/// Flow updates in `transaction` that requote and that stop a position.
+fn count_flow_updates(transaction: &IndexedTransaction) -> (u32, u32) {
+    transaction
+        .instructions
+        .iter()
+        .fold((0, 0), |(requotes, stops), ix| match ix.instruction {
+            TwobInstruction::UpdateLiquidityFlows {
+                base_flow_u64: 0,
+                quote_flow_u64: 0,
+                ..
+            }
+            | TwobInstruction::PublicStopLiquidityPosition { .. } => (requotes, stops + 1),
+            TwobInstruction::UpdateLiquidityFlows { .. } => (requotes + 1, stops),
+            _ => (requotes, stops),
+        })
+}
+
+/// Report on the last 24 hours once the configured hour has passed, once per UTC day.
+fn send_daily_report(config: &Config, store: &Store, alerter: &Alerter) -> anyhow::Result<()> {
+    let Some(hour) = config.daily_report_hour else {
+        return Ok(());
+    };
+    let now = chrono::Utc::now();
+    let authority = config.authority.to_string();
+    if now.hour() < hour
+        || !store.claim_daily_report(
+            config.market_id,
+            &authority,
+            now.timestamp().div_euclid(86_400),
+        )?
+    {
+        return Ok(());
+    }
+
+    let since = now.timestamp() - DEFAULT_REPORT_HOURS * 3_600;
+    let report = DailyReport::new(
+        config.market_id,
+        &store.snapshots_since(config.market_id, &authority, since)?,
+        &store.daily_expenses(&authority, since)?,
+        config.base_token_decimals,
+        config.quote_token_decimals,
+    );
+    info!(
+        event.name = "pnl_daily_report",
+        market.id = report.market_id,
+        lp.authority = %authority,
+        pnl.total_quote = report.pnl.as_ref().map(|summary| summary.pnl),
+        pnl.vs_hold_quote = report.pnl.as_ref().map(|summary| summary.pnl_vs_hold),
+        pnl.max_drawdown_quote = report.pnl.as_ref().map(|summary| summary.max_drawdown),
+        pnl.turnover_quote = report.turnover_quote,
+        pnl.transactions = report.expenses.transactions,
+        pnl.expense_lamports = report.expenses.total_lamports(),
+        pnl.requotes = report.expenses.requotes,
+        pnl.stops = report.expenses.stops,
+        pnl.worst_runway_slots = report.worst_runway_slots,
+        "{}",
+        report.text()
+    );
+    if config.daily_report_alert {
+        alerter.notify(
+            AlertKind::DailyReport,
+            Some(report.market_id),
+            report.text(),
+        );
+    }
+    Ok(())
+}
+
+fn log_running_pnl(config: &Config, store: &Store, latest: &Snapshot) -> anyhow::Result<()> {
+    // Only the ends of the history count towards the running PnL, marked alike.
+    let Some(source) = latest.mark_source() else {
+        return Ok(());
+    };
+    let Some(first) = store.first_snapshot(config.market_id, &latest.authority, source)? else {
+        return Ok(());
+    };
+    let Some(summary) = summarize(
+        &[first, latest.clone()],
+        config.base_token_decimals,
+        config.quote_token_decimals,
+    ) else {
+        return Ok(());
+    };
+
+    info!(
+        event.name = "pnl_snapshot_recorded",
+        market.id = config.market_id,
+        lp.authority = %latest.authority,
+        slot.current = latest.slot,
+        position.base_balance.raw = latest.base_balance,
+        position.quote_balance.raw = latest.quote_balance,
+        price.oracle = ?latest.oracle_price,
+        price.market = ?latest.market_price,
+        price.inventory = ?latest.inventory_price,
+        pnl.value_quote = summary.end_value,
+        pnl.total_quote = summary.pnl,
+        pnl.vs_hold_quote = summary.pnl_vs_hold,
+        gauge.pnl_value_quote = summary.end_value,
+        gauge.pnl_total_quote = summary.pnl,
+    );
+    Ok(())
+}
+
+fn report(config: &Config, store: &Store, args: &ReportArgs) -> anyhow::Result<()> {
+    let hours = args.hours;
+    let since = chrono::Utc::now().timestamp() - hours * 3_600;
+    let authority = config.authority.to_string();
+    let history = store.snapshots_since(config.market_id, &authority, since)?;
+
+    let summary = summarize(
+        &history,
+        config.base_token_decimals,
+        config.quote_token_decimals,
+    );
+    let expenses = store.daily_expenses(&authority, since)?;
+    let expenses_sol = total_expenses_sol(&expenses);
+    let turnover = turnover_quote(
+        &history,
+        config.base_token_decimals,
+        config.quote_token_decimals,
+    );
+    let worst_runway = history
+        .iter()
+        .filter_map(|snapshot| snapshot.runway_slots)
+        .min();
+    // Fees are paid in the base token, so they're valued at the same end price.
+    let expenses_quote = summary
+        .as_ref()
+        .filter(|_| config.base_is_sol)
+        .map(|summary| expenses_sol * summary.end_price);
+
+    if args.json {
+        let report = serde_json::json!({
+            "market_id": config.market_id,
+            "authority": authority,
+            "hours": hours,
+            "summary": summary,
+            "turnover": turnover,
+            "worst_runway_slots": worst_runway,
+            "expenses_sol": expenses_sol,
+            "expenses": expenses,
+            "expenses_quote": expenses_quote,
+            "pnl_net_fees": summary
+                .as_ref()
+                .zip(expenses_quote)
+                .map(|(summary, expenses)| summary.pnl - expenses),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let Some(summary) = summary else {
+        println!(
+            "No priced snapshots for market {} / {} in the last {}h",
+            config.market_id, authority, hours
+        );
+        return Ok(());
+    };
+
+    let format_ts = |ts: i64| {
+        chrono::DateTime::from_timestamp(ts, 0)
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_else(|| ts.to_string())
+    };
+
+    println!("PnL report for market {} / {}", config.market_id, authority);
+    println!(
+        "  period:        {} -> {} ({} snapshots)",
+        format_ts(summary.start_timestamp),
+        format_ts(summary.end_timestamp),
+        summary.snapshots
+    );
+    println!("  start value:   {:.6}", summary.start_value);
+    println!("  end value:     {:.6}", summary.end_value);
+    println!("  pnl:           {:.6}", summary.pnl);
+    println!("  hold value:    {:.6}", summary.hold_value);
+    println!("  pnl vs hold:   {:.6}", summary.pnl_vs_hold);
+    println!("  max drawdown:  {:.6}", summary.max_drawdown);
+    println!("  debt snapshots: {}", summary.debt_snapshots);
+    println!("  turnover:      {:.6}", turnover);
+    match worst_runway {
+        Some(slots) => println!("  worst runway:  {} slots", slots),
+        None => println!("  worst runway:  n/a"),
+    }
+
+    println!("  sol expenses:  {:.9} SOL", expenses_sol);
+    for day in &expenses {
+        println!(
+            "    {}  {} txs  {} requotes  {} stops  fees {}  priority {}  rent {} lamports",
+            chrono::DateTime::from_timestamp(day.day * 86_400, 0)
+                .map(|dt| dt.date_naive().to_string())
+                .unwrap_or_else(|| day.day.to_string()),
+            day.transactions,
+            day.requotes,
+            day.stops,
+            day.fee_lamports,
+            day.priority_fee_lamports,
+            day.rent_lamports
+        );
+    }
+    if let Some(expenses_quote) = expenses_quote {
+        println!("  expenses:      {:.6}", expenses_quote);
+        println!("  pnl net fees:  {:.6}", summary.pnl - expenses_quote);
+    }
+
+    Ok(())
+}
+
+/// `report [--hours N] [--json]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ReportArgs {
+    hours: i64,
+    /// Print the report as one JSON object instead of text.
+    json: bool,
+}
+
+impl ReportArgs {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut parsed = Self {
+            hours: DEFAULT_REPORT_HOURS,
+            json: false,
+        };
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--hours" => {
+                    let value = args.next().map(String::as_str).unwrap_or_default();
+                    parsed.hours = value
+                        .parse::<i64>()
+                        .map_err(|e| anyhow::anyhow!("invalid --hours value `{value}`: {e}"))?;
+                }
+                "--json" => parsed.json = true,
+                _ => anyhow::bail!("usage: pnl-tracker report [--hours N] [--json]"),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_args_take_hours_and_json_in_any_order() {
+        let args = |args: &[&str]| {
+            ReportArgs::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            args(&[]).unwrap(),
+            ReportArgs {
+                hours: DEFAULT_REPORT_HOURS,
+                json: false
+            }
+        );
+        assert_eq!(
+            args(&["--json", "--hours", "6"]).unwrap(),
+            ReportArgs {
+                hours: 6,
+                json: true
+            }
+        );
+        assert!(args(&["--hours"]).is_err());
+        assert!(args(&["--csv"]).is_err());
+    }
+}
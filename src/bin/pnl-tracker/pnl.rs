@@ -0,0 +1,417 @@
+use serde::Serialize;
+
+/// A single point-in-time record of a liquidity position and the prices around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub timestamp: i64,
+    pub slot: u64,
+    pub market_id: u64,
+    pub authority: String,
+    pub base_balance: u64,
+    pub quote_balance: u64,
+    pub base_debt: u64,
+    pub quote_debt: u64,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+    /// Quote per base implied by the position's own inventory.
+    pub inventory_price: Option<f64>,
+    /// Quote per base implied by the aggregate market flows.
+    pub market_price: Option<f64>,
+    pub oracle_price: Option<f64>,
+    /// Slots until the position would start accruing debt at the market's flows then.
+    pub runway_slots: Option<u64>,
+}
+
+/// Where a mark price comes from. Marks from different sources don't compare, so a PnL
+/// marks its start and end with the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceSource {
+    Oracle,
+    Market,
+}
+
+impl PriceSource {
+    pub fn price(self, snapshot: &Snapshot) -> Option<f64> {
+        match self {
+            Self::Oracle => snapshot.oracle_price,
+            Self::Market => snapshot.market_price,
+        }
+    }
+}
+
+impl Snapshot {
+    /// Source of [`Self::mark_price`]: the oracle when available, else the market flows.
+    pub fn mark_source(&self) -> Option<PriceSource> {
+        if self.oracle_price.is_some() {
+            Some(PriceSource::Oracle)
+        } else if self.market_price.is_some() {
+            Some(PriceSource::Market)
+        } else {
+            None
+        }
+    }
+
+    /// Price used to mark the position.
+    pub fn mark_price(&self) -> Option<f64> {
+        self.mark_source()?.price(self)
+    }
+
+    /// Net position value in quote UI units at an arbitrary price.
+    pub fn value_quote_at(
+        &self,
+        price: f64,
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+    ) -> f64 {
+        let base = signed_ui(self.base_balance, self.base_debt, base_token_decimals);
+        let quote = signed_ui(self.quote_balance, self.quote_debt, quote_token_decimals);
+        base.mul_add(price, quote)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PnlSummary {
+    pub snapshots: usize,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub start_value: f64,
+    pub end_value: f64,
+    /// Mark price of the last priced snapshot.
+    pub end_price: f64,
+    pub pnl: f64,
+    /// Value of the starting inventory had it been held untouched until the end price.
+    pub hold_value: f64,
+    pub pnl_vs_hold: f64,
+    pub max_drawdown: f64,
+    pub debt_snapshots: usize,
+}
+
+/// Summarize PnL over an ordered series of snapshots.
+///
+/// PnL is measured against the first snapshot, so deposits and withdrawals made in between
+/// show up as PnL. Every snapshot is marked from the source of the last one's mark price;
+/// snapshots without a price from it are skipped.
+pub fn summarize(
+    snapshots: &[Snapshot],
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Option<PnlSummary> {
+    let source = snapshots.iter().rev().find_map(Snapshot::mark_source)?;
+    let priced: Vec<(&Snapshot, f64, f64)> = snapshots
+        .iter()
+        .filter_map(|snapshot| {
+            let price = source.price(snapshot)?;
+            let value = snapshot.value_quote_at(price, base_token_decimals, quote_token_decimals);
+            Some((snapshot, price, value))
+        })
+        .collect();
+
+    let (first, _, start_value) = *priced.first()?;
+    let (last, end_price, end_value) = *priced.last()?;
+
+    let mut peak = f64::MIN;
+    let mut max_drawdown = 0.0_f64;
+    for (_, _, value) in &priced {
+        peak = peak.max(*value);
+        max_drawdown = max_drawdown.max(peak - value);
+    }
+
+    let hold_value = first.value_quote_at(end_price, base_token_decimals, quote_token_decimals);
+
+    Some(PnlSummary {
+        snapshots: priced.len(),
+        start_timestamp: first.timestamp,
+        end_timestamp: last.timestamp,
+        start_value,
+        end_value,
+        end_price,
+        pnl: end_value - start_value,
+        hold_value,
+        pnl_vs_hold: end_value - hold_value,
+        max_drawdown,
+        debt_snapshots: priced
+            .iter()
+            .filter(|(snapshot, _, _)| snapshot.base_debt > 0 || snapshot.quote_debt > 0)
+            .count(),
+    })
+}
+
+/// Quote value of what the position's flows sold between consecutive snapshots, each
+/// interval valued at its closing mark price. Stands in for the volume the position made,
+/// which the chain doesn't record per fill.
+pub fn turnover_quote(
+    snapshots: &[Snapshot],
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> f64 {
+    snapshots
+        .windows(2)
+        .filter_map(|pair| {
+            let (start, end) = (&pair[0], &pair[1]);
+            let price = end.mark_price().or_else(|| start.mark_price())?;
+            let slots = end.slot.saturating_sub(start.slot) as f64;
+            let base = slots * start.base_flow as f64 / 10f64.powi(i32::from(base_token_decimals));
+            let quote =
+                slots * start.quote_flow as f64 / 10f64.powi(i32::from(quote_token_decimals));
+            Some(base.mul_add(price, quote))
+        })
+        .sum()
+}
+
+/// SOL a position's authority spent on its own transactions during one UTC day.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DailyExpenses {
+    /// Days since the Unix epoch.
+    pub day: i64,
+    pub transactions: u64,
+    /// Total fees in lamports, priority fees included.
+    pub fee_lamports: u64,
+    pub priority_fee_lamports: u64,
+    pub rent_lamports: i64,
+    /// Successful flow updates to non-zero flows.
+    pub requotes: u64,
+    /// Successful flow updates to zero and public stops.
+    pub stops: u64,
+}
+
+impl DailyExpenses {
+    pub fn total_lamports(&self) -> i64 {
+        self.fee_lamports as i64 + self.rent_lamports
+    }
+}
+
+/// A position over a period, as logged and sent by the scheduled daily report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyReport {
+    pub market_id: u64,
+    /// `None` when no snapshot in the period had a price.
+    pub pnl: Option<PnlSummary>,
+    pub turnover_quote: f64,
+    /// The authority's transactions across every market it trades.
+    pub expenses: DailyExpenses,
+    /// Lowest runway any snapshot saw; `None` when the position never drained a side.
+    pub worst_runway_slots: Option<u64>,
+}
+
+impl DailyReport {
+    pub fn new(
+        market_id: u64,
+        snapshots: &[Snapshot],
+        expenses: &[DailyExpenses],
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+    ) -> Self {
+        let total = expenses
+            .iter()
+            .fold(DailyExpenses::default(), |total, day| DailyExpenses {
+                day: total.day.max(day.day),
+                transactions: total.transactions + day.transactions,
+                fee_lamports: total.fee_lamports + day.fee_lamports,
+                priority_fee_lamports: total.priority_fee_lamports + day.priority_fee_lamports,
+                rent_lamports: total.rent_lamports + day.rent_lamports,
+                requotes: total.requotes + day.requotes,
+                stops: total.stops + day.stops,
+            });
+        Self {
+            market_id,
+            pnl: summarize(snapshots, base_token_decimals, quote_token_decimals),
+            turnover_quote: turnover_quote(snapshots, base_token_decimals, quote_token_decimals),
+            expenses: total,
+            worst_runway_slots: snapshots
+                .iter()
+                .filter_map(|snapshot| snapshot.runway_slots)
+                .min(),
+        }
+    }
+
+    /// One line for the alert sinks.
+    pub fn text(&self) -> String {
+        let pnl = match &self.pnl {
+            Some(summary) => format!(
+                "pnl {:.2} (vs hold {:.2}, max drawdown {:.2})",
+                summary.pnl, summary.pnl_vs_hold, summary.max_drawdown
+            ),
+            None => "pnl n/a (no priced snapshots)".to_string(),
+        };
+        let runway = match self.worst_runway_slots {
+            Some(slots) => format!("{} slots", slots),
+            None => "n/a".to_string(),
+        };
+        format!(
+            "{}, turnover {:.2}, fees {:.6} SOL over {} txs, {} requotes, {} stops, \
+             worst runway {}",
+            pnl,
+            self.turnover_quote,
+            self.expenses.total_lamports() as f64 / LAMPORTS_PER_SOL,
+            self.expenses.transactions,
+            self.expenses.requotes,
+            self.expenses.stops,
+            runway
+        )
+    }
+}
+
+/// Total lamports spent over `days`, in SOL.
+pub fn total_expenses_sol(days: &[DailyExpenses]) -> f64 {
+    days.iter().map(DailyExpenses::total_lamports).sum::<i64>() as f64 / LAMPORTS_PER_SOL
+}
+
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+fn signed_ui(balance: u64, debt: u64, decimals: u8) -> f64 {
+    (balance as f64 - debt as f64) / 10f64.powi(i32::from(decimals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(timestamp: i64, base_balance: u64, quote_balance: u64, price: f64) -> Snapshot {
+        Snapshot {
+            timestamp,
+            slot: timestamp as u64,
+            market_id: 1,
+            authority: "lp".to_string(),
+            base_balance,
+            quote_balance,
+            base_debt: 0,
+            quote_debt: 0,
+            base_flow: 0,
+            quote_flow: 0,
+            inventory_price: None,
+            market_price: None,
+            oracle_price: Some(price),
+            runway_slots: None,
+        }
+    }
+
+    #[test]
+    fn summarizes_pnl_against_first_snapshot_and_hold() {
+        // 1 SOL + 100 USDC at 100 => 200; later 0.5 SOL + 160 USDC at 110 => 215.
+        let snapshots = vec![
+            snapshot(0, 1_000_000_000, 100_000_000, 100.0),
+            snapshot(60, 500_000_000, 160_000_000, 110.0),
+        ];
+
+        let summary = summarize(&snapshots, 9, 6).unwrap();
+        assert!((summary.start_value - 200.0).abs() < 1e-9);
+        assert!((summary.end_value - 215.0).abs() < 1e-9);
+        assert!((summary.pnl - 15.0).abs() < 1e-9);
+        assert!((summary.hold_value - 210.0).abs() < 1e-9);
+        assert!((summary.pnl_vs_hold - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn marks_start_and_end_from_the_same_source() {
+        // The oracle was down at the start, so that snapshot only has the market price.
+        let mut start = snapshot(0, 1_000_000_000, 0, 100.0);
+        start.market_price = start.oracle_price.take();
+        let mut end = snapshot(60, 1_000_000_000, 0, 110.0);
+        end.market_price = Some(105.0);
+
+        let summary = summarize(&[start.clone(), end.clone()], 9, 6).unwrap();
+        assert_eq!(summary.snapshots, 1);
+        assert!(summary.pnl.abs() < 1e-9);
+
+        end.oracle_price = None;
+        let summary = summarize(&[start, end], 9, 6).unwrap();
+        assert!((summary.pnl - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tracks_max_drawdown_from_peak() {
+        let snapshots = vec![
+            snapshot(0, 0, 100_000_000, 1.0),
+            snapshot(1, 0, 130_000_000, 1.0),
+            snapshot(2, 0, 90_000_000, 1.0),
+            snapshot(3, 0, 120_000_000, 1.0),
+        ];
+
+        let summary = summarize(&snapshots, 9, 6).unwrap();
+        assert!((summary.max_drawdown - 40.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn expenses_add_fees_and_net_rent() {
+        let days = [
+            DailyExpenses {
+                day: 1,
+                transactions: 2,
+                fee_lamports: 500_000_000,
+                priority_fee_lamports: 490_000_000,
+                rent_lamports: 2_000_000_000,
+                requotes: 2,
+                stops: 0,
+            },
+            DailyExpenses {
+                day: 2,
+                transactions: 1,
+                fee_lamports: 500_000_000,
+                priority_fee_lamports: 0,
+                rent_lamports: -1_000_000_000,
+                requotes: 0,
+                stops: 1,
+            },
+        ];
+
+        assert_eq!(days[0].total_lamports(), 2_500_000_000);
+        assert!((total_expenses_sol(&days) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn daily_report_sums_turnover_activity_and_worst_runway() {
+        // 0.1 SOL and 5 USDC flowing per slot for 10 slots, closing at 100.
+        let mut start = snapshot(0, 1_000_000_000, 100_000_000, 100.0);
+        start.slot = 0;
+        start.base_flow = 100_000_000;
+        start.quote_flow = 5_000_000;
+        start.runway_slots = Some(50);
+        let mut end = snapshot(60, 0, 150_000_000, 100.0);
+        end.slot = 10;
+        end.runway_slots = Some(20);
+        let days = [
+            DailyExpenses {
+                day: 0,
+                transactions: 3,
+                fee_lamports: 15_000,
+                requotes: 2,
+                stops: 1,
+                ..DailyExpenses::default()
+            },
+            DailyExpenses {
+                day: 1,
+                transactions: 1,
+                fee_lamports: 5_000,
+                requotes: 1,
+                ..DailyExpenses::default()
+            },
+        ];
+
+        let report = DailyReport::new(1, &[start, end], &days, 9, 6);
+        assert!((report.turnover_quote - 150.0).abs() < 1e-9);
+        assert_eq!(report.worst_runway_slots, Some(20));
+        assert_eq!(
+            (
+                report.expenses.transactions,
+                report.expenses.requotes,
+                report.expenses.stops
+            ),
+            (4, 3, 1)
+        );
+        assert_eq!(report.expenses.fee_lamports, 20_000);
+        assert!(
+            report
+                .text()
+                .contains("3 requotes, 1 stops, worst runway 20 slots")
+        );
+    }
+
+    #[test]
+    fn debt_reduces_position_value() {
+        let mut snapshot = snapshot(0, 0, 100_000_000, 100.0);
+        snapshot.base_debt = 100_000_000;
+
+        let value = snapshot.value_quote_at(100.0, 9, 6);
+        assert!((value - 90.0).abs() < 1e-9);
+    }
+}
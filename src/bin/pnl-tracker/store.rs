@@ -0,0 +1,432 @@
+use anyhow::Context;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use crate::pnl::{DailyExpenses, PriceSource, Snapshot};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    market_id INTEGER NOT NULL,
+    authority TEXT NOT NULL,
+    base_balance INTEGER NOT NULL,
+    quote_balance INTEGER NOT NULL,
+    base_debt INTEGER NOT NULL,
+    quote_debt INTEGER NOT NULL,
+    base_flow INTEGER NOT NULL,
+    quote_flow INTEGER NOT NULL,
+    inventory_price REAL,
+    market_price REAL,
+    oracle_price REAL,
+    runway_slots INTEGER
+);
+CREATE INDEX IF NOT EXISTS snapshots_position_time
+    ON snapshots (market_id, authority, timestamp);
+CREATE TABLE IF NOT EXISTS expenses (
+    signature TEXT PRIMARY KEY,
+    authority TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    success INTEGER NOT NULL,
+    fee_lamports INTEGER NOT NULL,
+    priority_fee_lamports INTEGER NOT NULL,
+    rent_lamports INTEGER NOT NULL,
+    requotes INTEGER NOT NULL DEFAULT 0,
+    stops INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS expenses_authority_time
+    ON expenses (authority, timestamp);
+CREATE TABLE IF NOT EXISTS expense_cursors (
+    authority TEXT PRIMARY KEY,
+    signature TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS daily_reports (
+    market_id INTEGER NOT NULL,
+    authority TEXT NOT NULL,
+    day INTEGER NOT NULL,
+    PRIMARY KEY (market_id, authority, day)
+);
+";
+
+/// Columns added since the tables were first created, for databases that predate them.
+const ADDED_COLUMNS: [(&str, &str, &str); 3] = [
+    ("snapshots", "runway_slots", "INTEGER"),
+    ("expenses", "requotes", "INTEGER NOT NULL DEFAULT 0"),
+    ("expenses", "stops", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open PnL database at {}", path))?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to initialize PnL database schema")?;
+        for (table, column, definition) in ADDED_COLUMNS {
+            add_column_if_missing(&conn, table, column, definition)
+                .with_context(|| format!("Failed to add {table}.{column}"))?;
+        }
+        Ok(Self { conn })
+    }
+
+    pub fn insert_snapshot(&self, snapshot: &Snapshot) -> anyhow::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO snapshots (
+                    timestamp, slot, market_id, authority,
+                    base_balance, quote_balance, base_debt, quote_debt,
+                    base_flow, quote_flow,
+                    inventory_price, market_price, oracle_price, runway_slots
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    snapshot.timestamp,
+                    snapshot.slot as i64,
+                    snapshot.market_id as i64,
+                    snapshot.authority,
+                    snapshot.base_balance as i64,
+                    snapshot.quote_balance as i64,
+                    snapshot.base_debt as i64,
+                    snapshot.quote_debt as i64,
+                    snapshot.base_flow as i64,
+                    snapshot.quote_flow as i64,
+                    snapshot.inventory_price,
+                    snapshot.market_price,
+                    snapshot.oracle_price,
+                    snapshot.runway_slots.map(|slots| slots as i64),
+                ],
+            )
+            .context("Failed to insert PnL snapshot")?;
+        Ok(())
+    }
+
+    /// Snapshots for a position taken at or after `since`, oldest first.
+    pub fn snapshots_since(
+        &self,
+        market_id: u64,
+        authority: &str,
+        since: i64,
+    ) -> anyhow::Result<Vec<Snapshot>> {
+        let mut statement = self.conn.prepare(
+            "SELECT timestamp, slot, market_id, authority,
+                    base_balance, quote_balance, base_debt, quote_debt,
+                    base_flow, quote_flow,
+                    inventory_price, market_price, oracle_price, runway_slots
+             FROM snapshots
+             WHERE market_id = ?1 AND authority = ?2 AND timestamp >= ?3
+             ORDER BY timestamp ASC, id ASC",
+        )?;
+        let rows = statement.query_map(params![market_id as i64, authority, since], read_row)?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read PnL snapshots")
+    }
+
+    /// A position's oldest snapshot with a price from `source`.
+    pub fn first_snapshot(
+        &self,
+        market_id: u64,
+        authority: &str,
+        source: PriceSource,
+    ) -> anyhow::Result<Option<Snapshot>> {
+        let price = match source {
+            PriceSource::Oracle => "oracle_price",
+            PriceSource::Market => "market_price",
+        };
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT timestamp, slot, market_id, authority,
+                            base_balance, quote_balance, base_debt, quote_debt,
+                            base_flow, quote_flow,
+                            inventory_price, market_price, oracle_price, runway_slots
+                     FROM snapshots
+                     WHERE market_id = ?1 AND authority = ?2 AND {price} IS NOT NULL
+                     ORDER BY timestamp ASC, id ASC
+                     LIMIT 1"
+                ),
+                params![market_id as i64, authority],
+                read_row,
+            )
+            .optional()
+            .context("Failed to read the first PnL snapshot")
+    }
+}
+
+/// A transaction paid for by a tracked authority.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expense {
+    pub signature: String,
+    pub authority: String,
+    pub slot: u64,
+    pub timestamp: i64,
+    pub success: bool,
+    pub fee_lamports: u64,
+    pub priority_fee_lamports: u64,
+    pub rent_lamports: i64,
+    /// Flow updates to non-zero flows in the transaction.
+    pub requotes: u32,
+    /// Flow updates to zero and public stops in the transaction.
+    pub stops: u32,
+}
+
+impl Store {
+    /// Record a transaction's cost; recording the same signature twice is a no-op.
+    pub fn insert_expense(&self, expense: &Expense) -> anyhow::Result<()> {
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO expenses (
+                    signature, authority, slot, timestamp, success,
+                    fee_lamports, priority_fee_lamports, rent_lamports, requotes, stops
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    expense.signature,
+                    expense.authority,
+                    expense.slot as i64,
+                    expense.timestamp,
+                    expense.success,
+                    expense.fee_lamports as i64,
+                    expense.priority_fee_lamports as i64,
+                    expense.rent_lamports,
+                    expense.requotes,
+                    expense.stops,
+                ],
+            )
+            .context("Failed to insert expense")?;
+        Ok(())
+    }
+
+    /// Newest signature of `authority` already scanned for expenses.
+    pub fn expense_cursor(&self, authority: &str) -> anyhow::Result<Option<String>> {
+        self.conn
+            .query_row(
+                "SELECT signature FROM expense_cursors WHERE authority = ?1",
+                params![authority],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to read expense cursor")
+    }
+
+    pub fn set_expense_cursor(&self, authority: &str, signature: &str) -> anyhow::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO expense_cursors (authority, signature) VALUES (?1, ?2)
+                 ON CONFLICT (authority) DO UPDATE SET signature = excluded.signature",
+                params![authority, signature],
+            )
+            .context("Failed to update expense cursor")?;
+        Ok(())
+    }
+
+    /// Expenses of `authority` at or after `since`, summed per UTC day, oldest first.
+    pub fn daily_expenses(
+        &self,
+        authority: &str,
+        since: i64,
+    ) -> anyhow::Result<Vec<DailyExpenses>> {
+        let mut statement = self.conn.prepare(
+            "SELECT timestamp / 86400 AS day, COUNT(*),
+                    SUM(fee_lamports), SUM(priority_fee_lamports), SUM(rent_lamports),
+                    SUM(CASE WHEN success THEN requotes ELSE 0 END),
+                    SUM(CASE WHEN success THEN stops ELSE 0 END)
+             FROM expenses
+             WHERE authority = ?1 AND timestamp >= ?2
+             GROUP BY day
+             ORDER BY day ASC",
+        )?;
+        let rows = statement.query_map(params![authority, since], |row| {
+            Ok(DailyExpenses {
+                day: row.get(0)?,
+                transactions: row.get::<_, i64>(1)? as u64,
+                fee_lamports: row.get::<_, i64>(2)? as u64,
+                priority_fee_lamports: row.get::<_, i64>(3)? as u64,
+                rent_lamports: row.get(4)?,
+                requotes: row.get::<_, i64>(5)? as u64,
+                stops: row.get::<_, i64>(6)? as u64,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read expenses")
+    }
+
+    /// Claim the daily report for `day` (days since the Unix epoch); false when it was
+    /// already sent, e.g. before a restart.
+    pub fn claim_daily_report(
+        &self,
+        market_id: u64,
+        authority: &str,
+        day: i64,
+    ) -> anyhow::Result<bool> {
+        let inserted = self
+            .conn
+            .execute(
+                "INSERT OR IGNORE INTO daily_reports (market_id, authority, day)
+                 VALUES (?1, ?2, ?3)",
+                params![market_id as i64, authority, day],
+            )
+            .context("Failed to record daily report")?;
+        Ok(inserted == 1)
+    }
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists = conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+        ))?
+        .exists(params![column])?;
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+    Ok(())
+}
+
+fn read_row(row: &Row<'_>) -> rusqlite::Result<Snapshot> {
+    Ok(Snapshot {
+        timestamp: row.get(0)?,
+        slot: row.get::<_, i64>(1)? as u64,
+        market_id: row.get::<_, i64>(2)? as u64,
+        authority: row.get(3)?,
+        base_balance: row.get::<_, i64>(4)? as u64,
+        quote_balance: row.get::<_, i64>(5)? as u64,
+        base_debt: row.get::<_, i64>(6)? as u64,
+        quote_debt: row.get::<_, i64>(7)? as u64,
+        base_flow: row.get::<_, i64>(8)? as u64,
+        quote_flow: row.get::<_, i64>(9)? as u64,
+        inventory_price: row.get(10)?,
+        market_price: row.get(11)?,
+        oracle_price: row.get(12)?,
+        runway_slots: row.get::<_, Option<i64>>(13)?.map(|slots| slots as u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_snapshots_in_time_order() {
+        let store = Store::open(":memory:").unwrap();
+        let snapshot = |timestamp: i64| Snapshot {
+            timestamp,
+            slot: 100 + timestamp as u64,
+            market_id: 1,
+            authority: "lp".to_string(),
+            base_balance: 1,
+            quote_balance: 2,
+            base_debt: 0,
+            quote_debt: 0,
+            base_flow: 3,
+            quote_flow: 4,
+            inventory_price: Some(2.0),
+            market_price: None,
+            oracle_price: Some(1.5),
+            runway_slots: Some(900),
+        };
+
+        store.insert_snapshot(&snapshot(20)).unwrap();
+        store.insert_snapshot(&snapshot(10)).unwrap();
+        store.insert_snapshot(&snapshot(30)).unwrap();
+
+        let loaded = store.snapshots_since(1, "lp", 15).unwrap();
+        assert_eq!(loaded, vec![snapshot(20), snapshot(30)]);
+        assert!(store.snapshots_since(2, "lp", 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_the_first_snapshot_priced_by_a_source() {
+        let store = Store::open(":memory:").unwrap();
+        let snapshot = |timestamp: i64, oracle_price: Option<f64>| Snapshot {
+            timestamp,
+            slot: 100 + timestamp as u64,
+            market_id: 1,
+            authority: "lp".to_string(),
+            base_balance: 1,
+            quote_balance: 2,
+            base_debt: 0,
+            quote_debt: 0,
+            base_flow: 3,
+            quote_flow: 4,
+            inventory_price: None,
+            market_price: Some(1.4),
+            oracle_price,
+            runway_slots: None,
+        };
+
+        store.insert_snapshot(&snapshot(30, Some(1.6))).unwrap();
+        store.insert_snapshot(&snapshot(10, None)).unwrap();
+        store.insert_snapshot(&snapshot(20, Some(1.5))).unwrap();
+
+        assert_eq!(
+            store.first_snapshot(1, "lp", PriceSource::Oracle).unwrap(),
+            Some(snapshot(20, Some(1.5)))
+        );
+        assert_eq!(
+            store.first_snapshot(1, "lp", PriceSource::Market).unwrap(),
+            Some(snapshot(10, None))
+        );
+        assert_eq!(
+            store.first_snapshot(2, "lp", PriceSource::Market).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn sums_expenses_per_day_once_per_signature() {
+        let store = Store::open(":memory:").unwrap();
+        let expense = |signature: &str, timestamp: i64, rent_lamports: i64| Expense {
+            signature: signature.to_string(),
+            authority: "bot".to_string(),
+            slot: timestamp as u64,
+            timestamp,
+            success: true,
+            fee_lamports: 10_000,
+            priority_fee_lamports: 5_000,
+            rent_lamports,
+            requotes: 1,
+            stops: 0,
+        };
+
+        store.insert_expense(&expense("a", 100, 2_039_280)).unwrap();
+        store.insert_expense(&expense("a", 100, 2_039_280)).unwrap();
+        store.insert_expense(&expense("b", 200, 0)).unwrap();
+        store
+            .insert_expense(&expense("c", 86_400 + 5, -2_039_280))
+            .unwrap();
+
+        let days = store.daily_expenses("bot", 0).unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(
+            days[0],
+            DailyExpenses {
+                day: 0,
+                transactions: 2,
+                fee_lamports: 20_000,
+                priority_fee_lamports: 10_000,
+                rent_lamports: 2_039_280,
+                requotes: 2,
+                stops: 0,
+            }
+        );
+        assert_eq!(days[1].rent_lamports, -2_039_280);
+
+        assert_eq!(store.expense_cursor("bot").unwrap(), None);
+        store.set_expense_cursor("bot", "a").unwrap();
+        store.set_expense_cursor("bot", "c").unwrap();
+        assert_eq!(store.expense_cursor("bot").unwrap().as_deref(), Some("c"));
+
+        assert!(store.claim_daily_report(1, "bot", 0).unwrap());
+        assert!(!store.claim_daily_report(1, "bot", 0).unwrap());
+        assert!(store.claim_daily_report(1, "bot", 1).unwrap());
+    }
+}
@@ -0,0 +1,104 @@
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair},
+};
+
+use crate::plan::{Band, TokenBand};
+use twob_market_making::{
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::{JitterConfig, env_var},
+    tx::{TxSenderConfig, keypair_from_env},
+};
+
+pub struct Config {
+    pub keypair: Keypair,
+    pub rpc_url: String,
+    pub ws_url: String,
+    /// Bot wallets kept funded by the treasury.
+    pub wallets: Vec<Pubkey>,
+    pub sol_band: Band,
+    /// Lamports the treasury never gives away, so it can keep paying its own fees.
+    pub sol_reserve_lamports: u64,
+    pub token_bands: Vec<TokenBand>,
+    pub db_path: String,
+    pub interval_secs: u64,
+    pub dry_run: bool,
+    pub tx: TxSenderConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+    pub jitter: JitterConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let keypair = keypair_from_env("TREASURY_KEYPAIR")?;
+
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let wallets = env_var("TREASURY_WALLETS")
+            .map_err(|_| anyhow::anyhow!("TREASURY_WALLETS env var not set"))?
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value.parse::<Pubkey>().map_err(|e| {
+                    anyhow::anyhow!("Invalid wallet `{}` in TREASURY_WALLETS: {}", value, e)
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if wallets.is_empty() {
+            anyhow::bail!("TREASURY_WALLETS must list at least one wallet");
+        }
+
+        let sol_band = Band::new(
+            env_var("TREASURY_SOL_MIN_LAMPORTS")
+                .unwrap_or_else(|_| "100000000".to_string())
+                .parse::<u64>()?,
+            env_var("TREASURY_SOL_TARGET_LAMPORTS")
+                .unwrap_or_else(|_| "500000000".to_string())
+                .parse::<u64>()?,
+        )?;
+
+        let sol_reserve_lamports = env_var("TREASURY_SOL_RESERVE_LAMPORTS")
+            .unwrap_or_else(|_| "50000000".to_string())
+            .parse::<u64>()?;
+
+        let token_bands = env_var("TREASURY_TOKEN_BANDS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|value| !value.trim().is_empty())
+            .map(str::parse::<TokenBand>)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let db_path = env_var("TREASURY_DB_PATH").unwrap_or_else(|_| "treasury.sqlite".to_string());
+
+        let interval_secs = env_var("TREASURY_INTERVAL_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()?;
+
+        let dry_run = env_var("TREASURY_DRY_RUN")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()?;
+
+        Ok(Self {
+            keypair,
+            rpc_url,
+            ws_url,
+            wallets,
+            sol_band,
+            sol_reserve_lamports,
+            token_bands,
+            db_path,
+            interval_secs,
+            dry_run,
+            tx: TxSenderConfig::from_env()?,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+            jitter: JitterConfig::from_env()?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
@@ -0,0 +1,325 @@
+mod config;
+mod plan;
+mod store;
+
+use std::{sync::Arc, time::Duration};
+
+use anchor_client::{
+    Client,
+    solana_sdk::{instruction::Instruction, pubkey::Pubkey, signer::Signer},
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    token_2022::spl_token_2022,
+};
+use config::Config;
+use plan::{TokenBand, fundable};
+use store::{Movement, MovementStatus, SOL_ASSET, Store};
+use tokio::{signal, time::sleep};
+use tracing::{error, info, warn};
+use twob_market_making::{
+    ProgramPayer, get_token_program_id, program_payer, twob_anchor, tx::TxSender,
+};
+
+type TreasuryProgram = anchor_client::Program<ProgramPayer>;
+
+const DEFAULT_REPORT_HOURS: i64 = 24;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let store = Store::open(&config.db_path)?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None | Some("run") => run(&config, &store).await,
+        Some("report") => report(&config, &store, &ReportArgs::parse(&args[1..])?),
+        Some(other) => {
+            anyhow::bail!(
+                "unknown command `{other}`; expected `run` or `report [--hours N] [--json]`"
+            )
+        }
+    }
+}
+
+async fn run(config: &Config, store: &Store) -> anyhow::Result<()> {
+    let treasury = Arc::new(config.keypair.insecure_clone());
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(treasury.pubkey()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let sender = TxSender::for_program(&program, treasury.clone(), config.tx.clone())?;
+    let interval = Duration::from_secs(config.interval_secs);
+
+    info!(
+        event.name = "treasury_started",
+        treasury.authority = %treasury.pubkey(),
+        treasury.wallets = config.wallets.len(),
+        treasury.token_bands = config.token_bands.len(),
+        treasury.dry_run = config.dry_run,
+    );
+
+    loop {
+        for wallet in &config.wallets {
+            if let Err(error) = top_up_sol(&program, &sender, config, store, wallet).await {
+                error!(event.name = "treasury_sol_check_failed", wallet = %wallet, ?error);
+            }
+            for token_band in &config.token_bands {
+                if let Err(error) =
+                    top_up_token(&program, &sender, config, store, wallet, token_band).await
+                {
+                    error!(
+                        event.name = "treasury_token_check_failed",
+                        wallet = %wallet,
+                        token.mint = %token_band.mint,
+                        ?error,
+                    );
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "treasury_shutdown");
+                return Ok(());
+            }
+            _ = sleep(config.jitter.apply(interval)) => {}
+        }
+    }
+}
+
+async fn top_up_sol(
+    program: &TreasuryProgram,
+    sender: &TxSender,
+    config: &Config,
+    store: &Store,
+    wallet: &Pubkey,
+) -> anyhow::Result<()> {
+    let rpc = program.rpc();
+    let balance = rpc.get_balance(wallet).await?;
+    info!(
+        event.name = "treasury_balance",
+        wallet = %wallet,
+        asset = SOL_ASSET,
+        gauge.treasury_wallet_balance = balance,
+    );
+    let Some(wanted) = config.sol_band.top_up(balance) else {
+        return Ok(());
+    };
+
+    let treasury_balance = rpc.get_balance(&program.payer()).await?;
+    let Some(amount) = fundable(wanted, treasury_balance, config.sol_reserve_lamports) else {
+        warn!(
+            event.name = "treasury_underfunded",
+            wallet = %wallet,
+            asset = SOL_ASSET,
+            treasury.balance = treasury_balance,
+            transfer.wanted = wanted,
+        );
+        return Ok(());
+    };
+
+    let transfer = solana_system_interface::instruction::transfer(&program.payer(), wallet, amount);
+    send_and_record(
+        sender,
+        config,
+        store,
+        wallet,
+        SOL_ASSET,
+        amount,
+        balance,
+        vec![transfer],
+    )
+    .await
+}
+
+async fn top_up_token(
+    program: &TreasuryProgram,
+    sender: &TxSender,
+    config: &Config,
+    store: &Store,
+    wallet: &Pubkey,
+    token_band: &TokenBand,
+) -> anyhow::Result<()> {
+    let rpc = program.rpc();
+    let mint = token_band.mint;
+    let token_program = get_token_program_id(program, &mint).await?;
+    let wallet_ata = get_associated_token_address_with_program_id(wallet, &mint, &token_program);
+    let treasury_ata =
+        get_associated_token_address_with_program_id(&program.payer(), &mint, &token_program);
+
+    // A missing wallet ATA just means an empty inventory; it is created with the transfer.
+    let balance = match rpc.get_token_account_balance(&wallet_ata).await {
+        Ok(amount) => amount.amount.parse::<u64>()?,
+        Err(_) => 0,
+    };
+    info!(
+        event.name = "treasury_balance",
+        wallet = %wallet,
+        asset = %mint,
+        gauge.treasury_wallet_balance = balance,
+    );
+    let Some(wanted) = token_band.band.top_up(balance) else {
+        return Ok(());
+    };
+
+    let treasury_tokens = rpc.get_token_account_balance(&treasury_ata).await?;
+    let Some(amount) = fundable(wanted, treasury_tokens.amount.parse::<u64>()?, 0) else {
+        warn!(
+            event.name = "treasury_underfunded",
+            wallet = %wallet,
+            asset = %mint,
+            treasury.balance = %treasury_tokens.amount,
+            transfer.wanted = wanted,
+        );
+        return Ok(());
+    };
+
+    let instructions = vec![
+        create_associated_token_account_idempotent(&program.payer(), wallet, &mint, &token_program),
+        spl_token_2022::instruction::transfer_checked(
+            &token_program,
+            &treasury_ata,
+            &mint,
+            &wallet_ata,
+            &program.payer(),
+            &[],
+            amount,
+            treasury_tokens.decimals,
+        )?,
+    ];
+    send_and_record(
+        sender,
+        config,
+        store,
+        wallet,
+        &mint.to_string(),
+        amount,
+        balance,
+        instructions,
+    )
+    .await
+}
+
+/// Send a top-up (unless in dry-run) and record the movement whatever the outcome.
+#[allow(clippy::too_many_arguments)]
+async fn send_and_record(
+    sender: &TxSender,
+    config: &Config,
+    store: &Store,
+    wallet: &Pubkey,
+    asset: &str,
+    amount: u64,
+    balance_before: u64,
+    instructions: Vec<Instruction>,
+) -> anyhow::Result<()> {
+    let result = if config.dry_run {
+        Ok(None)
+    } else {
+        sender.send(instructions).await.map(Some)
+    };
+
+    let (status, signature, error) = match &result {
+        Ok(Some(signature)) => (MovementStatus::Sent, Some(signature.to_string()), None),
+        Ok(None) => (MovementStatus::DryRun, None, None),
+        Err(error) => (MovementStatus::Failed, None, Some(error.to_string())),
+    };
+    store.record(&Movement {
+        timestamp: chrono::Utc::now().timestamp(),
+        wallet: wallet.to_string(),
+        asset: asset.to_string(),
+        amount,
+        balance_before,
+        status,
+        signature: signature.clone(),
+        error,
+    })?;
+
+    info!(
+        event.name = "treasury_top_up",
+        wallet = %wallet,
+        asset = asset,
+        transfer.amount = amount,
+        transfer.balance_before = balance_before,
+        transfer.status = ?status,
+        transfer.signature = ?signature,
+        monotonic_counter.treasury_top_ups_total = 1_u64,
+    );
+    result?;
+    Ok(())
+}
+
+fn report(config: &Config, store: &Store, args: &ReportArgs) -> anyhow::Result<()> {
+    let hours = args.hours;
+    let since = chrono::Utc::now().timestamp() - hours * 3_600;
+
+    if args.json {
+        let mut movements = Vec::new();
+        for wallet in &config.wallets {
+            movements.extend(store.movements_since(&wallet.to_string(), since)?);
+        }
+        let report = serde_json::json!({ "hours": hours, "movements": movements });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("Treasury movements in the last {}h", hours);
+    for wallet in &config.wallets {
+        let movements = store.movements_since(&wallet.to_string(), since)?;
+        println!("  {} ({} movements)", wallet, movements.len());
+        for movement in movements {
+            let timestamp = chrono::DateTime::from_timestamp(movement.timestamp, 0)
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_else(|| movement.timestamp.to_string());
+            println!(
+                "    {}  {:<44}  {:>20}  {:?}  {}",
+                timestamp,
+                movement.asset,
+                movement.amount,
+                movement.status,
+                movement.signature.or(movement.error).unwrap_or_default()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// `report [--hours N] [--json]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ReportArgs {
+    hours: i64,
+    /// Print the movements as JSON instead of text.
+    json: bool,
+}
+
+impl ReportArgs {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut parsed = Self {
+            hours: DEFAULT_REPORT_HOURS,
+            json: false,
+        };
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--hours" => {
+                    let value = args.next().map(String::as_str).unwrap_or_default();
+                    parsed.hours = value
+                        .parse::<i64>()
+                        .map_err(|e| anyhow::anyhow!("invalid --hours value `{value}`: {e}"))?;
+                }
+                "--json" => parsed.json = true,
+                _ => anyhow::bail!("usage: treasury report [--hours N] [--json]"),
+            }
+        }
+        Ok(parsed)
+    }
+}
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+
+/// Keep a balance at or above `min`; when it drops below, top it back up to `target`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Band {
+    pub min: u64,
+    pub target: u64,
+}
+
+impl Band {
+    pub fn new(min: u64, target: u64) -> anyhow::Result<Self> {
+        if target < min {
+            anyhow::bail!("band target {} is below its minimum {}", target, min);
+        }
+        Ok(Self { min, target })
+    }
+
+    /// Amount to send a wallet holding `balance`, or `None` while it is inside the band.
+    pub fn top_up(&self, balance: u64) -> Option<u64> {
+        (balance < self.min).then(|| self.target - balance)
+    }
+}
+
+/// A token inventory band, parsed from `mint:min:target` in raw token units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBand {
+    pub mint: Pubkey,
+    pub band: Band,
+}
+
+impl FromStr for TokenBand {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let parts: Vec<&str> = value.trim().split(':').collect();
+        let [mint, min, target] = parts.as_slice() else {
+            anyhow::bail!("invalid token band `{value}`; expected `mint:min:target`");
+        };
+        Ok(Self {
+            mint: mint
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid mint in token band `{value}`: {e}"))?,
+            band: Band::new(min.parse()?, target.parse()?)?,
+        })
+    }
+}
+
+/// Clamp a top-up to what the treasury can give without dipping below `reserve`. Returns
+/// `None` when nothing can be sent.
+pub fn fundable(amount: u64, treasury_balance: u64, reserve: u64) -> Option<u64> {
+    let available = treasury_balance.saturating_sub(reserve);
+    let amount = amount.min(available);
+    (amount > 0).then_some(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tops_up_to_target_only_below_min() {
+        let band = Band::new(100, 500).unwrap();
+        assert_eq!(band.top_up(100), None);
+        assert_eq!(band.top_up(700), None);
+        assert_eq!(band.top_up(40), Some(460));
+        assert!(Band::new(500, 100).is_err());
+    }
+
+    #[test]
+    fn parses_token_bands() {
+        let mint = Pubkey::new_unique();
+        let band: TokenBand = format!(" {mint}:1000:5000 ").parse().unwrap();
+        assert_eq!(band.mint, mint);
+        assert_eq!(band.band, Band::new(1_000, 5_000).unwrap());
+
+        assert!(format!("{mint}:1000").parse::<TokenBand>().is_err());
+        assert!("not-a-mint:1:2".parse::<TokenBand>().is_err());
+    }
+
+    #[test]
+    fn fundable_respects_treasury_reserve() {
+        assert_eq!(fundable(300, 1_000, 100), Some(300));
+        assert_eq!(fundable(300, 250, 100), Some(150));
+        assert_eq!(fundable(300, 100, 100), None);
+    }
+}
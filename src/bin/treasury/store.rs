@@ -0,0 +1,165 @@
+use anyhow::Context;
+use rusqlite::{Connection, Row, params};
+use serde::Serialize;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS movements (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    wallet TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    balance_before INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    signature TEXT,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS movements_wallet_time
+    ON movements (wallet, timestamp);
+";
+
+/// `asset` is `SOL` for lamport transfers and the mint address for token transfers.
+pub const SOL_ASSET: &str = "SOL";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementStatus {
+    Sent,
+    DryRun,
+    Failed,
+}
+
+impl MovementStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::DryRun => "dry_run",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> rusqlite::Result<Self> {
+        match value {
+            "sent" => Ok(Self::Sent),
+            "dry_run" => Ok(Self::DryRun),
+            "failed" => Ok(Self::Failed),
+            other => Err(rusqlite::Error::InvalidColumnType(
+                6,
+                format!("status `{other}`"),
+                rusqlite::types::Type::Text,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Movement {
+    pub timestamp: i64,
+    pub wallet: String,
+    pub asset: String,
+    pub amount: u64,
+    pub balance_before: u64,
+    pub status: MovementStatus,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open treasury database at {}", path))?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to initialize treasury database schema")?;
+        Ok(Self { conn })
+    }
+
+    pub fn record(&self, movement: &Movement) -> anyhow::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO movements (
+                    timestamp, wallet, asset, amount, balance_before, status, signature, error
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    movement.timestamp,
+                    movement.wallet,
+                    movement.asset,
+                    movement.amount as i64,
+                    movement.balance_before as i64,
+                    movement.status.as_str(),
+                    movement.signature,
+                    movement.error,
+                ],
+            )
+            .context("Failed to record treasury movement")?;
+        Ok(())
+    }
+
+    /// Movements to `wallet` at or after `since`, oldest first.
+    pub fn movements_since(&self, wallet: &str, since: i64) -> anyhow::Result<Vec<Movement>> {
+        let mut statement = self.conn.prepare(
+            "SELECT timestamp, wallet, asset, amount, balance_before, status, signature, error
+             FROM movements
+             WHERE wallet = ?1 AND timestamp >= ?2
+             ORDER BY timestamp ASC, id ASC",
+        )?;
+        let rows = statement.query_map(params![wallet, since], read_row)?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read treasury movements")
+    }
+}
+
+fn read_row(row: &Row<'_>) -> rusqlite::Result<Movement> {
+    Ok(Movement {
+        timestamp: row.get(0)?,
+        wallet: row.get(1)?,
+        asset: row.get(2)?,
+        amount: row.get::<_, i64>(3)? as u64,
+        balance_before: row.get::<_, i64>(4)? as u64,
+        status: MovementStatus::parse(&row.get::<_, String>(5)?)?,
+        signature: row.get(6)?,
+        error: row.get(7)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_movements_per_wallet() {
+        let store = Store::open(":memory:").unwrap();
+        let movement = |timestamp: i64, wallet: &str, status: MovementStatus| Movement {
+            timestamp,
+            wallet: wallet.to_string(),
+            asset: SOL_ASSET.to_string(),
+            amount: 400_000_000,
+            balance_before: 100_000_000,
+            status,
+            signature: (status == MovementStatus::Sent).then(|| "sig".to_string()),
+            error: (status == MovementStatus::Failed).then(|| "rpc timeout".to_string()),
+        };
+
+        store
+            .record(&movement(20, "bot", MovementStatus::Failed))
+            .unwrap();
+        store
+            .record(&movement(10, "bot", MovementStatus::Sent))
+            .unwrap();
+        store
+            .record(&movement(30, "other", MovementStatus::DryRun))
+            .unwrap();
+
+        assert_eq!(
+            store.movements_since("bot", 0).unwrap(),
+            vec![
+                movement(10, "bot", MovementStatus::Sent),
+                movement(20, "bot", MovementStatus::Failed),
+            ]
+        );
+        assert_eq!(store.movements_since("other", 31).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair},
+};
+use twob_market_making::{
+    OrderSide,
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::{JitterConfig, env_var},
+    tx::{TxSenderConfig, keypair_from_env},
+};
+
+pub struct Config {
+    pub keypair: Keypair,
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    pub side: OrderSide,
+    /// Raw units of the token being spent: quote for a buy, base for a sell.
+    pub total_amount: u64,
+    /// The whole order is spread over this many slots from start-up.
+    pub duration_slots: u64,
+    /// Each child trade position runs for about this many slots.
+    pub child_slots: u64,
+    /// Cap each child's flow at this share of the market's flow on the same side.
+    pub max_participation_bps: Option<u64>,
+    pub poll_interval: Duration,
+    pub tx: TxSenderConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+    pub jitter: JitterConfig,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let keypair = keypair_from_env("TWAP_KEYPAIR")?;
+
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let market_id = env_var("MARKET_ID")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<u64>()?;
+
+        let side = env_var("TWAP_SIDE")
+            .map_err(|_| anyhow::anyhow!("TWAP_SIDE env var not set"))?
+            .parse::<OrderSide>()?;
+
+        let total_amount = env_var("TWAP_TOTAL_AMOUNT")
+            .map_err(|_| anyhow::anyhow!("TWAP_TOTAL_AMOUNT env var not set"))?
+            .parse::<u64>()?;
+        if total_amount == 0 {
+            anyhow::bail!("TWAP_TOTAL_AMOUNT must be positive");
+        }
+
+        let duration_slots = env_var("TWAP_DURATION_SLOTS")
+            .map_err(|_| anyhow::anyhow!("TWAP_DURATION_SLOTS env var not set"))?
+            .parse::<u64>()?;
+
+        let child_slots = env_var("TWAP_CHILD_SLOTS")
+            .unwrap_or_else(|_| "1500".to_string())
+            .parse::<u64>()?;
+        if child_slots == 0 || child_slots > duration_slots {
+            anyhow::bail!(
+                "TWAP_CHILD_SLOTS {} must be between 1 and TWAP_DURATION_SLOTS {}",
+                child_slots,
+                duration_slots
+            );
+        }
+
+        let max_participation_bps = env_var("TWAP_MAX_PARTICIPATION_BPS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse::<u64>()?;
+        let max_participation_bps = (max_participation_bps > 0).then_some(max_participation_bps);
+
+        let poll_interval = Duration::from_secs(
+            env_var("TWAP_POLL_INTERVAL_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse::<u64>()?,
+        );
+
+        Ok(Self {
+            keypair,
+            rpc_url,
+            ws_url,
+            market_id,
+            side,
+            total_amount,
+            duration_slots,
+            child_slots,
+            max_participation_bps,
+            poll_interval,
+            tx: TxSenderConfig::from_env()?,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+            jitter: JitterConfig::from_env()?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
@@ -0,0 +1,319 @@
+mod config;
+mod schedule;
+
+use std::sync::Arc;
+
+use anchor_client::{
+    Client, Program,
+    solana_sdk::{pubkey::Pubkey, signer::Signer},
+};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use config::Config;
+use tokio::{signal, time::sleep};
+use tracing::{error, info, warn};
+use twob_market_making::{
+    AccountResolver, MarketState, OrderSide, ProgramPayer, execute_authority_close_position,
+    execute_submit_order, fetch_market_state, get_token_program_id, nearest_reference_index,
+    program_payer,
+    state::estimated_trade_fill,
+    twob_anchor::{self, accounts::TradePosition},
+    tx::TxSender,
+};
+
+/// The child trade position currently spending part of the parent order.
+#[derive(Debug, Clone, Copy)]
+struct OpenChild {
+    id: u64,
+    amount: u64,
+    end_slot: u64,
+}
+
+/// Associated token accounts the order spends from and receives into.
+struct Wallet {
+    spend: Pubkey,
+    receive: Pubkey,
+}
+
+/// Progress of the parent order, in raw units.
+#[derive(Debug, Default)]
+struct Progress {
+    spent: u64,
+    received: u64,
+    children: u64,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let payer = Arc::new(config.keypair.insecure_clone());
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(payer.pubkey()),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let sender = TxSender::for_program(&program, payer.clone(), config.tx.clone())?;
+
+    let start = fetch_market_state(&program, config.market_id).await?;
+    let spend_mint = config.side.spend_mint(&start.market);
+    let receive_mint = config.side.receive_mint(&start.market);
+    let wallet = Wallet {
+        spend: get_associated_token_address_with_program_id(
+            &payer.pubkey(),
+            &spend_mint,
+            &get_token_program_id(&program, &spend_mint).await?,
+        ),
+        receive: get_associated_token_address_with_program_id(
+            &payer.pubkey(),
+            &receive_mint,
+            &get_token_program_id(&program, &receive_mint).await?,
+        ),
+    };
+    let deadline = start.current_slot + config.duration_slots;
+    // Trade position ids only need to be unique per authority; slots never repeat.
+    let mut next_id = start.current_slot;
+    let mut open: Option<OpenChild> = None;
+    let mut progress = Progress::default();
+
+    info!(
+        event.name = "twap_started",
+        market.id = config.market_id,
+        twap.side = config.side.as_str(),
+        twap.total_amount = config.total_amount,
+        twap.deadline_slot = deadline,
+        twap.child_slots = config.child_slots,
+        twap.max_participation_bps = config.max_participation_bps,
+    );
+
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "twap_shutdown");
+                if let Some(child) = open.take() {
+                    close_child(&program, &config, &wallet, child, &sender, &mut progress)
+                        .await?;
+                }
+                break;
+            }
+            _ = sleep(config.jitter.apply(config.poll_interval)) => {
+                let state = match fetch_market_state(&program, config.market_id).await {
+                    Ok(state) => state,
+                    Err(error) => {
+                        warn!(event.name = "twap_state_fetch_failed", ?error);
+                        continue;
+                    }
+                };
+
+                if let Some(child) = open {
+                    if state.current_slot < child.end_slot {
+                        report_child_progress(&program, &config, &state, child).await;
+                        continue;
+                    }
+                    let closed = close_child(
+                        &program,
+                        &config,
+                        &wallet,
+                        child,
+                        &sender,
+                        &mut progress,
+                    )
+                    .await;
+                    match closed {
+                        Ok(()) => open = None,
+                        Err(error) => {
+                            error!(
+                                event.name = "twap_child_close_failed",
+                                twap.child_id = child.id,
+                                ?error,
+                            );
+                            continue;
+                        }
+                    }
+                }
+
+                let remaining = config.total_amount.saturating_sub(progress.spent);
+                if remaining == 0 || state.current_slot >= deadline {
+                    break;
+                }
+
+                let market_side_flow = match config.side {
+                    OrderSide::Buy => state.market.quote_flow,
+                    OrderSide::Sell => state.market.base_flow,
+                };
+                let Some(child) = schedule::next_child(
+                    remaining,
+                    state.current_slot,
+                    deadline,
+                    config.child_slots,
+                    state.market.end_slot_interval,
+                    market_side_flow,
+                    config.max_participation_bps,
+                ) else {
+                    break;
+                };
+                if child.amount == 0 {
+                    info!(
+                        event.name = "twap_child_deferred",
+                        market.id = config.market_id,
+                        reason = "participation_limit",
+                    );
+                    continue;
+                }
+
+                let id = next_id;
+                next_id += 1;
+                let submitted = execute_submit_order(
+                    &program,
+                    config.market_id,
+                    id,
+                    config.side,
+                    child.amount,
+                    child.end_slot,
+                    nearest_reference_index(
+                        state.current_slot,
+                        state.market.end_slot_interval,
+                    ),
+                    &sender,
+                )
+                .await;
+                match submitted {
+                    Ok(()) => {
+                        info!(
+                            event.name = "twap_child_opened",
+                            market.id = config.market_id,
+                            twap.child_id = id,
+                            twap.child_amount = child.amount,
+                            twap.child_end_slot = child.end_slot,
+                            twap.participation_capped = child.capped,
+                            monotonic_counter.twap_children_opened_total = 1_u64,
+                        );
+                        open = Some(OpenChild {
+                            id,
+                            amount: child.amount,
+                            end_slot: child.end_slot,
+                        });
+                    }
+                    Err(error) => warn!(
+                        event.name = "twap_child_open_failed",
+                        market.id = config.market_id,
+                        twap.child_id = id,
+                        ?error,
+                    ),
+                }
+            }
+        }
+    }
+
+    let unspent = config.total_amount.saturating_sub(progress.spent);
+    info!(
+        event.name = "twap_finished",
+        market.id = config.market_id,
+        twap.side = config.side.as_str(),
+        twap.spent = progress.spent,
+        twap.received = progress.received,
+        twap.unspent = unspent,
+        twap.children = progress.children,
+    );
+    if unspent > 0 {
+        warn!(
+            event.name = "twap_incomplete",
+            twap.unspent = unspent,
+            "order window ended before the full amount was spent"
+        );
+    }
+
+    Ok(())
+}
+
+async fn report_child_progress(
+    program: &Program<ProgramPayer>,
+    config: &Config,
+    state: &MarketState,
+    child: OpenChild,
+) {
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let market = resolver.market_pda(config.market_id).address();
+    let address = resolver
+        .trade_position_pda(&market, &program.payer(), child.id)
+        .address();
+    match program.account::<TradePosition>(address).await {
+        Ok(position) => {
+            let fill = estimated_trade_fill(&position, &state.bookkeeping);
+            info!(
+                event.name = "twap_child_progress",
+                market.id = config.market_id,
+                twap.child_id = child.id,
+                twap.slots_left = child.end_slot.saturating_sub(state.current_slot),
+                gauge.twap_child_spent_estimate = fill.spent,
+                gauge.twap_child_received_estimate = fill.received,
+            );
+        }
+        Err(error) => warn!(
+            event.name = "twap_child_fetch_failed",
+            twap.child_id = child.id,
+            ?error,
+        ),
+    }
+}
+
+/// Close `child`, crediting what it actually paid out (measured on the wallet) to
+/// `progress`. Any unspent amount refunded on close is not counted as spent.
+async fn close_child(
+    program: &Program<ProgramPayer>,
+    config: &Config,
+    wallet: &Wallet,
+    child: OpenChild,
+    sender: &TxSender,
+    progress: &mut Progress,
+) -> anyhow::Result<()> {
+    let spend_before = token_balance(program, &wallet.spend).await;
+    let receive_before = token_balance(program, &wallet.receive).await;
+
+    let state = fetch_market_state(program, config.market_id).await?;
+    execute_authority_close_position(
+        program,
+        config.market_id,
+        child.id,
+        nearest_reference_index(state.current_slot, state.market.end_slot_interval),
+        sender,
+    )
+    .await?;
+
+    let refunded = token_balance(program, &wallet.spend)
+        .await
+        .saturating_sub(spend_before);
+    let received = token_balance(program, &wallet.receive)
+        .await
+        .saturating_sub(receive_before);
+    let spent = child.amount.saturating_sub(refunded);
+    progress.spent = progress.spent.saturating_add(spent);
+    progress.received = progress.received.saturating_add(received);
+    progress.children += 1;
+
+    info!(
+        event.name = "twap_child_closed",
+        market.id = config.market_id,
+        twap.child_id = child.id,
+        twap.child_spent = spent,
+        twap.child_received = received,
+        twap.child_refunded = refunded,
+        gauge.twap_spent = progress.spent,
+        gauge.twap_received = progress.received,
+        monotonic_counter.twap_children_closed_total = 1_u64,
+    );
+    Ok(())
+}
+
+/// Raw balance of a token account; a missing account counts as empty.
+async fn token_balance(program: &Program<ProgramPayer>, account: &Pubkey) -> u64 {
+    program
+        .rpc()
+        .get_token_account_balance(account)
+        .await
+        .ok()
+        .and_then(|balance| balance.amount.parse().ok())
+        .unwrap_or(0)
+}
@@ -0,0 +1,95 @@
+//! Sizing child trade positions so a parent order is spent evenly over its window.
+
+use twob_market_making::FLOW_PRECISION;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Child {
+    pub amount: u64,
+    /// A multiple of the market's `end_slot_interval`.
+    pub end_slot: u64,
+    /// The participation limit shrank the time-weighted amount.
+    pub capped: bool,
+}
+
+/// Size the next child so the `remaining` amount is spread evenly until `deadline`.
+///
+/// The child ends on the first end-slot boundary at least `child_slots` ahead (or at the
+/// deadline, if closer). With `max_participation_bps`, its per-slot flow is capped at that
+/// share of `market_side_flow`, the market's flow of the token being spent.
+pub fn next_child(
+    remaining: u64,
+    current_slot: u64,
+    deadline: u64,
+    child_slots: u64,
+    end_slot_interval: u64,
+    market_side_flow: u128,
+    max_participation_bps: Option<u64>,
+) -> Option<Child> {
+    let remaining_slots = deadline.saturating_sub(current_slot);
+    if remaining == 0 || remaining_slots == 0 || end_slot_interval == 0 {
+        return None;
+    }
+
+    let target_end = current_slot + child_slots.min(remaining_slots);
+    let end_slot = target_end.div_ceil(end_slot_interval) * end_slot_interval;
+    let duration = end_slot - current_slot;
+
+    let time_weighted = (u128::from(remaining) * u128::from(duration)
+        / u128::from(remaining_slots.max(duration)))
+    .min(u128::from(remaining));
+    let (amount, capped) = match max_participation_bps {
+        Some(bps) => {
+            let max_flow = market_side_flow / FLOW_PRECISION * u128::from(bps) / 10_000;
+            let max_amount = max_flow.saturating_mul(u128::from(duration));
+            (time_weighted.min(max_amount), max_amount < time_weighted)
+        }
+        None => (time_weighted, false),
+    };
+
+    Some(Child {
+        amount: amount as u64,
+        end_slot,
+        capped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn children_are_time_weighted_and_aligned() {
+        let child = next_child(10_000, 1_005, 11_005, 1_000, 10, 0, None).unwrap();
+        assert_eq!(child.end_slot, 2_010);
+        assert_eq!(child.amount, 1_005);
+        assert!(!child.capped);
+
+        // Close to the deadline the last child takes everything left.
+        let last = next_child(500, 10_900, 11_005, 1_000, 10, 0, None).unwrap();
+        assert_eq!(last.end_slot, 11_010);
+        assert_eq!(last.amount, 500);
+
+        assert_eq!(next_child(500, 11_005, 11_005, 1_000, 10, 0, None), None);
+        assert_eq!(next_child(0, 1_000, 11_005, 1_000, 10, 0, None), None);
+    }
+
+    #[test]
+    fn participation_caps_child_flow() {
+        // 1000 raw units per slot flow on our side; 10% of that over 1000 slots is 100_000.
+        let child = next_child(
+            10_000_000,
+            1_000,
+            11_000,
+            1_000,
+            10,
+            1_000 * FLOW_PRECISION,
+            Some(1_000),
+        )
+        .unwrap();
+        assert_eq!(child.amount, 100_000);
+        assert!(child.capped);
+
+        let idle = next_child(10_000, 1_000, 11_000, 1_000, 10, 0, Some(1_000)).unwrap();
+        assert_eq!(idle.amount, 0);
+    }
+}
@@ -0,0 +1,123 @@
+//! Liveness checks for one bot: its heartbeat file, its heartbeat endpoint and whether its
+//! position's flows are still being updated on-chain.
+
+use std::{fmt, path::Path, time::Duration};
+
+use anchor_client::Program;
+use twob_market_making::{ProgramPayer, fetch_liquidity_position};
+
+use crate::config::Target;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Silence {
+    HeartbeatFileMissing(String),
+    HeartbeatFileStale { age: Duration },
+    HeartbeatUrlFailed(String),
+    FlowsStale { slots_since_update: u64 },
+}
+
+impl fmt::Display for Silence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Silence::HeartbeatFileMissing(error) => {
+                write!(f, "heartbeat file unreadable: {}", error)
+            }
+            Silence::HeartbeatFileStale { age } => {
+                write!(f, "heartbeat file last touched {}s ago", age.as_secs())
+            }
+            Silence::HeartbeatUrlFailed(error) => write!(f, "heartbeat endpoint failed: {}", error),
+            Silence::FlowsStale { slots_since_update } => write!(
+                f,
+                "flows last updated on-chain {} slots ago",
+                slots_since_update
+            ),
+        }
+    }
+}
+
+/// Run every check configured for `target`. An RPC failure while checking the flows is an
+/// error rather than silence: the watchdog can't tell anything about the bot then.
+pub async fn check_target(
+    program: &Program<ProgramPayer>,
+    http: &reqwest::Client,
+    target: &Target,
+    current_slot: u64,
+) -> anyhow::Result<Vec<Silence>> {
+    let mut silences = Vec::new();
+
+    if let Some(path) = &target.heartbeat_file {
+        silences.extend(heartbeat_file_silence(path, target.max_silence));
+    }
+
+    if let Some(url) = &target.heartbeat_url {
+        let response = http
+            .get(url)
+            .timeout(target.max_silence.min(Duration::from_secs(10)))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(error) = response {
+            silences.push(Silence::HeartbeatUrlFailed(error.to_string()));
+        }
+    }
+
+    if let Some(max_age) = target.max_update_age_slots {
+        let position =
+            fetch_liquidity_position(program, target.market_id, &target.authority).await?;
+        silences.extend(flows_silence(
+            position.last_update_slot,
+            current_slot,
+            max_age,
+        ));
+    }
+
+    Ok(silences)
+}
+
+fn heartbeat_file_silence(path: &Path, max_silence: Duration) -> Option<Silence> {
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    match modified {
+        Err(error) => Some(Silence::HeartbeatFileMissing(error.to_string())),
+        Ok(modified) => {
+            // A clock step backwards makes the file look fresh, which is the safe direction.
+            let age = modified.elapsed().unwrap_or_default();
+            (age > max_silence).then_some(Silence::HeartbeatFileStale { age })
+        }
+    }
+}
+
+fn flows_silence(last_update_slot: u64, current_slot: u64, max_age: u64) -> Option<Silence> {
+    let slots_since_update = current_slot.saturating_sub(last_update_slot);
+    (slots_since_update > max_age).then_some(Silence::FlowsStale { slots_since_update })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flows_are_stale_only_past_the_limit() {
+        assert_eq!(flows_silence(1_000, 1_500, 500), None);
+        assert_eq!(
+            flows_silence(1_000, 1_501, 500),
+            Some(Silence::FlowsStale {
+                slots_since_update: 501
+            })
+        );
+        assert_eq!(flows_silence(2_000, 1_000, 500), None);
+    }
+
+    #[test]
+    fn missing_heartbeat_file_is_silence() {
+        let path = std::env::temp_dir().join("twob-watchdog-missing-heartbeat");
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(
+            heartbeat_file_silence(&path, Duration::from_secs(60)),
+            Some(Silence::HeartbeatFileMissing(_))
+        ));
+
+        std::fs::write(&path, "now").unwrap();
+        assert_eq!(heartbeat_file_silence(&path, Duration::from_secs(60)), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use std::{path::PathBuf, time::Duration};
+
+use anchor_client::{
+    Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair},
+};
+use anyhow::Context;
+use serde::Deserialize;
+use twob_market_making::{
+    alerts::AlertConfig,
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    config::env_var,
+    tx::TxSenderConfig,
+};
+
+pub struct Config {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub targets: Vec<Target>,
+    pub check_interval: Duration,
+    /// The bots' authority, if the watchdog is trusted with it. Lets it zero a silent
+    /// position's flows directly instead of submitting a pre-signed transaction.
+    pub keypair: Option<Keypair>,
+    pub alerts: AlertConfig,
+    pub tx: TxSenderConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Remedy {
+    /// Only raise an alert.
+    #[default]
+    Alert,
+    /// Run `restart_command`; stop the position once `max_restarts` is used up.
+    Restart,
+    /// Stop the position straight away.
+    Stop,
+}
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    pub market_id: u64,
+    pub authority: Pubkey,
+    /// Touched by the bot every loop iteration (its `HEARTBEAT_FILE`).
+    pub heartbeat_file: Option<PathBuf>,
+    /// Polled with GET; any non-2xx response or transport error counts as silence.
+    pub heartbeat_url: Option<String>,
+    pub max_silence: Duration,
+    /// Flag the position when its flows haven't been updated on-chain for this many slots.
+    pub max_update_age_slots: Option<u64>,
+    pub on_silence: Remedy,
+    pub restart_command: Option<Vec<String>>,
+    pub max_restarts: u32,
+    /// A base64 bincode transaction zeroing the position's flows, signed ahead of time
+    /// against a durable nonce so it stays valid until used.
+    pub stop_transaction_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct TargetEntry {
+    name: String,
+    market_id: u64,
+    authority: String,
+    #[serde(default)]
+    heartbeat_file: Option<PathBuf>,
+    #[serde(default)]
+    heartbeat_url: Option<String>,
+    #[serde(default = "default_max_silence_secs")]
+    max_silence_secs: u64,
+    #[serde(default)]
+    max_update_age_slots: Option<u64>,
+    #[serde(default)]
+    on_silence: Remedy,
+    #[serde(default)]
+    restart_command: Option<Vec<String>>,
+    #[serde(default = "default_max_restarts")]
+    max_restarts: u32,
+    #[serde(default)]
+    stop_transaction_file: Option<PathBuf>,
+}
+
+fn default_max_silence_secs() -> u64 {
+    600
+}
+
+fn default_max_restarts() -> u32 {
+    3
+}
+
+#[derive(Deserialize)]
+struct TargetsFile {
+    targets: Vec<TargetEntry>,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let (rpc_url, ws_url) = endpoints_from_env(ClusterPreset::Localnet)?;
+
+        let keypair = env_var("WATCHDOG_KEYPAIR")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| -> anyhow::Result<Keypair> {
+                let bytes: Vec<u8> = serde_json::from_str(&value)?;
+                Keypair::try_from(bytes.as_slice())
+                    .map_err(|e| anyhow::anyhow!("Invalid keypair: {}", e))
+            })
+            .transpose()?;
+
+        let targets_path = env_var("WATCHDOG_CONFIG")
+            .map_err(|_| anyhow::anyhow!("WATCHDOG_CONFIG env var not set"))?;
+        let targets = parse_targets(
+            &std::fs::read_to_string(&targets_path)
+                .with_context(|| format!("Failed to read {}", targets_path))?,
+            keypair.is_some(),
+        )
+        .with_context(|| format!("Invalid watchdog config {}", targets_path))?;
+
+        let check_interval = Duration::from_secs(
+            env_var("WATCHDOG_CHECK_INTERVAL_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse::<u64>()?,
+        );
+
+        let alerts = AlertConfig::from_env()?;
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            targets,
+            check_interval,
+            keypair,
+            alerts,
+            tx: TxSenderConfig::from_env()?,
+            read_commitment: commitment_from_env("READ_COMMITMENT")?,
+        })
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
+
+fn parse_targets(json: &str, has_keypair: bool) -> anyhow::Result<Vec<Target>> {
+    let file: TargetsFile = serde_json::from_str(json)?;
+    if file.targets.is_empty() {
+        anyhow::bail!("no watchdog targets configured");
+    }
+
+    let mut names = std::collections::BTreeSet::new();
+    let mut targets = Vec::with_capacity(file.targets.len());
+    for entry in file.targets {
+        if !names.insert(entry.name.clone()) {
+            anyhow::bail!("duplicate target name `{}`", entry.name);
+        }
+        let authority = entry
+            .authority
+            .parse::<Pubkey>()
+            .map_err(|e| anyhow::anyhow!("{}: invalid authority: {}", entry.name, e))?;
+        if entry.heartbeat_file.is_none()
+            && entry.heartbeat_url.is_none()
+            && entry.max_update_age_slots.is_none()
+        {
+            anyhow::bail!(
+                "{}: set heartbeat_file, heartbeat_url or max_update_age_slots",
+                entry.name
+            );
+        }
+        let can_stop = has_keypair || entry.stop_transaction_file.is_some();
+        match entry.on_silence {
+            Remedy::Restart if entry.restart_command.as_ref().is_none_or(Vec::is_empty) => {
+                anyhow::bail!("{}: on_silence = restart needs restart_command", entry.name)
+            }
+            Remedy::Stop if !can_stop => anyhow::bail!(
+                "{}: on_silence = stop needs stop_transaction_file or WATCHDOG_KEYPAIR",
+                entry.name
+            ),
+            _ => {}
+        }
+
+        targets.push(Target {
+            name: entry.name,
+            market_id: entry.market_id,
+            authority,
+            heartbeat_file: entry.heartbeat_file,
+            heartbeat_url: entry.heartbeat_url,
+            max_silence: Duration::from_secs(entry.max_silence_secs),
+            max_update_age_slots: entry.max_update_age_slots,
+            on_silence: entry.on_silence,
+            restart_command: entry.restart_command,
+            max_restarts: entry.max_restarts,
+            stop_transaction_file: entry.stop_transaction_file,
+        });
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUTHORITY: &str = "CCAmAqvza37EWzou7LoYCaGKzdJsCu1CLPMp3Wvx3Bc5";
+
+    #[test]
+    fn parses_targets_and_validates_remedies() {
+        let targets = parse_targets(
+            &format!(
+                r#"{{"targets": [
+                    {{"name": "sol-usdc", "market_id": 1, "authority": "{AUTHORITY}",
+                      "heartbeat_file": "/run/twob/sol-usdc", "on_silence": "restart",
+                      "restart_command": ["systemctl", "restart", "oracle-flow@sol-usdc"]}},
+                    {{"name": "bonk-usdc", "market_id": 2, "authority": "{AUTHORITY}",
+                      "max_update_age_slots": 9000}}
+                ]}}"#
+            ),
+            false,
+        )
+        .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].on_silence, Remedy::Restart);
+        assert_eq!(targets[0].max_silence, Duration::from_secs(600));
+        assert_eq!(targets[1].on_silence, Remedy::Alert);
+
+        let stop = format!(
+            r#"{{"targets": [{{"name": "a", "market_id": 1, "authority": "{AUTHORITY}",
+                "heartbeat_url": "http://127.0.0.1:8080/healthz", "on_silence": "stop"}}]}}"#
+        );
+        assert!(parse_targets(&stop, false).is_err());
+        assert!(parse_targets(&stop, true).is_ok());
+
+        let no_checks = format!(
+            r#"{{"targets": [{{"name": "a", "market_id": 1, "authority": "{AUTHORITY}"}}]}}"#
+        );
+        assert!(parse_targets(&no_checks, true).is_err());
+    }
+}
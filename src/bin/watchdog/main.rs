@@ -0,0 +1,246 @@
+mod check;
+mod config;
+mod remedy;
+
+use std::{sync::Arc, time::Instant};
+
+use anchor_client::{
+    Client, Program,
+    solana_sdk::{pubkey::Pubkey, signer::Signer},
+};
+use config::{Config, Remedy, Target};
+use tokio::{signal, time::sleep};
+use tracing::{error, info, warn};
+use twob_market_making::{
+    ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    program_payer, twob_anchor,
+    tx::TxSender,
+};
+
+/// Per-target escalation state, reset as soon as the bot looks alive again.
+#[derive(Debug, Default)]
+struct TargetState {
+    restarts: u32,
+    last_remedy_at: Option<Instant>,
+    stopped: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    twob_market_making::telemetry::init_logging()?;
+
+    let config = Config::from_env()?;
+    let keypair = config
+        .keypair
+        .as_ref()
+        .map(|keypair| Arc::new(keypair.insecure_clone()));
+    // Without a keypair the watchdog only reads accounts and submits pre-signed
+    // transactions, so a placeholder payer is enough for the client.
+    let payer = keypair
+        .as_ref()
+        .map_or_else(Pubkey::default, |keypair| keypair.pubkey());
+    let client = Client::new_with_options(
+        config.cluster(),
+        program_payer(payer),
+        config.read_commitment,
+    );
+    let program = client.program(twob_anchor::ID)?;
+    let alerter = Alerter::from_config("watchdog", &config.alerts)?;
+    let sender = keypair
+        .clone()
+        .map(|keypair| TxSender::for_program(&program, keypair, config.tx.clone()))
+        .transpose()?
+        .map(|sender| sender.with_alerter(alerter.clone()));
+    let http = reqwest::Client::new();
+    let mut states: Vec<TargetState> = config
+        .targets
+        .iter()
+        .map(|_| TargetState::default())
+        .collect();
+
+    info!(
+        event.name = "watchdog_started",
+        watchdog.targets = config.targets.len(),
+        watchdog.check_interval_secs = config.check_interval.as_secs(),
+        watchdog.has_keypair = keypair.is_some(),
+    );
+
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!(event.name = "watchdog_shutdown");
+                break;
+            }
+            _ = sleep(config.check_interval) => {
+                let current_slot = match program.rpc().get_slot().await {
+                    Ok(slot) => slot,
+                    Err(error) => {
+                        warn!(event.name = "watchdog_slot_fetch_failed", ?error);
+                        alerter.notify(
+                            AlertKind::RpcDown,
+                            None,
+                            format!("watchdog can't reach the RPC: {error}"),
+                        );
+                        continue;
+                    }
+                };
+                for (target, state) in config.targets.iter().zip(states.iter_mut()) {
+                    watch_target(
+                        &program,
+                        &http,
+                        target,
+                        state,
+                        current_slot,
+                        sender.as_ref(),
+                        &alerter,
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn watch_target(
+    program: &Program<ProgramPayer>,
+    http: &reqwest::Client,
+    target: &Target,
+    state: &mut TargetState,
+    current_slot: u64,
+    sender: Option<&TxSender>,
+    alerter: &Alerter,
+) {
+    let silences = match check::check_target(program, http, target, current_slot).await {
+        Ok(silences) => silences,
+        Err(error) => {
+            warn!(
+                event.name = "watchdog_check_failed",
+                target.name = %target.name,
+                market.id = target.market_id,
+                ?error,
+            );
+            return;
+        }
+    };
+
+    if silences.is_empty() {
+        if state.restarts > 0 || state.stopped {
+            info!(
+                event.name = "watchdog_target_recovered",
+                target.name = %target.name,
+                market.id = target.market_id,
+                watchdog.restarts = state.restarts,
+            );
+        }
+        *state = TargetState::default();
+        return;
+    }
+
+    let reasons = silences
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    warn!(
+        event.name = "watchdog_target_silent",
+        target.name = %target.name,
+        market.id = target.market_id,
+        watchdog.reasons = %reasons,
+        monotonic_counter.watchdog_silences_total = 1_u64,
+    );
+
+    if state.stopped {
+        return;
+    }
+    // Give the last remedy as long to take effect as the bot is allowed to stay silent.
+    if state
+        .last_remedy_at
+        .is_some_and(|at| at.elapsed() < target.max_silence)
+    {
+        return;
+    }
+    state.last_remedy_at = Some(Instant::now());
+
+    let can_stop = sender.is_some() || target.stop_transaction_file.is_some();
+    match remedy::plan(target, state.restarts, can_stop) {
+        Remedy::Alert => alerter.notify(
+            AlertKind::BotSilent,
+            Some(target.market_id),
+            format!("{} is silent: {}", target.name, reasons),
+        ),
+        Remedy::Restart => {
+            state.restarts += 1;
+            let result = remedy::restart(target).await;
+            match &result {
+                Ok(()) => info!(
+                    event.name = "watchdog_target_restarted",
+                    target.name = %target.name,
+                    market.id = target.market_id,
+                    watchdog.restarts = state.restarts,
+                    monotonic_counter.watchdog_restarts_total = 1_u64,
+                ),
+                Err(error) => error!(
+                    event.name = "watchdog_restart_failed",
+                    target.name = %target.name,
+                    market.id = target.market_id,
+                    ?error,
+                ),
+            }
+            alerter.notify(
+                AlertKind::BotSilent,
+                Some(target.market_id),
+                format!(
+                    "{} is silent ({}); restart {}/{} {}",
+                    target.name,
+                    reasons,
+                    state.restarts,
+                    target.max_restarts,
+                    match &result {
+                        Ok(()) => "issued".to_string(),
+                        Err(error) => format!("failed: {error:#}"),
+                    }
+                ),
+            );
+        }
+        Remedy::Stop => match remedy::stop(program, target, sender).await {
+            Ok(outcome) => {
+                state.stopped = true;
+                info!(
+                    event.name = "watchdog_position_stopped",
+                    target.name = %target.name,
+                    market.id = target.market_id,
+                    watchdog.outcome = %outcome,
+                    monotonic_counter.watchdog_stops_total = 1_u64,
+                );
+                alerter.notify(
+                    AlertKind::StopExecuted,
+                    Some(target.market_id),
+                    format!(
+                        "{} is silent ({}); stopped: {}",
+                        target.name, reasons, outcome
+                    ),
+                );
+            }
+            Err(error) => {
+                error!(
+                    event.name = "watchdog_stop_failed",
+                    target.name = %target.name,
+                    market.id = target.market_id,
+                    ?error,
+                );
+                alerter.notify(
+                    AlertKind::StopFailed,
+                    Some(target.market_id),
+                    format!(
+                        "{} is silent ({}); stop failed: {error:#}",
+                        target.name, reasons
+                    ),
+                );
+            }
+        },
+    }
+}
@@ -0,0 +1,140 @@
+use std::{path::Path, process::Stdio};
+
+use anchor_client::{Program, solana_sdk::transaction::Transaction};
+use anyhow::Context;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tokio::process::Command;
+use twob_market_making::{
+    ProgramPayer,
+    risk::pull_all_quotes,
+    tx::{TxIntent, TxSender},
+};
+
+use crate::config::{Remedy, Target};
+
+/// What to do about a silent bot, given how many restarts it has already had.
+/// A restart policy escalates to a stop once `max_restarts` is used up, or to an alert
+/// when the watchdog has no way to stop the position.
+pub fn plan(target: &Target, restarts: u32, can_stop: bool) -> Remedy {
+    match target.on_silence {
+        Remedy::Restart if restarts < target.max_restarts => Remedy::Restart,
+        Remedy::Restart | Remedy::Stop if can_stop => Remedy::Stop,
+        Remedy::Restart | Remedy::Stop | Remedy::Alert => Remedy::Alert,
+    }
+}
+
+pub async fn restart(target: &Target) -> anyhow::Result<()> {
+    let Some((program, args)) = target
+        .restart_command
+        .as_deref()
+        .and_then(|command| command.split_first())
+    else {
+        anyhow::bail!("{} has no restart_command", target.name);
+    };
+
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("Failed to run restart command for {}", target.name))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "restart command for {} exited with {}: {}",
+            target.name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Stop the target's position: submit its pre-signed stop transaction if it has one,
+/// otherwise zero its flows with the watchdog's own keypair. Returns the signature or a
+/// short description of what was done.
+pub async fn stop(
+    program: &Program<ProgramPayer>,
+    target: &Target,
+    sender: Option<&TxSender>,
+) -> anyhow::Result<String> {
+    if let Some(path) = &target.stop_transaction_file {
+        let transaction = read_presigned_transaction(path)?;
+        let signature = match sender {
+            Some(sender) => {
+                let intent = TxIntent::new("presigned_stop", target.market_id);
+                sender.send_presigned(&intent, &transaction).await
+            }
+            None => program
+                .rpc()
+                .send_and_confirm_transaction(&transaction)
+                .await
+                .map_err(anyhow::Error::from),
+        }
+        .with_context(|| format!("Failed to submit pre-signed stop for {}", target.name))?;
+        return Ok(signature.to_string());
+    }
+
+    let Some(sender) = sender else {
+        anyhow::bail!(
+            "{} has no stop_transaction_file and WATCHDOG_KEYPAIR is not set",
+            target.name
+        );
+    };
+    if sender.payer() != target.authority {
+        anyhow::bail!(
+            "WATCHDOG_KEYPAIR {} is not {}'s authority {}",
+            sender.payer(),
+            target.name,
+            target.authority
+        );
+    }
+    pull_all_quotes(program, &[target.market_id], sender).await?;
+    Ok("flows zeroed".to_string())
+}
+
+fn read_presigned_transaction(path: &Path) -> anyhow::Result<Transaction> {
+    let encoded = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .with_context(|| format!("{} is not base64", path.display()))?;
+    bincode::deserialize(&bytes)
+        .with_context(|| format!("{} is not a serialized transaction", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anchor_client::solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    fn target(on_silence: Remedy) -> Target {
+        Target {
+            name: "sol-usdc".to_string(),
+            market_id: 1,
+            authority: Pubkey::new_unique(),
+            heartbeat_file: None,
+            heartbeat_url: None,
+            max_silence: Duration::from_secs(600),
+            max_update_age_slots: Some(9_000),
+            on_silence,
+            restart_command: Some(vec!["true".to_string()]),
+            max_restarts: 2,
+            stop_transaction_file: None,
+        }
+    }
+
+    #[test]
+    fn restarts_escalate_to_stop_when_possible() {
+        let restart = target(Remedy::Restart);
+        assert_eq!(plan(&restart, 0, true), Remedy::Restart);
+        assert_eq!(plan(&restart, 1, false), Remedy::Restart);
+        assert_eq!(plan(&restart, 2, true), Remedy::Stop);
+        assert_eq!(plan(&restart, 2, false), Remedy::Alert);
+
+        assert_eq!(plan(&target(Remedy::Stop), 0, true), Remedy::Stop);
+        assert_eq!(plan(&target(Remedy::Alert), 5, true), Remedy::Alert);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{
+    config::{CommonConfig, ConfigErrors, InventoryFlowSection, env_var},
+    telemetry::LogFormat,
+};
+
+pub struct Config {
+    pub common: CommonConfig,
+    pub strategy: InventoryFlowSection,
+    /// Service name traces and metrics are exported under.
+    pub service_name: String,
+    /// Log to stdout as JSON rather than plain lines.
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DelayConfig {
+    pub critical_threshold: u128,
+    pub safe_threshold: u128,
+    pub critical_delay_ms: u128,
+    pub normal_delay_ms: u128,
+    pub delay_scale_factor: u128,
+    pub max_additional_slots: u128,
+}
+
+impl Default for DelayConfig {
+    fn default() -> Self {
+        Self {
+            critical_threshold: 25,
+            safe_threshold: 10_000,
+            critical_delay_ms: 100,
+            normal_delay_ms: 2_000,
+            delay_scale_factor: 400,
+            max_additional_slots: 1_000,
+        }
+    }
+}
+
+impl Config {
+    /// Read and validate everything, reporting every problem found rather than the first.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let service_name = env_var("OTEL_SERVICE_NAME")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| "twob-inventory-flow".to_string());
+
+        ConfigErrors::collect(|errors| {
+            let common = CommonConfig::read("INVENTORY_FLOW_KEYPAIR", errors);
+            let strategy = InventoryFlowSection::read(errors);
+            let log_format = errors.check("LOG_FORMAT", LogFormat::from_env(LogFormat::Pretty));
+            Some(Self {
+                common: common?,
+                strategy: strategy?,
+                service_name,
+                log_format: log_format?,
+            })
+        })
+    }
+}
@@ -0,0 +1,1022 @@
+//! Streams a TwoB position's inventory back into its market in proportion to what it
+//! holds. Runs as its own binary or as a task of the orchestrator.
+
+mod config;
+mod position;
+mod strategy;
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use anchor_client::{
+    Client, Program,
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+};
+use config::{Config, DelayConfig};
+use position::{PositionSnapshot, TopUp, fetch_snapshot, zero_flows};
+use strategy::InventoryFlowStrategy;
+use tokio::{
+    sync::mpsc,
+    task::{JoinHandle, JoinSet},
+    time::sleep,
+};
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::{
+    LiquidityPositionBalances, ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    bots::runtime::{StrategyRuntime, Trigger},
+    config::{FlowArgs, JitterConfig, inherit},
+    control::{
+        CircuitBreaker, ControlState, admin, emergency::EmergencyStop, heartbeat::HeartbeatPinger,
+        probes, telegram, write_heartbeat,
+    },
+    crank,
+    event_bus::BusEvent,
+    execute_open_next_window, execute_stop_position,
+    reporting::Reporting,
+    risk::{PositionExposure, RiskEngine, pull_all_quotes},
+    rotation::rotate,
+    slot_lag::{self, Failover},
+    strategy::{Action, Strategy, execute_action},
+    subscription::{SubscriptionTracker, backfill_market_updates},
+    supervisor::{KillSwitch, Launch},
+    telemetry,
+    turnover::record_flow_change,
+    twob_anchor::{self, events::MarketUpdateEvent},
+    tx::{SendOptions, TxSender},
+    verify_flows,
+    webhooks::LifecycleEvent,
+};
+
+/// How often the periodic task rebalances flows without a market event.
+const PERIODIC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Most market transactions searched for events missed while the subscription was down.
+const BACKFILL_LIMIT: usize = 200;
+
+/// Run the bot until `kill` trips, it is force stopped or it fails. Its position is held to
+/// `risk` with the other positions of the process; `None` gives it an engine of its own with
+/// the limits in its config.
+pub async fn run(
+    flow: FlowArgs,
+    kill: KillSwitch,
+    launch: Launch,
+    risk: Option<Arc<RiskEngine>>,
+) -> anyhow::Result<()> {
+    let Some(config) = load(&flow).await? else {
+        return Ok(());
+    };
+    let strategy = InventoryFlowStrategy::new(config.strategy.flow_divisor, DelayConfig::default());
+    start(config, Box::new(strategy), kill, launch, risk).await
+}
+
+/// [`run`] with `strategy` deciding in place of inventory-flow's own. It is asked about
+/// each market update ([`Strategy::on_market_event`]) and on each periodic tick or
+/// reevaluation it asked for ([`Strategy::on_tick`]).
+pub async fn run_strategy(
+    strategy: Box<dyn Strategy>,
+    flow: FlowArgs,
+    kill: KillSwitch,
+    launch: Launch,
+    risk: Option<Arc<RiskEngine>>,
+) -> anyhow::Result<()> {
+    let Some(config) = load(&flow).await? else {
+        return Ok(());
+    };
+    start(config, strategy, kill, launch, risk).await
+}
+
+/// Apply `flow` and read the config, or `None` when `flow` asked for a config mode, which
+/// has run instead.
+async fn load(flow: &FlowArgs) -> anyhow::Result<Option<Config>> {
+    flow.apply();
+    if crate::config::run_config_mode(
+        flow,
+        "inventory-flow",
+        "INVENTORY_FLOW_KEYPAIR",
+        Config::from_env,
+    )? {
+        return Ok(None);
+    }
+    crate::config::load_env("inventory-flow").await?;
+    Ok(Some(Config::from_env()?))
+}
+
+async fn start(
+    config: Config,
+    strategy: Box<dyn Strategy>,
+    kill: KillSwitch,
+    launch: Launch,
+    risk: Option<Arc<RiskEngine>>,
+) -> anyhow::Result<()> {
+    let reporting = Reporting::start(&config.common, "inventory-flow")?;
+    if let (Launch::Process, Some(crash_dump)) = (launch, &reporting.crash_dump) {
+        crash_dump.install_panic_hook();
+    }
+    let risk = risk.unwrap_or_else(|| RiskEngine::new(config.common.risk_limits));
+    let runtime = StrategyRuntime::new(strategy, risk);
+    let result = trade(config, runtime, &reporting, kill, launch).await;
+    if let Err(error) = &result {
+        reporting.write_crash_dump(&format!("fatal error: {error:#}"));
+    }
+    result
+}
+
+async fn trade(
+    config: Config,
+    runtime: StrategyRuntime,
+    reporting: &Reporting,
+    kill: KillSwitch,
+    launch: Launch,
+) -> anyhow::Result<()> {
+    let _telemetry_guard = match launch {
+        Launch::Process => Some(telemetry::init_telemetry(telemetry::TelemetryInitConfig {
+            service_name: config.service_name.clone(),
+            bot_role: "inventory-flow",
+            log_format: config.log_format,
+            market_id: config.common.market_id,
+            authority: config.common.signer.pubkey().to_string(),
+            rpc_url: config.common.rpc_url.clone(),
+            program_id: twob_anchor::ID.to_string(),
+        })?),
+        Launch::Task => None,
+    };
+    let market_id = config.common.market_id;
+    let top_up = TopUp::from_section(&config.strategy);
+    let api_bind_addr = config.common.api_bind_addr;
+    let control_bind_addr = config.common.control_bind_addr;
+    let admin_config = config.common.admin.clone();
+    let probe_config = config.common.probes.clone();
+    let emergency_stop_config = config.common.emergency_stop;
+    let telegram_control = config.common.telegram_control.clone();
+    let circuit_breaker_max_failures = config.common.circuit_breaker_max_failures;
+    let jitter = config.common.jitter;
+    let heartbeat_file = config.common.heartbeat_file.clone();
+    let heartbeat_ping = config
+        .common
+        .heartbeat_ping
+        .clone()
+        .map(|config| Arc::new(HeartbeatPinger::new(config)));
+    let slot_lag_config = config.common.slot_lag.clone();
+    let crank_config = config.common.crank.clone();
+    let slot_lag_rpc_url = config.common.rpc_url.clone();
+    let alerter = Alerter::from_config("inventory-flow", &config.common.alerts)?;
+    let (mut client, sender) = config
+        .common
+        .connect(config.common.signer.clone(), &alerter, reporting)
+        .await?;
+    let failover = Failover::default();
+    let mut sender = Arc::new(sender);
+    let mut authority = sender.payer();
+
+    // The servers and monitors go with the run, as the orchestrator may start another, and
+    // keep its settings.
+    let mut background = JoinSet::new();
+    #[cfg(feature = "api")]
+    if let Some(addr) = api_bind_addr {
+        let api_client = client.clone();
+        background.spawn(inherit(async move {
+            if let Err(error) = crate::api::serve(addr, api_client).await {
+                error!(event.name = "api_server_failed", api.addr = %addr, ?error);
+            }
+        }));
+    }
+    #[cfg(not(feature = "api"))]
+    if api_bind_addr.is_some() {
+        warn!(
+            event.name = "api_server_unavailable",
+            reason = "built_without_api_feature",
+        );
+    }
+
+    let mut subscription_program = client.program(twob_anchor::ID)?;
+
+    let control = ControlState::new("inventory-flow", market_id, authority);
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = control_bind_addr {
+        let control = control.clone();
+        background.spawn(inherit(async move {
+            if let Err(error) = crate::control::grpc::serve(addr, control).await {
+                error!(event.name = "control_server_failed", control.addr = %addr, ?error);
+            }
+        }));
+    }
+    #[cfg(not(feature = "grpc"))]
+    if control_bind_addr.is_some() {
+        warn!(
+            event.name = "control_server_unavailable",
+            reason = "built_without_grpc_feature",
+        );
+    }
+    if let Some(admin_config) = admin_config {
+        let control = control.clone();
+        background.spawn(inherit(async move {
+            let addr = admin_config.addr.clone();
+            if let Err(error) = admin::serve(admin_config, control).await {
+                error!(event.name = "admin_server_failed", admin.addr = %addr, ?error);
+            }
+        }));
+    }
+    if let Some(probe_config) = probe_config {
+        let (control, rpc) = (control.clone(), subscription_program.rpc());
+        background.spawn(inherit(async move {
+            let addr = probe_config.bind_addr;
+            if let Err(error) = probes::serve(probe_config, control, rpc).await {
+                error!(event.name = "probe_server_failed", probe.addr = %addr, ?error);
+            }
+        }));
+    }
+    if let Some(slot_lag_config) = slot_lag_config {
+        background.spawn(inherit(slot_lag::monitor(
+            slot_lag_config,
+            slot_lag_rpc_url,
+            failover.clone(),
+            alerter.clone(),
+            market_id,
+        )));
+    }
+    if let Some(telegram_config) = telegram_control {
+        let control = control.clone();
+        background.spawn(inherit(async move {
+            if let Err(error) = telegram::serve(telegram_config, control).await {
+                error!(event.name = "telegram_control_failed", ?error);
+            }
+        }));
+    }
+
+    // Sends the previous run was still waiting on are settled before new ones go out.
+    if let Err(error) = sender.reconcile_journal().await {
+        warn!(event.name = "tx_journal_reconcile_failed", ?error);
+    }
+
+    let emergency_stop = match emergency_stop_config {
+        Some(config) => {
+            let stop =
+                EmergencyStop::prepare(config, &subscription_program, market_id, &sender).await?;
+            if launch == Launch::Process {
+                stop.install_panic_hook();
+            }
+            Some(stop)
+        }
+        None => None,
+    };
+
+    // Event-driven updates
+    // Recalculates update timing when market state changes
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut event_unsubscriber = Some(
+        subscription_program
+            .on(move |ctx, event: MarketUpdateEvent| {
+                let _ = tx.send((ctx.signature, ctx.slot, event));
+            })
+            .await?,
+    );
+    control.set_subscribed(true);
+    let mut subscription =
+        SubscriptionTracker::new("market_update", &config.common.ws_url, market_id);
+
+    // Periodic update task
+    // Keeps inventory balanced within acceptable bounds
+    let mut periodic = PeriodicTask {
+        client: client.clone(),
+        sender: sender.clone(),
+        authority,
+        control: control.clone(),
+        alerter: alerter.clone(),
+        emergency_stop: emergency_stop.clone(),
+        failover: failover.clone(),
+        heartbeat_file,
+        heartbeat_ping,
+        market_id,
+        runtime: runtime.clone(),
+        top_up: top_up.clone(),
+        circuit_breaker_max_failures,
+        jitter,
+    };
+    let mut update_flows_task = tokio::spawn(inherit(periodic_updates(periodic.clone())));
+    // Like the periodic task, the crank sends as the current authority and restarts with
+    // a key rotation.
+    let spawn_crank = |client: &Arc<Client<ProgramPayer>>, sender: &Arc<TxSender>| {
+        crank_config.clone().map(|config| {
+            tokio::spawn(inherit(crank::run(
+                config,
+                client.clone(),
+                market_id,
+                sender.clone(),
+            )))
+        })
+    };
+    let mut crank_task = spawn_crank(&client, &sender);
+
+    let mut current_task: Option<JoinHandle<()>> = None;
+
+    let result = loop {
+        tokio::select! {
+            _ = kill.triggered() => {
+                info!(event.name = "inventory_flow_shutdown");
+                break Ok(());
+            }
+            rotation = control.key_rotation_requested() => {
+                info!(
+                    event.name = "inventory_flow_key_rotation_started",
+                    market.id = market_id,
+                    lp.authority = %authority,
+                );
+                if let Some(handle) = current_task.take() {
+                    handle.abort();
+                }
+                update_flows_task.abort();
+                if let Some(task) = crank_task.take() {
+                    task.abort();
+                }
+                let program = match client.program(twob_anchor::ID) {
+                    Ok(program) => program,
+                    Err(error) => break Err(error.into()),
+                };
+                let rotated = rotate(
+                    &config.common,
+                    &rotation,
+                    &program,
+                    &sender,
+                    emergency_stop.as_deref(),
+                    &alerter,
+                    &control,
+                )
+                .await;
+                if let Some(rotated) = rotated {
+                    client = rotated.client;
+                    sender = Arc::new(rotated.sender);
+                    authority = sender.payer();
+                    periodic.client = client.clone();
+                    periodic.sender = sender.clone();
+                    periodic.authority = authority;
+                }
+                update_flows_task = tokio::spawn(inherit(periodic_updates(periodic.clone())));
+                crank_task = spawn_crank(&client, &sender);
+            }
+            _ = control.force_stopped() => {
+                warn!(
+                    event.name = "inventory_flow_force_stop",
+                    market.id = market_id,
+                    lp.authority = %authority,
+                );
+                if let Some(handle) = current_task.take() {
+                    handle.abort();
+                }
+                let program = match client.program(twob_anchor::ID) {
+                    Ok(program) => program,
+                    Err(error) => break Err(error.into()),
+                };
+                if let Err(error) = zero_flows(&program, market_id, &sender).await {
+                    error!(
+                        event.name = "inventory_flow_zero_flows_failed",
+                        market.id = market_id,
+                        ?error,
+                    );
+                    reporting.write_crash_dump(&format!("zeroing flows failed: {error:#}"));
+                    if let Some(stop) = &emergency_stop {
+                        stop.trigger(format!("zeroing flows failed: {error:#}")).await;
+                    }
+                }
+                break Ok(());
+            }
+            result = &mut update_flows_task => {
+                match result {
+                    Ok(_) => info!(event.name = "inventory_flow_periodic_task_completed"),
+                    Err(error) => {
+                        error!(
+                            event.name = "inventory_flow_periodic_task_failed",
+                            task.panicked = error.is_panic(),
+                            ?error,
+                        );
+                        // The panic hook has already fired for a panic; this covers a
+                        // task cancelled from under us.
+                        if !error.is_panic() {
+                            reporting.write_crash_dump(&format!("periodic task failed: {error}"));
+                        }
+                        if let Some(stop) = &emergency_stop {
+                            stop.trigger(format!("periodic task failed: {error}")).await;
+                        }
+                    }
+                }
+                break Ok(());
+            }
+            event = rx.recv() => {
+                let Some((signature, slot, event)) = event else {
+                    subscription.disconnected();
+                    control.set_subscribed(false);
+
+                    if let Some(handle) = current_task.take() {
+                        handle.abort();
+                    }
+
+                    if let Some(unsubscriber) = event_unsubscriber.take() {
+                        drop(unsubscriber);
+                    }
+
+                    let mut attempts = 0;
+                    loop {
+                        attempts += 1;
+                        subscription_program = match client.program(twob_anchor::ID) {
+                            Ok(p) => p,
+                            Err(error) => {
+                                error!(event.name = "program_client_failed", ?error);
+                                sleep(Duration::from_secs(5)).await;
+                                continue;
+                            }
+                        };
+
+                        let (new_tx, new_rx) = mpsc::unbounded_channel();
+                        let replay_tx = new_tx.clone();
+                        match subscription_program
+                            .on(move |ctx, event: MarketUpdateEvent| {
+                                let _ = new_tx.send((ctx.signature, ctx.slot, event));
+                            })
+                            .await
+                        {
+                            Ok(unsubscriber) => {
+                                rx = new_rx;
+                                event_unsubscriber = Some(unsubscriber);
+                                control.set_subscribed(true);
+                                subscription.resubscribed(attempts);
+                                backfill(
+                                    &subscription_program,
+                                    market_id,
+                                    &mut subscription,
+                                    &replay_tx,
+                                )
+                                .await;
+                                break;
+                            }
+                            Err(error) => {
+                                error!(
+                                    event.name = "market_events_resubscribe_failed",
+                                    market.id = market_id,
+                                    ?error,
+                                );
+                                sleep(Duration::from_secs(5)).await;
+                            }
+                        }
+                    }
+
+                    continue;
+                };
+                subscription.on_event(signature, slot);
+                reporting.publish(BusEvent::MarketUpdate {
+                    market_id: event.market_id,
+                    base_flow: event.base_flow,
+                    quote_flow: event.quote_flow,
+                    slot: Some(slot),
+                    signature: Some(signature.to_string()),
+                });
+
+                if control.is_paused() {
+                    continue;
+                }
+
+                if let Some(handle) = current_task.take() {
+                    handle.abort();
+                }
+
+                let program = match read_program(&client, &failover) {
+                    Ok(p) => p,
+                    Err(error) => {
+                        error!(event.name = "program_client_failed", ?error);
+                        continue;
+                    }
+                };
+
+                let event_span = info_span!(
+                    "inventory_flow.market_event",
+                    market.id = market_id,
+                    lp.authority = %authority,
+                    slot,
+                );
+                let snapshot = match fetch_snapshot(
+                    &program,
+                    market_id,
+                    &authority,
+                    reporting.crash_dump.as_ref(),
+                )
+                    .instrument(info_span!(
+                        parent: &event_span,
+                        "state.fetch",
+                        market.id = market_id,
+                    ))
+                    .await
+                {
+                    Ok(snapshot) => snapshot,
+                    Err(e) => {
+                        error!(
+                            parent: &event_span,
+                            event.name = "inventory_flow_evaluate_failed",
+                            error = ?e,
+                        );
+                        alerter.notify(
+                            AlertKind::RpcDown,
+                            Some(market_id),
+                            format!("failed to evaluate position: {e:#}"),
+                        );
+                        continue;
+                    }
+                };
+
+                if let Err(error) = check_risk(&program, &runtime, &snapshot, &sender, &alerter)
+                    .instrument(event_span.clone())
+                    .await
+                {
+                    error!(
+                        parent: &event_span,
+                        event.name = "inventory_flow_risk_limit_breached",
+                        ?error,
+                    );
+                    continue;
+                }
+
+                let mut stopped = false;
+                let actions = event_span.in_scope(|| {
+                    runtime.decide(
+                        Trigger::MarketEvent(&event),
+                        &snapshot.context(),
+                        &control.thresholds(),
+                        sender.reporting(),
+                    )
+                });
+                for action in actions {
+                    if let Action::Reevaluate { after } = action {
+                        let client = client.clone();
+                        let sender = sender.clone();
+                        let alerter = alerter.clone();
+                        let control = control.clone();
+                        let top_up = top_up.clone();
+                        let failover = failover.clone();
+                        let runtime = runtime.clone();
+                        current_task = Some(tokio::spawn(inherit(async move {
+                            sleep(after).await;
+
+                            let program = match read_program(&client, &failover) {
+                                Ok(p) => p,
+                                Err(error) => {
+                                    error!(event.name = "program_client_failed", ?error);
+                                    return;
+                                }
+                            };
+
+                            let _ = run_tick(
+                                &program,
+                                &runtime,
+                                market_id,
+                                top_up.as_ref(),
+                                &sender,
+                                &alerter,
+                                &control,
+                            )
+                            .instrument(info_span!(
+                                "inventory_flow.reevaluate",
+                                market.id = market_id,
+                                lp.authority = %authority,
+                            ))
+                            .await;
+                        })));
+                        continue;
+                    }
+
+                    if let Ok(true) = apply_actions(
+                        &program,
+                        &snapshot,
+                        vec![action],
+                        &sender,
+                        &alerter,
+                        &control,
+                    )
+                    .instrument(event_span.clone())
+                    .await
+                    {
+                        stopped = true;
+                        break;
+                    }
+                }
+                if stopped {
+                    break Ok(());
+                }
+            }
+        }
+    };
+
+    // Cleanup
+    if let Some(task) = current_task.take() {
+        task.abort();
+    }
+    update_flows_task.abort();
+    if let Some(task) = crank_task.take() {
+        task.abort();
+    }
+
+    result
+}
+
+/// What the periodic task runs with. A key rotation swaps in the new client, sender and
+/// authority and restarts the task.
+#[derive(Clone)]
+struct PeriodicTask {
+    client: Arc<Client<ProgramPayer>>,
+    sender: Arc<TxSender>,
+    authority: Pubkey,
+    control: Arc<ControlState>,
+    alerter: Alerter,
+    emergency_stop: Option<Arc<EmergencyStop>>,
+    failover: Failover,
+    heartbeat_file: Option<PathBuf>,
+    heartbeat_ping: Option<Arc<HeartbeatPinger>>,
+    market_id: u64,
+    runtime: StrategyRuntime,
+    top_up: Option<TopUp>,
+    circuit_breaker_max_failures: u32,
+    jitter: JitterConfig,
+}
+
+/// `client`'s program, on the reference endpoint while `failover` has the bot's reads there.
+fn read_program(
+    client: &Client<ProgramPayer>,
+    failover: &Failover,
+) -> anyhow::Result<Program<ProgramPayer>> {
+    failover.reads(client.program(twob_anchor::ID)?)
+}
+
+async fn periodic_updates(task: PeriodicTask) {
+    let mut circuit_breaker = CircuitBreaker::new(task.circuit_breaker_max_failures);
+    let mut forced = false;
+    loop {
+        if let Some(Err(error)) = task.heartbeat_file.as_deref().map(write_heartbeat) {
+            warn!(event.name = "heartbeat_write_failed", ?error);
+        }
+        if let Some(pinger) = &task.heartbeat_ping {
+            pinger.ping();
+        }
+
+        if task.control.is_paused() && !forced {
+            info!(
+                event.name = "inventory_flow_cycle_skipped",
+                market.id = task.market_id,
+                lp.authority = %task.authority,
+                reason = "paused",
+            );
+            forced = task
+                .control
+                .next_cycle(task.jitter.apply(PERIODIC_INTERVAL))
+                .await;
+            continue;
+        }
+
+        let program = match read_program(&task.client, &task.failover) {
+            Ok(p) => p,
+            Err(error) => {
+                error!(event.name = "program_client_failed", ?error);
+                sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let cycle = run_tick(
+            &program,
+            &task.runtime,
+            task.market_id,
+            task.top_up.as_ref(),
+            &task.sender,
+            &task.alerter,
+            &task.control,
+        )
+        .instrument(info_span!(
+            "inventory_flow.tick",
+            market.id = task.market_id,
+            lp.authority = %task.authority,
+        ))
+        .await;
+        task.control.record_cycle(&cycle);
+        if let Some(stop) = &task.emergency_stop
+            && let Err(error) = stop.refresh(&program, &task.sender).await
+        {
+            warn!(
+                event.name = "emergency_stop_refresh_failed",
+                market.id = task.market_id,
+                ?error,
+            );
+        }
+
+        match &cycle {
+            Ok(true) => return,
+            Ok(false) => info!(
+                event.name = "inventory_flow_tick_completed",
+                market.id = task.market_id,
+                lp.authority = %task.authority,
+                cycle.outcome = "ok",
+                monotonic_counter.cycles_total = 1_u64,
+            ),
+            Err(_) => warn!(
+                event.name = "inventory_flow_tick_failed",
+                market.id = task.market_id,
+                lp.authority = %task.authority,
+                cycle.outcome = "error",
+                monotonic_counter.cycles_total = 1_u64,
+            ),
+        }
+
+        if circuit_breaker.record_cycle(&cycle) {
+            task.control.pause();
+            error!(
+                event.name = "inventory_flow_circuit_breaker_tripped",
+                market.id = task.market_id,
+                lp.authority = %task.authority,
+                circuit_breaker.consecutive_failures = circuit_breaker.consecutive_failures(),
+                monotonic_counter.circuit_breaker_trips_total = 1_u64,
+            );
+            task.alerter.notify(
+                AlertKind::CircuitBreakerTripped,
+                Some(task.market_id),
+                format!(
+                    "{} consecutive failed cycles; bot paused until resumed via the control plane",
+                    circuit_breaker.consecutive_failures()
+                ),
+            );
+        }
+
+        forced = task
+            .control
+            .next_cycle(task.jitter.apply(PERIODIC_INTERVAL))
+            .await;
+    }
+}
+
+/// Recover the market events missed since the last one heard and replay the newest, which
+/// supersedes the rest since the strategy works from current state anyway.
+async fn backfill(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    subscription: &mut SubscriptionTracker,
+    replay: &mpsc::UnboundedSender<(Signature, u64, MarketUpdateEvent)>,
+) {
+    let Some(until) = subscription.last_signature() else {
+        return;
+    };
+    match backfill_market_updates(&program.rpc(), market_id, until, BACKFILL_LIMIT).await {
+        Ok((mut events, truncated)) => {
+            subscription.backfilled(events.len(), truncated);
+            if let Some((signature, slot, event)) = events.pop() {
+                subscription.on_event(signature, slot);
+                let _ = replay.send((signature, slot, event));
+            }
+        }
+        Err(error) => {
+            warn!(
+                event.name = "ws_subscription_backfill_failed",
+                market.id = market_id,
+                ?error,
+            );
+        }
+    }
+}
+
+/// Fetch fresh state, top the position up if it is due, ask the strategy for its periodic
+/// decision and carry it out. Returns whether the position was stopped.
+async fn run_tick(
+    program: &Program<ProgramPayer>,
+    runtime: &StrategyRuntime,
+    market_id: u64,
+    top_up: Option<&TopUp>,
+    sender: &TxSender,
+    alerter: &Alerter,
+    control: &ControlState,
+) -> anyhow::Result<bool> {
+    let payer = sender.payer();
+    let fetch = || {
+        fetch_snapshot(
+            program,
+            market_id,
+            &payer,
+            sender.reporting().crash_dump.as_ref(),
+        )
+        .instrument(info_span!("state.fetch", market.id = market_id))
+    };
+    let report = |e: &anyhow::Error| {
+        error!(
+            event.name = "inventory_flow_evaluate_failed",
+            market.id = market_id,
+            error = ?e,
+        );
+        alerter.notify(
+            AlertKind::RpcDown,
+            Some(market_id),
+            format!("failed to evaluate position: {e:#}"),
+        );
+    };
+    let mut snapshot = fetch().await.inspect_err(report)?;
+    check_risk(program, runtime, &snapshot, sender, alerter).await?;
+    if let Some(top_up) = top_up.filter(|top_up| top_up.is_due(&snapshot)) {
+        match top_up.execute(program, &snapshot, sender).await {
+            // The strategy sizes flows from the balances, so it sees the deposit.
+            Ok(()) => {
+                snapshot = fetch().await.inspect_err(report)?;
+                check_risk(program, runtime, &snapshot, sender, alerter).await?;
+            }
+            Err(error) => warn!(
+                event.name = "inventory_flow_top_up_failed",
+                market.id = market_id,
+                ?error,
+            ),
+        }
+    }
+    let actions = runtime.decide(
+        Trigger::Tick,
+        &snapshot.context(),
+        &control.thresholds(),
+        sender.reporting(),
+    );
+    apply_actions(program, &snapshot, actions, sender, alerter, control).await
+}
+
+/// Report `snapshot` to the risk engine. Fails once the engine is halted while the position
+/// still quotes, after pulling its quotes. The position isn't priced, so it counts towards
+/// the deployed and flow limits but not the drawdown limit.
+async fn check_risk(
+    program: &Program<ProgramPayer>,
+    runtime: &StrategyRuntime,
+    snapshot: &PositionSnapshot,
+    sender: &TxSender,
+    alerter: &Alerter,
+) -> anyhow::Result<()> {
+    let exposure = PositionExposure::new(
+        &snapshot.balances,
+        snapshot.position.base_flow_u64,
+        snapshot.position.quote_flow_u64,
+        0.0,
+    );
+    let Some(violation) = runtime.observe(snapshot.market_id, exposure) else {
+        return Ok(());
+    };
+    alerter.notify(
+        AlertKind::RiskLimitBreached,
+        Some(snapshot.market_id),
+        format!("{}; pulling quotes", violation),
+    );
+    pull_all_quotes(program, &[snapshot.market_id], sender).await?;
+    anyhow::bail!("Risk limit breached: {}", violation);
+}
+
+/// Carry out on-chain actions in order. Returns whether the position was stopped, in
+/// which case any remaining actions are dropped. A flow update that doesn't show on the
+/// position afterwards forces a fresh cycle.
+async fn apply_actions(
+    program: &Program<ProgramPayer>,
+    snapshot: &PositionSnapshot,
+    actions: Vec<Action>,
+    sender: &TxSender,
+    alerter: &Alerter,
+    control: &ControlState,
+) -> anyhow::Result<bool> {
+    if actions
+        .iter()
+        .any(|action| matches!(action, Action::Stop { .. } | Action::UpdateFlows { .. }))
+    {
+        // Best effort: the actions go ahead either way.
+        if let Err(error) =
+            execute_open_next_window(program, snapshot.market_id, &snapshot.market_state, sender)
+                .await
+        {
+            warn!(
+                event.name = "inventory_flow_open_window_failed",
+                market.id = snapshot.market_id,
+                ?error,
+            );
+        }
+    }
+    for action in actions {
+        match action {
+            Action::Stop { reference_index } => {
+                stop_position(
+                    program,
+                    snapshot.market_id,
+                    reference_index,
+                    &snapshot.balances,
+                    sender,
+                    alerter,
+                )
+                .await;
+                return Ok(true);
+            }
+            Action::UpdateFlows {
+                base_flow,
+                quote_flow,
+                ..
+            } => {
+                // Close to debt, the slot saved by going straight to the leaders matters.
+                let near_debt = snapshot.slots_until_debt().is_some_and(|slots| {
+                    u128::from(slots) <= DelayConfig::default().critical_threshold
+                });
+                let leader_sender;
+                let sender = if near_debt {
+                    leader_sender = sender.with_options(SendOptions {
+                        tpu: true,
+                        ..sender.options()
+                    });
+                    &leader_sender
+                } else {
+                    sender
+                };
+                execute_action(program, snapshot.market_id, &action, sender)
+                    .instrument(info_span!(
+                        "action.execute",
+                        market.id = snapshot.market_id,
+                        action = ?action,
+                    ))
+                    .await
+                    .inspect_err(|error| {
+                        error!(
+                            event.name = "inventory_flow_update_failed",
+                            market.id = snapshot.market_id,
+                            action = ?action,
+                            ?error,
+                        )
+                    })?;
+                record_flow_change(
+                    snapshot.market_id,
+                    &snapshot.position,
+                    snapshot.market_state.current_slot,
+                    base_flow,
+                    quote_flow,
+                );
+                match verify_flows(program, snapshot.market_id, base_flow, quote_flow).await {
+                    Ok(None) => {}
+                    Ok(Some(mismatch)) => {
+                        alerter.notify(
+                            AlertKind::FlowMismatch,
+                            Some(snapshot.market_id),
+                            format!("{mismatch}; re-evaluating"),
+                        );
+                        control.request_force_update();
+                    }
+                    Err(error) => warn!(
+                        event.name = "flow_update_verify_failed",
+                        market.id = snapshot.market_id,
+                        ?error,
+                    ),
+                }
+            }
+            Action::Rebalance | Action::Reevaluate { .. } => {
+                warn!(
+                    event.name = "inventory_flow_action_unsupported",
+                    market.id = snapshot.market_id,
+                    action = ?action,
+                );
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Stop a position that has run into debt, alerting on the debt and on the outcome.
+async fn stop_position(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    reference_index: u64,
+    balances: &LiquidityPositionBalances,
+    sender: &TxSender,
+    alerter: &Alerter,
+) {
+    alerter.notify(
+        AlertKind::DebtDetected,
+        Some(market_id),
+        format!(
+            "base debt {} quote debt {}; stopping position",
+            balances.base_debt, balances.quote_debt
+        ),
+    );
+    sender.reporting().fire(
+        market_id,
+        &program.payer(),
+        LifecycleEvent::DebtDetected {
+            base_debt: balances.base_debt,
+            quote_debt: balances.quote_debt,
+        },
+    );
+
+    let sender = sender.with_options(SendOptions::urgent());
+    match execute_stop_position(program, market_id, reference_index, &sender).await {
+        Ok(()) => alerter.notify(
+            AlertKind::StopExecuted,
+            Some(market_id),
+            format!("position stopped at reference index {}", reference_index),
+        ),
+        Err(e) => {
+            error!(
+                event.name = "inventory_flow_stop_failed",
+                market.id = market_id,
+                reference_index,
+                error = ?e,
+            );
+            alerter.notify(
+                AlertKind::StopFailed,
+                Some(market_id),
+                format!("failed to stop position: {e:#}"),
+            );
+        }
+    }
+}
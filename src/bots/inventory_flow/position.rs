@@ -0,0 +1,199 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use anchor_client::Program;
+use anchor_lang::prelude::Pubkey;
+use tracing::{debug, info, warn};
+
+use crate::{
+    LiquidityPositionBalances, MarketState, ProgramPayer, TwobRpc,
+    config::InventoryFlowSection,
+    crash_dump::CrashDump,
+    execute_deposit_liquidity, execute_open_next_window, execute_update_flows,
+    fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances_dumped,
+    nearest_reference_index, record_runway, slots_until_debt,
+    strategy::StrategyContext,
+    twob_anchor::accounts::LiquidityPosition,
+    tx::{SendOptions, TxSender},
+};
+
+/// Everything the strategy is evaluated against, fetched in one go.
+pub struct PositionSnapshot {
+    pub market_id: u64,
+    pub market_state: MarketState,
+    pub position: LiquidityPosition,
+    pub balances: LiquidityPositionBalances,
+}
+
+impl PositionSnapshot {
+    pub fn context(&self) -> StrategyContext<'_> {
+        StrategyContext {
+            market_id: self.market_id,
+            market_state: &self.market_state,
+            position: &self.position,
+            balances: &self.balances,
+        }
+    }
+
+    pub fn slots_until_debt(&self) -> Option<u64> {
+        slots_until_debt(&self.position, &self.market_state.market, &self.balances)
+    }
+}
+
+/// Deposits from the wallet into the position as it runs low, so it keeps streaming
+/// without being stopped and reopened. Clones share the slot of the last top-up, so the
+/// periodic and event-driven ticks hold to one cooldown.
+#[derive(Debug, Clone)]
+pub struct TopUp {
+    pub runway_slots: u64,
+    pub base_lamports: u64,
+    pub quote_lamports: u64,
+    pub cooldown_slots: u64,
+    last_slot: Arc<AtomicU64>,
+}
+
+impl TopUp {
+    /// `None` when `section` never tops up.
+    pub fn from_section(section: &InventoryFlowSection) -> Option<Self> {
+        (section.top_up_runway_slots > 0).then(|| Self {
+            runway_slots: section.top_up_runway_slots,
+            base_lamports: section.top_up_base_lamports,
+            quote_lamports: section.top_up_quote_lamports,
+            cooldown_slots: section.top_up_cooldown_slots,
+            last_slot: Arc::default(),
+        })
+    }
+
+    /// A position already in debt is stopped rather than topped up.
+    pub fn is_due(&self, snapshot: &PositionSnapshot) -> bool {
+        self.cooled_down(snapshot.market_state.current_slot)
+            && !snapshot.context().has_debt()
+            && snapshot
+                .slots_until_debt()
+                .is_some_and(|slots| slots <= self.runway_slots)
+    }
+
+    /// Whether [`Self::cooldown_slots`] have passed since the last top-up at `current_slot`.
+    fn cooled_down(&self, current_slot: u64) -> bool {
+        current_slot.saturating_sub(self.last_slot.load(Ordering::Relaxed)) >= self.cooldown_slots
+    }
+
+    pub async fn execute(
+        &self,
+        program: &Program<ProgramPayer>,
+        snapshot: &PositionSnapshot,
+        sender: &TxSender,
+    ) -> anyhow::Result<()> {
+        info!(
+            event.name = "inventory_flow_top_up",
+            market.id = snapshot.market_id,
+            lp.slots_until_debt = snapshot.slots_until_debt(),
+            top_up.base_lamports = self.base_lamports,
+            top_up.quote_lamports = self.quote_lamports,
+        );
+        execute_deposit_liquidity(
+            program,
+            &snapshot.market_state,
+            self.base_lamports,
+            self.quote_lamports,
+            sender,
+        )
+        .await?;
+        self.last_slot
+            .store(snapshot.market_state.current_slot, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+pub async fn fetch_snapshot(
+    rpc: &(impl TwobRpc + ?Sized),
+    market_id: u64,
+    authority: &Pubkey,
+    crash_dump: Option<&CrashDump>,
+) -> anyhow::Result<PositionSnapshot> {
+    let market_state = fetch_market_state(rpc, market_id).await?;
+    let position = fetch_liquidity_position(rpc, market_id, authority).await?;
+
+    debug!(
+        event.name = "liquidity_position_fetched",
+        market.id = market_id,
+        lp.authority = %authority,
+        ?position,
+    );
+
+    let balances = get_liquidity_position_balances_dumped(
+        rpc,
+        position,
+        market_state.bookkeeping,
+        market_state.market,
+        market_state.current_slot,
+        crash_dump,
+    )
+    .await?;
+    record_runway(market_id, &position, &market_state.market, &balances);
+
+    Ok(PositionSnapshot {
+        market_id,
+        market_state,
+        position,
+        balances,
+    })
+}
+
+/// Set both flows to zero so the position stops providing liquidity.
+pub async fn zero_flows(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    sender: &TxSender,
+) -> anyhow::Result<()> {
+    let market_state = fetch_market_state(program, market_id).await?;
+    let reference_index = nearest_reference_index(
+        market_state.current_slot,
+        market_state.market.end_slot_interval,
+    );
+
+    if let Err(error) = execute_open_next_window(program, market_id, &market_state, sender).await {
+        warn!(
+            event.name = "inventory_flow_open_window_failed",
+            market.id = market_id,
+            ?error,
+        );
+    }
+    let sender = sender.with_options(SendOptions::urgent());
+    execute_update_flows(program, market_id, 0, 0, reference_index, &sender).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top_up(cooldown_slots: u64) -> TopUp {
+        TopUp::from_section(&InventoryFlowSection {
+            flow_divisor: 5,
+            top_up_runway_slots: 500,
+            top_up_base_lamports: 1_000,
+            top_up_quote_lamports: 0,
+            top_up_cooldown_slots: cooldown_slots,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn waits_out_the_cooldown_between_top_ups() {
+        let top_up = top_up(1_500);
+        assert!(top_up.cooled_down(90_000));
+
+        let shared = top_up.clone();
+        top_up.last_slot.store(90_000, Ordering::Relaxed);
+        assert!(!shared.cooled_down(90_000));
+        assert!(!shared.cooled_down(91_499));
+        assert!(shared.cooled_down(91_500));
+
+        let uncapped = self::top_up(0);
+        uncapped.last_slot.store(90_000, Ordering::Relaxed);
+        assert!(uncapped.cooled_down(90_000));
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::{
+    LiquidityPositionBalances, MarketState,
+    control::ThresholdOverrides,
+    strategy::{Action, Strategy, StrategyContext},
+    twob_anchor::{accounts::LiquidityPosition, events::MarketUpdateEvent},
+};
+
+use super::config::DelayConfig;
+
+/// Quotes a fixed fraction of each balance and stops the position as soon as it has debt.
+/// Market events don't change the quote directly; they reschedule the next evaluation
+/// based on how close the position is to running dry.
+#[derive(Debug, Clone, Copy)]
+pub struct InventoryFlowStrategy {
+    pub flow_divisor: u64,
+    pub delay_config: DelayConfig,
+    configured_flow_divisor: u64,
+}
+
+impl InventoryFlowStrategy {
+    pub fn new(flow_divisor: u64, delay_config: DelayConfig) -> Self {
+        Self {
+            flow_divisor,
+            delay_config,
+            configured_flow_divisor: flow_divisor,
+        }
+    }
+}
+
+impl Strategy for InventoryFlowStrategy {
+    fn name(&self) -> &str {
+        "inventory-flow"
+    }
+
+    fn on_tick(&mut self, ctx: &StrategyContext<'_>) -> Vec<Action> {
+        if ctx.has_debt() {
+            return vec![Action::Stop {
+                reference_index: ctx.nearest_reference_index(),
+            }];
+        }
+        vec![Action::UpdateFlows {
+            base_flow: ctx.balances.base_balance / self.flow_divisor,
+            quote_flow: ctx.balances.quote_balance / self.flow_divisor,
+            reference_index: ctx.nearest_reference_index(),
+        }]
+    }
+
+    fn on_market_event(
+        &mut self,
+        _event: &MarketUpdateEvent,
+        ctx: &StrategyContext<'_>,
+    ) -> Vec<Action> {
+        if ctx.has_debt() {
+            return vec![Action::Stop {
+                reference_index: ctx.nearest_reference_index(),
+            }];
+        }
+        let delay = calculate_update_delay(
+            ctx.position,
+            ctx.market_state,
+            ctx.balances,
+            &self.delay_config,
+        );
+        vec![Action::Reevaluate {
+            after: Duration::from_millis(delay),
+        }]
+    }
+
+    fn on_overrides(&mut self, overrides: &ThresholdOverrides) {
+        self.flow_divisor = overrides
+            .flow_divisor
+            .unwrap_or(self.configured_flow_divisor);
+    }
+}
+
+pub fn calculate_update_delay(
+    position: &LiquidityPosition,
+    market_state: &MarketState,
+    balances: &LiquidityPositionBalances,
+    delay_config: &DelayConfig,
+) -> u64 {
+    let base_outflow = position.base_flow_u64 as u128;
+    let quote_outflow = position.quote_flow_u64 as u128;
+
+    if market_state.market.quote_flow == 0 || market_state.market.base_flow == 0 {
+        return delay_config.normal_delay_ms as u64;
+    }
+
+    let base_inflow =
+        quote_outflow * market_state.market.base_flow / market_state.market.quote_flow;
+    let quote_inflow =
+        base_outflow * market_state.market.quote_flow / market_state.market.base_flow;
+
+    let slots_until_debt = if base_outflow > base_inflow {
+        let delta = base_outflow - base_inflow;
+        balances.base_balance as u128 / delta
+    } else if quote_outflow > quote_inflow {
+        let delta = quote_outflow - quote_inflow;
+        balances.quote_balance as u128 / delta
+    } else {
+        u64::MAX as u128
+    };
+
+    let delay = if slots_until_debt <= delay_config.critical_threshold {
+        delay_config.critical_delay_ms
+    } else if slots_until_debt <= delay_config.safe_threshold {
+        delay_config.normal_delay_ms
+    } else {
+        let additional_slots = slots_until_debt
+            .min(delay_config.safe_threshold + delay_config.max_additional_slots)
+            - delay_config.safe_threshold;
+        additional_slots * delay_config.delay_scale_factor + delay_config.normal_delay_ms
+    };
+
+    info!(
+        event.name = "inventory_flow_delay_computed",
+        lp.slots_until_debt = slots_until_debt as u64,
+        inventory_flow.delay_ms = delay as u64,
+    );
+    delay as u64
+}
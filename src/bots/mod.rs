@@ -0,0 +1,8 @@
+//! The flow bots. Each has a binary of its own, and the orchestrator runs several of them
+//! as tasks of one process through the same [`run`](oracle_flow::run) entry points. Both
+//! drive their strategy through a [`runtime::StrategyRuntime`], and drive any other
+//! [`Strategy`](crate::strategy::Strategy) handed to their `run_strategy` the same way.
+
+pub mod inventory_flow;
+pub mod oracle_flow;
+pub mod runtime;
@@ -0,0 +1,70 @@
+use crate::{
+    config::{CommonConfig, ConfigErrors, OracleFlowSection, env_var, var},
+    feed_health::FeedHealthConfig,
+    lending::IdleYieldConfig,
+};
+
+use super::telemetry::TelemetryConfig;
+
+#[derive(Clone, Debug)]
+pub struct JupiterConfig {
+    pub api_key: Option<String>,
+    pub ultra_api_base_url: String,
+    pub max_slippage_bps: u64,
+    pub max_price_impact_bps: u64,
+    pub dry_run: bool,
+}
+
+pub struct Config {
+    pub common: CommonConfig,
+    pub strategy: OracleFlowSection,
+    pub jupiter: JupiterConfig,
+    pub telemetry: TelemetryConfig,
+    pub feed_health: FeedHealthConfig,
+    /// Lend quote the position doesn't need; `None` keeps it all in the position.
+    pub idle_yield: Option<IdleYieldConfig>,
+}
+
+impl Config {
+    /// Read and validate everything, reporting every problem found rather than the first.
+    pub fn from_env() -> anyhow::Result<Self> {
+        ConfigErrors::collect(|errors| {
+            let common = CommonConfig::read("ORACLE_FLOW_KEYPAIR", errors);
+            let strategy = OracleFlowSection::read(errors);
+            let max_slippage_bps = errors.check(
+                "JUPITER_MAX_SLIPPAGE_BPS",
+                var("JUPITER_MAX_SLIPPAGE_BPS", 50),
+            );
+            let max_price_impact_bps = errors.check(
+                "JUPITER_MAX_PRICE_IMPACT_BPS",
+                var("JUPITER_MAX_PRICE_IMPACT_BPS", 50),
+            );
+            let jupiter_dry_run = errors.check("JUPITER_DRY_RUN", var("JUPITER_DRY_RUN", false));
+            let telemetry = errors.check("telemetry", TelemetryConfig::from_env());
+            let feed_health = errors.check("feed_health", FeedHealthConfig::from_env());
+            let idle_yield = errors.check("idle_yield", IdleYieldConfig::from_env());
+
+            let common = common?;
+            let jupiter = JupiterConfig {
+                api_key: env_var("JUPITER_API_KEY")
+                    .ok()
+                    .filter(|value| !value.trim().is_empty()),
+                ultra_api_base_url: env_var("JUPITER_ULTRA_API_BASE_URL")
+                    .unwrap_or_else(|_| "https://api.jup.ag/ultra/v1".to_string()),
+                max_slippage_bps: max_slippage_bps?,
+                max_price_impact_bps: max_price_impact_bps?,
+                // Swaps go to Jupiter rather than through the sender, so a dry run has to
+                // hold them back separately.
+                dry_run: jupiter_dry_run? || common.tx.dry_run,
+            };
+            Some(Self {
+                common,
+                strategy: strategy?,
+                jupiter,
+                telemetry: telemetry?,
+                feed_health: feed_health?,
+                idle_yield: idle_yield?,
+            })
+        })
+    }
+}
@@ -3,17 +3,14 @@ use std::{
     time::{Duration, Instant},
 };
 
-use anchor_client::solana_sdk::{
-    pubkey::Pubkey,
-    signature::{Keypair, Signer},
-    transaction::VersionedTransaction,
-};
+use crate::tx::TxSigner;
+use anchor_client::solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
 use anyhow::{Context, ensure};
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
 use serde::{Deserialize, Serialize};
 use tracing::{Instrument, info, info_span, warn};
 
-use crate::config::JupiterConfig;
+use super::config::JupiterConfig;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SwapDirection {
@@ -57,7 +54,7 @@ impl<'a> JupiterUltraClient<'a> {
 
     pub async fn swap_exact_in(
         &self,
-        liquidity_provider: Arc<Keypair>,
+        liquidity_provider: Arc<dyn TxSigner>,
         input_mint: Pubkey,
         output_mint: Pubkey,
         amount: u64,
@@ -179,7 +176,7 @@ impl<'a> JupiterUltraClient<'a> {
             .filter(|value| !value.trim().is_empty())
             .context("Jupiter order response missing transaction payload")?;
 
-        let signed_transaction = sign_transaction(&transaction, liquidity_provider)?;
+        let signed_transaction = sign_transaction(&transaction, liquidity_provider).await?;
         let execute_response = self
             .execute_order(api_key, &request_id, &signed_transaction)
             .instrument(info_span!(
@@ -285,9 +282,9 @@ impl<'a> JupiterUltraClient<'a> {
     }
 }
 
-fn sign_transaction(
+async fn sign_transaction(
     transaction_base64: &str,
-    liquidity_provider: Arc<Keypair>,
+    liquidity_provider: Arc<dyn TxSigner>,
 ) -> anyhow::Result<String> {
     let transaction_bytes = BASE64_STANDARD
         .decode(transaction_base64)
@@ -312,7 +309,7 @@ fn sign_transaction(
         })?;
 
     let message_bytes = transaction.message.serialize();
-    transaction.signatures[signer_index] = liquidity_provider.sign_message(&message_bytes);
+    transaction.signatures[signer_index] = liquidity_provider.sign_message(&message_bytes).await?;
 
     let signed_bytes = bincode::serialize(&transaction)
         .context("Failed to serialize signed Jupiter transaction")?;
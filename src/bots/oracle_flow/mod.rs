@@ -0,0 +1,1150 @@
+//! Quotes a TwoB market around an oracle price, rebalancing through Jupiter. Runs as its
+//! own binary or as a task of the orchestrator.
+
+mod config;
+mod jupiter;
+mod rebalance;
+mod strategy;
+mod telemetry;
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anchor_client::solana_sdk::transaction::TransactionError;
+use config::{Config, JupiterConfig};
+use rebalance::{RebalanceOutcome, execute_rebalance};
+use strategy::OracleFlowStrategy;
+use tokio::task::JoinSet;
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::{
+    LiquidityPositionBalances, MarketState, ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    bots::runtime::{StrategyRuntime, Trigger},
+    build_update_liquidity_flows_instruction,
+    config::{FlowArgs, inherit},
+    control::{
+        CircuitBreaker, ControlState, ThresholdOverrides, admin, emergency::EmergencyStop,
+        heartbeat::HeartbeatPinger, probes, telegram, write_heartbeat,
+    },
+    error::{ProgramErrorCode, program_error},
+    execute_open_next_window, execute_update_flows,
+    feed_health::{FeedHealth, FeedStatus, fetch_prices},
+    fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances_dumped, lending,
+    nearest_reference_index, record_runway,
+    reporting::Reporting,
+    risk::{PositionExposure, RiskEngine, pull_all_quotes},
+    rotation::rotate,
+    slot_lag::{self, Failover},
+    strategy::{Action, Strategy, StrategyContext},
+    supervisor::{KillSwitch, Launch},
+    turnover::{SpreadTracker, record_flow_change},
+    twob_anchor::{self, accounts::LiquidityPosition},
+    tx::{SendOptions, TransactionFailed, TxSender, TxSigner},
+    verify_flows,
+    webhooks::LifecycleEvent,
+};
+
+const BALANCED_QUOTE_VALUE_WEIGHT: f64 = 0.5;
+type OracleProgram = anchor_client::Program<ProgramPayer>;
+
+/// Run the bot until `kill` trips, it is force stopped or it fails. Its position is held to
+/// `risk` with the other positions of the process; `None` gives it an engine of its own with
+/// the limits in its config.
+pub async fn run(
+    flow: FlowArgs,
+    kill: KillSwitch,
+    launch: Launch,
+    risk: Option<Arc<RiskEngine>>,
+) -> anyhow::Result<()> {
+    let Some(config) = load(&flow).await? else {
+        return Ok(());
+    };
+    let strategy = OracleFlowStrategy::new(
+        config.strategy.quote_threshold_bps,
+        config.strategy.rebalance_threshold_bps,
+        config.strategy.base_token_decimals,
+        config.strategy.quote_token_decimals,
+        config.strategy.optimal_quote_weight,
+        Duration::from_secs(config.strategy.rebalance_cooldown_secs),
+    );
+    start(config, Box::new(strategy), kill, launch, risk).await
+}
+
+/// [`run`] with `strategy` deciding in place of oracle-flow's own. It is asked about each
+/// price the feeds agree on ([`Strategy::on_price`](crate::strategy::Strategy::on_price)),
+/// and again on the refreshed state after a rebalance it asked for
+/// ([`Strategy::on_tick`](crate::strategy::Strategy::on_tick)).
+pub async fn run_strategy(
+    strategy: Box<dyn Strategy>,
+    flow: FlowArgs,
+    kill: KillSwitch,
+    launch: Launch,
+    risk: Option<Arc<RiskEngine>>,
+) -> anyhow::Result<()> {
+    let Some(config) = load(&flow).await? else {
+        return Ok(());
+    };
+    start(config, strategy, kill, launch, risk).await
+}
+
+/// Apply `flow` and read the config, or `None` when `flow` asked for a config mode, which
+/// has run instead.
+async fn load(flow: &FlowArgs) -> anyhow::Result<Option<Config>> {
+    flow.apply();
+    if crate::config::run_config_mode(flow, "oracle-flow", "ORACLE_FLOW_KEYPAIR", Config::from_env)?
+    {
+        return Ok(None);
+    }
+    crate::config::load_env("oracle-flow").await?;
+    Ok(Some(Config::from_env()?))
+}
+
+async fn start(
+    config: Config,
+    strategy: Box<dyn Strategy>,
+    kill: KillSwitch,
+    launch: Launch,
+    risk: Option<Arc<RiskEngine>>,
+) -> anyhow::Result<()> {
+    let reporting = Reporting::start(&config.common, "oracle-flow")?;
+    if let (Launch::Process, Some(crash_dump)) = (launch, &reporting.crash_dump) {
+        crash_dump.install_panic_hook();
+    }
+    let risk = risk.unwrap_or_else(|| RiskEngine::new(config.common.risk_limits));
+    let runtime = StrategyRuntime::new(strategy, risk);
+    let result = trade(config, runtime, &reporting, kill, launch).await;
+    if let Err(error) = &result {
+        reporting.write_crash_dump(&format!("fatal error: {error:#}"));
+    }
+    result
+}
+
+async fn trade(
+    config: Config,
+    runtime: StrategyRuntime,
+    reporting: &Reporting,
+    kill: KillSwitch,
+    launch: Launch,
+) -> anyhow::Result<()> {
+    let telemetry_config = config.telemetry.clone();
+    let rpc_url = config.common.rpc_url.clone();
+    let market_id = config.common.market_id;
+    let poll_interval = Duration::from_secs(config.strategy.poll_interval_secs);
+    let jitter = config.common.jitter;
+    let quote_threshold_bps = config.strategy.quote_threshold_bps;
+    let rebalance_threshold_bps = config.strategy.rebalance_threshold_bps;
+    let base_token_decimals = config.strategy.base_token_decimals;
+    let quote_token_decimals = config.strategy.quote_token_decimals;
+    let optimal_quote_weight = config.strategy.optimal_quote_weight;
+    let flow_reduction_factor = config.strategy.flow_reduction_factor;
+    let max_flow_reduction_attempts = config.strategy.max_flow_reduction_attempts;
+    let rebalance_cooldown = Duration::from_secs(config.strategy.rebalance_cooldown_secs);
+    let min_rebalance_value_usd = config.strategy.min_rebalance_value_usd;
+    let is_devnet = config.common.rpc_url.contains("devnet");
+    let mut feed_health = FeedHealth::new(
+        config.feed_health,
+        std::iter::once(config.strategy.price_feed_url)
+            .chain(config.strategy.price_feed_secondary_urls),
+    );
+    let heartbeat_file = config.common.heartbeat_file.clone();
+    let heartbeat_ping = config
+        .common
+        .heartbeat_ping
+        .clone()
+        .map(HeartbeatPinger::new);
+    let slot_lag_config = config.common.slot_lag.clone();
+    let slot_lag_rpc_url = config.common.rpc_url.clone();
+    let jupiter_config = config.jupiter.clone();
+    let mut liquidity_provider = config.common.signer.clone();
+    let alerter = Alerter::from_config("oracle-flow", &config.common.alerts)?;
+    let (mut client, mut sender) = config
+        .common
+        .connect(liquidity_provider.clone(), &alerter, reporting)
+        .await?;
+    let failover = Failover::default();
+
+    let http_client = reqwest::Client::new();
+    let mut program = client.program(twob_anchor::ID)?;
+    let api_bind_addr = config.common.api_bind_addr;
+    let control_bind_addr = config.common.control_bind_addr;
+    let admin_config = config.common.admin.clone();
+    let probe_config = config.common.probes.clone();
+    let emergency_stop_config = config.common.emergency_stop;
+    let telegram_control = config.common.telegram_control.clone();
+    let idle_yield = config.idle_yield.clone();
+    let mut circuit_breaker = CircuitBreaker::new(config.common.circuit_breaker_max_failures);
+    let mut authority = liquidity_provider.pubkey();
+    let _telemetry_guard = match launch {
+        Launch::Process => Some(telemetry::init_telemetry(telemetry::TelemetryInitConfig {
+            service_name: telemetry_config.service_name.clone(),
+            bot_role: "oracle-flow",
+            log_format: telemetry_config.log_format,
+            market_id,
+            authority: authority.to_string(),
+            rpc_url,
+            program_id: twob_anchor::ID.to_string(),
+        })?),
+        Launch::Task => None,
+    };
+
+    info!(
+        event.name = "oracle_flow_started",
+        market.id = market_id,
+        lp.authority = %authority,
+        poll_interval_secs = poll_interval.as_secs(),
+        rebalance.threshold_bps = rebalance_threshold_bps,
+        quote.threshold_bps = quote_threshold_bps,
+        quote.optimal_weight = optimal_quote_weight,
+        jupiter.api_key_configured = jupiter_config.api_key.is_some(),
+        jupiter.dry_run = jupiter_config.dry_run,
+        solana.devnet_mode = is_devnet,
+        rebalance.cooldown_secs = rebalance_cooldown.as_secs(),
+        rebalance.min_value_usd = min_rebalance_value_usd,
+        balance_snapshot_interval_secs = telemetry_config.balance_snapshot_interval_secs,
+    );
+
+    // Settle whatever the last run left waiting before sending anything new.
+    if let Err(error) = sender.reconcile_journal().await {
+        warn!(event.name = "tx_journal_reconcile_failed", ?error);
+    }
+
+    let emergency_stop = match emergency_stop_config {
+        Some(config) => {
+            let stop = EmergencyStop::prepare(config, &program, market_id, &sender).await?;
+            if launch == Launch::Process {
+                stop.install_panic_hook();
+            }
+            Some(stop)
+        }
+        None => None,
+    };
+
+    // The servers and monitors go with the run, as the orchestrator may start another, and
+    // keep its settings.
+    let mut background = JoinSet::new();
+    #[cfg(feature = "api")]
+    if let Some(addr) = api_bind_addr {
+        let api_client = client.clone();
+        background.spawn(inherit(async move {
+            if let Err(error) = crate::api::serve(addr, api_client).await {
+                error!(event.name = "api_server_failed", api.addr = %addr, ?error);
+            }
+        }));
+    }
+    #[cfg(not(feature = "api"))]
+    if api_bind_addr.is_some() {
+        warn!(
+            event.name = "api_server_unavailable",
+            reason = "built_without_api_feature",
+        );
+    }
+
+    let control = ControlState::new("oracle-flow", market_id, authority);
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = control_bind_addr {
+        let control = control.clone();
+        background.spawn(inherit(async move {
+            if let Err(error) = crate::control::grpc::serve(addr, control).await {
+                error!(event.name = "control_server_failed", control.addr = %addr, ?error);
+            }
+        }));
+    }
+    #[cfg(not(feature = "grpc"))]
+    if control_bind_addr.is_some() {
+        warn!(
+            event.name = "control_server_unavailable",
+            reason = "built_without_grpc_feature",
+        );
+    }
+    if let Some(admin_config) = admin_config {
+        let control = control.clone();
+        background.spawn(inherit(async move {
+            let addr = admin_config.addr.clone();
+            if let Err(error) = admin::serve(admin_config, control).await {
+                error!(event.name = "admin_server_failed", admin.addr = %addr, ?error);
+            }
+        }));
+    }
+    if let Some(probe_config) = probe_config {
+        let (control, rpc) = (control.clone(), program.rpc());
+        background.spawn(inherit(async move {
+            let addr = probe_config.bind_addr;
+            if let Err(error) = probes::serve(probe_config, control, rpc).await {
+                error!(event.name = "probe_server_failed", probe.addr = %addr, ?error);
+            }
+        }));
+    }
+    if let Some(slot_lag_config) = slot_lag_config {
+        background.spawn(inherit(slot_lag::monitor(
+            slot_lag_config,
+            slot_lag_rpc_url,
+            failover.clone(),
+            alerter.clone(),
+            market_id,
+        )));
+    }
+    if let Some(telegram_config) = telegram_control {
+        let control = control.clone();
+        background.spawn(inherit(async move {
+            if let Err(error) = telegram::serve(telegram_config, control).await {
+                error!(event.name = "telegram_control_failed", ?error);
+            }
+        }));
+    }
+
+    let mut spread = SpreadTracker::default();
+    let mut cycle_number = 0_u64;
+
+    loop {
+        tokio::select! {
+            _ = kill.triggered() => {
+                info!(event.name = "oracle_flow_shutdown");
+                break;
+            }
+            rotation = control.key_rotation_requested() => {
+                info!(
+                    event.name = "oracle_flow_key_rotation_started",
+                    market.id = market_id,
+                    lp.authority = %authority,
+                );
+                let rotated = rotate(
+                    &config.common,
+                    &rotation,
+                    &program,
+                    &sender,
+                    emergency_stop.as_deref(),
+                    &alerter,
+                    &control,
+                )
+                .await;
+                if let Some(rotated) = rotated {
+                    client = rotated.client;
+                    program = client.program(twob_anchor::ID)?;
+                    sender = rotated.sender;
+                    liquidity_provider = rotated.signer;
+                    authority = liquidity_provider.pubkey();
+                }
+            }
+            _ = control.force_stopped() => {
+                warn!(
+                    event.name = "oracle_flow_force_stop",
+                    market.id = market_id,
+                    lp.authority = %authority,
+                );
+                if let Err(error) = force_stop(&program, market_id, &sender).await {
+                    if let Some(stop) = &emergency_stop {
+                        stop.trigger(format!("force stop failed: {error:#}")).await;
+                    }
+                    return Err(error);
+                }
+                break;
+            }
+            forced = control.next_cycle(jitter.apply(poll_interval)) => {
+                if let Some(Err(error)) = heartbeat_file.as_deref().map(write_heartbeat) {
+                    warn!(event.name = "heartbeat_write_failed", ?error);
+                }
+                if let Some(pinger) = &heartbeat_ping {
+                    pinger.ping();
+                }
+                if control.is_paused() && !forced {
+                    info!(
+                        event.name = "oracle_flow_cycle_skipped",
+                        market.id = market_id,
+                        lp.authority = %authority,
+                        reason = "paused",
+                    );
+                    continue;
+                }
+                let overrides = control.thresholds();
+                cycle_number = cycle_number.saturating_add(1);
+                let cycle_id = format!("{}-{}", market_id, cycle_number);
+                let cycle_span = info_span!(
+                    "oracle_flow.update_cycle",
+                    cycle.id = %cycle_id,
+                    market.id = market_id,
+                    lp.authority = %authority,
+                );
+                // Reads go through the reference endpoint while the primary lags.
+                let reads = failover.reads(client.program(twob_anchor::ID)?)?;
+                let result = run_update_cycle(
+                    &reads,
+                    &http_client,
+                    &mut feed_health,
+                    &runtime,
+                    &overrides,
+                    &mut spread,
+                    overrides.quote_threshold_bps.unwrap_or(quote_threshold_bps),
+                    rebalance_cooldown,
+                    base_token_decimals,
+                    quote_token_decimals,
+                    flow_reduction_factor,
+                    max_flow_reduction_attempts,
+                    min_rebalance_value_usd,
+                    &jupiter_config,
+                    is_devnet,
+                    market_id,
+                    &authority,
+                    liquidity_provider.clone(),
+                    &sender,
+                    &cycle_id,
+                    &alerter,
+                    &control,
+                ).instrument(cycle_span).await;
+                control.record_cycle(&result);
+                if let Some(stop) = &emergency_stop
+                    && let Err(error) = stop.refresh(&program, &sender).await
+                {
+                    warn!(
+                        event.name = "emergency_stop_refresh_failed",
+                        cycle.id = %cycle_id,
+                        market.id = market_id,
+                        ?error,
+                    );
+                }
+                if circuit_breaker.record_cycle(&result) {
+                    control.pause();
+                    error!(
+                        event.name = "oracle_flow_circuit_breaker_tripped",
+                        market.id = market_id,
+                        lp.authority = %authority,
+                        circuit_breaker.consecutive_failures = circuit_breaker.consecutive_failures(),
+                        monotonic_counter.circuit_breaker_trips_total = 1_u64,
+                        "pausing until resumed via the control plane"
+                    );
+                    alerter.notify(
+                        AlertKind::CircuitBreakerTripped,
+                        Some(market_id),
+                        format!(
+                            "{} consecutive failed cycles; bot paused until resumed via the control plane",
+                            circuit_breaker.consecutive_failures()
+                        ),
+                    );
+                }
+                if let (Ok(()), Some(idle_yield)) = (&result, &idle_yield)
+                    && let Err(error) = lending::manage_idle_quote(
+                        &reads,
+                        market_id,
+                        quote_token_decimals,
+                        idle_yield,
+                        runtime.risk(),
+                        &sender,
+                    )
+                    .await
+                {
+                    warn!(
+                        event.name = "oracle_flow_idle_yield_failed",
+                        cycle.id = %cycle_id,
+                        market.id = market_id,
+                        ?error,
+                    );
+                }
+                if let Err(error) = result {
+                    error!(
+                        event.name = "oracle_flow_cycle_error",
+                        cycle.id = %cycle_id,
+                        market.id = market_id,
+                        lp.authority = %authority,
+                        cycle.outcome = "error",
+                        monotonic_counter.cycles_total = 1_u64,
+                        ?error,
+                        "update cycle failed"
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_update_cycle(
+    program: &OracleProgram,
+    http_client: &reqwest::Client,
+    feed_health: &mut FeedHealth,
+    runtime: &StrategyRuntime,
+    overrides: &ThresholdOverrides,
+    spread: &mut SpreadTracker,
+    quote_threshold_bps: u64,
+    rebalance_cooldown: Duration,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+    flow_reduction_factor: f64,
+    max_flow_reduction_attempts: usize,
+    min_rebalance_value_usd: f64,
+    jupiter_config: &JupiterConfig,
+    is_devnet: bool,
+    market_id: u64,
+    authority: &anchor_client::solana_sdk::pubkey::Pubkey,
+    liquidity_provider: Arc<dyn TxSigner>,
+    sender: &TxSender,
+    cycle_id: &str,
+    alerter: &Alerter,
+    control: &ControlState,
+) -> anyhow::Result<()> {
+    let cycle_started_at = Instant::now();
+    let cycle_ts = chrono::Utc::now();
+    info!(
+        event.name = "oracle_flow_cycle_start",
+        cycle.id = %cycle_id,
+        cycle.started_at = %cycle_ts.to_rfc3339(),
+        market.id = market_id,
+        lp.authority = %authority,
+    );
+
+    // 1. Fetch external price from every source and keep the healthy ones' median
+    let feed_report = fetch_prices(http_client, feed_health)
+        .instrument(info_span!("price.fetch", cycle.id = %cycle_id))
+        .await;
+    control.record_feed_health(feed_report.status, feed_report.summary());
+    let Some(price_data) = feed_report.price.clone() else {
+        alerter.notify(
+            AlertKind::FeedStale,
+            Some(market_id),
+            format!("price feed unhealthy: {}", feed_report.summary()),
+        );
+        anyhow::bail!("Price feed is unhealthy: {}", feed_report.summary());
+    };
+    if feed_report.status == FeedStatus::Degraded {
+        warn!(
+            event.name = "price_feed_degraded",
+            cycle.id = %cycle_id,
+            market.id = market_id,
+            problems = %feed_report.summary(),
+        );
+    }
+    control.record_price(price_data.timestamp);
+    let price_age_secs = (cycle_ts.timestamp().max(0) as u64).saturating_sub(price_data.timestamp);
+    info!(
+        event.name = "price_fetched",
+        cycle.id = %cycle_id,
+        market.id = market_id,
+        price.oracle = price_data.price,
+        price.age_secs = price_age_secs,
+    );
+    // 2. Fetch liquidity position and market state
+    let (mut market_state, mut position, mut balances) =
+        refresh_position_state(program, market_id, authority, sender.reporting())
+            .instrument(info_span!(
+                "state.refresh",
+                cycle.id = %cycle_id,
+                market.id = market_id,
+                lp.authority = %authority,
+            ))
+            .await
+            .inspect_err(|error| {
+                alerter.notify(
+                    AlertKind::RpcDown,
+                    Some(market_id),
+                    format!("failed to refresh position state: {error:#}"),
+                );
+            })?;
+
+    if balances.base_debt > 0 || balances.quote_debt > 0 {
+        alerter.notify(
+            AlertKind::DebtDetected,
+            Some(market_id),
+            format!(
+                "base debt {} quote debt {}",
+                balances.base_debt, balances.quote_debt
+            ),
+        );
+        sender.reporting().fire(
+            market_id,
+            authority,
+            LifecycleEvent::DebtDetected {
+                base_debt: balances.base_debt,
+                quote_debt: balances.quote_debt,
+            },
+        );
+    }
+
+    let current_position_value = position_value(
+        &balances,
+        base_token_decimals,
+        quote_token_decimals,
+        price_data.price,
+    );
+    let exposure = PositionExposure::new(
+        &balances,
+        position.base_flow_u64,
+        position.quote_flow_u64,
+        current_position_value,
+    );
+    let violation = runtime.observe(market_id, exposure);
+    // The engine also counts quote parked in a lending venue, so moving idle quote out of
+    // the position doesn't read as a loss.
+    control.record_position_value(runtime.risk().market_value(market_id));
+    if let Some(violation) = violation {
+        alerter.notify(
+            AlertKind::RiskLimitBreached,
+            Some(market_id),
+            format!("{}; pulling quotes", violation),
+        );
+        pull_all_quotes(program, &[market_id], sender).await?;
+        anyhow::bail!("Risk limit breached: {}", violation);
+    }
+
+    emit_position_snapshot(
+        "cycle_start",
+        cycle_id,
+        market_id,
+        authority,
+        &market_state,
+        &position,
+        &balances,
+        base_token_decimals,
+        quote_token_decimals,
+        price_data.price,
+    );
+    spread.record(
+        market_id,
+        &position,
+        price_data.price,
+        market_state.current_slot,
+        base_token_decimals,
+        quote_token_decimals,
+    );
+
+    // 3. Let the strategy choose between rebalancing inventory and requoting
+    let ctx = StrategyContext {
+        market_id,
+        market_state: &market_state,
+        position: &position,
+        balances: &balances,
+    };
+    let mut actions = runtime.decide(
+        Trigger::Price(&price_data),
+        &ctx,
+        overrides,
+        sender.reporting(),
+    );
+
+    if actions.contains(&Action::Rebalance) {
+        let attempt_started_at = Instant::now();
+        let attempt_id = format!("{}-rebalance-{}", cycle_id, cycle_ts.timestamp_millis());
+        info!(
+            event.name = "rebalance_triggered",
+            cycle.id = %cycle_id,
+            market.id = market_id,
+            lp.authority = %authority,
+            rebalance.attempt_id = %attempt_id,
+            rebalance.reason = "inventory_deviation",
+            monotonic_counter.rebalance_attempts_total = 1_u64,
+        );
+        let rebalance_result = execute_rebalance(
+            program,
+            http_client,
+            market_id,
+            &market_state,
+            &price_data,
+            &balances,
+            base_token_decimals,
+            quote_token_decimals,
+            position.base_flow_u64,
+            position.quote_flow_u64,
+            liquidity_provider.clone(),
+            sender,
+            jupiter_config,
+            flow_reduction_factor,
+            max_flow_reduction_attempts,
+            min_rebalance_value_usd,
+            is_devnet,
+            cycle_id,
+            &attempt_id,
+        )
+        .instrument(info_span!(
+            "rebalance.execute",
+            cycle.id = %cycle_id,
+            market.id = market_id,
+            lp.authority = %authority,
+            rebalance.attempt_id = %attempt_id,
+        ))
+        .await;
+
+        match rebalance_result {
+            Ok(RebalanceOutcome::Executed) => {
+                runtime.rebalanced(attempt_started_at);
+                sender
+                    .reporting()
+                    .fire(market_id, authority, LifecycleEvent::Rebalance);
+                match refresh_position_state(program, market_id, authority, sender.reporting())
+                    .instrument(info_span!(
+                        "state.refresh",
+                        cycle.id = %cycle_id,
+                        market.id = market_id,
+                        lp.authority = %authority,
+                        rebalance.attempt_id = %attempt_id,
+                    ))
+                    .await
+                {
+                    Ok((new_market_state, new_position, new_balances)) => {
+                        market_state = new_market_state;
+                        position = new_position;
+                        balances = new_balances;
+                    }
+                    Err(error) => {
+                        error!(
+                            event.name = "rebalance_refresh_failed",
+                            cycle.id = %cycle_id,
+                            market.id = market_id,
+                            lp.authority = %authority,
+                            rebalance.attempt_id = %attempt_id,
+                            ?error,
+                            "rebalance completed but refresh failed; skipping quote update"
+                        );
+                        return Ok(());
+                    }
+                }
+                info!(
+                    event.name = "rebalance_completed",
+                    cycle.id = %cycle_id,
+                    market.id = market_id,
+                    lp.authority = %authority,
+                    rebalance.attempt_id = %attempt_id,
+                    rebalance.outcome = "executed",
+                    rebalance.cooldown_secs = rebalance_cooldown.as_secs(),
+                    histogram.rebalance_duration_ms = attempt_started_at.elapsed().as_millis() as f64,
+                );
+            }
+            Ok(RebalanceOutcome::Skipped) => {
+                info!(
+                    event.name = "rebalance_skipped",
+                    cycle.id = %cycle_id,
+                    market.id = market_id,
+                    lp.authority = %authority,
+                    rebalance.attempt_id = %attempt_id,
+                    rebalance.outcome = "skipped",
+                    monotonic_counter.rebalance_skips_total = 1_u64,
+                    histogram.rebalance_duration_ms = attempt_started_at.elapsed().as_millis() as f64,
+                );
+            }
+            Err(error) => {
+                runtime.rebalanced(attempt_started_at);
+                error!(
+                    event.name = "rebalance_failed",
+                    cycle.id = %cycle_id,
+                    market.id = market_id,
+                    lp.authority = %authority,
+                    rebalance.attempt_id = %attempt_id,
+                    rebalance.outcome = "error",
+                    rebalance.cooldown_secs = rebalance_cooldown.as_secs(),
+                    histogram.rebalance_duration_ms = attempt_started_at.elapsed().as_millis() as f64,
+                    ?error,
+                    "rebalance failed; cooldown starts now"
+                );
+                match refresh_position_state(program, market_id, authority, sender.reporting())
+                    .instrument(info_span!(
+                        "state.refresh",
+                        cycle.id = %cycle_id,
+                        market.id = market_id,
+                        lp.authority = %authority,
+                        rebalance.attempt_id = %attempt_id,
+                    ))
+                    .await
+                {
+                    Ok((new_market_state, new_position, new_balances)) => {
+                        market_state = new_market_state;
+                        position = new_position;
+                        balances = new_balances;
+                    }
+                    Err(error) => {
+                        error!(
+                            event.name = "rebalance_failure_refresh_failed",
+                            cycle.id = %cycle_id,
+                            market.id = market_id,
+                            lp.authority = %authority,
+                            rebalance.attempt_id = %attempt_id,
+                            ?error,
+                            "refresh after rebalance failure failed; skipping quote update"
+                        );
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        let ctx = StrategyContext {
+            market_id,
+            market_state: &market_state,
+            position: &position,
+            balances: &balances,
+        };
+        actions = runtime.decide(Trigger::Tick, &ctx, overrides, sender.reporting());
+    }
+
+    // 4. Apply the requested quote, if any
+    let current_base_flow = position.base_flow_u64;
+    let current_quote_flow = position.quote_flow_u64;
+    let update = actions.iter().find_map(|action| match *action {
+        Action::UpdateFlows {
+            base_flow,
+            quote_flow,
+            reference_index,
+        } => Some((base_flow, quote_flow, reference_index)),
+        _ => None,
+    });
+
+    if let Some((target_base_flow, target_quote_flow, reference_index)) = update {
+        info!(
+            event.name = "flow_update_planned",
+            cycle.id = %cycle_id,
+            market.id = market_id,
+            lp.authority = %authority,
+            quote.threshold_bps = quote_threshold_bps,
+            quote.current_base_flow = current_base_flow,
+            quote.target_base_flow = target_base_flow,
+            quote.current_quote_flow = current_quote_flow,
+            quote.target_quote_flow = target_quote_flow,
+        );
+
+        // Best effort: the update goes ahead either way.
+        if let Err(error) =
+            execute_open_next_window(program, market_id, &market_state, sender).await
+        {
+            warn!(
+                event.name = "oracle_flow_open_window_failed",
+                market.id = market_id,
+                ?error,
+            );
+        }
+        let (final_base_flow, final_quote_flow) = execute_update_flows_with_backoff(
+            program,
+            market_id,
+            target_base_flow,
+            target_quote_flow,
+            reference_index,
+            flow_reduction_factor,
+            max_flow_reduction_attempts,
+            sender,
+        )
+        .instrument(info_span!(
+            "twob.update_flows",
+            cycle.id = %cycle_id,
+            market.id = market_id,
+            lp.authority = %authority,
+            twob.instruction = "update_liquidity_flows",
+            twob.reference_index = reference_index,
+        ))
+        .await?;
+        record_flow_change(
+            market_id,
+            &position,
+            market_state.current_slot,
+            final_base_flow,
+            final_quote_flow,
+        );
+
+        // A competing update may have landed after ours; requote on fresh state if so.
+        match verify_flows(program, market_id, final_base_flow, final_quote_flow).await {
+            Ok(None) => {}
+            Ok(Some(mismatch)) => {
+                alerter.notify(
+                    AlertKind::FlowMismatch,
+                    Some(market_id),
+                    format!("{mismatch}; requoting"),
+                );
+                control.request_force_update();
+            }
+            Err(error) => warn!(
+                event.name = "flow_update_verify_failed",
+                cycle.id = %cycle_id,
+                market.id = market_id,
+                ?error,
+            ),
+        }
+
+        info!(
+            event.name = "flow_update_completed",
+            cycle.id = %cycle_id,
+            market.id = market_id,
+            lp.authority = %authority,
+            twob.instruction = "update_liquidity_flows",
+            twob.reference_index = reference_index,
+            quote.final_base_flow = final_base_flow,
+            quote.final_quote_flow = final_quote_flow,
+        );
+    } else {
+        info!(
+            event.name = "flow_update_skipped",
+            cycle.id = %cycle_id,
+            market.id = market_id,
+            lp.authority = %authority,
+            quote.threshold_bps = quote_threshold_bps,
+            quote.current_base_flow = current_base_flow,
+            quote.current_quote_flow = current_quote_flow,
+        );
+    }
+
+    emit_position_snapshot(
+        "cycle_end",
+        cycle_id,
+        market_id,
+        authority,
+        &market_state,
+        &position,
+        &balances,
+        base_token_decimals,
+        quote_token_decimals,
+        price_data.price,
+    );
+    info!(
+        event.name = "oracle_flow_cycle_end",
+        cycle.id = %cycle_id,
+        market.id = market_id,
+        lp.authority = %authority,
+        cycle.outcome = "ok",
+        monotonic_counter.cycles_total = 1_u64,
+        histogram.cycle_duration_ms = cycle_started_at.elapsed().as_millis() as f64,
+    );
+
+    Ok(())
+}
+
+/// Zero the position's flows so it stops quoting. Used when the control plane asks the bot
+/// to stand down.
+async fn force_stop(
+    program: &OracleProgram,
+    market_id: u64,
+    sender: &TxSender,
+) -> anyhow::Result<()> {
+    let market_state = fetch_market_state(program, market_id).await?;
+    let reference_index = nearest_reference_index(
+        market_state.current_slot,
+        market_state.market.end_slot_interval,
+    );
+
+    if let Err(error) = execute_open_next_window(program, market_id, &market_state, sender).await {
+        warn!(
+            event.name = "oracle_flow_open_window_failed",
+            market.id = market_id,
+            ?error,
+        );
+    }
+    let sender = sender.with_options(SendOptions::urgent());
+    execute_update_flows(program, market_id, 0, 0, reference_index, &sender).await?;
+    info!(
+        event.name = "oracle_flow_flows_zeroed",
+        market.id = market_id,
+        twob.instruction = "update_liquidity_flows",
+        twob.reference_index = reference_index,
+    );
+    Ok(())
+}
+
+async fn refresh_position_state(
+    program: &OracleProgram,
+    market_id: u64,
+    authority: &anchor_client::solana_sdk::pubkey::Pubkey,
+    reporting: &Reporting,
+) -> anyhow::Result<(MarketState, LiquidityPosition, LiquidityPositionBalances)> {
+    let market_state = fetch_market_state(program, market_id).await?;
+    let position = fetch_liquidity_position(program, market_id, authority).await?;
+    let balances = get_liquidity_position_balances_dumped(
+        program,
+        position,
+        market_state.bookkeeping,
+        market_state.market,
+        market_state.current_slot,
+        reporting.crash_dump.as_ref(),
+    )
+    .await?;
+    record_runway(market_id, &position, &market_state.market, &balances);
+
+    Ok((market_state, position, balances))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn emit_position_snapshot(
+    stage: &str,
+    cycle_id: &str,
+    market_id: u64,
+    authority: &anchor_client::solana_sdk::pubkey::Pubkey,
+    market_state: &MarketState,
+    position: &LiquidityPosition,
+    balances: &LiquidityPositionBalances,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+    oracle_price: f64,
+) {
+    let quote_ui = telemetry::token_amount_ui(balances.quote_balance, quote_token_decimals);
+    let total_quote_value = position_value(
+        balances,
+        base_token_decimals,
+        quote_token_decimals,
+        oracle_price,
+    );
+    let quote_weight = if total_quote_value > 0.0 {
+        quote_ui / total_quote_value
+    } else {
+        0.0
+    };
+    let inventory_deviation_bps =
+        ((quote_weight - BALANCED_QUOTE_VALUE_WEIGHT).abs() * 10_000.0).round();
+
+    info!(
+        event.name = "position_balance_snapshot",
+        snapshot.stage = stage,
+        cycle.id = %cycle_id,
+        slot.current = market_state.current_slot,
+        market.id = market_id,
+        lp.authority = %authority,
+        base.mint = %market_state.market.base_mint,
+        quote.mint = %market_state.market.quote_mint,
+        position.base_balance.raw = balances.base_balance,
+        position.quote_balance.raw = balances.quote_balance,
+        position.base_debt.raw = balances.base_debt,
+        position.quote_debt.raw = balances.quote_debt,
+        position.base_flow.raw = position.base_flow_u64,
+        position.quote_flow.raw = position.quote_flow_u64,
+        market.base_flow.raw = market_state.market.base_flow,
+        market.quote_flow.raw = market_state.market.quote_flow,
+        market.end_slot_interval = market_state.market.end_slot_interval,
+        inventory.quote_weight = quote_weight,
+        inventory.quote_weight_target = BALANCED_QUOTE_VALUE_WEIGHT,
+        gauge.position_base_balance_raw = balances.base_balance as f64,
+        gauge.position_quote_balance_raw = balances.quote_balance as f64,
+        gauge.inventory_deviation_bps = inventory_deviation_bps,
+    );
+}
+
+/// The position's balances valued in quote tokens at `oracle_price`.
+fn position_value(
+    balances: &LiquidityPositionBalances,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+    oracle_price: f64,
+) -> f64 {
+    let base_ui = telemetry::token_amount_ui(balances.base_balance, base_token_decimals);
+    let quote_ui = telemetry::token_amount_ui(balances.quote_balance, quote_token_decimals);
+    base_ui.mul_add(oracle_price, quote_ui)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn execute_update_flows_with_backoff(
+    program: &OracleProgram,
+    market_id: u64,
+    base_flow: u64,
+    quote_flow: u64,
+    reference_index: u64,
+    flow_reduction_factor: f64,
+    max_flow_reduction_attempts: usize,
+    sender: &TxSender,
+) -> anyhow::Result<(u64, u64)> {
+    let mut candidate_base_flow = base_flow.max(1);
+    let mut candidate_quote_flow = quote_flow.max(1);
+
+    for attempt in 0..max_flow_reduction_attempts {
+        let ix = build_update_liquidity_flows_instruction(
+            program,
+            market_id,
+            twob_anchor::client::args::UpdateLiquidityFlows {
+                reference_index,
+                base_flow_u64: candidate_base_flow,
+                quote_flow_u64: candidate_quote_flow,
+            },
+        )?;
+
+        let simulation = sender.simulate(vec![ix]).await?;
+        let Some(error) = simulation.err else {
+            execute_update_flows(
+                program,
+                market_id,
+                candidate_base_flow,
+                candidate_quote_flow,
+                reference_index,
+                sender,
+            )
+            .await?;
+            return Ok((candidate_base_flow, candidate_quote_flow));
+        };
+
+        if is_blockhash_not_found(&error) {
+            // Transient: the blockhash hasn't propagated to all validators yet.
+            // The next iteration simulates again, against the node's latest blockhash.
+            warn!(
+                event.name = "flow_update_simulation_retry",
+                twob.instruction = "update_liquidity_flows",
+                twob.reference_index = reference_index,
+                update.attempt = attempt + 1,
+                update.reason = "blockhash_not_found",
+                "simulation returned BlockhashNotFound; retrying with fresh blockhash"
+            );
+            continue;
+        }
+
+        if is_liquidity_position_unhealthy(&error, simulation.logs.as_deref()) {
+            let next_base_flow = reduce_flow(candidate_base_flow, flow_reduction_factor);
+            let next_quote_flow = reduce_flow(candidate_quote_flow, flow_reduction_factor);
+
+            warn!(
+                event.name = "flow_update_flow_reduced",
+                twob.instruction = "update_liquidity_flows",
+                twob.reference_index = reference_index,
+                update.attempt = attempt + 1,
+                update.reason = "liquidity_position_unhealthy",
+                quote.previous_base_flow = candidate_base_flow,
+                quote.next_base_flow = next_base_flow,
+                quote.previous_quote_flow = candidate_quote_flow,
+                quote.next_quote_flow = next_quote_flow,
+            );
+
+            if next_base_flow == candidate_base_flow && next_quote_flow == candidate_quote_flow {
+                anyhow::bail!(
+                    "Unable to reduce flows further after LiquidityPositionUnhealthy. Last attempted flows: base={}, quote={}",
+                    candidate_base_flow,
+                    candidate_quote_flow
+                );
+            }
+
+            candidate_base_flow = next_base_flow;
+            candidate_quote_flow = next_quote_flow;
+            continue;
+        }
+
+        return Err(anyhow::Error::new(TransactionFailed {
+            signature: None,
+            error,
+            logs: simulation.logs,
+        })
+        .context("Update-flows simulation failed with non-retriable error"));
+    }
+
+    anyhow::bail!(
+        "Failed to find healthy flows after {} attempts. Last attempted base={} quote={}",
+        max_flow_reduction_attempts,
+        candidate_base_flow,
+        candidate_quote_flow
+    )
+}
+
+fn is_blockhash_not_found(err: &TransactionError) -> bool {
+    matches!(err, TransactionError::BlockhashNotFound)
+}
+
+fn is_liquidity_position_unhealthy(err: &TransactionError, logs: Option<&[String]>) -> bool {
+    program_error(err, logs) == Some(ProgramErrorCode::LiquidityPositionUnhealthy)
+}
+
+fn reduce_flow(flow: u64, factor: f64) -> u64 {
+    if flow <= 1 {
+        return flow;
+    }
+
+    let reduced = ((flow as f64) * factor).floor() as u64;
+    reduced.clamp(1, flow - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduce_flow_always_makes_progress_when_possible() {
+        assert_eq!(reduce_flow(100, 0.99), 99);
+        assert_eq!(reduce_flow(2, 0.99), 1);
+        assert_eq!(reduce_flow(1, 0.99), 1);
+    }
+}
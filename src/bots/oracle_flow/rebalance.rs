@@ -2,9 +2,7 @@ use std::{sync::Arc, time::Duration};
 
 use anchor_client::{
     Program,
-    solana_sdk::{
-        commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair, signer::Signer,
-    },
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
 };
 use anchor_lang::solana_program::{program_pack::Pack, system_instruction};
 use anchor_spl::{
@@ -14,16 +12,19 @@ use anchor_spl::{
 use anyhow::{Context, ensure};
 use tokio::time::sleep;
 use tracing::{Instrument, info, info_span, warn};
-use twob_market_making::{
-    ARRAY_LENGTH, AccountResolver, LIQUIDITY_AMPLIFICATION, LiquidityPositionBalances, MarketState,
+
+use crate::{
+    AccountResolver, LIQUIDITY_AMPLIFICATION, LiquidityPositionBalances, MarketState, ProgramPayer,
     build_withdraw_liquidity_instruction, execute_add_liquidity, execute_withdraw_liquidity,
-    get_token_program_id,
+    get_token_program_id, nearest_reference_index,
+    price::PriceData,
+    twob_anchor,
+    tx::{TransactionFailed, TxSender, TxSigner},
 };
 
-use crate::{
+use super::{
     config::JupiterConfig,
     jupiter::{JupiterUltraClient, SwapDirection},
-    price::PriceData,
     telemetry,
 };
 
@@ -105,7 +106,7 @@ pub fn needs_rebalance(
 
 #[allow(clippy::too_many_arguments)]
 pub async fn execute_rebalance(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     http_client: &reqwest::Client,
     market_id: u64,
     market_state: &MarketState,
@@ -115,7 +116,8 @@ pub async fn execute_rebalance(
     quote_token_decimals: u8,
     current_base_flow: u64,
     current_quote_flow: u64,
-    liquidity_provider: Arc<Keypair>,
+    liquidity_provider: Arc<dyn TxSigner>,
+    sender: &TxSender,
     jupiter_config: &JupiterConfig,
     _reduction_factor: f64,
     _max_reduction_attempts: usize,
@@ -311,7 +313,7 @@ pub async fn execute_rebalance(
         program,
         market_id,
         withdraw_reference_index,
-        liquidity_provider.clone(),
+        sender,
         withdraw_plan,
     )
     .instrument(info_span!(
@@ -386,14 +388,8 @@ pub async fn execute_rebalance(
     // not the SPL token ATA balance. Always unwrap wSOL input before ordering, otherwise
     // Jupiter can spend fee-wallet SOL while the withdrawn wSOL stays stranded outside
     // the liquidity position.
-    prepare_wsol_input_for_jupiter(
-        program,
-        &input_mint,
-        &lp_input_ata,
-        swap_amount,
-        liquidity_provider.clone(),
-    )
-    .await?;
+    prepare_wsol_input_for_jupiter(program, &input_mint, &lp_input_ata, swap_amount, sender)
+        .await?;
 
     log_wallet_balance_snapshot(
         program,
@@ -509,7 +505,7 @@ pub async fn execute_rebalance(
         &market_state.market.base_mint,
         &liquidity_provider.pubkey(),
         deposit_base_lamports,
-        liquidity_provider.clone(),
+        sender,
     )
     .await?;
     deposit_quote_lamports = prepare_deposit_balance(
@@ -517,7 +513,7 @@ pub async fn execute_rebalance(
         &market_state.market.quote_mint,
         &liquidity_provider.pubkey(),
         deposit_quote_lamports,
-        liquidity_provider.clone(),
+        sender,
     )
     .await?;
 
@@ -546,7 +542,7 @@ pub async fn execute_rebalance(
         deposit_base_lamports,
         deposit_quote_lamports,
         add_reference_index,
-        liquidity_provider.clone(),
+        sender,
     )
     .instrument(info_span!(
         "twob.add_liquidity",
@@ -596,16 +592,16 @@ pub async fn execute_rebalance(
 
 #[allow(clippy::too_many_arguments)]
 async fn execute_exact_withdraw_liquidity(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     market_id: u64,
     reference_index: u64,
-    signer: Arc<Keypair>,
+    sender: &TxSender,
     plan: RebalancePlan,
 ) -> anyhow::Result<RebalancePlan> {
     let ix = build_withdraw_liquidity_instruction(
         program,
         market_id,
-        crate::twob_anchor::client::args::WithdrawLiquidity {
+        twob_anchor::client::args::WithdrawLiquidity {
             reference_index,
             base_lamports: plan.withdraw_base_lamports,
             quote_lamports: plan.withdraw_quote_lamports,
@@ -613,20 +609,14 @@ async fn execute_exact_withdraw_liquidity(
     )
     .await?;
 
-    let signed_tx = program
-        .request()
-        .instruction(ix)
-        .signer(signer.clone())
-        .signed_transaction()
-        .await?;
-
-    let simulation = program.rpc().simulate_transaction(&signed_tx).await?;
-    if let Some(err) = simulation.value.err {
-        anyhow::bail!(
-            "Withdraw simulation failed. err={:?} logs={:?}",
-            err,
-            simulation.value.logs
-        );
+    let simulation = sender.simulate(vec![ix]).await?;
+    if let Some(error) = simulation.err {
+        return Err(anyhow::Error::new(TransactionFailed {
+            signature: None,
+            error,
+            logs: simulation.logs,
+        })
+        .context("Withdraw simulation failed"));
     }
 
     execute_withdraw_liquidity(
@@ -635,7 +625,7 @@ async fn execute_exact_withdraw_liquidity(
         plan.withdraw_base_lamports,
         plan.withdraw_quote_lamports,
         reference_index,
-        signer,
+        sender,
     )
     .await?;
 
@@ -643,13 +633,13 @@ async fn execute_exact_withdraw_liquidity(
 }
 
 async fn log_rebalance_transfer_accounts(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     market_id: u64,
     market_state: &MarketState,
     authority: anchor_client::solana_sdk::pubkey::Pubkey,
     plan: RebalancePlan,
 ) {
-    let resolver = AccountResolver::new(crate::twob_anchor::ID);
+    let resolver = AccountResolver::new(twob_anchor::ID);
     let market_pda = resolver.market_pda(market_id).address();
 
     let base_token_program =
@@ -737,7 +727,7 @@ async fn log_rebalance_transfer_accounts(
 }
 
 async fn log_token_account_state(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     label: &str,
     token_account: anchor_client::solana_sdk::pubkey::Pubkey,
 ) {
@@ -783,11 +773,11 @@ async fn log_token_account_state(
 }
 
 async fn oracle_flow_reference_index(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     end_slot_interval: u64,
 ) -> anyhow::Result<u64> {
     let current_slot = program.rpc().get_slot().await?;
-    let reference_index = (current_slot + ARRAY_LENGTH / 2) / ARRAY_LENGTH / end_slot_interval;
+    let reference_index = nearest_reference_index(current_slot, end_slot_interval);
     ensure!(
         reference_index > 0,
         "Oracle-flow rebalance requires reference_index > 0 with the current calculation"
@@ -965,7 +955,7 @@ struct WalletBalanceSnapshot {
 
 #[allow(clippy::too_many_arguments)]
 async fn log_wallet_balance_snapshot(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     market_state: &MarketState,
     owner: &Pubkey,
     balances: &LiquidityPositionBalances,
@@ -1040,7 +1030,7 @@ async fn log_wallet_balance_snapshot(
 }
 
 async fn read_wallet_balance_snapshot(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     market_state: &MarketState,
     owner: &Pubkey,
 ) -> anyhow::Result<WalletBalanceSnapshot> {
@@ -1079,17 +1069,17 @@ fn clamp_delta(delta: i128) -> i64 {
 }
 
 async fn prepare_wsol_input_for_jupiter(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     input_mint: &Pubkey,
     wsol_ata: &Pubkey,
     swap_amount: u64,
-    signer: Arc<Keypair>,
+    sender: &TxSender,
 ) -> anyhow::Result<()> {
     if !is_native_sol_mint(input_mint) {
         return Ok(());
     }
 
-    let lp_pubkey = signer.pubkey();
+    let lp_pubkey = sender.payer();
     let wsol_balance = read_ata_balance_or_zero(program, wsol_ata).await?;
 
     if wsol_balance > 0 {
@@ -1108,11 +1098,8 @@ async fn prepare_wsol_input_for_jupiter(
         )
         .map_err(|e| anyhow::anyhow!("Failed to build close_account instruction: {e}"))?;
 
-        program
-            .request()
-            .instruction(close_ix)
-            .signer(signer.clone())
-            .send()
+        sender
+            .send(vec![close_ix])
             .await
             .context("Failed to close wSOL ATA")?;
     }
@@ -1142,7 +1129,7 @@ async fn prepare_wsol_input_for_jupiter(
 }
 
 async fn read_ata_balance_or_zero(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     token_account: &Pubkey,
 ) -> anyhow::Result<u64> {
     let Some(account) = read_token_account(program, token_account).await? else {
@@ -1154,7 +1141,7 @@ async fn read_ata_balance_or_zero(
 }
 
 async fn read_token_account(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     token_account: &Pubkey,
 ) -> anyhow::Result<Option<anchor_client::solana_sdk::account::Account>> {
     let response = program
@@ -1166,7 +1153,7 @@ async fn read_token_account(
 }
 
 async fn read_swap_input_balance(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     input_mint: &Pubkey,
     input_ata: &Pubkey,
     owner: &Pubkey,
@@ -1186,7 +1173,7 @@ async fn read_swap_input_balance(
 }
 
 async fn wait_for_ata_balance_at_least(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     token_account: &Pubkey,
     expected_amount: u64,
 ) -> anyhow::Result<u64> {
@@ -1204,7 +1191,7 @@ async fn wait_for_ata_balance_at_least(
 }
 
 async fn wait_for_native_balance_at_least(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     owner: &Pubkey,
     expected_amount: u64,
 ) -> anyhow::Result<u64> {
@@ -1226,11 +1213,11 @@ async fn wait_for_native_balance_at_least(
 }
 
 async fn prepare_deposit_balance(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     mint: &Pubkey,
     owner: &Pubkey,
     requested_amount: u64,
-    signer: Arc<Keypair>,
+    sender: &TxSender,
 ) -> anyhow::Result<u64> {
     if requested_amount == 0 {
         return Ok(0);
@@ -1240,7 +1227,7 @@ async fn prepare_deposit_balance(
     let ata = get_associated_token_address_with_program_id(owner, mint, &token_program);
 
     if is_native_sol_mint(mint) {
-        return prepare_wsol_deposit_balance(program, &ata, requested_amount, signer).await;
+        return prepare_wsol_deposit_balance(program, &ata, requested_amount, sender).await;
     }
 
     let available = wait_for_ata_balance_at_least(program, &ata, requested_amount).await?;
@@ -1256,17 +1243,17 @@ async fn prepare_deposit_balance(
 }
 
 async fn prepare_wsol_deposit_balance(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     wsol_ata: &Pubkey,
     requested_amount: u64,
-    signer: Arc<Keypair>,
+    sender: &TxSender,
 ) -> anyhow::Result<u64> {
     let current_wsol = read_ata_balance_or_zero(program, wsol_ata).await?;
     if current_wsol >= requested_amount {
         return Ok(requested_amount);
     }
 
-    let owner = signer.pubkey();
+    let owner = sender.payer();
     let deficit = requested_amount - current_wsol;
     let required_native = deficit
         .checked_add(FEE_RESERVE_LAMPORTS)
@@ -1301,13 +1288,8 @@ async fn prepare_wsol_deposit_balance(
     )
     .map_err(|e| anyhow::anyhow!("Failed to build sync_native instruction: {e}"))?;
 
-    program
-        .request()
-        .instruction(create_ata_ix)
-        .instruction(transfer_ix)
-        .instruction(sync_ix)
-        .signer(signer)
-        .send()
+    sender
+        .send(vec![create_ata_ix, transfer_ix, sync_ix])
         .await
         .context("Failed to wrap native SOL for add_liquidity")?;
 
@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use tracing::{info, info_span};
+
+use crate::{
+    control::ThresholdOverrides,
+    price::PriceData,
+    quote::{calculate_optimal_quote, should_update_quote},
+    strategy::{Action, Strategy, StrategyContext},
+};
+
+use super::rebalance::needs_rebalance;
+
+/// Quotes around the oracle price blended with the inventory-implied price, and asks for a
+/// rebalance when inventory drifts too far from the oracle.
+///
+/// The thresholds in force are the configured ones under the control plane's overrides.
+/// The cooldown runs from the last rebalance the runtime attempted, as only it knows
+/// whether one was.
+pub struct OracleFlowStrategy {
+    pub quote_threshold_bps: u64,
+    pub rebalance_threshold_bps: u64,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+    pub optimal_quote_weight: f64,
+    pub rebalance_cooldown: Duration,
+    pub last_rebalance_at: Option<Instant>,
+    configured_thresholds_bps: (u64, u64),
+    last_price: Option<PriceData>,
+}
+
+impl OracleFlowStrategy {
+    pub fn new(
+        quote_threshold_bps: u64,
+        rebalance_threshold_bps: u64,
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+        optimal_quote_weight: f64,
+        rebalance_cooldown: Duration,
+    ) -> Self {
+        Self {
+            quote_threshold_bps,
+            rebalance_threshold_bps,
+            base_token_decimals,
+            quote_token_decimals,
+            optimal_quote_weight,
+            rebalance_cooldown,
+            last_rebalance_at: None,
+            configured_thresholds_bps: (quote_threshold_bps, rebalance_threshold_bps),
+            last_price: None,
+        }
+    }
+
+    fn rebalance_due(&self, price: &PriceData, ctx: &StrategyContext<'_>) -> bool {
+        if let Some(elapsed) = self
+            .last_rebalance_at
+            .map(|at| at.elapsed())
+            .filter(|elapsed| *elapsed < self.rebalance_cooldown)
+        {
+            info!(
+                event.name = "rebalance_skipped",
+                market.id = ctx.market_id,
+                rebalance.reason = "cooldown_active",
+                rebalance.cooldown_elapsed_secs = elapsed.as_secs(),
+                rebalance.cooldown_required_secs = self.rebalance_cooldown.as_secs(),
+                monotonic_counter.rebalance_skips_total = 1_u64,
+            );
+            return false;
+        }
+
+        let needed = {
+            let rebalance_evaluate_span = info_span!(
+                "rebalance.evaluate",
+                market.id = ctx.market_id,
+                rebalance.threshold_bps = self.rebalance_threshold_bps,
+            );
+            let _rebalance_evaluate_guard = rebalance_evaluate_span.enter();
+            needs_rebalance(
+                price,
+                ctx.balances,
+                self.base_token_decimals,
+                self.quote_token_decimals,
+                self.rebalance_threshold_bps,
+            )
+        };
+        if !needed {
+            info!(
+                event.name = "rebalance_skipped",
+                market.id = ctx.market_id,
+                rebalance.reason = "within_threshold",
+                monotonic_counter.rebalance_skips_total = 1_u64,
+            );
+        }
+        needed
+    }
+
+    fn quote(&self, price: &PriceData, ctx: &StrategyContext<'_>) -> Vec<Action> {
+        let optimal = {
+            let quote_span = info_span!("quote.compute", market.id = ctx.market_id);
+            let _quote_guard = quote_span.enter();
+            calculate_optimal_quote(
+                price,
+                ctx.position,
+                ctx.market_state,
+                ctx.balances,
+                self.base_token_decimals,
+                self.quote_token_decimals,
+                self.optimal_quote_weight,
+            )
+        };
+
+        if !should_update_quote(
+            ctx.position.base_flow_u64,
+            ctx.position.quote_flow_u64,
+            &optimal,
+            self.quote_threshold_bps,
+        ) {
+            return Vec::new();
+        }
+        vec![Action::UpdateFlows {
+            base_flow: optimal.base_flow,
+            quote_flow: optimal.quote_flow,
+            reference_index: ctx.nearest_reference_index(),
+        }]
+    }
+}
+
+impl Strategy for OracleFlowStrategy {
+    fn name(&self) -> &str {
+        "oracle-flow"
+    }
+
+    /// Requote against the last oracle price seen, e.g. after a rebalance moved inventory.
+    fn on_tick(&mut self, ctx: &StrategyContext<'_>) -> Vec<Action> {
+        match &self.last_price {
+            Some(price) => self.quote(price, ctx),
+            None => Vec::new(),
+        }
+    }
+
+    /// Rebalance first when inventory has drifted; the runtime calls `on_tick` with the
+    /// refreshed state afterwards. Otherwise requote.
+    fn on_price(&mut self, price: &PriceData, ctx: &StrategyContext<'_>) -> Vec<Action> {
+        self.last_price = Some(price.clone());
+        if self.rebalance_due(price, ctx) {
+            return vec![Action::Rebalance];
+        }
+        self.quote(price, ctx)
+    }
+
+    fn on_overrides(&mut self, overrides: &ThresholdOverrides) {
+        let (quote_threshold_bps, rebalance_threshold_bps) = self.configured_thresholds_bps;
+        self.quote_threshold_bps = overrides.quote_threshold_bps.unwrap_or(quote_threshold_bps);
+        self.rebalance_threshold_bps = overrides
+            .rebalance_threshold_bps
+            .unwrap_or(rebalance_threshold_bps);
+    }
+
+    fn on_rebalanced(&mut self, started_at: Instant) {
+        self.last_rebalance_at = Some(started_at);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::config::env_var;
+pub use crate::telemetry::{LogFormat, TelemetryInitConfig, init_telemetry};
+use anyhow::{Context, Result};
+
+const DEFAULT_SERVICE_NAME: &str = "twob-market-maker";
+const DEFAULT_BALANCE_SNAPSHOT_INTERVAL_SECS: u64 = 60;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TelemetryConfig {
+    pub service_name: String,
+    pub log_format: LogFormat,
+    pub balance_snapshot_interval_secs: u64,
+}
+
+impl TelemetryConfig {
+    pub fn from_env() -> Result<Self> {
+        Self::from_lookup(|key| env_var(key).ok())
+    }
+
+    fn from_lookup<F>(lookup: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let service_name = lookup("OTEL_SERVICE_NAME")
+            .filter(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+        let log_format = LogFormat::from_lookup(&lookup, LogFormat::Json)?;
+        let balance_snapshot_interval_secs = lookup("BALANCE_SNAPSHOT_INTERVAL_SECS")
+            .map(|value| {
+                value.parse::<u64>().with_context(|| {
+                    format!("invalid BALANCE_SNAPSHOT_INTERVAL_SECS value `{value}`")
+                })
+            })
+            .transpose()?
+            .unwrap_or(DEFAULT_BALANCE_SNAPSHOT_INTERVAL_SECS);
+
+        Ok(Self {
+            service_name,
+            log_format,
+            balance_snapshot_interval_secs,
+        })
+    }
+}
+
+pub fn balance_delta(after: u64, before: u64) -> i128 {
+    i128::from(after) - i128::from(before)
+}
+
+pub fn external_wallet_input_estimated(input_consumed: u64, available_budget: u64) -> u64 {
+    input_consumed.saturating_sub(available_budget)
+}
+
+pub fn token_amount_ui(raw_amount: u64, decimals: u8) -> f64 {
+    let scale = 10_u64.checked_pow(u32::from(decimals)).unwrap_or(1) as f64;
+    raw_amount as f64 / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn parses_telemetry_config_defaults() {
+        let config = TelemetryConfig::from_lookup(|_| None).unwrap();
+
+        assert_eq!(config.service_name, DEFAULT_SERVICE_NAME);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(
+            config.balance_snapshot_interval_secs,
+            DEFAULT_BALANCE_SNAPSHOT_INTERVAL_SECS
+        );
+    }
+
+    #[test]
+    fn parses_telemetry_config_from_lookup() {
+        let env = HashMap::from([
+            ("OTEL_SERVICE_NAME", "custom-service"),
+            ("TELEMETRY_STDOUT_JSON", "false"),
+            ("BALANCE_SNAPSHOT_INTERVAL_SECS", "15"),
+        ]);
+        let config =
+            TelemetryConfig::from_lookup(|key| env.get(key).map(|value| value.to_string()))
+                .unwrap();
+
+        assert_eq!(config.service_name, "custom-service");
+        assert_eq!(config.log_format, LogFormat::Pretty);
+        assert_eq!(config.balance_snapshot_interval_secs, 15);
+    }
+
+    #[test]
+    fn computes_balance_delta() {
+        assert_eq!(balance_delta(110, 100), 10);
+        assert_eq!(balance_delta(90, 100), -10);
+    }
+
+    #[test]
+    fn estimates_external_wallet_input() {
+        assert_eq!(external_wallet_input_estimated(150, 100), 50);
+        assert_eq!(external_wallet_input_estimated(80, 100), 0);
+    }
+
+    #[test]
+    fn formats_token_amount_ui() {
+        assert_eq!(token_amount_ui(1_000_000, 6), 1.0);
+        assert_eq!(token_amount_ui(1_000_000_000, 9), 1.0);
+    }
+}
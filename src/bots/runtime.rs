@@ -0,0 +1,367 @@
+//! What the flow bots drive their [`Strategy`] through, whether their own or one plugged in
+//! with [`oracle_flow::run_strategy`](super::oracle_flow::run_strategy) or
+//! [`inventory_flow::run_strategy`](super::inventory_flow::run_strategy).
+//!
+//! The bot fetches the state and carries out the actions; the [`StrategyRuntime`] has the
+//! strategy decide between the two, under the control plane's overrides, drops what the
+//! process's [`RiskEngine`] vetoes and audits the rest.
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+
+use tracing::{info_span, warn};
+
+use crate::{
+    control::ThresholdOverrides,
+    price::PriceData,
+    reporting::Reporting,
+    risk::{PositionExposure, RiskEngine, RiskViolation},
+    strategy::{Action, Strategy, StrategyContext, audit_decisions},
+    twob_anchor::events::MarketUpdateEvent,
+};
+
+/// What the strategy is asked to decide about.
+#[derive(Clone, Copy)]
+pub enum Trigger<'a> {
+    /// A timer tick, or an [`Action::Reevaluate`] coming due.
+    Tick,
+    MarketEvent(&'a MarketUpdateEvent),
+    Price(&'a PriceData),
+}
+
+/// One bot's strategy, shared by the tasks that evaluate it, and the risk engine it shares
+/// with the process's other bots. Cheap to clone.
+#[derive(Clone)]
+pub struct StrategyRuntime {
+    strategy: Arc<Mutex<Box<dyn Strategy>>>,
+    name: Arc<str>,
+    risk: Arc<RiskEngine>,
+}
+
+impl StrategyRuntime {
+    pub fn new(strategy: Box<dyn Strategy>, risk: Arc<RiskEngine>) -> Self {
+        Self {
+            name: strategy.name().into(),
+            strategy: Arc::new(Mutex::new(strategy)),
+            risk,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn risk(&self) -> &RiskEngine {
+        &self.risk
+    }
+
+    /// Report the bot's position on `market_id` to the risk engine. Returns the breach that
+    /// halted the engine while the position still quotes, whichever bot's position caused
+    /// it, so the bot pulls its own quotes with
+    /// [`pull_all_quotes`](crate::risk::pull_all_quotes).
+    pub fn observe(&self, market_id: u64, exposure: PositionExposure) -> Option<RiskViolation> {
+        let quoting = exposure.base_flow > 0 || exposure.quote_flow > 0;
+        self.risk
+            .observe(market_id, exposure)
+            .or_else(|| self.risk.halted().filter(|_| quoting))
+    }
+
+    fn strategy(&self) -> MutexGuard<'_, Box<dyn Strategy>> {
+        self.strategy.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Have the strategy decide what to do about `trigger` in `ctx` under `overrides`, drop
+    /// the actions the risk engine vetoes and audit the rest through `reporting`.
+    pub fn decide(
+        &self,
+        trigger: Trigger<'_>,
+        ctx: &StrategyContext<'_>,
+        overrides: &ThresholdOverrides,
+        reporting: &Reporting,
+    ) -> Vec<Action> {
+        let actions = {
+            let mut strategy = self.strategy();
+            strategy.on_overrides(overrides);
+            info_span!("strategy.evaluate", market.id = ctx.market_id).in_scope(|| match trigger {
+                Trigger::Tick => strategy.on_tick(ctx),
+                Trigger::MarketEvent(event) => strategy.on_market_event(event, ctx),
+                Trigger::Price(price) => strategy.on_price(price, ctx),
+            })
+        };
+        let actions = self.vet(ctx.market_id, actions);
+        audit_decisions(&self.name, ctx, &actions, reporting);
+        actions
+    }
+
+    /// Tell the strategy the rebalance it asked for was attempted from `started_at`.
+    pub fn rebalanced(&self, started_at: Instant) {
+        self.strategy().on_rebalanced(started_at);
+    }
+
+    fn vet(&self, market_id: u64, actions: Vec<Action>) -> Vec<Action> {
+        actions
+            .into_iter()
+            .filter(|action| match self.risk.check(market_id, action) {
+                Ok(()) => true,
+                Err(violation) => {
+                    warn!(
+                        event.name = "risk_action_vetoed",
+                        market.id = market_id,
+                        risk.action = ?action,
+                        risk.violation = %violation,
+                        monotonic_counter.risk_vetoes_total = 1_u64,
+                    );
+                    false
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+    use crate::{
+        LiquidityPositionBalances, MarketState,
+        accounts::test_market,
+        risk::RiskLimits,
+        twob_anchor::accounts::{Bookkeeping, LiquidityPosition},
+    };
+
+    /// What [`TenthStrategy`] was told, kept outside it so the test can see it.
+    #[derive(Default)]
+    struct Seen {
+        overrides: Vec<ThresholdOverrides>,
+        rebalanced: Vec<Instant>,
+        events: usize,
+    }
+
+    /// Quotes a tenth of each balance on a tick, waits a second after a market event and
+    /// asks for a rebalance on a price below its floor.
+    struct TenthStrategy {
+        floor: f64,
+        seen: Arc<Mutex<Seen>>,
+    }
+
+    impl Strategy for TenthStrategy {
+        fn name(&self) -> &str {
+            "tenth"
+        }
+
+        fn on_tick(&mut self, ctx: &StrategyContext<'_>) -> Vec<Action> {
+            vec![Action::UpdateFlows {
+                base_flow: ctx.balances.base_balance / 10,
+                quote_flow: ctx.balances.quote_balance / 10,
+                reference_index: ctx.nearest_reference_index(),
+            }]
+        }
+
+        fn on_market_event(
+            &mut self,
+            _event: &MarketUpdateEvent,
+            _ctx: &StrategyContext<'_>,
+        ) -> Vec<Action> {
+            self.seen.lock().unwrap().events += 1;
+            vec![Action::Reevaluate {
+                after: Duration::from_secs(1),
+            }]
+        }
+
+        fn on_price(&mut self, price: &PriceData, ctx: &StrategyContext<'_>) -> Vec<Action> {
+            if price.price < self.floor {
+                return vec![Action::Rebalance];
+            }
+            self.on_tick(ctx)
+        }
+
+        fn on_overrides(&mut self, overrides: &ThresholdOverrides) {
+            self.seen.lock().unwrap().overrides.push(*overrides);
+        }
+
+        fn on_rebalanced(&mut self, started_at: Instant) {
+            self.seen.lock().unwrap().rebalanced.push(started_at);
+        }
+    }
+
+    fn market_state() -> MarketState {
+        MarketState {
+            market: test_market(),
+            bookkeeping: Bookkeeping {
+                base_per_quote: 0,
+                previous_base_per_quote: 0,
+                quote_per_base: 0,
+                previous_quote_per_base: 0,
+                slots_without_trade: 0,
+                last_update_slot: 0,
+                previous_update_slot: 0,
+                bump: 255,
+            },
+            current_slot: 260,
+        }
+    }
+
+    fn position() -> LiquidityPosition {
+        LiquidityPosition {
+            authority: Pubkey::new_unique(),
+            base_balance: 0,
+            quote_balance: 0,
+            base_per_quote_snapshot: 0,
+            quote_per_base_snapshot: 0,
+            slots_without_trade_snapshot: 0,
+            base_flow_u64: 0,
+            quote_flow_u64: 0,
+            base_debt: 0,
+            quote_debt: 0,
+            last_update_slot: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn drives_a_custom_strategy_through_each_trigger() {
+        let (state, position) = (market_state(), position());
+        let balances = LiquidityPositionBalances {
+            base_balance: 1_000,
+            quote_balance: 2_000,
+            base_debt: 0,
+            quote_debt: 0,
+        };
+        let ctx = StrategyContext {
+            market_id: 1,
+            market_state: &state,
+            position: &position,
+            balances: &balances,
+        };
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let runtime = StrategyRuntime::new(
+            Box::new(TenthStrategy {
+                floor: 100.0,
+                seen: seen.clone(),
+            }),
+            RiskEngine::new(RiskLimits::default()),
+        );
+        let reporting = Reporting::default();
+        let overrides = ThresholdOverrides {
+            flow_divisor: Some(4),
+            ..ThresholdOverrides::default()
+        };
+        assert_eq!(runtime.name(), "tenth");
+
+        let quote = Action::UpdateFlows {
+            base_flow: 100,
+            quote_flow: 200,
+            reference_index: 2,
+        };
+        assert_eq!(
+            runtime.decide(Trigger::Tick, &ctx, &overrides, &reporting),
+            vec![quote]
+        );
+
+        let event = MarketUpdateEvent {
+            market_id: 1,
+            base_flow: 5,
+            quote_flow: 6,
+        };
+        assert_eq!(
+            runtime.decide(
+                Trigger::MarketEvent(&event),
+                &ctx,
+                &ThresholdOverrides::default(),
+                &reporting,
+            ),
+            vec![Action::Reevaluate {
+                after: Duration::from_secs(1)
+            }]
+        );
+
+        let price = |price| PriceData {
+            price,
+            timestamp: 0,
+        };
+        assert_eq!(
+            runtime.decide(Trigger::Price(&price(150.0)), &ctx, &overrides, &reporting),
+            vec![quote]
+        );
+        assert_eq!(
+            runtime.decide(Trigger::Price(&price(50.0)), &ctx, &overrides, &reporting),
+            vec![Action::Rebalance]
+        );
+        let started_at = Instant::now();
+        runtime.clone().rebalanced(started_at);
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.events, 1);
+        assert_eq!(seen.overrides.len(), 4);
+        assert_eq!(seen.overrides[0], overrides);
+        assert_eq!(seen.overrides[1], ThresholdOverrides::default());
+        assert_eq!(seen.rebalanced, vec![started_at]);
+    }
+
+    #[test]
+    fn bots_sharing_an_engine_are_held_to_one_set_of_limits() {
+        let (state, position) = (market_state(), position());
+        let balances = LiquidityPositionBalances {
+            base_balance: 1_000,
+            quote_balance: 2_000,
+            base_debt: 0,
+            quote_debt: 0,
+        };
+        let risk = RiskEngine::new(RiskLimits {
+            max_base_flow: Some(150),
+            max_quote_deployed: Some(3_000),
+            ..RiskLimits::default()
+        });
+        let runtime = || {
+            let strategy = TenthStrategy {
+                floor: 0.0,
+                seen: Arc::default(),
+            };
+            StrategyRuntime::new(Box::new(strategy), risk.clone())
+        };
+        let (first, second) = (runtime(), runtime());
+        let reporting = Reporting::default();
+        let overrides = ThresholdOverrides::default();
+        let exposure = |quote_deployed, base_flow| PositionExposure {
+            base_deployed: 1_000,
+            quote_deployed,
+            base_flow,
+            quote_flow: 0,
+            value: 0.0,
+        };
+
+        assert_eq!(first.observe(1, exposure(2_000, 100)), None);
+        assert_eq!(second.observe(2, exposure(500, 0)), None);
+        assert_eq!(risk.exposure().base_flow, 100);
+
+        // A tenth of market 2's base would take the total base flow to 200.
+        let ctx = StrategyContext {
+            market_id: 2,
+            market_state: &state,
+            position: &position,
+            balances: &balances,
+        };
+        assert!(
+            second
+                .decide(Trigger::Tick, &ctx, &overrides, &reporting)
+                .is_empty()
+        );
+
+        // Market 2's quote takes the total over the deployed limit and halts the engine for
+        // both bots: the one still quoting is told to pull its quotes.
+        let violation = RiskViolation::QuoteDeployed {
+            total: 4_000,
+            limit: 3_000,
+        };
+        assert_eq!(second.observe(2, exposure(2_000, 0)), Some(violation));
+        assert_eq!(second.observe(2, exposure(2_000, 0)), None);
+        assert_eq!(first.observe(1, exposure(2_000, 100)), Some(violation));
+        assert_eq!(first.observe(1, exposure(2_000, 0)), None);
+        assert_eq!(risk.halted(), Some(violation));
+    }
+}
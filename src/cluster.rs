@@ -0,0 +1,116 @@
+//! Named clusters, so a deployment can say `CLUSTER=devnet` instead of spelling out an
+//! RPC and websocket URL pair. `RPC_URL` and `WS_URL` still override the preset's
+//! endpoints one at a time, e.g. to point a mainnet bot at a paid RPC provider.
+//!
+//! Only the endpoints change with the cluster: the instruction builders target the
+//! program ID this crate was built against, which is where TwoB is deployed on each
+//! preset.
+
+#[cfg(not(feature = "client"))]
+use std::env::var as env_var;
+use std::{fmt, str::FromStr};
+
+use anyhow::{Result, anyhow};
+
+#[cfg(feature = "client")]
+use crate::config::env_var;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ClusterPreset {
+    MainnetBeta,
+    Devnet,
+    Localnet,
+}
+
+impl ClusterPreset {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MainnetBeta => "mainnet-beta",
+            Self::Devnet => "devnet",
+            Self::Localnet => "localnet",
+        }
+    }
+
+    pub fn rpc_url(self) -> &'static str {
+        match self {
+            Self::MainnetBeta => "https://api.mainnet-beta.solana.com",
+            Self::Devnet => "https://api.devnet.solana.com",
+            Self::Localnet => "http://127.0.0.1:8899",
+        }
+    }
+
+    pub fn ws_url(self) -> &'static str {
+        match self {
+            Self::MainnetBeta => "wss://api.mainnet-beta.solana.com",
+            Self::Devnet => "wss://api.devnet.solana.com",
+            Self::Localnet => "ws://127.0.0.1:8900",
+        }
+    }
+
+    /// `CLUSTER`, or `None` when unset.
+    pub fn from_env() -> Result<Option<Self>> {
+        env_var("CLUSTER")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.parse())
+            .transpose()
+    }
+}
+
+impl FromStr for ClusterPreset {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "mainnet-beta" | "mainnet" => Ok(Self::MainnetBeta),
+            "devnet" => Ok(Self::Devnet),
+            "localnet" | "localhost" => Ok(Self::Localnet),
+            other => Err(anyhow!(
+                "invalid CLUSTER `{other}`, expected mainnet-beta, devnet or localnet"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ClusterPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The RPC and websocket URLs to use: `RPC_URL` and `WS_URL` where set, else those of
+/// `CLUSTER`, else those of `default`.
+pub fn endpoints_from_env(default: ClusterPreset) -> Result<(String, String)> {
+    let preset = ClusterPreset::from_env()?.unwrap_or(default);
+    let var = |name: &str| env_var(name).ok().filter(|value| !value.trim().is_empty());
+    Ok((
+        var("RPC_URL").unwrap_or_else(|| preset.rpc_url().to_string()),
+        var("WS_URL").unwrap_or_else(|| preset.ws_url().to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cluster_names() {
+        assert_eq!(
+            "mainnet-beta".parse::<ClusterPreset>().unwrap(),
+            ClusterPreset::MainnetBeta
+        );
+        assert_eq!(
+            " Devnet ".parse::<ClusterPreset>().unwrap(),
+            ClusterPreset::Devnet
+        );
+        assert_eq!(
+            "localhost".parse::<ClusterPreset>().unwrap(),
+            ClusterPreset::Localnet
+        );
+        assert!("testnet".parse::<ClusterPreset>().is_err());
+        assert_eq!(
+            ClusterPreset::Devnet.ws_url(),
+            "wss://api.devnet.solana.com"
+        );
+    }
+}
@@ -0,0 +1,1088 @@
+//! Configuration shared by the flow bots: a TOML file, overridden by environment variables,
+//! parsed into the settings every bot takes ([`CommonConfig`]) and a typed section per
+//! strategy ([`InventoryFlowSection`], [`OracleFlowSection`]).
+//!
+//! `CONFIG_FILE` names the file. Its keys are the environment variable names in lower
+//! case; top-level keys apply to every bot and a `[<bot>]` table, e.g. `[oracle-flow]`,
+//! to that bot alone, over the top level. A variable already set in the environment wins
+//! over the file, so one file can serve a fleet while a deployment overrides single values.
+//! Arrays are joined with commas. The file's values are held in [`vars`], not written to
+//! the environment, and every setting is read through it.
+//!
+//! `[market.<name>]` tables configure one market each: its `strategy` (the bot that runs
+//! it), `market_id` and whatever thresholds or decimals differ from the rest.
+//! A bot started with `MARKET=<name>` takes that table over its own and the top level;
+//! the orchestrator, given the file, runs one bot per market ([`markets`]).
+//!
+//! Reading a configuration records every setting that fails to parse or validate in
+//! [`ConfigErrors`], so a bad deployment is reported in full on its first start rather
+//! than one variable per restart.
+//!
+//! [`FlowArgs`] are the command-line flags the flow bots share. A flag overrides the
+//! variable it stands for, so settings resolve command line first, then environment,
+//! then file, then the [`profile`] `--profile` picks. Two of them don't start the bot:
+//! `--print-config-schema` prints the [`schema`] of every setting with its default, and
+//! `--check-config <PATH>` checks a file ([`check_config_file`]), so a deployment
+//! pipeline can gate on it.
+//!
+//! ```toml
+//! rpc_url = "https://api.mainnet-beta.solana.com"
+//! market_id = 3
+//!
+//! [oracle-flow]
+//! price_feed_secondary_urls = ["https://a.example/price", "https://b.example/price"]
+//! optimal_quote_weight = 0.2
+//!
+//! [inventory-flow]
+//! flow_divisor = 4
+//!
+//! [market.sol-usdc]
+//! strategy = "oracle-flow"
+//! market_id = 1
+//! quote_threshold_bps = 30
+//!
+//! [market.bonk-usdc]
+//! strategy = "oracle-flow"
+//! market_id = 4
+//! base_token_decimals = 5
+//!
+//! [market.jup-usdc]
+//! strategy = "inventory-flow"
+//! market_id = 7
+//! flow_divisor = 8
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anchor_client::{
+    Client, Cluster,
+    solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair},
+};
+use anyhow::{Context, Result, anyhow, bail, ensure};
+
+use crate::{
+    ProgramPayer,
+    alerts::{AlertConfig, Alerter},
+    cluster::{ClusterPreset, endpoints_from_env},
+    commitment_from_env,
+    control::{
+        admin::AdminConfig, emergency::EmergencyStopConfig, heartbeat::HeartbeatConfig,
+        probes::ProbeConfig, telegram::TelegramControlConfig,
+    },
+    crank::CrankConfig,
+    crash_dump::CrashDumpConfig,
+    event_bus::EventBusConfig,
+    jittered, program_payer,
+    reporting::Reporting,
+    risk::RiskLimits,
+    secrets,
+    slot_lag::SlotLagConfig,
+    telemetry::LogFormat,
+    twob_anchor,
+    tx::{SignerConfig, TxSender, TxSenderConfig, TxSigner},
+    webhooks::WebhookConfig,
+};
+
+pub mod profile;
+pub mod schema;
+pub mod vars;
+
+use profile::Profile;
+pub use vars::{env_var, env_vars, inherit, scoped, set_env_var, set_env_var_default};
+
+/// The most decimals a mint can have for one whole token, 10^decimals raw units, to fit a
+/// `u64`.
+const MAX_TOKEN_DECIMALS: u8 = 19;
+
+/// Bots with a section of their own in the file.
+const SECTIONS: [&str; 2] = ["inventory-flow", "oracle-flow"];
+
+/// Holds the `[market.<name>]` tables.
+const MARKETS: &str = "market";
+
+/// A config file's values for one bot, keyed by environment variable.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    values: BTreeMap<String, String>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path, bot: &str, market: Option<&str>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        Self::parse(&text, bot, market)
+            .with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// `bot`'s values, with those of the `[market.<market>]` table, when given, on top.
+    pub fn parse(text: &str, bot: &str, market: Option<&str>) -> Result<Self> {
+        let mut table = text.parse::<toml::Table>()?;
+        let mut markets = parse_markets(table.remove(MARKETS))?;
+        let mut values = BTreeMap::new();
+        let mut section = None;
+        for (key, value) in table {
+            match value {
+                toml::Value::Table(table) if SECTIONS.contains(&key.as_str()) => {
+                    if key == bot {
+                        section = Some(table);
+                    }
+                }
+                toml::Value::Table(_) => bail!("unknown section [{key}]"),
+                value => {
+                    values.insert(env_name(&key), env_value(&key, value)?);
+                }
+            }
+        }
+        for (key, value) in section.unwrap_or_default() {
+            values.insert(env_name(&key), env_value(&key, value)?);
+        }
+        if let Some(name) = market {
+            let market = markets
+                .remove(name)
+                .with_context(|| format!("no [market.{name}] section"))?;
+            ensure!(
+                market.strategy == bot,
+                "[market.{name}] is run by {}, not {bot}",
+                market.strategy
+            );
+            values.extend(market.values);
+        }
+        Ok(Self { values })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// The variables the file sets.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    /// Set each value whose variable isn't already set, for the bot alone ([`vars`]).
+    pub fn apply(&self) {
+        for (name, value) in &self.values {
+            set_env_var_default(name, value);
+        }
+    }
+}
+
+fn env_name(key: &str) -> String {
+    key.trim().replace('-', "_").to_ascii_uppercase()
+}
+
+fn env_value(key: &str, value: toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(value) => value,
+        toml::Value::Integer(value) => value.to_string(),
+        toml::Value::Float(value) => value.to_string(),
+        toml::Value::Boolean(value) => value.to_string(),
+        toml::Value::Datetime(value) => value.to_string(),
+        toml::Value::Array(values) => values
+            .into_iter()
+            .map(|value| env_value(key, value))
+            .collect::<Result<Vec<_>>>()?
+            .join(","),
+        toml::Value::Table(_) => bail!("`{key}` can't be a table"),
+    })
+}
+
+/// A `[market.<name>]` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarketSection {
+    pub name: String,
+    /// The bot that runs the market, e.g. `oracle-flow`.
+    pub strategy: String,
+    /// Everything else in the table, keyed by environment variable.
+    pub values: BTreeMap<String, String>,
+}
+
+/// The `[market.<name>]` tables in config file `text`, by name.
+pub fn markets(text: &str) -> Result<BTreeMap<String, MarketSection>> {
+    parse_markets(text.parse::<toml::Table>()?.remove(MARKETS))
+}
+
+fn parse_markets(markets: Option<toml::Value>) -> Result<BTreeMap<String, MarketSection>> {
+    let Some(markets) = markets else {
+        return Ok(BTreeMap::new());
+    };
+    let toml::Value::Table(markets) = markets else {
+        bail!("`{MARKETS}` must hold [{MARKETS}.<name>] tables");
+    };
+    markets
+        .into_iter()
+        .map(|(name, table)| {
+            let toml::Value::Table(mut table) = table else {
+                bail!("`{MARKETS}.{name}` must be a table");
+            };
+            let strategy = match table.remove("strategy") {
+                Some(toml::Value::String(strategy)) => strategy,
+                _ => bail!("[{MARKETS}.{name}] needs a `strategy`"),
+            };
+            ensure!(
+                table.contains_key("market_id") || table.contains_key("market-id"),
+                "[{MARKETS}.{name}] needs a `market_id`"
+            );
+            let values = table
+                .into_iter()
+                .map(|(key, value)| Ok((env_name(&key), env_value(&key, value)?)))
+                .collect::<Result<_>>()?;
+            Ok((
+                name.clone(),
+                MarketSection {
+                    name,
+                    strategy,
+                    values,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Command-line flags shared by the flow bots.
+#[derive(Debug, Clone, Default, PartialEq, Eq, clap::Args)]
+pub struct FlowArgs {
+    /// TOML config file; sets CONFIG_FILE.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Market to quote; sets MARKET_ID.
+    #[arg(long)]
+    pub market_id: Option<u64>,
+    /// `[market.<name>]` section of the config file to run; sets MARKET.
+    #[arg(long, value_name = "NAME")]
+    pub market: Option<String>,
+    /// Simulate and log transactions instead of sending them; sets TX_DRY_RUN.
+    #[arg(long)]
+    pub dry_run: bool,
+    /// RPC endpoint; sets RPC_URL.
+    #[arg(long, value_name = "URL")]
+    pub rpc_url: Option<String>,
+    /// `json` or `pretty`; sets LOG_FORMAT.
+    #[arg(long, value_name = "FORMAT")]
+    pub log_format: Option<LogFormat>,
+    /// `dev`, `staging` or `prod`; sets PROFILE.
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<Profile>,
+    /// Print the JSON Schema of the config file, with every setting's default, and exit.
+    #[arg(long)]
+    pub print_config_schema: bool,
+    /// Check the config file at PATH (and the `--market` section, if given) and exit.
+    #[arg(long, value_name = "PATH")]
+    pub check_config: Option<PathBuf>,
+}
+
+impl FlowArgs {
+    /// The variables the flags given stand for.
+    pub fn overrides(&self) -> Vec<(&'static str, String)> {
+        let mut overrides = Vec::new();
+        if let Some(path) = &self.config {
+            overrides.push(("CONFIG_FILE", path.display().to_string()));
+        }
+        if let Some(market_id) = self.market_id {
+            overrides.push(("MARKET_ID", market_id.to_string()));
+        }
+        if let Some(market) = &self.market {
+            overrides.push(("MARKET", market.clone()));
+        }
+        if self.dry_run {
+            overrides.push(("TX_DRY_RUN", "true".to_string()));
+        }
+        if let Some(rpc_url) = &self.rpc_url {
+            overrides.push(("RPC_URL", rpc_url.clone()));
+        }
+        if let Some(format) = self.log_format {
+            overrides.push(("LOG_FORMAT", format.as_str().to_string()));
+        }
+        if let Some(profile) = self.profile {
+            overrides.push(("PROFILE", profile.as_str().to_string()));
+        }
+        overrides
+    }
+
+    /// Set the variables the flags given stand for, over the environment's, for the bot
+    /// alone ([`vars`]).
+    pub fn apply(&self) {
+        for (name, value) in self.overrides() {
+            set_env_var(name, value);
+        }
+    }
+}
+
+/// What [`check_config_file`] checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigCheck {
+    /// How many settings the file sets.
+    pub settings: usize,
+    /// The variable whose keypair couldn't be read here, which was checked with a
+    /// throwaway keypair instead.
+    pub unchecked_keypair: Option<String>,
+}
+
+/// Check config file `path` as `bot` would run it with the `[market.<name>]` table
+/// `market`: every key must be a setting `bot` reads, and `read`, the bot's own
+/// `Config::from_env`, must accept what the file sets over the environment. Fails with
+/// every problem found.
+///
+/// A pipeline checking a file needn't hold the bot's key, so a local keypair that can't be
+/// read is reported in the result rather than as a problem. Secret references are left
+/// unresolved.
+pub fn check_config_file<T>(
+    path: &Path,
+    bot: &str,
+    market: Option<&str>,
+    keypair_var: &str,
+    read: impl FnOnce() -> Result<T>,
+) -> Result<ConfigCheck> {
+    let file = ConfigFile::load(path, bot, market)?;
+    let mut errors = ConfigErrors::default();
+    for name in file.names() {
+        errors.ensure(
+            schema::is_setting(bot, name),
+            name,
+            format_args!("is not a setting {bot} reads"),
+        );
+    }
+    file.apply();
+    // An invalid PROFILE is `read`'s to report.
+    if let Ok(Some(profile)) = Profile::from_env() {
+        profile.apply();
+    }
+
+    let mut unchecked_keypair = None;
+    if matches!(SignerConfig::from_env(), Ok(SignerConfig::Local))
+        && SignerConfig::Local.connect(keypair_var).is_err()
+    {
+        let throwaway = Keypair::new();
+        set_env_var(keypair_var, format!("{:?}", throwaway.to_bytes()));
+        unchecked_keypair = Some(keypair_var.to_string());
+    }
+
+    if let Err(error) = read() {
+        match error.downcast::<ConfigErrors>() {
+            Ok(found) => found
+                .into_problems()
+                .into_iter()
+                .for_each(|problem| errors.push(problem)),
+            Err(error) => errors.push(ConfigProblem {
+                field: "config".to_string(),
+                error,
+            }),
+        }
+    }
+    if !errors.problems().is_empty() {
+        return Err(errors.into());
+    }
+    Ok(ConfigCheck {
+        settings: file.names().count(),
+        unchecked_keypair,
+    })
+}
+
+/// Run `flow`'s `--print-config-schema` or `--check-config` for `bot`, with its
+/// [`check_config_file`] arguments, once the flags and `.env` are applied. Returns whether
+/// one ran, in which case the bot exits without starting.
+pub fn run_config_mode<T>(
+    flow: &FlowArgs,
+    bot: &str,
+    keypair_var: &str,
+    read: impl FnOnce() -> Result<T>,
+) -> Result<bool> {
+    if flow.print_config_schema {
+        let schema = schema::json_schema(bot).with_context(|| format!("no schema for {bot}"))?;
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(true);
+    }
+    let Some(path) = &flow.check_config else {
+        return Ok(false);
+    };
+    let market = env_var("MARKET")
+        .ok()
+        .filter(|value| !value.trim().is_empty());
+    let check = check_config_file(path, bot, market.as_deref(), keypair_var, read)?;
+    println!("{}: {} settings OK", path.display(), check.settings);
+    if let Some(var) = check.unchecked_keypair {
+        println!("{var} not checked: its keypair can't be read here");
+    }
+    Ok(true)
+}
+
+/// Apply the file named by `CONFIG_FILE` for `bot`, and the market named by `MARKET`, if
+/// any, then the defaults of the `PROFILE`, if any, and return the file's path.
+pub fn load_config_file(bot: &str) -> Result<Option<PathBuf>> {
+    let path = env_var("CONFIG_FILE")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(PathBuf::from);
+    if let Some(path) = &path {
+        let market = env_var("MARKET")
+            .ok()
+            .filter(|value| !value.trim().is_empty());
+        ConfigFile::load(path, bot, market.as_deref())?.apply();
+    }
+    if let Some(profile) = Profile::from_env()? {
+        profile.apply();
+    }
+    Ok(path)
+}
+
+/// Everything a bot's configuration reads from beyond the process environment: the
+/// config file, via [`load_config_file`], then any secret references it or the
+/// environment hold, via [`secrets::resolve_env`].
+pub async fn load_env(bot: &str) -> Result<Option<PathBuf>> {
+    let path = load_config_file(bot)?;
+    secrets::resolve_env().await?;
+    Ok(path)
+}
+
+/// Variable `name` parsed, `default` when unset or blank.
+pub fn var<T>(name: &str, default: T) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match env_var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid {name} value `{value}`")),
+        _ => Ok(default),
+    }
+}
+
+/// Variable `name` parsed, `None` when unset or blank.
+pub fn optional<T>(name: &str) -> Result<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    env_var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|error| anyhow!("invalid {name} value `{value}`: {error}"))
+        })
+        .transpose()
+}
+
+/// A setting that didn't read or validate, under the name it's set by: its variable, or
+/// for a group of them the field they configure.
+#[derive(Debug)]
+pub struct ConfigProblem {
+    pub field: String,
+    pub error: anyhow::Error,
+}
+
+/// The problems found reading a configuration.
+#[derive(Debug, Default)]
+pub struct ConfigErrors {
+    problems: Vec<ConfigProblem>,
+}
+
+impl ConfigErrors {
+    /// Read a configuration with `read`, failing with everything it recorded.
+    pub fn collect<T>(read: impl FnOnce(&mut Self) -> Option<T>) -> Result<T> {
+        let mut errors = Self::default();
+        let config = read(&mut errors);
+        if !errors.problems.is_empty() {
+            return Err(errors.into());
+        }
+        config.context("configuration incomplete")
+    }
+
+    /// `result`'s value, or `None` with its error recorded against `field`.
+    pub fn check<T>(&mut self, field: &str, result: Result<T>) -> Option<T> {
+        result
+            .map_err(|error| {
+                self.problems.push(ConfigProblem {
+                    field: field.to_string(),
+                    error,
+                })
+            })
+            .ok()
+    }
+
+    /// Record `message` against `field` unless `ok`.
+    pub fn ensure(&mut self, ok: bool, field: &str, message: impl fmt::Display) {
+        if !ok {
+            self.check::<()>(field, Err(anyhow!("{message}")));
+        }
+    }
+
+    pub fn problems(&self) -> &[ConfigProblem] {
+        &self.problems
+    }
+
+    pub fn into_problems(self) -> Vec<ConfigProblem> {
+        self.problems
+    }
+
+    pub fn push(&mut self, problem: ConfigProblem) {
+        self.problems.push(problem);
+    }
+}
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.problems.len();
+        write!(
+            f,
+            "{count} configuration problem{}:",
+            if count == 1 { "" } else { "s" }
+        )?;
+        for problem in &self.problems {
+            write!(f, "\n  {}: {:#}", problem.field, problem.error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// Settings every flow bot takes.
+pub struct CommonConfig {
+    /// Signs for the liquidity provider; see [`SignerConfig`].
+    pub signer: Arc<dyn TxSigner>,
+    /// Env var a local signer's keypair was read from, read again on a key rotation.
+    pub keypair_var: String,
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub market_id: u64,
+    /// Serve the read-only REST API here (requires the `api` feature).
+    pub api_bind_addr: Option<SocketAddr>,
+    /// Serve the gRPC control plane here (requires the `grpc` feature).
+    pub control_bind_addr: Option<SocketAddr>,
+    /// Serve the local admin interface; `None` leaves it off.
+    pub admin: Option<AdminConfig>,
+    /// Serve `/healthz` and `/readyz` for a supervisor; `None` disables the probes.
+    pub probes: Option<ProbeConfig>,
+    /// Zero flows from the panic hook; `None` leaves a panicking bot's flows running.
+    pub emergency_stop: Option<EmergencyStopConfig>,
+    /// Accept operator commands over Telegram; `None` disables the command bot.
+    pub telegram_control: Option<TelegramControlConfig>,
+    pub alerts: AlertConfig,
+    /// Pause after this many consecutive failed cycles; 0 disables the breaker.
+    pub circuit_breaker_max_failures: u32,
+    /// Limits on the positions of every bot in the process. Under the orchestrator, its
+    /// own limits apply across the instances instead.
+    pub risk_limits: RiskLimits,
+    /// Touched on every loop iteration so the watchdog can tell the bot is alive.
+    pub heartbeat_file: Option<PathBuf>,
+    /// Pinged from the main loop so an external monitor alerts when the pings stop.
+    pub heartbeat_ping: Option<HeartbeatConfig>,
+    /// Watch the RPC endpoint's slot for lag behind the cluster.
+    pub slot_lag: Option<SlotLagConfig>,
+    /// Crank the market's bookkeeping when nobody else has for a while.
+    pub crank: Option<CrankConfig>,
+    /// Where to write the state the bot was working on when it dies.
+    pub crash_dump: Option<CrashDumpConfig>,
+    /// Publish market events and the bot's actions to Kafka or NATS.
+    pub event_bus: Option<EventBusConfig>,
+    /// Call out to operator webhooks on the position's lifecycle events.
+    pub webhooks: Option<WebhookConfig>,
+    pub tx: TxSenderConfig,
+    /// Commitment for account and slot reads.
+    pub read_commitment: CommitmentConfig,
+    pub jitter: JitterConfig,
+    /// The deployment profile the configuration was held to.
+    pub profile: Option<Profile>,
+}
+
+impl CommonConfig {
+    /// Read from the environment, with the signer's keypair in `keypair_var`.
+    pub fn from_env(keypair_var: &str) -> Result<Self> {
+        ConfigErrors::collect(|errors| Self::read(keypair_var, errors))
+    }
+
+    /// [`from_env`](Self::from_env), recording problems in `errors`; `None` if any setting
+    /// couldn't be read.
+    pub fn read(keypair_var: &str, errors: &mut ConfigErrors) -> Option<Self> {
+        let endpoints = errors.check("CLUSTER", endpoints_from_env(ClusterPreset::Localnet));
+        let signer = errors.check(
+            keypair_var,
+            SignerConfig::from_env().and_then(|signer| signer.connect(keypair_var)),
+        );
+        let market_id = errors.check("MARKET_ID", var("MARKET_ID", 1));
+        let api_bind_addr = errors.check("API_BIND_ADDR", optional("API_BIND_ADDR"));
+        let control_bind_addr = errors.check("CONTROL_BIND_ADDR", optional("CONTROL_BIND_ADDR"));
+        let admin = errors.check("admin", AdminConfig::from_env());
+        let probes = errors.check("probes", ProbeConfig::from_env());
+        let emergency_stop = errors.check("emergency_stop", EmergencyStopConfig::from_env());
+        let telegram_control = errors.check("telegram_control", TelegramControlConfig::from_env());
+        let alerts = errors.check("alerts", AlertConfig::from_env());
+        let circuit_breaker_max_failures = errors.check(
+            "CIRCUIT_BREAKER_MAX_FAILURES",
+            var("CIRCUIT_BREAKER_MAX_FAILURES", 10),
+        );
+        let risk_limits = errors.check("risk_limits", RiskLimits::from_env());
+        let heartbeat_file = errors.check("HEARTBEAT_FILE", optional("HEARTBEAT_FILE"));
+        let heartbeat_ping = errors.check("heartbeat_ping", HeartbeatConfig::from_env());
+        let slot_lag = errors.check("slot_lag", SlotLagConfig::from_env());
+        let crank = errors.check("crank", CrankConfig::from_env());
+        let crash_dump = errors.check("crash_dump", CrashDumpConfig::from_env());
+        let event_bus = errors.check("EVENT_BUS_URL", EventBusConfig::from_env());
+        let webhooks = errors.check("webhooks", WebhookConfig::from_env());
+        let tx = errors.check("tx", TxSenderConfig::from_env());
+        let read_commitment =
+            errors.check("READ_COMMITMENT", commitment_from_env("READ_COMMITMENT"));
+        let jitter = errors.check("JITTER_PCT", JitterConfig::from_env());
+        let profile = errors.check("PROFILE", Profile::from_env());
+
+        let (rpc_url, ws_url) = endpoints?;
+        let config = Self {
+            signer: signer?,
+            keypair_var: keypair_var.to_string(),
+            rpc_url,
+            ws_url,
+            market_id: market_id?,
+            api_bind_addr: api_bind_addr?,
+            control_bind_addr: control_bind_addr?,
+            admin: admin?,
+            probes: probes?,
+            emergency_stop: emergency_stop?,
+            telegram_control: telegram_control?,
+            alerts: alerts?,
+            circuit_breaker_max_failures: circuit_breaker_max_failures?,
+            risk_limits: risk_limits?,
+            heartbeat_file: heartbeat_file?,
+            heartbeat_ping: heartbeat_ping?,
+            slot_lag: slot_lag?,
+            crank: crank?,
+            crash_dump: crash_dump?,
+            event_bus: event_bus?,
+            webhooks: webhooks?,
+            tx: tx?,
+            read_commitment: read_commitment?,
+            jitter: jitter?,
+            profile: profile?,
+        };
+        config.check(errors);
+        Some(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ConfigErrors::collect(|errors| {
+            self.check(errors);
+            Some(())
+        })
+    }
+
+    fn check(&self, errors: &mut ConfigErrors) {
+        errors.ensure(
+            self.rpc_url.starts_with("http://") || self.rpc_url.starts_with("https://"),
+            "RPC_URL",
+            format_args!("must be an http(s) URL, got `{}`", self.rpc_url),
+        );
+        errors.ensure(
+            self.ws_url.starts_with("ws://") || self.ws_url.starts_with("wss://"),
+            "WS_URL",
+            format_args!("must be a ws(s) URL, got `{}`", self.ws_url),
+        );
+        errors.ensure(
+            self.api_bind_addr.is_none() || self.api_bind_addr != self.control_bind_addr,
+            "CONTROL_BIND_ADDR",
+            "is the same address as API_BIND_ADDR",
+        );
+        if let Some(profile) = self.profile {
+            profile.check(self, errors);
+        }
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+
+    /// A client and a queued sender paying with `signer`, set up as a flow bot runs them,
+    /// reporting through the bot's `reporting`.
+    pub async fn connect(
+        &self,
+        signer: Arc<dyn TxSigner>,
+        alerter: &Alerter,
+        reporting: &Reporting,
+    ) -> Result<(Arc<Client<ProgramPayer>>, TxSender)> {
+        let client = Arc::new(Client::new_with_options(
+            self.cluster(),
+            program_payer(signer.pubkey()),
+            self.read_commitment,
+        ));
+        let program = client.program(twob_anchor::ID)?;
+        let sender = TxSender::for_program(&program, signer, self.tx.clone())?
+            .with_alerter(alerter.clone())
+            .with_reporting(reporting.clone())
+            .connect_tpu(&self.ws_url)
+            .await?
+            .prepare_nonces()
+            .await?
+            .start_queue();
+        Ok((client, sender))
+    }
+}
+
+/// How far the waits between a bot's cycles are randomly spread, so its transactions don't
+/// keep a cadence others can time theirs around.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JitterConfig {
+    /// Percentage by which each wait between cycles is randomly lengthened or shortened.
+    pub pct: u32,
+}
+
+impl JitterConfig {
+    /// Read `JITTER_PCT`; no jitter when unset.
+    pub fn from_env() -> Result<Self> {
+        let pct = var("JITTER_PCT", 0)?;
+        ensure!(pct <= 100, "JITTER_PCT must be at most 100");
+        Ok(Self { pct })
+    }
+
+    /// `interval` spread by [`Self::pct`]; see [`jittered`].
+    pub fn apply(self, interval: Duration) -> Duration {
+        jittered(interval, self.pct)
+    }
+}
+
+/// inventory-flow's strategy settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryFlowSection {
+    /// Stream this fraction (1/n) of the position's inventory per slot.
+    pub flow_divisor: u64,
+    /// Deposit [`Self::top_up_base_lamports`] and [`Self::top_up_quote_lamports`] into the
+    /// position once it is this few slots from debt; 0 never tops up.
+    pub top_up_runway_slots: u64,
+    pub top_up_base_lamports: u64,
+    pub top_up_quote_lamports: u64,
+    /// Slots to wait after a top-up before the next, so a position the deposit hasn't
+    /// lifted clear of [`Self::top_up_runway_slots`] isn't topped up on every tick.
+    pub top_up_cooldown_slots: u64,
+}
+
+impl InventoryFlowSection {
+    pub fn from_env() -> Result<Self> {
+        ConfigErrors::collect(Self::read)
+    }
+
+    pub fn read(errors: &mut ConfigErrors) -> Option<Self> {
+        let section = Self {
+            flow_divisor: errors.check("FLOW_DIVISOR", var("FLOW_DIVISOR", 5))?,
+            top_up_runway_slots: errors
+                .check("TOP_UP_RUNWAY_SLOTS", var("TOP_UP_RUNWAY_SLOTS", 0))?,
+            top_up_base_lamports: errors
+                .check("TOP_UP_BASE_LAMPORTS", var("TOP_UP_BASE_LAMPORTS", 0))?,
+            top_up_quote_lamports: errors
+                .check("TOP_UP_QUOTE_LAMPORTS", var("TOP_UP_QUOTE_LAMPORTS", 0))?,
+            top_up_cooldown_slots: errors
+                .check("TOP_UP_COOLDOWN_SLOTS", var("TOP_UP_COOLDOWN_SLOTS", 1_500))?,
+        };
+        section.check(errors);
+        Some(section)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ConfigErrors::collect(|errors| {
+            self.check(errors);
+            Some(())
+        })
+    }
+
+    fn check(&self, errors: &mut ConfigErrors) {
+        errors.ensure(self.flow_divisor > 0, "FLOW_DIVISOR", "must be positive");
+        errors.ensure(
+            self.top_up_runway_slots == 0
+                || self.top_up_base_lamports > 0
+                || self.top_up_quote_lamports > 0,
+            "TOP_UP_RUNWAY_SLOTS",
+            "needs TOP_UP_BASE_LAMPORTS or TOP_UP_QUOTE_LAMPORTS",
+        );
+    }
+}
+
+/// oracle-flow's strategy settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OracleFlowSection {
+    pub price_feed_url: String,
+    /// Further sources checked against the primary; the bot trades on the median of the
+    /// healthy ones.
+    pub price_feed_secondary_urls: Vec<String>,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+    pub optimal_quote_weight: f64,
+    pub poll_interval_secs: u64,
+    pub rebalance_threshold_bps: u64,
+    pub quote_threshold_bps: u64,
+    pub flow_reduction_factor: f64,
+    pub max_flow_reduction_attempts: usize,
+    pub rebalance_cooldown_secs: u64,
+    pub min_rebalance_value_usd: f64,
+}
+
+impl OracleFlowSection {
+    pub fn from_env() -> Result<Self> {
+        ConfigErrors::collect(Self::read)
+    }
+
+    pub fn read(errors: &mut ConfigErrors) -> Option<Self> {
+        let price_feed_url = env_var("PRICE_FEED_URL").unwrap_or_else(|_| {
+            let base_url = env_var("PRICE_FEED_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:8080/api/v1/price".to_string());
+            let base_token = env_var("BASE_TOKEN").unwrap_or_else(|_| "SOL".to_string());
+            let quote_token = env_var("QUOTE_TOKEN").unwrap_or_else(|_| "USDC".to_string());
+
+            format!(
+                "{}/{}/{}",
+                base_url.trim_end_matches('/'),
+                base_token.trim(),
+                quote_token.trim(),
+            )
+        });
+        let price_feed_secondary_urls = env_var("PRICE_FEED_SECONDARY_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        let base_token_decimals =
+            errors.check("BASE_TOKEN_DECIMALS", var("BASE_TOKEN_DECIMALS", 9));
+        let quote_token_decimals =
+            errors.check("QUOTE_TOKEN_DECIMALS", var("QUOTE_TOKEN_DECIMALS", 6));
+        let optimal_quote_weight =
+            errors.check("OPTIMAL_QUOTE_WEIGHT", var("OPTIMAL_QUOTE_WEIGHT", 0.1));
+        let poll_interval_secs = errors.check("POLL_INTERVAL_SECS", var("POLL_INTERVAL_SECS", 1));
+        let rebalance_threshold_bps = errors.check(
+            "REBALANCE_THRESHOLD_BPS",
+            var("REBALANCE_THRESHOLD_BPS", 100),
+        );
+        let quote_threshold_bps =
+            errors.check("QUOTE_THRESHOLD_BPS", var("QUOTE_THRESHOLD_BPS", 50));
+        let flow_reduction_factor =
+            errors.check("FLOW_REDUCTION_FACTOR", var("FLOW_REDUCTION_FACTOR", 0.99));
+        let max_flow_reduction_attempts = errors.check(
+            "MAX_FLOW_REDUCTION_ATTEMPTS",
+            var("MAX_FLOW_REDUCTION_ATTEMPTS", 200),
+        );
+        let rebalance_cooldown_secs = errors.check(
+            "REBALANCE_COOLDOWN_SECS",
+            var("REBALANCE_COOLDOWN_SECS", 60),
+        );
+        let min_rebalance_value_usd = errors.check(
+            "MIN_REBALANCE_VALUE_USD",
+            var("MIN_REBALANCE_VALUE_USD", 1.0),
+        );
+
+        let section = Self {
+            price_feed_url,
+            price_feed_secondary_urls,
+            base_token_decimals: base_token_decimals?,
+            quote_token_decimals: quote_token_decimals?,
+            optimal_quote_weight: optimal_quote_weight?,
+            poll_interval_secs: poll_interval_secs?,
+            rebalance_threshold_bps: rebalance_threshold_bps?,
+            quote_threshold_bps: quote_threshold_bps?,
+            flow_reduction_factor: flow_reduction_factor?,
+            max_flow_reduction_attempts: max_flow_reduction_attempts?,
+            rebalance_cooldown_secs: rebalance_cooldown_secs?,
+            min_rebalance_value_usd: min_rebalance_value_usd?,
+        };
+        section.check(errors);
+        Some(section)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ConfigErrors::collect(|errors| {
+            self.check(errors);
+            Some(())
+        })
+    }
+
+    fn check(&self, errors: &mut ConfigErrors) {
+        errors.ensure(
+            (0.0..=1.0).contains(&self.optimal_quote_weight),
+            "OPTIMAL_QUOTE_WEIGHT",
+            format_args!("must be between 0 and 1, got {}", self.optimal_quote_weight),
+        );
+        errors.ensure(
+            self.flow_reduction_factor > 0.0 && self.flow_reduction_factor < 1.0,
+            "FLOW_REDUCTION_FACTOR",
+            format_args!(
+                "must be between 0 and 1 exclusive, got {}",
+                self.flow_reduction_factor
+            ),
+        );
+        errors.ensure(
+            self.poll_interval_secs > 0,
+            "POLL_INTERVAL_SECS",
+            "must be positive",
+        );
+        for (name, decimals) in [
+            ("BASE_TOKEN_DECIMALS", self.base_token_decimals),
+            ("QUOTE_TOKEN_DECIMALS", self.quote_token_decimals),
+        ] {
+            errors.ensure(
+                decimals <= MAX_TOKEN_DECIMALS,
+                name,
+                format_args!("must be at most {MAX_TOKEN_DECIMALS}, got {decimals}"),
+            );
+        }
+        errors.ensure(
+            !self
+                .price_feed_secondary_urls
+                .contains(&self.price_feed_url),
+            "PRICE_FEED_SECONDARY_URLS",
+            "lists the primary PRICE_FEED_URL, which would count its price twice",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_bot_section_overrides_the_top_level_and_other_sections_are_skipped() {
+        let text = r#"
+            rpc_url = "https://rpc.example"
+            market_id = 3
+            flow-divisor = 8
+
+            [inventory-flow]
+            flow_divisor = 4
+
+            [oracle-flow]
+            price_feed_secondary_urls = ["https://a.example", "https://b.example"]
+            optimal_quote_weight = 0.2
+        "#;
+
+        let inventory = ConfigFile::parse(text, "inventory-flow", None).unwrap();
+        assert_eq!(inventory.get("RPC_URL"), Some("https://rpc.example"));
+        assert_eq!(inventory.get("MARKET_ID"), Some("3"));
+        assert_eq!(inventory.get("FLOW_DIVISOR"), Some("4"));
+        assert_eq!(inventory.get("OPTIMAL_QUOTE_WEIGHT"), None);
+
+        let oracle = ConfigFile::parse(text, "oracle-flow", None).unwrap();
+        assert_eq!(oracle.get("FLOW_DIVISOR"), Some("8"));
+        assert_eq!(
+            oracle.get("PRICE_FEED_SECONDARY_URLS"),
+            Some("https://a.example,https://b.example")
+        );
+        assert_eq!(oracle.get("OPTIMAL_QUOTE_WEIGHT"), Some("0.2"));
+
+        assert!(ConfigFile::parse("[jupiter]\nkey = 1", "oracle-flow", None).is_err());
+    }
+
+    #[test]
+    fn a_market_section_overrides_its_bot_section() {
+        let text = r#"
+            quote_threshold_bps = 50
+
+            [oracle-flow]
+            quote_threshold_bps = 40
+            poll_interval_secs = 2
+
+            [market.sol-usdc]
+            strategy = "oracle-flow"
+            market_id = 1
+            quote_threshold_bps = 30
+
+            [market.bonk-usdc]
+            strategy = "inventory-flow"
+            market_id = 4
+        "#;
+
+        let sol = ConfigFile::parse(text, "oracle-flow", Some("sol-usdc")).unwrap();
+        assert_eq!(sol.get("QUOTE_THRESHOLD_BPS"), Some("30"));
+        assert_eq!(sol.get("POLL_INTERVAL_SECS"), Some("2"));
+        assert_eq!(sol.get("MARKET_ID"), Some("1"));
+        assert_eq!(sol.get("STRATEGY"), None);
+
+        assert!(ConfigFile::parse(text, "oracle-flow", Some("bonk-usdc")).is_err());
+        assert!(ConfigFile::parse(text, "oracle-flow", Some("eth-usdc")).is_err());
+
+        let sections = markets(text).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections["bonk-usdc"].strategy, "inventory-flow");
+        assert!(markets("[market.a]\nstrategy = \"oracle-flow\"").is_err());
+    }
+
+    #[test]
+    fn flags_stand_for_their_variables() {
+        use clap::Parser;
+
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            flow: FlowArgs,
+        }
+
+        let cli = Cli::try_parse_from(["bot", "--market-id", "7", "--dry-run"]).unwrap();
+        assert_eq!(
+            cli.flow.overrides(),
+            vec![
+                ("MARKET_ID", "7".to_string()),
+                ("TX_DRY_RUN", "true".to_string())
+            ]
+        );
+
+        let cli = Cli::try_parse_from(["bot", "--log-format", "json"]).unwrap();
+        assert_eq!(cli.flow.log_format, Some(LogFormat::Json));
+        assert!(Cli::try_parse_from(["bot", "--log-format", "xml"]).is_err());
+
+        let cli = Cli::try_parse_from(["bot", "--check-config", "deploy.toml"]).unwrap();
+        assert_eq!(cli.flow.check_config, Some(PathBuf::from("deploy.toml")));
+        assert!(cli.flow.overrides().is_empty());
+
+        let cli = Cli::try_parse_from(["bot", "--profile", "prod"]).unwrap();
+        assert_eq!(cli.flow.overrides(), vec![("PROFILE", "prod".to_string())]);
+        assert!(Cli::try_parse_from(["bot", "--profile", "qa"]).is_err());
+    }
+
+    #[test]
+    fn every_problem_is_reported_under_its_field() {
+        let section = OracleFlowSection {
+            price_feed_url: "https://a.example".to_string(),
+            price_feed_secondary_urls: vec!["https://a.example".to_string()],
+            base_token_decimals: 9,
+            quote_token_decimals: 24,
+            optimal_quote_weight: 1.5,
+            poll_interval_secs: 1,
+            rebalance_threshold_bps: 100,
+            quote_threshold_bps: 50,
+            flow_reduction_factor: 0.99,
+            max_flow_reduction_attempts: 200,
+            rebalance_cooldown_secs: 60,
+            min_rebalance_value_usd: 1.0,
+        };
+        let mut errors = ConfigErrors::default();
+        section.check(&mut errors);
+        errors.check::<u64>("MARKET_ID", "x".parse().map_err(anyhow::Error::from));
+
+        let fields: Vec<_> = errors
+            .problems()
+            .iter()
+            .map(|problem| problem.field.as_str())
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "OPTIMAL_QUOTE_WEIGHT",
+                "QUOTE_TOKEN_DECIMALS",
+                "PRICE_FEED_SECONDARY_URLS",
+                "MARKET_ID"
+            ]
+        );
+        assert!(
+            errors
+                .to_string()
+                .starts_with("4 configuration problems:\n")
+        );
+        assert!(ConfigErrors::collect(|_| Some(())).is_ok());
+    }
+}
@@ -0,0 +1,137 @@
+//! Deployment profiles, chosen with `--profile` or `PROFILE`.
+//!
+//! A profile is the lowest layer of configuration: its defaults apply only to variables
+//! the command line, environment and config file all leave unset. It also holds the
+//! configuration to its environment's standards, so a prod keypair isn't run with the
+//! settings of a test:
+//!
+//! - `dev` defaults to localnet and dry-run;
+//! - `staging` defaults to devnet, and requires an alert sink;
+//! - `prod` defaults to mainnet-beta, requires an alert sink and a priority fee, and
+//!   refuses dry-run and a loopback RPC endpoint.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{Result, anyhow};
+
+use super::{CommonConfig, ConfigErrors, env_var, set_env_var_default};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Profile {
+    Dev,
+    Staging,
+    Prod,
+}
+
+impl Profile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dev => "dev",
+            Self::Staging => "staging",
+            Self::Prod => "prod",
+        }
+    }
+
+    /// `PROFILE`, or `None` when unset.
+    pub fn from_env() -> Result<Option<Self>> {
+        env_var("PROFILE")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.parse())
+            .transpose()
+    }
+
+    /// The variables this profile sets when nothing else does.
+    pub fn defaults(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Dev => &[("CLUSTER", "localnet"), ("TX_DRY_RUN", "true")],
+            Self::Staging => &[("CLUSTER", "devnet")],
+            Self::Prod => &[("CLUSTER", "mainnet-beta")],
+        }
+    }
+
+    /// Set each of [`defaults`](Self::defaults) whose variable isn't already set.
+    pub fn apply(self) {
+        for &(name, value) in self.defaults() {
+            set_env_var_default(name, value);
+        }
+    }
+
+    /// Record where `config` falls short of this profile in `errors`.
+    pub fn check(self, config: &CommonConfig, errors: &mut ConfigErrors) {
+        let profile = self.as_str();
+        if matches!(self, Self::Staging | Self::Prod) {
+            errors.ensure(
+                config.alerts.has_sinks(),
+                "alerts",
+                format_args!("the {profile} profile needs an ALERT_* sink"),
+            );
+        }
+        if self == Self::Prod {
+            errors.ensure(
+                !config.tx.dry_run,
+                "TX_DRY_RUN",
+                "the prod profile sends transactions; unset it or run another profile",
+            );
+            errors.ensure(
+                config.tx.compute_unit_price.is_some()
+                    || config.tx.priority_fee_percentile.is_some(),
+                "priority_fee",
+                "the prod profile needs TX_COMPUTE_UNIT_PRICE or TX_PRIORITY_FEE_PERCENTILE",
+            );
+            errors.ensure(
+                !is_loopback(&config.rpc_url),
+                "RPC_URL",
+                format_args!("the prod profile can't use local RPC `{}`", config.rpc_url),
+            );
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "dev" => Ok(Self::Dev),
+            "staging" => Ok(Self::Staging),
+            "prod" | "production" => Ok(Self::Prod),
+            other => Err(anyhow!(
+                "invalid PROFILE `{other}`, expected dev, staging or prod"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+fn is_loopback(url: &str) -> bool {
+    let host = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split(['/', ':'])
+        .next()
+        .unwrap_or_default();
+    host == "localhost" || host.starts_with("127.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_profiles_and_spots_local_endpoints() {
+        assert_eq!(" Prod ".parse::<Profile>().unwrap(), Profile::Prod);
+        assert_eq!("staging".parse::<Profile>().unwrap(), Profile::Staging);
+        assert!("qa".parse::<Profile>().is_err());
+        assert!(Profile::Dev.defaults().contains(&("TX_DRY_RUN", "true")));
+
+        assert!(is_loopback("http://127.0.0.1:8899"));
+        assert!(is_loopback("http://localhost:8899/"));
+        assert!(!is_loopback("https://api.mainnet-beta.solana.com"));
+    }
+}
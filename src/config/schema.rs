@@ -0,0 +1,1020 @@
+//! Every setting the flow bots read, with its type and default, for
+//! `--print-config-schema` and `--check-config`.
+//!
+//! Settings are read next to the code they configure, so this list is the one place
+//! that names them all; a setting added there belongs here too.
+
+use serde_json::{Map, Value, json};
+
+/// How a setting's value is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Integer,
+    Number,
+    Boolean,
+    /// Comma-separated in the environment, an array in the file.
+    List,
+}
+
+impl Kind {
+    fn json_type(self) -> &'static str {
+        match self {
+            Kind::Text => "string",
+            Kind::Integer => "integer",
+            Kind::Number => "number",
+            Kind::Boolean => "boolean",
+            Kind::List => "array",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Setting {
+    /// The environment variable; the file key is the same in lower case.
+    pub name: &'static str,
+    pub kind: Kind,
+    /// The value used when it's unset, as it would be written in the environment.
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+}
+
+const fn setting(
+    name: &'static str,
+    kind: Kind,
+    default: Option<&'static str>,
+    description: &'static str,
+) -> Setting {
+    Setting {
+        name,
+        kind,
+        default,
+        description,
+    }
+}
+
+use Kind::{Boolean, Integer, List, Number, Text};
+
+/// Read by every flow bot, besides its keypair variable.
+pub const COMMON: &[Setting] = &[
+    setting(
+        "PROFILE",
+        Text,
+        None,
+        "dev, staging or prod: defaults and requirements for the deployment",
+    ),
+    setting(
+        "CLUSTER",
+        Text,
+        Some("localnet"),
+        "mainnet-beta, devnet or localnet: the endpoints used when RPC_URL and WS_URL are unset",
+    ),
+    setting("RPC_URL", Text, None, "HTTP(S) RPC endpoint"),
+    setting("WS_URL", Text, None, "WebSocket RPC endpoint"),
+    setting("MARKET_ID", Integer, Some("1"), "Market to quote"),
+    setting(
+        "MARKET",
+        Text,
+        None,
+        "[market.<name>] section of the config file to run",
+    ),
+    setting(
+        "READ_COMMITMENT",
+        Text,
+        Some("confirmed"),
+        "Commitment for account and slot reads",
+    ),
+    setting(
+        "JITTER_PCT",
+        Integer,
+        Some("0"),
+        "Percentage by which waits between cycles are randomly spread, at most 100",
+    ),
+    setting(
+        "CIRCUIT_BREAKER_MAX_FAILURES",
+        Integer,
+        Some("10"),
+        "Pause after this many consecutive failed cycles; 0 disables the breaker",
+    ),
+    setting(
+        "RISK_MAX_BASE_DEPLOYED",
+        Integer,
+        None,
+        "Most base the positions may hold",
+    ),
+    setting(
+        "RISK_MAX_QUOTE_DEPLOYED",
+        Integer,
+        None,
+        "Most quote the positions may hold",
+    ),
+    setting(
+        "RISK_MAX_BASE_FLOW",
+        Integer,
+        None,
+        "Largest total base flow",
+    ),
+    setting(
+        "RISK_MAX_QUOTE_FLOW",
+        Integer,
+        None,
+        "Largest total quote flow",
+    ),
+    setting(
+        "RISK_MAX_DRAWDOWN_BPS",
+        Integer,
+        None,
+        "Drawdown past which the bots stop quoting",
+    ),
+    setting("RISK_MAX_LENT", Integer, None, "Most quote lent out"),
+    setting("LOG_FORMAT", Text, None, "json or pretty"),
+    setting(
+        "TELEMETRY_STDOUT_JSON",
+        Boolean,
+        None,
+        "Log JSON to stdout; LOG_FORMAT wins when both are set",
+    ),
+    setting(
+        "OTEL_SERVICE_NAME",
+        Text,
+        None,
+        "Service name traces and metrics are exported under",
+    ),
+    setting(
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        Text,
+        None,
+        "OTLP collector to export traces and metrics to",
+    ),
+    setting(
+        "OTEL_EXPORTER_OTLP_HEADERS",
+        Text,
+        None,
+        "Headers sent to the collector, as key=value pairs separated by commas",
+    ),
+    setting(
+        "DEPLOYMENT_ENVIRONMENT_NAME",
+        Text,
+        None,
+        "Environment name attached to traces and metrics",
+    ),
+    setting(
+        "DEPLOYMENT_ENVIRONMENT",
+        Text,
+        None,
+        "Fallback for DEPLOYMENT_ENVIRONMENT_NAME",
+    ),
+    setting(
+        "KEYPAIR_PATH",
+        Text,
+        None,
+        "Keypair file for a local signer when <BOT>_KEYPAIR and <BOT>_KEYPAIR_PATH are unset",
+    ),
+    setting(
+        "KEYPAIR_PASSPHRASE",
+        Text,
+        None,
+        "Passphrase of an age-encrypted keypair file",
+    ),
+    setting(
+        "KEYPAIR_PASSPHRASE_FILE",
+        Text,
+        None,
+        "File holding the passphrase of an age-encrypted keypair file",
+    ),
+    setting(
+        "KEYPAIR_AGE_IDENTITY",
+        Text,
+        None,
+        "age identity file an encrypted keypair file is decrypted with",
+    ),
+    setting("TX_SIGNER", Text, Some("local"), "local, remote or ledger"),
+    setting(
+        "TX_REMOTE_SIGNER_URL",
+        Text,
+        None,
+        "Signing service for TX_SIGNER=remote",
+    ),
+    setting(
+        "TX_REMOTE_SIGNER_PUBKEY",
+        Text,
+        None,
+        "Pubkey the signing service signs for",
+    ),
+    setting(
+        "TX_REMOTE_SIGNER_TOKEN",
+        Text,
+        None,
+        "Bearer token for the signing service",
+    ),
+    setting(
+        "TX_LEDGER_LOCATOR",
+        Text,
+        Some("usb://ledger"),
+        "Ledger to sign with for TX_SIGNER=ledger",
+    ),
+    setting(
+        "TX_LEDGER_DERIVATION_PATH",
+        Text,
+        None,
+        "Derivation path of the Ledger key",
+    ),
+    setting(
+        "TX_COMPUTE_UNIT_PRICE",
+        Integer,
+        None,
+        "Priority fee in micro-lamports per compute unit",
+    ),
+    setting(
+        "TX_PRIORITY_FEE_PERCENTILE",
+        Integer,
+        None,
+        "Price from this percentile of recent fees when TX_COMPUTE_UNIT_PRICE is unset",
+    ),
+    setting(
+        "TX_MAX_COMPUTE_UNIT_PRICE",
+        Integer,
+        Some("1000000"),
+        "Cap on the priority fee, in micro-lamports per compute unit",
+    ),
+    setting(
+        "TX_COMPUTE_UNIT_LIMIT",
+        Integer,
+        None,
+        "Compute unit limit; unset estimates one",
+    ),
+    setting(
+        "TX_COMPUTE_UNIT_MARGIN_PCT",
+        Integer,
+        Some("20"),
+        "Margin added to the estimated compute units; empty disables estimation",
+    ),
+    setting(
+        "TX_COMMITMENT",
+        Text,
+        Some("confirmed"),
+        "Commitment sends wait for",
+    ),
+    setting(
+        "TX_SIMULATE",
+        Boolean,
+        Some("true"),
+        "Simulate before sending",
+    ),
+    setting("TX_MAX_ATTEMPTS", Integer, Some("3"), "Attempts per send"),
+    setting(
+        "TX_RETRY_DELAY_MS",
+        Integer,
+        Some("500"),
+        "Wait between attempts",
+    ),
+    setting(
+        "TX_CONFIRM_TIMEOUT_SECS",
+        Integer,
+        Some("60"),
+        "How long a send waits to confirm",
+    ),
+    setting(
+        "TX_BLOCKHASH_MAX_AGE_SECS",
+        Integer,
+        Some("20"),
+        "Age after which a cached blockhash is refetched",
+    ),
+    setting(
+        "TX_REBROADCAST_INTERVAL_BLOCKS",
+        Integer,
+        Some("10"),
+        "Blocks between rebroadcasts of an unconfirmed send",
+    ),
+    setting(
+        "TX_SKIP_PREFLIGHT",
+        Boolean,
+        Some("false"),
+        "Skip the RPC node's preflight check",
+    ),
+    setting(
+        "TX_PREFLIGHT_COMMITMENT",
+        Text,
+        None,
+        "Commitment of the preflight check",
+    ),
+    setting(
+        "TX_MAX_RETRIES",
+        Integer,
+        None,
+        "Retries the RPC node makes per send",
+    ),
+    setting(
+        "TX_PRIVATE",
+        Boolean,
+        Some("false"),
+        "Send through the private path only",
+    ),
+    setting(
+        "TX_BROADCAST_RPC_URLS",
+        List,
+        None,
+        "Further endpoints each send is broadcast to",
+    ),
+    setting(
+        "TX_JOURNAL_PATH",
+        Text,
+        None,
+        "File recording sends in flight, reconciled on start",
+    ),
+    setting(
+        "TX_MIN_SOL_BALANCE_LAMPORTS",
+        Integer,
+        None,
+        "Payer balance below which only stops are sent",
+    ),
+    setting(
+        "TX_TPU",
+        Boolean,
+        Some("false"),
+        "Send straight to the leaders' TPU ports as well",
+    ),
+    setting(
+        "TX_TPU_FANOUT_SLOTS",
+        Integer,
+        Some("12"),
+        "Upcoming slots whose leaders a TPU send goes to",
+    ),
+    setting(
+        "TX_VALID_UNTIL_GUARD_SLOTS",
+        Integer,
+        Some("2"),
+        "Slots before a send's deadline it is given up at",
+    ),
+    setting(
+        "TX_JITO_BLOCK_ENGINE_URL",
+        Text,
+        None,
+        "Send bundles through this Jito block engine",
+    ),
+    setting(
+        "TX_JITO_TIP_LAMPORTS",
+        Integer,
+        Some("10000"),
+        "Tip per Jito bundle",
+    ),
+    setting(
+        "TX_JITO_TIP_ACCOUNTS",
+        List,
+        None,
+        "Tip accounts; unset uses Jito's",
+    ),
+    setting(
+        "TX_NONCE_POOL_SIZE",
+        Integer,
+        Some("0"),
+        "Durable nonces kept for pre-signed transactions",
+    ),
+    setting(
+        "TX_QUEUE",
+        Boolean,
+        Some("false"),
+        "Send through the prioritized queue",
+    ),
+    setting(
+        "TX_QUEUE_CRANK_INTERVAL_MS",
+        Integer,
+        Some("2000"),
+        "Least time between queue cranks",
+    ),
+    setting(
+        "TX_QUEUE_HOUSEKEEPING_INTERVAL_MS",
+        Integer,
+        Some("10000"),
+        "Least time between queue housekeeping runs",
+    ),
+    setting(
+        "TX_DEADLINES_MS",
+        List,
+        None,
+        "Per-action deadlines, as action=ms pairs",
+    ),
+    setting(
+        "TX_MAX_REBUILDS",
+        Integer,
+        Some("2"),
+        "Rebuilds of a send for a later reference index",
+    ),
+    setting(
+        "TX_DAILY_FEE_BUDGET_LAMPORTS",
+        Integer,
+        None,
+        "Fees non-critical sends may spend per UTC day",
+    ),
+    setting(
+        "TX_ESTIMATE_RENT",
+        Boolean,
+        Some("false"),
+        "Include rent in send cost estimates",
+    ),
+    setting(
+        "TX_DRY_RUN",
+        Boolean,
+        Some("false"),
+        "Simulate and log sends instead of submitting them",
+    ),
+    setting(
+        "RPC_RATE_LIMIT_RPS",
+        Number,
+        None,
+        "Requests per second to any RPC endpoint",
+    ),
+    setting(
+        "RPC_RATE_LIMITS",
+        List,
+        None,
+        "Requests per second per endpoint host, as host=rps pairs",
+    ),
+    setting(
+        "API_BIND_ADDR",
+        Text,
+        None,
+        "Serve the read-only REST API here",
+    ),
+    setting(
+        "CONTROL_BIND_ADDR",
+        Text,
+        None,
+        "Serve the gRPC control plane here",
+    ),
+    setting(
+        "ADMIN_BIND_ADDR",
+        Text,
+        None,
+        "Serve the admin interface here: a loopback address or unix:/path",
+    ),
+    setting(
+        "ADMIN_TOKEN",
+        Text,
+        None,
+        "Bearer token the admin interface requires; needed on a loopback address",
+    ),
+    setting(
+        "HEALTH_BIND_ADDR",
+        Text,
+        None,
+        "Serve /healthz and /readyz here",
+    ),
+    setting(
+        "HEALTH_MAX_EVALUATION_AGE_SECS",
+        Integer,
+        Some("900"),
+        "Age of the last successful cycle past which the bot isn't healthy",
+    ),
+    setting(
+        "HEALTH_MAX_PRICE_AGE_SECS",
+        Integer,
+        Some("120"),
+        "Age of the last price past which the bot isn't ready",
+    ),
+    setting(
+        "HEALTH_RPC_CHECK_INTERVAL_SECS",
+        Integer,
+        Some("15"),
+        "How often RPC reachability is checked",
+    ),
+    setting(
+        "EMERGENCY_STOP_ON_PANIC",
+        Boolean,
+        Some("false"),
+        "Zero flows from the panic hook",
+    ),
+    setting(
+        "EMERGENCY_STOP_TIMEOUT_SECS",
+        Integer,
+        Some("5"),
+        "How long the emergency stop may take",
+    ),
+    setting(
+        "TELEGRAM_CONTROL_BOT_TOKEN",
+        Text,
+        None,
+        "Accept operator commands through this Telegram bot",
+    ),
+    setting(
+        "TELEGRAM_CONTROL_CHAT_IDS",
+        List,
+        None,
+        "Chats the command bot obeys",
+    ),
+    setting(
+        "ALERT_TELEGRAM_BOT_TOKEN",
+        Text,
+        None,
+        "Telegram bot alerts are sent through",
+    ),
+    setting(
+        "ALERT_TELEGRAM_CHAT_ID",
+        Text,
+        None,
+        "Telegram chat alerts are sent to",
+    ),
+    setting(
+        "ALERT_DISCORD_WEBHOOK_URL",
+        Text,
+        None,
+        "Discord webhook alerts are sent to",
+    ),
+    setting(
+        "ALERT_SLACK_WEBHOOK_URL",
+        Text,
+        None,
+        "Slack webhook alerts are sent to",
+    ),
+    setting(
+        "ALERT_WEBHOOK_URL",
+        Text,
+        None,
+        "Webhook alerts are posted to as JSON",
+    ),
+    setting(
+        "ALERT_MIN_INTERVAL_SECS",
+        Integer,
+        Some("300"),
+        "Least time between alerts of one kind",
+    ),
+    setting(
+        "HEARTBEAT_FILE",
+        Text,
+        None,
+        "File touched every loop for the watchdog",
+    ),
+    setting(
+        "HEARTBEAT_PING_URL",
+        Text,
+        None,
+        "URL pinged so an external monitor alerts when pings stop",
+    ),
+    setting(
+        "HEARTBEAT_PING_INTERVAL_SECS",
+        Integer,
+        Some("60"),
+        "Least time between pings",
+    ),
+    setting(
+        "HEARTBEAT_PING_TIMEOUT_SECS",
+        Integer,
+        Some("10"),
+        "Timeout of each ping",
+    ),
+    setting(
+        "SLOT_LAG_MAX_SLOTS",
+        Integer,
+        None,
+        "Alert when the RPC endpoint trails the cluster by more slots",
+    ),
+    setting(
+        "SLOT_LAG_CHECK_INTERVAL_SECS",
+        Integer,
+        Some("10"),
+        "How often slot lag is checked",
+    ),
+    setting(
+        "SLOT_LAG_FAILOVER",
+        Boolean,
+        Some("false"),
+        "Read from the reference endpoint while the primary lags",
+    ),
+    setting(
+        "SLOT_LAG_REFERENCE_RPC_URL",
+        Text,
+        None,
+        "Endpoint the primary's slot is compared against",
+    ),
+    setting(
+        "CRANK_MAX_STALENESS_SLOTS",
+        Integer,
+        None,
+        "Send update_books once the market's bookkeeping is this many slots old",
+    ),
+    setting(
+        "CRANK_CHECK_INTERVAL_SECS",
+        Integer,
+        Some("30"),
+        "How often the bookkeeping's age is checked",
+    ),
+    setting(
+        "CRASH_DUMP_DIR",
+        Text,
+        None,
+        "Where to write the state the bot was working on when it dies",
+    ),
+    setting(
+        "EVENT_BUS_URL",
+        Text,
+        None,
+        "kafka://host:port,... or nats://host:port to publish market events and actions to",
+    ),
+    setting(
+        "EVENT_BUS_TOPIC",
+        Text,
+        Some("twob.events"),
+        "Kafka topic, or prefix of the NATS subjects, events are published to",
+    ),
+    setting(
+        "WEBHOOK_URLS",
+        List,
+        None,
+        "URLs POSTed each deposit, withdrawal, flow update, stop, debt detection and rebalance",
+    ),
+    setting(
+        "WEBHOOK_SECRET",
+        Text,
+        None,
+        "HMAC-SHA256 key webhook requests are signed with",
+    ),
+    setting(
+        "WEBHOOK_EVENTS",
+        List,
+        None,
+        "Only send these webhook events; all of them when unset",
+    ),
+    setting(
+        "WEBHOOK_MAX_ATTEMPTS",
+        Integer,
+        Some("5"),
+        "Tries per webhook call before giving up",
+    ),
+    setting(
+        "AWS_REGION",
+        Text,
+        None,
+        "Region of aws-sm:// secret references",
+    ),
+    setting(
+        "AWS_ACCESS_KEY_ID",
+        Text,
+        None,
+        "Credentials for aws-sm:// secret references",
+    ),
+    setting(
+        "AWS_SECRET_ACCESS_KEY",
+        Text,
+        None,
+        "Credentials for aws-sm:// secret references",
+    ),
+    setting(
+        "AWS_SESSION_TOKEN",
+        Text,
+        None,
+        "Session token for aws-sm:// secret references",
+    ),
+    setting(
+        "GCP_ACCESS_TOKEN",
+        Text,
+        None,
+        "OAuth token for gcp-sm:// secret references",
+    ),
+    setting(
+        "VAULT_ADDR",
+        Text,
+        None,
+        "Vault server for vault:// secret references",
+    ),
+    setting(
+        "VAULT_TOKEN",
+        Text,
+        None,
+        "Vault token for vault:// secret references",
+    ),
+    setting(
+        "VAULT_NAMESPACE",
+        Text,
+        None,
+        "Vault namespace for vault:// secret references",
+    ),
+];
+
+pub const INVENTORY_FLOW: &[Setting] = &[
+    setting(
+        "INVENTORY_FLOW_KEYPAIR",
+        Text,
+        None,
+        "Keypair as a JSON byte array",
+    ),
+    setting("INVENTORY_FLOW_KEYPAIR_PATH", Text, None, "Keypair file"),
+    setting(
+        "FLOW_DIVISOR",
+        Integer,
+        Some("5"),
+        "Stream this fraction (1/n) of the position's inventory per slot",
+    ),
+    setting(
+        "TOP_UP_RUNWAY_SLOTS",
+        Integer,
+        Some("0"),
+        "Top the position up once it is this few slots from debt; 0 never tops up",
+    ),
+    setting(
+        "TOP_UP_BASE_LAMPORTS",
+        Integer,
+        Some("0"),
+        "Base lamports deposited per top-up",
+    ),
+    setting(
+        "TOP_UP_QUOTE_LAMPORTS",
+        Integer,
+        Some("0"),
+        "Quote lamports deposited per top-up",
+    ),
+    setting(
+        "TOP_UP_COOLDOWN_SLOTS",
+        Integer,
+        Some("1500"),
+        "Slots to wait after a top-up before the next",
+    ),
+];
+
+pub const ORACLE_FLOW: &[Setting] = &[
+    setting(
+        "ORACLE_FLOW_KEYPAIR",
+        Text,
+        None,
+        "Keypair as a JSON byte array",
+    ),
+    setting("ORACLE_FLOW_KEYPAIR_PATH", Text, None, "Keypair file"),
+    setting(
+        "PRICE_FEED_URL",
+        Text,
+        None,
+        "Price feed; unset builds one from PRICE_FEED_BASE_URL and the tokens",
+    ),
+    setting(
+        "PRICE_FEED_BASE_URL",
+        Text,
+        Some("http://localhost:8080/api/v1/price"),
+        "Price service the feed URL is built on",
+    ),
+    setting(
+        "BASE_TOKEN",
+        Text,
+        Some("SOL"),
+        "Base token symbol for the built feed URL",
+    ),
+    setting(
+        "QUOTE_TOKEN",
+        Text,
+        Some("USDC"),
+        "Quote token symbol for the built feed URL",
+    ),
+    setting(
+        "PRICE_FEED_SECONDARY_URLS",
+        List,
+        None,
+        "Further price sources checked against the primary",
+    ),
+    setting(
+        "PRICE_MAX_AGE_SECS",
+        Integer,
+        Some("120"),
+        "Age past which a price is stale",
+    ),
+    setting(
+        "PRICE_FEED_MAX_FAILURES",
+        Integer,
+        Some("3"),
+        "Consecutive failures after which a source is unhealthy",
+    ),
+    setting(
+        "PRICE_FEED_MAX_DEVIATION_BPS",
+        Integer,
+        Some("100"),
+        "Deviation from the median past which a source is unhealthy",
+    ),
+    setting(
+        "BASE_TOKEN_DECIMALS",
+        Integer,
+        Some("9"),
+        "Decimals of the base mint",
+    ),
+    setting(
+        "QUOTE_TOKEN_DECIMALS",
+        Integer,
+        Some("6"),
+        "Decimals of the quote mint",
+    ),
+    setting(
+        "OPTIMAL_QUOTE_WEIGHT",
+        Number,
+        Some("0.1"),
+        "Weight of the oracle price against the inventory price, 0 to 1",
+    ),
+    setting(
+        "POLL_INTERVAL_SECS",
+        Integer,
+        Some("1"),
+        "Time between cycles",
+    ),
+    setting(
+        "REBALANCE_THRESHOLD_BPS",
+        Integer,
+        Some("100"),
+        "Inventory imbalance that triggers a rebalance",
+    ),
+    setting(
+        "QUOTE_THRESHOLD_BPS",
+        Integer,
+        Some("50"),
+        "Flow change below which the quote is left alone",
+    ),
+    setting(
+        "FLOW_REDUCTION_FACTOR",
+        Number,
+        Some("0.99"),
+        "Factor flows are cut by when an update is rejected, between 0 and 1",
+    ),
+    setting(
+        "MAX_FLOW_REDUCTION_ATTEMPTS",
+        Integer,
+        Some("200"),
+        "Cuts tried before giving up",
+    ),
+    setting(
+        "REBALANCE_COOLDOWN_SECS",
+        Integer,
+        Some("60"),
+        "Least time between rebalances",
+    ),
+    setting(
+        "MIN_REBALANCE_VALUE_USD",
+        Number,
+        Some("1.0"),
+        "Smallest rebalance worth swapping",
+    ),
+    setting(
+        "BALANCE_SNAPSHOT_INTERVAL_SECS",
+        Integer,
+        Some("60"),
+        "Time between wallet balance snapshots",
+    ),
+    setting("JUPITER_API_KEY", Text, None, "Jupiter API key"),
+    setting(
+        "JUPITER_ULTRA_API_BASE_URL",
+        Text,
+        Some("https://api.jup.ag/ultra/v1"),
+        "Jupiter Ultra API",
+    ),
+    setting(
+        "JUPITER_MAX_SLIPPAGE_BPS",
+        Integer,
+        Some("50"),
+        "Largest slippage a rebalance swap accepts",
+    ),
+    setting(
+        "JUPITER_MAX_PRICE_IMPACT_BPS",
+        Integer,
+        Some("50"),
+        "Largest price impact a rebalance swap accepts",
+    ),
+    setting(
+        "JUPITER_DRY_RUN",
+        Boolean,
+        Some("false"),
+        "Quote rebalance swaps without executing them",
+    ),
+    setting(
+        "IDLE_YIELD_VENUE",
+        Text,
+        None,
+        "Lend idle quote here: kamino",
+    ),
+    setting(
+        "IDLE_YIELD_MIN_RUNWAY_SLOTS",
+        Integer,
+        Some("216000"),
+        "Runway below which lent quote is withdrawn",
+    ),
+    setting(
+        "IDLE_YIELD_TARGET_RUNWAY_SLOTS",
+        Integer,
+        Some("432000"),
+        "Runway kept in the position when lending",
+    ),
+    setting(
+        "IDLE_YIELD_MIN_MOVE",
+        Integer,
+        Some("0"),
+        "Smallest deposit or withdrawal worth sending",
+    ),
+    setting("KAMINO_RESERVE", Text, None, "Kamino reserve lent to"),
+    setting(
+        "KAMINO_COLLATERAL_MINT",
+        Text,
+        None,
+        "Collateral mint of the Kamino reserve",
+    ),
+    setting(
+        "KAMINO_SCOPE_PRICES",
+        Text,
+        None,
+        "Scope price account of the Kamino reserve",
+    ),
+    setting(
+        "KAMINO_PYTH_ORACLE",
+        Text,
+        None,
+        "Pyth oracle of the Kamino reserve",
+    ),
+];
+
+/// The settings `bot` reads, or `None` for a bot without a config file.
+pub fn settings(bot: &str) -> Option<Vec<Setting>> {
+    let own = match bot {
+        "inventory-flow" => INVENTORY_FLOW,
+        "oracle-flow" => ORACLE_FLOW,
+        _ => return None,
+    };
+    Some(COMMON.iter().chain(own).copied().collect())
+}
+
+/// Whether file key or variable `name` is a setting `bot` reads.
+pub fn is_setting(bot: &str, name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    settings(bot).is_some_and(|settings| settings.iter().any(|setting| setting.name == name))
+}
+
+/// A JSON Schema for `bot`'s config file: its settings at the top level, in its own
+/// `[<bot>]` table and in `[market.<name>]` tables.
+pub fn json_schema(bot: &str) -> Option<Value> {
+    let properties: Map<String, Value> = settings(bot)?
+        .iter()
+        .map(|setting| (setting.name.to_ascii_lowercase(), property(setting)))
+        .collect();
+    let mut market = properties.clone();
+    market.insert(
+        "strategy".to_string(),
+        json!({ "type": "string", "description": "The bot that runs the market" }),
+    );
+    let mut top = properties.clone();
+    top.insert(
+        bot.to_string(),
+        json!({ "type": "object", "properties": properties }),
+    );
+    top.insert(
+        "market".to_string(),
+        json!({
+            "type": "object",
+            "additionalProperties": {
+                "type": "object",
+                "properties": market,
+                "required": ["strategy", "market_id"],
+            },
+        }),
+    );
+    Some(json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": format!("{bot} configuration"),
+        "type": "object",
+        "properties": top,
+    }))
+}
+
+fn property(setting: &Setting) -> Value {
+    let mut property = json!({
+        "type": setting.kind.json_type(),
+        "description": setting.description,
+        "x-env": setting.name,
+    });
+    if let Some(default) = setting.default {
+        property["default"] = default_value(setting.kind, default);
+    }
+    property
+}
+
+fn default_value(kind: Kind, default: &str) -> Value {
+    let parsed = match kind {
+        Kind::Integer => default.parse::<i64>().ok().map(Value::from),
+        Kind::Number => default.parse::<f64>().ok().map(Value::from),
+        Kind::Boolean => default.parse::<bool>().ok().map(Value::from),
+        Kind::List => Some(Value::from(
+            default.split(',').map(str::trim).collect::<Vec<_>>(),
+        )),
+        Kind::Text => None,
+    };
+    parsed.unwrap_or_else(|| Value::from(default))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_types_defaults_and_covers_the_bot_section() {
+        let schema = json_schema("oracle-flow").unwrap();
+        let weight = &schema["properties"]["optimal_quote_weight"];
+        assert_eq!(weight["type"], "number");
+        assert_eq!(weight["default"], 0.1);
+        assert_eq!(schema["properties"]["tx_simulate"]["default"], true);
+        assert!(schema["properties"]["oracle-flow"]["properties"]["market_id"].is_object());
+        assert!(schema["properties"].get("flow_divisor").is_none());
+
+        assert!(is_setting("inventory-flow", "flow_divisor"));
+        assert!(!is_setting("inventory-flow", "optimal_quote_weight"));
+        assert!(json_schema("hedger").is_none());
+    }
+}
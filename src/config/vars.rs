@@ -0,0 +1,134 @@
+//! The variables settings are read from: the process environment, under those a bot sets
+//! itself from its flags, config file, profile and secret references.
+//!
+//! Those are kept here rather than written to the environment, which can't be changed
+//! soundly once the runtime's threads are up, and so that each bot the orchestrator runs
+//! in its process reads its own. A bot started as a process of its own sets them for the
+//! whole process; one the orchestrator runs sets them within [`scoped`].
+
+use std::{
+    collections::BTreeMap,
+    env,
+    future::Future,
+    sync::{Arc, LazyLock, PoisonError, RwLock},
+};
+
+#[derive(Debug, Clone, Default)]
+struct Vars(Arc<RwLock<BTreeMap<String, String>>>);
+
+static PROCESS: LazyLock<Vars> = LazyLock::new(Vars::default);
+
+tokio::task_local! {
+    static SCOPED: Vars;
+}
+
+/// The variables of the bot running on this task: its [`scoped`] ones, else the process's.
+fn current() -> Vars {
+    SCOPED
+        .try_with(Vars::clone)
+        .unwrap_or_else(|_| PROCESS.clone())
+}
+
+/// Run `future` with variables of its own, starting with `vars`, over the environment.
+/// Tasks it spawns read the process's unless they are spawned through [`inherit`].
+pub async fn scoped<F: Future>(
+    vars: impl IntoIterator<Item = (String, String)>,
+    future: F,
+) -> F::Output {
+    let vars = Vars(Arc::new(RwLock::new(vars.into_iter().collect())));
+    SCOPED.scope(vars, future).await
+}
+
+/// `future` with the variables of the task calling this, to spawn as a task of the same
+/// bot. A task spawned without it reads the process's.
+pub fn inherit<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    SCOPED.scope(current(), future)
+}
+
+/// Variable `name` as the bot set it, else as the environment has it.
+pub fn env_var(name: &str) -> Result<String, env::VarError> {
+    let vars = current();
+    let value = vars
+        .0
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(name)
+        .cloned();
+    value.map_or_else(|| env::var(name), Ok)
+}
+
+/// Set variable `name` for the bot, over the environment's.
+pub fn set_env_var(name: &str, value: impl Into<String>) {
+    current()
+        .0
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name.to_string(), value.into());
+}
+
+/// Set variable `name` unless the bot or the environment already has.
+pub fn set_env_var_default(name: &str, value: impl Into<String>) {
+    if env_var(name).is_err() {
+        set_env_var(name, value);
+    }
+}
+
+/// Every variable, the bot's over the environment's.
+pub fn env_vars() -> BTreeMap<String, String> {
+    let mut vars = env::vars_os()
+        .map(|(name, value)| {
+            (
+                name.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    vars.extend(
+        current()
+            .0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone(),
+    );
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scoped_variables_shadow_the_environment_and_stay_in_their_scope() {
+        let name = "TWOB_VARS_TEST_SCOPED";
+        scoped([(name.to_string(), "a".to_string())], async {
+            assert_eq!(env_var(name).unwrap(), "a");
+            set_env_var_default(name, "b");
+            assert_eq!(env_var(name).unwrap(), "a");
+            set_env_var(name, "c");
+            assert_eq!(env_vars()[name], "c");
+        })
+        .await;
+        scoped([], async {
+            assert!(env_var(name).is_err());
+        })
+        .await;
+        assert!(env_var(name).is_err());
+    }
+
+    #[tokio::test]
+    async fn inherited_tasks_read_and_write_their_spawners_variables() {
+        let name = "TWOB_VARS_TEST_INHERITED";
+        scoped([(name.to_string(), "a".to_string())], async {
+            let inherited = tokio::spawn(inherit(async move {
+                let value = env_var(name).unwrap();
+                set_env_var(name, "b");
+                value
+            }));
+            assert_eq!(inherited.await.unwrap(), "a");
+            assert_eq!(env_var(name).unwrap(), "b");
+            let plain = tokio::spawn(async move { env_var(name).is_err() });
+            assert!(plain.await.unwrap());
+        })
+        .await;
+    }
+}
@@ -2,3 +2,6 @@ pub const BOOKKEEPING_PRECISION_FACTOR: u128 = 1_000_000_000_000_000;
 pub const FLOW_PRECISION: u128 = 1_000_000_000;
 pub const ARRAY_LENGTH: u64 = 10;
 pub const LIQUIDITY_AMPLIFICATION: u64 = 2;
+/// How many slots before a window boundary an instruction may already name the next
+/// window, as [`nearest_reference_index`](crate::nearest_reference_index) does.
+pub const NEXT_WINDOW_LEAD_SLOTS: u64 = ARRAY_LENGTH / 2;
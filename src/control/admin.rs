@@ -0,0 +1,448 @@
+//! Local admin interface: plain HTTP on a loopback address or a unix socket.
+//!
+//! Operators intervene with `curl` and nothing else:
+//!
+//! ```text
+//! curl -H "Authorization: Bearer $ADMIN_TOKEN" localhost:9091/status
+//! curl -H "Authorization: Bearer $ADMIN_TOKEN" -X POST localhost:9091/pause
+//! curl --unix-socket /run/oracle-flow.sock -X POST http://bot/force-update
+//! curl --unix-socket /run/oracle-flow.sock -X POST 'http://bot/rotate-key?keypair=/keys/next.json'
+//! ```
+//!
+//! `GET /status` and `POST /pause`, `/resume`, `/force-update`, `/force-stop` and
+//! `/rotate-key` all answer with the bot's [`BotStatus`] as JSON after applying the
+//! command. `/rotate-key` only queues the rotation; `key_rotation_pending` clears once the
+//! bot has taken it up, and `authority` changes once the handover is done.
+//!
+//! Any local user can reach a loopback port, so on one every request must carry
+//! `ADMIN_TOKEN` as a bearer token. The unix socket is only open to the bot's own user and
+//! needs the token only if one is set. A request with an `Origin` header comes from a
+//! browser page and is refused either way.
+
+use std::{fmt, net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, UnixListener},
+};
+use tracing::{info, warn};
+
+use crate::config::env_var;
+
+use super::{ControlState, KeyRotation};
+
+/// Largest request head accepted; commands carry no body.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Where the admin interface listens: `127.0.0.1:9091` or `unix:/path/to/socket`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for AdminAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let value = value.trim();
+        if let Some(path) = value.strip_prefix("unix:") {
+            return Ok(AdminAddr::Unix(PathBuf::from(path)));
+        }
+        let addr = value
+            .parse::<SocketAddr>()
+            .map_err(|e| anyhow::anyhow!("Invalid admin address `{value}`: {e}"))?;
+        // The interface has no authentication, so it never listens beyond this host.
+        anyhow::ensure!(
+            addr.ip().is_loopback(),
+            "admin address `{value}` must be a loopback address or a unix socket"
+        );
+        Ok(AdminAddr::Tcp(addr))
+    }
+}
+
+impl fmt::Display for AdminAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminAddr::Tcp(addr) => write!(f, "{addr}"),
+            AdminAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Where the admin interface listens and the token it asks for.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AdminConfig {
+    pub addr: AdminAddr,
+    /// Required as a bearer token on every request; always set for a TCP address.
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("addr", &self.addr)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl AdminConfig {
+    /// `None` unless `ADMIN_BIND_ADDR` is set. A TCP address needs `ADMIN_TOKEN` too.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(addr) = env_var("ADMIN_BIND_ADDR")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let addr = addr.parse::<AdminAddr>()?;
+        let token = env_var("ADMIN_TOKEN")
+            .ok()
+            .filter(|value| !value.trim().is_empty());
+        anyhow::ensure!(
+            token.is_some() || matches!(addr, AdminAddr::Unix(_)),
+            "ADMIN_TOKEN must be set to serve the admin interface on {addr}"
+        );
+        Ok(Some(Self { addr, token }))
+    }
+}
+
+/// Serve the admin interface as `config` says until the task is dropped.
+pub async fn serve(config: AdminConfig, state: Arc<ControlState>) -> anyhow::Result<()> {
+    let AdminConfig { addr, token } = config;
+    info!(event.name = "admin_server_started", admin.addr = %addr);
+    let token: Option<Arc<str>> = token.map(Into::into);
+    match &addr {
+        AdminAddr::Tcp(socket_addr) => {
+            anyhow::ensure!(
+                token.is_some(),
+                "the admin interface on {addr} needs a token"
+            );
+            let listener = TcpListener::bind(socket_addr).await?;
+            loop {
+                let (stream, _) = listener.accept().await?;
+                tokio::spawn(handle_connection(stream, state.clone(), token.clone()));
+            }
+        }
+        AdminAddr::Unix(path) => {
+            // A socket left behind by a previous run would make the bind fail.
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            let listener = UnixListener::bind(path)?;
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+            }
+            loop {
+                let (stream, _) = listener.accept().await?;
+                tokio::spawn(handle_connection(stream, state.clone(), token.clone()));
+            }
+        }
+    }
+}
+
+async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    state: Arc<ControlState>,
+    token: Option<Arc<str>>,
+) {
+    respond(stream, |request| {
+        match authorize(request, token.as_deref()) {
+            Ok(()) => handle(&state, request.method, request.path),
+            Err(refused) => refused,
+        }
+    })
+    .await;
+}
+
+/// Refuse a request from a browser page, which carries an `Origin`, and one without
+/// `token` when there is one.
+fn authorize(request: &Request<'_>, token: Option<&str>) -> Result<(), (u16, String)> {
+    if request.header("origin").is_some() {
+        return Err((403, error_body("cross-origin requests are refused")));
+    }
+    let Some(token) = token else {
+        return Ok(());
+    };
+    let presented = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(presented) if tokens_match(presented.trim(), token) => Ok(()),
+        _ => Err((401, error_body("missing or wrong bearer token"))),
+    }
+}
+
+/// Compare in time independent of where the two first differ.
+fn tokens_match(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// One request's head.
+pub(super) struct Request<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    headers: Vec<(&'a str, &'a str)>,
+}
+
+impl Request<'_> {
+    /// The value of header `name`, matched regardless of case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Read one request head from `stream` and answer it with the status and JSON body
+/// `route` gives for it.
+pub(super) async fn respond<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    route: impl FnOnce(&Request<'_>) -> (u16, String),
+) {
+    let mut request = Vec::new();
+    let mut buffer = [0_u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+            Err(error) => {
+                warn!(event.name = "admin_read_failed", ?error);
+                return;
+            }
+        }
+        if request.len() > MAX_REQUEST_BYTES {
+            break;
+        }
+    }
+
+    let head = String::from_utf8_lossy(&request);
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect();
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some(method), Some(path)) => route(&Request {
+            method,
+            path,
+            headers,
+        }),
+        _ => (400, error_body("malformed request")),
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    if let Err(error) = stream.write_all(response.as_bytes()).await {
+        warn!(event.name = "admin_write_failed", ?error);
+    }
+}
+
+/// Apply one admin command and render the response status and JSON body.
+fn handle(state: &ControlState, method: &str, path: &str) -> (u16, String) {
+    let (command, query) = path.split_once('?').unwrap_or((path, ""));
+    let command = command.trim_end_matches('/');
+    match (method, command) {
+        ("GET", "/status") => {}
+        ("POST", "/pause") => state.pause(),
+        ("POST", "/resume") => state.resume(),
+        ("POST", "/force-update") => state.request_force_update(),
+        ("POST", "/force-stop") => state.request_force_stop(),
+        ("POST", "/rotate-key") => match key_rotation(query) {
+            Ok(rotation) => state.request_key_rotation(rotation),
+            Err(message) => return (400, error_body(&message)),
+        },
+        (_, "/status" | "/pause" | "/resume" | "/force-update" | "/force-stop" | "/rotate-key") => {
+            return (405, error_body("method not allowed"));
+        }
+        _ => return (404, error_body("unknown command")),
+    }
+
+    let status = state.status();
+    info!(
+        event.name = "admin_call",
+        admin.command = command.trim_start_matches('/'),
+        control.paused = status.paused,
+        control.force_stop_requested = status.force_stop_requested,
+    );
+    match serde_json::to_string(&status) {
+        Ok(body) => (200, body),
+        Err(error) => (500, error_body(&error.to_string())),
+    }
+}
+
+/// Read a `/rotate-key` query: an optional, percent-encoded `keypair` path.
+fn key_rotation(query: &str) -> Result<KeyRotation, String> {
+    let mut rotation = KeyRotation::default();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        match pair.split_once('=') {
+            Some(("keypair", value)) if !value.is_empty() => {
+                rotation.keypair_path = Some(PathBuf::from(percent_decode(value)?));
+            }
+            _ => return Err(format!("unexpected query parameter `{pair}`")),
+        }
+    }
+    Ok(rotation)
+}
+
+fn percent_decode(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("bad escape in `{value}`"))?;
+                decoded.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| format!("`{value}` is not UTF-8"))
+}
+
+pub(super) fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+
+    #[test]
+    fn admin_addr_accepts_loopback_and_unix_sockets_only() {
+        assert_eq!(
+            "127.0.0.1:9091".parse::<AdminAddr>().unwrap(),
+            AdminAddr::Tcp("127.0.0.1:9091".parse().unwrap())
+        );
+        assert_eq!(
+            "unix:/tmp/bot.sock".parse::<AdminAddr>().unwrap(),
+            AdminAddr::Unix(PathBuf::from("/tmp/bot.sock"))
+        );
+        assert!("0.0.0.0:9091".parse::<AdminAddr>().is_err());
+    }
+
+    #[tokio::test]
+    async fn commands_update_state_and_return_status() {
+        let state = ControlState::new("oracle-flow", 1, Pubkey::new_unique());
+
+        let (status, body) = handle(&state, "POST", "/pause");
+        assert_eq!(status, 200);
+        assert!(state.is_paused());
+        assert!(body.contains("\"paused\":true"));
+
+        assert_eq!(handle(&state, "GET", "/pause").0, 405);
+        assert_eq!(handle(&state, "POST", "/reboot").0, 404);
+
+        let response = send(
+            &state,
+            None,
+            "POST /force-stop HTTP/1.1\r\nHost: bot\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(state.force_stop_requested());
+    }
+
+    async fn send(state: &Arc<ControlState>, token: Option<&str>, request: &str) -> String {
+        let (mut client, server) = tokio::io::duplex(4096);
+        tokio::spawn(handle_connection(
+            server,
+            state.clone(),
+            token.map(Into::into),
+        ));
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn requests_need_the_token_and_no_origin() {
+        let state = ControlState::new("oracle-flow", 1, Pubkey::new_unique());
+        let token = Some("s3cret");
+
+        let response = send(&state, token, "POST /pause HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 401"));
+        let response = send(
+            &state,
+            token,
+            "POST /pause HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 401"));
+        assert!(!state.is_paused());
+
+        let response = send(
+            &state,
+            token,
+            "POST /pause HTTP/1.1\r\nOrigin: https://evil.example\r\n\
+             authorization: Bearer s3cret\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 403"));
+        assert!(!state.is_paused());
+
+        let response = send(
+            &state,
+            token,
+            "POST /pause HTTP/1.1\r\nauthorization: Bearer s3cret\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(state.is_paused());
+    }
+
+    #[test]
+    fn rotate_key_queues_the_requested_keypair() {
+        let state = ControlState::new("inventory-flow", 2, Pubkey::new_unique());
+
+        let (status, body) = handle(&state, "POST", "/rotate-key?keypair=%2Fkeys%2Fnext.json");
+        assert_eq!(status, 200);
+        assert!(body.contains("\"key_rotation_pending\":true"));
+
+        assert_eq!(handle(&state, "POST", "/rotate-key?key=next.json").0, 400);
+        assert_eq!(handle(&state, "GET", "/rotate-key").0, 405);
+    }
+}
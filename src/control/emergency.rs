@@ -0,0 +1,333 @@
+//! A last-ditch zero-flow transaction for when the bot dies without stopping its position.
+//!
+//! A panic unwinds past every cleanup path in the main loop, so the position keeps
+//! streaming at whatever flows were last set with nobody watching it. [`EmergencyStop`]
+//! keeps an `update_liquidity_flows(0, 0)` transaction signed over a nonce account of its
+//! own, re-signing it whenever the market moves to a new window, so it's ready before
+//! anything goes wrong. [`install_panic_hook`](EmergencyStop::install_panic_hook) sends it
+//! from the panic hook, and the bots [`fire`](EmergencyStop::fire) it themselves when a
+//! task they depend on fails.
+//!
+//! Sending is best effort: one broadcast, no preflight, no confirmation, given at most
+//! [`timeout`](EmergencyStopConfig::timeout) before the process goes on dying. The nonce
+//! keeps the signature valid for as long as it isn't advanced, but the instruction names a
+//! window, and `update_liquidity_flows` for a window that has passed fails on-chain (see
+//! [`LandsTooLate`](crate::tx::LandsTooLate)). So the signed stop is only good until the
+//! end of the window it was signed for, which is why it is re-signed at every boundary.
+//!
+//! A key rotation moves the position to another authority, whose nonce accounts the old
+//! transaction can't use, so the bots [`rearm`](EmergencyStop::rearm) the stop on the new
+//! position in place, where the panic hook still finds it.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anchor_client::{
+    Program,
+    solana_sdk::{commitment_config::CommitmentConfig, transaction::Transaction},
+};
+use anchor_lang::prelude::Pubkey;
+use anyhow::Context;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_types::config::RpcSendTransactionConfig;
+use tracing::{error, info, warn};
+
+use crate::{
+    ProgramPayer, build_update_liquidity_flows_instruction, config::env_var, fetch_market_state,
+    nearest_reference_index, telemetry::parse_bool, twob_anchor::client::args, tx::TxSender,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmergencyStopConfig {
+    /// How long a dying process waits for the broadcast.
+    pub timeout: Duration,
+}
+
+impl EmergencyStopConfig {
+    /// `None` unless `EMERGENCY_STOP_ON_PANIC` is on. Needs `TX_NONCE_POOL_SIZE`, since the
+    /// transaction is kept signed over a nonce account.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let enabled = match env_var("EMERGENCY_STOP_ON_PANIC") {
+            Ok(value) if !value.trim().is_empty() => parse_bool(&value)
+                .map_err(|e| anyhow::anyhow!("Invalid EMERGENCY_STOP_ON_PANIC: {}", e))?,
+            _ => false,
+        };
+        if !enabled {
+            return Ok(None);
+        }
+        let timeout_secs = match env_var("EMERGENCY_STOP_TIMEOUT_SECS") {
+            Ok(value) if !value.trim().is_empty() => value
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("Invalid EMERGENCY_STOP_TIMEOUT_SECS: {}", e))?,
+            _ => 5,
+        };
+        Ok(Some(Self {
+            timeout: Duration::from_secs(timeout_secs),
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct EmergencyStop {
+    market_id: u64,
+    timeout: Duration,
+    armed: Mutex<Armed>,
+    fired: AtomicBool,
+}
+
+/// The position the stop is armed on, through its authority's sender.
+#[derive(Debug)]
+struct Armed {
+    rpc_url: String,
+    /// `None` while a re-arm has yet to reserve one.
+    nonce_account: Option<Pubkey>,
+    /// The signed transaction and the window it was signed for.
+    signed: Option<(u64, Transaction)>,
+}
+
+impl EmergencyStop {
+    /// Reserve a nonce account from `sender`'s pool and sign the first transaction.
+    pub async fn prepare(
+        config: EmergencyStopConfig,
+        program: &Program<ProgramPayer>,
+        market_id: u64,
+        sender: &TxSender,
+    ) -> anyhow::Result<Arc<Self>> {
+        let nonce_account = sender.reserve_nonce()?;
+        let stop = Arc::new(Self {
+            market_id,
+            timeout: config.timeout,
+            armed: Mutex::new(Armed {
+                rpc_url: sender.rpc().url(),
+                nonce_account: Some(nonce_account),
+                signed: None,
+            }),
+            fired: AtomicBool::new(false),
+        });
+        stop.refresh(program, sender).await?;
+        info!(
+            event.name = "emergency_stop_armed",
+            market.id = market_id,
+            tx.nonce_account = %nonce_account,
+        );
+        Ok(stop)
+    }
+
+    /// Arm the stop on the position `sender`'s payer now holds, after a key rotation moved
+    /// it there: reserve a nonce account from `sender`'s pool and sign over it with
+    /// `program`, the client for that payer. The old transaction is dropped first, so should
+    /// this fail the stop sends nothing rather than stop a position the bot no longer holds.
+    pub async fn rearm(
+        &self,
+        program: &Program<ProgramPayer>,
+        sender: &TxSender,
+    ) -> anyhow::Result<()> {
+        self.arm(sender.rpc().url(), None);
+        let nonce_account = sender.reserve_nonce()?;
+        self.arm(sender.rpc().url(), Some(nonce_account));
+        self.refresh(program, sender).await?;
+        info!(
+            event.name = "emergency_stop_rearmed",
+            market.id = self.market_id,
+            lp.authority = %sender.payer(),
+            tx.nonce_account = %nonce_account,
+        );
+        Ok(())
+    }
+
+    /// Re-sign the transaction if the market has moved to another window since it was
+    /// signed. Cheap to call every cycle.
+    pub async fn refresh(
+        &self,
+        program: &Program<ProgramPayer>,
+        sender: &TxSender,
+    ) -> anyhow::Result<()> {
+        let state = fetch_market_state(program, self.market_id).await?;
+        let reference_index =
+            nearest_reference_index(state.current_slot, state.market.end_slot_interval);
+        if self.signed_for() == Some(reference_index) {
+            return Ok(());
+        }
+        let nonce_account = self
+            .lock()
+            .nonce_account
+            .context("No nonce account to sign the emergency stop over; re-arm it")?;
+        let args = args::UpdateLiquidityFlows {
+            reference_index,
+            base_flow_u64: 0,
+            quote_flow_u64: 0,
+        };
+        let ix = build_update_liquidity_flows_instruction(program, self.market_id, args)?;
+        let transaction = sender.presign_durable_over(nonce_account, vec![ix]).await?;
+        let mut armed = self.lock();
+        // A re-arm while this was signing has moved the stop to another position.
+        if armed.nonce_account == Some(nonce_account) {
+            armed.signed = Some((reference_index, transaction));
+        }
+        Ok(())
+    }
+
+    /// Send the transaction, waiting up to the configured timeout. Only the first call
+    /// sends anything; returns whether this one did and the RPC took it.
+    ///
+    /// Blocks the calling thread; async callers use [`trigger`](Self::trigger).
+    pub fn fire(&self, reason: &str) -> bool {
+        if self.fired.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        let (rpc_url, signed) = {
+            let armed = self.lock();
+            (armed.rpc_url.clone(), armed.signed.clone())
+        };
+        let Some((reference_index, transaction)) = signed else {
+            error!(
+                event.name = "emergency_stop_unavailable",
+                market.id = self.market_id,
+                reason,
+            );
+            return false;
+        };
+        warn!(
+            event.name = "emergency_stop_fired",
+            market.id = self.market_id,
+            reference_index,
+            tx.signature = %transaction.signatures[0],
+            reason,
+            monotonic_counter.emergency_stops_total = 1_u64,
+        );
+
+        // A thread of its own keeps the blocking client clear of any runtime the caller
+        // is on, and lets us give up on it.
+        let (done, result) = mpsc::channel();
+        thread::spawn(move || {
+            let rpc = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+            let config = RpcSendTransactionConfig {
+                skip_preflight: true,
+                ..RpcSendTransactionConfig::default()
+            };
+            let _ = done.send(rpc.send_transaction_with_config(&transaction, config));
+        });
+        match result.recv_timeout(self.timeout) {
+            Ok(Ok(signature)) => {
+                info!(
+                    event.name = "emergency_stop_sent",
+                    market.id = self.market_id,
+                    tx.signature = %signature,
+                );
+                true
+            }
+            Ok(Err(error)) => {
+                error!(
+                    event.name = "emergency_stop_send_failed",
+                    market.id = self.market_id,
+                    ?error,
+                );
+                false
+            }
+            Err(_) => {
+                error!(
+                    event.name = "emergency_stop_send_failed",
+                    market.id = self.market_id,
+                    error = "timed out",
+                    timeout_secs = self.timeout.as_secs(),
+                );
+                false
+            }
+        }
+    }
+
+    /// [`fire`](Self::fire), from async code.
+    pub async fn trigger(self: &Arc<Self>, reason: String) -> bool {
+        let stop = self.clone();
+        tokio::task::spawn_blocking(move || stop.fire(&reason))
+            .await
+            .unwrap_or(false)
+    }
+
+    /// Fire on any panic, task panics included, before handing over to the hook that was
+    /// installed before.
+    pub fn install_panic_hook(self: &Arc<Self>) {
+        let stop = self.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            stop.fire(&format!("panic: {info}"));
+            previous(info);
+        }));
+    }
+
+    /// Point the stop at `nonce_account` through `rpc_url`, with nothing signed yet.
+    fn arm(&self, rpc_url: String, nonce_account: Option<Pubkey>) {
+        *self.lock() = Armed {
+            rpc_url,
+            nonce_account,
+            signed: None,
+        };
+    }
+
+    fn signed_for(&self) -> Option<u64> {
+        self.lock()
+            .signed
+            .as_ref()
+            .map(|(reference_index, _)| *reference_index)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Armed> {
+        self.armed
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop() -> EmergencyStop {
+        EmergencyStop {
+            market_id: 1,
+            timeout: Duration::from_millis(10),
+            armed: Mutex::new(Armed {
+                rpc_url: "http://127.0.0.1:1".to_string(),
+                nonce_account: Some(Pubkey::new_unique()),
+                signed: None,
+            }),
+            fired: AtomicBool::new(false),
+        }
+    }
+
+    #[test]
+    fn fires_at_most_once() {
+        let stop = stop();
+        // Nothing signed yet: the attempt still counts, so a panic storm sends nothing.
+        assert!(!stop.fire("test"));
+        assert!(stop.fired.load(Ordering::SeqCst));
+        stop.lock().signed = Some((7, Transaction::default()));
+        assert_eq!(stop.signed_for(), Some(7));
+        assert!(!stop.fire("test"));
+    }
+
+    #[test]
+    fn rearming_drops_the_old_transaction() {
+        let stop = stop();
+        stop.lock().signed = Some((7, Transaction::default()));
+
+        // Re-armed in the window the old transaction was signed for, the new position's
+        // still gets signed.
+        let nonce_account = Pubkey::new_unique();
+        stop.arm("http://127.0.0.2:1".to_string(), Some(nonce_account));
+        assert_eq!(stop.signed_for(), None);
+        assert_eq!(stop.lock().nonce_account, Some(nonce_account));
+        assert_eq!(stop.lock().rpc_url, "http://127.0.0.2:1");
+
+        // Fired before the new one is signed, it sends nothing.
+        assert!(!stop.fire("test"));
+    }
+}
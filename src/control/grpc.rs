@@ -0,0 +1,166 @@
+//! tonic server for the `twob.control.v1.Control` service defined in `proto/control.proto`.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use tonic::{Request, Response, Status, transport::Server};
+use tracing::info;
+
+use super::{BotStatus, ControlState, ThresholdOverrides};
+
+pub mod proto {
+    tonic::include_proto!("twob.control.v1");
+}
+
+use proto::{
+    ForceStopRequest, ForceUpdateRequest, PauseRequest, ResumeRequest, SetThresholdsRequest,
+    StatusRequest, StatusResponse,
+    control_server::{Control, ControlServer},
+};
+
+/// Serve the control plane on `addr` until the task is dropped.
+pub async fn serve(addr: SocketAddr, state: Arc<ControlState>) -> anyhow::Result<()> {
+    info!(event.name = "control_server_started", control.addr = %addr);
+    Server::builder()
+        .add_service(ControlServer::new(ControlService { state }))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+struct ControlService {
+    state: Arc<ControlState>,
+}
+
+impl ControlService {
+    fn respond(&self, call: &'static str) -> Response<StatusResponse> {
+        let status = self.state.status();
+        info!(
+            event.name = "control_call",
+            control.call = call,
+            control.paused = status.paused,
+            control.force_stop_requested = status.force_stop_requested,
+        );
+        Response::new(status.into())
+    }
+}
+
+#[tonic::async_trait]
+impl Control for ControlService {
+    async fn status(
+        &self,
+        _request: Request<StatusRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        Ok(Response::new(self.state.status().into()))
+    }
+
+    async fn pause(
+        &self,
+        _request: Request<PauseRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        self.state.pause();
+        Ok(self.respond("pause"))
+    }
+
+    async fn resume(
+        &self,
+        _request: Request<ResumeRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        self.state.resume();
+        Ok(self.respond("resume"))
+    }
+
+    async fn set_thresholds(
+        &self,
+        request: Request<SetThresholdsRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        let request = request.into_inner();
+        if request.flow_divisor == Some(0) {
+            return Err(Status::invalid_argument("flow_divisor must be non-zero"));
+        }
+        self.state.set_thresholds(ThresholdOverrides {
+            quote_threshold_bps: request.quote_threshold_bps,
+            rebalance_threshold_bps: request.rebalance_threshold_bps,
+            flow_divisor: request.flow_divisor,
+        });
+        Ok(self.respond("set_thresholds"))
+    }
+
+    async fn force_stop(
+        &self,
+        _request: Request<ForceStopRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        self.state.request_force_stop();
+        Ok(self.respond("force_stop"))
+    }
+
+    async fn force_update(
+        &self,
+        _request: Request<ForceUpdateRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        self.state.request_force_update();
+        Ok(self.respond("force_update"))
+    }
+}
+
+impl From<BotStatus> for StatusResponse {
+    fn from(status: BotStatus) -> Self {
+        Self {
+            bot: status.bot,
+            market_id: status.market_id,
+            authority: status.authority,
+            paused: status.paused,
+            force_stop_requested: status.force_stop_requested,
+            quote_threshold_bps: status.overrides.quote_threshold_bps,
+            rebalance_threshold_bps: status.overrides.rebalance_threshold_bps,
+            flow_divisor: status.overrides.flow_divisor,
+            cycles: status.cycles,
+            errors: status.errors,
+            last_cycle_at: status
+                .last_cycle_at
+                .map(|at| at.to_rfc3339())
+                .unwrap_or_default(),
+            last_error: status.last_error.unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn set_thresholds_rejects_zero_flow_divisor() {
+        let service = ControlService {
+            state: ControlState::new("inventory-flow", 1, Pubkey::new_unique()),
+        };
+
+        let result = service
+            .set_thresholds(Request::new(SetThresholdsRequest {
+                flow_divisor: Some(0),
+                ..Default::default()
+            }))
+            .await;
+
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert_eq!(service.state.thresholds(), ThresholdOverrides::default());
+    }
+
+    #[tokio::test]
+    async fn pause_and_force_stop_are_reflected_in_the_response() {
+        let service = ControlService {
+            state: ControlState::new("oracle-flow", 1, Pubkey::new_unique()),
+        };
+
+        let paused = service.pause(Request::new(PauseRequest {})).await.unwrap();
+        assert!(paused.get_ref().paused);
+
+        let stopped = service
+            .force_stop(Request::new(ForceStopRequest {}))
+            .await
+            .unwrap();
+        assert!(stopped.get_ref().force_stop_requested);
+        assert!(stopped.get_ref().last_cycle_at.is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+//! Heartbeat pings to an external dead-man's switch, e.g. healthchecks.io or Cronitor.
+//!
+//! The [`write_heartbeat`](super::write_heartbeat) file only helps a watchdog on the same
+//! host. A bot stuck in an await that never returns doesn't error or crash, so nothing
+//! alerts on it; pinging a monitoring service from the main loop turns that silence into
+//! an alert once pings stop arriving. Pings go out in the background, at most once per
+//! [`min_interval`](HeartbeatConfig::min_interval), so a slow monitoring service can't
+//! hold up the loop it is watching.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, warn};
+
+use crate::config::env_var;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    pub url: String,
+    /// Loop iterations closer together than this share one ping.
+    pub min_interval: Duration,
+    pub timeout: Duration,
+}
+
+impl HeartbeatConfig {
+    /// `None` unless `HEARTBEAT_PING_URL` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(url) = env_var("HEARTBEAT_PING_URL")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+        else {
+            return Ok(None);
+        };
+        reqwest::Url::parse(&url)
+            .map_err(|e| anyhow::anyhow!("Invalid HEARTBEAT_PING_URL: {}", e))?;
+        let secs = |name: &str, default: u64| -> anyhow::Result<Duration> {
+            let secs = match env_var(name) {
+                Ok(value) if !value.trim().is_empty() => value
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))?,
+                _ => default,
+            };
+            Ok(Duration::from_secs(secs))
+        };
+        Ok(Some(Self {
+            url,
+            min_interval: secs("HEARTBEAT_PING_INTERVAL_SECS", 60)?,
+            timeout: secs("HEARTBEAT_PING_TIMEOUT_SECS", 10)?,
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct HeartbeatPinger {
+    config: HeartbeatConfig,
+    client: reqwest::Client,
+    last_ping: Mutex<Option<Instant>>,
+}
+
+impl HeartbeatPinger {
+    pub fn new(config: HeartbeatConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            last_ping: Mutex::new(None),
+        }
+    }
+
+    /// Report the calling loop alive. Returns right away; the request runs on its own
+    /// task.
+    pub fn ping(&self) {
+        if !self.take_due(Instant::now()) {
+            return;
+        }
+        let request = self
+            .client
+            .get(&self.config.url)
+            .timeout(self.config.timeout)
+            .send();
+        tokio::spawn(async move {
+            match request
+                .await
+                .and_then(|response| response.error_for_status())
+            {
+                Ok(_) => debug!(event.name = "heartbeat_ping_sent"),
+                Err(error) => warn!(
+                    event.name = "heartbeat_ping_failed",
+                    monotonic_counter.heartbeat_ping_failures_total = 1_u64,
+                    error = %error.without_url(),
+                ),
+            }
+        });
+    }
+
+    /// Whether a ping is due at `now`, claiming it if so.
+    fn take_due(&self, now: Instant) -> bool {
+        let mut last_ping = self
+            .last_ping
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let due = last_ping
+            .is_none_or(|last| now.saturating_duration_since(last) >= self.config.min_interval);
+        if due {
+            *last_ping = Some(now);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pings_at_most_once_per_interval() {
+        let pinger = HeartbeatPinger::new(HeartbeatConfig {
+            url: "https://hc-ping.com/uuid".to_string(),
+            min_interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+        });
+        let start = Instant::now();
+        assert!(pinger.take_due(start));
+        assert!(!pinger.take_due(start + Duration::from_secs(59)));
+        assert!(pinger.take_due(start + Duration::from_secs(60)));
+        assert!(!pinger.take_due(start + Duration::from_secs(61)));
+    }
+}
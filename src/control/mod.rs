@@ -0,0 +1,498 @@
+//! Runtime control shared between a bot's main loop and its control-plane server.
+//!
+//! The bots consult a [`ControlState`] before every cycle: paused bots skip work, threshold
+//! overrides replace the values read from the environment, a force-update request runs a
+//! cycle right away (even while paused), a force-stop request makes the bot zero its
+//! flows and exit, and a key-rotation request makes it hand its position over to a new
+//! signing keypair. The gRPC server (`grpc` feature), the local [`admin`] interface and the
+//! [`telegram`] command bot only mutate this state.
+//!
+//! Bots also report what their supervisor needs to judge them by: successful cycles,
+//! their event subscription, price updates, price-feed health and RPC reachability. The
+//! [`probes`] server turns that into `/healthz` and `/readyz`.
+//!
+//! Should a bot die anyway, [`emergency`] zeroes its flows on the way out, and should it
+//! hang, the pings [`heartbeat`] sends to an external monitor stop.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use anchor_lang::prelude::Pubkey;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::feed_health::FeedStatus;
+
+pub mod admin;
+pub mod emergency;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod heartbeat;
+pub mod probes;
+pub mod telegram;
+
+/// Runtime overrides for the tunables a bot reads from its config. `None` keeps the
+/// configured value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ThresholdOverrides {
+    pub quote_threshold_bps: Option<u64>,
+    pub rebalance_threshold_bps: Option<u64>,
+    pub flow_divisor: Option<u64>,
+}
+
+impl ThresholdOverrides {
+    /// Overlay the fields set in `other` onto `self`.
+    pub fn merge(&mut self, other: ThresholdOverrides) {
+        if other.quote_threshold_bps.is_some() {
+            self.quote_threshold_bps = other.quote_threshold_bps;
+        }
+        if other.rebalance_threshold_bps.is_some() {
+            self.rebalance_threshold_bps = other.rebalance_threshold_bps;
+        }
+        if other.flow_divisor.is_some() {
+            self.flow_divisor = other.flow_divisor;
+        }
+    }
+}
+
+/// A request to swap the bot's signing keypair for the one at `keypair_path`, or, without
+/// a path, for whatever the bot's configured keypair source holds now.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyRotation {
+    pub keypair_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BotStatus {
+    pub bot: String,
+    pub market_id: u64,
+    pub authority: String,
+    pub paused: bool,
+    pub force_stop_requested: bool,
+    pub key_rotation_pending: bool,
+    pub overrides: ThresholdOverrides,
+    pub cycles: u64,
+    pub errors: u64,
+    pub last_cycle_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Latest position value reported by the bot, in quote units.
+    pub position_value: Option<f64>,
+    /// Change in position value since the first value the bot reported.
+    pub pnl: Option<f64>,
+    pub started_at: DateTime<Utc>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// Whether the bot's event subscription is up; `None` for bots without one.
+    pub subscribed: Option<bool>,
+    /// Timestamp of the latest price the bot read from its feed.
+    pub price_at: Option<DateTime<Utc>>,
+    /// Composite health of the bot's price sources, with the unhealthy ones' problems.
+    pub price_feed_status: Option<FeedStatus>,
+    pub price_feed_detail: Option<String>,
+    /// When RPC was last checked, and the error if it couldn't be reached.
+    pub rpc_checked_at: Option<DateTime<Utc>>,
+    pub rpc_error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    authority: Pubkey,
+    paused: bool,
+    force_stop_requested: bool,
+    key_rotation: Option<KeyRotation>,
+    overrides: ThresholdOverrides,
+    cycles: u64,
+    errors: u64,
+    last_cycle_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    initial_position_value: Option<f64>,
+    position_value: Option<f64>,
+    last_success_at: Option<DateTime<Utc>>,
+    subscribed: Option<bool>,
+    price_at: Option<DateTime<Utc>>,
+    price_feed_status: Option<FeedStatus>,
+    price_feed_detail: Option<String>,
+    rpc_checked_at: Option<DateTime<Utc>>,
+    rpc_error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ControlState {
+    bot: String,
+    market_id: u64,
+    started_at: DateTime<Utc>,
+    inner: Mutex<Inner>,
+    force_stop: Notify,
+    force_update: Notify,
+    key_rotation: Notify,
+}
+
+impl ControlState {
+    pub fn new(bot: impl Into<String>, market_id: u64, authority: Pubkey) -> Arc<Self> {
+        Arc::new(Self {
+            bot: bot.into(),
+            market_id,
+            started_at: Utc::now(),
+            inner: Mutex::new(Inner {
+                authority,
+                ..Inner::default()
+            }),
+            force_stop: Notify::new(),
+            force_update: Notify::new(),
+            key_rotation: Notify::new(),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // The guarded data is plain counters and flags, so a poisoned lock is still usable.
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The pubkey the bot currently signs with.
+    pub fn authority(&self) -> Pubkey {
+        self.lock().authority
+    }
+
+    /// Report that the bot now signs with `authority`, after a key rotation.
+    pub fn set_authority(&self, authority: Pubkey) {
+        self.lock().authority = authority;
+    }
+
+    pub fn pause(&self) {
+        self.lock().paused = true;
+    }
+
+    pub fn resume(&self) {
+        self.lock().paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    pub fn set_thresholds(&self, overrides: ThresholdOverrides) {
+        self.lock().overrides.merge(overrides);
+    }
+
+    pub fn thresholds(&self) -> ThresholdOverrides {
+        self.lock().overrides
+    }
+
+    /// Ask the bot to zero its flows and exit. Idempotent.
+    pub fn request_force_stop(&self) {
+        self.lock().force_stop_requested = true;
+        self.force_stop.notify_waiters();
+    }
+
+    pub fn force_stop_requested(&self) -> bool {
+        self.lock().force_stop_requested
+    }
+
+    /// Resolves once a force-stop has been requested, including one requested earlier.
+    pub async fn force_stopped(&self) {
+        // Created before the flag check so a concurrent request can't slip in between.
+        let notified = self.force_stop.notified();
+        if self.force_stop_requested() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Ask the bot to rotate its signing keypair. A request made while another is pending
+    /// replaces it.
+    pub fn request_key_rotation(&self, rotation: KeyRotation) {
+        self.lock().key_rotation = Some(rotation);
+        self.key_rotation.notify_one();
+    }
+
+    /// Resolves with the next key-rotation request, taking it so it is carried out once.
+    pub async fn key_rotation_requested(&self) -> KeyRotation {
+        loop {
+            let notified = self.key_rotation.notified();
+            if let Some(rotation) = self.lock().key_rotation.take() {
+                return rotation;
+            }
+            notified.await;
+        }
+    }
+
+    /// Ask the bot to run a cycle now instead of waiting out its interval. Requests made
+    /// while no cycle is pending collapse into one.
+    pub fn request_force_update(&self) {
+        self.force_update.notify_one();
+    }
+
+    /// Wait for the next cycle: `interval` elapsing, or a force-update request. Returns
+    /// `true` when the cycle was forced.
+    pub async fn next_cycle(&self, interval: Duration) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => false,
+            _ = self.force_update.notified() => true,
+        }
+    }
+
+    pub fn record_cycle<T, E: std::fmt::Display>(&self, result: &Result<T, E>) {
+        let mut inner = self.lock();
+        inner.cycles = inner.cycles.saturating_add(1);
+        inner.last_cycle_at = Some(Utc::now());
+        match result {
+            Ok(_) => inner.last_success_at = inner.last_cycle_at,
+            Err(error) => {
+                inner.errors = inner.errors.saturating_add(1);
+                inner.last_error = Some(error.to_string());
+            }
+        }
+    }
+
+    /// Report the bot's event subscription coming up or going down.
+    pub fn set_subscribed(&self, subscribed: bool) {
+        self.lock().subscribed = Some(subscribed);
+    }
+
+    /// Report a price read from the feed, stamped `timestamp` in unix seconds.
+    pub fn record_price(&self, timestamp: u64) {
+        self.lock().price_at = DateTime::from_timestamp(timestamp as i64, 0);
+    }
+
+    /// Report the composite health of the bot's price sources.
+    pub fn record_feed_health(&self, status: FeedStatus, detail: impl Into<String>) {
+        let mut inner = self.lock();
+        inner.price_feed_status = Some(status);
+        inner.price_feed_detail = Some(detail.into());
+    }
+
+    /// Report whether RPC answered just now.
+    pub fn record_rpc_check(&self, result: Result<(), String>) {
+        let mut inner = self.lock();
+        inner.rpc_checked_at = Some(Utc::now());
+        inner.rpc_error = result.err();
+    }
+
+    /// Report the position's current value; the first report is the baseline for
+    /// [`BotStatus::pnl`].
+    pub fn record_position_value(&self, value: f64) {
+        let mut inner = self.lock();
+        inner.initial_position_value.get_or_insert(value);
+        inner.position_value = Some(value);
+    }
+
+    pub fn status(&self) -> BotStatus {
+        let inner = self.lock();
+        BotStatus {
+            bot: self.bot.clone(),
+            market_id: self.market_id,
+            authority: inner.authority.to_string(),
+            paused: inner.paused,
+            force_stop_requested: inner.force_stop_requested,
+            key_rotation_pending: inner.key_rotation.is_some(),
+            overrides: inner.overrides,
+            cycles: inner.cycles,
+            errors: inner.errors,
+            last_cycle_at: inner.last_cycle_at,
+            last_error: inner.last_error.clone(),
+            position_value: inner.position_value,
+            pnl: inner
+                .position_value
+                .zip(inner.initial_position_value)
+                .map(|(value, initial)| value - initial),
+            started_at: self.started_at,
+            last_success_at: inner.last_success_at,
+            subscribed: inner.subscribed,
+            price_at: inner.price_at,
+            price_feed_status: inner.price_feed_status,
+            price_feed_detail: inner.price_feed_detail.clone(),
+            rpc_checked_at: inner.rpc_checked_at,
+            rpc_error: inner.rpc_error.clone(),
+        }
+    }
+}
+
+/// Mark the bot's main loop as alive by rewriting `path` with the current time. The
+/// watchdog judges liveness by the file's modification time.
+pub fn write_heartbeat(path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, Utc::now().to_rfc3339())
+}
+
+/// Counts consecutive failed cycles and trips once `max_failures` is reached in a row.
+/// A successful cycle resets the count; `max_failures == 0` disables the breaker.
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreaker {
+    max_failures: u32,
+    consecutive_failures: u32,
+}
+
+impl CircuitBreaker {
+    pub fn new(max_failures: u32) -> Self {
+        Self {
+            max_failures,
+            consecutive_failures: 0,
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Record a cycle outcome. Returns `true` exactly once per run of failures, on the
+    /// failure that reaches the limit.
+    pub fn record<T, E>(&mut self, result: &Result<T, E>) -> bool {
+        if result.is_ok() {
+            self.consecutive_failures = 0;
+            return false;
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.max_failures > 0 && self.consecutive_failures == self.max_failures
+    }
+
+    /// [`record`](Self::record) a bot cycle, not counting one that only failed because its
+    /// sends were [dry runs](crate::tx::DryRun).
+    pub fn record_cycle<T>(&mut self, result: &anyhow::Result<T>) -> bool {
+        match result {
+            Err(error) if crate::tx::is_dry_run(error) => self.record::<(), ()>(&Ok(())),
+            result => self.record(result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn threshold_overrides_merge_only_set_fields() {
+        let state = ControlState::new("oracle-flow", 1, Pubkey::new_unique());
+        state.set_thresholds(ThresholdOverrides {
+            quote_threshold_bps: Some(25),
+            rebalance_threshold_bps: Some(200),
+            flow_divisor: None,
+        });
+        state.set_thresholds(ThresholdOverrides {
+            quote_threshold_bps: Some(40),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            state.thresholds(),
+            ThresholdOverrides {
+                quote_threshold_bps: Some(40),
+                rebalance_threshold_bps: Some(200),
+                flow_divisor: None,
+            }
+        );
+    }
+
+    #[test]
+    fn status_tracks_pause_and_cycle_results() {
+        let state = ControlState::new("inventory-flow", 3, Pubkey::new_unique());
+        state.pause();
+        state.record_cycle::<(), &str>(&Ok(()));
+        state.record_cycle::<(), &str>(&Err("rpc timeout"));
+
+        let status = state.status();
+        assert!(status.paused);
+        assert_eq!(status.cycles, 2);
+        assert_eq!(status.errors, 1);
+        assert_eq!(status.last_error.as_deref(), Some("rpc timeout"));
+
+        state.resume();
+        assert!(!state.is_paused());
+    }
+
+    #[tokio::test]
+    async fn force_stop_wakes_waiters_requested_before_or_after() {
+        let state = ControlState::new("oracle-flow", 1, Pubkey::new_unique());
+
+        let waiter = tokio::spawn({
+            let state = state.clone();
+            async move { state.force_stopped().await }
+        });
+        tokio::task::yield_now().await;
+        state.request_force_stop();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), state.force_stopped())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn force_update_cuts_the_wait_short_once() {
+        let state = ControlState::new("oracle-flow", 1, Pubkey::new_unique());
+
+        state.request_force_update();
+        state.request_force_update();
+        let forced = tokio::time::timeout(
+            Duration::from_secs(1),
+            state.next_cycle(Duration::from_secs(3_600)),
+        )
+        .await
+        .unwrap();
+        assert!(forced);
+
+        assert!(!state.next_cycle(Duration::from_millis(10)).await);
+    }
+
+    #[tokio::test]
+    async fn key_rotation_is_taken_once() {
+        let state = ControlState::new("oracle-flow", 1, Pubkey::new_unique());
+        let rotation = KeyRotation {
+            keypair_path: Some(PathBuf::from("/keys/next.json")),
+        };
+        state.request_key_rotation(rotation.clone());
+        assert!(state.status().key_rotation_pending);
+
+        let taken = tokio::time::timeout(Duration::from_secs(1), state.key_rotation_requested())
+            .await
+            .unwrap();
+        assert_eq!(taken, rotation);
+        assert!(!state.status().key_rotation_pending);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), state.key_rotation_requested())
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn circuit_breaker_trips_once_per_failure_streak() {
+        let mut breaker = CircuitBreaker::new(2);
+        assert!(!breaker.record::<(), _>(&Err("timeout")));
+        assert!(!breaker.record::<(), &str>(&Ok(())));
+        assert!(!breaker.record::<(), _>(&Err("timeout")));
+        assert!(breaker.record::<(), _>(&Err("timeout")));
+        assert!(!breaker.record::<(), _>(&Err("timeout")));
+        assert_eq!(breaker.consecutive_failures(), 3);
+
+        let mut disabled = CircuitBreaker::new(0);
+        assert!(!disabled.record::<(), _>(&Err("timeout")));
+    }
+
+    #[test]
+    fn dry_run_cycles_are_not_failures() {
+        let dry_run = || crate::tx::DryRun {
+            action: "update_liquidity_flows".to_string(),
+            units_consumed: Some(1_000),
+        };
+        let mut breaker = CircuitBreaker::new(1);
+        assert!(!breaker.record_cycle::<()>(&Err(dry_run().into())));
+        // As it reaches a bot, through an `execute_*` returning a `TwobError`.
+        let through_twob = crate::TwobError::from(anyhow::Error::new(dry_run()));
+        assert!(through_twob.is_dry_run());
+        assert!(
+            !breaker.record_cycle::<()>(&Err(anyhow::Error::new(through_twob).context("cycle")))
+        );
+        assert_eq!(breaker.consecutive_failures(), 0);
+
+        assert!(breaker.record_cycle::<()>(&Err(anyhow::anyhow!("timeout"))));
+    }
+}
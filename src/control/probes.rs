@@ -0,0 +1,313 @@
+//! Liveness and readiness probes for a bot's supervisor (Kubernetes, systemd, a load
+//! balancer) to poll:
+//!
+//! ```text
+//! curl 10.0.0.5:8080/healthz
+//! curl 10.0.0.5:8080/readyz
+//! ```
+//!
+//! `/healthz` fails only when the bot looks wedged: not paused, yet no cycle has
+//! succeeded for [`max_evaluation_age`](ProbeConfig::max_evaluation_age). Restarting it
+//! is then the fix. `/readyz` also fails while the event subscription is down, the last
+//! price read is stale, no price source is healthy, RPC can't be reached, or no cycle
+//! has succeeded yet. Both answer
+//! with each check's outcome as JSON, with 200 when all pass and 503 otherwise.
+//!
+//! Unlike the [`admin`](super::admin) interface this one may listen on any address, since
+//! probes come from outside the host. It is read-only.
+
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use tokio::{net::TcpListener, time::sleep};
+use tracing::{info, warn};
+
+use super::{
+    BotStatus, ControlState,
+    admin::{error_body, respond},
+};
+use crate::{config::env_var, feed_health::FeedStatus};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeConfig {
+    pub bind_addr: SocketAddr,
+    /// Longest a running bot may go without a successful cycle.
+    pub max_evaluation_age: Duration,
+    /// Oldest the last price read may be for the bot to be ready.
+    pub max_price_age: Duration,
+    /// How often RPC reachability is checked.
+    pub rpc_check_interval: Duration,
+}
+
+impl ProbeConfig {
+    /// `None` unless `HEALTH_BIND_ADDR` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(bind_addr) = env_var("HEALTH_BIND_ADDR")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let secs = |name: &str, default: u64| -> anyhow::Result<Duration> {
+            let secs = match env_var(name) {
+                Ok(value) if !value.trim().is_empty() => value
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))?,
+                _ => default,
+            };
+            Ok(Duration::from_secs(secs))
+        };
+        Ok(Some(Self {
+            bind_addr: bind_addr
+                .trim()
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid HEALTH_BIND_ADDR: {}", e))?,
+            max_evaluation_age: secs("HEALTH_MAX_EVALUATION_AGE_SECS", 900)?,
+            max_price_age: secs("HEALTH_MAX_PRICE_AGE_SECS", 120)?,
+            rpc_check_interval: secs("HEALTH_RPC_CHECK_INTERVAL_SECS", 15)?,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn pass(detail: impl Into<String>) -> Self {
+        Self {
+            ok: true,
+            detail: detail.into(),
+        }
+    }
+
+    fn fail(detail: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeReport {
+    pub ok: bool,
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+impl ProbeReport {
+    fn new(checks: BTreeMap<&'static str, Check>) -> Self {
+        Self {
+            ok: checks.values().all(|check| check.ok),
+            checks,
+        }
+    }
+}
+
+/// Whether the bot is still making progress. Counted from startup until its first
+/// successful cycle.
+pub fn liveness(status: &BotStatus, config: &ProbeConfig, now: DateTime<Utc>) -> ProbeReport {
+    let since = status.last_success_at.unwrap_or(status.started_at);
+    let evaluation = if status.paused {
+        Check::pass("paused")
+    } else {
+        age_check(
+            "last successful cycle",
+            since,
+            config.max_evaluation_age,
+            now,
+        )
+    };
+    ProbeReport::new(BTreeMap::from([("evaluation", evaluation)]))
+}
+
+/// Whether the bot is in a state to act on the market. Checks for inputs a bot doesn't
+/// report (e.g. a subscription, for a polling bot) are left out.
+pub fn readiness(status: &BotStatus, config: &ProbeConfig, now: DateTime<Utc>) -> ProbeReport {
+    let mut checks = BTreeMap::new();
+    checks.insert(
+        "evaluation",
+        match status.last_success_at {
+            Some(at) => age_check("last successful cycle", at, config.max_evaluation_age, now),
+            None => Check::fail("no successful cycle yet"),
+        },
+    );
+    if let Some(subscribed) = status.subscribed {
+        checks.insert(
+            "subscription",
+            if subscribed {
+                Check::pass("subscribed")
+            } else {
+                Check::fail("event subscription is down")
+            },
+        );
+    }
+    if let Some(at) = status.price_at {
+        checks.insert(
+            "price_feed",
+            age_check("last price", at, config.max_price_age, now),
+        );
+    }
+    if let Some(feed_status) = status.price_feed_status {
+        let detail = status.price_feed_detail.clone().unwrap_or_default();
+        checks.insert(
+            "price_feed_health",
+            match feed_status {
+                // A degraded feed still has healthy sources to price from.
+                FeedStatus::Healthy | FeedStatus::Degraded => {
+                    Check::pass(format!("{}: {}", feed_status.as_str(), detail))
+                }
+                FeedStatus::Unhealthy => Check::fail(detail),
+            },
+        );
+    }
+    checks.insert(
+        "rpc",
+        match (&status.rpc_checked_at, &status.rpc_error) {
+            (None, _) => Check::fail("not checked yet"),
+            (Some(_), Some(error)) => Check::fail(format!("unreachable: {}", error)),
+            // Checks stop when the checking task does, so an old success counts for nothing.
+            (Some(at), None) => age_check(
+                "last successful check",
+                *at,
+                config.rpc_check_interval * 3,
+                now,
+            ),
+        },
+    );
+    ProbeReport::new(checks)
+}
+
+fn age_check(what: &str, at: DateTime<Utc>, max_age: Duration, now: DateTime<Utc>) -> Check {
+    let age = (now - at).num_seconds().max(0) as u64;
+    if age <= max_age.as_secs() {
+        Check::pass(format!("{} {}s ago", what, age))
+    } else {
+        Check::fail(format!(
+            "{} {}s ago (limit {}s)",
+            what,
+            age,
+            max_age.as_secs()
+        ))
+    }
+}
+
+/// Serve the probes on the configured address, checking `rpc` on the side, until the task
+/// is dropped.
+pub async fn serve(
+    config: ProbeConfig,
+    state: Arc<ControlState>,
+    rpc: RpcClient,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(config.bind_addr).await?;
+    info!(event.name = "probe_server_started", probe.addr = %config.bind_addr);
+    let checker = tokio::spawn(check_rpc(rpc, state.clone(), config.rpc_check_interval));
+    let config = Arc::new(config);
+    let error = loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(error) => break error,
+        };
+        let (state, config) = (state.clone(), config.clone());
+        tokio::spawn(async move {
+            respond(stream, |request| {
+                handle(&state, &config, request.method, request.path)
+            })
+            .await;
+        });
+    };
+    checker.abort();
+    Err(error.into())
+}
+
+async fn check_rpc(rpc: RpcClient, state: Arc<ControlState>, interval: Duration) {
+    loop {
+        let result = rpc.get_slot().await.map(|_| ()).map_err(|error| {
+            warn!(event.name = "probe_rpc_check_failed", ?error);
+            error.to_string()
+        });
+        state.record_rpc_check(result);
+        sleep(interval).await;
+    }
+}
+
+fn handle(state: &ControlState, config: &ProbeConfig, method: &str, path: &str) -> (u16, String) {
+    let probe = match (method, path.trim_end_matches('/')) {
+        ("GET", "/healthz") => liveness,
+        ("GET", "/readyz") => readiness,
+        (_, "/healthz" | "/readyz") => return (405, error_body("method not allowed")),
+        _ => return (404, error_body("unknown probe")),
+    };
+    let report = probe(&state.status(), config, Utc::now());
+    match serde_json::to_string(&report) {
+        Ok(body) => (if report.ok { 200 } else { 503 }, body),
+        Err(error) => (500, error_body(&error.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+
+    fn config() -> ProbeConfig {
+        ProbeConfig {
+            bind_addr: "127.0.0.1:8080".parse().unwrap(),
+            max_evaluation_age: Duration::from_secs(600),
+            max_price_age: Duration::from_secs(60),
+            rpc_check_interval: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn wedged_bot_fails_liveness_unless_paused() {
+        let state = ControlState::new("inventory-flow", 1, Pubkey::new_unique());
+        let mut status = state.status();
+        let later = status.started_at + chrono::Duration::seconds(601);
+
+        assert!(liveness(&status, &config(), status.started_at).ok);
+        assert!(!liveness(&status, &config(), later).ok);
+        status.paused = true;
+        assert!(liveness(&status, &config(), later).ok);
+    }
+
+    #[test]
+    fn readiness_needs_every_reported_input_fresh() {
+        let state = ControlState::new("oracle-flow", 1, Pubkey::new_unique());
+        assert!(!readiness(&state.status(), &config(), Utc::now()).ok);
+
+        state.record_cycle(&Ok::<(), String>(()));
+        state.record_rpc_check(Ok(()));
+        let now = Utc::now();
+        state.record_price(now.timestamp() as u64);
+        let report = readiness(&state.status(), &config(), now);
+        assert!(report.ok);
+        // A polling bot reports no subscription, so it isn't checked.
+        assert!(!report.checks.contains_key("subscription"));
+
+        state.set_subscribed(false);
+        assert!(!readiness(&state.status(), &config(), now).checks["subscription"].ok);
+        state.set_subscribed(true);
+        let stale = now + chrono::Duration::seconds(61);
+        let report = readiness(&state.status(), &config(), stale);
+        assert!(!report.checks["price_feed"].ok);
+        assert!(report.checks["evaluation"].ok);
+
+        state.record_feed_health(FeedStatus::Degraded, "a.example: stale");
+        assert!(readiness(&state.status(), &config(), now).checks["price_feed_health"].ok);
+        state.record_feed_health(FeedStatus::Unhealthy, "no source left");
+        assert!(!readiness(&state.status(), &config(), now).checks["price_feed_health"].ok);
+
+        state.record_rpc_check(Err("connection refused".to_string()));
+        assert!(!readiness(&state.status(), &config(), now).checks["rpc"].ok);
+        assert_eq!(handle(&state, &config(), "GET", "/readyz").0, 503);
+        assert_eq!(handle(&state, &config(), "GET", "/healthz").0, 200);
+        assert_eq!(handle(&state, &config(), "POST", "/healthz").0, 405);
+    }
+}
@@ -0,0 +1,299 @@
+//! Telegram command bot: operators control a running bot from chat.
+//!
+//! The bot long-polls the Bot API for messages and only answers chats listed in
+//! `TELEGRAM_CONTROL_CHAT_IDS`; everyone else is ignored. Supported commands:
+//!
+//! ```text
+//! /status          paused flag, cycles, errors and last error
+//! /pause           skip cycles until resumed
+//! /resume          resume quoting
+//! /stop <market>   zero the position's flows and shut the bot down
+//! /pnl             position value and its change since the bot started
+//! ```
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::config::env_var;
+
+use super::ControlState;
+
+/// How long a `getUpdates` call waits for new messages before returning empty.
+const LONG_POLL_TIMEOUT_SECS: u64 = 25;
+/// Backoff after a failed poll so a Bot API outage doesn't spin.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct TelegramControlConfig {
+    pub bot_token: String,
+    /// Chats allowed to issue commands.
+    pub authorized_chat_ids: Vec<i64>,
+}
+
+impl TelegramControlConfig {
+    /// `None` when `TELEGRAM_CONTROL_BOT_TOKEN` is unset, which disables the command bot.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(bot_token) = env_var("TELEGRAM_CONTROL_BOT_TOKEN")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+        else {
+            return Ok(None);
+        };
+
+        let authorized_chat_ids = env_var("TELEGRAM_CONTROL_CHAT_IDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse::<i64>()
+                    .with_context(|| format!("Invalid Telegram chat id `{value}`"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        anyhow::ensure!(
+            !authorized_chat_ids.is_empty(),
+            "TELEGRAM_CONTROL_CHAT_IDS must list at least one chat when \
+             TELEGRAM_CONTROL_BOT_TOKEN is set"
+        );
+
+        Ok(Some(Self {
+            bot_token,
+            authorized_chat_ids,
+        }))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdatesResponse {
+    ok: bool,
+    #[serde(default)]
+    result: Vec<Update>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/// Poll Telegram for commands and apply them to `state` until the task is dropped.
+pub async fn serve(config: TelegramControlConfig, state: Arc<ControlState>) -> anyhow::Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(LONG_POLL_TIMEOUT_SECS + 10))
+        .build()?;
+    let base_url = format!("https://api.telegram.org/bot{}", config.bot_token);
+    info!(
+        event.name = "telegram_control_started",
+        telegram.authorized_chats = config.authorized_chat_ids.len(),
+    );
+
+    let mut offset = 0_i64;
+    loop {
+        let updates = match poll_updates(&client, &base_url, offset).await {
+            Ok(updates) => updates,
+            Err(error) => {
+                warn!(event.name = "telegram_control_poll_failed", ?error);
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        for update in updates {
+            offset = offset.max(update.update_id + 1);
+            let Some(message) = update.message else {
+                continue;
+            };
+            let Some(text) = message.text else {
+                continue;
+            };
+            let chat_id = message.chat.id;
+            if !config.authorized_chat_ids.contains(&chat_id) {
+                warn!(
+                    event.name = "telegram_control_unauthorized",
+                    telegram.chat_id = chat_id,
+                );
+                continue;
+            }
+            let Some(reply) = handle_command(&state, &text) else {
+                continue;
+            };
+            info!(
+                event.name = "telegram_control_command",
+                telegram.chat_id = chat_id,
+                telegram.command = text.split_whitespace().next().unwrap_or_default(),
+            );
+            if let Err(error) = send_message(&client, &base_url, chat_id, &reply).await {
+                warn!(event.name = "telegram_control_reply_failed", ?error);
+            }
+        }
+    }
+}
+
+async fn poll_updates(
+    client: &reqwest::Client,
+    base_url: &str,
+    offset: i64,
+) -> anyhow::Result<Vec<Update>> {
+    let response: UpdatesResponse = client
+        .get(format!("{base_url}/getUpdates"))
+        .query(&[
+            ("offset", offset.to_string()),
+            ("timeout", LONG_POLL_TIMEOUT_SECS.to_string()),
+            ("allowed_updates", "[\"message\"]".to_string()),
+        ])
+        .send()
+        .await?
+        .json()
+        .await
+        .context("Failed to decode Telegram updates")?;
+    anyhow::ensure!(
+        response.ok,
+        "Telegram rejected getUpdates: {}",
+        response.description.unwrap_or_default()
+    );
+    Ok(response.result)
+}
+
+async fn send_message(
+    client: &reqwest::Client,
+    base_url: &str,
+    chat_id: i64,
+    text: &str,
+) -> anyhow::Result<()> {
+    client
+        .post(format!("{base_url}/sendMessage"))
+        .json(&json!({ "chat_id": chat_id, "text": text }))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Telegram rejected sendMessage")?;
+    Ok(())
+}
+
+/// Apply one chat message to `state` and render the reply. Messages that aren't commands
+/// get no reply.
+fn handle_command(state: &ControlState, text: &str) -> Option<String> {
+    let mut words = text.split_whitespace();
+    let command = words.next()?.strip_prefix('/')?;
+    // Commands in group chats arrive as `/status@SomeBot`.
+    let command = command.split('@').next().unwrap_or(command);
+
+    let reply = match command {
+        "status" => render_status(state),
+        "pause" => {
+            state.pause();
+            format!("Paused.\n\n{}", render_status(state))
+        }
+        "resume" => {
+            state.resume();
+            format!("Resumed.\n\n{}", render_status(state))
+        }
+        "stop" => {
+            let market_id = state.status().market_id;
+            match words.next().map(str::parse::<u64>) {
+                Some(Ok(requested)) if requested == market_id => {
+                    state.request_force_stop();
+                    format!("Force-stop requested for market {market_id}; flows will be zeroed.")
+                }
+                Some(Ok(requested)) => {
+                    format!("This bot manages market {market_id}, not market {requested}.")
+                }
+                _ => format!("Usage: /stop <market>  (this bot manages market {market_id})"),
+            }
+        }
+        "pnl" => render_pnl(state),
+        _ => "Commands: /status, /pause, /resume, /stop <market>, /pnl".to_string(),
+    };
+    Some(reply)
+}
+
+fn render_status(state: &ControlState) -> String {
+    let status = state.status();
+    let mut text = format!(
+        "{} market {}\npaused: {}\nforce stop requested: {}\ncycles: {} ({} failed)",
+        status.bot,
+        status.market_id,
+        status.paused,
+        status.force_stop_requested,
+        status.cycles,
+        status.errors
+    );
+    if let Some(at) = status.last_cycle_at {
+        text.push_str(&format!("\nlast cycle: {}", at.to_rfc3339()));
+    }
+    if let Some(error) = status.last_error {
+        text.push_str(&format!("\nlast error: {error}"));
+    }
+    text
+}
+
+fn render_pnl(state: &ControlState) -> String {
+    let status = state.status();
+    match (status.position_value, status.pnl) {
+        (Some(value), Some(pnl)) => format!(
+            "{} market {}\nposition value: {:.2}\npnl since start: {:+.2}",
+            status.bot, status.market_id, value, pnl
+        ),
+        _ => format!(
+            "{} market {} has not reported a position value yet.",
+            status.bot, status.market_id
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+
+    #[test]
+    fn commands_route_to_the_control_state() {
+        let state = ControlState::new("oracle-flow", 7, Pubkey::new_unique());
+
+        assert!(handle_command(&state, "gm").is_none());
+        assert!(
+            handle_command(&state, "/pause@TwobBot")
+                .unwrap()
+                .contains("paused: true")
+        );
+        assert!(state.is_paused());
+
+        let reply = handle_command(&state, "/stop 8").unwrap();
+        assert!(reply.contains("not market 8"));
+        assert!(!state.force_stop_requested());
+        handle_command(&state, "/stop 7").unwrap();
+        assert!(state.force_stop_requested());
+
+        assert!(
+            handle_command(&state, "/pnl")
+                .unwrap()
+                .contains("not reported")
+        );
+        state.record_position_value(1_000.0);
+        state.record_position_value(1_012.5);
+        assert!(
+            handle_command(&state, "/pnl")
+                .unwrap()
+                .contains("pnl since start: +12.50")
+        );
+    }
+}
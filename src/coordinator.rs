@@ -0,0 +1,285 @@
+//! Cross-market market making from one shared base inventory.
+//!
+//! When the same base token trades against several quote tokens (SOL/USDC, SOL/USDT),
+//! treating each market's position as its own silo leaves base idle where nobody trades it.
+//! The [`FillRates`] tracker watches how fast each market's position actually trades, and
+//! [`allocate`] splits the pooled base between markets in proportion: each market gets a
+//! target share of the base, base flow sized from that share, and [`BaseTransfer`]s moving
+//! base from positions holding more than their share to those holding less. Quote tokens
+//! differ per market, so quote flow stays sized from each position's own quote.
+//!
+//! Every market keeps at least [`AllocationConfig::min_weight`] of the base so a market that
+//! hasn't traded yet still gets a chance to.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BTreeMap,
+};
+
+use serde::Serialize;
+
+/// A position's balances, net of debt, at one slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegSnapshot {
+    pub market_id: u64,
+    pub slot: u64,
+    /// The position's `last_update_slot`; it moves whenever the authority touches the
+    /// position, so balance changes across it aren't all trading.
+    pub last_update_slot: u64,
+    pub base: u64,
+    pub quote: u64,
+}
+
+/// Base traded per slot on each market, as an exponentially weighted average.
+#[derive(Debug, Clone)]
+pub struct FillRates {
+    half_life_slots: f64,
+    rates: BTreeMap<u64, f64>,
+    baselines: BTreeMap<u64, LegSnapshot>,
+}
+
+impl FillRates {
+    pub fn new(half_life_slots: u64) -> Self {
+        Self {
+            half_life_slots: half_life_slots.max(1) as f64,
+            rates: BTreeMap::new(),
+            baselines: BTreeMap::new(),
+        }
+    }
+
+    /// Fold the base `snapshot`'s market traded since its baseline into the average, and
+    /// make `snapshot` the new baseline. Changes the authority made in between are skipped.
+    pub fn observe(&mut self, snapshot: LegSnapshot) {
+        let previous = self.baselines.insert(snapshot.market_id, snapshot);
+        let Some(previous) = previous else {
+            return;
+        };
+        if previous.last_update_slot != snapshot.last_update_slot || snapshot.slot <= previous.slot
+        {
+            return;
+        }
+
+        // Trading moves the two sides in opposite directions; anything else is not a fill.
+        let traded = match (
+            snapshot.base.cmp(&previous.base),
+            snapshot.quote.cmp(&previous.quote),
+        ) {
+            (Ordering::Less, Ordering::Greater) => previous.base - snapshot.base,
+            (Ordering::Greater, Ordering::Less) => snapshot.base - previous.base,
+            _ => 0,
+        };
+        let slots = (snapshot.slot - previous.slot) as f64;
+        let sample = traded as f64 / slots;
+        let alpha = 1.0 - 0.5_f64.powf(slots / self.half_life_slots);
+        let rate = self.rates.entry(snapshot.market_id).or_insert(sample);
+        *rate += alpha * (sample - *rate);
+    }
+
+    /// Replace `snapshot`'s market baseline without observing, e.g. right after the
+    /// coordinator itself changed the position.
+    pub fn rebase(&mut self, snapshot: LegSnapshot) {
+        self.baselines.insert(snapshot.market_id, snapshot);
+    }
+
+    /// Base traded per slot; zero for markets with no observation yet.
+    pub fn rate(&self, market_id: u64) -> f64 {
+        self.rates.get(&market_id).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AllocationConfig {
+    /// Flows are sized so a position's balance lasts this many slots, like inventory-flow's
+    /// divisor.
+    pub flow_divisor: u64,
+    /// Smallest share of the base any market gets, in `[0, 1 / markets]`.
+    pub min_weight: f64,
+    /// Only move base between positions once one is off its target by more than this share
+    /// of the total base.
+    pub rebalance_threshold_bps: u64,
+}
+
+/// One market's share of the inventory and the flows it should quote.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Allocation {
+    pub market_id: u64,
+    pub weight: f64,
+    pub target_base: u64,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+}
+
+/// Move `amount` raw base out of one market's position into another's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BaseTransfer {
+    pub from_market_id: u64,
+    pub to_market_id: u64,
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Plan {
+    pub allocations: Vec<Allocation>,
+    pub transfers: Vec<BaseTransfer>,
+}
+
+/// Split the base held across `legs` by observed fill rate.
+///
+/// A position's base flow is sized from the smaller of its target share and what it holds
+/// now, so it never sells faster than its own balance allows before a transfer lands.
+pub fn allocate(config: &AllocationConfig, legs: &[LegSnapshot], rates: &FillRates) -> Plan {
+    if legs.is_empty() {
+        return Plan::default();
+    }
+    let divisor = config.flow_divisor.max(1);
+    let total_base: u64 = legs.iter().map(|leg| leg.base).sum();
+    let markets = legs.len() as f64;
+    let min_weight = config.min_weight.clamp(0.0, 1.0 / markets);
+    let total_rate: f64 = legs.iter().map(|leg| rates.rate(leg.market_id)).sum();
+
+    let allocations = legs
+        .iter()
+        .map(|leg| {
+            let share = if total_rate > 0.0 {
+                rates.rate(leg.market_id) / total_rate
+            } else {
+                1.0 / markets
+            };
+            let weight = min_weight + (1.0 - min_weight * markets) * share;
+            let target_base = (total_base as f64 * weight).round() as u64;
+            Allocation {
+                market_id: leg.market_id,
+                weight,
+                target_base,
+                base_flow: target_base.min(leg.base) / divisor,
+                quote_flow: leg.quote / divisor,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let threshold = u128::from(total_base) * u128::from(config.rebalance_threshold_bps) / 10_000;
+    let off_target = legs
+        .iter()
+        .zip(&allocations)
+        .any(|(leg, allocation)| u128::from(leg.base.abs_diff(allocation.target_base)) > threshold);
+    let transfers = if off_target {
+        plan_transfers(legs, &allocations)
+    } else {
+        Vec::new()
+    };
+
+    Plan {
+        allocations,
+        transfers,
+    }
+}
+
+/// Match positions above their target with those below it, largest first.
+fn plan_transfers(legs: &[LegSnapshot], allocations: &[Allocation]) -> Vec<BaseTransfer> {
+    let mut surplus = Vec::new();
+    let mut deficit = Vec::new();
+    for (leg, allocation) in legs.iter().zip(allocations) {
+        if leg.base > allocation.target_base {
+            surplus.push((leg.market_id, leg.base - allocation.target_base));
+        } else if leg.base < allocation.target_base {
+            deficit.push((leg.market_id, allocation.target_base - leg.base));
+        }
+    }
+    surplus.sort_by_key(|&(_, amount)| Reverse(amount));
+    deficit.sort_by_key(|&(_, amount)| Reverse(amount));
+
+    let mut transfers = Vec::new();
+    let mut deficits = deficit.into_iter().peekable();
+    for (from_market_id, mut available) in surplus {
+        while available > 0 {
+            let Some((to_market_id, needed)) = deficits.peek_mut() else {
+                return transfers;
+            };
+            let amount = available.min(*needed);
+            transfers.push(BaseTransfer {
+                from_market_id,
+                to_market_id: *to_market_id,
+                amount,
+            });
+            available -= amount;
+            *needed -= amount;
+            if *needed == 0 {
+                deficits.next();
+            }
+        }
+    }
+    transfers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(market_id: u64, slot: u64, base: u64, quote: u64) -> LegSnapshot {
+        LegSnapshot {
+            market_id,
+            slot,
+            last_update_slot: 0,
+            base,
+            quote,
+        }
+    }
+
+    #[test]
+    fn fill_rates_average_traded_base_and_skip_authority_changes() {
+        let mut rates = FillRates::new(100);
+        rates.observe(leg(1, 0, 10_000, 0));
+        rates.observe(leg(1, 100, 9_000, 500));
+        assert_eq!(rates.rate(1), 10.0);
+
+        // One half-life later, a 30/slot sample moves the average halfway.
+        rates.observe(leg(1, 200, 6_000, 2_000));
+        assert!((rates.rate(1) - 20.0).abs() < 1e-9);
+
+        // Both sides growing isn't trading, so the interval counts as no fills.
+        rates.observe(leg(1, 300, 8_000, 3_000));
+        assert!((rates.rate(1) - 10.0).abs() < 1e-9);
+
+        let mut touched = leg(1, 400, 1_000, 9_000);
+        touched.last_update_slot = 350;
+        rates.observe(touched);
+        assert!((rates.rate(1) - 10.0).abs() < 1e-9);
+        assert_eq!(rates.rate(2), 0.0);
+    }
+
+    #[test]
+    fn allocation_follows_fill_rates_with_a_floor() {
+        let config = AllocationConfig {
+            flow_divisor: 10,
+            min_weight: 0.1,
+            rebalance_threshold_bps: 500,
+        };
+        let mut rates = FillRates::new(100);
+        for (market_id, traded) in [(1, 300), (2, 100)] {
+            rates.observe(leg(market_id, 0, 10_000, 0));
+            rates.observe(leg(market_id, 100, 10_000 - traded, traded));
+        }
+        let legs = [leg(1, 100, 5_000, 1_000), leg(2, 100, 5_000, 2_000)];
+
+        let plan = allocate(&config, &legs, &rates);
+        // Market 1 trades three times as fast: 0.1 + 0.8 * 0.75 = 0.7 of the base.
+        assert_eq!(plan.allocations[0].target_base, 7_000);
+        assert_eq!(plan.allocations[1].target_base, 3_000);
+        // Market 1 can only sell what it holds until the transfer lands.
+        assert_eq!(plan.allocations[0].base_flow, 500);
+        assert_eq!(plan.allocations[1].base_flow, 300);
+        assert_eq!(plan.allocations[1].quote_flow, 200);
+        assert_eq!(
+            plan.transfers,
+            vec![BaseTransfer {
+                from_market_id: 2,
+                to_market_id: 1,
+                amount: 2_000,
+            }]
+        );
+
+        // Within the threshold nothing moves.
+        let legs = [leg(1, 100, 6_700, 0), leg(2, 100, 3_300, 0)];
+        assert!(allocate(&config, &legs, &rates).transfers.is_empty());
+    }
+}
@@ -0,0 +1,563 @@
+//! A liquidity position's balances at a slot, brought forward from its last on-chain
+//! update by the flows in and out of it since.
+
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+use crate::{
+    ARRAY_LENGTH, BOOKKEEPING_PRECISION_FACTOR,
+    twob_anchor::accounts::{Bookkeeping, Exits, LiquidityPosition, Market},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LiquidityPositionBalances {
+    pub base_balance: u64,
+    pub quote_balance: u64,
+    pub base_debt: u64,
+    pub quote_debt: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MathError {
+    /// The slot asked about is before the position's last update, e.g. from a lagging
+    /// node.
+    #[error("slot {current_slot} is behind the position's last update at slot {last_update_slot}")]
+    StaleSlot {
+        current_slot: u64,
+        last_update_slot: u64,
+    },
+    #[error("{field} overflowed")]
+    Overflow { field: &'static str },
+}
+
+/// Slots since the position's last update, and how many of them the market traded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct ActiveSlots {
+    pub elapsed: u64,
+    /// Slots without a trade, uncapped: more than `elapsed` means the bookkeeping and the
+    /// position's snapshot of it disagree.
+    pub inactive: u64,
+    pub active: u64,
+}
+
+pub fn active_slots(
+    position: &LiquidityPosition,
+    bookkeeping: &Bookkeeping,
+    current_slot: u64,
+) -> Result<ActiveSlots, MathError> {
+    let elapsed =
+        current_slot
+            .checked_sub(position.last_update_slot)
+            .ok_or(MathError::StaleSlot {
+                current_slot,
+                last_update_slot: position.last_update_slot,
+            })?;
+    let inactive = bookkeeping
+        .slots_without_trade
+        .saturating_sub(position.slots_without_trade_snapshot);
+    Ok(ActiveSlots {
+        elapsed,
+        inactive,
+        active: elapsed.saturating_sub(inactive),
+    })
+}
+
+/// Indexes of the exits accounts [`accumulated_flows`] reads: from the window the
+/// bookkeeping was last updated in to the one holding `current_slot`.
+pub fn exits_window(
+    bookkeeping: &Bookkeeping,
+    market: &Market,
+    current_slot: u64,
+) -> RangeInclusive<u64> {
+    let last_update_index = bookkeeping.last_update_slot / ARRAY_LENGTH / market.end_slot_interval;
+    let current_slot_index = current_slot / ARRAY_LENGTH / market.end_slot_interval;
+    last_update_index..=current_slot_index
+}
+
+/// What has flowed out of and into a position since its last update, scaled by
+/// [`BOOKKEEPING_PRECISION_FACTOR`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct PositionFlows {
+    pub base_outflow: u128,
+    pub base_inflow: u128,
+    pub quote_outflow: u128,
+    pub quote_inflow: u128,
+}
+
+/// The position's flows up to `current_slot`. `exits` holds the exits accounts of
+/// [`exits_window`] by index; a window missing from it counts as having no exits.
+pub fn accumulated_flows(
+    position: &LiquidityPosition,
+    bookkeeping: &Bookkeeping,
+    market: &Market,
+    exits: &BTreeMap<u64, Exits>,
+    current_slot: u64,
+) -> Result<PositionFlows, MathError> {
+    let active_slots = active_slots(position, bookkeeping, current_slot)?.active;
+
+    // Base token outflow since last update slot
+    let base_outflow = BOOKKEEPING_PRECISION_FACTOR
+        .checked_mul(active_slots as u128 * position.base_flow_u64 as u128)
+        .ok_or(MathError::Overflow {
+            field: "base_outflow",
+        })?;
+
+    // Quote token outflow since last update slot
+    let quote_outflow = BOOKKEEPING_PRECISION_FACTOR
+        .checked_mul(active_slots as u128 * position.quote_flow_u64 as u128)
+        .ok_or(MathError::Overflow {
+            field: "quote_outflow",
+        })?;
+
+    let base_per_quote = cumulative_price(
+        bookkeeping.base_per_quote,
+        Side::Base,
+        bookkeeping,
+        market,
+        exits,
+        current_slot,
+    )?;
+    let quote_per_base = cumulative_price(
+        bookkeeping.quote_per_base,
+        Side::Quote,
+        bookkeeping,
+        market,
+        exits,
+        current_slot,
+    )?;
+
+    // Base token inflow since last update slot
+    let base_inflow = base_per_quote
+        .checked_sub(position.base_per_quote_snapshot)
+        .and_then(|price| price.checked_mul(position.quote_flow_u64 as u128))
+        .ok_or(MathError::Overflow {
+            field: "base_inflow",
+        })?;
+
+    // Quote token inflow since last update slot
+    let quote_inflow = quote_per_base
+        .checked_sub(position.quote_per_base_snapshot)
+        .and_then(|price| price.checked_mul(position.base_flow_u64 as u128))
+        .ok_or(MathError::Overflow {
+            field: "quote_inflow",
+        })?;
+
+    Ok(PositionFlows {
+        base_outflow,
+        base_inflow,
+        quote_outflow,
+        quote_inflow,
+    })
+}
+
+/// The position's balances once `flows` are settled against what it held at its last
+/// update. A side that has flowed out more than it held and took in shows as debt.
+pub fn balances_from_flows(
+    position: &LiquidityPosition,
+    flows: &PositionFlows,
+) -> Result<LiquidityPositionBalances, MathError> {
+    let (base_balance, base_debt) = settle(
+        position.base_balance,
+        flows.base_inflow,
+        flows.base_outflow,
+        "base_balance",
+    )?;
+    let (quote_balance, quote_debt) = settle(
+        position.quote_balance,
+        flows.quote_inflow,
+        flows.quote_outflow,
+        "quote_balance",
+    )?;
+
+    let to_u64 = |amount: u128, field: &'static str| {
+        u64::try_from(amount).map_err(|_| MathError::Overflow { field })
+    };
+    Ok(LiquidityPositionBalances {
+        base_balance: to_u64(base_balance, "base_balance")?,
+        quote_balance: to_u64(quote_balance, "quote_balance")?,
+        base_debt: to_u64(base_debt, "base_debt")?,
+        quote_debt: to_u64(quote_debt, "quote_debt")?,
+    })
+}
+
+/// What `held` plus `inflow` leaves after `outflow`, as a balance and a debt, in raw units.
+/// At most one of them is nonzero.
+fn settle(
+    held: u128,
+    inflow: u128,
+    outflow: u128,
+    field: &'static str,
+) -> Result<(u128, u128), MathError> {
+    let available = held
+        .checked_add(inflow)
+        .ok_or(MathError::Overflow { field })?;
+    Ok(match available.checked_sub(outflow) {
+        Some(left) => (left / BOOKKEEPING_PRECISION_FACTOR, 0),
+        None => (0, (outflow - available) / BOOKKEEPING_PRECISION_FACTOR),
+    })
+}
+
+/// [`accumulated_flows`] and [`balances_from_flows`] in one.
+pub fn position_balances(
+    position: &LiquidityPosition,
+    bookkeeping: &Bookkeeping,
+    market: &Market,
+    exits: &BTreeMap<u64, Exits>,
+    current_slot: u64,
+) -> Result<LiquidityPositionBalances, MathError> {
+    let flows = accumulated_flows(position, bookkeeping, market, exits, current_slot)?;
+    balances_from_flows(position, &flows)
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Base,
+    Quote,
+}
+
+/// The bookkeeping's cumulative base per quote (`Side::Base`) or quote per base price
+/// brought forward from its last update to `current_slot`.
+///
+/// The bookkeeping only has data up to its last update, so this walks the exits accounts
+/// from there, taking the market's flows down by each slot's exits as it goes.
+fn cumulative_price(
+    mut cumulative: u128,
+    side: Side,
+    bookkeeping: &Bookkeeping,
+    market: &Market,
+    exits: &BTreeMap<u64, Exits>,
+    current_slot: u64,
+) -> Result<u128, MathError> {
+    let mut market_base_flow = market.base_flow;
+    let mut market_quote_flow = market.quote_flow;
+    let mut last_update_slot = bookkeeping.last_update_slot;
+    let window = exits_window(bookkeeping, market, current_slot);
+    let (last_update_index, current_slot_index) = (*window.start(), *window.end());
+    let field = match side {
+        Side::Base => "base_per_quote",
+        Side::Quote => "quote_per_base",
+    };
+    // The cumulative price after `slot_diff` slots at the price of `base_flow` and
+    // `quote_flow`.
+    let advance = |cumulative: u128, base_flow: u128, quote_flow: u128, slot_diff: u64| {
+        let (numerator, denominator) = match side {
+            Side::Base => (base_flow, quote_flow),
+            Side::Quote => (quote_flow, base_flow),
+        };
+        BOOKKEEPING_PRECISION_FACTOR
+            .checked_mul(numerator)
+            .and_then(|scaled| (scaled / denominator).checked_mul(slot_diff as u128))
+            .and_then(|amount| cumulative.checked_add(amount))
+            .ok_or(MathError::Overflow { field })
+    };
+
+    // This sums up prices to the last slot of the last exits account, then from there to
+    // the current slot.
+    for exits_index in window {
+        let exits_account = exits.get(&exits_index);
+
+        let start_index = if exits_index == last_update_index {
+            (bookkeeping.last_update_slot
+                - last_update_index * market.end_slot_interval * ARRAY_LENGTH)
+                / market.end_slot_interval
+                + 1
+        } else {
+            0
+        };
+
+        let end_index = if exits_index == current_slot_index {
+            (current_slot - current_slot_index * market.end_slot_interval * ARRAY_LENGTH)
+                / market.end_slot_interval
+        } else {
+            ARRAY_LENGTH - 1
+        };
+
+        for i in start_index..=end_index {
+            let slot = i * market.end_slot_interval
+                + exits_index * market.end_slot_interval * ARRAY_LENGTH;
+            let slot_diff = slot - last_update_slot;
+            last_update_slot = slot;
+
+            if market_base_flow == 0 || market_quote_flow == 0 {
+                continue;
+            }
+            cumulative = advance(cumulative, market_base_flow, market_quote_flow, slot_diff)?;
+
+            let base_exit = exits_account.map_or(0, |exits| exits.base_exits[i as usize]);
+            let quote_exit = exits_account.map_or(0, |exits| exits.quote_exits[i as usize]);
+            market_base_flow =
+                market_base_flow
+                    .checked_sub(base_exit)
+                    .ok_or(MathError::Overflow {
+                        field: "market.base_flow",
+                    })?;
+            market_quote_flow =
+                market_quote_flow
+                    .checked_sub(quote_exit)
+                    .ok_or(MathError::Overflow {
+                        field: "market.quote_flow",
+                    })?;
+        }
+
+        if exits_index == current_slot_index {
+            // Nothing to add when the bookkeeping is already past `current_slot`, as read
+            // from a node behind the one that served it.
+            let slot_diff = current_slot.saturating_sub(last_update_slot);
+            if market_base_flow == 0 || market_quote_flow == 0 {
+                continue;
+            }
+            cumulative = advance(cumulative, market_base_flow, market_quote_flow, slot_diff)?;
+        }
+    }
+    Ok(cumulative)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::accounts::test_market;
+
+    const P: u128 = BOOKKEEPING_PRECISION_FACTOR;
+
+    fn market() -> Market {
+        Market {
+            base_flow: 1_000,
+            quote_flow: 2_000,
+            ..test_market()
+        }
+    }
+
+    fn bookkeeping() -> Bookkeeping {
+        Bookkeeping {
+            base_per_quote: 0,
+            previous_base_per_quote: 0,
+            quote_per_base: 0,
+            previous_quote_per_base: 0,
+            slots_without_trade: 0,
+            last_update_slot: 100,
+            previous_update_slot: 0,
+            bump: 255,
+        }
+    }
+
+    fn position() -> LiquidityPosition {
+        LiquidityPosition {
+            authority: Pubkey::new_unique(),
+            base_balance: 120 * P,
+            quote_balance: 100 * P,
+            base_per_quote_snapshot: 0,
+            quote_per_base_snapshot: 0,
+            slots_without_trade_snapshot: 0,
+            base_flow_u64: 3,
+            quote_flow_u64: 2,
+            base_debt: 0,
+            quote_debt: 0,
+            last_update_slot: 100,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn balances_follow_the_market_price_between_updates() {
+        // 50 slots at 1 base per 2 quote: 150 base out for 50 in, 100 quote out for 300 in.
+        let balances = position_balances(
+            &position(),
+            &bookkeeping(),
+            &market(),
+            &BTreeMap::new(),
+            150,
+        )
+        .unwrap();
+        assert_eq!(
+            balances,
+            LiquidityPositionBalances {
+                base_balance: 20,
+                quote_balance: 300,
+                base_debt: 0,
+                quote_debt: 0,
+            }
+        );
+        assert_eq!(exits_window(&bookkeeping(), &market(), 150), 1..=1);
+    }
+
+    #[test]
+    fn exits_change_the_price_from_their_slot() {
+        // Half the base flow leaves at slot 120, so the last 30 slots trade 1 base per 4
+        // quote.
+        let mut base_exits = [0; 10];
+        base_exits[2] = 500;
+        let exits = BTreeMap::from([(
+            1,
+            Exits {
+                owner: Pubkey::new_unique(),
+                base_exits,
+                quote_exits: [0; 10],
+                open_positions: 0,
+                index: 1,
+                bump: 255,
+            },
+        )]);
+        let flows = accumulated_flows(&position(), &bookkeeping(), &market(), &exits, 150).unwrap();
+        assert_eq!(flows.base_inflow, 35 * P);
+        assert_eq!(flows.quote_inflow, 480 * P);
+    }
+
+    #[test]
+    fn a_slot_before_the_last_update_is_stale() {
+        assert_eq!(
+            active_slots(&position(), &bookkeeping(), 99),
+            Err(MathError::StaleSlot {
+                current_slot: 99,
+                last_update_slot: 100,
+            })
+        );
+    }
+
+    /// Raw amounts scaled by the precision factor, now and then one too large for it.
+    fn scaled_amount() -> impl Strategy<Value = u128> {
+        prop_oneof![
+            4 => any::<u64>().prop_map(|raw| u128::from(raw) * P),
+            1 => any::<u128>(),
+        ]
+    }
+
+    fn market_flow() -> impl Strategy<Value = u128> {
+        prop_oneof![
+            1 => Just(0),
+            4 => any::<u64>().prop_map(u128::from),
+            1 => any::<u128>(),
+        ]
+    }
+
+    fn exits_amounts() -> impl Strategy<Value = [u128; 10]> {
+        prop::array::uniform10(prop_oneof![
+            6 => Just(0_u128),
+            1 => any::<u64>().prop_map(u128::from),
+        ])
+    }
+
+    prop_compose! {
+        /// A market, its bookkeeping and a position on it read some slots after the
+        /// position's last update, with the bookkeeping updated before or after it, and
+        /// exits in a few of the windows in between.
+        fn scenario()(
+            end_slot_interval in 1..=20_u64,
+            market_flows in (market_flow(), market_flow()),
+            position_flows in (any::<u64>(), any::<u64>()),
+            held in (scaled_amount(), scaled_amount()),
+            prices in (0..=10_u128.pow(30), 0..=10_u128.pow(30)),
+            snapshots in (0..=10_u128.pow(30), 0..=10_u128.pow(30)),
+            position_update in 0..1_000_000_u64,
+            bookkeeping_offset in -20_000..20_000_i64,
+            gap in 0..20_000_u64,
+            slots_without_trade in (0..40_000_u64, 0..40_000_u64),
+            exits in prop::collection::vec((0..6_u64, exits_amounts(), exits_amounts()), 0..4),
+        ) -> (LiquidityPosition, Bookkeeping, Market, BTreeMap<u64, Exits>, u64) {
+            let market = Market {
+                base_flow: market_flows.0,
+                quote_flow: market_flows.1,
+                end_slot_interval,
+                ..market()
+            };
+            let bookkeeping = Bookkeeping {
+                base_per_quote: prices.0,
+                quote_per_base: prices.1,
+                slots_without_trade: slots_without_trade.0,
+                last_update_slot: position_update.saturating_add_signed(bookkeeping_offset),
+                ..bookkeeping()
+            };
+            let position = LiquidityPosition {
+                base_balance: held.0,
+                quote_balance: held.1,
+                base_per_quote_snapshot: snapshots.0,
+                quote_per_base_snapshot: snapshots.1,
+                slots_without_trade_snapshot: slots_without_trade.1,
+                base_flow_u64: position_flows.0,
+                quote_flow_u64: position_flows.1,
+                last_update_slot: position_update,
+                ..position()
+            };
+            let first_index = *exits_window(&bookkeeping, &market, 0).start();
+            let exits = exits
+                .into_iter()
+                .map(|(offset, base_exits, quote_exits)| {
+                    let index = first_index + offset;
+                    let exits = Exits {
+                        owner: Pubkey::default(),
+                        base_exits,
+                        quote_exits,
+                        open_positions: 0,
+                        index,
+                        bump: 255,
+                    };
+                    (index, exits)
+                })
+                .collect();
+            (position, bookkeeping, market, exits, position_update + gap)
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn balances_never_hold_both_a_balance_and_a_debt(
+            (position, bookkeeping, market, exits, current_slot) in scenario(),
+        ) {
+            // Overflowing inputs are reported, never panicked on.
+            if let Ok(balances) =
+                position_balances(&position, &bookkeeping, &market, &exits, current_slot)
+            {
+                prop_assert!(balances.base_balance == 0 || balances.base_debt == 0);
+                prop_assert!(balances.quote_balance == 0 || balances.quote_debt == 0);
+            }
+        }
+
+        #[test]
+        fn a_position_without_flows_keeps_its_balances(
+            (mut position, bookkeeping, market, exits, current_slot) in scenario(),
+        ) {
+            position.base_flow_u64 = 0;
+            position.quote_flow_u64 = 0;
+            if let Ok(balances) =
+                position_balances(&position, &bookkeeping, &market, &exits, current_slot)
+            {
+                prop_assert_eq!(u128::from(balances.base_balance), position.base_balance / P);
+                prop_assert_eq!(u128::from(balances.quote_balance), position.quote_balance / P);
+                prop_assert_eq!((balances.base_debt, balances.quote_debt), (0, 0));
+            }
+        }
+
+        #[test]
+        fn flows_only_grow_with_time(
+            (position, bookkeeping, market, exits, current_slot) in scenario(),
+            later in 0..5_000_u64,
+        ) {
+            let now = accumulated_flows(&position, &bookkeeping, &market, &exits, current_slot);
+            let then =
+                accumulated_flows(&position, &bookkeeping, &market, &exits, current_slot + later);
+            if let (Ok(now), Ok(then)) = (now, then) {
+                prop_assert!(then.base_outflow >= now.base_outflow);
+                prop_assert!(then.quote_outflow >= now.quote_outflow);
+                prop_assert!(then.base_inflow >= now.base_inflow);
+                prop_assert!(then.quote_inflow >= now.quote_inflow);
+            }
+        }
+
+        #[test]
+        fn a_slot_before_the_last_update_is_always_stale(
+            (position, bookkeeping, market, exits, _) in scenario(),
+            behind in 1..1_000_u64,
+        ) {
+            let current_slot = position.last_update_slot.saturating_sub(behind);
+            prop_assume!(current_slot < position.last_update_slot);
+            let result =
+                position_balances(&position, &bookkeeping, &market, &exits, current_slot);
+            prop_assert!(
+                matches!(result, Err(MathError::StaleSlot { .. })),
+                "expected a stale slot, got {:?}",
+                result
+            );
+        }
+    }
+}
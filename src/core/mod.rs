@@ -0,0 +1,16 @@
+//! The position, projection and quoting math, on nothing but the program's account structs
+//! and integer arithmetic: no RPC client, no async runtime, no clock. The RPC-backed
+//! wrappers elsewhere in the crate fetch the accounts and hand them to it, and a wasm
+//! build, a fuzzer or a simulator can do the same with accounts from anywhere.
+//!
+//! Built with the `core` feature, which `client` turns on.
+
+pub mod balances;
+pub mod quote;
+pub mod runway;
+pub mod trade_fill;
+
+pub use balances::*;
+pub use quote::*;
+pub use runway::*;
+pub use trade_fill::*;
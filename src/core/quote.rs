@@ -1,98 +1,14 @@
-use tracing::{info, warn};
-use twob_market_making::FLOW_PRECISION;
-use twob_market_making::{
-    LiquidityPositionBalances, MarketState, twob_anchor::accounts::LiquidityPosition,
-};
+//! Flows that quote a target price out of a position's balances, and when a quote has
+//! drifted far enough from its target to be worth replacing.
 
-use crate::price::PriceData;
+use crate::core::LiquidityPositionBalances;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct OptimalQuote {
     pub base_flow: u64,
     pub quote_flow: u64,
 }
 
-/// Calculate the optimal quote based on oracle price and inventory-implied price.
-pub fn calculate_optimal_quote(
-    price: &PriceData,
-    position: &LiquidityPosition,
-    market_state: &MarketState,
-    balances: &LiquidityPositionBalances,
-    base_token_decimals: u8,
-    quote_token_decimals: u8,
-    weight: f64,
-) -> OptimalQuote {
-    let fallback = OptimalQuote {
-        base_flow: position.base_flow_u64.max(1),
-        quote_flow: position.quote_flow_u64.max(1),
-    };
-
-    let oracle_price = price.price;
-    if !oracle_price.is_finite() || oracle_price <= 0.0 {
-        warn!(
-            event.name = "quote_compute_fallback",
-            quote.reason = "invalid_oracle_price",
-            price.oracle = oracle_price,
-        );
-        return fallback;
-    }
-
-    let Some(inventory_price) =
-        liquidity_position_price(balances, base_token_decimals, quote_token_decimals)
-    else {
-        warn!(
-            event.name = "quote_compute_fallback",
-            quote.reason = "liquidity_position_price_unavailable",
-            position.base_balance.raw = balances.base_balance,
-            position.quote_balance.raw = balances.quote_balance,
-        );
-        return fallback;
-    };
-
-    let _market_price = market_price_excluding_position(
-        position,
-        market_state,
-        base_token_decimals,
-        quote_token_decimals,
-    );
-
-    let normalized_weight = sanitize_weight(weight);
-    // Weighted blend between oracle and inventory-implied price.
-    let target_quote_price =
-        (oracle_price + normalized_weight * inventory_price) / (1.0 + normalized_weight);
-
-    let Some(target_flows) = compute_target_flows(
-        balances,
-        target_quote_price,
-        inventory_price,
-        base_token_decimals,
-        quote_token_decimals,
-    ) else {
-        warn!(
-            event.name = "quote_compute_fallback",
-            quote.reason = "target_flow_compute_failed",
-            position.base_balance.raw = balances.base_balance,
-            position.quote_balance.raw = balances.quote_balance,
-            quote.target_price = target_quote_price,
-        );
-        return fallback;
-    };
-
-    info!(
-        event.name = "quote_computed",
-        price.oracle = oracle_price,
-        price.inventory = inventory_price,
-        quote.weight = normalized_weight,
-        quote.target_price = target_quote_price,
-        quote.target_base_flow = target_flows.base_flow,
-        quote.target_quote_flow = target_flows.quote_flow,
-        quote.previous_base_flow = position.base_flow_u64,
-        quote.previous_quote_flow = position.quote_flow_u64,
-    );
-
-    target_flows
-}
-
 /// Check if the current quote deviates from optimal by more than the threshold.
 ///
 /// Returns true if an update is needed.
@@ -116,7 +32,12 @@ pub fn should_update_quote(
     base_deviation_bps > threshold_bps as u128 || quote_deviation_bps > threshold_bps as u128
 }
 
-fn sanitize_weight(weight: f64) -> f64 {
+/// Weighted blend between oracle and inventory-implied price.
+pub fn blended_quote_price(oracle_price: f64, inventory_price: f64, weight: f64) -> f64 {
+    (oracle_price + weight * inventory_price) / (1.0 + weight)
+}
+
+pub fn sanitize_weight(weight: f64) -> f64 {
     if weight.is_finite() && weight >= 0.0 {
         weight
     } else {
@@ -138,7 +59,7 @@ fn flow_deviation_bps(current: u64, target: u64) -> u128 {
     (larger - smaller) * 10_000 / target as u128
 }
 
-fn liquidity_position_price(
+pub fn liquidity_position_price(
     balances: &LiquidityPositionBalances,
     base_token_decimals: u8,
     quote_token_decimals: u8,
@@ -153,31 +74,23 @@ fn liquidity_position_price(
         return None;
     }
 
-    Some(quote_ui / base_ui)
+    let price = quote_ui / base_ui;
+    (price.is_finite() && price > 0.0).then_some(price)
 }
 
-fn market_price_excluding_position(
-    position: &LiquidityPosition,
-    market_state: &MarketState,
+/// Quote per base in UI units at which a pair of flows clears, e.g. the market's
+/// `base_flow`/`quote_flow`. `None` when either side is not flowing.
+pub fn flow_price(
+    base_flow: u128,
+    quote_flow: u128,
     base_token_decimals: u8,
     quote_token_decimals: u8,
 ) -> Option<f64> {
-    let own_base_flow = position.base_flow_u64 as u128 * FLOW_PRECISION;
-    let own_quote_flow = position.quote_flow_u64 as u128 * FLOW_PRECISION;
-
-    if market_state.market.base_flow <= own_base_flow
-        || market_state.market.quote_flow <= own_quote_flow
-    {
+    if base_flow == 0 || quote_flow == 0 {
         return None;
     }
 
-    let market_base_flow = market_state.market.base_flow - own_base_flow;
-    let market_quote_flow = market_state.market.quote_flow - own_quote_flow;
-    if market_base_flow == 0 || market_quote_flow == 0 {
-        return None;
-    }
-
-    let native_ratio = market_quote_flow as f64 / market_base_flow as f64;
+    let native_ratio = quote_flow as f64 / base_flow as f64;
     if !native_ratio.is_finite() || native_ratio <= 0.0 {
         return None;
     }
@@ -187,7 +100,7 @@ fn market_price_excluding_position(
     Some(native_ratio * base_scale / quote_scale)
 }
 
-fn compute_target_flows(
+pub fn compute_target_flows(
     balances: &LiquidityPositionBalances,
     target_quote_price: f64,
     inventory_quote_price: f64,
@@ -292,8 +205,9 @@ fn base_flow_for_price(
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
-    use twob_market_making::LiquidityPositionBalances;
 
     #[test]
     fn weighted_quote_price_is_oracle_dominant_with_small_weight() {
@@ -301,7 +215,7 @@ mod tests {
         let lp = 80.0;
         let weight = sanitize_weight(0.1);
 
-        let blended = (oracle + weight * lp) / (1.0 + weight);
+        let blended = blended_quote_price(oracle, lp, weight);
         assert!((blended - 98.1818181818).abs() < 1e-9);
     }
 
@@ -412,4 +326,102 @@ mod tests {
             20
         ));
     }
+
+    fn balance() -> impl Strategy<Value = u64> {
+        prop_oneof![Just(0), 1..=1_000_u64, any::<u64>(), Just(u64::MAX),]
+    }
+
+    /// Prices from dust to absurd, plus zero, negative and non-finite ones.
+    fn price() -> impl Strategy<Value = f64> {
+        prop_oneof![
+            4 => 1e-12..1e12_f64,
+            1 => any::<f64>(),
+            1 => prop_oneof![
+                Just(0.0),
+                Just(f64::NAN),
+                Just(f64::INFINITY),
+                Just(f64::MIN_POSITIVE),
+                Just(f64::MAX),
+            ],
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn target_flows_fit_the_balances_and_anchor_one_side(
+            base_balance in balance(),
+            quote_balance in balance(),
+            target in price(),
+            inventory in price(),
+            base_decimals in any::<u8>(),
+            quote_decimals in any::<u8>(),
+        ) {
+            let balances = LiquidityPositionBalances {
+                base_balance,
+                quote_balance,
+                base_debt: 0,
+                quote_debt: 0,
+            };
+            let Some(optimal) =
+                compute_target_flows(&balances, target, inventory, base_decimals, quote_decimals)
+            else {
+                return Ok(());
+            };
+            prop_assert!((1..=base_balance).contains(&optimal.base_flow));
+            prop_assert!((1..=quote_balance).contains(&optimal.quote_flow));
+            if target >= inventory {
+                prop_assert_eq!(optimal.quote_flow, quote_balance);
+            } else {
+                prop_assert_eq!(optimal.base_flow, base_balance);
+            }
+            // One unit of base flow off the optimum deviates by `10_000 / base_flow` bps,
+            // rounded down: it updates under any tighter threshold and holds at that one.
+            let step_bps = 10_000 / optimal.base_flow;
+            let off_by_one = optimal.base_flow - 1;
+            prop_assert!(!should_update_quote(off_by_one, optimal.quote_flow, &optimal, step_bps));
+            if step_bps > 0 {
+                prop_assert!(should_update_quote(
+                    off_by_one,
+                    optimal.quote_flow,
+                    &optimal,
+                    step_bps - 1
+                ));
+            }
+        }
+
+        #[test]
+        fn a_looser_threshold_never_updates_more(
+            current in (balance(), balance()),
+            optimal in (balance(), balance()),
+            thresholds in (any::<u64>(), any::<u64>()),
+        ) {
+            let optimal = OptimalQuote {
+                base_flow: optimal.0,
+                quote_flow: optimal.1,
+            };
+            let (tight, loose) = (thresholds.0.min(thresholds.1), thresholds.0.max(thresholds.1));
+            if should_update_quote(current.0, current.1, &optimal, loose) {
+                prop_assert!(should_update_quote(current.0, current.1, &optimal, tight));
+            }
+        }
+
+        #[test]
+        fn position_price_is_positive_and_finite(
+            base_balance in balance(),
+            quote_balance in balance(),
+            base_decimals in any::<u8>(),
+            quote_decimals in any::<u8>(),
+        ) {
+            let balances = LiquidityPositionBalances {
+                base_balance,
+                quote_balance,
+                base_debt: 0,
+                quote_debt: 0,
+            };
+            if let Some(price) = liquidity_position_price(&balances, base_decimals, quote_decimals)
+            {
+                prop_assert!(price.is_finite() && price > 0.0);
+            }
+        }
+    }
 }
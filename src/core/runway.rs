@@ -0,0 +1,131 @@
+//! How long a position can keep flowing at the market's current rates before it runs into
+//! debt.
+
+use crate::{
+    core::LiquidityPositionBalances,
+    twob_anchor::accounts::{LiquidityPosition, Market},
+};
+
+/// Net outflow per slot on each side at the current market flows: what the position sells
+/// minus what its flow on the other side buys back. At most one side is non-zero.
+///
+/// `None` when the market has no flow on one side so no inflow rate can be derived.
+pub fn net_outflows(position: &LiquidityPosition, market: &Market) -> Option<(u128, u128)> {
+    if market.base_flow == 0 || market.quote_flow == 0 {
+        return None;
+    }
+
+    let base_outflow = u128::from(position.base_flow_u64);
+    let quote_outflow = u128::from(position.quote_flow_u64);
+    let base_inflow = quote_outflow * market.base_flow / market.quote_flow;
+    let quote_inflow = base_outflow * market.quote_flow / market.base_flow;
+    Some((
+        base_outflow.saturating_sub(base_inflow),
+        quote_outflow.saturating_sub(quote_inflow),
+    ))
+}
+
+/// Slots until the position starts accruing debt at the current market flows: its net
+/// outflow on the side it is draining, divided into what is left on that side.
+///
+/// `None` when the position is not draining either side, or when the market has no flow on
+/// one side so no inflow rate can be derived.
+pub fn slots_until_debt(
+    position: &LiquidityPosition,
+    market: &Market,
+    balances: &LiquidityPositionBalances,
+) -> Option<u64> {
+    let (base, quote) = slots_until_debt_per_side(position, market, balances);
+    base.or(quote)
+}
+
+/// [`slots_until_debt`] for the base and the quote side; `None` for a side the position
+/// isn't draining, or for both when the market has no flow on one side.
+pub fn slots_until_debt_per_side(
+    position: &LiquidityPosition,
+    market: &Market,
+    balances: &LiquidityPositionBalances,
+) -> (Option<u64>, Option<u64>) {
+    let Some((base_net, quote_net)) = net_outflows(position, market) else {
+        return (None, None);
+    };
+    let runway = |balance: u64, net: u128| {
+        (net > 0).then(|| u64::try_from(u128::from(balance) / net).unwrap_or(u64::MAX))
+    };
+    (
+        runway(balances.base_balance, base_net),
+        runway(balances.quote_balance, quote_net),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+    use crate::accounts::test_market;
+
+    fn market(base_flow: u128, quote_flow: u128) -> Market {
+        Market {
+            base_flow,
+            quote_flow,
+            ..test_market()
+        }
+    }
+
+    fn position(base_flow: u64, quote_flow: u64) -> LiquidityPosition {
+        LiquidityPosition {
+            authority: Pubkey::new_unique(),
+            base_balance: 0,
+            quote_balance: 0,
+            base_per_quote_snapshot: 0,
+            quote_per_base_snapshot: 0,
+            slots_without_trade_snapshot: 0,
+            base_flow_u64: base_flow,
+            quote_flow_u64: quote_flow,
+            base_debt: 0,
+            quote_debt: 0,
+            last_update_slot: 0,
+            bump: 255,
+        }
+    }
+
+    const BALANCES: LiquidityPositionBalances = LiquidityPositionBalances {
+        base_balance: 1_000,
+        quote_balance: 50_000,
+        base_debt: 0,
+        quote_debt: 0,
+    };
+
+    #[test]
+    fn runway_on_the_draining_side() {
+        // Market trades 1 base for 100 quote. Selling 10 base/slot while buying with 500
+        // quote/slot nets 5 base out per slot.
+        let runway = slots_until_debt(&position(10, 500), &market(1_000, 100_000), &BALANCES);
+        assert_eq!(runway, Some(200));
+
+        // Buying with 2_000 quote/slot against 10 base/slot nets 1_000 quote out per slot.
+        let runway = slots_until_debt(&position(10, 2_000), &market(1_000, 100_000), &BALANCES);
+        assert_eq!(runway, Some(50));
+        assert_eq!(
+            slots_until_debt_per_side(&position(10, 2_000), &market(1_000, 100_000), &BALANCES),
+            (None, Some(50))
+        );
+    }
+
+    #[test]
+    fn no_runway_when_balanced_or_market_is_one_sided() {
+        assert_eq!(
+            slots_until_debt(&position(10, 1_000), &market(1_000, 100_000), &BALANCES),
+            None
+        );
+        assert_eq!(
+            slots_until_debt(&position(10, 500), &market(0, 100_000), &BALANCES),
+            None
+        );
+        assert_eq!(
+            net_outflows(&position(10, 2_000), &market(1_000, 100_000)),
+            Some((0, 1_000))
+        );
+    }
+}
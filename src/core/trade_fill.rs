@@ -0,0 +1,94 @@
+use crate::{
+    BOOKKEEPING_PRECISION_FACTOR,
+    twob_anchor::accounts::{Bookkeeping, TradePosition},
+};
+
+/// How far a trade position has traded, in raw units: `spent` of the token it deposited
+/// and `received` of the other one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct TradeFill {
+    pub spent: u64,
+    pub received: u64,
+}
+
+impl TradeFill {
+    pub fn unspent(&self, position: &TradePosition) -> u64 {
+        position.amount.saturating_sub(self.spent)
+    }
+}
+
+/// Estimate a trade position's fill from the bookkeeping snapshots. Only as fresh as the
+/// bookkeeping's last update.
+pub fn estimated_trade_fill(position: &TradePosition, bookkeeping: &Bookkeeping) -> TradeFill {
+    let duration = position.end_slot.saturating_sub(position.start_slot);
+    if duration == 0 {
+        return TradeFill::default();
+    }
+    let flow = u128::from(position.amount) / u128::from(duration);
+
+    let elapsed = bookkeeping
+        .last_update_slot
+        .min(position.end_slot)
+        .saturating_sub(position.start_slot);
+    let inactive = bookkeeping
+        .slots_without_trade
+        .saturating_sub(position.slots_without_trades_snapshot);
+    let active = u128::from(elapsed.saturating_sub(inactive));
+
+    // Buys accrue base per quote spent, sells quote per base.
+    let cumulative = if position.is_buy == 1 {
+        bookkeeping.base_per_quote
+    } else {
+        bookkeeping.quote_per_base
+    };
+    let received = cumulative
+        .saturating_sub(position.bookkeeping_snapshot)
+        .saturating_mul(flow)
+        / BOOKKEEPING_PRECISION_FACTOR;
+
+    TradeFill {
+        spent: (flow * active).min(u128::from(position.amount)) as u64,
+        received: received as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_estimate_follows_bookkeeping() {
+        let position = TradePosition {
+            authority: Default::default(),
+            id: 1,
+            amount: 1_000,
+            start_slot: 100,
+            end_slot: 200,
+            bookkeeping_snapshot: 5 * BOOKKEEPING_PRECISION_FACTOR,
+            slots_without_trades_snapshot: 3,
+            is_buy: 0,
+            bump: 0,
+        };
+        let bookkeeping = Bookkeeping {
+            base_per_quote: 0,
+            previous_base_per_quote: 0,
+            // 2 quote per base over the 40 active slots.
+            quote_per_base: (5 + 80) * BOOKKEEPING_PRECISION_FACTOR,
+            previous_quote_per_base: 0,
+            slots_without_trade: 13,
+            last_update_slot: 150,
+            previous_update_slot: 0,
+            bump: 0,
+        };
+
+        let fill = estimated_trade_fill(&position, &bookkeeping);
+        assert_eq!(
+            fill,
+            TradeFill {
+                spent: 400,
+                received: 800
+            }
+        );
+        assert_eq!(fill.unspent(&position), 600);
+    }
+}
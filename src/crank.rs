@@ -0,0 +1,148 @@
+//! A background task keeping a market's bookkeeping fresh.
+//!
+//! The program only settles bookkeeping when an instruction touches the market, so on a
+//! quiet market `last_update_slot` falls further and further behind, and every balance
+//! read walks the exits of each window since (see
+//! [`get_liquidity_position_balances`](crate::get_liquidity_position_balances)). With
+//! `CRANK_MAX_STALENESS_SLOTS` set, [`run`] sends `update_books` whenever the bookkeeping
+//! is that many slots old.
+
+use std::{sync::Arc, time::Duration};
+
+use anchor_client::Client;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    ProgramPayer,
+    config::env_var,
+    execute_update_books, fetch_market_state,
+    twob_anchor::{self, accounts::Bookkeeping},
+    tx::TxSender,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrankConfig {
+    /// Crank once the bookkeeping was last updated this many slots ago.
+    pub max_staleness_slots: u64,
+    pub check_interval: Duration,
+}
+
+impl CrankConfig {
+    /// `None` unless `CRANK_MAX_STALENESS_SLOTS` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let var = |name: &str| {
+            env_var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let Some(max_staleness_slots) = var("CRANK_MAX_STALENESS_SLOTS") else {
+            return Ok(None);
+        };
+        let max_staleness_slots = max_staleness_slots
+            .parse::<u64>()
+            .map_err(|e| anyhow::anyhow!("Invalid CRANK_MAX_STALENESS_SLOTS: {}", e))?;
+        anyhow::ensure!(
+            max_staleness_slots > 0,
+            "CRANK_MAX_STALENESS_SLOTS must be positive"
+        );
+        let check_interval_secs = match var("CRANK_CHECK_INTERVAL_SECS") {
+            Some(value) => value
+                .parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("Invalid CRANK_CHECK_INTERVAL_SECS: {}", e))?,
+            None => 30,
+        };
+        Ok(Some(Self {
+            max_staleness_slots,
+            check_interval: Duration::from_secs(check_interval_secs),
+        }))
+    }
+}
+
+/// Whether `bookkeeping`, read at `current_slot`, is old enough to crank.
+pub fn crank_due(bookkeeping: &Bookkeeping, current_slot: u64, max_staleness_slots: u64) -> bool {
+    current_slot.saturating_sub(bookkeeping.last_update_slot) >= max_staleness_slots
+}
+
+/// Check market `market_id` every interval until the task is dropped, cranking it through
+/// `sender` when it is due.
+pub async fn run(
+    config: CrankConfig,
+    client: Arc<Client<ProgramPayer>>,
+    market_id: u64,
+    sender: Arc<TxSender>,
+) {
+    info!(
+        event.name = "crank_started",
+        market.id = market_id,
+        crank.max_staleness_slots = config.max_staleness_slots,
+    );
+    loop {
+        sleep(config.check_interval).await;
+        let program = match client.program(twob_anchor::ID) {
+            Ok(program) => program,
+            Err(error) => {
+                warn!(event.name = "program_client_failed", ?error);
+                continue;
+            }
+        };
+        let state = match fetch_market_state(&program, market_id).await {
+            Ok(state) => state,
+            Err(error) => {
+                warn!(
+                    event.name = "crank_check_failed",
+                    market.id = market_id,
+                    ?error
+                );
+                continue;
+            }
+        };
+        if !crank_due(
+            &state.bookkeeping,
+            state.current_slot,
+            config.max_staleness_slots,
+        ) {
+            continue;
+        }
+
+        let staleness = state.current_slot - state.bookkeeping.last_update_slot;
+        match execute_update_books(&program, market_id, &sender).await {
+            Ok(()) => info!(
+                event.name = "crank_sent",
+                market.id = market_id,
+                crank.staleness_slots = staleness,
+                monotonic_counter.crank_sent_total = 1_u64,
+            ),
+            Err(error) => warn!(
+                event.name = "crank_failed",
+                market.id = market_id,
+                crank.staleness_slots = staleness,
+                ?error,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn due_once_the_bookkeeping_is_old_enough() {
+        let bookkeeping = Bookkeeping {
+            base_per_quote: 0,
+            previous_base_per_quote: 0,
+            quote_per_base: 0,
+            previous_quote_per_base: 0,
+            slots_without_trade: 0,
+            last_update_slot: 1_000,
+            previous_update_slot: 0,
+            bump: 255,
+        };
+        assert!(!crank_due(&bookkeeping, 1_099, 100));
+        assert!(crank_due(&bookkeeping, 1_100, 100));
+        // A node behind the bookkeeping never cranks.
+        assert!(!crank_due(&bookkeeping, 900, 100));
+    }
+}
@@ -0,0 +1,270 @@
+//! What a bot was working on when it died, written out so the failure can be replayed
+//! offline from the exact inputs.
+//!
+//! Given a bot's [`CrashDump`], [`get_liquidity_position_balances_dumped`] notes the market,
+//! bookkeeping and position accounts and the slot it computes balances from, the exits
+//! accounts it reads on the way and the balances it comes to, and
+//! [`audit_decisions`](crate::strategy::audit_decisions) notes the actions decided on them.
+//! When the bot hands a fatal error to [`write`](CrashDump::write), or on a panic once its
+//! [hook](CrashDump::install_panic_hook) is installed, the latest of each goes to
+//! `<dir>/<bot>-<market id>-<unix time>.json`.
+//!
+//! Accounts are kept Borsh-encoded, in base64, exactly as they were read, next to a
+//! readable rendering. The configuration is identified by a hash of the environment, so a
+//! dump can be matched to the deployment that wrote it without carrying its secrets.
+//!
+//! [`get_liquidity_position_balances_dumped`]: crate::get_liquidity_position_balances_dumped
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use anchor_lang::AnchorSerialize;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+
+use crate::{
+    LiquidityPositionBalances,
+    config::{env_var, env_vars},
+    twob_anchor::accounts::{Bookkeeping, Exits, LiquidityPosition, Market},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashDumpConfig {
+    pub dir: PathBuf,
+}
+
+impl CrashDumpConfig {
+    /// `None` unless `CRASH_DUMP_DIR` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        Ok(env_var("CRASH_DUMP_DIR")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .map(|dir| Self { dir: dir.into() }))
+    }
+}
+
+/// An account as it was read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DumpedAccount {
+    pub borsh_base64: String,
+    pub debug: String,
+}
+
+impl DumpedAccount {
+    fn new<T: AnchorSerialize + fmt::Debug>(account: &T) -> Self {
+        let mut data = Vec::new();
+        // Writing to a Vec can't fail.
+        let _ = account.serialize(&mut data);
+        Self {
+            borsh_base64: STANDARD.encode(data),
+            debug: format!("{account:?}"),
+        }
+    }
+}
+
+/// The latest inputs and decisions noted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DumpState {
+    pub recorded_at: Option<DateTime<Utc>>,
+    pub current_slot: Option<u64>,
+    pub market: Option<DumpedAccount>,
+    pub bookkeeping: Option<DumpedAccount>,
+    pub position: Option<DumpedAccount>,
+    /// Exits accounts read for the balances, by index.
+    pub exits: BTreeMap<u64, DumpedAccount>,
+    pub balances: Option<LiquidityPositionBalances>,
+    pub actions: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct Dump<'a> {
+    bot: &'a str,
+    market_id: u64,
+    reason: &'a str,
+    written_at: DateTime<Utc>,
+    started_at: DateTime<Utc>,
+    config_hash: &'a str,
+    state: &'a DumpState,
+}
+
+/// What one bot instance has noted, for writing out. Each instance keeps its own, so
+/// instances sharing a process dump their own state. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct CrashDump(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    config: CrashDumpConfig,
+    bot: &'static str,
+    market_id: u64,
+    started_at: DateTime<Utc>,
+    config_hash: String,
+    state: Mutex<DumpState>,
+}
+
+impl CrashDump {
+    /// Start noting state for `bot` on `market_id`, under the current settings' hash.
+    pub fn new(config: CrashDumpConfig, bot: &'static str, market_id: u64) -> Self {
+        Self(Arc::new(Inner {
+            config,
+            bot,
+            market_id,
+            started_at: Utc::now(),
+            config_hash: config_hash(env_vars()),
+            state: Mutex::default(),
+        }))
+    }
+
+    /// Also write a dump on any panic in the process, so only for a bot that has the
+    /// process to itself.
+    pub fn install_panic_hook(&self) {
+        let dump = self.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            dump.write(&format!("panic: {info}"));
+            previous(info);
+        }));
+    }
+
+    fn state(&self) -> MutexGuard<'_, DumpState> {
+        self.0
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Note the accounts and slot balances are about to be computed from. Clears
+    /// everything noted from the last computation.
+    pub fn record_inputs(
+        &self,
+        market: &Market,
+        bookkeeping: &Bookkeeping,
+        position: &LiquidityPosition,
+        current_slot: u64,
+    ) {
+        *self.state() = DumpState {
+            recorded_at: Some(Utc::now()),
+            current_slot: Some(current_slot),
+            market: Some(DumpedAccount::new(market)),
+            bookkeeping: Some(DumpedAccount::new(bookkeeping)),
+            position: Some(DumpedAccount::new(position)),
+            ..DumpState::default()
+        };
+    }
+
+    pub fn record_exits(&self, index: u64, exits: &Exits) {
+        self.state().exits.insert(index, DumpedAccount::new(exits));
+    }
+
+    pub fn record_balances(&self, balances: &LiquidityPositionBalances) {
+        self.state().balances = Some(*balances);
+    }
+
+    pub fn record_actions(&self, actions: Vec<serde_json::Value>) {
+        self.state().actions = actions;
+    }
+
+    /// Write what was last noted, with `reason`. Returns the file written.
+    pub fn write(&self, reason: &str) -> Option<PathBuf> {
+        let dump = &self.0;
+        let state = self.state();
+        let written_at = Utc::now();
+        let path = dump.config.dir.join(format!(
+            "{}-{}-{}.json",
+            dump.bot,
+            dump.market_id,
+            written_at.timestamp()
+        ));
+        let body = Dump {
+            bot: dump.bot,
+            market_id: dump.market_id,
+            reason,
+            written_at,
+            started_at: dump.started_at,
+            config_hash: &dump.config_hash,
+            state: &state,
+        };
+        let result = (|| -> anyhow::Result<()> {
+            std::fs::create_dir_all(&dump.config.dir)?;
+            std::fs::write(&path, serde_json::to_vec_pretty(&body)?)?;
+            Ok(())
+        })();
+        match result {
+            Ok(()) => {
+                info!(event.name = "crash_dump_written", crash_dump.path = %path.display());
+                Some(path)
+            }
+            Err(error) => {
+                error!(
+                    event.name = "crash_dump_write_failed",
+                    crash_dump.path = %path.display(),
+                    ?error,
+                );
+                None
+            }
+        }
+    }
+}
+
+/// SHA-256 over `vars` sorted by name, in hex.
+fn config_hash(vars: impl IntoIterator<Item = (String, String)>) -> String {
+    let vars = vars.into_iter().collect::<BTreeMap<_, _>>();
+    let mut hasher = Sha256::new();
+    for (name, value) in &vars {
+        hasher.update(name.as_bytes());
+        hasher.update(b"=");
+        hasher.update(value.as_bytes());
+        hasher.update(b"\n");
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_hash_ignores_order_but_not_values() {
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let hash = config_hash(vars(&[("MARKET_ID", "1"), ("RPC_URL", "http://a")]));
+        assert_eq!(
+            hash,
+            config_hash(vars(&[("RPC_URL", "http://a"), ("MARKET_ID", "1")]))
+        );
+        assert_ne!(
+            hash,
+            config_hash(vars(&[("MARKET_ID", "2"), ("RPC_URL", "http://a")]))
+        );
+        assert_eq!(hash.len(), 64);
+    }
+
+    #[test]
+    fn each_dump_writes_only_what_it_noted() {
+        let dir = std::env::temp_dir().join(format!("twob-crash-dump-{}", std::process::id()));
+        let config = CrashDumpConfig { dir: dir.clone() };
+        let first = CrashDump::new(config.clone(), "oracle-flow", 1);
+        let second = CrashDump::new(config, "inventory-flow", 2);
+        first.record_actions(vec![serde_json::json!({ "action": "stop" })]);
+
+        let path = second.write("test").unwrap();
+        let dump: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(dump["bot"], "inventory-flow");
+        assert_eq!(dump["market_id"], 2);
+        assert_eq!(dump["state"]["actions"], serde_json::json!([]));
+    }
+}
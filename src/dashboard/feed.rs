@@ -0,0 +1,304 @@
+//! Polls the watched positions and publishes a fresh [`DashboardView`] after every round.
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use anchor_client::{Client, Program};
+use anchor_lang::prelude::Pubkey;
+use serde::Serialize;
+use tokio::{sync::watch, time::sleep};
+use tracing::warn;
+
+use crate::{
+    ProgramPayer, fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances,
+    price::{fetch_price, ui_price},
+    slots_until_debt, twob_anchor,
+};
+
+/// Actions kept per market for the "recent actions" panel.
+const MAX_RECENT_ACTIONS: usize = 50;
+
+#[derive(Debug, Clone)]
+pub struct WatchTarget {
+    pub market_id: u64,
+    pub authority: Pubkey,
+    pub price_feed_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionSnapshot {
+    pub market_id: u64,
+    pub authority: String,
+    pub timestamp: i64,
+    pub slot: u64,
+    pub market_paused: bool,
+    /// u128 market flows are rendered as strings so JSON consumers don't lose precision.
+    pub market_base_flow: String,
+    pub market_quote_flow: String,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+    /// Balances accrued up to `slot`, in UI units.
+    pub base_balance: f64,
+    pub quote_balance: f64,
+    pub base_debt: f64,
+    pub quote_debt: f64,
+    /// Slots until the position runs into debt at current flows; `None` when not draining.
+    pub runway_slots: Option<u64>,
+    /// Quote per base implied by the aggregate market flows.
+    pub implied_price: Option<f64>,
+    pub oracle_price: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    FlowsUpdated,
+    DebtDetected,
+    DebtCleared,
+    MarketPaused,
+    MarketResumed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Action {
+    pub timestamp: i64,
+    pub slot: u64,
+    pub kind: ActionKind,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MarketPanel {
+    pub market_id: u64,
+    pub authority: String,
+    pub snapshot: Option<PositionSnapshot>,
+    pub last_error: Option<String>,
+    /// Newest first.
+    pub recent_actions: VecDeque<Action>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DashboardView {
+    pub panels: Vec<MarketPanel>,
+}
+
+/// Actions implied by the change from `previous` to `next`.
+pub fn diff_actions(previous: &PositionSnapshot, next: &PositionSnapshot) -> Vec<Action> {
+    let action = |kind, detail: String| Action {
+        timestamp: next.timestamp,
+        slot: next.slot,
+        kind,
+        detail,
+    };
+    let mut actions = Vec::new();
+
+    if (previous.base_flow, previous.quote_flow) != (next.base_flow, next.quote_flow) {
+        actions.push(action(
+            ActionKind::FlowsUpdated,
+            format!(
+                "base {} -> {}, quote {} -> {}",
+                previous.base_flow, next.base_flow, previous.quote_flow, next.quote_flow
+            ),
+        ));
+    }
+
+    let had_debt = previous.base_debt > 0.0 || previous.quote_debt > 0.0;
+    let has_debt = next.base_debt > 0.0 || next.quote_debt > 0.0;
+    if has_debt && !had_debt {
+        actions.push(action(
+            ActionKind::DebtDetected,
+            format!("base {} quote {}", next.base_debt, next.quote_debt),
+        ));
+    } else if had_debt && !has_debt {
+        actions.push(action(ActionKind::DebtCleared, String::new()));
+    }
+
+    if next.market_paused != previous.market_paused {
+        let kind = if next.market_paused {
+            ActionKind::MarketPaused
+        } else {
+            ActionKind::MarketResumed
+        };
+        actions.push(action(kind, String::new()));
+    }
+
+    actions
+}
+
+/// Refresh every target each `interval` and publish the result on `tx`. Runs until the
+/// receiving side is gone.
+pub async fn run_feed(
+    client: Arc<Client<ProgramPayer>>,
+    targets: Vec<WatchTarget>,
+    interval: Duration,
+    tx: watch::Sender<DashboardView>,
+) -> anyhow::Result<()> {
+    let program = client.program(twob_anchor::ID)?;
+    let http_client = reqwest::Client::new();
+    let mut panels: Vec<MarketPanel> = targets
+        .iter()
+        .map(|target| MarketPanel {
+            market_id: target.market_id,
+            authority: target.authority.to_string(),
+            snapshot: None,
+            last_error: None,
+            recent_actions: VecDeque::new(),
+        })
+        .collect();
+
+    loop {
+        for (target, panel) in targets.iter().zip(panels.iter_mut()) {
+            match take_snapshot(&program, &http_client, target).await {
+                Ok(snapshot) => {
+                    if let Some(previous) = &panel.snapshot {
+                        for action in diff_actions(previous, &snapshot) {
+                            panel.recent_actions.push_front(action);
+                        }
+                        panel.recent_actions.truncate(MAX_RECENT_ACTIONS);
+                    }
+                    panel.snapshot = Some(snapshot);
+                    panel.last_error = None;
+                }
+                Err(error) => {
+                    warn!(
+                        event.name = "dashboard_snapshot_failed",
+                        market.id = target.market_id,
+                        lp.authority = %target.authority,
+                        ?error,
+                    );
+                    panel.last_error = Some(format!("{error:#}"));
+                }
+            }
+        }
+
+        if tx
+            .send(DashboardView {
+                panels: panels.clone(),
+            })
+            .is_err()
+        {
+            return Ok(());
+        }
+        sleep(interval).await;
+    }
+}
+
+async fn take_snapshot(
+    program: &Program<ProgramPayer>,
+    http_client: &reqwest::Client,
+    target: &WatchTarget,
+) -> anyhow::Result<PositionSnapshot> {
+    let market_state = fetch_market_state(program, target.market_id).await?;
+    let position = fetch_liquidity_position(program, target.market_id, &target.authority).await?;
+    let balances = get_liquidity_position_balances(
+        program,
+        position,
+        market_state.bookkeeping,
+        market_state.market,
+        market_state.current_slot,
+    )
+    .await?;
+
+    let rpc = program.rpc();
+    let base_decimals = rpc
+        .get_token_supply(&market_state.market.base_mint)
+        .await?
+        .decimals;
+    let quote_decimals = rpc
+        .get_token_supply(&market_state.market.quote_mint)
+        .await?
+        .decimals;
+    let ui = |raw: u64, decimals: u8| raw as f64 / 10f64.powi(i32::from(decimals));
+
+    let oracle_price = match &target.price_feed_url {
+        Some(url) => fetch_price(http_client, url)
+            .await
+            .map(|price_data| price_data.price)
+            .ok(),
+        None => None,
+    };
+
+    Ok(PositionSnapshot {
+        market_id: target.market_id,
+        authority: target.authority.to_string(),
+        timestamp: chrono::Utc::now().timestamp(),
+        slot: market_state.current_slot,
+        market_paused: market_state.market.is_paused != 0,
+        market_base_flow: market_state.market.base_flow.to_string(),
+        market_quote_flow: market_state.market.quote_flow.to_string(),
+        base_flow: position.base_flow_u64,
+        quote_flow: position.quote_flow_u64,
+        base_balance: ui(balances.base_balance, base_decimals),
+        quote_balance: ui(balances.quote_balance, quote_decimals),
+        base_debt: ui(balances.base_debt, base_decimals),
+        quote_debt: ui(balances.quote_debt, quote_decimals),
+        runway_slots: slots_until_debt(&position, &market_state.market, &balances),
+        implied_price: ui_price(
+            market_state.market.base_flow,
+            market_state.market.quote_flow,
+            base_decimals,
+            quote_decimals,
+        ),
+        oracle_price,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> PositionSnapshot {
+        PositionSnapshot {
+            market_id: 1,
+            authority: "lp".to_string(),
+            timestamp: 100,
+            slot: 1_000,
+            market_paused: false,
+            market_base_flow: "10".to_string(),
+            market_quote_flow: "1000".to_string(),
+            base_flow: 5,
+            quote_flow: 500,
+            base_balance: 1.0,
+            quote_balance: 100.0,
+            base_debt: 0.0,
+            quote_debt: 0.0,
+            runway_slots: None,
+            implied_price: Some(100.0),
+            oracle_price: None,
+        }
+    }
+
+    #[test]
+    fn unchanged_snapshots_produce_no_actions() {
+        assert!(diff_actions(&snapshot(), &snapshot()).is_empty());
+    }
+
+    #[test]
+    fn detects_flow_changes_debt_and_pauses() {
+        let previous = snapshot();
+        let mut next = snapshot();
+        next.slot = 1_100;
+        next.quote_flow = 400;
+        next.quote_debt = 2.5;
+        next.market_paused = true;
+
+        let kinds: Vec<ActionKind> = diff_actions(&previous, &next)
+            .iter()
+            .map(|action| action.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ActionKind::FlowsUpdated,
+                ActionKind::DebtDetected,
+                ActionKind::MarketPaused,
+            ]
+        );
+
+        let kinds: Vec<ActionKind> = diff_actions(&next, &previous)
+            .iter()
+            .map(|action| action.kind)
+            .collect();
+        assert!(kinds.contains(&ActionKind::DebtCleared));
+        assert!(kinds.contains(&ActionKind::MarketResumed));
+    }
+}
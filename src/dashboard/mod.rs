@@ -0,0 +1,140 @@
+//! Live dashboard: current flows, balances, runway, implied vs oracle price and recent
+//! actions for each watched position, pushed to the browser over server-sent events.
+//!
+//! Enabled with the `dashboard` feature. A background [`feed::run_feed`] task refreshes
+//! on-chain state and publishes it on a `watch` channel that every HTTP client reads from.
+
+pub mod feed;
+
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+
+use anchor_client::Client;
+use axum::{
+    Json, Router,
+    extract::State,
+    response::{
+        Html,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::get,
+};
+use futures::{Stream, stream};
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use crate::ProgramPayer;
+
+pub use feed::{DashboardView, WatchTarget};
+
+/// Serve the dashboard on `addr`, refreshing `targets` every `refresh_interval`.
+pub async fn serve(
+    addr: SocketAddr,
+    client: Arc<Client<ProgramPayer>>,
+    targets: Vec<WatchTarget>,
+    refresh_interval: Duration,
+) -> anyhow::Result<()> {
+    let (tx, rx) = watch::channel(DashboardView::default());
+    tokio::spawn(async move {
+        if let Err(error) = feed::run_feed(client, targets, refresh_interval, tx).await {
+            error!(event.name = "dashboard_feed_failed", ?error);
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(event.name = "dashboard_server_started", dashboard.addr = %addr);
+    axum::serve(listener, router(rx)).await?;
+    Ok(())
+}
+
+pub fn router(rx: watch::Receiver<DashboardView>) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/state", get(state))
+        .route("/events", get(events))
+        .with_state(rx)
+}
+
+async fn index() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+async fn state(State(rx): State<watch::Receiver<DashboardView>>) -> Json<DashboardView> {
+    Json(rx.borrow().clone())
+}
+
+/// The current view immediately, then one event per refresh.
+async fn events(
+    State(rx): State<watch::Receiver<DashboardView>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let updates = stream::unfold((rx, true), |(mut rx, first)| async move {
+        if !first {
+            rx.changed().await.ok()?;
+        }
+        let view = rx.borrow_and_update().clone();
+        let event = Event::default()
+            .event("view")
+            .data(serde_json::to_string(&view).unwrap_or_default());
+        Some((Ok(event), (rx, false)))
+    });
+    Sse::new(updates).keep_alive(KeepAlive::default())
+}
+
+const INDEX_HTML: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>twob market making</title>
+<style>
+  body { font-family: ui-monospace, monospace; margin: 2rem; background: #111; color: #ddd; }
+  section { border: 1px solid #333; padding: 1rem; margin-bottom: 1.5rem; }
+  table { border-collapse: collapse; }
+  td { padding: 0.15rem 1rem 0.15rem 0; }
+  .warn { color: #f5a623; }
+  .error { color: #ff5c5c; }
+</style>
+</head>
+<body>
+<h1>twob market making</h1>
+<div id="panels">waiting for data…</div>
+<script>
+const fmt = (value, digits = 6) =>
+  value === null || value === undefined ? "–" : Number(value).toFixed(digits);
+
+function render(view) {
+  document.getElementById("panels").innerHTML = view.panels.map((panel) => {
+    const s = panel.snapshot;
+    if (!s) {
+      return `<section><h2>market ${panel.market_id}</h2><p>${panel.authority}</p>` +
+        `<p class="error">${panel.last_error ?? "no data yet"}</p></section>`;
+    }
+    const debt = s.base_debt > 0 || s.quote_debt > 0;
+    const actions = panel.recent_actions.map((a) =>
+      `<tr><td>${new Date(a.timestamp * 1000).toLocaleTimeString()}</td><td>${a.slot}</td>` +
+      `<td>${a.kind}</td><td>${a.detail}</td></tr>`).join("");
+    return `<section>
+      <h2>market ${s.market_id}${s.market_paused ? " (paused)" : ""}</h2>
+      <p>${s.authority} · slot ${s.slot}</p>
+      ${panel.last_error ? `<p class="error">${panel.last_error}</p>` : ""}
+      <table>
+        <tr><td>flows (base / quote)</td><td>${s.base_flow} / ${s.quote_flow}</td></tr>
+        <tr><td>market flows</td><td>${s.market_base_flow} / ${s.market_quote_flow}</td></tr>
+        <tr><td>balances</td><td>${fmt(s.base_balance)} / ${fmt(s.quote_balance)}</td></tr>
+        <tr class="${debt ? "error" : ""}"><td>debt</td>
+          <td>${fmt(s.base_debt)} / ${fmt(s.quote_debt)}</td></tr>
+        <tr class="${s.runway_slots !== null && s.runway_slots < 1000 ? "warn" : ""}">
+          <td>runway (slots)</td><td>${s.runway_slots ?? "∞"}</td></tr>
+        <tr><td>implied / oracle price</td>
+          <td>${fmt(s.implied_price, 4)} / ${fmt(s.oracle_price, 4)}</td></tr>
+      </table>
+      <h3>recent actions</h3>
+      <table>${actions || "<tr><td>none</td></tr>"}</table>
+    </section>`;
+  }).join("");
+}
+
+const source = new EventSource("events");
+source.addEventListener("view", (event) => render(JSON.parse(event.data)));
+</script>
+</body>
+</html>
+"#;
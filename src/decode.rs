@@ -0,0 +1,363 @@
+//! Decoders for twob instruction data and `emit!`ted events.
+//!
+//! Instructions are matched on their 8-byte Anchor discriminator; events are read from the
+//! `Program data: <base64>` log lines Anchor writes for `emit!`.
+
+use anchor_lang::{AnchorDeserialize, Discriminator, prelude::Pubkey};
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::twob_anchor::{client::args, events};
+
+const DISCRIMINATOR_LEN: usize = 8;
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TwobInstruction {
+    InitializeMarket {
+        id: u64,
+        start_slot: u64,
+        end_slot_interval: u64,
+        fee_bps: u8,
+        unhealthy_liquidity_fee_bps: u8,
+    },
+    ProvideLiquidity {
+        reference_index: u64,
+        base_deposit_lamports: u64,
+        quote_deposit_lamports: u64,
+        base_flow_u64: u64,
+        quote_flow_u64: u64,
+    },
+    AddLiquidity {
+        reference_index: u64,
+        base_lamports: u64,
+        quote_lamports: u64,
+    },
+    WithdrawLiquidity {
+        reference_index: u64,
+        base_lamports: u64,
+        quote_lamports: u64,
+    },
+    UpdateLiquidityFlows {
+        reference_index: u64,
+        base_flow_u64: u64,
+        quote_flow_u64: u64,
+    },
+    PublicStopLiquidityPosition {
+        reference_index: u64,
+    },
+    AuthorityCloseLiquidityPosition {
+        reference_index: u64,
+    },
+    SubmitOrder {
+        id: u64,
+        future_index: u64,
+        reference_index: u64,
+        amount: u64,
+        end_slot: u64,
+    },
+    AuthorityClosePosition {
+        reference_index: u64,
+    },
+    PublicClosePosition {
+        reference_index: u64,
+    },
+    UpdateBooks {
+        reference_index: u64,
+        slot: u64,
+    },
+    /// A twob instruction the decoder recognises but does not unpack.
+    Other {
+        name: &'static str,
+    },
+}
+
+impl TwobInstruction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InitializeMarket { .. } => "initialize_market",
+            Self::ProvideLiquidity { .. } => "provide_liquidity",
+            Self::AddLiquidity { .. } => "add_liquidity",
+            Self::WithdrawLiquidity { .. } => "withdraw_liquidity",
+            Self::UpdateLiquidityFlows { .. } => "update_liquidity_flows",
+            Self::PublicStopLiquidityPosition { .. } => "public_stop_liquidity_position",
+            Self::AuthorityCloseLiquidityPosition { .. } => "authority_close_liquidity_position",
+            Self::SubmitOrder { .. } => "submit_order",
+            Self::AuthorityClosePosition { .. } => "authority_close_position",
+            Self::PublicClosePosition { .. } => "public_close_position",
+            Self::UpdateBooks { .. } => "update_books",
+            Self::Other { name } => name,
+        }
+    }
+}
+
+/// Decode twob instruction data. Returns `None` for unknown or malformed data.
+pub fn decode_instruction(data: &[u8]) -> Option<TwobInstruction> {
+    let (discriminator, payload) = data.split_at_checked(DISCRIMINATOR_LEN)?;
+
+    if discriminator == args::InitializeMarket::DISCRIMINATOR {
+        let a = args::InitializeMarket::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::InitializeMarket {
+            id: a.id,
+            start_slot: a.start_slot,
+            end_slot_interval: a.end_slot_interval,
+            fee_bps: a.fee_bps,
+            unhealthy_liquidity_fee_bps: a.unhealthy_liquidity_fee_bps,
+        });
+    }
+    if discriminator == args::ProvideLiquidity::DISCRIMINATOR {
+        let a = args::ProvideLiquidity::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::ProvideLiquidity {
+            reference_index: a.reference_index,
+            base_deposit_lamports: a.base_deposit_lamports,
+            quote_deposit_lamports: a.quote_deposit_lamports,
+            base_flow_u64: a.base_flow_u64,
+            quote_flow_u64: a.quote_flow_u64,
+        });
+    }
+    if discriminator == args::AddLiquidity::DISCRIMINATOR {
+        let a = args::AddLiquidity::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::AddLiquidity {
+            reference_index: a.reference_index,
+            base_lamports: a.base_lamports,
+            quote_lamports: a.quote_lamports,
+        });
+    }
+    if discriminator == args::WithdrawLiquidity::DISCRIMINATOR {
+        let a = args::WithdrawLiquidity::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::WithdrawLiquidity {
+            reference_index: a.reference_index,
+            base_lamports: a.base_lamports,
+            quote_lamports: a.quote_lamports,
+        });
+    }
+    if discriminator == args::UpdateLiquidityFlows::DISCRIMINATOR {
+        let a = args::UpdateLiquidityFlows::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::UpdateLiquidityFlows {
+            reference_index: a.reference_index,
+            base_flow_u64: a.base_flow_u64,
+            quote_flow_u64: a.quote_flow_u64,
+        });
+    }
+    if discriminator == args::PublicStopLiquidityPosition::DISCRIMINATOR {
+        let a = args::PublicStopLiquidityPosition::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::PublicStopLiquidityPosition {
+            reference_index: a.reference_index,
+        });
+    }
+    if discriminator == args::AuthorityCloseLiquidityPosition::DISCRIMINATOR {
+        let a = args::AuthorityCloseLiquidityPosition::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::AuthorityCloseLiquidityPosition {
+            reference_index: a.reference_index,
+        });
+    }
+    if discriminator == args::SubmitOrder::DISCRIMINATOR {
+        let a = args::SubmitOrder::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::SubmitOrder {
+            id: a.id,
+            future_index: a.future_index,
+            reference_index: a.reference_index,
+            amount: a.amount,
+            end_slot: a.end_slot,
+        });
+    }
+    if discriminator == args::AuthorityClosePosition::DISCRIMINATOR {
+        let a = args::AuthorityClosePosition::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::AuthorityClosePosition {
+            reference_index: a.reference_index,
+        });
+    }
+    if discriminator == args::PublicClosePosition::DISCRIMINATOR {
+        let a = args::PublicClosePosition::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::PublicClosePosition {
+            reference_index: a.reference_index,
+        });
+    }
+    if discriminator == args::UpdateBooks::DISCRIMINATOR {
+        let a = args::UpdateBooks::try_from_slice(payload).ok()?;
+        return Some(TwobInstruction::UpdateBooks {
+            reference_index: a.reference_index,
+            slot: a.slot,
+        });
+    }
+
+    let name = [
+        (
+            args::ClosePricesAccount::DISCRIMINATOR,
+            "close_prices_account",
+        ),
+        (
+            args::CloseExitsAccount::DISCRIMINATOR,
+            "close_exits_account",
+        ),
+        (args::CloseMarket::DISCRIMINATOR, "close_market"),
+        (
+            args::InitializeProgramConfig::DISCRIMINATOR,
+            "initialize_program_config",
+        ),
+        (args::PauseMarket::DISCRIMINATOR, "pause_market"),
+        (
+            args::PublicCompensateDebt::DISCRIMINATOR,
+            "public_compensate_debt",
+        ),
+        (args::UpdateFees::DISCRIMINATOR, "update_fees"),
+        (args::WithdrawFees::DISCRIMINATOR, "withdraw_fees"),
+    ]
+    .into_iter()
+    .find_map(|(known, name)| (discriminator == known).then_some(name))?;
+
+    Some(TwobInstruction::Other { name })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TwobEvent {
+    MarketUpdate {
+        market_id: u64,
+        base_flow: u64,
+        quote_flow: u64,
+    },
+    ClosePosition {
+        position_authority: Pubkey,
+        market_id: u64,
+        start_slot: u64,
+        end_slot: u64,
+        deposit_amount: u64,
+        swapped_amount: u64,
+        remaining_amount: u64,
+        fee_amount: u64,
+        is_buy: bool,
+    },
+}
+
+impl From<events::MarketUpdateEvent> for TwobEvent {
+    fn from(event: events::MarketUpdateEvent) -> Self {
+        Self::MarketUpdate {
+            market_id: event.market_id,
+            base_flow: event.base_flow,
+            quote_flow: event.quote_flow,
+        }
+    }
+}
+
+impl From<events::ClosePositionEvent> for TwobEvent {
+    fn from(event: events::ClosePositionEvent) -> Self {
+        Self::ClosePosition {
+            position_authority: event.position_authority,
+            market_id: event.market_id,
+            start_slot: event.start_slot,
+            end_slot: event.end_slot,
+            deposit_amount: event.deposit_amount,
+            swapped_amount: event.swapped_amount,
+            remaining_amount: event.remaining_amount,
+            fee_amount: event.fee_amount,
+            is_buy: event.is_buy != 0,
+        }
+    }
+}
+
+/// Decode raw event bytes (discriminator followed by the Borsh payload).
+pub fn decode_event(data: &[u8]) -> Option<TwobEvent> {
+    let (discriminator, payload) = data.split_at_checked(DISCRIMINATOR_LEN)?;
+
+    if discriminator == events::MarketUpdateEvent::DISCRIMINATOR {
+        return events::MarketUpdateEvent::try_from_slice(payload)
+            .ok()
+            .map(Into::into);
+    }
+    if discriminator == events::ClosePositionEvent::DISCRIMINATOR {
+        return events::ClosePositionEvent::try_from_slice(payload)
+            .ok()
+            .map(Into::into);
+    }
+
+    None
+}
+
+/// Extract every twob event from a transaction's log messages, in emission order.
+///
+/// Logs are not attributed to a program here; foreign `Program data:` lines simply fail to
+/// match a twob discriminator and are skipped.
+pub fn decode_events_from_logs<S: AsRef<str>>(logs: &[S]) -> Vec<TwobEvent> {
+    logs.iter()
+        .filter_map(|line| line.as_ref().strip_prefix(PROGRAM_DATA_PREFIX))
+        .filter_map(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .filter_map(|bytes| decode_event(&bytes))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{AnchorSerialize, InstructionData};
+
+    #[test]
+    fn decodes_update_liquidity_flows() {
+        let data = args::UpdateLiquidityFlows {
+            reference_index: 3,
+            base_flow_u64: 10,
+            quote_flow_u64: 20,
+        }
+        .data();
+
+        assert_eq!(
+            decode_instruction(&data),
+            Some(TwobInstruction::UpdateLiquidityFlows {
+                reference_index: 3,
+                base_flow_u64: 10,
+                quote_flow_u64: 20,
+            })
+        );
+    }
+
+    #[test]
+    fn names_known_instructions_without_unpacking_them() {
+        let data = args::PauseMarket { reference_index: 1 }.data();
+        assert_eq!(
+            decode_instruction(&data).map(|ix| ix.name()),
+            Some("pause_market")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_data() {
+        assert_eq!(decode_instruction(&[0; 16]), None);
+        assert_eq!(decode_instruction(&[1, 2, 3]), None);
+
+        let mut data = args::SubmitOrder {
+            id: 1,
+            future_index: 2,
+            reference_index: 3,
+            amount: 4,
+            end_slot: 5,
+        }
+        .data();
+        data.truncate(12);
+        assert_eq!(decode_instruction(&data), None);
+    }
+
+    #[test]
+    fn decodes_events_from_program_data_logs() {
+        let mut bytes = events::MarketUpdateEvent::DISCRIMINATOR.to_vec();
+        events::MarketUpdateEvent {
+            market_id: 1,
+            base_flow: 2,
+            quote_flow: 3,
+        }
+        .serialize(&mut bytes)
+        .unwrap();
+
+        let logs = vec![
+            "Program log: Instruction: UpdateLiquidityFlows".to_string(),
+            format!("Program data: {}", STANDARD.encode(&bytes)),
+            "Program data: bm90IGFuIGV2ZW50".to_string(),
+        ];
+
+        assert_eq!(
+            decode_events_from_logs(&logs),
+            vec![TwobEvent::MarketUpdate {
+                market_id: 1,
+                base_flow: 2,
+                quote_flow: 3,
+            }]
+        );
+    }
+}
@@ -0,0 +1,444 @@
+//! The library's error type.
+//!
+//! [`TwobError`] sorts failures by what a caller should do about them: an
+//! [`RpcError`](TwobError::RpcError) or [`StaleData`](TwobError::StaleData) is worth
+//! retrying, while a [`MathOverflow`](TwobError::MathOverflow) or an on-chain
+//! [`ProgramError`](TwobError::ProgramError) will fail the same way again and should stop
+//! the bot instead. [`is_retryable`](TwobError::is_retryable) makes that call.
+//!
+//! Sends still fail with the [`tx`](crate::tx) module's own errors, e.g.
+//! [`TxExpired`](crate::tx::TxExpired), [`TxUnconfirmed`](crate::tx::TxUnconfirmed) or
+//! [`LandsTooLate`](crate::tx::LandsTooLate).
+//! Converting one into a `TwobError` pulls out the RPC or program error behind it where
+//! there is one, and otherwise keeps it as [`Send`](TwobError::Send), where it can still be
+//! downcast.
+//!
+//! [`ProgramErrorCode`] names the twob program's custom errors, so a failure reads as
+//! `LiquidityPositionUnhealthy` with a hint at what to do rather than as
+//! `custom program error: 0x177e`. [`program_error`] finds one in a failed transaction's
+//! error or its logs.
+
+use anchor_client::{
+    ClientError,
+    solana_sdk::{instruction::InstructionError, transaction::TransactionError},
+};
+use anchor_lang::prelude::Pubkey;
+use solana_rpc_client_api::client_error::Error as RpcClientError;
+
+use crate::{core::MathError, instructions::BuildError, twob_anchor, tx::TransactionFailed};
+
+pub type Result<T, E = TwobError> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TwobError {
+    /// The RPC node couldn't be reached or refused the request.
+    #[error("RPC request failed: {0}")]
+    RpcError(#[source] Box<RpcClientError>),
+    #[error("account {pubkey} not found")]
+    AccountNotFound { pubkey: Pubkey },
+    /// Accounting arithmetic went out of range, i.e. the state read makes no sense.
+    #[error("{field} overflowed")]
+    MathOverflow { field: &'static str },
+    /// What was read is older than what it's being compared with, e.g. a slot behind the
+    /// position's last update.
+    #[error("stale data: {0}")]
+    StaleData(String),
+    /// The twob program (or another the transaction called) failed with a custom error.
+    #[error("program error {code} ({code:#x}){}", describe_code(*code))]
+    ProgramError { code: u32 },
+    /// An instruction couldn't be built from what was asked, e.g. reference index 0.
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    /// A send failed for another reason, e.g. it expired or the fee budget ran out.
+    #[error(transparent)]
+    Send(anyhow::Error),
+}
+
+impl TwobError {
+    /// `err`, from reading the account at `pubkey`.
+    pub fn account(pubkey: Pubkey, err: ClientError) -> Self {
+        match err {
+            ClientError::AccountNotFound => Self::AccountNotFound { pubkey },
+            err => err.into(),
+        }
+    }
+
+    /// Whether this is a send that was only simulated, in
+    /// [`dry_run`](crate::tx::TxSenderConfig::dry_run) mode.
+    pub fn is_dry_run(&self) -> bool {
+        matches!(self, Self::Send(error) if error.is::<crate::tx::DryRun>())
+    }
+
+    /// Whether the same call could succeed if made again. A send that expired is; one left
+    /// [unconfirmed](crate::tx::TxUnconfirmed) is not, as it may still land.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RpcError(_) | Self::StaleData(_) => true,
+            Self::AccountNotFound { .. }
+            | Self::MathOverflow { .. }
+            | Self::ProgramError { .. }
+            | Self::Build(_) => false,
+            Self::Send(error) => error.downcast_ref::<crate::tx::TxExpired>().is_some(),
+        }
+    }
+}
+
+/// The twob program's custom errors, numbered as in its IDL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ProgramErrorCode {
+    DurationTooShort = 6000,
+    DurationTooLong,
+    EndSlotAlreadyPassed,
+    DepositTooSmall,
+    FlowTooSmall,
+    InvalidMint,
+    WrongExitsAccount,
+    WrongPricesAccount,
+    WrongTokenAccount,
+    InvalidOrder,
+    BookNotUpToDate,
+    PositionNotEnded,
+    NotEnoughDeposits,
+    MinAmountOutNotReached,
+    LiquidityPositionUnhealthy,
+    LiquidityPositionStillActive,
+    LiquidityPositionNotActive,
+    NoDebt,
+    MustCoverDebt,
+    MarketIsPaused,
+    RemainingOrders,
+    AccountStillUsed,
+    TradePositionExpired,
+}
+
+impl ProgramErrorCode {
+    const ALL: [Self; 23] = [
+        Self::DurationTooShort,
+        Self::DurationTooLong,
+        Self::EndSlotAlreadyPassed,
+        Self::DepositTooSmall,
+        Self::FlowTooSmall,
+        Self::InvalidMint,
+        Self::WrongExitsAccount,
+        Self::WrongPricesAccount,
+        Self::WrongTokenAccount,
+        Self::InvalidOrder,
+        Self::BookNotUpToDate,
+        Self::PositionNotEnded,
+        Self::NotEnoughDeposits,
+        Self::MinAmountOutNotReached,
+        Self::LiquidityPositionUnhealthy,
+        Self::LiquidityPositionStillActive,
+        Self::LiquidityPositionNotActive,
+        Self::NoDebt,
+        Self::MustCoverDebt,
+        Self::MarketIsPaused,
+        Self::RemainingOrders,
+        Self::AccountStillUsed,
+        Self::TradePositionExpired,
+    ];
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|error| error.code() == code)
+    }
+
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub fn name(self) -> &'static str {
+        self.describe().0
+    }
+
+    /// The program's own message for the error.
+    pub fn message(self) -> &'static str {
+        self.describe().1
+    }
+
+    /// What usually fixes it.
+    pub fn hint(self) -> &'static str {
+        self.describe().2
+    }
+
+    fn describe(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Self::DurationTooShort => (
+                "DurationTooShort",
+                "Duration is too short",
+                "move the end slot later",
+            ),
+            Self::DurationTooLong => (
+                "DurationTooLong",
+                "Duration is too long",
+                "move the end slot earlier",
+            ),
+            Self::EndSlotAlreadyPassed => (
+                "EndSlotAlreadyPassed",
+                "End slot has already passed",
+                "end slot stale, recompute from current slot",
+            ),
+            Self::DepositTooSmall => ("DepositTooSmall", "Increase order size", "deposit more"),
+            Self::FlowTooSmall => (
+                "FlowTooSmall",
+                "Increase order size or reduce order duration",
+                "raise the amount or shorten the duration so at least 1 lamport flows per slot",
+            ),
+            Self::InvalidMint => (
+                "InvalidMint",
+                "Invalid mint account",
+                "pass the market's base or quote mint",
+            ),
+            Self::WrongExitsAccount => (
+                "WrongExitsAccount",
+                "Wrong exits account",
+                "reference_index stale, recompute from current slot",
+            ),
+            Self::WrongPricesAccount => (
+                "WrongPricesAccount",
+                "Wrong prices account",
+                "reference_index stale, recompute from current slot",
+            ),
+            Self::WrongTokenAccount => (
+                "WrongTokenAccount",
+                "Wrong token account",
+                "pass the authority's token account for the market's mint",
+            ),
+            Self::InvalidOrder => (
+                "InvalidOrder",
+                "Invalid order submission",
+                "check the order's side and amounts",
+            ),
+            Self::BookNotUpToDate => (
+                "BookNotUpToDate",
+                "Book not up to date",
+                "crank update_books for the market, then retry",
+            ),
+            Self::PositionNotEnded => (
+                "PositionNotEnded",
+                "Cannot close open position.",
+                "wait for the position's end slot before closing it",
+            ),
+            Self::NotEnoughDeposits => (
+                "NotEnoughDeposits",
+                "Deposits are too low for specified flow.",
+                "lower the flows or deposit more",
+            ),
+            Self::MinAmountOutNotReached => (
+                "MinAmountOutNotReached",
+                "Minimum amount out is not reached.",
+                "the price moved; requote or allow more slippage",
+            ),
+            Self::LiquidityPositionUnhealthy => (
+                "LiquidityPositionUnhealthy",
+                "Liquidity position is unhealthy.",
+                "lower the flows or deposit more to cover the position's debt",
+            ),
+            Self::LiquidityPositionStillActive => (
+                "LiquidityPositionStillActive",
+                "Liquidity position still active.",
+                "stop the position before closing it",
+            ),
+            Self::LiquidityPositionNotActive => (
+                "LiquidityPositionNotActive",
+                "Liquidity position not active anymore.",
+                "the position was stopped; open a new one",
+            ),
+            Self::NoDebt => (
+                "NoDebt",
+                "Liquidity position has no debt.",
+                "nothing to repay",
+            ),
+            Self::MustCoverDebt => (
+                "MustCoverDebt",
+                "Deposits must cover debt.",
+                "deposit at least the position's debt",
+            ),
+            Self::MarketIsPaused => (
+                "MarketIsPaused",
+                "Market is paused.",
+                "wait for the market to be unpaused",
+            ),
+            Self::RemainingOrders => (
+                "RemainingOrders",
+                "Market still has orders.",
+                "close the market's remaining orders first",
+            ),
+            Self::AccountStillUsed => (
+                "AccountStillUsed",
+                "Too early to close account.",
+                "wait until no open position references the account",
+            ),
+            Self::TradePositionExpired => (
+                "TradePositionExpired",
+                "Too late to close position.",
+                "the window to close the position has passed",
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for ProgramErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}; {}",
+            self.name(),
+            self.code(),
+            self.message().trim_end_matches('.'),
+            self.hint()
+        )
+    }
+}
+
+fn describe_code(code: u32) -> String {
+    ProgramErrorCode::from_code(code)
+        .map(|error| format!(": {error}"))
+        .unwrap_or_default()
+}
+
+/// The twob error a transaction failed with, from its error or, failing that, its logs.
+pub fn program_error(err: &TransactionError, logs: Option<&[String]>) -> Option<ProgramErrorCode> {
+    custom_error_code(err)
+        .and_then(ProgramErrorCode::from_code)
+        .or_else(|| logs.and_then(program_error_in_logs))
+}
+
+/// The twob error named in `logs`, read from the runtime's
+/// `Program <id> failed: custom program error: 0x…` line or Anchor's
+/// `AnchorError … Error Number: …` line.
+pub fn program_error_in_logs(logs: &[String]) -> Option<ProgramErrorCode> {
+    let failed = format!(
+        "Program {} failed: custom program error: 0x",
+        twob_anchor::ID
+    );
+    logs.iter().find_map(|line| {
+        let code = match line.strip_prefix(&failed) {
+            Some(hex) => u32::from_str_radix(hex.trim(), 16).ok()?,
+            None => {
+                let (_, number) = line.split_once("Error Number: ")?;
+                number.split('.').next()?.trim().parse().ok()?
+            }
+        };
+        ProgramErrorCode::from_code(code)
+    })
+}
+
+/// The custom error code `err` failed with, if any.
+pub fn custom_error_code(err: &TransactionError) -> Option<u32> {
+    match err {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => Some(*code),
+        _ => None,
+    }
+}
+
+impl From<RpcClientError> for TwobError {
+    fn from(err: RpcClientError) -> Self {
+        // Preflight rejects a failing instruction as an RPC error.
+        match err
+            .get_transaction_error()
+            .as_ref()
+            .and_then(custom_error_code)
+        {
+            Some(code) => Self::ProgramError { code },
+            None => Self::RpcError(Box::new(err)),
+        }
+    }
+}
+
+impl From<ClientError> for TwobError {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::SolanaClientError(err) => (*err).into(),
+            // An account that isn't there is reported through `account`, which knows its
+            // pubkey.
+            err => Self::Send(err.into()),
+        }
+    }
+}
+
+impl From<MathError> for TwobError {
+    fn from(err: MathError) -> Self {
+        match err {
+            MathError::StaleSlot { .. } => Self::StaleData(err.to_string()),
+            MathError::Overflow { field } => Self::MathOverflow { field },
+        }
+    }
+}
+
+impl From<anyhow::Error> for TwobError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<TwobError>() {
+            Ok(twob) => return twob,
+            Err(error) => error,
+        };
+        if let Some(code) = error
+            .downcast_ref::<TransactionFailed>()
+            .and_then(|failed| custom_error_code(&failed.error))
+        {
+            return Self::ProgramError { code };
+        }
+        match error.downcast::<RpcClientError>() {
+            Ok(err) => err.into(),
+            Err(error) => match error.downcast::<ClientError>() {
+                Ok(err) => err.into(),
+                Err(error) => Self::Send(error),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_errors_are_pulled_out_of_failed_sends() {
+        let failed = TransactionFailed {
+            signature: None,
+            error: TransactionError::InstructionError(0, InstructionError::Custom(6014)),
+            logs: None,
+        };
+        let error = TwobError::from(anyhow::Error::new(failed).context("stopping position"));
+        assert!(matches!(error, TwobError::ProgramError { code: 6014 }));
+        assert!(!error.is_retryable());
+
+        let error = TwobError::from(anyhow::anyhow!("fee budget exhausted"));
+        assert!(matches!(error, TwobError::Send(_)));
+        assert!(!error.is_retryable());
+        assert!(TwobError::StaleData("slot behind".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn program_errors_are_named_from_the_error_or_the_logs() {
+        let unhealthy = TransactionError::InstructionError(2, InstructionError::Custom(0x177e));
+        assert_eq!(
+            program_error(&unhealthy, None),
+            Some(ProgramErrorCode::LiquidityPositionUnhealthy)
+        );
+        assert!(
+            TwobError::ProgramError { code: 6014 }
+                .to_string()
+                .contains("LiquidityPositionUnhealthy")
+        );
+
+        let logs = vec![
+            format!("Program {} invoke [1]", twob_anchor::ID),
+            "Program log: AnchorError thrown in programs/twob/src/lib.rs:88. Error Code: \
+             WrongExitsAccount. Error Number: 6006. Error Message: Wrong exits account."
+                .to_string(),
+            format!(
+                "Program {} failed: custom program error: 0x1776",
+                twob_anchor::ID
+            ),
+        ];
+        let other = TransactionError::AccountInUse;
+        let error = program_error(&other, Some(logs.as_slice())).unwrap();
+        assert_eq!(error, ProgramErrorCode::WrongExitsAccount);
+        assert!(error.hint().contains("reference_index stale"));
+        assert_eq!(program_error(&other, Some(&logs[..1])), None);
+        // Another program's custom error isn't mistaken for one of twob's.
+        let token = TransactionError::InstructionError(0, InstructionError::Custom(1));
+        assert_eq!(program_error(&token, None), None);
+    }
+}
@@ -0,0 +1,53 @@
+//! Publishing to a Kafka topic over rskafka, a pure-Rust client.
+
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use futures::future::BoxFuture;
+use rskafka::{
+    client::{
+        ClientBuilder,
+        partition::{Compression, PartitionClient, UnknownTopicHandling},
+    },
+    record::Record,
+};
+
+use super::EventSink;
+
+const PARTITION: i32 = 0;
+
+pub(super) struct KafkaSink {
+    partition: PartitionClient,
+}
+
+impl KafkaSink {
+    pub(super) async fn connect(brokers: Vec<String>, topic: String) -> anyhow::Result<Self> {
+        let client = ClientBuilder::new(brokers).build().await?;
+        let partition = client
+            .partition_client(topic, PARTITION, UnknownTopicHandling::Retry)
+            .await?;
+        Ok(Self { partition })
+    }
+}
+
+impl EventSink for KafkaSink {
+    fn publish<'a>(
+        &'a self,
+        kind: &'static str,
+        key: String,
+        payload: Vec<u8>,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let record = Record {
+                key: Some(key.into_bytes()),
+                value: Some(payload),
+                headers: BTreeMap::from([("type".to_string(), kind.as_bytes().to_vec())]),
+                timestamp: Utc::now(),
+            };
+            self.partition
+                .produce(vec![record], Compression::NoCompression)
+                .await?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,460 @@
+//! Forwarding what the bots see and do onto Kafka or NATS, so risk and analytics systems
+//! downstream can subscribe without reading Solana RPC themselves.
+//!
+//! With `EVENT_BUS_URL` set, a bot [`start`](EventBus::start)s an [`EventBus`] of its own and
+//! three kinds of event are published through it as
+//! JSON [`Envelope`]s: decoded `MarketUpdateEvent`s and `ClosePositionEvent`s (from the
+//! indexer and inventory-flow's subscription), fills (from the fill notifier) and bot actions
+//! (every [`AuditRecord`], i.e. each strategy decision and each transaction sent).
+//!
+//! The schema is versioned by [`SCHEMA_VERSION`]: fields may be added within a version, but
+//! none is renamed, retyped or removed without bumping it. Every envelope carries a `type`
+//! naming its event. On Kafka (`kafka://host:9092,host:9092`, built with `--features kafka`)
+//! every event goes to partition 0 of `EVENT_BUS_TOPIC`, keyed by market ID, with the type in
+//! a `type` header. On NATS (`nats://host:4222`, `--features nats`) each type has its own
+//! subject, `<EVENT_BUS_TOPIC>.<type>`, e.g. `twob.events.fill`.
+//!
+//! Publishing never holds up a bot: events are queued for a background task, which
+//! reconnects as needed. Events published while the queue is full are dropped and counted.
+
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+mod nats;
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use tokio::{sync::mpsc, time::sleep};
+use tracing::{info, warn};
+
+use crate::{audit::AuditRecord, config::env_var, decode::TwobEvent};
+
+/// Bumped whenever a published field changes meaning, type or name, or goes away.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const DEFAULT_TOPIC: &str = "twob.events";
+const QUEUE_CAPACITY: usize = 4_096;
+const PUBLISH_ATTEMPTS: u32 = 3;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventBusBackend {
+    Kafka { brokers: Vec<String> },
+    Nats { url: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventBusConfig {
+    pub backend: EventBusBackend,
+    /// The Kafka topic, or the prefix of the NATS subjects.
+    pub topic: String,
+}
+
+impl EventBusConfig {
+    /// `None` unless `EVENT_BUS_URL` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(url) = env_var("EVENT_BUS_URL")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+        else {
+            return Ok(None);
+        };
+        let topic = env_var("EVENT_BUS_TOPIC")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| DEFAULT_TOPIC.to_string());
+        Ok(Some(Self {
+            backend: parse_url(&url)?,
+            topic,
+        }))
+    }
+}
+
+fn parse_url(url: &str) -> anyhow::Result<EventBusBackend> {
+    if let Some(brokers) = url.strip_prefix("kafka://") {
+        let brokers: Vec<String> = brokers
+            .split(',')
+            .map(str::trim)
+            .filter(|broker| !broker.is_empty())
+            .map(str::to_string)
+            .collect();
+        anyhow::ensure!(!brokers.is_empty(), "EVENT_BUS_URL names no Kafka brokers");
+        anyhow::ensure!(
+            cfg!(feature = "kafka"),
+            "EVENT_BUS_URL is a Kafka URL but the bot was built without `--features kafka`"
+        );
+        return Ok(EventBusBackend::Kafka { brokers });
+    }
+    if url.starts_with("nats://") || url.starts_with("tls://") {
+        anyhow::ensure!(
+            cfg!(feature = "nats"),
+            "EVENT_BUS_URL is a NATS URL but the bot was built without `--features nats`"
+        );
+        return Ok(EventBusBackend::Nats {
+            url: url.to_string(),
+        });
+    }
+    anyhow::bail!("EVENT_BUS_URL must start with kafka://, nats:// or tls://, got `{url}`")
+}
+
+/// One published message.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Envelope {
+    pub schema_version: u32,
+    pub timestamp: DateTime<Utc>,
+    /// The bin that published it, e.g. `indexer` or `oracle-flow`.
+    pub bot: &'static str,
+    #[serde(flatten)]
+    pub event: BusEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BusEvent {
+    /// A market's total flows after a `MarketUpdateEvent`.
+    MarketUpdate {
+        market_id: u64,
+        base_flow: u64,
+        quote_flow: u64,
+        slot: Option<u64>,
+        signature: Option<String>,
+    },
+    /// A trade position closed, from its `ClosePositionEvent`.
+    ClosePosition {
+        market_id: u64,
+        position_authority: String,
+        start_slot: u64,
+        end_slot: u64,
+        deposit_amount: u64,
+        swapped_amount: u64,
+        remaining_amount: u64,
+        fee_amount: u64,
+        is_buy: bool,
+        slot: Option<u64>,
+        signature: Option<String>,
+    },
+    /// What a liquidity position traded between two snapshots.
+    Fill {
+        market_id: u64,
+        authority: String,
+        /// `sold` (base for quote) or `bought` (base with quote).
+        side: String,
+        /// Raw base and quote exchanged.
+        base: u64,
+        quote: u64,
+        /// Quote per base in UI units.
+        price: f64,
+        from_slot: u64,
+        to_slot: u64,
+    },
+    /// A bot's strategy decision or transaction, as written to the audit log.
+    Action {
+        market_id: Option<u64>,
+        source: &'static str,
+        action: String,
+        status: String,
+        slot: Option<u64>,
+        signature: Option<String>,
+        error: Option<String>,
+        inputs: serde_json::Value,
+    },
+}
+
+impl BusEvent {
+    /// The envelope's `type`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::MarketUpdate { .. } => "market_update",
+            Self::ClosePosition { .. } => "close_position",
+            Self::Fill { .. } => "fill",
+            Self::Action { .. } => "action",
+        }
+    }
+
+    pub fn market_id(&self) -> Option<u64> {
+        match self {
+            Self::MarketUpdate { market_id, .. }
+            | Self::ClosePosition { market_id, .. }
+            | Self::Fill { market_id, .. } => Some(*market_id),
+            Self::Action { market_id, .. } => *market_id,
+        }
+    }
+
+    /// A decoded program event, with the transaction it was emitted in where known.
+    pub fn from_twob_event(
+        event: &TwobEvent,
+        signature: Option<String>,
+        slot: Option<u64>,
+    ) -> Self {
+        match *event {
+            TwobEvent::MarketUpdate {
+                market_id,
+                base_flow,
+                quote_flow,
+            } => Self::MarketUpdate {
+                market_id,
+                base_flow,
+                quote_flow,
+                slot,
+                signature,
+            },
+            TwobEvent::ClosePosition {
+                position_authority,
+                market_id,
+                start_slot,
+                end_slot,
+                deposit_amount,
+                swapped_amount,
+                remaining_amount,
+                fee_amount,
+                is_buy,
+            } => Self::ClosePosition {
+                market_id,
+                position_authority: position_authority.to_string(),
+                start_slot,
+                end_slot,
+                deposit_amount,
+                swapped_amount,
+                remaining_amount,
+                fee_amount,
+                is_buy,
+                slot,
+                signature,
+            },
+        }
+    }
+}
+
+impl From<&AuditRecord> for BusEvent {
+    fn from(record: &AuditRecord) -> Self {
+        Self::Action {
+            market_id: record.market_id,
+            source: record.source,
+            action: record.action.clone(),
+            status: record.status.clone(),
+            slot: record.slot,
+            signature: record.signature.clone(),
+            error: record.error.clone(),
+            inputs: record.inputs.clone(),
+        }
+    }
+}
+
+/// A connected broker.
+trait EventSink: Send + Sync {
+    /// Publish `payload`, an [`Envelope`] of type `kind`, keyed by `key`.
+    fn publish<'a>(
+        &'a self,
+        kind: &'static str,
+        key: String,
+        payload: Vec<u8>,
+    ) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+async fn connect(config: &EventBusConfig) -> anyhow::Result<Box<dyn EventSink>> {
+    match &config.backend {
+        #[cfg(feature = "kafka")]
+        EventBusBackend::Kafka { brokers } => Ok(Box::new(
+            kafka::KafkaSink::connect(brokers.clone(), config.topic.clone()).await?,
+        )),
+        #[cfg(feature = "nats")]
+        EventBusBackend::Nats { url } => Ok(Box::new(
+            nats::NatsSink::connect(url, config.topic.clone()).await?,
+        )),
+        #[allow(unreachable_patterns)]
+        backend => anyhow::bail!("no event bus support built in for {backend:?}"),
+    }
+}
+
+/// Publishes one bot's events. Each bot instance starts its own, so instances sharing a
+/// process publish under their own bot name and settings. Cheap to clone; the background
+/// task stops once every clone is dropped.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    bot: &'static str,
+    queue: mpsc::Sender<Envelope>,
+}
+
+impl EventBus {
+    /// Start publishing for `bot`. Call from within the Tokio runtime.
+    pub fn start(config: EventBusConfig, bot: &'static str) -> Self {
+        let (queue, events) = mpsc::channel(QUEUE_CAPACITY);
+        info!(
+            event.name = "event_bus_installed",
+            event_bus.backend = ?config.backend,
+            event_bus.topic = %config.topic,
+        );
+        tokio::spawn(forward(config, events));
+        Self { bot, queue }
+    }
+
+    /// Queue `event` for the event bus.
+    pub fn publish(&self, event: BusEvent) {
+        let envelope = Envelope {
+            schema_version: SCHEMA_VERSION,
+            timestamp: Utc::now(),
+            bot: self.bot,
+            event,
+        };
+        if let Err(error) = self.queue.try_send(envelope) {
+            warn!(
+                event.name = "event_bus_event_dropped",
+                event_bus.event_type = error.into_inner().event.kind(),
+                monotonic_counter.event_bus_dropped_total = 1_u64,
+            );
+        }
+    }
+}
+
+async fn forward(config: EventBusConfig, mut events: mpsc::Receiver<Envelope>) {
+    let mut sink: Option<Box<dyn EventSink>> = None;
+    while let Some(envelope) = events.recv().await {
+        let kind = envelope.event.kind();
+        let key = envelope
+            .event
+            .market_id()
+            .map(|market_id| market_id.to_string())
+            .unwrap_or_default();
+        let payload = match serde_json::to_vec(&envelope) {
+            Ok(payload) => payload,
+            Err(error) => {
+                warn!(
+                    event.name = "event_bus_encode_failed",
+                    event_bus.event_type = kind,
+                    ?error
+                );
+                continue;
+            }
+        };
+
+        let mut attempt = 1;
+        loop {
+            let result = publish_once(&config, &mut sink, kind, &key, &payload).await;
+            match result {
+                Ok(()) => {
+                    info!(
+                        event.name = "event_bus_published",
+                        event_bus.event_type = kind,
+                        monotonic_counter.event_bus_published_total = 1_u64,
+                    );
+                    break;
+                }
+                Err(error) if attempt < PUBLISH_ATTEMPTS => {
+                    warn!(
+                        event.name = "event_bus_publish_retry",
+                        event_bus.event_type = kind,
+                        retry.attempt = attempt,
+                        ?error,
+                    );
+                    sink = None;
+                    attempt += 1;
+                    sleep(RECONNECT_DELAY).await;
+                }
+                Err(error) => {
+                    warn!(
+                        event.name = "event_bus_publish_failed",
+                        event_bus.event_type = kind,
+                        monotonic_counter.event_bus_failed_total = 1_u64,
+                        ?error,
+                    );
+                    sink = None;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Publish over `sink`, connecting it first if it isn't.
+async fn publish_once(
+    config: &EventBusConfig,
+    sink: &mut Option<Box<dyn EventSink>>,
+    kind: &'static str,
+    key: &str,
+    payload: &[u8],
+) -> anyhow::Result<()> {
+    let sink = match sink {
+        Some(sink) => sink,
+        None => {
+            let connected = sink.insert(connect(config).await?);
+            info!(event.name = "event_bus_connected", event_bus.topic = %config.topic);
+            connected
+        }
+    };
+    sink.publish(kind, key.to_string(), payload.to_vec()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+
+    #[test]
+    fn envelopes_carry_version_type_and_flattened_fields() {
+        let authority = Pubkey::new_unique();
+        let event = TwobEvent::ClosePosition {
+            position_authority: authority,
+            market_id: 2,
+            start_slot: 10,
+            end_slot: 20,
+            deposit_amount: 100,
+            swapped_amount: 60,
+            remaining_amount: 40,
+            fee_amount: 1,
+            is_buy: true,
+        };
+        let envelope = Envelope {
+            schema_version: SCHEMA_VERSION,
+            timestamp: Utc::now(),
+            bot: "indexer",
+            event: BusEvent::from_twob_event(&event, Some("sig".to_string()), Some(21)),
+        };
+
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["type"], "close_position");
+        assert_eq!(json["bot"], "indexer");
+        assert_eq!(json["market_id"], 2);
+        assert_eq!(json["position_authority"], authority.to_string());
+        assert_eq!(json["is_buy"], true);
+        assert_eq!(json["slot"], 21);
+        assert_eq!(envelope.event.kind(), "close_position");
+    }
+
+    #[test]
+    fn audit_records_become_actions() {
+        let record = AuditRecord {
+            market_id: Some(3),
+            signature: Some("sig".to_string()),
+            inputs: serde_json::json!({ "base_flow": 5 }),
+            ..AuditRecord::new("tx", "update_liquidity_flows", "sent")
+        };
+        let event = BusEvent::from(&record);
+
+        assert_eq!(event.kind(), "action");
+        assert_eq!(event.market_id(), Some(3));
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["action"], "update_liquidity_flows");
+        assert_eq!(json["inputs"]["base_flow"], 5);
+    }
+
+    #[test]
+    fn parses_backend_urls() {
+        assert!(parse_url("redis://localhost").is_err());
+        assert!(parse_url("kafka://").is_err());
+        assert_eq!(
+            parse_url("kafka://a:9092, b:9092").is_ok(),
+            cfg!(feature = "kafka")
+        );
+        assert_eq!(
+            parse_url("nats://localhost:4222").is_ok(),
+            cfg!(feature = "nats")
+        );
+    }
+}
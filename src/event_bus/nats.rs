@@ -0,0 +1,38 @@
+//! Publishing to NATS subjects, one per event type.
+
+use futures::future::BoxFuture;
+
+use super::EventSink;
+
+pub(super) struct NatsSink {
+    client: async_nats::Client,
+    /// Prefix of the subjects, e.g. `twob.events`.
+    subject: String,
+}
+
+impl NatsSink {
+    pub(super) async fn connect(url: &str, subject: String) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: async_nats::connect(url).await?,
+            subject,
+        })
+    }
+}
+
+impl EventSink for NatsSink {
+    fn publish<'a>(
+        &'a self,
+        kind: &'static str,
+        _key: String,
+        payload: Vec<u8>,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.client
+                .publish(format!("{}.{}", self.subject, kind), payload.into())
+                .await?;
+            // `publish` only buffers; flush so a dead connection shows up here.
+            self.client.flush().await?;
+            Ok(())
+        })
+    }
+}
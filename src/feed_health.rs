@@ -0,0 +1,382 @@
+//! Health of the price feeds a bot reads, source by source.
+//!
+//! Every cycle [`fetch_prices`] asks each configured source for a price and records how
+//! long it took, how old the answer is, how many times in a row the source has failed,
+//! and how far its price sits from the median of the fresh ones. A source is healthy
+//! while it keeps answering, its price is fresh, and it agrees with the rest; the bot
+//! trades on the median of the healthy sources.
+//!
+//! The composite [`FeedStatus`] is `degraded` while at least one source is unhealthy and
+//! `unhealthy` once none is left. An unhealthy feed fails the cycle, so the circuit
+//! breaker counts it, and fails the `price_feed_health` readiness check.
+
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    config::env_var,
+    price::{PriceData, fetch_price},
+    rate_limit::endpoint_host,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeedHealthConfig {
+    /// Prices older than this don't count; 0 disables the check.
+    pub max_age_secs: u64,
+    /// A source failing this many fetches in a row is unhealthy; 0 disables the check.
+    pub max_consecutive_failures: u32,
+    /// A source further than this from the median is unhealthy; 0 disables the check.
+    pub max_deviation_bps: u64,
+}
+
+impl FeedHealthConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str, default: u64| -> anyhow::Result<u64> {
+            match env_var(name) {
+                Ok(value) if !value.trim().is_empty() => value
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e)),
+                _ => Ok(default),
+            }
+        };
+        Ok(Self {
+            max_age_secs: var("PRICE_MAX_AGE_SECS", 120)?,
+            max_consecutive_failures: u32::try_from(var("PRICE_FEED_MAX_FAILURES", 3)?)
+                .map_err(|e| anyhow::anyhow!("Invalid PRICE_FEED_MAX_FAILURES: {}", e))?,
+            max_deviation_bps: var("PRICE_FEED_MAX_DEVIATION_BPS", 100)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedStatus {
+    Healthy,
+    /// Some sources are unhealthy, but there is still a price to trade on.
+    Degraded,
+    Unhealthy,
+}
+
+impl FeedStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeedStatus::Healthy => "healthy",
+            FeedStatus::Degraded => "degraded",
+            FeedStatus::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SourceState {
+    last_price: Option<PriceData>,
+    last_latency: Option<Duration>,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SourceHealth {
+    pub source: String,
+    pub healthy: bool,
+    /// Why the source is unhealthy.
+    pub problem: Option<String>,
+    pub price: Option<f64>,
+    pub age_secs: Option<u64>,
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub deviation_bps: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeedHealthReport {
+    pub status: FeedStatus,
+    pub sources: Vec<SourceHealth>,
+    /// Median of the healthy sources, stamped with the oldest of their timestamps.
+    #[serde(skip)]
+    pub price: Option<PriceData>,
+}
+
+impl FeedHealthReport {
+    /// One line per unhealthy source, for alerts and the readiness check.
+    pub fn summary(&self) -> String {
+        let problems = self
+            .sources
+            .iter()
+            .filter_map(|source| {
+                let problem = source.problem.as_ref()?;
+                Some(format!("{}: {}", endpoint_host(&source.source), problem))
+            })
+            .collect::<Vec<_>>();
+        if problems.is_empty() {
+            format!("{} sources healthy", self.sources.len())
+        } else {
+            problems.join("; ")
+        }
+    }
+}
+
+/// Health of every configured source, in the order they were configured.
+#[derive(Debug)]
+pub struct FeedHealth {
+    config: FeedHealthConfig,
+    sources: Vec<(String, SourceState)>,
+}
+
+impl FeedHealth {
+    pub fn new(config: FeedHealthConfig, sources: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            config,
+            sources: sources
+                .into_iter()
+                .map(|source| (source, SourceState::default()))
+                .collect(),
+        }
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|(source, _)| source.as_str())
+    }
+
+    /// Record the outcome of one fetch from `source`.
+    pub fn record(&mut self, source: &str, latency: Duration, result: &anyhow::Result<PriceData>) {
+        let Some((_, state)) = self.sources.iter_mut().find(|(name, _)| name == source) else {
+            return;
+        };
+        state.last_latency = Some(latency);
+        match result {
+            Ok(price) => {
+                state.last_price = Some(price.clone());
+                state.consecutive_failures = 0;
+                state.last_error = None;
+            }
+            Err(error) => {
+                state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+                state.last_error = Some(format!("{error:#}"));
+            }
+        }
+    }
+
+    /// Judge every source as of `now` (unix seconds).
+    pub fn report(&self, now: u64) -> FeedHealthReport {
+        let config = &self.config;
+        let age = |price: &PriceData| now.saturating_sub(price.timestamp);
+        let fresh =
+            |price: &&PriceData| config.max_age_secs == 0 || age(price) <= config.max_age_secs;
+        let fresh_prices = self
+            .sources
+            .iter()
+            .filter_map(|(_, state)| state.last_price.as_ref())
+            .filter(fresh)
+            .map(|price| price.price)
+            .collect::<Vec<_>>();
+        let reference = median(fresh_prices);
+
+        let mut healthy_prices = Vec::new();
+        let sources = self
+            .sources
+            .iter()
+            .map(|(source, state)| {
+                let price = state.last_price.as_ref();
+                let deviation_bps = price
+                    .zip(reference)
+                    .map(|(price, reference)| deviation_bps(price.price, reference));
+                let problem = if config.max_consecutive_failures > 0
+                    && state.consecutive_failures >= config.max_consecutive_failures
+                {
+                    Some(format!(
+                        "{} failures in a row, last: {}",
+                        state.consecutive_failures,
+                        state.last_error.as_deref().unwrap_or("unknown")
+                    ))
+                } else if let Some(price) = price.filter(|price| !fresh(price)) {
+                    Some(format!(
+                        "stale: {}s old (limit {}s)",
+                        age(price),
+                        config.max_age_secs
+                    ))
+                } else if price.is_none() {
+                    Some(format!(
+                        "no price yet: {}",
+                        state.last_error.as_deref().unwrap_or("not fetched")
+                    ))
+                } else {
+                    deviation_bps
+                        .filter(|bps| {
+                            config.max_deviation_bps > 0 && *bps > config.max_deviation_bps
+                        })
+                        .map(|bps| {
+                            format!(
+                                "{}bps from the median (limit {}bps)",
+                                bps, config.max_deviation_bps
+                            )
+                        })
+                };
+                if let (None, Some(price)) = (&problem, price) {
+                    healthy_prices.push(price.clone());
+                }
+                SourceHealth {
+                    source: source.clone(),
+                    healthy: problem.is_none(),
+                    problem,
+                    price: price.map(|price| price.price),
+                    age_secs: price.map(age),
+                    latency_ms: state.last_latency.map(|latency| latency.as_millis() as u64),
+                    consecutive_failures: state.consecutive_failures,
+                    deviation_bps,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let status = match healthy_prices.len() {
+            0 => FeedStatus::Unhealthy,
+            n if n == sources.len() => FeedStatus::Healthy,
+            _ => FeedStatus::Degraded,
+        };
+        let price = healthy_prices
+            .iter()
+            .map(|price| price.timestamp)
+            .min()
+            .zip(median(
+                healthy_prices.iter().map(|price| price.price).collect(),
+            ))
+            .map(|(timestamp, price)| PriceData { price, timestamp });
+        FeedHealthReport {
+            status,
+            sources,
+            price,
+        }
+    }
+}
+
+fn median(mut prices: Vec<f64>) -> Option<f64> {
+    if prices.is_empty() {
+        return None;
+    }
+    prices.sort_by(f64::total_cmp);
+    let mid = prices.len() / 2;
+    Some(if prices.len().is_multiple_of(2) {
+        (prices[mid - 1] + prices[mid]) / 2.0
+    } else {
+        prices[mid]
+    })
+}
+
+fn deviation_bps(price: f64, reference: f64) -> u64 {
+    if reference <= 0.0 {
+        return 0;
+    }
+    ((price - reference).abs() / reference * 10_000.0).round() as u64
+}
+
+/// Fetch every source in `health` at once, record the outcomes, and report on them.
+pub async fn fetch_prices(client: &reqwest::Client, health: &mut FeedHealth) -> FeedHealthReport {
+    let fetches = health
+        .sources()
+        .map(|source| async move {
+            let started = Instant::now();
+            let result = fetch_price(client, source).await;
+            (source.to_string(), started.elapsed(), result)
+        })
+        .collect::<Vec<_>>();
+    let results = join_all(fetches).await;
+    for (source, latency, result) in &results {
+        if let Err(error) = result {
+            warn!(
+                event.name = "price_feed_fetch_failed",
+                price.source = endpoint_host(source).as_str(),
+                error = %format!("{error:#}"),
+            );
+        }
+        info!(
+            event.name = "price_feed_fetch",
+            price.source = endpoint_host(source).as_str(),
+            histogram.price_feed_latency_ms = latency.as_millis() as u64,
+        );
+        health.record(source, *latency, result);
+    }
+
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let report = health.report(now);
+    for source in &report.sources {
+        info!(
+            event.name = "price_feed_health",
+            price.source = endpoint_host(&source.source).as_str(),
+            price.source_healthy = source.healthy,
+            problem = ?source.problem,
+            gauge.price_feed_age_secs = source.age_secs.unwrap_or(u64::MAX) as f64,
+            gauge.price_feed_consecutive_failures = source.consecutive_failures as f64,
+            gauge.price_feed_deviation_bps = source.deviation_bps.unwrap_or(0) as f64,
+        );
+    }
+    info!(
+        event.name = "price_feed_status",
+        price.feed_status = report.status.as_str(),
+        gauge.price_feed_healthy_sources = report
+            .sources
+            .iter()
+            .filter(|source| source.healthy)
+            .count() as f64,
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FeedHealthConfig {
+        FeedHealthConfig {
+            max_age_secs: 60,
+            max_consecutive_failures: 2,
+            max_deviation_bps: 100,
+        }
+    }
+
+    fn price(price: f64, timestamp: u64) -> anyhow::Result<PriceData> {
+        Ok(PriceData { price, timestamp })
+    }
+
+    #[test]
+    fn trades_on_the_median_of_healthy_sources() {
+        let sources = [
+            "https://a.example",
+            "https://b.example",
+            "https://c.example",
+        ];
+        let mut health = FeedHealth::new(config(), sources.map(str::to_string));
+        let latency = Duration::from_millis(50);
+        health.record(sources[0], latency, &price(100.0, 1_000));
+        health.record(sources[1], latency, &price(100.4, 990));
+        health.record(sources[2], latency, &price(100.2, 1_000));
+
+        let report = health.report(1_010);
+        assert_eq!(report.status, FeedStatus::Healthy);
+        let traded = report.price.unwrap();
+        assert!((traded.price - 100.2).abs() < 1e-9);
+        assert_eq!(traded.timestamp, 990);
+
+        // c drifts 5% off: out of the median and flagged, the others carry on.
+        health.record(sources[2], latency, &price(105.0, 1_000));
+        let report = health.report(1_010);
+        assert_eq!(report.status, FeedStatus::Degraded);
+        assert!(!report.sources[2].healthy);
+        assert!((report.price.unwrap().price - 100.2).abs() < 1e-9);
+
+        // One failure is tolerated, the second in a row is not.
+        health.record(sources[0], latency, &Err(anyhow::anyhow!("timeout")));
+        assert!(health.report(1_010).sources[0].healthy);
+        health.record(sources[0], latency, &Err(anyhow::anyhow!("timeout")));
+        assert!(!health.report(1_010).sources[0].healthy);
+
+        // Once b goes stale nothing is left to trade on.
+        let report = health.report(1_051);
+        assert_eq!(report.status, FeedStatus::Unhealthy);
+        assert_eq!(report.price, None);
+        assert!(report.summary().contains("stale"));
+    }
+}
@@ -0,0 +1,274 @@
+//! A C interface to the balance math of [`core`](crate::core), built with the `ffi`
+//! feature; `include/twob_mm.h` declares it. Link the `cdylib` (or build a `staticlib`
+//! with `cargo rustc --crate-type staticlib`) to accrue positions in C or C++ the way the
+//! bots do.
+//!
+//! Accounts are passed as the raw data an RPC returns. Every function returns a
+//! `TWOB_*` status and writes its result through an out pointer only on `TWOB_OK`.
+//!
+//! A projection is the same call at a later slot: balances at slot `s` bring the position
+//! forward to `s` at the market's current flows, less the exits scheduled up to it.
+
+use std::{collections::BTreeMap, slice};
+
+use anchor_lang::AccountDeserialize;
+
+use crate::{
+    core::{
+        LiquidityPositionBalances, MathError, exits_window, position_balances,
+        slots_until_debt_per_side,
+    },
+    twob_anchor::accounts::{Bookkeeping, Exits, LiquidityPosition, Market},
+};
+
+pub const TWOB_OK: i32 = 0;
+/// A required pointer was null.
+pub const TWOB_NULL_ARGUMENT: i32 = 1;
+/// Account data didn't deserialize as the account it was passed as.
+pub const TWOB_INVALID_ACCOUNT: i32 = 2;
+/// The slot is before the position's last update.
+pub const TWOB_STALE_SLOT: i32 = 3;
+pub const TWOB_OVERFLOW: i32 = 4;
+
+/// `TwobBalances`: a position's balances, in the tokens' smallest units.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TwobBalances {
+    pub base_balance: u64,
+    pub quote_balance: u64,
+    pub base_debt: u64,
+    pub quote_debt: u64,
+}
+
+/// `TwobExits`: the data of the exits account at `index`.
+#[repr(C)]
+pub struct TwobExits {
+    pub index: u64,
+    pub data: *const u8,
+    pub len: usize,
+}
+
+/// `TwobRunway`: slots until each side runs dry; `draining_*` is 0 for a side the position
+/// isn't draining, whose `*_slots` is then 0.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TwobRunway {
+    pub draining_base: u8,
+    pub base_slots: u64,
+    pub draining_quote: u8,
+    pub quote_slots: u64,
+}
+
+impl From<LiquidityPositionBalances> for TwobBalances {
+    fn from(balances: LiquidityPositionBalances) -> Self {
+        Self {
+            base_balance: balances.base_balance,
+            quote_balance: balances.quote_balance,
+            base_debt: balances.base_debt,
+            quote_debt: balances.quote_debt,
+        }
+    }
+}
+
+impl From<TwobBalances> for LiquidityPositionBalances {
+    fn from(balances: TwobBalances) -> Self {
+        Self {
+            base_balance: balances.base_balance,
+            quote_balance: balances.quote_balance,
+            base_debt: balances.base_debt,
+            quote_debt: balances.quote_debt,
+        }
+    }
+}
+
+/// The first and last index of the exits accounts [`twob_position_balances`] reads at
+/// `current_slot`.
+///
+/// # Safety
+///
+/// Each data pointer must be valid for reads of its length, and `first` and `last` for a
+/// write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn twob_exits_window(
+    bookkeeping: *const u8,
+    bookkeeping_len: usize,
+    market: *const u8,
+    market_len: usize,
+    current_slot: u64,
+    first: *mut u64,
+    last: *mut u64,
+) -> i32 {
+    if first.is_null() || last.is_null() {
+        return TWOB_NULL_ARGUMENT;
+    }
+    let accounts = unsafe {
+        (
+            account::<Bookkeeping>(bookkeeping, bookkeeping_len),
+            account::<Market>(market, market_len),
+        )
+    };
+    let (bookkeeping, market) = match accounts {
+        (Ok(bookkeeping), Ok(market)) => (bookkeeping, market),
+        (Err(status), _) | (_, Err(status)) => return status,
+    };
+    let window = exits_window(&bookkeeping, &market, current_slot);
+    unsafe {
+        *first = *window.start();
+        *last = *window.end();
+    }
+    TWOB_OK
+}
+
+/// The position's balances at `current_slot`. `exits` holds `exits_len` exits accounts
+/// from [`twob_exits_window`]; one left out is read as having no exits, and `exits` may be
+/// null when `exits_len` is 0.
+///
+/// # Safety
+///
+/// Each data pointer must be valid for reads of its length, `exits` for reads of
+/// `exits_len` entries, and `out` for a write.
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn twob_position_balances(
+    position: *const u8,
+    position_len: usize,
+    bookkeeping: *const u8,
+    bookkeeping_len: usize,
+    market: *const u8,
+    market_len: usize,
+    exits: *const TwobExits,
+    exits_len: usize,
+    current_slot: u64,
+    out: *mut TwobBalances,
+) -> i32 {
+    if out.is_null() || (exits.is_null() && exits_len > 0) {
+        return TWOB_NULL_ARGUMENT;
+    }
+    let position = match unsafe { account::<LiquidityPosition>(position, position_len) } {
+        Ok(position) => position,
+        Err(status) => return status,
+    };
+    let bookkeeping = match unsafe { account::<Bookkeeping>(bookkeeping, bookkeeping_len) } {
+        Ok(bookkeeping) => bookkeeping,
+        Err(status) => return status,
+    };
+    let market = match unsafe { account::<Market>(market, market_len) } {
+        Ok(market) => market,
+        Err(status) => return status,
+    };
+    let entries = if exits_len == 0 {
+        &[][..]
+    } else {
+        unsafe { slice::from_raw_parts(exits, exits_len) }
+    };
+    let mut exits = BTreeMap::new();
+    for entry in entries {
+        match unsafe { account::<Exits>(entry.data, entry.len) } {
+            Ok(account) => exits.insert(entry.index, account),
+            Err(status) => return status,
+        };
+    }
+
+    match position_balances(&position, &bookkeeping, &market, &exits, current_slot) {
+        Ok(balances) => {
+            unsafe { *out = balances.into() };
+            TWOB_OK
+        }
+        Err(MathError::StaleSlot { .. }) => TWOB_STALE_SLOT,
+        Err(MathError::Overflow { .. }) => TWOB_OVERFLOW,
+    }
+}
+
+/// Slots until the position, holding `balances`, runs dry on each side at the market's
+/// current flows.
+///
+/// # Safety
+///
+/// Each data pointer must be valid for reads of its length, `balances` for a read and
+/// `out` for a write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn twob_runway(
+    position: *const u8,
+    position_len: usize,
+    market: *const u8,
+    market_len: usize,
+    balances: *const TwobBalances,
+    out: *mut TwobRunway,
+) -> i32 {
+    if balances.is_null() || out.is_null() {
+        return TWOB_NULL_ARGUMENT;
+    }
+    let accounts = unsafe {
+        (
+            account::<LiquidityPosition>(position, position_len),
+            account::<Market>(market, market_len),
+        )
+    };
+    let (position, market) = match accounts {
+        (Ok(position), Ok(market)) => (position, market),
+        (Err(status), _) | (_, Err(status)) => return status,
+    };
+    let balances = unsafe { *balances }.into();
+    let (base, quote) = slots_until_debt_per_side(&position, &market, &balances);
+    unsafe {
+        *out = TwobRunway {
+            draining_base: u8::from(base.is_some()),
+            base_slots: base.unwrap_or_default(),
+            draining_quote: u8::from(quote.is_some()),
+            quote_slots: quote.unwrap_or_default(),
+        };
+    }
+    TWOB_OK
+}
+
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes, or null.
+unsafe fn account<T: AccountDeserialize>(data: *const u8, len: usize) -> Result<T, i32> {
+    if data.is_null() {
+        return Err(TWOB_NULL_ARGUMENT);
+    }
+    let mut data = unsafe { slice::from_raw_parts(data, len) };
+    T::try_deserialize(&mut data).map_err(|_| TWOB_INVALID_ACCOUNT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    #[test]
+    fn reports_null_pointers_and_foreign_accounts() {
+        let garbage = [0_u8; 16];
+        let mut balances = TwobBalances::default();
+        let status = unsafe {
+            twob_position_balances(
+                garbage.as_ptr(),
+                garbage.len(),
+                garbage.as_ptr(),
+                garbage.len(),
+                ptr::null(),
+                0,
+                ptr::null(),
+                0,
+                1,
+                &mut balances,
+            )
+        };
+        assert_eq!(status, TWOB_INVALID_ACCOUNT);
+
+        let status = unsafe {
+            twob_runway(
+                garbage.as_ptr(),
+                garbage.len(),
+                garbage.as_ptr(),
+                garbage.len(),
+                &balances,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, TWOB_NULL_ARGUMENT);
+        assert_eq!(balances, TwobBalances::default());
+    }
+}
@@ -0,0 +1,321 @@
+//! Market health checks, for keepers watching markets and as a pre-flight check before
+//! capital is deployed.
+//!
+//! A market can be live on-chain and still be a bad place to quote: nobody has touched its
+//! bookkeeping in ages, only one side has flow so nothing trades, the exits or prices
+//! account for the current reference index was never created (so instructions touching it
+//! fail), or the price its flows imply has drifted far from the oracle. [`assess`] turns a
+//! [`MarketState`] into the list of such [`MarketIssue`]s; [`scan_market`] fetches what it
+//! needs first.
+
+use std::fmt;
+
+use anchor_client::Program;
+use serde::Serialize;
+
+use crate::{
+    AccountResolver, MarketState, ProgramPayer, fetch_market_state, nearest_reference_index,
+    quote::flow_price, twob_anchor,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthThresholds {
+    /// Flag bookkeeping not updated for more than this many slots.
+    pub max_bookkeeping_age_slots: u64,
+    /// Flag flow-implied prices further than this from the oracle.
+    pub max_price_deviation_bps: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum MarketIssue {
+    Paused,
+    StaleBookkeeping {
+        slots_since_update: u64,
+    },
+    /// Flow on one side only; nothing trades until the other side has some.
+    OneSidedFlow {
+        base_flow: u128,
+        quote_flow: u128,
+    },
+    MissingExits {
+        reference_index: u64,
+    },
+    MissingPrices {
+        reference_index: u64,
+    },
+    PriceDeviation {
+        implied: f64,
+        oracle: f64,
+        deviation_bps: u64,
+    },
+}
+
+impl fmt::Display for MarketIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketIssue::Paused => write!(f, "market is paused"),
+            MarketIssue::StaleBookkeeping { slots_since_update } => {
+                write!(f, "bookkeeping not updated for {slots_since_update} slots")
+            }
+            MarketIssue::OneSidedFlow {
+                base_flow,
+                quote_flow,
+            } => write!(
+                f,
+                "flow on one side only (base {base_flow}, quote {quote_flow})"
+            ),
+            MarketIssue::MissingExits { reference_index } => {
+                write!(f, "no exits account for reference index {reference_index}")
+            }
+            MarketIssue::MissingPrices { reference_index } => {
+                write!(f, "no prices account for reference index {reference_index}")
+            }
+            MarketIssue::PriceDeviation {
+                implied,
+                oracle,
+                deviation_bps,
+            } => write!(
+                f,
+                "implied price {implied:.6} is {deviation_bps} bps off oracle {oracle:.6}"
+            ),
+        }
+    }
+}
+
+/// What a scan found on one market.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarketHealth {
+    pub market_id: u64,
+    pub current_slot: u64,
+    pub reference_index: u64,
+    /// Quote per base implied by the market's flows, when both sides have some.
+    pub implied_price: Option<f64>,
+    pub oracle_price: Option<f64>,
+    pub issues: Vec<MarketIssue>,
+}
+
+impl MarketHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Whether the exits and prices accounts for the current reference index exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexAccounts {
+    pub exits: bool,
+    pub prices: bool,
+}
+
+/// Check `state` against `thresholds`. The price check is skipped without an
+/// `oracle_price` or when the market has no two-sided flow to imply one.
+pub fn assess(
+    market_id: u64,
+    state: &MarketState,
+    accounts: IndexAccounts,
+    base_decimals: u8,
+    quote_decimals: u8,
+    oracle_price: Option<f64>,
+    thresholds: &HealthThresholds,
+) -> MarketHealth {
+    let market = &state.market;
+    let reference_index = current_reference_index(state);
+    let mut issues = Vec::new();
+
+    if market.is_paused != 0 {
+        issues.push(MarketIssue::Paused);
+    }
+    let slots_since_update = state
+        .current_slot
+        .saturating_sub(state.bookkeeping.last_update_slot);
+    if slots_since_update > thresholds.max_bookkeeping_age_slots {
+        issues.push(MarketIssue::StaleBookkeeping { slots_since_update });
+    }
+    if (market.base_flow == 0) != (market.quote_flow == 0) {
+        issues.push(MarketIssue::OneSidedFlow {
+            base_flow: market.base_flow,
+            quote_flow: market.quote_flow,
+        });
+    }
+    if !accounts.exits {
+        issues.push(MarketIssue::MissingExits { reference_index });
+    }
+    if !accounts.prices {
+        issues.push(MarketIssue::MissingPrices { reference_index });
+    }
+
+    let implied_price = flow_price(
+        market.base_flow,
+        market.quote_flow,
+        base_decimals,
+        quote_decimals,
+    );
+    if let (Some(implied), Some(oracle)) = (implied_price, oracle_price)
+        && oracle > 0.0
+    {
+        let deviation_bps = ((implied - oracle).abs() / oracle * 10_000.0).round() as u64;
+        if deviation_bps > thresholds.max_price_deviation_bps {
+            issues.push(MarketIssue::PriceDeviation {
+                implied,
+                oracle,
+                deviation_bps,
+            });
+        }
+    }
+
+    MarketHealth {
+        market_id,
+        current_slot: state.current_slot,
+        reference_index,
+        implied_price,
+        oracle_price,
+        issues,
+    }
+}
+
+/// Fetch `market_id` and the accounts of its current reference index, then [`assess`] it.
+pub async fn scan_market(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    oracle_price: Option<f64>,
+    thresholds: &HealthThresholds,
+) -> anyhow::Result<MarketHealth> {
+    let rpc = program.rpc();
+    let state = fetch_market_state(program, market_id).await?;
+    let reference_index = current_reference_index(&state);
+
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let market = resolver.market_pda(market_id).address();
+    let exits = resolver.exits_pda(&market, reference_index).address();
+    let prices = resolver.prices_pda(&market, reference_index).address();
+    let found = rpc.get_multiple_accounts(&[exits, prices]).await?;
+    let accounts = IndexAccounts {
+        exits: found.first().is_some_and(Option::is_some),
+        prices: found.get(1).is_some_and(Option::is_some),
+    };
+
+    let base_decimals = rpc
+        .get_token_supply(&state.market.base_mint)
+        .await?
+        .decimals;
+    let quote_decimals = rpc
+        .get_token_supply(&state.market.quote_mint)
+        .await?
+        .decimals;
+
+    Ok(assess(
+        market_id,
+        &state,
+        accounts,
+        base_decimals,
+        quote_decimals,
+        oracle_price,
+        thresholds,
+    ))
+}
+
+fn current_reference_index(state: &MarketState) -> u64 {
+    nearest_reference_index(state.current_slot, state.market.end_slot_interval.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ARRAY_LENGTH,
+        accounts::test_market,
+        twob_anchor::accounts::{Bookkeeping, Market},
+    };
+
+    const THRESHOLDS: HealthThresholds = HealthThresholds {
+        max_bookkeeping_age_slots: 1_000,
+        max_price_deviation_bps: 500,
+    };
+
+    fn state(base_flow: u128, quote_flow: u128, last_update_slot: u64) -> MarketState {
+        MarketState {
+            market: Market {
+                base_flow,
+                quote_flow,
+                end_slot_interval: 1,
+                open_positions: 1,
+                ..test_market()
+            },
+            bookkeeping: Bookkeeping {
+                base_per_quote: 0,
+                previous_base_per_quote: 0,
+                quote_per_base: 0,
+                previous_quote_per_base: 0,
+                slots_without_trade: 0,
+                last_update_slot,
+                previous_update_slot: 0,
+                bump: 255,
+            },
+            current_slot: 10_000,
+        }
+    }
+
+    const PRESENT: IndexAccounts = IndexAccounts {
+        exits: true,
+        prices: true,
+    };
+
+    #[test]
+    fn healthy_market_has_no_issues() {
+        let health = assess(
+            1,
+            &state(100, 2_000, 9_500),
+            PRESENT,
+            0,
+            0,
+            Some(20.5),
+            &THRESHOLDS,
+        );
+        assert!(health.is_healthy(), "{:?}", health.issues);
+        assert_eq!(health.implied_price, Some(20.0));
+        assert_eq!(health.reference_index, 10_000 / ARRAY_LENGTH);
+    }
+
+    #[test]
+    fn flags_each_kind_of_problem() {
+        let missing = IndexAccounts {
+            exits: false,
+            prices: true,
+        };
+        let health = assess(1, &state(100, 0, 5_000), missing, 0, 0, None, &THRESHOLDS);
+        assert_eq!(
+            health.issues,
+            vec![
+                MarketIssue::StaleBookkeeping {
+                    slots_since_update: 5_000
+                },
+                MarketIssue::OneSidedFlow {
+                    base_flow: 100,
+                    quote_flow: 0
+                },
+                MarketIssue::MissingExits {
+                    reference_index: health.reference_index
+                },
+            ]
+        );
+
+        let health = assess(
+            1,
+            &state(100, 3_000, 9_500),
+            PRESENT,
+            0,
+            0,
+            Some(20.0),
+            &THRESHOLDS,
+        );
+        assert_eq!(
+            health.issues,
+            vec![MarketIssue::PriceDeviation {
+                implied: 30.0,
+                oracle: 20.0,
+                deviation_bps: 5_000,
+            }]
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Fetching twob transactions from an RPC node and resolving their instructions and events.
+//!
+//! Shared by the indexer, which stores everything the program does, the audit tool, which
+//! replays a single position's history, the PnL tracker, which adds up what a bot's
+//! transactions cost, and the ledger, which books a wallet's token movements.
+
+use std::collections::BTreeMap;
+
+use anchor_client::solana_sdk::{
+    commitment_config::CommitmentConfig, message::compiled_instruction::CompiledInstruction,
+    pubkey::Pubkey, signature::Signature,
+};
+use anyhow::Context;
+use solana_rpc_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+};
+use solana_rpc_client_types::{
+    config::RpcTransactionConfig, response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_transaction_status_client_types::{
+    UiTransactionEncoding, UiTransactionTokenBalance, option_serializer::OptionSerializer,
+};
+
+use crate::{
+    decode::{TwobEvent, TwobInstruction, decode_events_from_logs, decode_instruction},
+    twob_anchor,
+};
+
+const SIGNATURE_PAGE_SIZE: usize = 1_000;
+/// Base fee charged per transaction signature; anything above it is priority fee.
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// A twob instruction with its account keys resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedInstruction {
+    pub index: usize,
+    pub instruction: TwobInstruction,
+    pub accounts: Vec<Pubkey>,
+}
+
+impl IndexedInstruction {
+    pub fn account(&self, position: usize) -> anyhow::Result<Pubkey> {
+        self.accounts.get(position).copied().with_context(|| {
+            format!(
+                "{} instruction #{} has no account at position {}",
+                self.instruction.name(),
+                self.index,
+                position
+            )
+        })
+    }
+}
+
+/// What a transaction cost its fee payer, in lamports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransactionCost {
+    /// Total fee, base and priority, charged whether or not the transaction succeeded.
+    pub fee: u64,
+    pub priority_fee: u64,
+    /// Lamports moved into accounts the transaction created, less lamports released by the
+    /// accounts it closed. Negative when closing refunded more rent than was paid.
+    pub rent: i64,
+}
+
+impl TransactionCost {
+    pub fn from_meta(
+        fee: u64,
+        signatures: usize,
+        pre_balances: &[u64],
+        post_balances: &[u64],
+    ) -> Self {
+        let base_fee = LAMPORTS_PER_SIGNATURE.saturating_mul(signatures as u64);
+        // Index 0 is the fee payer, whose balance also carries the fee and any transfers.
+        let rent = pre_balances
+            .iter()
+            .zip(post_balances)
+            .skip(1)
+            .map(|(&pre, &post)| match (pre, post) {
+                (0, created) => created as i64,
+                (closed, 0) => -(closed as i64),
+                _ => 0,
+            })
+            .sum();
+        Self {
+            fee,
+            priority_fee: fee.saturating_sub(base_fee),
+            rent,
+        }
+    }
+}
+
+/// Net change of the tokens of one mint held by one owner over a transaction, in raw units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBalanceChange {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub change: i128,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub success: bool,
+    pub fee_payer: Pubkey,
+    pub cost: TransactionCost,
+    /// Accounts whose lamports changed, fee included.
+    pub lamport_changes: Vec<(Pubkey, i64)>,
+    pub token_changes: Vec<TokenBalanceChange>,
+    pub instructions: Vec<IndexedInstruction>,
+    pub events: Vec<TwobEvent>,
+}
+
+pub async fn fetch_transaction(
+    rpc: &RpcClient,
+    signature: &Signature,
+) -> anyhow::Result<IndexedTransaction> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    let fetched = rpc
+        .get_transaction_with_config(signature, config)
+        .await
+        .with_context(|| format!("Failed to fetch transaction {}", signature))?;
+
+    let transaction = fetched
+        .transaction
+        .transaction
+        .decode()
+        .with_context(|| format!("Failed to decode transaction {}", signature))?;
+    let meta = fetched
+        .transaction
+        .meta
+        .with_context(|| format!("Transaction {} has no status meta", signature))?;
+
+    let mut keys = transaction.message.static_account_keys().to_vec();
+    if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+        for address in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(address.parse()?);
+        }
+    }
+
+    let success = meta.err.is_none();
+    let cost = TransactionCost::from_meta(
+        meta.fee,
+        transaction.signatures.len(),
+        &meta.pre_balances,
+        &meta.post_balances,
+    );
+    let lamport_changes = keys
+        .iter()
+        .zip(meta.pre_balances.iter().zip(&meta.post_balances))
+        .filter(|(_, (pre, post))| pre != post)
+        .map(|(key, (&pre, &post))| (*key, post as i64 - pre as i64))
+        .collect();
+    let pre_token_balances: Option<Vec<UiTransactionTokenBalance>> = meta.pre_token_balances.into();
+    let post_token_balances: Option<Vec<UiTransactionTokenBalance>> =
+        meta.post_token_balances.into();
+    let token_changes = token_balance_changes(
+        &pre_token_balances.unwrap_or_default(),
+        &post_token_balances.unwrap_or_default(),
+    );
+    let logs: Option<Vec<String>> = meta.log_messages.into();
+
+    Ok(IndexedTransaction {
+        signature: signature.to_string(),
+        slot: fetched.slot,
+        block_time: fetched.block_time,
+        success,
+        fee_payer: keys.first().copied().unwrap_or_default(),
+        cost,
+        lamport_changes,
+        token_changes,
+        instructions: if success {
+            twob_instructions(&keys, transaction.message.instructions())
+        } else {
+            Vec::new()
+        },
+        events: if success {
+            decode_events_from_logs(&logs.unwrap_or_default())
+        } else {
+            Vec::new()
+        },
+    })
+}
+
+/// Net token balance changes per owner and mint, skipping balances without an owner.
+pub fn token_balance_changes(
+    pre: &[UiTransactionTokenBalance],
+    post: &[UiTransactionTokenBalance],
+) -> Vec<TokenBalanceChange> {
+    let mut changes = BTreeMap::<(Pubkey, Pubkey), i128>::new();
+    let balances = pre
+        .iter()
+        .map(|balance| (balance, -1))
+        .chain(post.iter().map(|balance| (balance, 1)));
+    for (balance, sign) in balances {
+        let OptionSerializer::Some(owner) = &balance.owner else {
+            continue;
+        };
+        let (Ok(owner), Ok(mint), Ok(amount)) = (
+            owner.parse::<Pubkey>(),
+            balance.mint.parse::<Pubkey>(),
+            balance.ui_token_amount.amount.parse::<u64>(),
+        ) else {
+            continue;
+        };
+        *changes.entry((owner, mint)).or_default() += sign * i128::from(amount);
+    }
+    changes
+        .into_iter()
+        .filter(|(_, change)| *change != 0)
+        .map(|((owner, mint), change)| TokenBalanceChange {
+            owner,
+            mint,
+            change,
+        })
+        .collect()
+}
+
+/// Signatures mentioning `address`, newest first, stopping at `until` (exclusive) or after
+/// `limit` entries. Returns whether `limit` cut the history short.
+pub async fn fetch_signatures(
+    rpc: &RpcClient,
+    address: &Pubkey,
+    until: Option<Signature>,
+    limit: usize,
+) -> anyhow::Result<(Vec<RpcConfirmedTransactionStatusWithSignature>, bool)> {
+    let mut before = None;
+    let mut signatures = Vec::new();
+    while signatures.len() < limit {
+        let page = rpc
+            .get_signatures_for_address_with_config(
+                address,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(SIGNATURE_PAGE_SIZE.min(limit - signatures.len())),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        before = Some(last.signature.parse::<Signature>()?);
+        let exhausted = page.len() < SIGNATURE_PAGE_SIZE;
+        signatures.extend(page);
+        if exhausted {
+            break;
+        }
+    }
+
+    let truncated = signatures.len() >= limit;
+    Ok((signatures, truncated))
+}
+
+/// Top-level instructions addressed to the twob program. CPIs into twob are not indexed.
+pub fn twob_instructions(
+    keys: &[Pubkey],
+    instructions: &[CompiledInstruction],
+) -> Vec<IndexedInstruction> {
+    instructions
+        .iter()
+        .enumerate()
+        .filter(|(_, ix)| keys.get(usize::from(ix.program_id_index)) == Some(&twob_anchor::ID))
+        .filter_map(|(index, ix)| {
+            Some(IndexedInstruction {
+                index,
+                instruction: decode_instruction(&ix.data)?,
+                accounts: ix
+                    .accounts
+                    .iter()
+                    .filter_map(|account| keys.get(usize::from(*account)).copied())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::InstructionData;
+
+    use super::*;
+    use crate::twob_anchor::client::args;
+
+    #[test]
+    fn keeps_only_decodable_twob_instructions() {
+        let authority = Pubkey::new_unique();
+        let market = Pubkey::new_unique();
+        let other_program = Pubkey::new_unique();
+        let keys = vec![authority, market, twob_anchor::ID, other_program];
+
+        let update = args::UpdateLiquidityFlows {
+            reference_index: 1,
+            base_flow_u64: 2,
+            quote_flow_u64: 3,
+        }
+        .data();
+        let instructions = vec![
+            CompiledInstruction::new_from_raw_parts(3, update.clone(), vec![0]),
+            CompiledInstruction::new_from_raw_parts(2, update, vec![0, 1]),
+            CompiledInstruction::new_from_raw_parts(2, vec![0; 8], vec![0]),
+        ];
+
+        let indexed = twob_instructions(&keys, &instructions);
+
+        assert_eq!(indexed.len(), 1);
+        assert_eq!(indexed[0].index, 1);
+        assert_eq!(indexed[0].accounts, vec![authority, market]);
+        assert_eq!(indexed[0].account(1).unwrap(), market);
+        assert!(indexed[0].account(2).is_err());
+    }
+
+    #[test]
+    fn splits_fee_into_priority_and_nets_rent() {
+        let cost = TransactionCost::from_meta(
+            15_000,
+            2,
+            &[1_000_000_000, 0, 2_039_280, 500],
+            &[997_945_720, 2_039_280, 0, 500],
+        );
+
+        assert_eq!(cost.fee, 15_000);
+        assert_eq!(cost.priority_fee, 5_000);
+        assert_eq!(cost.rent, 0);
+
+        let cost = TransactionCost::from_meta(5_000, 1, &[10_000_000, 0], &[7_955_720, 2_039_280]);
+        assert_eq!(cost.priority_fee, 0);
+        assert_eq!(cost.rent, 2_039_280);
+    }
+}
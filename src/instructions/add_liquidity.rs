@@ -1,72 +1,70 @@
-use anchor_client::{Program, solana_sdk::signature::Keypair};
+#[cfg(feature = "client")]
+use anchor_client::Program;
 use anchor_lang::prelude::{instruction::Instruction, *};
 use anchor_spl::associated_token::get_associated_token_address_with_program_id;
-use std::sync::Arc;
 
 use crate::{
-    AccountResolver, get_token_program_id,
+    AccountResolver,
+    instructions::{BuildError, TokenPrograms, previous_index, twob_instruction},
     twob_anchor::{
         self,
         accounts::Market,
         client::{accounts, args},
     },
 };
+#[cfg(feature = "client")]
+use crate::{
+    MarketState, ProgramPayer,
+    error::Result,
+    fetch_account, nearest_reference_index,
+    tx::{TxIntent, TxSender},
+    webhooks::LifecycleEvent,
+};
 
-pub async fn build_add_liquidity_instruction(
-    program: &Program<Arc<Keypair>>,
-    market_id: u64,
+/// Build an `add_liquidity` instruction depositing into `authority`'s position on `market`.
+pub fn add_liquidity_instruction(
+    authority: Pubkey,
+    market: &Market,
+    token_programs: TokenPrograms,
     add_liquidity_args: args::AddLiquidity,
-) -> anyhow::Result<Instruction> {
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(add_liquidity_args.reference_index)?;
     let resolver = AccountResolver::new(twob_anchor::ID);
 
-    let liquidity_provider = program.payer();
-    let market_pda = resolver.market_pda(market_id);
-    let market = program.account::<Market>(market_pda.address()).await?;
-
-    let liquidity_position_pda =
-        resolver.liquidity_position_pda(&market_pda.address(), &liquidity_provider);
+    let market_pda = resolver.market_pda(market.id);
+    let liquidity_position_pda = resolver.liquidity_position_pda(&market_pda.address(), &authority);
     let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
     let current_exits_pda =
         resolver.exits_pda(&market_pda.address(), add_liquidity_args.reference_index);
-    let previous_exits_pda = resolver.exits_pda(
-        &market_pda.address(),
-        add_liquidity_args.reference_index - 1,
-    );
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
     let current_prices_pda =
         resolver.prices_pda(&market_pda.address(), add_liquidity_args.reference_index);
-    let previous_prices_pda = resolver.prices_pda(
-        &market_pda.address(),
-        add_liquidity_args.reference_index - 1,
-    );
-
-    let base_token_program = get_token_program_id(program, &market.base_mint).await?;
-    let quote_token_program = get_token_program_id(program, &market.quote_mint).await?;
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
 
     let authority_base_token_account = get_associated_token_address_with_program_id(
-        &liquidity_provider,
+        &authority,
         &market.base_mint,
-        &base_token_program,
+        &token_programs.base,
     );
     let authority_quote_token_account = get_associated_token_address_with_program_id(
-        &liquidity_provider,
+        &authority,
         &market.quote_mint,
-        &quote_token_program,
+        &token_programs.quote,
     );
     let base_vault = get_associated_token_address_with_program_id(
         &market_pda.address(),
         &market.base_mint,
-        &base_token_program,
+        &token_programs.base,
     );
     let quote_vault = get_associated_token_address_with_program_id(
         &market_pda.address(),
         &market.quote_mint,
-        &quote_token_program,
+        &token_programs.quote,
     );
 
-    Ok(program
-        .request()
-        .accounts(accounts::AddLiquidity {
-            authority: liquidity_provider,
+    Ok(twob_instruction(
+        accounts::AddLiquidity {
+            authority,
             base_mint: market.base_mint,
             quote_mint: market.quote_mint,
             authority_base_token_account,
@@ -80,37 +78,134 @@ pub async fn build_add_liquidity_instruction(
             previous_exits: previous_exits_pda.address(),
             current_prices: current_prices_pda.address(),
             previous_prices: previous_prices_pda.address(),
-            base_token_program,
-            quote_token_program,
+            base_token_program: token_programs.base,
+            quote_token_program: token_programs.quote,
             associated_token_program: anchor_spl::associated_token::ID,
             system_program: system_program::ID,
-        })
-        .args(add_liquidity_args)
-        .instructions()?
-        .remove(0))
+        },
+        add_liquidity_args,
+    ))
 }
 
+#[cfg(feature = "client")]
+pub async fn build_add_liquidity_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    add_liquidity_args: args::AddLiquidity,
+) -> Result<Instruction> {
+    let market_pda = AccountResolver::new(twob_anchor::ID).market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    let token_programs = TokenPrograms::fetch(program, &market).await?;
+    Ok(add_liquidity_instruction(
+        program.payer(),
+        &market,
+        token_programs,
+        add_liquidity_args,
+    )?)
+}
+
+/// An `add_liquidity` instruction topping up the payer's open position on the market read
+/// at `state`. The position keeps streaming at its current flows; the deposit is made
+/// against the window `state.current_slot` is nearest, so it still lands if the slot
+/// crosses into it.
+#[cfg(feature = "client")]
+pub async fn build_deposit_liquidity_instruction(
+    program: &Program<ProgramPayer>,
+    state: &MarketState,
+    base_lamports: u64,
+    quote_lamports: u64,
+) -> Result<Instruction> {
+    let token_programs = TokenPrograms::fetch(program, &state.market).await?;
+    Ok(add_liquidity_instruction(
+        program.payer(),
+        &state.market,
+        token_programs,
+        args::AddLiquidity {
+            reference_index: nearest_reference_index(
+                state.current_slot,
+                state.market.end_slot_interval,
+            ),
+            base_lamports,
+            quote_lamports,
+        },
+    )?)
+}
+
+/// Send a [`build_deposit_liquidity_instruction`] deposit.
+#[cfg(feature = "client")]
+pub async fn execute_deposit_liquidity(
+    program: &Program<ProgramPayer>,
+    state: &MarketState,
+    base_lamports: u64,
+    quote_lamports: u64,
+    sender: &TxSender,
+) -> Result<()> {
+    let ix =
+        build_deposit_liquidity_instruction(program, state, base_lamports, quote_lamports).await?;
+    let reference_index =
+        nearest_reference_index(state.current_slot, state.market.end_slot_interval);
+    send_add_liquidity(
+        program,
+        state.market.id,
+        ix,
+        base_lamports,
+        quote_lamports,
+        reference_index,
+        sender,
+    )
+    .await
+}
+
+#[cfg(feature = "client")]
 pub async fn execute_add_liquidity(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     market_id: u64,
     base_lamports: u64,
     quote_lamports: u64,
     reference_index: u64,
-    signer: Arc<Keypair>,
-) -> anyhow::Result<()> {
+    sender: &TxSender,
+) -> Result<()> {
     let args = args::AddLiquidity {
         reference_index,
         base_lamports,
         quote_lamports,
     };
     let ix = build_add_liquidity_instruction(program, market_id, args).await?;
+    send_add_liquidity(
+        program,
+        market_id,
+        ix,
+        base_lamports,
+        quote_lamports,
+        reference_index,
+        sender,
+    )
+    .await
+}
 
-    program
-        .request()
-        .instruction(ix)
-        .signer(signer)
-        .send()
-        .await?;
+#[cfg(feature = "client")]
+async fn send_add_liquidity(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    ix: Instruction,
+    base_lamports: u64,
+    quote_lamports: u64,
+    reference_index: u64,
+    sender: &TxSender,
+) -> Result<()> {
+    let intent = TxIntent::new("add_liquidity", market_id)
+        .reference_index(reference_index)
+        .amounts(base_lamports, quote_lamports);
+    sender.send_with_intent(&intent, vec![ix]).await?;
+    sender.reporting().fire(
+        market_id,
+        &program.payer(),
+        LifecycleEvent::Deposit {
+            base_lamports,
+            quote_lamports,
+            reference_index,
+        },
+    );
 
     Ok(())
 }
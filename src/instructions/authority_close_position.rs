@@ -0,0 +1,143 @@
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::prelude::{instruction::Instruction, *};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+
+use crate::{
+    AccountResolver,
+    instructions::{
+        BuildError, TokenPrograms, previous_index, submit_order::future_index, twob_instruction,
+    },
+    twob_anchor::{
+        self,
+        accounts::{Market, TradePosition},
+        client::{accounts, args},
+    },
+};
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer,
+    error::Result,
+    fetch_account,
+    tx::{TxIntent, TxSender},
+};
+
+/// Build an `authority_close_position` instruction closing `trade_position` on `market`
+/// and paying out what it has bought plus any unspent amount.
+pub fn authority_close_position_instruction(
+    market: &Market,
+    trade_position: &TradePosition,
+    token_programs: TokenPrograms,
+    close_position_args: args::AuthorityClosePosition,
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(close_position_args.reference_index)?;
+    let resolver = AccountResolver::new(twob_anchor::ID);
+
+    let authority = trade_position.authority;
+    let market_pda = resolver.market_pda(market.id);
+    let trade_position_pda =
+        resolver.trade_position_pda(&market_pda.address(), &authority, trade_position.id);
+    let future_index = future_index(trade_position.end_slot, market.end_slot_interval);
+
+    let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
+    let current_exits_pda =
+        resolver.exits_pda(&market_pda.address(), close_position_args.reference_index);
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
+    let current_prices_pda =
+        resolver.prices_pda(&market_pda.address(), close_position_args.reference_index);
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
+    let future_exits_pda = resolver.exits_pda(&market_pda.address(), future_index);
+    let future_prices_pda = resolver.prices_pda(&market_pda.address(), future_index);
+
+    let authority_base_token_account = get_associated_token_address_with_program_id(
+        &authority,
+        &market.base_mint,
+        &token_programs.base,
+    );
+    let authority_quote_token_account = get_associated_token_address_with_program_id(
+        &authority,
+        &market.quote_mint,
+        &token_programs.quote,
+    );
+    let base_vault = get_associated_token_address_with_program_id(
+        &market_pda.address(),
+        &market.base_mint,
+        &token_programs.base,
+    );
+    let quote_vault = get_associated_token_address_with_program_id(
+        &market_pda.address(),
+        &market.quote_mint,
+        &token_programs.quote,
+    );
+
+    Ok(twob_instruction(
+        accounts::AuthorityClosePosition {
+            authority,
+            base_mint: market.base_mint,
+            quote_mint: market.quote_mint,
+            authority_base_token_account,
+            authority_quote_token_account,
+            market: market_pda.address(),
+            trade_position: trade_position_pda.address(),
+            base_vault,
+            quote_vault,
+            bookkeeping: bookkeeping_pda.address(),
+            future_exits: future_exits_pda.address(),
+            future_prices: future_prices_pda.address(),
+            current_exits: current_exits_pda.address(),
+            previous_exits: previous_exits_pda.address(),
+            current_prices: current_prices_pda.address(),
+            previous_prices: previous_prices_pda.address(),
+            base_token_program: token_programs.base,
+            quote_token_program: token_programs.quote,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        close_position_args,
+    ))
+}
+
+/// Build an `authority_close_position` instruction closing the payer's trade position
+/// `position_id`.
+#[cfg(feature = "client")]
+pub async fn build_authority_close_position_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    position_id: u64,
+    close_position_args: args::AuthorityClosePosition,
+) -> Result<Instruction> {
+    let resolver = AccountResolver::new(twob_anchor::ID);
+
+    let market_pda = resolver.market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    let trade_position_pda =
+        resolver.trade_position_pda(&market_pda.address(), &program.payer(), position_id);
+    let trade_position =
+        fetch_account::<TradePosition>(program, trade_position_pda.address()).await?;
+    let token_programs = TokenPrograms::fetch(program, &market).await?;
+    Ok(authority_close_position_instruction(
+        &market,
+        &trade_position,
+        token_programs,
+        close_position_args,
+    )?)
+}
+
+#[cfg(feature = "client")]
+pub async fn execute_authority_close_position(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    position_id: u64,
+    reference_index: u64,
+    sender: &TxSender,
+) -> Result<()> {
+    let args = args::AuthorityClosePosition { reference_index };
+    let ix =
+        build_authority_close_position_instruction(program, market_id, position_id, args).await?;
+
+    let intent =
+        TxIntent::new("authority_close_position", market_id).reference_index(reference_index);
+    sender.send_with_intent(&intent, vec![ix]).await?;
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::prelude::{instruction::Instruction, *};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+
+use crate::{
+    AccountResolver,
+    instructions::{TokenPrograms, twob_instruction},
+    twob_anchor::{
+        self,
+        client::{accounts, args},
+    },
+};
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer, TwobRpc,
+    error::Result,
+    get_token_program_id,
+    tx::{TxIntent, TxSender},
+};
+
+/// Build an `initialize_market` instruction by which the program config's `authority`
+/// creates market `initialize_market_args.id` trading `base_mint` against `quote_mint`,
+/// with its vaults and bookkeeping. Returns the instruction and the market's address.
+pub fn initialize_market_instruction(
+    authority: Pubkey,
+    payer: Pubkey,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+    token_programs: TokenPrograms,
+    initialize_market_args: args::InitializeMarket,
+) -> (Instruction, Pubkey) {
+    let resolver = AccountResolver::new(twob_anchor::ID);
+
+    let market = resolver.market_pda(initialize_market_args.id).address();
+    let base_vault =
+        get_associated_token_address_with_program_id(&market, &base_mint, &token_programs.base);
+    let quote_vault =
+        get_associated_token_address_with_program_id(&market, &quote_mint, &token_programs.quote);
+
+    let ix = twob_instruction(
+        accounts::InitializeMarket {
+            authority,
+            payer,
+            program_config: resolver.program_config_pda().address(),
+            base_mint,
+            quote_mint,
+            market,
+            base_vault,
+            quote_vault,
+            bookkeeping: resolver.bookkeeping_pda(&market).address(),
+            base_token_program: token_programs.base,
+            quote_token_program: token_programs.quote,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        initialize_market_args,
+    );
+    (ix, market)
+}
+
+/// [`initialize_market_instruction`] for the payer as admin, starting the market at the
+/// current slot. Each of its exits/prices windows spans `ARRAY_LENGTH * end_slot_interval`
+/// slots.
+#[cfg(feature = "client")]
+pub async fn build_create_market_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+    end_slot_interval: u64,
+    fee_bps: u8,
+    unhealthy_liquidity_fee_bps: u8,
+) -> Result<(Instruction, Pubkey)> {
+    let token_programs = TokenPrograms {
+        base: get_token_program_id(program, &base_mint).await?,
+        quote: get_token_program_id(program, &quote_mint).await?,
+    };
+    let start_slot = TwobRpc::get_slot(program).await?;
+    Ok(initialize_market_instruction(
+        program.payer(),
+        program.payer(),
+        base_mint,
+        quote_mint,
+        token_programs,
+        args::InitializeMarket {
+            id: market_id,
+            start_slot,
+            end_slot_interval,
+            fee_bps,
+            unhealthy_liquidity_fee_bps,
+        },
+    ))
+}
+
+/// Create market `market_id`; see [`build_create_market_instruction`]. Returns the
+/// market's address.
+#[cfg(feature = "client")]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_create_market(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+    end_slot_interval: u64,
+    fee_bps: u8,
+    unhealthy_liquidity_fee_bps: u8,
+    sender: &TxSender,
+) -> Result<Pubkey> {
+    let (ix, market) = build_create_market_instruction(
+        program,
+        market_id,
+        base_mint,
+        quote_mint,
+        end_slot_interval,
+        fee_bps,
+        unhealthy_liquidity_fee_bps,
+    )
+    .await?;
+
+    let intent = TxIntent::new("initialize_market", market_id);
+    sender.send_with_intent(&intent, vec![ix]).await?;
+
+    Ok(market)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_the_market_and_its_accounts_from_the_id() {
+        let authority = Pubkey::new_unique();
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        let token_programs = TokenPrograms {
+            base: Pubkey::new_unique(),
+            quote: Pubkey::new_unique(),
+        };
+        let (ix, market) = initialize_market_instruction(
+            authority,
+            authority,
+            base_mint,
+            quote_mint,
+            token_programs,
+            args::InitializeMarket {
+                id: 9,
+                start_slot: 1_000,
+                end_slot_interval: 10,
+                fee_bps: 30,
+                unhealthy_liquidity_fee_bps: 100,
+            },
+        );
+        let resolver = AccountResolver::new(twob_anchor::ID);
+
+        assert_eq!(market, resolver.market_pda(9).address());
+        assert_eq!(
+            ix.accounts[2].pubkey,
+            resolver.program_config_pda().address()
+        );
+        assert_eq!(ix.accounts[5].pubkey, market);
+        assert_eq!(
+            ix.accounts[7].pubkey,
+            get_associated_token_address_with_program_id(
+                &market,
+                &quote_mint,
+                &token_programs.quote
+            )
+        );
+        assert_eq!(
+            ix.accounts[8].pubkey,
+            resolver.bookkeeping_pda(&market).address()
+        );
+    }
+}
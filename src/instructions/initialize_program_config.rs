@@ -0,0 +1,93 @@
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::prelude::{instruction::Instruction, *};
+
+use crate::{
+    AccountResolver,
+    instructions::twob_instruction,
+    twob_anchor::{
+        self,
+        client::{accounts, args},
+    },
+};
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer,
+    error::Result,
+    tx::{TxIntent, TxSender},
+};
+
+/// Build an `initialize_program_config` instruction making `authority` the program's
+/// admin, the only signer allowed to create markets. It can be sent once per deployment.
+pub fn initialize_program_config_instruction(authority: Pubkey, payer: Pubkey) -> Instruction {
+    twob_instruction(
+        accounts::InitializeProgramConfig {
+            authority,
+            payer,
+            program_config: AccountResolver::new(twob_anchor::ID)
+                .program_config_pda()
+                .address(),
+            system_program: system_program::ID,
+        },
+        args::InitializeProgramConfig {},
+    )
+}
+
+/// [`initialize_program_config_instruction`] with the payer as admin.
+#[cfg(feature = "client")]
+pub fn build_initialize_program_config_instruction(program: &Program<ProgramPayer>) -> Instruction {
+    initialize_program_config_instruction(program.payer(), program.payer())
+}
+
+#[cfg(feature = "client")]
+pub async fn execute_initialize_program_config(
+    program: &Program<ProgramPayer>,
+    sender: &TxSender,
+) -> Result<()> {
+    let ix = build_initialize_program_config_instruction(program);
+
+    let intent = TxIntent {
+        action: "initialize_program_config".to_string(),
+        ..TxIntent::default()
+    };
+    sender.send_with_intent(&intent, vec![ix]).await?;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use anchor_client::{Client, Cluster, solana_sdk::commitment_config::CommitmentConfig};
+    use anchor_lang::InstructionData;
+
+    use super::*;
+    use crate::program_payer;
+
+    #[test]
+    fn makes_the_payer_admin_of_the_program_config() {
+        let admin = Pubkey::new_unique();
+        let client = Client::new_with_options(
+            Cluster::Localnet,
+            program_payer(admin),
+            CommitmentConfig::confirmed(),
+        );
+        let ix =
+            build_initialize_program_config_instruction(&client.program(twob_anchor::ID).unwrap());
+
+        assert_eq!(ix.program_id, twob_anchor::ID);
+        assert_eq!(ix.accounts.len(), 4);
+        assert_eq!(ix.accounts[0].pubkey, admin);
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[1].pubkey, admin);
+        assert!(ix.accounts[1].is_signer && ix.accounts[1].is_writable);
+        assert_eq!(
+            ix.accounts[2].pubkey,
+            AccountResolver::new(twob_anchor::ID)
+                .program_config_pda()
+                .address()
+        );
+        assert!(ix.accounts[2].is_writable);
+        assert_eq!(ix.accounts[3].pubkey, system_program::ID);
+        assert_eq!(ix.data, args::InitializeProgramConfig {}.data());
+    }
+}
@@ -1,9 +1,87 @@
+//! Builders for the TwoB program's instructions.
+//!
+//! Each instruction has a plain builder, e.g. [`add_liquidity_instruction`], that takes the
+//! accounts it can't derive (the market, the mints' token programs) and needs no RPC
+//! client. With the `client` feature, `build_*` wraps it to fetch those over RPC
+//! and `execute_*` sends the result through a [`TxSender`](crate::tx::TxSender).
+
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::{
+    InstructionData, ToAccountMetas,
+    prelude::{Pubkey, instruction::Instruction},
+};
+
+use crate::twob_anchor;
+#[cfg(feature = "client")]
+use crate::{ProgramPayer, error::Result, get_token_program_id, twob_anchor::accounts::Market};
+
 pub mod add_liquidity;
+pub mod authority_close_position;
+pub mod initialize_market;
+pub mod initialize_program_config;
+pub mod open_next_window;
+pub mod provide_liquidity;
+pub mod public_close_position;
 pub mod public_stop_liquidity_position;
+pub mod submit_order;
+pub mod update_books;
 pub mod update_liquidity_flows;
 pub mod withdraw_liquidity;
 
 pub use add_liquidity::*;
+pub use authority_close_position::*;
+pub use initialize_market::*;
+pub use initialize_program_config::*;
+pub use open_next_window::*;
+pub use provide_liquidity::*;
+pub use public_close_position::*;
 pub use public_stop_liquidity_position::*;
+pub use submit_order::*;
+pub use update_books::*;
 pub use update_liquidity_flows::*;
 pub use withdraw_liquidity::*;
+
+/// The token programs owning a market's base and quote mints: spl-token or token-2022.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenPrograms {
+    pub base: Pubkey,
+    pub quote: Pubkey,
+}
+
+impl TokenPrograms {
+    /// Look up the owners of `market`'s mints.
+    #[cfg(feature = "client")]
+    pub async fn fetch(program: &Program<ProgramPayer>, market: &Market) -> Result<Self> {
+        Ok(Self {
+            base: get_token_program_id(program, &market.base_mint).await?,
+            quote: get_token_program_id(program, &market.quote_mint).await?,
+        })
+    }
+}
+
+/// Why a builder couldn't build an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum BuildError {
+    /// The instructions that settle the books read the window before their reference index
+    /// as well, and window 0 has none.
+    #[error("reference index 0 has no previous window")]
+    ReferenceIndexZero,
+}
+
+/// The window before `reference_index`, whose exits and prices accounts an instruction
+/// naming `reference_index` also reads.
+fn previous_index(reference_index: u64) -> std::result::Result<u64, BuildError> {
+    reference_index
+        .checked_sub(1)
+        .ok_or(BuildError::ReferenceIndexZero)
+}
+
+/// An instruction to the TwoB program, encoded as anchor's request builder would.
+fn twob_instruction(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: twob_anchor::ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
@@ -0,0 +1,149 @@
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::prelude::{instruction::Instruction, *};
+#[cfg(feature = "client")]
+use tracing::info;
+
+use crate::{
+    ARRAY_LENGTH,
+    instructions::{BuildError, update_books_instruction},
+    twob_anchor::client::args,
+};
+#[cfg(feature = "client")]
+use crate::{
+    AccountResolver, MarketState, NEXT_WINDOW_LEAD_SLOTS, ProgramPayer, TwobRpc,
+    error::Result,
+    next_window_needs_opening, reference_window_last_slot, twob_anchor,
+    tx::{TxIntent, TxSender},
+};
+
+/// Build an instruction by which `signer` opens market `market_id`'s next exits/prices
+/// window ahead of its boundary: an `update_books` read at `current_slot` naming the window
+/// after `current_slot`'s, which creates that window's prices account. Send it no earlier
+/// than [`NEXT_WINDOW_LEAD_SLOTS`](crate::NEXT_WINDOW_LEAD_SLOTS) before the boundary. No
+/// instruction creates an exits account on its own; `submit_order` does for the window its
+/// order ends in, and until then the window reads as one without exits.
+pub fn open_next_window_instruction(
+    signer: Pubkey,
+    market_id: u64,
+    current_slot: u64,
+    end_slot_interval: u64,
+) -> std::result::Result<Instruction, BuildError> {
+    update_books_instruction(
+        signer,
+        market_id,
+        args::UpdateBooks {
+            reference_index: current_slot / ARRAY_LENGTH / end_slot_interval + 1,
+            slot: current_slot,
+        },
+    )
+}
+
+/// [`open_next_window_instruction`] from the payer, for market state read at `state`.
+#[cfg(feature = "client")]
+pub fn build_open_next_window_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    state: &MarketState,
+) -> Result<Instruction> {
+    Ok(open_next_window_instruction(
+        program.payer(),
+        market_id,
+        state.current_slot,
+        state.market.end_slot_interval,
+    )?)
+}
+
+/// Whether window `reference_index`'s prices account exists yet.
+#[cfg(feature = "client")]
+pub async fn window_opened(
+    rpc: &(impl TwobRpc + ?Sized),
+    market_id: u64,
+    reference_index: u64,
+) -> Result<bool> {
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let market_pda = resolver.market_pda(market_id).address();
+    let prices = resolver.prices_pda(&market_pda, reference_index).address();
+    Ok(rpc.get_account_data(prices).await?.is_some())
+}
+
+/// Open market `market_id`'s next window if, read at `state`, it starts within
+/// [`NEXT_WINDOW_LEAD_SLOTS`] and nobody has opened it yet, so the flow updates and stops
+/// sent around the boundary find its accounts. Returns whether it sent anything.
+#[cfg(feature = "client")]
+pub async fn execute_open_next_window(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    state: &MarketState,
+    sender: &TxSender,
+) -> Result<bool> {
+    let end_slot_interval = state.market.end_slot_interval;
+    if !next_window_needs_opening(
+        state.current_slot,
+        end_slot_interval,
+        NEXT_WINDOW_LEAD_SLOTS,
+    ) {
+        return Ok(false);
+    }
+    let next_index = state.current_slot / ARRAY_LENGTH / end_slot_interval + 1;
+    if window_opened(program, market_id, next_index).await? {
+        return Ok(false);
+    }
+
+    let ix = build_open_next_window_instruction(program, market_id, state)?;
+    let intent = TxIntent::new("open_next_window", market_id)
+        .reference_index(next_index)
+        .valid_until_slot(reference_window_last_slot(next_index, end_slot_interval));
+    sender.send_with_intent(&intent, vec![ix]).await?;
+    info!(
+        event.name = "window_opened",
+        market.id = market_id,
+        twob.instruction = "update_books",
+        twob.reference_index = next_index,
+    );
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AccountResolver, twob_anchor};
+
+    #[test]
+    fn names_the_next_window_at_the_current_slot() {
+        let signer = Pubkey::new_unique();
+        // Windows of 10 * 5 slots: slot 1_247 is in window 24.
+        let ix = open_next_window_instruction(signer, 3, 1_247, 5).unwrap();
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(3).address();
+
+        assert_eq!(ix.accounts[0].pubkey, signer);
+        assert_eq!(
+            ix.accounts[3].pubkey,
+            resolver.exits_pda(&market_pda, 25).address()
+        );
+        assert_eq!(
+            ix.accounts[5].pubkey,
+            resolver.prices_pda(&market_pda, 25).address()
+        );
+        assert!(ix.accounts[5].is_writable);
+        assert_eq!(
+            ix.accounts[6].pubkey,
+            resolver.prices_pda(&market_pda, 24).address()
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn a_window_is_open_once_its_prices_account_exists() {
+        let rpc = crate::state::MockRpc::new(0);
+        assert!(!window_opened(&rpc, 1, 4).await.unwrap());
+
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(1).address();
+        rpc.set_account_data(resolver.prices_pda(&market_pda, 4).address(), vec![0; 8]);
+        assert!(window_opened(&rpc, 1, 4).await.unwrap());
+        assert!(!window_opened(&rpc, 1, 5).await.unwrap());
+    }
+}
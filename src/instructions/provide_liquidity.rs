@@ -0,0 +1,235 @@
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::prelude::{instruction::Instruction, *};
+use anchor_spl::associated_token::{
+    get_associated_token_address_with_program_id,
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+};
+
+use crate::{
+    AccountResolver,
+    instructions::{BuildError, TokenPrograms, previous_index, twob_instruction},
+    twob_anchor::{
+        self,
+        accounts::Market,
+        client::{accounts, args},
+    },
+};
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer,
+    error::Result,
+    fetch_account,
+    tx::{TxIntent, TxSender},
+    webhooks::LifecycleEvent,
+};
+
+/// Build a `provide_liquidity` instruction opening `authority`'s position on `market` with
+/// an initial deposit and flows.
+pub fn provide_liquidity_instruction(
+    authority: Pubkey,
+    market: &Market,
+    token_programs: TokenPrograms,
+    provide_liquidity_args: args::ProvideLiquidity,
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(provide_liquidity_args.reference_index)?;
+    let resolver = AccountResolver::new(twob_anchor::ID);
+
+    let market_pda = resolver.market_pda(market.id);
+    let liquidity_position_pda = resolver.liquidity_position_pda(&market_pda.address(), &authority);
+    let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
+    let current_exits_pda = resolver.exits_pda(
+        &market_pda.address(),
+        provide_liquidity_args.reference_index,
+    );
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
+    let current_prices_pda = resolver.prices_pda(
+        &market_pda.address(),
+        provide_liquidity_args.reference_index,
+    );
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
+
+    let authority_base_token_account = get_associated_token_address_with_program_id(
+        &authority,
+        &market.base_mint,
+        &token_programs.base,
+    );
+    let authority_quote_token_account = get_associated_token_address_with_program_id(
+        &authority,
+        &market.quote_mint,
+        &token_programs.quote,
+    );
+    let base_vault = get_associated_token_address_with_program_id(
+        &market_pda.address(),
+        &market.base_mint,
+        &token_programs.base,
+    );
+    let quote_vault = get_associated_token_address_with_program_id(
+        &market_pda.address(),
+        &market.quote_mint,
+        &token_programs.quote,
+    );
+
+    Ok(twob_instruction(
+        accounts::ProvideLiquidity {
+            authority,
+            base_mint: market.base_mint,
+            quote_mint: market.quote_mint,
+            authority_base_token_account,
+            authority_quote_token_account,
+            market: market_pda.address(),
+            liquidity_position: liquidity_position_pda.address(),
+            base_vault,
+            quote_vault,
+            bookkeeping: bookkeeping_pda.address(),
+            current_exits: current_exits_pda.address(),
+            previous_exits: previous_exits_pda.address(),
+            current_prices: current_prices_pda.address(),
+            previous_prices: previous_prices_pda.address(),
+            base_token_program: token_programs.base,
+            quote_token_program: token_programs.quote,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        provide_liquidity_args,
+    ))
+}
+
+/// [`provide_liquidity_instruction`] preceded by creating `authority`'s base and quote
+/// token accounts where they don't exist yet, paid for by `authority`.
+pub fn open_liquidity_position_instructions(
+    authority: Pubkey,
+    market: &Market,
+    token_programs: TokenPrograms,
+    provide_liquidity_args: args::ProvideLiquidity,
+) -> std::result::Result<Vec<Instruction>, BuildError> {
+    Ok(vec![
+        create_associated_token_account_idempotent(
+            &authority,
+            &authority,
+            &market.base_mint,
+            &token_programs.base,
+        ),
+        create_associated_token_account_idempotent(
+            &authority,
+            &authority,
+            &market.quote_mint,
+            &token_programs.quote,
+        ),
+        provide_liquidity_instruction(authority, market, token_programs, provide_liquidity_args)?,
+    ])
+}
+
+/// The instructions opening the payer's position on `market_id`; see
+/// [`open_liquidity_position_instructions`].
+#[cfg(feature = "client")]
+pub async fn build_open_liquidity_position_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    provide_liquidity_args: args::ProvideLiquidity,
+) -> Result<Vec<Instruction>> {
+    let market_pda = AccountResolver::new(twob_anchor::ID).market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    let token_programs = TokenPrograms::fetch(program, &market).await?;
+    Ok(open_liquidity_position_instructions(
+        program.payer(),
+        &market,
+        token_programs,
+        provide_liquidity_args,
+    )?)
+}
+
+/// Open the payer's position on `market_id` with `provide_liquidity_args`' deposit and
+/// flows.
+#[cfg(feature = "client")]
+pub async fn execute_open_position(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    provide_liquidity_args: args::ProvideLiquidity,
+    sender: &TxSender,
+) -> Result<()> {
+    let args::ProvideLiquidity {
+        reference_index,
+        base_deposit_lamports,
+        quote_deposit_lamports,
+        base_flow_u64: base_flow,
+        quote_flow_u64: quote_flow,
+    } = provide_liquidity_args;
+    let ixs = build_open_liquidity_position_instruction(program, market_id, provide_liquidity_args)
+        .await?;
+
+    let intent = TxIntent::new("provide_liquidity", market_id)
+        .reference_index(reference_index)
+        .flows(base_flow, quote_flow)
+        .amounts(base_deposit_lamports, quote_deposit_lamports);
+    sender.send_with_intent(&intent, ixs).await?;
+    sender.reporting().fire(
+        market_id,
+        &program.payer(),
+        LifecycleEvent::Deposit {
+            base_lamports: base_deposit_lamports,
+            quote_lamports: quote_deposit_lamports,
+            reference_index,
+        },
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::test_market;
+
+    #[test]
+    fn opens_the_position_after_its_token_accounts() {
+        let authority = Pubkey::new_unique();
+        let market = Market {
+            id: 4,
+            ..test_market()
+        };
+        let token_programs = TokenPrograms {
+            base: Pubkey::new_unique(),
+            quote: Pubkey::new_unique(),
+        };
+        let ixs = open_liquidity_position_instructions(
+            authority,
+            &market,
+            token_programs,
+            args::ProvideLiquidity {
+                reference_index: 7,
+                base_deposit_lamports: 1_000,
+                quote_deposit_lamports: 2_000,
+                base_flow_u64: 10,
+                quote_flow_u64: 20,
+            },
+        )
+        .unwrap();
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(4).address();
+
+        assert_eq!(ixs.len(), 3);
+        assert_eq!(ixs[0].program_id, anchor_spl::associated_token::ID);
+        assert_eq!(ixs[1].program_id, anchor_spl::associated_token::ID);
+        let provide = &ixs[2];
+        assert_eq!(provide.program_id, twob_anchor::ID);
+        assert_eq!(
+            provide.accounts[4].pubkey,
+            get_associated_token_address_with_program_id(
+                &authority,
+                &market.quote_mint,
+                &token_programs.quote
+            )
+        );
+        assert_eq!(
+            provide.accounts[6].pubkey,
+            resolver
+                .liquidity_position_pda(&market_pda, &authority)
+                .address()
+        );
+        assert_eq!(
+            provide.accounts[11].pubkey,
+            resolver.exits_pda(&market_pda, 6).address()
+        );
+    }
+}
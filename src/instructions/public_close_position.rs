@@ -0,0 +1,217 @@
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::prelude::{instruction::Instruction, *};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+
+use crate::{
+    AccountResolver,
+    instructions::{
+        BuildError, TokenPrograms, previous_index, submit_order::future_index, twob_instruction,
+    },
+    twob_anchor::{
+        self,
+        accounts::{Market, TradePosition},
+        client::{accounts, args},
+    },
+};
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer,
+    error::Result,
+    fetch_account, fetch_trade_position,
+    tx::{TxIntent, TxSender},
+};
+
+/// Build a `public_close_position` instruction by which `signer` settles someone else's
+/// `trade_position` on `market` once it has ended, paying what it bought and any unspent
+/// amount to the position's authority.
+pub fn public_close_position_instruction(
+    signer: Pubkey,
+    market: &Market,
+    trade_position: &TradePosition,
+    token_programs: TokenPrograms,
+    close_position_args: args::PublicClosePosition,
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(close_position_args.reference_index)?;
+    let resolver = AccountResolver::new(twob_anchor::ID);
+
+    let position_authority = trade_position.authority;
+    let market_pda = resolver.market_pda(market.id);
+    let trade_position_pda = resolver.trade_position_pda(
+        &market_pda.address(),
+        &position_authority,
+        trade_position.id,
+    );
+    let future_index = future_index(trade_position.end_slot, market.end_slot_interval);
+
+    let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
+    let current_exits_pda =
+        resolver.exits_pda(&market_pda.address(), close_position_args.reference_index);
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
+    let current_prices_pda =
+        resolver.prices_pda(&market_pda.address(), close_position_args.reference_index);
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
+    let future_exits_pda = resolver.exits_pda(&market_pda.address(), future_index);
+    let future_prices_pda = resolver.prices_pda(&market_pda.address(), future_index);
+
+    let authority_base_token_account = get_associated_token_address_with_program_id(
+        &position_authority,
+        &market.base_mint,
+        &token_programs.base,
+    );
+    let authority_quote_token_account = get_associated_token_address_with_program_id(
+        &position_authority,
+        &market.quote_mint,
+        &token_programs.quote,
+    );
+    let base_vault = get_associated_token_address_with_program_id(
+        &market_pda.address(),
+        &market.base_mint,
+        &token_programs.base,
+    );
+    let quote_vault = get_associated_token_address_with_program_id(
+        &market_pda.address(),
+        &market.quote_mint,
+        &token_programs.quote,
+    );
+
+    Ok(twob_instruction(
+        accounts::PublicClosePosition {
+            signer,
+            position_authority,
+            base_mint: market.base_mint,
+            quote_mint: market.quote_mint,
+            authority_base_token_account,
+            authority_quote_token_account,
+            market: market_pda.address(),
+            trade_position: trade_position_pda.address(),
+            base_vault,
+            quote_vault,
+            bookkeeping: bookkeeping_pda.address(),
+            future_exits: future_exits_pda.address(),
+            future_prices: future_prices_pda.address(),
+            current_exits: current_exits_pda.address(),
+            previous_exits: previous_exits_pda.address(),
+            current_prices: current_prices_pda.address(),
+            previous_prices: previous_prices_pda.address(),
+            base_token_program: token_programs.base,
+            quote_token_program: token_programs.quote,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        close_position_args,
+    ))
+}
+
+/// Build a `public_close_position` instruction by which the payer settles
+/// `position_authority`'s trade position `position_id`.
+#[cfg(feature = "client")]
+pub async fn build_public_close_position_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    position_authority: &Pubkey,
+    position_id: u64,
+    close_position_args: args::PublicClosePosition,
+) -> Result<Instruction> {
+    let market_pda = AccountResolver::new(twob_anchor::ID).market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    let trade_position =
+        fetch_trade_position(program, market_id, position_authority, position_id).await?;
+    let token_programs = TokenPrograms::fetch(program, &market).await?;
+    Ok(public_close_position_instruction(
+        program.payer(),
+        &market,
+        &trade_position,
+        token_programs,
+        close_position_args,
+    )?)
+}
+
+#[cfg(feature = "client")]
+pub async fn execute_public_close_position(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    position_authority: &Pubkey,
+    position_id: u64,
+    reference_index: u64,
+    sender: &TxSender,
+) -> Result<()> {
+    let args = args::PublicClosePosition { reference_index };
+    let ix = build_public_close_position_instruction(
+        program,
+        market_id,
+        position_authority,
+        position_id,
+        args,
+    )
+    .await?;
+
+    let intent = TxIntent::new("public_close_position", market_id).reference_index(reference_index);
+    sender.send_with_intent(&intent, vec![ix]).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::test_market;
+
+    #[test]
+    fn pays_out_to_the_position_authority_not_the_signer() {
+        let signer = Pubkey::new_unique();
+        let position_authority = Pubkey::new_unique();
+        let market = Market {
+            id: 2,
+            ..test_market()
+        };
+        let trade_position = TradePosition {
+            authority: position_authority,
+            id: 3,
+            amount: 1_000,
+            start_slot: 100,
+            end_slot: 450,
+            bookkeeping_snapshot: 0,
+            slots_without_trades_snapshot: 0,
+            is_buy: 1,
+            bump: 255,
+        };
+        let token_programs = TokenPrograms {
+            base: Pubkey::new_unique(),
+            quote: Pubkey::new_unique(),
+        };
+        let ix = public_close_position_instruction(
+            signer,
+            &market,
+            &trade_position,
+            token_programs,
+            args::PublicClosePosition { reference_index: 5 },
+        )
+        .unwrap();
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(2).address();
+
+        assert_eq!(ix.accounts[0].pubkey, signer);
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[1].pubkey, position_authority);
+        assert_eq!(
+            ix.accounts[4].pubkey,
+            get_associated_token_address_with_program_id(
+                &position_authority,
+                &market.base_mint,
+                &token_programs.base
+            )
+        );
+        assert_eq!(
+            ix.accounts[7].pubkey,
+            resolver
+                .trade_position_pda(&market_pda, &position_authority, 3)
+                .address()
+        );
+        // Ends at slot 450, in window 4.
+        assert_eq!(
+            ix.accounts[11].pubkey,
+            resolver.exits_pda(&market_pda, 4).address()
+        );
+    }
+}
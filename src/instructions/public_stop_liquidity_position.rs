@@ -1,84 +1,80 @@
-use anchor_client::{Program, solana_sdk::signature::Keypair};
+#[cfg(feature = "client")]
+use anchor_client::Program;
 use anchor_lang::prelude::{instruction::Instruction, *};
 use anchor_spl::associated_token::get_associated_token_address_with_program_id;
-use std::sync::Arc;
+#[cfg(feature = "client")]
+use tracing::warn;
 
 use crate::{
-    AccountResolver, get_token_program_id,
+    AccountResolver,
+    instructions::{BuildError, TokenPrograms, previous_index, twob_instruction},
     twob_anchor::{
         self,
         accounts::Market,
         client::{accounts, args},
     },
 };
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer,
+    error::Result,
+    fetch_account,
+    tx::{TxIntent, TxSender},
+    webhooks::LifecycleEvent,
+};
 
-pub async fn build_public_stop_liquidity_position_instruction(
-    program: &Program<Arc<Keypair>>,
-    market_id: u64,
+/// Build a `public_stop_liquidity_position` instruction by which `signer` stops
+/// `position_authority`'s position on `market`.
+pub fn public_stop_liquidity_position_instruction(
+    signer: Pubkey,
+    position_authority: Pubkey,
+    market: &Market,
+    token_programs: TokenPrograms,
     stop_liquidity_position_args: args::PublicStopLiquidityPosition,
-) -> Instruction {
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(stop_liquidity_position_args.reference_index)?;
     let resolver = AccountResolver::new(twob_anchor::ID);
 
-    let liquidity_provider = program.payer();
-    let market_pda = resolver.market_pda(market_id);
-    let market = program
-        .account::<Market>(market_pda.address())
-        .await
-        .unwrap();
-
+    let market_pda = resolver.market_pda(market.id);
     let liquidity_position_pda =
-        resolver.liquidity_position_pda(&market_pda.address(), &liquidity_provider);
+        resolver.liquidity_position_pda(&market_pda.address(), &position_authority);
     let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
     let current_exits_pda = resolver.exits_pda(
         &market_pda.address(),
         stop_liquidity_position_args.reference_index,
     );
-    let previous_exits_pda = resolver.exits_pda(
-        &market_pda.address(),
-        stop_liquidity_position_args.reference_index - 1,
-    );
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
     let current_prices_pda = resolver.prices_pda(
         &market_pda.address(),
         stop_liquidity_position_args.reference_index,
     );
-    let previous_prices_pda = resolver.prices_pda(
-        &market_pda.address(),
-        stop_liquidity_position_args.reference_index - 1,
-    );
-
-    let base_token_program = get_token_program_id(program, &market.base_mint)
-        .await
-        .unwrap();
-    let quote_token_program = get_token_program_id(program, &market.quote_mint)
-        .await
-        .unwrap();
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
 
     let signer_base_token_account = get_associated_token_address_with_program_id(
-        &liquidity_provider,
+        &signer,
         &market.base_mint,
-        &base_token_program,
+        &token_programs.base,
     );
     let signer_quote_token_account = get_associated_token_address_with_program_id(
-        &liquidity_provider,
+        &signer,
         &market.quote_mint,
-        &quote_token_program,
+        &token_programs.quote,
     );
     let base_vault = get_associated_token_address_with_program_id(
         &market_pda.address(),
         &market.base_mint,
-        &base_token_program,
+        &token_programs.base,
     );
     let quote_vault = get_associated_token_address_with_program_id(
         &market_pda.address(),
         &market.quote_mint,
-        &quote_token_program,
+        &token_programs.quote,
     );
 
-    program
-        .request()
-        .accounts(accounts::PublicStopLiquidityPosition {
-            signer: liquidity_provider,
-            position_authority: liquidity_provider,
+    Ok(twob_instruction(
+        accounts::PublicStopLiquidityPosition {
+            signer,
+            position_authority,
             base_mint: market.base_mint,
             quote_mint: market.quote_mint,
             signer_base_token_account,
@@ -92,34 +88,58 @@ pub async fn build_public_stop_liquidity_position_instruction(
             previous_exits: previous_exits_pda.address(),
             current_prices: current_prices_pda.address(),
             previous_prices: previous_prices_pda.address(),
-            base_token_program,
-            quote_token_program,
+            base_token_program: token_programs.base,
+            quote_token_program: token_programs.quote,
             associated_token_program: anchor_spl::associated_token::ID,
             system_program: system_program::ID,
-        })
-        .args(stop_liquidity_position_args)
-        .instructions()
-        .unwrap()
-        .remove(0)
+        },
+        stop_liquidity_position_args,
+    ))
+}
+
+#[cfg(feature = "client")]
+pub async fn build_public_stop_liquidity_position_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    stop_liquidity_position_args: args::PublicStopLiquidityPosition,
+) -> Result<Instruction> {
+    let market_pda = AccountResolver::new(twob_anchor::ID).market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    let token_programs = TokenPrograms::fetch(program, &market).await?;
+    Ok(public_stop_liquidity_position_instruction(
+        program.payer(),
+        program.payer(),
+        &market,
+        token_programs,
+        stop_liquidity_position_args,
+    )?)
 }
 
+#[cfg(feature = "client")]
 pub async fn execute_stop_position(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     market_id: u64,
     reference_index: u64,
-    signer: Arc<Keypair>,
-) -> anyhow::Result<()> {
-    println!("🚨🚨🚨🚨 Position has accumulated debt. Stopping position.");
+    sender: &TxSender,
+) -> Result<()> {
+    warn!(
+        event.name = "position_stop_started",
+        market.id = market_id,
+        lp.authority = %program.payer(),
+        reference_index,
+    );
 
     let args = args::PublicStopLiquidityPosition { reference_index };
-    let ix = build_public_stop_liquidity_position_instruction(program, market_id, args).await;
+    let ix = build_public_stop_liquidity_position_instruction(program, market_id, args).await?;
 
-    program
-        .request()
-        .instruction(ix)
-        .signer(signer)
-        .send()
-        .await?;
+    let intent =
+        TxIntent::new("public_stop_liquidity_position", market_id).reference_index(reference_index);
+    sender.send_with_intent(&intent, vec![ix]).await?;
+    sender.reporting().fire(
+        market_id,
+        &program.payer(),
+        LifecycleEvent::Stop { reference_index },
+    );
 
     Ok(())
 }
@@ -0,0 +1,189 @@
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::prelude::{instruction::Instruction, *};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+
+use crate::{
+    ARRAY_LENGTH, AccountResolver,
+    instructions::{BuildError, previous_index, twob_instruction},
+    twob_anchor::{
+        self,
+        accounts::Market,
+        client::{accounts, args},
+    },
+};
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer,
+    error::Result,
+    fetch_account, get_token_program_id,
+    tx::{TxIntent, TxSender},
+};
+
+/// Which way a trade position trades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderSide {
+    /// Spend quote for base.
+    Buy,
+    /// Spend base for quote.
+    Sell,
+}
+
+impl OrderSide {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderSide::Buy => "buy",
+            OrderSide::Sell => "sell",
+        }
+    }
+
+    /// Mint of the token the order spends.
+    pub fn spend_mint(self, market: &Market) -> Pubkey {
+        match self {
+            OrderSide::Buy => market.quote_mint,
+            OrderSide::Sell => market.base_mint,
+        }
+    }
+
+    /// Mint of the token the order receives.
+    pub fn receive_mint(self, market: &Market) -> Pubkey {
+        match self {
+            OrderSide::Buy => market.base_mint,
+            OrderSide::Sell => market.quote_mint,
+        }
+    }
+}
+
+impl std::str::FromStr for OrderSide {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.trim() {
+            "buy" => Ok(Self::Buy),
+            "sell" => Ok(Self::Sell),
+            other => anyhow::bail!("Invalid order side `{}`; expected `buy` or `sell`", other),
+        }
+    }
+}
+
+/// Build a `submit_order` instruction by which `authority` opens trade position
+/// `submit_order_args.id` on `market`, spending from its account at `token_program`.
+///
+/// The program infers the side from the mint deposited. `future_index` must be the window
+/// containing `end_slot`; see [`future_index`].
+pub fn submit_order_instruction(
+    authority: Pubkey,
+    market: &Market,
+    side: OrderSide,
+    token_program: Pubkey,
+    submit_order_args: args::SubmitOrder,
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(submit_order_args.reference_index)?;
+    let resolver = AccountResolver::new(twob_anchor::ID);
+
+    let market_pda = resolver.market_pda(market.id);
+    let mint = side.spend_mint(market);
+
+    let trade_position_pda =
+        resolver.trade_position_pda(&market_pda.address(), &authority, submit_order_args.id);
+    let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
+    let current_exits_pda =
+        resolver.exits_pda(&market_pda.address(), submit_order_args.reference_index);
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
+    let current_prices_pda =
+        resolver.prices_pda(&market_pda.address(), submit_order_args.reference_index);
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
+    let future_exits_pda =
+        resolver.exits_pda(&market_pda.address(), submit_order_args.future_index);
+    let future_prices_pda =
+        resolver.prices_pda(&market_pda.address(), submit_order_args.future_index);
+
+    let authority_ata =
+        get_associated_token_address_with_program_id(&authority, &mint, &token_program);
+    let vault =
+        get_associated_token_address_with_program_id(&market_pda.address(), &mint, &token_program);
+
+    Ok(twob_instruction(
+        accounts::SubmitOrder {
+            authority,
+            authority_ata,
+            mint,
+            market: market_pda.address(),
+            trade_position: trade_position_pda.address(),
+            vault,
+            bookkeeping: bookkeeping_pda.address(),
+            current_exits: current_exits_pda.address(),
+            previous_exits: previous_exits_pda.address(),
+            current_prices: current_prices_pda.address(),
+            previous_prices: previous_prices_pda.address(),
+            future_exits: future_exits_pda.address(),
+            future_prices: future_prices_pda.address(),
+            token_program,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        },
+        submit_order_args,
+    ))
+}
+
+/// [`submit_order_instruction`] for the payer, fetching the market and the spent mint's
+/// token program.
+#[cfg(feature = "client")]
+pub async fn build_submit_order_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    side: OrderSide,
+    submit_order_args: args::SubmitOrder,
+) -> Result<Instruction> {
+    let market_pda = AccountResolver::new(twob_anchor::ID).market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    let token_program = get_token_program_id(program, &side.spend_mint(&market)).await?;
+    Ok(submit_order_instruction(
+        program.payer(),
+        &market,
+        side,
+        token_program,
+        submit_order_args,
+    )?)
+}
+
+/// Index of the exits/prices window an order ending at `end_slot` exits into.
+pub fn future_index(end_slot: u64, end_slot_interval: u64) -> u64 {
+    end_slot / ARRAY_LENGTH / end_slot_interval
+}
+
+#[cfg(feature = "client")]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_submit_order(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    position_id: u64,
+    side: OrderSide,
+    amount: u64,
+    end_slot: u64,
+    reference_index: u64,
+    sender: &TxSender,
+) -> Result<()> {
+    let market_pda = AccountResolver::new(twob_anchor::ID).market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    let future_index = future_index(end_slot, market.end_slot_interval);
+    sender
+        .send_for_window(
+            market.end_slot_interval,
+            reference_index,
+            |reference_index| async move {
+                let args = args::SubmitOrder {
+                    id: position_id,
+                    future_index,
+                    reference_index,
+                    amount,
+                    end_slot,
+                };
+                let ix = build_submit_order_instruction(program, market_id, side, args).await?;
+                Ok((TxIntent::new("submit_order", market_id), vec![ix]))
+            },
+        )
+        .await?;
+
+    Ok(())
+}
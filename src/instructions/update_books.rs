@@ -0,0 +1,150 @@
+#[cfg(feature = "client")]
+use anchor_client::Program;
+use anchor_lang::prelude::{instruction::Instruction, *};
+
+#[cfg(feature = "client")]
+use crate::{
+    ARRAY_LENGTH, ProgramPayer,
+    error::Result,
+    fetch_market_state,
+    tx::{TxIntent, TxPriority, TxSender},
+};
+use crate::{
+    AccountResolver,
+    instructions::{BuildError, previous_index, twob_instruction},
+    twob_anchor::{
+        self,
+        client::{accounts, args},
+    },
+};
+
+/// Build an `update_books` instruction by which `signer` brings market `market_id`'s
+/// bookkeeping up to `update_books_args.slot`. Anyone can send it; it moves no tokens.
+/// Naming a window whose prices account doesn't exist yet, it creates it; see
+/// [`open_next_window_instruction`](crate::open_next_window_instruction).
+pub fn update_books_instruction(
+    signer: Pubkey,
+    market_id: u64,
+    update_books_args: args::UpdateBooks,
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(update_books_args.reference_index)?;
+    let resolver = AccountResolver::new(twob_anchor::ID);
+
+    let market_pda = resolver.market_pda(market_id);
+    let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
+    let reference_exits_pda =
+        resolver.exits_pda(&market_pda.address(), update_books_args.reference_index);
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
+    let reference_prices_pda =
+        resolver.prices_pda(&market_pda.address(), update_books_args.reference_index);
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
+
+    Ok(twob_instruction(
+        accounts::UpdateBooks {
+            signer,
+            market: market_pda.address(),
+            bookkeeping: bookkeeping_pda.address(),
+            reference_exits: reference_exits_pda.address(),
+            previous_exits: previous_exits_pda.address(),
+            reference_prices: reference_prices_pda.address(),
+            previous_prices: previous_prices_pda.address(),
+            system_program: system_program::ID,
+        },
+        update_books_args,
+    ))
+}
+
+/// An `update_books` instruction from the payer bringing the bookkeeping up to `slot`, in
+/// the window `slot` falls in. The reference index is derived from `slot` rather than read
+/// separately, so the two can't straddle a window boundary.
+#[cfg(feature = "client")]
+pub fn build_update_books_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    end_slot_interval: u64,
+    slot: u64,
+) -> Result<Instruction> {
+    Ok(update_books_instruction(
+        program.payer(),
+        market_id,
+        args::UpdateBooks {
+            reference_index: slot / ARRAY_LENGTH / end_slot_interval,
+            slot,
+        },
+    )?)
+}
+
+/// Crank market `market_id`'s bookkeeping up to the current slot, queued as a
+/// [`TxPriority::Crank`] so it never holds up the bot's own flow updates or stops.
+#[cfg(feature = "client")]
+pub async fn execute_update_books(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    sender: &TxSender,
+) -> Result<()> {
+    let state = fetch_market_state(program, market_id).await?;
+    let (current_slot, end_slot_interval) = (state.current_slot, state.market.end_slot_interval);
+    sender
+        .send_for_window(
+            end_slot_interval,
+            current_slot / ARRAY_LENGTH / end_slot_interval,
+            |reference_index| async move {
+                // Rebuilt for a later window, it brings the books up to that window's start.
+                let slot = current_slot.max(reference_index * ARRAY_LENGTH * end_slot_interval);
+                let ix =
+                    build_update_books_instruction(program, market_id, end_slot_interval, slot)?;
+                let intent = TxIntent::new("update_books", market_id).priority(TxPriority::Crank);
+                Ok((intent, vec![ix]))
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_the_window_and_the_one_before() {
+        let signer = Pubkey::new_unique();
+        let ix = update_books_instruction(
+            signer,
+            3,
+            args::UpdateBooks {
+                reference_index: 8,
+                slot: 845,
+            },
+        )
+        .unwrap();
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(3).address();
+
+        assert_eq!(ix.program_id, twob_anchor::ID);
+        assert_eq!(ix.accounts[0].pubkey, signer);
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[1].pubkey, market_pda);
+        assert_eq!(
+            ix.accounts[3].pubkey,
+            resolver.exits_pda(&market_pda, 8).address()
+        );
+        assert_eq!(
+            ix.accounts[6].pubkey,
+            resolver.prices_pda(&market_pda, 7).address()
+        );
+    }
+
+    #[test]
+    fn rejects_reference_index_zero() {
+        let ix = update_books_instruction(
+            Pubkey::new_unique(),
+            3,
+            args::UpdateBooks {
+                reference_index: 0,
+                slot: 5,
+            },
+        );
+        assert_eq!(ix, Err(BuildError::ReferenceIndexZero));
+    }
+}
@@ -1,37 +1,87 @@
-use anchor_client::{Program, solana_sdk::signature::Keypair};
+use std::fmt;
+#[cfg(feature = "client")]
+use std::time::Duration;
+
+#[cfg(feature = "client")]
+use anchor_client::Program;
 use anchor_lang::prelude::{instruction::Instruction, *};
-use std::sync::Arc;
+#[cfg(feature = "client")]
+use tracing::{debug, warn};
 
 use crate::{
     AccountResolver,
+    instructions::{BuildError, previous_index, twob_instruction},
     twob_anchor::{self, client::accounts, client::args},
 };
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer,
+    error::Result,
+    fetch_account, fetch_liquidity_position,
+    twob_anchor::accounts::Market,
+    tx::{TxIntent, TxSender},
+    webhooks::LifecycleEvent,
+};
 
-pub fn build_update_liquidity_flows_instruction(
-    program: &Program<Arc<Keypair>>,
+/// How many times [`verify_flows`] reads the position before calling a mismatch, and how
+/// long it waits between reads, since a read may trail the commitment the update
+/// confirmed at.
+#[cfg(feature = "client")]
+const VERIFY_READS: u32 = 3;
+#[cfg(feature = "client")]
+const VERIFY_READ_INTERVAL: Duration = Duration::from_millis(400);
+
+/// A position whose flows aren't the ones just sent, e.g. because a competing update
+/// landed after ours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowMismatch {
+    pub market_id: u64,
+    pub expected_base_flow: u64,
+    pub expected_quote_flow: u64,
+    pub actual_base_flow: u64,
+    pub actual_quote_flow: u64,
+}
+
+impl fmt::Display for FlowMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "market {} flows are {}/{} (base/quote), not the {}/{} sent",
+            self.market_id,
+            self.actual_base_flow,
+            self.actual_quote_flow,
+            self.expected_base_flow,
+            self.expected_quote_flow
+        )
+    }
+}
+
+impl std::error::Error for FlowMismatch {}
+
+/// Build an `update_liquidity_flows` instruction setting the flows of `authority`'s
+/// position on market `market_id`. Nothing in it depends on the market's state, so it
+/// needs no account fetched first.
+pub fn update_liquidity_flows_instruction(
+    authority: Pubkey,
     market_id: u64,
     update_flows_args: args::UpdateLiquidityFlows,
-) -> Instruction {
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(update_flows_args.reference_index)?;
     let resolver = AccountResolver::new(twob_anchor::ID);
 
-    let liquidity_provider = program.payer();
     let market_pda = resolver.market_pda(market_id);
-    let liquidity_position_pda =
-        resolver.liquidity_position_pda(&market_pda.address(), &liquidity_provider);
+    let liquidity_position_pda = resolver.liquidity_position_pda(&market_pda.address(), &authority);
     let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
     let current_exits_pda =
         resolver.exits_pda(&market_pda.address(), update_flows_args.reference_index);
-    let previous_exits_pda =
-        resolver.exits_pda(&market_pda.address(), update_flows_args.reference_index - 1);
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
     let current_prices_pda =
         resolver.prices_pda(&market_pda.address(), update_flows_args.reference_index);
-    let previous_prices_pda =
-        resolver.prices_pda(&market_pda.address(), update_flows_args.reference_index - 1);
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
 
-    program
-        .request()
-        .accounts(accounts::UpdateLiquidityFlows {
-            authority: liquidity_provider,
+    Ok(twob_instruction(
+        accounts::UpdateLiquidityFlows {
+            authority,
             market: market_pda.address(),
             liquidity_position: liquidity_position_pda.address(),
             bookkeeping: bookkeeping_pda.address(),
@@ -40,34 +90,143 @@ pub fn build_update_liquidity_flows_instruction(
             current_prices: current_prices_pda.address(),
             previous_prices: previous_prices_pda.address(),
             system_program: system_program::ID,
-        })
-        .args(update_flows_args)
-        .instructions()
-        .unwrap()
-        .remove(0)
+        },
+        update_flows_args,
+    ))
+}
+
+#[cfg(feature = "client")]
+pub fn build_update_liquidity_flows_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    update_flows_args: args::UpdateLiquidityFlows,
+) -> Result<Instruction> {
+    Ok(update_liquidity_flows_instruction(
+        program.payer(),
+        market_id,
+        update_flows_args,
+    )?)
 }
 
+#[cfg(feature = "client")]
 pub async fn execute_update_flows(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     market_id: u64,
     base_flow: u64,
     quote_flow: u64,
     reference_index: u64,
-    signer: Arc<Keypair>,
-) -> anyhow::Result<()> {
-    let args = args::UpdateLiquidityFlows {
-        reference_index,
-        base_flow_u64: base_flow,
-        quote_flow_u64: quote_flow,
-    };
-    let ix = build_update_liquidity_flows_instruction(program, market_id, args);
-
-    program
-        .request()
-        .instruction(ix)
-        .signer(signer)
-        .send()
+    sender: &TxSender,
+) -> Result<()> {
+    let market_pda = AccountResolver::new(twob_anchor::ID).market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    // Flows don't depend on the window, so one that would land too late is resent as is
+    // for the next.
+    sender
+        .send_for_window(
+            market.end_slot_interval,
+            reference_index,
+            |reference_index| async move {
+                let args = args::UpdateLiquidityFlows {
+                    reference_index,
+                    base_flow_u64: base_flow,
+                    quote_flow_u64: quote_flow,
+                };
+                let ix = build_update_liquidity_flows_instruction(program, market_id, args)?;
+                let intent =
+                    TxIntent::new("update_liquidity_flows", market_id).flows(base_flow, quote_flow);
+                Ok((intent, vec![ix]))
+            },
+        )
         .await?;
+    sender.reporting().fire(
+        market_id,
+        &program.payer(),
+        LifecycleEvent::FlowUpdate {
+            base_flow,
+            quote_flow,
+        },
+    );
 
     Ok(())
 }
+
+/// Re-read the payer's position on `market_id` after a confirmed flow update and check it
+/// holds `base_flow` and `quote_flow`. A mismatch is logged and counted here; what to do
+/// about it is up to the caller.
+#[cfg(feature = "client")]
+pub async fn verify_flows(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    base_flow: u64,
+    quote_flow: u64,
+) -> Result<Option<FlowMismatch>> {
+    let authority = program.payer();
+    let mut reads = 0;
+    let position = loop {
+        reads += 1;
+        let position = fetch_liquidity_position(program, market_id, &authority).await?;
+        if position.base_flow_u64 == base_flow && position.quote_flow_u64 == quote_flow {
+            debug!(
+                event.name = "flow_update_verified",
+                market.id = market_id,
+                verify.reads = reads,
+            );
+            return Ok(None);
+        }
+        if reads == VERIFY_READS {
+            break position;
+        }
+        tokio::time::sleep(VERIFY_READ_INTERVAL).await;
+    };
+    let mismatch = FlowMismatch {
+        market_id,
+        expected_base_flow: base_flow,
+        expected_quote_flow: quote_flow,
+        actual_base_flow: position.base_flow_u64,
+        actual_quote_flow: position.quote_flow_u64,
+    };
+    warn!(
+        event.name = "flow_update_mismatch",
+        market.id = market_id,
+        lp.authority = %authority,
+        flows.expected_base = base_flow,
+        flows.expected_quote = quote_flow,
+        flows.actual_base = position.base_flow_u64,
+        flows.actual_quote = position.quote_flow_u64,
+        monotonic_counter.flow_update_mismatches_total = 1_u64,
+    );
+    Ok(Some(mismatch))
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::InstructionData;
+
+    use super::*;
+
+    #[test]
+    fn builds_without_a_client() {
+        let authority = Pubkey::new_unique();
+        let args = args::UpdateLiquidityFlows {
+            reference_index: 5,
+            base_flow_u64: 10,
+            quote_flow_u64: 20,
+        };
+        let data = args.data();
+        let ix = update_liquidity_flows_instruction(authority, 3, args).unwrap();
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market = resolver.market_pda(3).address();
+
+        assert_eq!(ix.program_id, twob_anchor::ID);
+        assert_eq!(ix.accounts[0].pubkey, authority);
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[1].pubkey, market);
+        assert_eq!(
+            ix.accounts[2].pubkey,
+            resolver
+                .liquidity_position_pda(&market, &authority)
+                .address()
+        );
+        assert_eq!(ix.data, data);
+    }
+}
@@ -1,76 +1,78 @@
-use anchor_client::{Program, solana_sdk::signature::Keypair};
+#[cfg(feature = "client")]
+use anchor_client::Program;
 use anchor_lang::prelude::{instruction::Instruction, *};
 use anchor_spl::associated_token::get_associated_token_address_with_program_id;
-use std::sync::Arc;
 
 use crate::{
-    AccountResolver, get_token_program_id,
+    AccountResolver,
+    instructions::{BuildError, TokenPrograms, previous_index, twob_instruction},
     twob_anchor::{
         self,
         accounts::Market,
         client::{accounts, args},
     },
 };
+#[cfg(feature = "core")]
+use crate::{LiquidityPositionBalances, twob_anchor::accounts::LiquidityPosition};
+#[cfg(feature = "client")]
+use crate::{
+    ProgramPayer,
+    error::Result,
+    fetch_account, fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances,
+    nearest_reference_index,
+    tx::{TxIntent, TxSender},
+    webhooks::LifecycleEvent,
+};
 
-pub async fn build_withdraw_liquidity_instruction(
-    program: &Program<Arc<Keypair>>,
-    market_id: u64,
+/// Build a `withdraw_liquidity` instruction paying out of `authority`'s position on
+/// `market`.
+pub fn withdraw_liquidity_instruction(
+    authority: Pubkey,
+    market: &Market,
+    token_programs: TokenPrograms,
     withdraw_liquidity_args: args::WithdrawLiquidity,
-) -> anyhow::Result<Instruction> {
+) -> std::result::Result<Instruction, BuildError> {
+    let previous = previous_index(withdraw_liquidity_args.reference_index)?;
     let resolver = AccountResolver::new(twob_anchor::ID);
 
-    let liquidity_provider = program.payer();
-    let market_pda = resolver.market_pda(market_id);
-    let market = program.account::<Market>(market_pda.address()).await?;
-
-    let liquidity_position_pda =
-        resolver.liquidity_position_pda(&market_pda.address(), &liquidity_provider);
+    let market_pda = resolver.market_pda(market.id);
+    let liquidity_position_pda = resolver.liquidity_position_pda(&market_pda.address(), &authority);
     let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
     let current_exits_pda = resolver.exits_pda(
         &market_pda.address(),
         withdraw_liquidity_args.reference_index,
     );
-    let previous_exits_pda = resolver.exits_pda(
-        &market_pda.address(),
-        withdraw_liquidity_args.reference_index - 1,
-    );
+    let previous_exits_pda = resolver.exits_pda(&market_pda.address(), previous);
     let current_prices_pda = resolver.prices_pda(
         &market_pda.address(),
         withdraw_liquidity_args.reference_index,
     );
-    let previous_prices_pda = resolver.prices_pda(
-        &market_pda.address(),
-        withdraw_liquidity_args.reference_index - 1,
-    );
-
-    let base_token_program = get_token_program_id(program, &market.base_mint).await?;
-    let quote_token_program = get_token_program_id(program, &market.quote_mint).await?;
+    let previous_prices_pda = resolver.prices_pda(&market_pda.address(), previous);
 
     let authority_base_token_account = get_associated_token_address_with_program_id(
-        &liquidity_provider,
+        &authority,
         &market.base_mint,
-        &base_token_program,
+        &token_programs.base,
     );
     let authority_quote_token_account = get_associated_token_address_with_program_id(
-        &liquidity_provider,
+        &authority,
         &market.quote_mint,
-        &quote_token_program,
+        &token_programs.quote,
     );
     let base_vault = get_associated_token_address_with_program_id(
         &market_pda.address(),
         &market.base_mint,
-        &base_token_program,
+        &token_programs.base,
     );
     let quote_vault = get_associated_token_address_with_program_id(
         &market_pda.address(),
         &market.quote_mint,
-        &quote_token_program,
+        &token_programs.quote,
     );
 
-    Ok(program
-        .request()
-        .accounts(accounts::WithdrawLiquidity {
-            authority: liquidity_provider,
+    Ok(twob_instruction(
+        accounts::WithdrawLiquidity {
+            authority,
             base_mint: market.base_mint,
             quote_mint: market.quote_mint,
             authority_base_token_account,
@@ -84,24 +86,71 @@ pub async fn build_withdraw_liquidity_instruction(
             previous_exits: previous_exits_pda.address(),
             current_prices: current_prices_pda.address(),
             previous_prices: previous_prices_pda.address(),
-            base_token_program,
-            quote_token_program,
+            base_token_program: token_programs.base,
+            quote_token_program: token_programs.quote,
             associated_token_program: anchor_spl::associated_token::ID,
             system_program: system_program::ID,
-        })
-        .args(withdraw_liquidity_args)
-        .instructions()?
-        .remove(0))
+        },
+        withdraw_liquidity_args,
+    ))
+}
+
+/// What to withdraw from `position`, holding `balances`, so it keeps `keep_base` and
+/// `keep_quote` and at least `runway_slots` of its current flow on each side: everything
+/// above that. `None` if that's nothing, if the position has debt, which a withdrawal
+/// would only deepen, or if a side that is flowing would be left with nothing to stream.
+#[cfg(feature = "core")]
+pub fn skim_amounts(
+    balances: &LiquidityPositionBalances,
+    position: &LiquidityPosition,
+    keep_base: u64,
+    keep_quote: u64,
+    runway_slots: u64,
+) -> Option<(u64, u64)> {
+    if balances.base_debt > 0 || balances.quote_debt > 0 {
+        return None;
+    }
+    let keep = |keep: u64, flow: u64| keep.max(flow.saturating_mul(runway_slots));
+    let keep_base = keep(keep_base, position.base_flow_u64);
+    let keep_quote = keep(keep_quote, position.quote_flow_u64);
+    if (position.base_flow_u64 > 0 && keep_base == 0)
+        || (position.quote_flow_u64 > 0 && keep_quote == 0)
+    {
+        return None;
+    }
+    let base = balances.base_balance.saturating_sub(keep_base);
+    let quote = balances.quote_balance.saturating_sub(keep_quote);
+    (base > 0 || quote > 0).then_some((base, quote))
+}
+
+/// Build a `withdraw_liquidity` instruction paying out of the payer's position on
+/// `market_id` into its own token accounts, which the program creates if needed.
+#[cfg(feature = "client")]
+pub async fn build_withdraw_liquidity_instruction(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    withdraw_liquidity_args: args::WithdrawLiquidity,
+) -> Result<Instruction> {
+    let market_pda = AccountResolver::new(twob_anchor::ID).market_pda(market_id);
+    let market = fetch_account::<Market>(program, market_pda.address()).await?;
+    let token_programs = TokenPrograms::fetch(program, &market).await?;
+    Ok(withdraw_liquidity_instruction(
+        program.payer(),
+        &market,
+        token_programs,
+        withdraw_liquidity_args,
+    )?)
 }
 
+#[cfg(feature = "client")]
 pub async fn execute_withdraw_liquidity(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     market_id: u64,
     base_lamports: u64,
     quote_lamports: u64,
     reference_index: u64,
-    signer: Arc<Keypair>,
-) -> anyhow::Result<()> {
+    sender: &TxSender,
+) -> Result<()> {
     let args = args::WithdrawLiquidity {
         reference_index,
         base_lamports,
@@ -109,12 +158,133 @@ pub async fn execute_withdraw_liquidity(
     };
     let ix = build_withdraw_liquidity_instruction(program, market_id, args).await?;
 
-    program
-        .request()
-        .instruction(ix)
-        .signer(signer)
-        .send()
-        .await?;
+    let intent = TxIntent::new("withdraw_liquidity", market_id)
+        .reference_index(reference_index)
+        .amounts(base_lamports, quote_lamports);
+    sender.send_with_intent(&intent, vec![ix]).await?;
+    sender.reporting().fire(
+        market_id,
+        &program.payer(),
+        LifecycleEvent::Withdraw {
+            base_lamports,
+            quote_lamports,
+            reference_index,
+        },
+    );
 
     Ok(())
 }
+
+/// Withdraw whatever the payer's position on `market_id` holds above `keep_base`,
+/// `keep_quote` and `runway_slots` of its current flows, e.g. to take profit off a position
+/// that has grown, leaving it streaming. Returns the amounts withdrawn, `None` if there was
+/// nothing to skim; see [`skim_amounts`].
+#[cfg(feature = "client")]
+pub async fn execute_skim(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    keep_base: u64,
+    keep_quote: u64,
+    runway_slots: u64,
+    sender: &TxSender,
+) -> Result<Option<(u64, u64)>> {
+    let state = fetch_market_state(program, market_id).await?;
+    let position = fetch_liquidity_position(program, market_id, &program.payer()).await?;
+    let balances = get_liquidity_position_balances(
+        program,
+        position,
+        state.bookkeeping,
+        state.market,
+        state.current_slot,
+    )
+    .await?;
+    let Some((base_lamports, quote_lamports)) =
+        skim_amounts(&balances, &position, keep_base, keep_quote, runway_slots)
+    else {
+        return Ok(None);
+    };
+
+    execute_withdraw_liquidity(
+        program,
+        market_id,
+        base_lamports,
+        quote_lamports,
+        nearest_reference_index(state.current_slot, state.market.end_slot_interval),
+        sender,
+    )
+    .await?;
+    Ok(Some((base_lamports, quote_lamports)))
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use super::*;
+
+    fn balances(base: u64, quote: u64) -> LiquidityPositionBalances {
+        LiquidityPositionBalances {
+            base_balance: base,
+            quote_balance: quote,
+            base_debt: 0,
+            quote_debt: 0,
+        }
+    }
+
+    fn position(base_flow: u64, quote_flow: u64) -> LiquidityPosition {
+        LiquidityPosition {
+            authority: Pubkey::new_unique(),
+            base_balance: 0,
+            quote_balance: 0,
+            base_per_quote_snapshot: 0,
+            quote_per_base_snapshot: 0,
+            slots_without_trade_snapshot: 0,
+            base_flow_u64: base_flow,
+            quote_flow_u64: quote_flow,
+            base_debt: 0,
+            quote_debt: 0,
+            last_update_slot: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn skims_what_is_above_the_kept_balances() {
+        let idle = position(0, 0);
+        assert_eq!(
+            skim_amounts(&balances(150, 80), &idle, 100, 100, 0),
+            Some((50, 0))
+        );
+        assert_eq!(
+            skim_amounts(&balances(150, 300), &idle, 0, 100, 0),
+            Some((150, 200))
+        );
+        assert_eq!(skim_amounts(&balances(100, 80), &idle, 100, 100, 0), None);
+
+        let mut in_debt = balances(500, 0);
+        in_debt.quote_debt = 1;
+        assert_eq!(skim_amounts(&in_debt, &idle, 100, 0, 0), None);
+    }
+
+    #[test]
+    fn keeps_the_runway_of_a_streaming_position() {
+        // Streams 2 base and 5 quote a slot.
+        let streaming = position(2, 5);
+        // 100 slots of runway is 200 base and 500 quote, above what was asked to keep.
+        assert_eq!(
+            skim_amounts(&balances(1_000, 1_000), &streaming, 0, 0, 100),
+            Some((800, 500))
+        );
+        assert_eq!(
+            skim_amounts(&balances(1_000, 1_000), &streaming, 300, 0, 100),
+            Some((700, 500))
+        );
+        // Without a runway, emptying a flowing side would put it straight into debt.
+        assert_eq!(
+            skim_amounts(&balances(1_000, 1_000), &streaming, 0, 0, 0),
+            None
+        );
+        assert_eq!(
+            skim_amounts(&balances(1_000, 1_000), &position(0, 5), 100, 0, 0),
+            None
+        );
+    }
+}
@@ -0,0 +1,563 @@
+//! Double-entry ledger of everything that moves a wallet's tokens.
+//!
+//! Every deposit, withdrawal, order, fill, swap and fee becomes an [`Entry`] whose postings
+//! sum to zero per asset: tokens leaving the wallet arrive in a liquidity position, a
+//! counterparty or an expense account, so the books always balance. Balances of the
+//! accounts that exist on chain (the wallet and its positions) can then be
+//! [`reconcile`]d against what the chain reports.
+//!
+//! Entries are derived from the wallet's transactions ([`transaction_entries`]). Fills are
+//! not individually visible on chain, so a position's balance change that no transaction
+//! explains is booked against its market as a fill ([`settlement_entry`]). The [`tax`]
+//! module turns the books into realized gains and tax tool imports.
+
+pub mod store;
+pub mod tax;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+
+use anchor_lang::prelude::Pubkey;
+
+use crate::{
+    decode::TwobInstruction,
+    ingest::IndexedTransaction,
+    portfolio::{HoldingKind, Portfolio},
+};
+
+pub use store::LedgerStore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Asset {
+    /// Native SOL held as lamports.
+    Sol,
+    Token(Pubkey),
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Asset::Sol => f.write_str("SOL"),
+            Asset::Token(mint) => write!(f, "{mint}"),
+        }
+    }
+}
+
+impl FromStr for Asset {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "SOL" => Ok(Asset::Sol),
+            mint => Ok(Asset::Token(mint.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid ledger asset `{mint}`: {e}")
+            })?)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerAccount {
+    /// Tokens and lamports in the owner's wallet and its associated token accounts.
+    Wallet,
+    LiquidityPosition(u64),
+    /// Every trade position the owner holds in a market.
+    TradePositions(u64),
+    /// Idle quote parked in a lending venue.
+    Lending,
+    /// The market's other participants, counterparty to fills.
+    Market(u64),
+    /// Counterparty to swaps made outside twob.
+    Swaps,
+    /// Transaction fees.
+    Fees,
+    /// Opening balances, transfers in and out, and rent.
+    Equity,
+}
+
+impl LedgerAccount {
+    /// Whether the chain holds a balance to reconcile this account against.
+    pub fn is_on_chain(self) -> bool {
+        matches!(
+            self,
+            LedgerAccount::Wallet
+                | LedgerAccount::LiquidityPosition(_)
+                | LedgerAccount::TradePositions(_)
+                | LedgerAccount::Lending
+        )
+    }
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerAccount::Wallet => f.write_str("wallet"),
+            LedgerAccount::LiquidityPosition(market_id) => {
+                write!(f, "liquidity_position:{market_id}")
+            }
+            LedgerAccount::TradePositions(market_id) => write!(f, "trade_positions:{market_id}"),
+            LedgerAccount::Lending => f.write_str("lending"),
+            LedgerAccount::Market(market_id) => write!(f, "market:{market_id}"),
+            LedgerAccount::Swaps => f.write_str("swaps"),
+            LedgerAccount::Fees => f.write_str("fees"),
+            LedgerAccount::Equity => f.write_str("equity"),
+        }
+    }
+}
+
+impl FromStr for LedgerAccount {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let account = match value.split_once(':') {
+            None => match value {
+                "wallet" => LedgerAccount::Wallet,
+                "lending" => LedgerAccount::Lending,
+                "swaps" => LedgerAccount::Swaps,
+                "fees" => LedgerAccount::Fees,
+                "equity" => LedgerAccount::Equity,
+                _ => anyhow::bail!("Invalid ledger account `{value}`"),
+            },
+            Some((name, market_id)) => {
+                let market_id = market_id.parse()?;
+                match name {
+                    "liquidity_position" => LedgerAccount::LiquidityPosition(market_id),
+                    "trade_positions" => LedgerAccount::TradePositions(market_id),
+                    "market" => LedgerAccount::Market(market_id),
+                    _ => anyhow::bail!("Invalid ledger account `{value}`"),
+                }
+            }
+        };
+        Ok(account)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Opening,
+    Deposit,
+    Withdrawal,
+    Order,
+    Fill,
+    Swap,
+    Fee,
+    Transfer,
+}
+
+impl EntryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::Opening => "opening",
+            EntryKind::Deposit => "deposit",
+            EntryKind::Withdrawal => "withdrawal",
+            EntryKind::Order => "order",
+            EntryKind::Fill => "fill",
+            EntryKind::Swap => "swap",
+            EntryKind::Fee => "fee",
+            EntryKind::Transfer => "transfer",
+        }
+    }
+}
+
+impl FromStr for EntryKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        Ok(match value {
+            "opening" => EntryKind::Opening,
+            "deposit" => EntryKind::Deposit,
+            "withdrawal" => EntryKind::Withdrawal,
+            "order" => EntryKind::Order,
+            "fill" => EntryKind::Fill,
+            "swap" => EntryKind::Swap,
+            "fee" => EntryKind::Fee,
+            "transfer" => EntryKind::Transfer,
+            _ => anyhow::bail!("Invalid ledger entry kind `{value}`"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Posting {
+    pub account: LedgerAccount,
+    pub asset: Asset,
+    /// Raw units (lamports for SOL); positive adds to the account.
+    pub amount: i128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub kind: EntryKind,
+    pub owner: Pubkey,
+    pub slot: u64,
+    pub timestamp: i64,
+    pub signature: Option<String>,
+    pub postings: Vec<Posting>,
+}
+
+impl Entry {
+    pub fn new(
+        kind: EntryKind,
+        owner: Pubkey,
+        slot: u64,
+        timestamp: i64,
+        signature: Option<String>,
+    ) -> Self {
+        Self {
+            kind,
+            owner,
+            slot,
+            timestamp,
+            signature,
+            postings: Vec::new(),
+        }
+    }
+
+    /// Move `amount` of `asset` from one account to another. Zero amounts post nothing.
+    pub fn transfer(&mut self, from: LedgerAccount, to: LedgerAccount, asset: Asset, amount: i128) {
+        if amount == 0 {
+            return;
+        }
+        self.postings.push(Posting {
+            account: from,
+            asset,
+            amount: -amount,
+        });
+        self.postings.push(Posting {
+            account: to,
+            asset,
+            amount,
+        });
+    }
+
+    /// Whether the postings sum to zero for every asset.
+    pub fn is_balanced(&self) -> bool {
+        let mut sums = BTreeMap::<Asset, i128>::new();
+        for posting in &self.postings {
+            *sums.entry(posting.asset).or_default() += posting.amount;
+        }
+        sums.values().all(|sum| *sum == 0)
+    }
+}
+
+pub type Balances = BTreeMap<(LedgerAccount, Asset), i128>;
+
+/// The twob markets the ledger books position movements for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedgerMarket {
+    pub market_id: u64,
+    /// The market's PDA.
+    pub address: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+}
+
+/// Entries for what one transaction did to `owner`'s wallet.
+///
+/// The fee is booked when `owner` paid it; any other lamport change (rent, transfers) goes
+/// to equity. Token changes are booked against a position when the transaction ran a twob
+/// instruction on one of `markets`, against swaps when tokens went both ways, and against
+/// equity otherwise.
+pub fn transaction_entries(
+    owner: &Pubkey,
+    transaction: &IndexedTransaction,
+    markets: &[LedgerMarket],
+) -> Vec<Entry> {
+    let timestamp = transaction.block_time.unwrap_or_default();
+    let entry = |kind| {
+        Entry::new(
+            kind,
+            *owner,
+            transaction.slot,
+            timestamp,
+            Some(transaction.signature.clone()),
+        )
+    };
+    let mut entries = Vec::new();
+
+    let mut lamports = transaction
+        .lamport_changes
+        .iter()
+        .filter(|(key, _)| key == owner)
+        .map(|(_, change)| i128::from(*change))
+        .sum::<i128>();
+    if transaction.fee_payer == *owner && transaction.cost.fee > 0 {
+        let mut fee = entry(EntryKind::Fee);
+        let amount = i128::from(transaction.cost.fee);
+        fee.transfer(
+            LedgerAccount::Wallet,
+            LedgerAccount::Fees,
+            Asset::Sol,
+            amount,
+        );
+        entries.push(fee);
+        lamports += amount;
+    }
+    if lamports != 0 {
+        let mut transfer = entry(EntryKind::Transfer);
+        transfer.transfer(
+            LedgerAccount::Equity,
+            LedgerAccount::Wallet,
+            Asset::Sol,
+            lamports,
+        );
+        entries.push(transfer);
+    }
+
+    let changes = transaction
+        .token_changes
+        .iter()
+        .filter(|change| change.owner == *owner)
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        return entries;
+    }
+    let position = transaction.instructions.iter().find_map(|ix| {
+        let market = markets
+            .iter()
+            .find(|market| ix.accounts.contains(&market.address))?;
+        match ix.instruction {
+            TwobInstruction::ProvideLiquidity { .. }
+            | TwobInstruction::AddLiquidity { .. }
+            | TwobInstruction::WithdrawLiquidity { .. }
+            | TwobInstruction::PublicStopLiquidityPosition { .. }
+            | TwobInstruction::AuthorityCloseLiquidityPosition { .. } => {
+                Some(LedgerAccount::LiquidityPosition(market.market_id))
+            }
+            TwobInstruction::SubmitOrder { .. }
+            | TwobInstruction::AuthorityClosePosition { .. }
+            | TwobInstruction::PublicClosePosition { .. } => {
+                Some(LedgerAccount::TradePositions(market.market_id))
+            }
+            _ => None,
+        }
+    });
+    let spent = changes.iter().any(|change| change.change < 0);
+    let received = changes.iter().any(|change| change.change > 0);
+    let (kind, counterparty) = match position {
+        Some(account @ LedgerAccount::TradePositions(_)) => (EntryKind::Order, account),
+        Some(account) if spent && !received => (EntryKind::Deposit, account),
+        Some(account) => (EntryKind::Withdrawal, account),
+        None if spent && received => (EntryKind::Swap, LedgerAccount::Swaps),
+        None => (EntryKind::Transfer, LedgerAccount::Equity),
+    };
+    let mut tokens = entry(kind);
+    for change in changes {
+        tokens.transfer(
+            counterparty,
+            LedgerAccount::Wallet,
+            Asset::Token(change.mint),
+            change.change,
+        );
+    }
+    entries.push(tokens);
+    entries
+}
+
+/// Book the difference between `account`'s balances on chain and in the ledger against
+/// the market as a fill. Returns `None` when they already agree.
+pub fn settlement_entry(
+    owner: &Pubkey,
+    slot: u64,
+    timestamp: i64,
+    account: LedgerAccount,
+    market_id: u64,
+    ledger: &Balances,
+    chain: &Balances,
+) -> Option<Entry> {
+    let mut entry = Entry::new(EntryKind::Fill, *owner, slot, timestamp, None);
+    let assets = ledger
+        .keys()
+        .chain(chain.keys())
+        .filter(|(key_account, _)| *key_account == account)
+        .map(|(_, asset)| *asset)
+        .collect::<BTreeSet<_>>();
+    for asset in assets {
+        let difference = chain.get(&(account, asset)).copied().unwrap_or_default()
+            - ledger.get(&(account, asset)).copied().unwrap_or_default();
+        entry.transfer(LedgerAccount::Market(market_id), account, asset, difference);
+    }
+    (!entry.postings.is_empty()).then_some(entry)
+}
+
+/// Balances the chain reports for a wallet holding `lamports` and the holdings in
+/// `portfolio`, keyed like the ledger's.
+pub fn chain_balances(portfolio: &Portfolio, lamports: u64) -> Balances {
+    let mut balances = Balances::new();
+    balances.insert((LedgerAccount::Wallet, Asset::Sol), i128::from(lamports));
+    for holding in &portfolio.holdings {
+        let Some(market) = portfolio.markets.get(&holding.market_id) else {
+            continue;
+        };
+        let account = match holding.kind {
+            HoldingKind::Wallet => LedgerAccount::Wallet,
+            HoldingKind::LiquidityPosition { .. } => {
+                LedgerAccount::LiquidityPosition(holding.market_id)
+            }
+            HoldingKind::TradePosition { .. } => LedgerAccount::TradePositions(holding.market_id),
+            HoldingKind::Lending => LedgerAccount::Lending,
+        };
+        for (mint, amount) in [
+            (market.base_mint, holding.base),
+            (market.quote_mint, holding.quote),
+        ] {
+            if amount > 0 {
+                *balances.entry((account, Asset::Token(mint))).or_default() += i128::from(amount);
+            }
+        }
+    }
+    balances
+}
+
+/// An on-chain account whose ledger balance is off by more than the tolerance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Discrepancy {
+    pub account: LedgerAccount,
+    pub asset: Asset,
+    pub ledger: i128,
+    pub chain: i128,
+}
+
+impl Discrepancy {
+    pub fn difference(&self) -> i128 {
+        self.chain - self.ledger
+    }
+}
+
+/// Compare every on-chain account's ledger balance with the chain's. Missing balances
+/// count as zero.
+pub fn reconcile(ledger: &Balances, chain: &Balances, tolerance: u64) -> Vec<Discrepancy> {
+    let keys = ledger
+        .keys()
+        .chain(chain.keys())
+        .filter(|(account, _)| account.is_on_chain())
+        .copied()
+        .collect::<BTreeSet<_>>();
+    keys.into_iter()
+        .map(|(account, asset)| Discrepancy {
+            account,
+            asset,
+            ledger: ledger.get(&(account, asset)).copied().unwrap_or_default(),
+            chain: chain.get(&(account, asset)).copied().unwrap_or_default(),
+        })
+        .filter(|discrepancy| discrepancy.difference().unsigned_abs() > u128::from(tolerance))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::{IndexedInstruction, TokenBalanceChange, TransactionCost};
+
+    fn transaction(owner: Pubkey, market: &LedgerMarket) -> IndexedTransaction {
+        IndexedTransaction {
+            signature: "sig".to_string(),
+            slot: 10,
+            block_time: Some(1_700_000_000),
+            success: true,
+            fee_payer: owner,
+            cost: TransactionCost {
+                fee: 10_000,
+                priority_fee: 5_000,
+                rent: 0,
+            },
+            lamport_changes: vec![(owner, -10_000)],
+            token_changes: vec![
+                TokenBalanceChange {
+                    owner,
+                    mint: market.base_mint,
+                    change: -1_000,
+                },
+                TokenBalanceChange {
+                    owner,
+                    mint: market.quote_mint,
+                    change: -2_000,
+                },
+            ],
+            instructions: vec![IndexedInstruction {
+                index: 0,
+                instruction: TwobInstruction::AddLiquidity {
+                    reference_index: 1,
+                    base_lamports: 1_000,
+                    quote_lamports: 2_000,
+                },
+                accounts: vec![owner, market.address],
+            }],
+            events: Vec::new(),
+        }
+    }
+
+    fn market() -> LedgerMarket {
+        LedgerMarket {
+            market_id: 1,
+            address: Pubkey::new_unique(),
+            base_mint: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+        }
+    }
+
+    fn balances_of(entries: &[Entry]) -> Balances {
+        let mut balances = Balances::new();
+        for posting in entries.iter().flat_map(|entry| &entry.postings) {
+            *balances
+                .entry((posting.account, posting.asset))
+                .or_default() += posting.amount;
+        }
+        balances
+    }
+
+    #[test]
+    fn books_fee_and_deposit_as_balanced_entries() {
+        let owner = Pubkey::new_unique();
+        let market = market();
+
+        let entries = transaction_entries(&owner, &transaction(owner, &market), &[market]);
+
+        assert_eq!(
+            entries.iter().map(|entry| entry.kind).collect::<Vec<_>>(),
+            vec![EntryKind::Fee, EntryKind::Deposit]
+        );
+        assert!(entries.iter().all(Entry::is_balanced));
+        let balances = balances_of(&entries);
+        assert_eq!(balances[&(LedgerAccount::Fees, Asset::Sol)], 10_000);
+        assert_eq!(balances[&(LedgerAccount::Wallet, Asset::Sol)], -10_000);
+        assert_eq!(
+            balances[&(
+                LedgerAccount::LiquidityPosition(1),
+                Asset::Token(market.quote_mint)
+            )],
+            2_000
+        );
+    }
+
+    #[test]
+    fn classifies_swaps_outside_twob_and_books_fills_from_chain() {
+        let owner = Pubkey::new_unique();
+        let market = market();
+        let mut swap = transaction(owner, &market);
+        swap.instructions.clear();
+        swap.token_changes[1].change = 150_000;
+
+        let entries = transaction_entries(&owner, &swap, &[market]);
+        assert_eq!(entries.last().unwrap().kind, EntryKind::Swap);
+
+        let account = LedgerAccount::LiquidityPosition(1);
+        let base = Asset::Token(market.base_mint);
+        let quote = Asset::Token(market.quote_mint);
+        let ledger = Balances::from([((account, base), 1_000), ((account, quote), 2_000)]);
+        let chain = Balances::from([((account, base), 800), ((account, quote), 2_300)]);
+
+        let fill = settlement_entry(&owner, 20, 0, account, 1, &ledger, &chain).unwrap();
+        assert!(fill.is_balanced());
+        let mut settled = ledger.clone();
+        for posting in &fill.postings {
+            *settled.entry((posting.account, posting.asset)).or_default() += posting.amount;
+        }
+        assert!(reconcile(&settled, &chain, 0).is_empty());
+        assert_eq!(reconcile(&ledger, &chain, 250).len(), 1);
+        assert!(settlement_entry(&owner, 20, 0, account, 1, &chain, &chain).is_none());
+    }
+}
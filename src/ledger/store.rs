@@ -0,0 +1,264 @@
+use anchor_lang::prelude::Pubkey;
+use anyhow::Context;
+use rusqlite::{Connection, OptionalExtension, params};
+
+use super::{Asset, Balances, Entry, LedgerAccount, Posting};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS ledger_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    signature TEXT
+);
+CREATE INDEX IF NOT EXISTS ledger_entries_owner_time
+    ON ledger_entries (owner, timestamp);
+CREATE TABLE IF NOT EXISTS ledger_postings (
+    entry_id INTEGER NOT NULL REFERENCES ledger_entries (id),
+    account TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    amount INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS ledger_postings_entry ON ledger_postings (entry_id);
+CREATE TABLE IF NOT EXISTS ledger_cursors (
+    owner TEXT PRIMARY KEY,
+    signature TEXT NOT NULL
+);
+";
+
+/// Ledger entries persisted in sqlite.
+pub struct LedgerStore {
+    conn: Connection,
+}
+
+impl LedgerStore {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open ledger database at {}", path))?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to initialize ledger database schema")?;
+        Ok(Self { conn })
+    }
+
+    /// Record `entries` and, with them, move `owner`'s cursor to `cursor`. Nothing is
+    /// written if any entry doesn't balance.
+    pub fn record(
+        &self,
+        owner: &Pubkey,
+        entries: &[Entry],
+        cursor: Option<&str>,
+    ) -> anyhow::Result<()> {
+        if let Some(entry) = entries.iter().find(|entry| !entry.is_balanced()) {
+            anyhow::bail!(
+                "Refusing to record unbalanced {} entry at slot {}",
+                entry.kind.as_str(),
+                entry.slot
+            );
+        }
+
+        let transaction = self.conn.unchecked_transaction()?;
+        for entry in entries {
+            transaction.execute(
+                "INSERT INTO ledger_entries (owner, kind, slot, timestamp, signature)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    entry.owner.to_string(),
+                    entry.kind.as_str(),
+                    entry.slot as i64,
+                    entry.timestamp,
+                    entry.signature,
+                ],
+            )?;
+            let entry_id = transaction.last_insert_rowid();
+            for posting in &entry.postings {
+                transaction.execute(
+                    "INSERT INTO ledger_postings (entry_id, account, asset, amount)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        entry_id,
+                        posting.account.to_string(),
+                        posting.asset.to_string(),
+                        i64::try_from(posting.amount)
+                            .context("Ledger posting amount out of range")?,
+                    ],
+                )?;
+            }
+        }
+        if let Some(signature) = cursor {
+            transaction.execute(
+                "INSERT INTO ledger_cursors (owner, signature) VALUES (?1, ?2)
+                 ON CONFLICT (owner) DO UPDATE SET signature = excluded.signature",
+                params![owner.to_string(), signature],
+            )?;
+        }
+        transaction
+            .commit()
+            .context("Failed to record ledger entries")?;
+        Ok(())
+    }
+
+    /// Newest of `owner`'s signatures already booked; `None` before the opening entry.
+    pub fn cursor(&self, owner: &Pubkey) -> anyhow::Result<Option<String>> {
+        self.conn
+            .query_row(
+                "SELECT signature FROM ledger_cursors WHERE owner = ?1",
+                params![owner.to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to read ledger cursor")
+    }
+
+    /// Whether `owner` has any entries yet.
+    pub fn is_open(&self, owner: &Pubkey) -> anyhow::Result<bool> {
+        let entries: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM ledger_entries WHERE owner = ?1",
+            params![owner.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(entries > 0)
+    }
+
+    /// Every account's balance per asset for `owner`.
+    pub fn balances(&self, owner: &Pubkey) -> anyhow::Result<Balances> {
+        let mut statement = self.conn.prepare(
+            "SELECT p.account, p.asset, SUM(p.amount)
+             FROM ledger_postings p JOIN ledger_entries e ON e.id = p.entry_id
+             WHERE e.owner = ?1
+             GROUP BY p.account, p.asset",
+        )?;
+        let rows = statement.query_map(params![owner.to_string()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        let mut balances = Balances::new();
+        for row in rows {
+            let (account, asset, amount) = row.context("Failed to read ledger balances")?;
+            balances.insert(
+                (account.parse::<LedgerAccount>()?, asset.parse::<Asset>()?),
+                i128::from(amount),
+            );
+        }
+        Ok(balances)
+    }
+
+    /// Every entry of `owner` with its postings, in the order they were recorded.
+    pub fn entries(&self, owner: &Pubkey) -> anyhow::Result<Vec<Entry>> {
+        let mut statement = self.conn.prepare(
+            "SELECT e.id, e.kind, e.slot, e.timestamp, e.signature,
+                    p.account, p.asset, p.amount
+             FROM ledger_entries e LEFT JOIN ledger_postings p ON p.entry_id = e.id
+             WHERE e.owner = ?1
+             ORDER BY e.id ASC, p.rowid ASC",
+        )?;
+        let rows = statement.query_map(params![owner.to_string()], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, Option<String>>(6)?,
+                row.get::<_, Option<i64>>(7)?,
+            ))
+        })?;
+
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut last_id = None;
+        for row in rows {
+            let (id, kind, slot, timestamp, signature, account, asset, amount) =
+                row.context("Failed to read ledger entries")?;
+            if last_id != Some(id) {
+                last_id = Some(id);
+                entries.push(Entry::new(
+                    kind.parse()?,
+                    *owner,
+                    slot as u64,
+                    timestamp,
+                    signature,
+                ));
+            }
+            if let (Some(account), Some(asset), Some(amount), Some(entry)) =
+                (account, asset, amount, entries.last_mut())
+            {
+                entry.postings.push(Posting {
+                    account: account.parse()?,
+                    asset: asset.parse()?,
+                    amount: i128::from(amount),
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Ids of `owner`'s entries whose stored postings don't sum to zero per asset.
+    pub fn unbalanced_entries(&self, owner: &Pubkey) -> anyhow::Result<Vec<i64>> {
+        let mut statement = self.conn.prepare(
+            "SELECT DISTINCT p.entry_id
+             FROM ledger_postings p JOIN ledger_entries e ON e.id = p.entry_id
+             WHERE e.owner = ?1
+             GROUP BY p.entry_id, p.asset
+             HAVING SUM(p.amount) != 0
+             ORDER BY p.entry_id",
+        )?;
+        let rows = statement.query_map(params![owner.to_string()], |row| row.get(0))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to check ledger entries")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::EntryKind;
+
+    #[test]
+    fn records_balanced_entries_atomically_with_the_cursor() {
+        let store = LedgerStore::open(":memory:").unwrap();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+
+        let mut opening = Entry::new(EntryKind::Opening, owner, 1, 0, None);
+        opening.transfer(
+            LedgerAccount::Equity,
+            LedgerAccount::Wallet,
+            Asset::Sol,
+            1_000,
+        );
+        opening.transfer(
+            LedgerAccount::Equity,
+            LedgerAccount::LiquidityPosition(1),
+            Asset::Token(mint),
+            500,
+        );
+        let mut unbalanced = Entry::new(EntryKind::Fee, owner, 2, 0, Some("b".to_string()));
+        unbalanced.postings.push(Posting {
+            account: LedgerAccount::Fees,
+            asset: Asset::Sol,
+            amount: 5,
+        });
+
+        assert!(!store.is_open(&owner).unwrap());
+        store.record(&owner, &[opening], Some("a")).unwrap();
+        assert!(store.record(&owner, &[unbalanced], Some("b")).is_err());
+
+        assert!(store.is_open(&owner).unwrap());
+        assert_eq!(store.cursor(&owner).unwrap().as_deref(), Some("a"));
+        assert!(store.unbalanced_entries(&owner).unwrap().is_empty());
+        let balances = store.balances(&owner).unwrap();
+        assert_eq!(balances[&(LedgerAccount::Wallet, Asset::Sol)], 1_000);
+        assert_eq!(balances[&(LedgerAccount::Equity, Asset::Token(mint))], -500);
+        assert_eq!(balances.len(), 4);
+
+        let entries = store.entries(&owner).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, EntryKind::Opening);
+        assert_eq!(entries[0].postings.len(), 4);
+    }
+}
@@ -0,0 +1,527 @@
+//! Tax and accounting exports derived from ledger entries.
+//!
+//! Entries are reduced to what the owner gained and gave up: movements between the wallet
+//! and its own positions (deposits, withdrawals, orders) are inventory transfers and never
+//! taxable, while fills, swaps, fees and outside transfers become [`TaxRow`]s written in the
+//! CSV layouts tax tools import. Realized gains match disposals against acquisitions
+//! first-in first-out, valued in a numeraire asset (the markets' quote token).
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::Write,
+    str::FromStr,
+};
+
+use chrono::DateTime;
+
+use super::{Asset, Entry, EntryKind, LedgerAccount};
+
+/// How an asset is named and scaled in exports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetInfo {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// CSV layouts understood by common crypto tax tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaxFormat {
+    /// Koinly's universal template.
+    Koinly,
+    /// CoinTracker's generic CSV import.
+    CoinTracker,
+}
+
+impl FromStr for TaxFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "koinly" => Ok(TaxFormat::Koinly),
+            "cointracker" => Ok(TaxFormat::CoinTracker),
+            other => anyhow::bail!("Invalid tax format `{other}`; expected koinly or cointracker"),
+        }
+    }
+}
+
+/// One taxable event: what left and entered the owner's holdings, and the fee paid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaxRow {
+    pub timestamp: i64,
+    pub kind: EntryKind,
+    pub sent: Option<(Asset, i128)>,
+    pub received: Option<(Asset, i128)>,
+    pub fee: Option<(Asset, i128)>,
+    pub signature: Option<String>,
+}
+
+/// The owner's net change per asset across the accounts it holds on chain.
+fn owner_changes(entry: &Entry) -> BTreeMap<Asset, i128> {
+    let mut changes = BTreeMap::<Asset, i128>::new();
+    for posting in entry
+        .postings
+        .iter()
+        .filter(|posting| posting.account.is_on_chain())
+    {
+        *changes.entry(posting.asset).or_default() += posting.amount;
+    }
+    changes.retain(|_, change| *change != 0);
+    changes
+}
+
+/// Whether an entry only moves tokens between the owner's wallet and its positions.
+pub fn is_inventory_transfer(entry: &Entry) -> bool {
+    matches!(
+        entry.kind,
+        EntryKind::Deposit | EntryKind::Withdrawal | EntryKind::Order
+    )
+}
+
+/// Taxable rows for `entries`, oldest first. A fee paid by the same transaction as a trade
+/// or transfer is folded into that row.
+pub fn tax_rows(entries: &[Entry]) -> Vec<TaxRow> {
+    let mut rows: Vec<TaxRow> = Vec::new();
+    let mut pending_fees: Vec<TaxRow> = Vec::new();
+    for entry in entries {
+        if entry.kind == EntryKind::Opening || is_inventory_transfer(entry) {
+            continue;
+        }
+        let changes = owner_changes(entry);
+        let sent = changes
+            .iter()
+            .find(|(_, change)| **change < 0)
+            .map(|(asset, change)| (*asset, -change));
+        let received = changes
+            .iter()
+            .find(|(_, change)| **change > 0)
+            .map(|(asset, change)| (*asset, *change));
+        let mut row = TaxRow {
+            timestamp: entry.timestamp,
+            kind: entry.kind,
+            sent,
+            received,
+            fee: None,
+            signature: entry.signature.clone(),
+        };
+        if entry.kind == EntryKind::Fee {
+            row.fee = row.sent.take();
+            pending_fees.push(row);
+            continue;
+        }
+        if sent.is_none() && received.is_none() {
+            continue;
+        }
+        if let Some(index) = pending_fees
+            .iter()
+            .position(|fee| fee.signature.is_some() && fee.signature == row.signature)
+        {
+            row.fee = pending_fees.remove(index).fee;
+        }
+        rows.push(row);
+    }
+    rows.extend(pending_fees);
+    rows.sort_by_key(|row| row.timestamp);
+    rows
+}
+
+/// A sale of part of an asset's inventory for the numeraire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disposal {
+    pub timestamp: i64,
+    pub asset: Asset,
+    pub quantity: i128,
+    /// Numeraire received, raw.
+    pub proceeds: i128,
+    /// Numeraire paid for the matched lots, raw.
+    pub cost_basis: i128,
+    /// Part of `quantity` matched against lots without a known cost (opening balances and
+    /// transfers in), left out of `cost_basis`.
+    pub unknown_basis_quantity: i128,
+}
+
+impl Disposal {
+    /// Gain on the part with a known basis, in raw numeraire units.
+    pub fn gain(&self) -> i128 {
+        let known = self.quantity - self.unknown_basis_quantity;
+        if self.quantity == 0 {
+            return 0;
+        }
+        self.proceeds * known / self.quantity - self.cost_basis
+    }
+}
+
+struct Lot {
+    quantity: i128,
+    /// Numeraire paid per lot, `None` when unknown.
+    cost: Option<i128>,
+}
+
+/// Match every disposal of a non-numeraire asset against earlier acquisitions, first in
+/// first out. Only trades against `numeraire` are priced; anything else moving an asset in
+/// or out adds or removes lots without a gain.
+pub fn realized_gains(entries: &[Entry], numeraire: Asset) -> Vec<Disposal> {
+    let mut lots = BTreeMap::<Asset, VecDeque<Lot>>::new();
+    let mut disposals = Vec::new();
+    for entry in entries {
+        if is_inventory_transfer(entry) {
+            continue;
+        }
+        let changes = owner_changes(entry);
+        let numeraire_change = changes.get(&numeraire).copied().unwrap_or_default();
+        for (&asset, &change) in changes.iter().filter(|(asset, _)| **asset != numeraire) {
+            let traded = match entry.kind {
+                EntryKind::Fill | EntryKind::Swap => numeraire_change.signum() == -change.signum(),
+                _ => false,
+            };
+            let inventory = lots.entry(asset).or_default();
+            if change > 0 {
+                inventory.push_back(Lot {
+                    quantity: change,
+                    cost: traded.then_some(-numeraire_change),
+                });
+                continue;
+            }
+
+            let quantity = -change;
+            let mut remaining = quantity;
+            let mut cost_basis = 0;
+            let mut unknown_basis_quantity = 0;
+            while remaining > 0 {
+                let Some(lot) = inventory.front_mut() else {
+                    unknown_basis_quantity += remaining;
+                    break;
+                };
+                let matched = remaining.min(lot.quantity);
+                match lot.cost {
+                    Some(cost) => {
+                        let share = cost * matched / lot.quantity;
+                        cost_basis += share;
+                        lot.cost = Some(cost - share);
+                    }
+                    None => unknown_basis_quantity += matched,
+                }
+                lot.quantity -= matched;
+                remaining -= matched;
+                if lot.quantity == 0 {
+                    inventory.pop_front();
+                }
+            }
+            if traded {
+                disposals.push(Disposal {
+                    timestamp: entry.timestamp,
+                    asset,
+                    quantity,
+                    proceeds: numeraire_change,
+                    cost_basis,
+                    unknown_basis_quantity,
+                });
+            }
+        }
+    }
+    disposals
+}
+
+/// Totals for one reporting period.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeriodSummary {
+    pub disposals: usize,
+    pub proceeds: i128,
+    pub cost_basis: i128,
+    pub realized_gain: i128,
+    pub fees: BTreeMap<Asset, i128>,
+    /// Net amount moved from the wallet into its positions (negative: out of them).
+    pub inventory_transfers: BTreeMap<Asset, i128>,
+}
+
+/// Summarize entries and disposals with timestamps in `[from, to)`.
+pub fn summarize_period(
+    entries: &[Entry],
+    disposals: &[Disposal],
+    from: i64,
+    to: i64,
+) -> PeriodSummary {
+    let in_period = |timestamp: i64| timestamp >= from && timestamp < to;
+    let mut summary = PeriodSummary::default();
+    for disposal in disposals.iter().filter(|d| in_period(d.timestamp)) {
+        summary.disposals += 1;
+        summary.proceeds += disposal.proceeds;
+        summary.cost_basis += disposal.cost_basis;
+        summary.realized_gain += disposal.gain();
+    }
+    for entry in entries.iter().filter(|entry| in_period(entry.timestamp)) {
+        let target = match entry.kind {
+            EntryKind::Fee => &mut summary.fees,
+            _ if is_inventory_transfer(entry) => &mut summary.inventory_transfers,
+            _ => continue,
+        };
+        for posting in &entry.postings {
+            let into_position = match entry.kind {
+                EntryKind::Fee => posting.account == LedgerAccount::Fees,
+                _ => posting.account.is_on_chain() && posting.account != LedgerAccount::Wallet,
+            };
+            if into_position {
+                *target.entry(posting.asset).or_default() += posting.amount;
+            }
+        }
+    }
+    summary
+}
+
+/// Render a raw amount with `decimals` decimal places, trailing zeros trimmed.
+pub fn format_amount(raw: i128, decimals: u8) -> String {
+    let scale = 10_i128.pow(u32::from(decimals));
+    let sign = if raw < 0 { "-" } else { "" };
+    let whole = raw.unsigned_abs() / scale.unsigned_abs();
+    let fraction = raw.unsigned_abs() % scale.unsigned_abs();
+    if fraction == 0 {
+        return format!("{sign}{whole}");
+    }
+    let fraction = format!("{:0width$}", fraction, width = usize::from(decimals));
+    format!("{sign}{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+fn describe(asset: &Asset, assets: &BTreeMap<Asset, AssetInfo>) -> (String, u8) {
+    match assets.get(asset) {
+        Some(info) => (info.symbol.clone(), info.decimals),
+        None => (asset.to_string(), if *asset == Asset::Sol { 9 } else { 0 }),
+    }
+}
+
+/// Write `rows` as CSV in `format`. Assets missing from `assets` are named by mint and
+/// written in raw units.
+pub fn write_tax_csv<W: Write>(
+    mut writer: W,
+    rows: &[TaxRow],
+    format: TaxFormat,
+    assets: &BTreeMap<Asset, AssetInfo>,
+) -> anyhow::Result<()> {
+    let amount = |value: Option<(Asset, i128)>| -> (String, String) {
+        value.map_or_else(
+            || (String::new(), String::new()),
+            |(asset, raw)| {
+                let (symbol, decimals) = describe(&asset, assets);
+                (format_amount(raw, decimals), symbol)
+            },
+        )
+    };
+
+    match format {
+        TaxFormat::Koinly => writeln!(
+            writer,
+            "Date,Sent Amount,Sent Currency,Received Amount,Received Currency,Fee Amount,\
+             Fee Currency,Net Worth Amount,Net Worth Currency,Label,Description,TxHash"
+        )?,
+        TaxFormat::CoinTracker => writeln!(
+            writer,
+            "Date,Received Quantity,Received Currency,Sent Quantity,Sent Currency,Fee Amount,\
+             Fee Currency,Tag"
+        )?,
+    }
+    for row in rows {
+        let timestamp = DateTime::from_timestamp(row.timestamp, 0).unwrap_or_default();
+        let (sent_amount, sent_currency) = amount(row.sent);
+        let (received_amount, received_currency) = amount(row.received);
+        let (fee_amount, fee_currency) = amount(row.fee);
+        let fee_only = row.sent.is_none() && row.received.is_none();
+        match format {
+            TaxFormat::Koinly => {
+                // Koinly records a standalone fee as a sent amount labelled `cost`.
+                let (sent_amount, sent_currency, fee_amount, fee_currency, label) = if fee_only {
+                    (
+                        fee_amount,
+                        fee_currency,
+                        String::new(),
+                        String::new(),
+                        "cost",
+                    )
+                } else {
+                    (sent_amount, sent_currency, fee_amount, fee_currency, "")
+                };
+                writeln!(
+                    writer,
+                    "{} UTC,{},{},{},{},{},{},,,{},{},{}",
+                    timestamp.format("%Y-%m-%d %H:%M"),
+                    sent_amount,
+                    sent_currency,
+                    received_amount,
+                    received_currency,
+                    fee_amount,
+                    fee_currency,
+                    label,
+                    row.kind.as_str(),
+                    row.signature.as_deref().unwrap_or_default()
+                )?
+            }
+            TaxFormat::CoinTracker => writeln!(
+                writer,
+                "{},{},{},{},{},{},{},",
+                timestamp.format("%m/%d/%Y %H:%M:%S"),
+                received_amount,
+                received_currency,
+                sent_amount,
+                sent_currency,
+                fee_amount,
+                fee_currency
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Write `disposals` as a realized gains CSV with amounts in the numeraire.
+pub fn write_gains_csv<W: Write>(
+    mut writer: W,
+    disposals: &[Disposal],
+    numeraire: &Asset,
+    assets: &BTreeMap<Asset, AssetInfo>,
+) -> anyhow::Result<()> {
+    let (numeraire_symbol, numeraire_decimals) = describe(numeraire, assets);
+    writeln!(
+        writer,
+        "date,asset,quantity,proceeds,cost_basis,gain,unknown_basis_quantity,currency"
+    )?;
+    for disposal in disposals {
+        let (symbol, decimals) = describe(&disposal.asset, assets);
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            DateTime::from_timestamp(disposal.timestamp, 0)
+                .unwrap_or_default()
+                .to_rfc3339(),
+            symbol,
+            format_amount(disposal.quantity, decimals),
+            format_amount(disposal.proceeds, numeraire_decimals),
+            format_amount(disposal.cost_basis, numeraire_decimals),
+            format_amount(disposal.gain(), numeraire_decimals),
+            format_amount(disposal.unknown_basis_quantity, decimals),
+            numeraire_symbol
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+
+    fn trade(
+        timestamp: i64,
+        base: Asset,
+        quote: Asset,
+        base_change: i128,
+        quote_change: i128,
+    ) -> Entry {
+        let mut entry = Entry::new(
+            EntryKind::Fill,
+            Pubkey::default(),
+            timestamp as u64,
+            timestamp,
+            None,
+        );
+        entry.transfer(
+            LedgerAccount::Market(1),
+            LedgerAccount::LiquidityPosition(1),
+            base,
+            base_change,
+        );
+        entry.transfer(
+            LedgerAccount::Market(1),
+            LedgerAccount::LiquidityPosition(1),
+            quote,
+            quote_change,
+        );
+        entry
+    }
+
+    #[test]
+    fn realizes_gains_first_in_first_out() {
+        let base = Asset::Token(Pubkey::new_unique());
+        let quote = Asset::Token(Pubkey::new_unique());
+        let entries = vec![
+            trade(1, base, quote, 100, -1_000),
+            trade(2, base, quote, 100, -2_000),
+            trade(3, base, quote, -150, 3_000),
+        ];
+
+        let disposals = realized_gains(&entries, quote);
+        assert_eq!(disposals.len(), 1);
+        assert_eq!(disposals[0].quantity, 150);
+        assert_eq!(disposals[0].cost_basis, 2_000);
+        assert_eq!(disposals[0].gain(), 1_000);
+
+        let summary = summarize_period(&entries, &disposals, 3, 4);
+        assert_eq!(summary.realized_gain, 1_000);
+        assert_eq!(summary.disposals, 1);
+    }
+
+    #[test]
+    fn folds_fees_into_their_trade_and_skips_inventory_transfers() {
+        let base = Asset::Token(Pubkey::new_unique());
+        let quote = Asset::Token(Pubkey::new_unique());
+        let owner = Pubkey::default();
+        let signature = Some("sig".to_string());
+
+        let mut fee = Entry::new(EntryKind::Fee, owner, 1, 1, signature.clone());
+        fee.transfer(
+            LedgerAccount::Wallet,
+            LedgerAccount::Fees,
+            Asset::Sol,
+            5_000,
+        );
+        let mut swap = Entry::new(EntryKind::Swap, owner, 1, 1, signature);
+        swap.transfer(
+            LedgerAccount::Wallet,
+            LedgerAccount::Swaps,
+            base,
+            1_000_000_000,
+        );
+        swap.transfer(
+            LedgerAccount::Swaps,
+            LedgerAccount::Wallet,
+            quote,
+            150_000_000,
+        );
+        let mut deposit = Entry::new(EntryKind::Deposit, owner, 2, 2, None);
+        deposit.transfer(
+            LedgerAccount::Wallet,
+            LedgerAccount::LiquidityPosition(1),
+            quote,
+            1,
+        );
+
+        let rows = tax_rows(&[fee, swap, deposit]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].sent, Some((base, 1_000_000_000)));
+        assert_eq!(rows[0].received, Some((quote, 150_000_000)));
+        assert_eq!(rows[0].fee, Some((Asset::Sol, 5_000)));
+
+        let assets = BTreeMap::from([
+            (
+                base,
+                AssetInfo {
+                    symbol: "SOL".to_string(),
+                    decimals: 9,
+                },
+            ),
+            (
+                quote,
+                AssetInfo {
+                    symbol: "USDC".to_string(),
+                    decimals: 6,
+                },
+            ),
+        ]);
+        let mut csv = Vec::new();
+        write_tax_csv(&mut csv, &rows, TaxFormat::Koinly, &assets).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(
+            csv.lines()
+                .nth(1)
+                .unwrap()
+                .starts_with("1970-01-01 00:00 UTC,1,SOL,150,USDC,0.000005,SOL,,,,swap,sig")
+        );
+        assert_eq!(format_amount(-1_500_000, 6), "-1.5");
+    }
+}
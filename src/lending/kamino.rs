@@ -0,0 +1,345 @@
+//! Kamino Lend reserve as a [`LendingVenue`].
+//!
+//! Quote is supplied with `deposit_reserve_liquidity`, which mints the reserve's collateral
+//! token (cToken) to the owner, and recalled with `redeem_reserve_collateral`. Interest
+//! accrues in the cToken's exchange rate, so what the owner has supplied is their cToken
+//! balance valued at the reserve's total liquidity over the cToken supply. Both
+//! instructions are preceded by `refresh_reserve`, which the program requires in the same
+//! transaction.
+
+use anchor_client::{
+    Program,
+    solana_sdk::instruction::{AccountMeta, Instruction},
+};
+use anchor_lang::{prelude::Pubkey, pubkey};
+use anchor_spl::associated_token::{
+    get_associated_token_address_with_program_id,
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+};
+use anyhow::Context;
+use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
+
+use super::LendingVenue;
+use crate::{ProgramPayer, config::env_var, get_token_program_id};
+
+/// Kamino Lend program on mainnet.
+pub const KLEND_PROGRAM_ID: Pubkey = pubkey!("KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD");
+
+// Offsets into the `Reserve` account, discriminator included.
+const LENDING_MARKET_OFFSET: usize = 32;
+const LIQUIDITY_MINT_OFFSET: usize = 128;
+const LIQUIDITY_SUPPLY_VAULT_OFFSET: usize = 160;
+const AVAILABLE_AMOUNT_OFFSET: usize = 224;
+const BORROWED_AMOUNT_SF_OFFSET: usize = 232;
+const ACCUMULATED_PROTOCOL_FEES_SF_OFFSET: usize = 344;
+const ACCUMULATED_REFERRER_FEES_SF_OFFSET: usize = 360;
+const PENDING_REFERRER_FEES_SF_OFFSET: usize = 376;
+/// Kamino's scaled fractions carry 60 fractional bits.
+const SF_FRACTION_BITS: u32 = 60;
+
+/// A Kamino reserve whose liquidity mint is the market's quote token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KaminoReserve {
+    pub reserve: Pubkey,
+    /// The reserve's cToken mint.
+    pub collateral_mint: Pubkey,
+    /// Scope price feed the reserve is configured with, if any.
+    pub scope_prices: Option<Pubkey>,
+    /// Pyth price account the reserve is configured with, if any.
+    pub pyth_oracle: Option<Pubkey>,
+}
+
+impl KaminoReserve {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let pubkey = |name: &str| -> anyhow::Result<Option<Pubkey>> {
+            env_var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<Pubkey>()
+                        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            reserve: pubkey("KAMINO_RESERVE")?.context("KAMINO_RESERVE must be set")?,
+            collateral_mint: pubkey("KAMINO_COLLATERAL_MINT")?
+                .context("KAMINO_COLLATERAL_MINT must be set")?,
+            scope_prices: pubkey("KAMINO_SCOPE_PRICES")?,
+            pyth_oracle: pubkey("KAMINO_PYTH_ORACLE")?,
+        })
+    }
+
+    async fn load(&self, program: &Program<ProgramPayer>) -> anyhow::Result<ReserveLiquidity> {
+        let rpc = program.rpc();
+        let data = rpc
+            .get_account_data(&self.reserve)
+            .await
+            .context("Failed to fetch Kamino reserve")?;
+        let mut liquidity = ReserveLiquidity::parse(&data)?;
+        liquidity.collateral_supply = rpc
+            .get_token_supply(&self.collateral_mint)
+            .await?
+            .amount
+            .parse()?;
+        Ok(liquidity)
+    }
+
+    async fn collateral_balance(
+        &self,
+        program: &Program<ProgramPayer>,
+        owner: &Pubkey,
+    ) -> anyhow::Result<u64> {
+        let ata = get_associated_token_address_with_program_id(
+            owner,
+            &self.collateral_mint,
+            &anchor_spl::token::ID,
+        );
+        // No cToken account yet means nothing supplied.
+        Ok(program
+            .rpc()
+            .get_token_account_balance(&ata)
+            .await
+            .ok()
+            .and_then(|balance| balance.amount.parse().ok())
+            .unwrap_or(0))
+    }
+
+    fn refresh_reserve(&self, lending_market: Pubkey) -> Instruction {
+        // Oracles the reserve doesn't use are passed as the program id.
+        let optional = |account: Option<Pubkey>| {
+            AccountMeta::new_readonly(account.unwrap_or(KLEND_PROGRAM_ID), false)
+        };
+        Instruction {
+            program_id: KLEND_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(self.reserve, false),
+                AccountMeta::new_readonly(lending_market, false),
+                optional(self.pyth_oracle),
+                optional(None),
+                optional(None),
+                optional(self.scope_prices),
+            ],
+            data: discriminator("refresh_reserve").to_vec(),
+        }
+    }
+
+    /// Accounts shared by deposit and redeem, in the deposit's order.
+    fn transfer_accounts(
+        &self,
+        owner: Pubkey,
+        liquidity: &ReserveLiquidity,
+        liquidity_token_program: Pubkey,
+    ) -> Vec<AccountMeta> {
+        let collateral_ata = get_associated_token_address_with_program_id(
+            &owner,
+            &self.collateral_mint,
+            &anchor_spl::token::ID,
+        );
+        let liquidity_ata = get_associated_token_address_with_program_id(
+            &owner,
+            &liquidity.mint,
+            &liquidity_token_program,
+        );
+        vec![
+            AccountMeta::new(owner, true),
+            AccountMeta::new(self.reserve, false),
+            AccountMeta::new_readonly(liquidity.lending_market, false),
+            AccountMeta::new_readonly(lending_market_authority(&liquidity.lending_market), false),
+            AccountMeta::new_readonly(liquidity.mint, false),
+            AccountMeta::new(liquidity.supply_vault, false),
+            AccountMeta::new(self.collateral_mint, false),
+            AccountMeta::new(liquidity_ata, false),
+            AccountMeta::new(collateral_ata, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+            AccountMeta::new_readonly(liquidity_token_program, false),
+            AccountMeta::new_readonly(anchor_lang::solana_program::sysvar::instructions::ID, false),
+        ]
+    }
+}
+
+impl LendingVenue for KaminoReserve {
+    fn name(&self) -> &str {
+        "kamino"
+    }
+
+    fn supplied<'a>(
+        &'a self,
+        program: &'a Program<ProgramPayer>,
+        owner: Pubkey,
+    ) -> BoxFuture<'a, anyhow::Result<u64>> {
+        Box::pin(async move {
+            let collateral = self.collateral_balance(program, &owner).await?;
+            if collateral == 0 {
+                return Ok(0);
+            }
+            Ok(self.load(program).await?.liquidity_for(collateral))
+        })
+    }
+
+    fn deposit_instructions<'a>(
+        &'a self,
+        program: &'a Program<ProgramPayer>,
+        owner: Pubkey,
+        amount: u64,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<Instruction>>> {
+        Box::pin(async move {
+            let liquidity = self.load(program).await?;
+            let liquidity_token_program = get_token_program_id(program, &liquidity.mint).await?;
+            let mut data = discriminator("deposit_reserve_liquidity").to_vec();
+            data.extend_from_slice(&amount.to_le_bytes());
+            Ok(vec![
+                create_associated_token_account_idempotent(
+                    &owner,
+                    &owner,
+                    &self.collateral_mint,
+                    &anchor_spl::token::ID,
+                ),
+                self.refresh_reserve(liquidity.lending_market),
+                Instruction {
+                    program_id: KLEND_PROGRAM_ID,
+                    accounts: self.transfer_accounts(owner, &liquidity, liquidity_token_program),
+                    data,
+                },
+            ])
+        })
+    }
+
+    fn withdraw_instructions<'a>(
+        &'a self,
+        program: &'a Program<ProgramPayer>,
+        owner: Pubkey,
+        amount: u64,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<Instruction>>> {
+        Box::pin(async move {
+            let liquidity = self.load(program).await?;
+            let liquidity_token_program = get_token_program_id(program, &liquidity.mint).await?;
+            let collateral = liquidity
+                .collateral_for(amount)
+                .min(self.collateral_balance(program, &owner).await?);
+            anyhow::ensure!(collateral > 0, "Nothing supplied to Kamino to withdraw");
+
+            // Redeem takes the same accounts as deposit, with the lending market ahead of
+            // the reserve and the collateral account ahead of the liquidity account.
+            let mut accounts = self.transfer_accounts(owner, &liquidity, liquidity_token_program);
+            accounts.swap(1, 2);
+            accounts.swap(5, 6);
+            accounts.swap(7, 8);
+            let mut data = discriminator("redeem_reserve_collateral").to_vec();
+            data.extend_from_slice(&collateral.to_le_bytes());
+            Ok(vec![
+                self.refresh_reserve(liquidity.lending_market),
+                Instruction {
+                    program_id: KLEND_PROGRAM_ID,
+                    accounts,
+                    data,
+                },
+            ])
+        })
+    }
+}
+
+/// What a reserve account says about its liquidity, plus the cToken supply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ReserveLiquidity {
+    lending_market: Pubkey,
+    mint: Pubkey,
+    supply_vault: Pubkey,
+    /// Available plus borrowed liquidity, net of fees owed to the protocol and referrers.
+    total_liquidity: u128,
+    collateral_supply: u64,
+}
+
+impl ReserveLiquidity {
+    fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            data.len() >= PENDING_REFERRER_FEES_SF_OFFSET + 16,
+            "Kamino reserve account is too short"
+        );
+        let pubkey = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let sf_at = |offset: usize| {
+            u128::from_le_bytes(data[offset..offset + 16].try_into().unwrap()) >> SF_FRACTION_BITS
+        };
+
+        let total_liquidity = (u128::from(u64_at(AVAILABLE_AMOUNT_OFFSET))
+            + sf_at(BORROWED_AMOUNT_SF_OFFSET))
+        .saturating_sub(sf_at(ACCUMULATED_PROTOCOL_FEES_SF_OFFSET))
+        .saturating_sub(sf_at(ACCUMULATED_REFERRER_FEES_SF_OFFSET))
+        .saturating_sub(sf_at(PENDING_REFERRER_FEES_SF_OFFSET));
+        Ok(Self {
+            lending_market: pubkey(LENDING_MARKET_OFFSET),
+            mint: pubkey(LIQUIDITY_MINT_OFFSET),
+            supply_vault: pubkey(LIQUIDITY_SUPPLY_VAULT_OFFSET),
+            total_liquidity,
+            collateral_supply: 0,
+        })
+    }
+
+    /// Liquidity `collateral` cTokens redeem for, rounded down.
+    fn liquidity_for(&self, collateral: u64) -> u64 {
+        if self.collateral_supply == 0 {
+            return 0;
+        }
+        let liquidity =
+            u128::from(collateral) * self.total_liquidity / u128::from(self.collateral_supply);
+        u64::try_from(liquidity).unwrap_or(u64::MAX)
+    }
+
+    /// cTokens needed to redeem at least `liquidity`, rounded up.
+    fn collateral_for(&self, liquidity: u64) -> u64 {
+        if self.total_liquidity == 0 {
+            return 0;
+        }
+        let collateral = (u128::from(liquidity) * u128::from(self.collateral_supply))
+            .div_ceil(self.total_liquidity);
+        u64::try_from(collateral).unwrap_or(u64::MAX)
+    }
+}
+
+fn lending_market_authority(lending_market: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"lma", lending_market.as_ref()], &KLEND_PROGRAM_ID).0
+}
+
+/// Anchor instruction discriminator: the first 8 bytes of `sha256("global:<name>")`.
+fn discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("global:{name}"));
+    hash[..8].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_liquidity_values_ctokens_at_the_exchange_rate() {
+        let lending_market = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0_u8; PENDING_REFERRER_FEES_SF_OFFSET + 16];
+        data[LENDING_MARKET_OFFSET..LENDING_MARKET_OFFSET + 32]
+            .copy_from_slice(lending_market.as_ref());
+        data[LIQUIDITY_MINT_OFFSET..LIQUIDITY_MINT_OFFSET + 32].copy_from_slice(mint.as_ref());
+        data[AVAILABLE_AMOUNT_OFFSET..AVAILABLE_AMOUNT_OFFSET + 8]
+            .copy_from_slice(&700_u64.to_le_bytes());
+        data[BORROWED_AMOUNT_SF_OFFSET..BORROWED_AMOUNT_SF_OFFSET + 16]
+            .copy_from_slice(&(400_u128 << SF_FRACTION_BITS).to_le_bytes());
+        data[ACCUMULATED_PROTOCOL_FEES_SF_OFFSET..ACCUMULATED_PROTOCOL_FEES_SF_OFFSET + 16]
+            .copy_from_slice(&(100_u128 << SF_FRACTION_BITS).to_le_bytes());
+
+        let mut liquidity = ReserveLiquidity::parse(&data).unwrap();
+        assert_eq!(liquidity.lending_market, lending_market);
+        assert_eq!(liquidity.mint, mint);
+        assert_eq!(liquidity.total_liquidity, 1_000);
+
+        // 800 cTokens over 1_000 liquidity: each cToken redeems for 1.25.
+        liquidity.collateral_supply = 800;
+        assert_eq!(liquidity.liquidity_for(80), 100);
+        assert_eq!(liquidity.collateral_for(100), 80);
+        assert_eq!(liquidity.collateral_for(101), 81);
+        assert!(ReserveLiquidity::parse(&data[..100]).is_err());
+    }
+}
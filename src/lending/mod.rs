@@ -0,0 +1,254 @@
+//! Idle-capital yield: park quote a liquidity position doesn't need in a lending venue.
+//!
+//! A position only needs enough quote to back its net quote outflow for a while. Anything
+//! beyond [`IdleYieldPolicy::target_runway_slots`] of outflow earns nothing in the LP, so
+//! [`manage_idle_quote`] withdraws it and supplies it to a [`LendingVenue`]. When the
+//! position's runway falls below [`IdleYieldPolicy::min_runway_slots`] the quote is
+//! recalled and added back. The gap between the two thresholds keeps the bot from moving
+//! funds every cycle.
+//!
+//! Lent quote is reported to the [`RiskEngine`] so it still counts towards the value the
+//! drawdown limit watches, and deposits are vetoed while the engine is halted or would
+//! breach its lending limit. [`crate::portfolio::Portfolio::add_lent`] adds it to a
+//! portfolio.
+
+pub mod kamino;
+
+use std::{fmt, sync::Arc};
+
+use anchor_client::{Program, solana_sdk::instruction::Instruction};
+use anchor_lang::prelude::Pubkey;
+use futures::future::BoxFuture;
+use tracing::info;
+
+use crate::{
+    ProgramPayer, config::env_var, execute_add_liquidity, execute_withdraw_liquidity,
+    fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances,
+    nearest_reference_index, risk::RiskEngine, state::net_outflows, tx::TxSender,
+};
+
+pub use kamino::KaminoReserve;
+
+/// A protocol that accepts deposits of the market's quote token and pays interest on them.
+pub trait LendingVenue: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Quote `owner` has supplied, interest included, in raw units.
+    fn supplied<'a>(
+        &'a self,
+        program: &'a Program<ProgramPayer>,
+        owner: Pubkey,
+    ) -> BoxFuture<'a, anyhow::Result<u64>>;
+
+    /// Instructions supplying `amount` raw quote from `owner`'s token account.
+    fn deposit_instructions<'a>(
+        &'a self,
+        program: &'a Program<ProgramPayer>,
+        owner: Pubkey,
+        amount: u64,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<Instruction>>>;
+
+    /// Instructions returning at least `amount` raw quote (or everything supplied, if less)
+    /// to `owner`'s token account.
+    fn withdraw_instructions<'a>(
+        &'a self,
+        program: &'a Program<ProgramPayer>,
+        owner: Pubkey,
+        amount: u64,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<Instruction>>>;
+}
+
+/// When to lend a position's quote and when to recall it, in slots of net quote outflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleYieldPolicy {
+    /// Recall lent quote once the position's runway falls below this.
+    pub min_runway_slots: u64,
+    /// Keep this much runway in the position; quote beyond it is idle.
+    pub target_runway_slots: u64,
+    /// Skip lending less than this, in raw quote, so fees don't eat the yield.
+    pub min_move: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+    Hold,
+    /// Withdraw this much quote from the position and supply it.
+    Lend(u64),
+    /// Withdraw this much quote from the venue and add it back to the position.
+    Recall(u64),
+}
+
+impl IdleYieldPolicy {
+    /// Decide what to do with a position holding `quote` that drains `quote_outflow` per
+    /// slot, with `lent` already supplied.
+    pub fn plan(&self, quote: u64, quote_outflow: u128, lent: u64) -> IdleAction {
+        let needed =
+            u64::try_from(quote_outflow.saturating_mul(u128::from(self.target_runway_slots)))
+                .unwrap_or(u64::MAX);
+        let runway = u128::from(quote)
+            .checked_div(quote_outflow)
+            .unwrap_or(u128::MAX);
+
+        if runway < u128::from(self.min_runway_slots) {
+            let recall = lent.min(needed.saturating_sub(quote));
+            return if recall > 0 {
+                IdleAction::Recall(recall)
+            } else {
+                IdleAction::Hold
+            };
+        }
+        let idle = quote.saturating_sub(needed);
+        if idle > 0 && idle >= self.min_move {
+            IdleAction::Lend(idle)
+        } else {
+            IdleAction::Hold
+        }
+    }
+}
+
+/// The idle-yield step, configured from the environment.
+#[derive(Clone)]
+pub struct IdleYieldConfig {
+    pub policy: IdleYieldPolicy,
+    pub venue: Arc<dyn LendingVenue>,
+}
+
+impl IdleYieldConfig {
+    /// `None` when `IDLE_YIELD_VENUE` is unset, which disables the step.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let venue = env_var("IDLE_YIELD_VENUE").unwrap_or_default();
+        let venue: Arc<dyn LendingVenue> = match venue.trim() {
+            "" => return Ok(None),
+            "kamino" => Arc::new(KaminoReserve::from_env()?),
+            other => anyhow::bail!("Unsupported IDLE_YIELD_VENUE `{other}`; expected `kamino`"),
+        };
+
+        let slots = |name: &str, default: &str| {
+            env_var(name)
+                .unwrap_or_else(|_| default.to_string())
+                .parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+        };
+        let policy = IdleYieldPolicy {
+            min_runway_slots: slots("IDLE_YIELD_MIN_RUNWAY_SLOTS", "216000")?,
+            target_runway_slots: slots("IDLE_YIELD_TARGET_RUNWAY_SLOTS", "432000")?,
+            min_move: slots("IDLE_YIELD_MIN_MOVE", "0")?,
+        };
+        anyhow::ensure!(
+            policy.min_runway_slots <= policy.target_runway_slots,
+            "IDLE_YIELD_MIN_RUNWAY_SLOTS must not exceed IDLE_YIELD_TARGET_RUNWAY_SLOTS"
+        );
+
+        Ok(Some(Self { policy, venue }))
+    }
+}
+
+impl fmt::Debug for IdleYieldConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdleYieldConfig")
+            .field("policy", &self.policy)
+            .field("venue", &self.venue.name())
+            .finish()
+    }
+}
+
+/// Lend the payer's idle quote on `market_id` or recall it, as `policy` decides, and
+/// report what is lent to `risk`. Returns the action taken.
+pub async fn manage_idle_quote(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    quote_decimals: u8,
+    config: &IdleYieldConfig,
+    risk: &RiskEngine,
+    sender: &TxSender,
+) -> anyhow::Result<IdleAction> {
+    let owner = sender.payer();
+    let venue = config.venue.as_ref();
+    let state = fetch_market_state(program, market_id).await?;
+    let position = fetch_liquidity_position(program, market_id, &owner).await?;
+    let balances = get_liquidity_position_balances(
+        program,
+        position,
+        state.bookkeeping,
+        state.market,
+        state.current_slot,
+    )
+    .await?;
+    let lent = venue.supplied(program, owner).await?;
+    let quote = balances.quote_balance.saturating_sub(balances.quote_debt);
+
+    // A one-sided market gives no outflow rate to size the runway with.
+    let action = match net_outflows(&position, &state.market) {
+        Some((_, quote_outflow)) => config.policy.plan(quote, quote_outflow, lent),
+        None => IdleAction::Hold,
+    };
+
+    let reference_index =
+        nearest_reference_index(state.current_slot, state.market.end_slot_interval);
+    match action {
+        IdleAction::Hold => {}
+        IdleAction::Lend(amount) => {
+            risk.check_lend(amount)?;
+            execute_withdraw_liquidity(program, market_id, 0, amount, reference_index, sender)
+                .await?;
+            sender
+                .send(venue.deposit_instructions(program, owner, amount).await?)
+                .await?;
+        }
+        IdleAction::Recall(amount) => {
+            sender
+                .send(venue.withdraw_instructions(program, owner, amount).await?)
+                .await?;
+            execute_add_liquidity(program, market_id, 0, amount, reference_index, sender).await?;
+        }
+    }
+
+    let lent = match action {
+        IdleAction::Hold => lent,
+        _ => venue.supplied(program, owner).await?,
+    };
+    risk.observe_lent(
+        market_id,
+        lent,
+        lent as f64 / 10f64.powi(i32::from(quote_decimals)),
+    );
+    info!(
+        event.name = "idle_yield_step",
+        market.id = market_id,
+        lending.venue = venue.name(),
+        lending.action = ?action,
+        position.quote = quote,
+        gauge.lending_supplied = lent,
+    );
+    Ok(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: IdleYieldPolicy = IdleYieldPolicy {
+        min_runway_slots: 100,
+        target_runway_slots: 200,
+        min_move: 50,
+    };
+
+    #[test]
+    fn lends_quote_beyond_the_target_runway() {
+        // 10 quote/slot out: 2_000 backs the target, the rest is idle.
+        assert_eq!(POLICY.plan(5_000, 10, 0), IdleAction::Lend(3_000));
+        assert_eq!(POLICY.plan(2_040, 10, 0), IdleAction::Hold);
+        // Not draining quote at all: everything is idle.
+        assert_eq!(POLICY.plan(500, 0, 0), IdleAction::Lend(500));
+    }
+
+    #[test]
+    fn recalls_up_to_the_target_once_runway_is_short() {
+        // 900 is 90 slots of runway, under the minimum; refill to 2_000.
+        assert_eq!(POLICY.plan(900, 10, 5_000), IdleAction::Recall(1_100));
+        assert_eq!(POLICY.plan(900, 10, 400), IdleAction::Recall(400));
+        assert_eq!(POLICY.plan(900, 10, 0), IdleAction::Hold);
+        // Between the thresholds nothing moves.
+        assert_eq!(POLICY.plan(1_500, 10, 5_000), IdleAction::Hold);
+    }
+}
@@ -1,24 +1,139 @@
-use std::sync::Arc;
-
-use anchor_client::{Program, solana_sdk::signature::Keypair};
+//! Market making on the TwoB program.
+//!
+//! The default `client` feature brings in the RPC client, the async runtime and everything
+//! the bots run on. Without it the crate is just the program's types, the PDA
+//! [`accounts`] resolvers, the [`decode`]rs and the [`instructions`] builders, for
+//! indexers and wallets that build or read TwoB instructions themselves:
+//!
+//! ```toml
+//! twob-market-making = { version = "0.1", default-features = false }
+//! ```
+//!
+//! The `core` feature adds the position and quoting math of [`core`](crate::core), which
+//! needs no more than that either. `wasm` exports it and the resolvers to JavaScript
+//! ([`wasm`](crate::wasm)), and `python` builds the `twob_mm` Python module with them and
+//! the instruction builders (see `pyproject.toml`). `ffi` exports the balance math to C
+//! ([`ffi`](crate::ffi)).
+
+use std::time::Duration;
+#[cfg(feature = "client")]
+use std::{collections::BTreeMap, sync::Arc};
+
+#[cfg(feature = "client")]
+use anchor_client::{
+    Program,
+    solana_sdk::{commitment_config::CommitmentConfig, signer::null_signer::NullSigner},
+};
 use anchor_lang::prelude::*;
+use rand::Rng;
+#[cfg(feature = "client")]
 use tracing::{info, warn};
 
 pub mod accounts;
+#[cfg(feature = "client")]
+pub mod alerts;
+#[cfg(feature = "api")]
+pub mod api;
+#[cfg(feature = "client")]
+pub mod audit;
+#[cfg(feature = "client")]
+pub mod backtest;
+#[cfg(feature = "client")]
+pub mod bots;
+pub mod cluster;
+#[cfg(feature = "client")]
+pub mod config;
 pub mod constants;
+#[cfg(feature = "client")]
+pub mod control;
+#[cfg(feature = "client")]
+pub mod coordinator;
+#[cfg(feature = "core")]
+pub mod core;
+#[cfg(feature = "client")]
+pub mod crank;
+#[cfg(feature = "client")]
+pub mod crash_dump;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+pub mod decode;
+#[cfg(feature = "client")]
+pub mod error;
+#[cfg(feature = "client")]
+pub mod event_bus;
+#[cfg(feature = "client")]
+pub mod feed_health;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "client")]
+pub mod health;
+#[cfg(feature = "client")]
+pub mod ingest;
 pub mod instructions;
+#[cfg(feature = "client")]
+pub mod ledger;
+#[cfg(feature = "client")]
+pub mod lending;
+#[cfg(feature = "client")]
+pub mod portfolio;
+#[cfg(feature = "client")]
+pub mod price;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "client")]
+pub mod quote;
+#[cfg(feature = "client")]
+pub mod rate_limit;
+#[cfg(feature = "client")]
+pub mod reporting;
+#[cfg(feature = "client")]
+pub mod risk;
+#[cfg(feature = "client")]
+pub mod rotation;
+#[cfg(feature = "client")]
+pub mod rpc_metrics;
+#[cfg(feature = "client")]
+pub mod secrets;
+#[cfg(feature = "client")]
+pub mod slot_lag;
+#[cfg(feature = "client")]
 pub mod state;
+#[cfg(feature = "client")]
+pub mod strategy;
+#[cfg(feature = "client")]
+pub mod subscription;
+#[cfg(feature = "client")]
+pub mod supervisor;
+#[cfg(feature = "client")]
+pub mod telemetry;
+#[cfg(feature = "client")]
+pub mod turnover;
+#[cfg(feature = "client")]
+pub mod tx;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "client")]
+pub mod webhooks;
 
 // Re-export commonly used types
+#[cfg(feature = "core")]
+pub use crate::core::LiquidityPositionBalances;
 pub use accounts::{AccountResolver, PdaResult};
 pub use constants::*;
+#[cfg(feature = "client")]
+pub use error::TwobError;
 pub use instructions::*;
-pub use state::{MarketState, fetch_liquidity_position, fetch_market_state};
+#[cfg(feature = "client")]
+pub use state::{
+    MarketState, TwobRpc, fetch_account, fetch_liquidity_position, fetch_market_state,
+    fetch_trade_position, record_runway, slots_until_debt,
+};
 
 declare_program!(twob_anchor);
-use twob_anchor::accounts::{Bookkeeping, LiquidityPosition, Market};
-
-use crate::twob_anchor::accounts::Exits;
+#[cfg(feature = "client")]
+use crash_dump::CrashDump;
+#[cfg(feature = "client")]
+use twob_anchor::accounts::{Bookkeeping, Exits, LiquidityPosition, Market};
 
 /// The TwoB Anchor program ID
 pub const TWOB_PROGRAM_ID: &str = "CCAmAqvza37EWzou7LoYCaGKzdJsCu1CLPMp3Wvx3Bc5";
@@ -28,54 +143,141 @@ pub fn program_id() -> anchor_lang::prelude::Pubkey {
     TWOB_PROGRAM_ID.parse().expect("Invalid program ID")
 }
 
+/// The payer anchor's `Client` and `Program` are built with. Only its pubkey is used, by
+/// the instruction builders through `program.payer()`: transactions are signed by a
+/// [`TxSigner`](tx::TxSigner) through the [`TxSender`](tx::TxSender), so the key itself
+/// can stay on a Ledger or behind a signing service.
+#[cfg(feature = "client")]
+pub type ProgramPayer = Arc<NullSigner>;
+
+#[cfg(feature = "client")]
+pub fn program_payer(pubkey: Pubkey) -> ProgramPayer {
+    Arc::new(NullSigner::new(&pubkey))
+}
+
+/// Commitment named by env var `name` (`processed`, `confirmed` or `finalized`),
+/// `confirmed` when unset.
+#[cfg(feature = "client")]
+pub fn commitment_from_env(name: &str) -> anyhow::Result<CommitmentConfig> {
+    match config::env_var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse::<CommitmentConfig>()
+            .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e)),
+        _ => Ok(CommitmentConfig::confirmed()),
+    }
+}
+
+/// `interval` moved by a random amount of up to `jitter_pct` percent either way, so a bot's
+/// transactions don't keep a cadence others can time theirs around.
+pub fn jittered(interval: Duration, jitter_pct: u32) -> Duration {
+    if jitter_pct == 0 {
+        return interval;
+    }
+    let spread = interval.as_secs_f64() * f64::from(jitter_pct.min(100)) / 100.0;
+    let offset = rand::thread_rng().gen_range(-spread..=spread);
+    Duration::from_secs_f64((interval.as_secs_f64() + offset).max(0.0))
+}
+
+/// The window an instruction sent at `current_slot` should reference, taken
+/// `ARRAY_LENGTH / 2` slots ahead so one sent just before a boundary targets the window it
+/// will land in.
+pub fn nearest_reference_index(current_slot: u64, end_slot_interval: u64) -> u64 {
+    (current_slot + ARRAY_LENGTH / 2) / ARRAY_LENGTH / end_slot_interval
+}
+
+/// Slots from `current_slot` to the first slot of the window after the one it is in.
+pub fn slots_until_next_window(current_slot: u64, end_slot_interval: u64) -> u64 {
+    let reference_index = current_slot / ARRAY_LENGTH / end_slot_interval;
+    reference_window_last_slot(reference_index, end_slot_interval) + 1 - current_slot
+}
+
+/// Whether the window after `current_slot`'s starts within `lead_slots`, so its accounts
+/// should be opened now for the sends that will name it; see
+/// [`execute_open_next_window`].
+pub fn next_window_needs_opening(
+    current_slot: u64,
+    end_slot_interval: u64,
+    lead_slots: u64,
+) -> bool {
+    slots_until_next_window(current_slot, end_slot_interval) <= lead_slots
+}
+
+/// The last slot of the exits/prices window `reference_index` names; an instruction
+/// carrying it must land by then.
+pub fn reference_window_last_slot(reference_index: u64, end_slot_interval: u64) -> u64 {
+    (reference_index + 1) * ARRAY_LENGTH * end_slot_interval - 1
+}
+
+#[cfg(feature = "client")]
 pub async fn get_token_program_id(
-    program: &Program<Arc<Keypair>>,
+    program: &Program<ProgramPayer>,
     mint: &Pubkey,
-) -> anyhow::Result<Pubkey> {
-    let account = program
-        .rpc()
-        .get_account(mint)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to fetch mint account: {}", e))?;
+) -> error::Result<Pubkey> {
+    let rpc = program.rpc();
+    rate_limit::throttle(&rpc, 1).await;
+    let account = rpc_metrics::timed(
+        &rpc.url(),
+        "getAccountInfo",
+        rpc.get_account_with_commitment(mint, rpc.commitment()),
+    )
+    .await?
+    .value
+    .ok_or(TwobError::AccountNotFound { pubkey: *mint })?;
 
     Ok(account.owner)
 }
 
-pub struct LiquidityPositionBalances {
-    pub base_balance: u64,
-    pub quote_balance: u64,
-    pub base_debt: u64,
-    pub quote_debt: u64,
-}
+/// [`position_balances`](crate::core::position_balances) for a position read now, fetching
+/// the exits accounts the bookkeeping hasn't caught up with.
+#[cfg(feature = "client")]
 pub async fn get_liquidity_position_balances(
-    program: &Program<Arc<Keypair>>,
+    rpc: &(impl TwobRpc + ?Sized),
     liquidity_position: LiquidityPosition,
     bookkeeping: Bookkeeping,
     market: Market,
     current_slot: u64,
-) -> LiquidityPositionBalances {
-    let resolver = AccountResolver::new(twob_anchor::ID);
-    let market_pda = resolver.market_pda(market.id);
+) -> error::Result<LiquidityPositionBalances> {
+    get_liquidity_position_balances_dumped(
+        rpc,
+        liquidity_position,
+        bookkeeping,
+        market,
+        current_slot,
+        None,
+    )
+    .await
+}
 
-    let elapsed_slots = current_slot - liquidity_position.last_update_slot;
-    let raw_inactive = bookkeeping
-        .slots_without_trade
-        .saturating_sub(liquidity_position.slots_without_trade_snapshot);
-    let active_slots = elapsed_slots.saturating_sub(raw_inactive);
+/// [`get_liquidity_position_balances`], noting what it reads and comes to in a bot's
+/// `crash_dump`, if it keeps one.
+#[cfg(feature = "client")]
+pub async fn get_liquidity_position_balances_dumped(
+    rpc: &(impl TwobRpc + ?Sized),
+    liquidity_position: LiquidityPosition,
+    bookkeeping: Bookkeeping,
+    market: Market,
+    current_slot: u64,
+    crash_dump: Option<&CrashDump>,
+) -> error::Result<LiquidityPositionBalances> {
+    if let Some(crash_dump) = crash_dump {
+        crash_dump.record_inputs(&market, &bookkeeping, &liquidity_position, current_slot);
+    }
+    let slots = crate::core::active_slots(&liquidity_position, &bookkeeping, current_slot)?;
 
     info!(
         event.name = "liquidity_position_balance_slots",
         slot.current = current_slot,
         lp.last_update_slot = liquidity_position.last_update_slot,
-        lp.elapsed_slots = elapsed_slots,
-        lp.inactive_slots = raw_inactive,
-        lp.active_slots = active_slots,
+        lp.elapsed_slots = slots.elapsed,
+        lp.inactive_slots = slots.inactive,
+        lp.active_slots = slots.active,
     );
-    if raw_inactive > elapsed_slots {
+    if slots.inactive > slots.elapsed {
         warn!(
             event.name = "liquidity_position_inactive_slots_saturated",
-            lp.inactive_slots = raw_inactive,
-            lp.elapsed_slots = elapsed_slots,
+            lp.inactive_slots = slots.inactive,
+            lp.elapsed_slots = slots.elapsed,
             bookkeeping.slots_without_trade = bookkeeping.slots_without_trade,
             lp.slots_without_trade_snapshot = liquidity_position.slots_without_trade_snapshot,
         );
@@ -90,214 +292,73 @@ pub async fn get_liquidity_position_balances(
         position.quote_flow.raw = liquidity_position.quote_flow_u64,
     );
 
-    // Base token outflow since last update slot
-    let accumulated_base_outflow = BOOKKEEPING_PRECISION_FACTOR
-        * active_slots as u128
-        * liquidity_position.base_flow_u64 as u128;
-
-    // Quote token outflow since last update slot
-    let accumulated_quote_outflow = BOOKKEEPING_PRECISION_FACTOR
-        * active_slots as u128
-        * liquidity_position.quote_flow_u64 as u128;
-
-    // Cacluclation token inflow is a bit tricky since we only have data up to bookkeeping last update slot.
-    // We need to go from there till current slot and loop through the exits accounts to adapt market flows
-    // First calculate correct base_per_quote and use that then.
-    let base_per_quote = {
-        let mut base_per_quote = bookkeeping.base_per_quote;
-        let mut market_base_flow = market.base_flow;
-        let mut market_quote_flow = market.quote_flow;
-        let mut last_update_slot = bookkeeping.last_update_slot;
-
-        let last_update_index = last_update_slot / ARRAY_LENGTH / market.end_slot_interval;
-        let current_slot_index = current_slot / ARRAY_LENGTH / market.end_slot_interval;
-
-        // This will sum up all prices up to the last index of the last exits account
-        // After that we still need to sum up prices from that point until the current slot
-        for exits_index in last_update_index..=current_slot_index {
-            let exits_account_pda = resolver.exits_pda(&market_pda.address(), exits_index);
-
-            let exits_account = program.account::<Exits>(exits_account_pda.address()).await;
-
-            let start_index = if exits_index == last_update_index {
-                (bookkeeping.last_update_slot
-                    - last_update_index * market.end_slot_interval * ARRAY_LENGTH)
-                    / market.end_slot_interval
-                    + 1
-            } else {
-                0
-            };
-
-            let end_index = if exits_index == current_slot_index {
-                (current_slot - current_slot_index * market.end_slot_interval * ARRAY_LENGTH)
-                    / market.end_slot_interval
-            } else {
-                ARRAY_LENGTH - 1
-            };
-
-            for i in start_index..=end_index {
-                let slot = i * market.end_slot_interval
-                    + exits_index * market.end_slot_interval * ARRAY_LENGTH;
-                let slot_diff = slot - last_update_slot;
-                last_update_slot = slot;
-
-                if market_base_flow == 0 || market_quote_flow == 0 {
-                    continue;
-                }
-                base_per_quote += BOOKKEEPING_PRECISION_FACTOR * market_base_flow
-                    / market_quote_flow
-                    * slot_diff as u128;
-
-                let base_exit = match exits_account {
-                    Ok(exits) => exits.base_exits[i as usize],
-                    Err(_) => 0,
-                };
-                let quote_exit = match exits_account {
-                    Ok(exits) => exits.quote_exits[i as usize],
-                    Err(_) => 0,
-                };
-                market_base_flow -= base_exit;
-                market_quote_flow -= quote_exit;
-            }
-
-            // After we went to all exits account we need to sum up prices up to current slot
-            if exits_index == current_slot_index {
-                let slot_diff = current_slot - last_update_slot;
-                if market_base_flow == 0 || market_quote_flow == 0 {
-                    continue;
-                }
-                base_per_quote += BOOKKEEPING_PRECISION_FACTOR * market_base_flow
-                    / market_quote_flow
-                    * slot_diff as u128;
-            }
-        }
-        base_per_quote
-    };
-
-    let quote_per_base = {
-        let mut quote_per_base = bookkeeping.quote_per_base;
-        let mut market_base_flow = market.base_flow;
-        let mut market_quote_flow = market.quote_flow;
-        let mut last_update_slot = bookkeeping.last_update_slot;
-
-        let last_update_index = last_update_slot / ARRAY_LENGTH / market.end_slot_interval;
-        let current_slot_index = current_slot / ARRAY_LENGTH / market.end_slot_interval;
-
-        for exits_index in last_update_index..=current_slot_index {
-            let exits_account_pda = resolver.exits_pda(&market_pda.address(), exits_index);
-            let exits_account = program.account::<Exits>(exits_account_pda.address()).await;
-
-            let start_index = if exits_index == last_update_index {
-                (bookkeeping.last_update_slot
-                    - last_update_index * market.end_slot_interval * ARRAY_LENGTH)
-                    / market.end_slot_interval
-                    + 1
-            } else {
-                0
-            };
-
-            let end_index = if exits_index == current_slot_index {
-                (current_slot - current_slot_index * market.end_slot_interval * ARRAY_LENGTH)
-                    / market.end_slot_interval
-            } else {
-                ARRAY_LENGTH - 1
-            };
-
-            for i in start_index..=end_index {
-                let slot = i * market.end_slot_interval
-                    + exits_index * market.end_slot_interval * ARRAY_LENGTH;
-                let slot_diff = slot - last_update_slot;
-                last_update_slot = slot;
-                if market_base_flow == 0 || market_quote_flow == 0 {
-                    continue;
-                }
-                quote_per_base += BOOKKEEPING_PRECISION_FACTOR * market_quote_flow
-                    / market_base_flow
-                    * slot_diff as u128;
-
-                let base_exit = match exits_account {
-                    Ok(exits) => exits.base_exits[i as usize],
-                    Err(_) => 0,
-                };
-                let quote_exit = match exits_account {
-                    Ok(exits) => exits.quote_exits[i as usize],
-                    Err(_) => 0,
-                };
-                market_base_flow -= base_exit;
-                market_quote_flow -= quote_exit;
-            }
-
-            // After we went to all exits account we need to sum up prices up to current slot
-            if exits_index == current_slot_index {
-                let slot_diff = current_slot - last_update_slot;
-                if market_base_flow == 0 || market_quote_flow == 0 {
-                    continue;
-                }
-                quote_per_base += BOOKKEEPING_PRECISION_FACTOR * market_quote_flow
-                    / market_base_flow
-                    * slot_diff as u128;
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let market_pda = resolver.market_pda(market.id);
+    let mut exits = BTreeMap::new();
+    for exits_index in crate::core::exits_window(&bookkeeping, &market, current_slot) {
+        let exits_account_pda = resolver.exits_pda(&market_pda.address(), exits_index);
+        // One that can't be read counts as a window without exits.
+        if let Ok(account) = fetch_account::<Exits>(rpc, exits_account_pda.address()).await {
+            if let Some(crash_dump) = crash_dump {
+                crash_dump.record_exits(exits_index, &account);
             }
+            exits.insert(exits_index, account);
         }
-        quote_per_base
-    };
-
-    // Base token inflow since last update slot
-    let accumulated_base_inflow = (base_per_quote - liquidity_position.base_per_quote_snapshot)
-        * liquidity_position.quote_flow_u64 as u128;
-
-    // Quote token inflow since last update slot
-    let accumulated_quote_inflow = (quote_per_base - liquidity_position.quote_per_base_snapshot)
-        * liquidity_position.base_flow_u64 as u128;
+    }
 
+    let flows = crate::core::accumulated_flows(
+        &liquidity_position,
+        &bookkeeping,
+        &market,
+        &exits,
+        current_slot,
+    )?;
     info!(
         event.name = "liquidity_position_computed_flows",
-        position.base_outflow.raw = accumulated_base_outflow / BOOKKEEPING_PRECISION_FACTOR,
-        position.base_inflow.raw = accumulated_base_inflow / BOOKKEEPING_PRECISION_FACTOR,
-        position.quote_outflow.raw = accumulated_quote_outflow / BOOKKEEPING_PRECISION_FACTOR,
-        position.quote_inflow.raw = accumulated_quote_inflow / BOOKKEEPING_PRECISION_FACTOR,
+        position.base_outflow.raw = flows.base_outflow / BOOKKEEPING_PRECISION_FACTOR,
+        position.base_inflow.raw = flows.base_inflow / BOOKKEEPING_PRECISION_FACTOR,
+        position.quote_outflow.raw = flows.quote_outflow / BOOKKEEPING_PRECISION_FACTOR,
+        position.quote_inflow.raw = flows.quote_inflow / BOOKKEEPING_PRECISION_FACTOR,
     );
 
-    let base_balance;
-    let base_debt;
-    if accumulated_base_outflow > liquidity_position.base_balance + accumulated_base_inflow {
-        base_balance = 0;
-        base_debt =
-            (accumulated_base_outflow - liquidity_position.base_balance - accumulated_base_inflow)
-                / BOOKKEEPING_PRECISION_FACTOR;
-    } else {
-        base_balance = (liquidity_position.base_balance + accumulated_base_inflow
-            - accumulated_base_outflow)
-            / BOOKKEEPING_PRECISION_FACTOR;
-        base_debt = 0;
-    }
-
-    let quote_balance;
-    let quote_debt;
-    if accumulated_quote_outflow > liquidity_position.quote_balance + accumulated_quote_inflow {
-        quote_balance = 0;
-        quote_debt = (accumulated_quote_outflow
-            - liquidity_position.quote_balance
-            - accumulated_quote_inflow)
-            / BOOKKEEPING_PRECISION_FACTOR;
-    } else {
-        quote_balance = (liquidity_position.quote_balance + accumulated_quote_inflow
-            - accumulated_quote_outflow)
-            / BOOKKEEPING_PRECISION_FACTOR;
-        quote_debt = 0;
-    }
-
+    let balances = crate::core::balances_from_flows(&liquidity_position, &flows)?;
     info!(
         event.name = "liquidity_position_computed_balances",
-        position.base_balance.raw = base_balance,
-        position.base_debt.raw = base_debt,
-        position.quote_balance.raw = quote_balance,
-        position.quote_debt.raw = quote_debt,
+        position.base_balance.raw = balances.base_balance,
+        position.base_debt.raw = balances.base_debt,
+        position.quote_balance.raw = balances.quote_balance,
+        position.quote_debt.raw = balances.quote_debt,
     );
+    if let Some(crash_dump) = crash_dump {
+        crash_dump.record_balances(&balances);
+    }
+    Ok(balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_down_to_the_next_window() {
+        // Windows of 10 * 5 slots: 0..=49, 50..=99, ...
+        assert_eq!(slots_until_next_window(0, 5), 50);
+        assert_eq!(slots_until_next_window(49, 5), 1);
+        assert_eq!(slots_until_next_window(50, 5), 50);
+        assert_eq!(slots_until_next_window(1_234, 5), 16);
+
+        assert!(!next_window_needs_opening(1_234, 5, 15));
+        assert!(next_window_needs_opening(1_235, 5, 15));
+        assert!(next_window_needs_opening(1_249, 5, 15));
+    }
 
-    LiquidityPositionBalances {
-        base_balance: base_balance as u64,
-        quote_balance: quote_balance as u64,
-        base_debt: base_debt as u64,
-        quote_debt: quote_debt as u64,
+    #[test]
+    fn jittered_stays_within_the_spread() {
+        let interval = Duration::from_secs(300);
+        assert_eq!(jittered(interval, 0), interval);
+        for _ in 0..100 {
+            let wait = jittered(interval, 10);
+            assert!(wait >= Duration::from_secs(270) && wait <= Duration::from_secs(330));
+        }
     }
 }
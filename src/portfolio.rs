@@ -0,0 +1,385 @@
+//! Everything a set of wallets holds across twob markets, valued in quote terms.
+//!
+//! A [`Portfolio`] combines idle wallet balances (the markets' base and quote ATAs),
+//! liquidity positions (accrued with [`get_liquidity_position_balances`]) and open trade
+//! positions (estimated from the bookkeeping). Idle quote parked in a lending venue is
+//! added by the caller with [`Portfolio::add_lent`]. Markets are assumed to share one quote
+//! token, so values from different markets add up.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anchor_client::Program;
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use serde::Serialize;
+use solana_rpc_client_types::filter::{Memcmp, RpcFilterType};
+use tracing::warn;
+
+use crate::{
+    AccountResolver, LiquidityPositionBalances, ProgramPayer, fetch_liquidity_position,
+    fetch_market_state, get_liquidity_position_balances, get_token_program_id,
+    quote::flow_price,
+    risk::PositionExposure,
+    state::estimated_trade_fill,
+    twob_anchor::{self, accounts::TradePosition},
+};
+
+/// Mints, decimals and the price a market's holdings are valued at.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MarketValuation {
+    pub market_id: u64,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub base_mint: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub quote_mint: Pubkey,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    /// Quote per base in UI units; `None` when the market isn't flowing and no price was
+    /// supplied.
+    pub price: Option<f64>,
+}
+
+impl MarketValuation {
+    /// Value raw base and quote amounts in UI quote units.
+    pub fn value(&self, base: u64, quote: u64) -> Option<f64> {
+        let base_ui = base as f64 / 10f64.powi(i32::from(self.base_decimals));
+        let quote_ui = quote as f64 / 10f64.powi(i32::from(self.quote_decimals));
+        if base == 0 {
+            return Some(quote_ui);
+        }
+        self.price.map(|price| base_ui * price + quote_ui)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HoldingKind {
+    Wallet,
+    LiquidityPosition {
+        base_flow: u64,
+        quote_flow: u64,
+    },
+    TradePosition {
+        id: u64,
+        is_buy: bool,
+    },
+    /// Idle quote parked in a lending venue by [`crate::lending`].
+    Lending,
+}
+
+/// One source of base and quote tokens, in raw units. Debt is already netted out.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Holding {
+    #[serde(flatten)]
+    pub kind: HoldingKind,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub owner: Pubkey,
+    pub market_id: u64,
+    pub base: u64,
+    pub quote: u64,
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Portfolio {
+    pub markets: BTreeMap<u64, MarketValuation>,
+    pub holdings: Vec<Holding>,
+    /// Sum of every priced holding, in UI quote units.
+    pub total_value: f64,
+    /// Holdings left out of `total_value` because their market has no price.
+    pub unpriced: usize,
+}
+
+impl Portfolio {
+    fn push(&mut self, holding: Holding) {
+        match holding.value {
+            Some(value) => self.total_value += value,
+            None => self.unpriced += 1,
+        }
+        self.holdings.push(holding);
+    }
+
+    /// Add `quote` that `owner` has lent out on `market_id`'s behalf, valued like the
+    /// market's quote. Ignored for markets the portfolio doesn't cover.
+    pub fn add_lent(&mut self, owner: Pubkey, market_id: u64, quote: u64) {
+        let Some(valuation) = self.markets.get(&market_id) else {
+            return;
+        };
+        let value = valuation.value(0, quote);
+        self.push(Holding {
+            kind: HoldingKind::Lending,
+            owner,
+            market_id,
+            base: 0,
+            quote,
+            value,
+        });
+    }
+
+    /// Liquidity positions summed per market, in the shape [`crate::risk::RiskEngine`]
+    /// observes.
+    pub fn position_exposures(&self) -> BTreeMap<u64, PositionExposure> {
+        let mut exposures = BTreeMap::<u64, PositionExposure>::new();
+        for holding in &self.holdings {
+            let HoldingKind::LiquidityPosition {
+                base_flow,
+                quote_flow,
+            } = holding.kind
+            else {
+                continue;
+            };
+            let exposure = exposures.entry(holding.market_id).or_default();
+            exposure.base_deployed = exposure.base_deployed.saturating_add(holding.base);
+            exposure.quote_deployed = exposure.quote_deployed.saturating_add(holding.quote);
+            exposure.base_flow = exposure.base_flow.saturating_add(base_flow);
+            exposure.quote_flow = exposure.quote_flow.saturating_add(quote_flow);
+            exposure.value += holding.value.unwrap_or_default();
+        }
+        exposures
+    }
+
+    /// Total value per holding kind: wallets (including lent idle quote), liquidity
+    /// positions, trade positions.
+    pub fn value_by_kind(&self) -> (f64, f64, f64) {
+        self.holdings
+            .iter()
+            .fold((0.0, 0.0, 0.0), |(wallet, liquidity, trade), holding| {
+                let value = holding.value.unwrap_or_default();
+                match holding.kind {
+                    HoldingKind::Wallet | HoldingKind::Lending => {
+                        (wallet + value, liquidity, trade)
+                    }
+                    HoldingKind::LiquidityPosition { .. } => (wallet, liquidity + value, trade),
+                    HoldingKind::TradePosition { .. } => (wallet, liquidity, trade + value),
+                }
+            })
+    }
+}
+
+/// Fetch and value everything `wallets` hold in `market_ids`.
+///
+/// `prices` overrides the valuation price per market (e.g. with an oracle price); other
+/// markets are valued at their current flow price. Missing positions and token accounts
+/// count as empty.
+pub async fn fetch_portfolio(
+    program: &Program<ProgramPayer>,
+    wallets: &[Pubkey],
+    market_ids: &[u64],
+    prices: &BTreeMap<u64, f64>,
+) -> anyhow::Result<Portfolio> {
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let rpc = program.rpc();
+    let mut portfolio = Portfolio::default();
+    let mut states = BTreeMap::new();
+
+    for &market_id in market_ids {
+        let state = fetch_market_state(program, market_id).await?;
+        let base_decimals = rpc
+            .get_token_supply(&state.market.base_mint)
+            .await?
+            .decimals;
+        let quote_decimals = rpc
+            .get_token_supply(&state.market.quote_mint)
+            .await?
+            .decimals;
+        let price = prices.get(&market_id).copied().or_else(|| {
+            flow_price(
+                state.market.base_flow,
+                state.market.quote_flow,
+                base_decimals,
+                quote_decimals,
+            )
+        });
+        portfolio.markets.insert(
+            market_id,
+            MarketValuation {
+                market_id,
+                base_mint: state.market.base_mint,
+                quote_mint: state.market.quote_mint,
+                base_decimals,
+                quote_decimals,
+                price,
+            },
+        );
+        states.insert(market_id, state);
+    }
+
+    // Each mint is counted once, under the first market that trades it.
+    let mut seen_mints = BTreeSet::new();
+    let mint_holdings = portfolio
+        .markets
+        .values()
+        .flat_map(|valuation| {
+            [
+                (valuation.market_id, valuation.base_mint, true),
+                (valuation.market_id, valuation.quote_mint, false),
+            ]
+        })
+        .filter(|(_, mint, _)| seen_mints.insert(*mint))
+        .collect::<Vec<_>>();
+    for &(market_id, mint, is_base) in &mint_holdings {
+        let token_program = get_token_program_id(program, &mint).await?;
+        for wallet in wallets {
+            let ata = get_associated_token_address_with_program_id(wallet, &mint, &token_program);
+            let amount = rpc
+                .get_token_account_balance(&ata)
+                .await
+                .ok()
+                .and_then(|balance| balance.amount.parse::<u64>().ok())
+                .unwrap_or(0);
+            if amount == 0 {
+                continue;
+            }
+            let (base, quote) = if is_base { (amount, 0) } else { (0, amount) };
+            portfolio.push(Holding {
+                kind: HoldingKind::Wallet,
+                owner: *wallet,
+                market_id,
+                base,
+                quote,
+                value: portfolio.markets[&market_id].value(base, quote),
+            });
+        }
+    }
+
+    for (&market_id, state) in &states {
+        let valuation = portfolio.markets[&market_id];
+        let market_pda = resolver.market_pda(market_id).address();
+        for wallet in wallets {
+            if let Ok(position) = fetch_liquidity_position(program, market_id, wallet).await {
+                let balances = get_liquidity_position_balances(
+                    program,
+                    position,
+                    state.bookkeeping,
+                    state.market,
+                    state.current_slot,
+                )
+                .await?;
+                let (base, quote) = net_of_debt(&balances);
+                portfolio.push(Holding {
+                    kind: HoldingKind::LiquidityPosition {
+                        base_flow: position.base_flow_u64,
+                        quote_flow: position.quote_flow_u64,
+                    },
+                    owner: *wallet,
+                    market_id,
+                    base,
+                    quote,
+                    value: valuation.value(base, quote),
+                });
+            }
+
+            let trade_positions = program
+                .accounts::<TradePosition>(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    8,
+                    wallet.as_ref(),
+                ))])
+                .await
+                .unwrap_or_else(|error| {
+                    warn!(
+                        event.name = "portfolio_trade_positions_fetch_failed",
+                        portfolio.wallet = %wallet,
+                        ?error,
+                    );
+                    Vec::new()
+                });
+            for (address, position) in trade_positions {
+                // Trade positions don't store their market; match the PDA instead.
+                let expected = resolver
+                    .trade_position_pda(&market_pda, wallet, position.id)
+                    .address();
+                if address != expected {
+                    continue;
+                }
+                let fill = estimated_trade_fill(&position, &state.bookkeeping);
+                let is_buy = position.is_buy == 1;
+                let (base, quote) = if is_buy {
+                    (fill.received, fill.unspent(&position))
+                } else {
+                    (fill.unspent(&position), fill.received)
+                };
+                portfolio.push(Holding {
+                    kind: HoldingKind::TradePosition {
+                        id: position.id,
+                        is_buy,
+                    },
+                    owner: *wallet,
+                    market_id,
+                    base,
+                    quote,
+                    value: valuation.value(base, quote),
+                });
+            }
+        }
+    }
+
+    Ok(portfolio)
+}
+
+fn net_of_debt(balances: &LiquidityPositionBalances) -> (u64, u64) {
+    (
+        balances.base_balance.saturating_sub(balances.base_debt),
+        balances.quote_balance.saturating_sub(balances.quote_debt),
+    )
+}
+
+fn serialize_pubkey<S: serde::Serializer>(
+    pubkey: &Pubkey,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(pubkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valuation(price: Option<f64>) -> MarketValuation {
+        MarketValuation {
+            market_id: 1,
+            base_mint: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+            base_decimals: 9,
+            quote_decimals: 6,
+            price,
+        }
+    }
+
+    #[test]
+    fn holdings_are_valued_in_quote_and_unpriced_ones_counted() {
+        let priced = valuation(Some(150.0));
+        assert_eq!(priced.value(2_000_000_000, 5_000_000), Some(305.0));
+        assert_eq!(valuation(None).value(0, 5_000_000), Some(5.0));
+        assert_eq!(valuation(None).value(1, 0), None);
+
+        let mut portfolio = Portfolio::default();
+        let owner = Pubkey::new_unique();
+        portfolio.push(Holding {
+            kind: HoldingKind::LiquidityPosition {
+                base_flow: 10,
+                quote_flow: 20,
+            },
+            owner,
+            market_id: 1,
+            base: 2_000_000_000,
+            quote: 5_000_000,
+            value: Some(305.0),
+        });
+        portfolio.push(Holding {
+            kind: HoldingKind::Wallet,
+            owner,
+            market_id: 2,
+            base: 7,
+            quote: 0,
+            value: None,
+        });
+
+        assert_eq!(portfolio.total_value, 305.0);
+        assert_eq!(portfolio.unpriced, 1);
+        assert_eq!(portfolio.value_by_kind(), (0.0, 305.0, 0.0));
+        let exposures = portfolio.position_exposures();
+        assert_eq!(exposures.len(), 1);
+        assert_eq!(exposures[&1].base_flow, 10);
+        assert_eq!(exposures[&1].value, 305.0);
+    }
+}
@@ -4,10 +4,9 @@ use serde::Deserialize;
 use serde_json::Value;
 use tracing::{info, warn};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PriceData {
     pub price: f64,
-    #[allow(dead_code)]
     pub timestamp: u64,
 }
 
@@ -97,6 +96,23 @@ fn json_type(value: &Value) -> &'static str {
     }
 }
 
+/// Quote per base in UI units for a pair of raw amounts.
+pub fn ui_price(
+    base_raw: u128,
+    quote_raw: u128,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Option<f64> {
+    if base_raw == 0 || quote_raw == 0 {
+        return None;
+    }
+
+    let base_ui = base_raw as f64 / 10f64.powi(i32::from(base_token_decimals));
+    let quote_ui = quote_raw as f64 / 10f64.powi(i32::from(quote_token_decimals));
+    let price = quote_ui / base_ui;
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,4 +154,11 @@ mod tests {
         assert_eq!(price, 42.5);
         assert_eq!(timestamp, 1_771_255_481);
     }
+
+    #[test]
+    fn ui_price_respects_decimals() {
+        let price = ui_price(2_000_000_000, 168_000_000, 9, 6).unwrap();
+        assert!((price - 84.0).abs() < 1e-9);
+        assert_eq!(ui_price(0, 1, 9, 6), None);
+    }
 }
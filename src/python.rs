@@ -0,0 +1,451 @@
+//! The `twob_mm` Python module, built with the `python` feature by maturin
+//! (`pyproject.toml`): the balance and quote math of [`core`](crate::core), the PDA
+//! [`accounts`](crate::accounts) resolvers and the plain [`instructions`](crate::instructions)
+//! builders, so a notebook works with the production math rather than a port of it.
+//!
+//! Addresses are base58 strings, account data the raw bytes an RPC returns and
+//! instruction data `bytes`. The liquidity instruction builders take the market's account
+//! data and the token programs owning its base and quote mints. Nothing here reaches the
+//! network.
+//!
+//! ```python
+//! import twob_mm
+//!
+//! market = twob_mm.market_address(1)
+//! balances = twob_mm.position_balances(position, bookkeeping, market_data, exits, slot)
+//! flows = twob_mm.compute_target_flows(balances, 151.2, 150.0, 9, 6)
+//! ```
+
+use std::collections::BTreeMap;
+
+use anchor_lang::{
+    AccountDeserialize,
+    prelude::{Pubkey, instruction::Instruction as SolanaInstruction},
+};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+
+use crate::{
+    accounts::AccountResolver,
+    core::{
+        LiquidityPositionBalances, OptimalQuote, exits_window, position_balances as balances,
+        slots_until_debt as runway,
+    },
+    instructions::{
+        BuildError, TokenPrograms, add_liquidity_instruction as add_liquidity,
+        provide_liquidity_instruction as provide_liquidity,
+        update_liquidity_flows_instruction as update_liquidity_flows,
+        withdraw_liquidity_instruction as withdraw_liquidity,
+    },
+    twob_anchor::{
+        self,
+        accounts::{Bookkeeping, Exits, LiquidityPosition, Market},
+        client::args,
+    },
+};
+
+#[pymodule]
+fn twob_mm(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Balances>()?;
+    module.add_class::<Instruction>()?;
+    module.add_function(wrap_pyfunction!(market_address, module)?)?;
+    module.add_function(wrap_pyfunction!(bookkeeping_address, module)?)?;
+    module.add_function(wrap_pyfunction!(liquidity_position_address, module)?)?;
+    module.add_function(wrap_pyfunction!(trade_position_address, module)?)?;
+    module.add_function(wrap_pyfunction!(exits_address, module)?)?;
+    module.add_function(wrap_pyfunction!(prices_address, module)?)?;
+    module.add_function(wrap_pyfunction!(exits_indexes, module)?)?;
+    module.add_function(wrap_pyfunction!(position_balances, module)?)?;
+    module.add_function(wrap_pyfunction!(slots_until_debt, module)?)?;
+    module.add_function(wrap_pyfunction!(compute_target_flows, module)?)?;
+    module.add_function(wrap_pyfunction!(should_update_quote, module)?)?;
+    module.add_function(wrap_pyfunction!(blended_quote_price, module)?)?;
+    module.add_function(wrap_pyfunction!(liquidity_position_price, module)?)?;
+    module.add_function(wrap_pyfunction!(flow_price, module)?)?;
+    module.add_function(wrap_pyfunction!(
+        update_liquidity_flows_instruction,
+        module
+    )?)?;
+    module.add_function(wrap_pyfunction!(provide_liquidity_instruction, module)?)?;
+    module.add_function(wrap_pyfunction!(add_liquidity_instruction, module)?)?;
+    module.add_function(wrap_pyfunction!(withdraw_liquidity_instruction, module)?)?;
+    Ok(())
+}
+
+/// A position's balances, in the token's smallest units.
+#[pyclass(frozen, get_all)]
+#[derive(Clone, Copy)]
+pub struct Balances {
+    pub base_balance: u64,
+    pub quote_balance: u64,
+    pub base_debt: u64,
+    pub quote_debt: u64,
+}
+
+#[pymethods]
+impl Balances {
+    #[new]
+    fn new(base_balance: u64, quote_balance: u64, base_debt: u64, quote_debt: u64) -> Self {
+        Self {
+            base_balance,
+            quote_balance,
+            base_debt,
+            quote_debt,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Balances(base_balance={}, quote_balance={}, base_debt={}, quote_debt={})",
+            self.base_balance, self.quote_balance, self.base_debt, self.quote_debt
+        )
+    }
+}
+
+impl From<LiquidityPositionBalances> for Balances {
+    fn from(balances: LiquidityPositionBalances) -> Self {
+        Self {
+            base_balance: balances.base_balance,
+            quote_balance: balances.quote_balance,
+            base_debt: balances.base_debt,
+            quote_debt: balances.quote_debt,
+        }
+    }
+}
+
+impl From<Balances> for LiquidityPositionBalances {
+    fn from(balances: Balances) -> Self {
+        Self {
+            base_balance: balances.base_balance,
+            quote_balance: balances.quote_balance,
+            base_debt: balances.base_debt,
+            quote_debt: balances.quote_debt,
+        }
+    }
+}
+
+/// An instruction to sign and send with any Solana client.
+#[pyclass(frozen)]
+pub struct Instruction(SolanaInstruction);
+
+#[pymethods]
+impl Instruction {
+    #[getter]
+    fn program_id(&self) -> String {
+        self.0.program_id.to_string()
+    }
+
+    /// `(address, is_signer, is_writable)` for each account, in order.
+    #[getter]
+    fn accounts(&self) -> Vec<(String, bool, bool)> {
+        self.0
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey.to_string(), meta.is_signer, meta.is_writable))
+            .collect()
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.data)
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (market_id, program_id=None))]
+fn market_address(market_id: u64, program_id: Option<&str>) -> PyResult<String> {
+    Ok(resolver(program_id)?
+        .market_pda(market_id)
+        .address()
+        .to_string())
+}
+
+#[pyfunction]
+#[pyo3(signature = (market, program_id=None))]
+fn bookkeeping_address(market: &str, program_id: Option<&str>) -> PyResult<String> {
+    Ok(resolver(program_id)?
+        .bookkeeping_pda(&pubkey(market)?)
+        .address()
+        .to_string())
+}
+
+#[pyfunction]
+#[pyo3(signature = (market, authority, program_id=None))]
+fn liquidity_position_address(
+    market: &str,
+    authority: &str,
+    program_id: Option<&str>,
+) -> PyResult<String> {
+    Ok(resolver(program_id)?
+        .liquidity_position_pda(&pubkey(market)?, &pubkey(authority)?)
+        .address()
+        .to_string())
+}
+
+#[pyfunction]
+#[pyo3(signature = (market, authority, position_id, program_id=None))]
+fn trade_position_address(
+    market: &str,
+    authority: &str,
+    position_id: u64,
+    program_id: Option<&str>,
+) -> PyResult<String> {
+    Ok(resolver(program_id)?
+        .trade_position_pda(&pubkey(market)?, &pubkey(authority)?, position_id)
+        .address()
+        .to_string())
+}
+
+#[pyfunction]
+#[pyo3(signature = (market, index, program_id=None))]
+fn exits_address(market: &str, index: u64, program_id: Option<&str>) -> PyResult<String> {
+    Ok(resolver(program_id)?
+        .exits_pda(&pubkey(market)?, index)
+        .address()
+        .to_string())
+}
+
+#[pyfunction]
+#[pyo3(signature = (market, index, program_id=None))]
+fn prices_address(market: &str, index: u64, program_id: Option<&str>) -> PyResult<String> {
+    Ok(resolver(program_id)?
+        .prices_pda(&pubkey(market)?, index)
+        .address()
+        .to_string())
+}
+
+/// Indexes of the exits accounts `position_balances` reads at `current_slot`.
+#[pyfunction]
+fn exits_indexes(bookkeeping: &[u8], market: &[u8], current_slot: u64) -> PyResult<Vec<u64>> {
+    let bookkeeping: Bookkeeping = account(bookkeeping, "bookkeeping")?;
+    let market: Market = account(market, "market")?;
+    Ok(exits_window(&bookkeeping, &market, current_slot).collect())
+}
+
+/// The position's balances at `current_slot`, from its account data and the market's.
+/// `exits` maps the indexes `exits_indexes` returns to their accounts' data; one left out
+/// is read as having no exits.
+#[pyfunction]
+fn position_balances(
+    position: &[u8],
+    bookkeeping: &[u8],
+    market: &[u8],
+    exits: BTreeMap<u64, Vec<u8>>,
+    current_slot: u64,
+) -> PyResult<Balances> {
+    let position: LiquidityPosition = account(position, "liquidity position")?;
+    let bookkeeping: Bookkeeping = account(bookkeeping, "bookkeeping")?;
+    let market: Market = account(market, "market")?;
+    let exits = exits
+        .into_iter()
+        .map(|(index, data)| Ok((index, account::<Exits>(&data, "exits")?)))
+        .collect::<PyResult<_>>()?;
+    balances(&position, &bookkeeping, &market, &exits, current_slot)
+        .map(Balances::from)
+        .map_err(|error| PyValueError::new_err(error.to_string()))
+}
+
+/// Slots until the position runs into debt at the market's current flows; `None` when it
+/// isn't draining either side.
+#[pyfunction]
+fn slots_until_debt(position: &[u8], market: &[u8], balances: Balances) -> PyResult<Option<u64>> {
+    let position: LiquidityPosition = account(position, "liquidity position")?;
+    let market: Market = account(market, "market")?;
+    Ok(runway(&position, &market, &balances.into()))
+}
+
+/// `(base_flow, quote_flow)` quoting `target_quote_price` out of `balances`, or `None`.
+#[pyfunction]
+fn compute_target_flows(
+    balances: Balances,
+    target_quote_price: f64,
+    inventory_quote_price: f64,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Option<(u64, u64)> {
+    crate::core::compute_target_flows(
+        &balances.into(),
+        target_quote_price,
+        inventory_quote_price,
+        base_token_decimals,
+        quote_token_decimals,
+    )
+    .map(|quote| (quote.base_flow, quote.quote_flow))
+}
+
+#[pyfunction]
+fn should_update_quote(
+    current_base_flow: u64,
+    current_quote_flow: u64,
+    target_base_flow: u64,
+    target_quote_flow: u64,
+    threshold_bps: u64,
+) -> bool {
+    crate::core::should_update_quote(
+        current_base_flow,
+        current_quote_flow,
+        &OptimalQuote {
+            base_flow: target_base_flow,
+            quote_flow: target_quote_flow,
+        },
+        threshold_bps,
+    )
+}
+
+#[pyfunction]
+fn blended_quote_price(oracle_price: f64, inventory_price: f64, weight: f64) -> f64 {
+    crate::core::blended_quote_price(
+        oracle_price,
+        inventory_price,
+        crate::core::sanitize_weight(weight),
+    )
+}
+
+#[pyfunction]
+fn liquidity_position_price(
+    balances: Balances,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Option<f64> {
+    crate::core::liquidity_position_price(
+        &balances.into(),
+        base_token_decimals,
+        quote_token_decimals,
+    )
+}
+
+#[pyfunction]
+fn flow_price(
+    base_flow: u128,
+    quote_flow: u128,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Option<f64> {
+    crate::core::flow_price(
+        base_flow,
+        quote_flow,
+        base_token_decimals,
+        quote_token_decimals,
+    )
+}
+
+#[pyfunction]
+fn update_liquidity_flows_instruction(
+    authority: &str,
+    market_id: u64,
+    reference_index: u64,
+    base_flow: u64,
+    quote_flow: u64,
+) -> PyResult<Instruction> {
+    instruction(update_liquidity_flows(
+        pubkey(authority)?,
+        market_id,
+        args::UpdateLiquidityFlows {
+            reference_index,
+            base_flow_u64: base_flow,
+            quote_flow_u64: quote_flow,
+        },
+    ))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn provide_liquidity_instruction(
+    authority: &str,
+    market: &[u8],
+    base_token_program: &str,
+    quote_token_program: &str,
+    reference_index: u64,
+    base_amount: u64,
+    quote_amount: u64,
+    base_flow: u64,
+    quote_flow: u64,
+) -> PyResult<Instruction> {
+    instruction(provide_liquidity(
+        pubkey(authority)?,
+        &account(market, "market")?,
+        token_programs(base_token_program, quote_token_program)?,
+        args::ProvideLiquidity {
+            reference_index,
+            base_deposit_lamports: base_amount,
+            quote_deposit_lamports: quote_amount,
+            base_flow_u64: base_flow,
+            quote_flow_u64: quote_flow,
+        },
+    ))
+}
+
+#[pyfunction]
+fn add_liquidity_instruction(
+    authority: &str,
+    market: &[u8],
+    base_token_program: &str,
+    quote_token_program: &str,
+    reference_index: u64,
+    base_amount: u64,
+    quote_amount: u64,
+) -> PyResult<Instruction> {
+    instruction(add_liquidity(
+        pubkey(authority)?,
+        &account(market, "market")?,
+        token_programs(base_token_program, quote_token_program)?,
+        args::AddLiquidity {
+            reference_index,
+            base_lamports: base_amount,
+            quote_lamports: quote_amount,
+        },
+    ))
+}
+
+#[pyfunction]
+fn withdraw_liquidity_instruction(
+    authority: &str,
+    market: &[u8],
+    base_token_program: &str,
+    quote_token_program: &str,
+    reference_index: u64,
+    base_amount: u64,
+    quote_amount: u64,
+) -> PyResult<Instruction> {
+    instruction(withdraw_liquidity(
+        pubkey(authority)?,
+        &account(market, "market")?,
+        token_programs(base_token_program, quote_token_program)?,
+        args::WithdrawLiquidity {
+            reference_index,
+            base_lamports: base_amount,
+            quote_lamports: quote_amount,
+        },
+    ))
+}
+
+fn resolver(program_id: Option<&str>) -> PyResult<AccountResolver> {
+    let program_id = match program_id {
+        Some(program_id) => pubkey(program_id)?,
+        None => twob_anchor::ID,
+    };
+    Ok(AccountResolver::new(program_id))
+}
+
+fn token_programs(base: &str, quote: &str) -> PyResult<TokenPrograms> {
+    Ok(TokenPrograms {
+        base: pubkey(base)?,
+        quote: pubkey(quote)?,
+    })
+}
+
+fn instruction(built: Result<SolanaInstruction, BuildError>) -> PyResult<Instruction> {
+    built
+        .map(Instruction)
+        .map_err(|error| PyValueError::new_err(error.to_string()))
+}
+
+fn pubkey(value: &str) -> PyResult<Pubkey> {
+    value
+        .parse()
+        .map_err(|error| PyValueError::new_err(format!("invalid address `{value}`: {error}")))
+}
+
+fn account<T: AccountDeserialize>(mut data: &[u8], name: &str) -> PyResult<T> {
+    T::try_deserialize(&mut data)
+        .map_err(|error| PyValueError::new_err(format!("invalid {name}: {error}")))
+}
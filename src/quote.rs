@@ -0,0 +1,109 @@
+use tracing::{info, warn};
+
+pub use crate::core::quote::*;
+use crate::{
+    FLOW_PRECISION, LiquidityPositionBalances, MarketState, price::PriceData,
+    twob_anchor::accounts::LiquidityPosition,
+};
+
+/// Calculate the optimal quote based on oracle price and inventory-implied price.
+pub fn calculate_optimal_quote(
+    price: &PriceData,
+    position: &LiquidityPosition,
+    market_state: &MarketState,
+    balances: &LiquidityPositionBalances,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+    weight: f64,
+) -> OptimalQuote {
+    let fallback = OptimalQuote {
+        base_flow: position.base_flow_u64.max(1),
+        quote_flow: position.quote_flow_u64.max(1),
+    };
+
+    let oracle_price = price.price;
+    if !oracle_price.is_finite() || oracle_price <= 0.0 {
+        warn!(
+            event.name = "quote_compute_fallback",
+            quote.reason = "invalid_oracle_price",
+            price.oracle = oracle_price,
+        );
+        return fallback;
+    }
+
+    let Some(inventory_price) =
+        liquidity_position_price(balances, base_token_decimals, quote_token_decimals)
+    else {
+        warn!(
+            event.name = "quote_compute_fallback",
+            quote.reason = "liquidity_position_price_unavailable",
+            position.base_balance.raw = balances.base_balance,
+            position.quote_balance.raw = balances.quote_balance,
+        );
+        return fallback;
+    };
+
+    let _market_price = market_price_excluding_position(
+        position,
+        market_state,
+        base_token_decimals,
+        quote_token_decimals,
+    );
+
+    let normalized_weight = sanitize_weight(weight);
+    let target_quote_price = blended_quote_price(oracle_price, inventory_price, normalized_weight);
+
+    let Some(target_flows) = compute_target_flows(
+        balances,
+        target_quote_price,
+        inventory_price,
+        base_token_decimals,
+        quote_token_decimals,
+    ) else {
+        warn!(
+            event.name = "quote_compute_fallback",
+            quote.reason = "target_flow_compute_failed",
+            position.base_balance.raw = balances.base_balance,
+            position.quote_balance.raw = balances.quote_balance,
+            quote.target_price = target_quote_price,
+        );
+        return fallback;
+    };
+
+    info!(
+        event.name = "quote_computed",
+        price.oracle = oracle_price,
+        price.inventory = inventory_price,
+        quote.weight = normalized_weight,
+        quote.target_price = target_quote_price,
+        quote.target_base_flow = target_flows.base_flow,
+        quote.target_quote_flow = target_flows.quote_flow,
+        quote.previous_base_flow = position.base_flow_u64,
+        quote.previous_quote_flow = position.quote_flow_u64,
+    );
+
+    target_flows
+}
+
+fn market_price_excluding_position(
+    position: &LiquidityPosition,
+    market_state: &MarketState,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Option<f64> {
+    let own_base_flow = position.base_flow_u64 as u128 * FLOW_PRECISION;
+    let own_quote_flow = position.quote_flow_u64 as u128 * FLOW_PRECISION;
+
+    if market_state.market.base_flow <= own_base_flow
+        || market_state.market.quote_flow <= own_quote_flow
+    {
+        return None;
+    }
+
+    flow_price(
+        market_state.market.base_flow - own_base_flow,
+        market_state.market.quote_flow - own_quote_flow,
+        base_token_decimals,
+        quote_token_decimals,
+    )
+}
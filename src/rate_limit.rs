@@ -0,0 +1,307 @@
+//! Request quotas for RPC endpoints.
+//!
+//! Every endpoint with a configured rate gets one token bucket for the whole process, so a
+//! burst of market events can't push the bot past a paid plan's quota however many
+//! fetchers and senders are calling it at once. Callers over the rate wait for a token
+//! rather than fail. RPC clients from [`rpc_client`] take their tokens on every request;
+//! anchor's `Program` builds its own clients, so fetchers going through it
+//! [`throttle`] first.
+//!
+//! Rates come from `RPC_RATE_LIMIT_RPS`, for any endpoint, and `RPC_RATE_LIMITS`, per
+//! endpoint host as `host=rps` pairs separated by commas. Each bucket holds a second's
+//! worth of requests, so that many can go out at once after a quiet spell.
+//!
+//! Clients from [`rpc_client`] also time every request for [`rpc_metrics`], rate or no
+//! rate.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+use solana_rpc_client::{
+    http_sender::HttpSender,
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_rpc_client_api::{client_error::Result as ClientResult, request::RpcRequest};
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use crate::{config::env_var, rpc_metrics};
+
+/// Requests per second allowed to each endpoint.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimitConfig {
+    /// For endpoints not in `per_endpoint`; `None` leaves them unlimited.
+    pub default_rps: Option<f64>,
+    /// By endpoint host, e.g. `mainnet.helius-rpc.com`.
+    pub per_endpoint: HashMap<String, f64>,
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let rps = |name: &str, value: &str| -> anyhow::Result<f64> {
+            let rps = value
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))?;
+            anyhow::ensure!(rps > 0.0, "{} must be positive", name);
+            Ok(rps)
+        };
+        let default_rps = env_var("RPC_RATE_LIMIT_RPS")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| rps("RPC_RATE_LIMIT_RPS", &value))
+            .transpose()?;
+        let per_endpoint = env_var("RPC_RATE_LIMITS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (host, value) = entry.rsplit_once('=').ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Invalid RPC_RATE_LIMITS entry {} (expected host=rps)",
+                        entry
+                    )
+                })?;
+                Ok((host.trim().to_string(), rps("RPC_RATE_LIMITS", value)?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            default_rps,
+            per_endpoint,
+        })
+    }
+
+    fn rps_for(&self, host: &str) -> Option<f64> {
+        self.per_endpoint.get(host).copied().or(self.default_rps)
+    }
+}
+
+/// Requests made through a [`RateLimiter`] and how many had to wait.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimitStats {
+    pub requests: u64,
+    pub throttled: u64,
+}
+
+/// A token bucket for one endpoint.
+#[derive(Debug)]
+pub struct RateLimiter {
+    endpoint: String,
+    rps: f64,
+    bucket: Mutex<Bucket>,
+    requests: AtomicU64,
+    throttled: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Negative while requests are waiting on tokens not yet refilled.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Take a token for a request made at `now`, returning how long it must wait for it.
+    fn reserve(&mut self, now: Instant, rps: f64, requests: f64) -> Duration {
+        let refilled = now.saturating_duration_since(self.updated).as_secs_f64() * rps;
+        self.tokens = (self.tokens + refilled).min(rps.max(1.0));
+        self.updated = now;
+        self.tokens -= requests;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rps)
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(endpoint: &str, rps: f64) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            rps,
+            bucket: Mutex::new(Bucket {
+                tokens: rps.max(1.0),
+                updated: Instant::now(),
+            }),
+            requests: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wait until `requests` more requests fit in the rate.
+    pub async fn acquire(&self, requests: u32) {
+        let wait = self
+            .bucket
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .reserve(Instant::now(), self.rps, f64::from(requests));
+        self.requests
+            .fetch_add(u64::from(requests), Ordering::Relaxed);
+        debug!(
+            event.name = "rpc_request_admitted",
+            rpc.endpoint = self.endpoint.as_str(),
+            monotonic_counter.rpc_requests_total = u64::from(requests),
+        );
+        if wait.is_zero() {
+            return;
+        }
+
+        self.throttled
+            .fetch_add(u64::from(requests), Ordering::Relaxed);
+        info!(
+            event.name = "rpc_rate_limited",
+            rpc.endpoint = self.endpoint.as_str(),
+            rpc.rps = self.rps,
+            histogram.rpc_rate_limit_wait_ms = wait.as_millis() as u64,
+            monotonic_counter.rpc_throttled_total = u64::from(requests),
+        );
+        sleep(wait).await;
+    }
+}
+
+/// The process's limiter for the endpoint at `url`, `None` when it has no rate. Rates are
+/// read from the environment on first use.
+pub fn limiter(url: &str) -> Option<Arc<RateLimiter>> {
+    static CONFIG: OnceLock<RateLimitConfig> = OnceLock::new();
+    static LIMITERS: OnceLock<Mutex<HashMap<String, Arc<RateLimiter>>>> = OnceLock::new();
+
+    let config = CONFIG.get_or_init(|| {
+        RateLimitConfig::from_env().unwrap_or_else(|error| {
+            warn!(event.name = "rpc_rate_limit_config_invalid", ?error);
+            RateLimitConfig::default()
+        })
+    });
+    let host = endpoint_host(url);
+    let rps = config.rps_for(&host)?;
+    let mut limiters = LIMITERS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    Some(
+        limiters
+            .entry(host.clone())
+            .or_insert_with(|| Arc::new(RateLimiter::new(&host, rps)))
+            .clone(),
+    )
+}
+
+/// Wait until `requests` more requests to `rpc`'s endpoint fit in its rate.
+pub async fn throttle(rpc: &RpcClient, requests: u32) {
+    if let Some(limiter) = limiter(&rpc.url()) {
+        limiter.acquire(requests).await;
+    }
+}
+
+/// An RPC client for `url` that takes a token from the endpoint's limiter for every
+/// request and records how each went.
+pub fn rpc_client(url: &str, commitment: CommitmentConfig) -> RpcClient {
+    RpcClient::new_sender(
+        EndpointSender {
+            inner: HttpSender::new(url),
+            limiter: limiter(url),
+        },
+        RpcClientConfig::with_commitment(commitment),
+    )
+}
+
+/// The host an endpoint is known by, so limits and metrics never carry the API key some
+/// providers put in the URL.
+pub(crate) fn endpoint_host(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| url.to_string())
+}
+
+struct EndpointSender {
+    inner: HttpSender,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+#[async_trait::async_trait]
+impl RpcSender for EndpointSender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire(1).await;
+        }
+        // Timed after the wait, so our own throttling isn't blamed on the endpoint.
+        rpc_metrics::timed(
+            &self.inner.url(),
+            &request.to_string(),
+            self.inner.send(request, params),
+        )
+        .await
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_spends_its_burst_then_paces() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 2.0,
+            updated: start,
+        };
+        assert_eq!(bucket.reserve(start, 2.0, 1.0), Duration::ZERO);
+        assert_eq!(bucket.reserve(start, 2.0, 1.0), Duration::ZERO);
+        assert_eq!(bucket.reserve(start, 2.0, 1.0), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(start, 2.0, 1.0), Duration::from_secs(1));
+
+        // A quiet spell refills no more than one second's worth.
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.reserve(later, 2.0, 2.0), Duration::ZERO);
+        assert_eq!(bucket.reserve(later, 2.0, 1.0), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn endpoints_are_known_by_host() {
+        assert_eq!(
+            endpoint_host("https://mainnet.helius-rpc.com/?api-key=secret"),
+            "mainnet.helius-rpc.com"
+        );
+        let config = RateLimitConfig {
+            default_rps: Some(10.0),
+            per_endpoint: HashMap::from([("mainnet.helius-rpc.com".to_string(), 50.0)]),
+        };
+        assert_eq!(config.rps_for("mainnet.helius-rpc.com"), Some(50.0));
+        assert_eq!(config.rps_for("api.mainnet-beta.solana.com"), Some(10.0));
+        assert_eq!(RateLimitConfig::default().rps_for("localhost"), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Where one bot instance's audit records, webhooks, event bus messages and crash dumps go.
+//!
+//! A bot sets up its [`Reporting`] from its own settings and hands it to whatever reports
+//! on its behalf, its [`TxSender`](crate::tx::TxSender) among them, so bots sharing a
+//! process under the orchestrator each report as themselves.
+
+use std::sync::Arc;
+
+use anchor_lang::prelude::Pubkey;
+
+use crate::{
+    audit::AuditLog,
+    config::CommonConfig,
+    crash_dump::CrashDump,
+    event_bus::{BusEvent, EventBus},
+    webhooks::{LifecycleEvent, Webhooks},
+};
+
+/// One bot instance's outlets; each is `None` when not configured. Cheap to clone.
+#[derive(Debug, Clone, Default)]
+pub struct Reporting {
+    pub audit_log: Option<Arc<AuditLog>>,
+    pub webhooks: Option<Webhooks>,
+    pub event_bus: Option<EventBus>,
+    pub crash_dump: Option<CrashDump>,
+}
+
+impl Reporting {
+    /// Start what `common` configures, for `bot`. Call from within the Tokio runtime.
+    pub fn start(common: &CommonConfig, bot: &'static str) -> anyhow::Result<Self> {
+        Ok(Self {
+            audit_log: AuditLog::from_env().map(Arc::new),
+            webhooks: common
+                .webhooks
+                .clone()
+                .map(|config| Webhooks::new(config, bot))
+                .transpose()?,
+            event_bus: common
+                .event_bus
+                .clone()
+                .map(|config| EventBus::start(config, bot)),
+            crash_dump: common
+                .crash_dump
+                .clone()
+                .map(|config| CrashDump::new(config, bot, common.market_id)),
+        })
+    }
+
+    /// [`Webhooks::fire`], if there are webhooks.
+    pub fn fire(&self, market_id: u64, authority: &Pubkey, event: LifecycleEvent) {
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.fire(market_id, authority, event);
+        }
+    }
+
+    /// [`EventBus::publish`], if there is an event bus.
+    pub fn publish(&self, event: BusEvent) {
+        if let Some(event_bus) = &self.event_bus {
+            event_bus.publish(event);
+        }
+    }
+
+    /// [`CrashDump::write`], if crash dumps are kept.
+    pub fn write_crash_dump(&self, reason: &str) {
+        if let Some(crash_dump) = &self.crash_dump {
+            crash_dump.write(reason);
+        }
+    }
+}
@@ -0,0 +1,601 @@
+//! Process-wide risk limits across every position a process manages.
+//!
+//! One [`RiskEngine`] is shared by every bot in the process, including each instance the
+//! orchestrator runs. Bots report their position to it after refreshing its state and ask
+//! it to [`check`](RiskEngine::check) every [`Action`] before carrying it out. Flow limits
+//! veto updates that would push the aggregate flow over the limit. Breaching the deployed
+//! or drawdown limits halts the engine: from then on only actions that reduce risk pass,
+//! and each bot is expected to pull its own quotes with [`pull_all_quotes`] the next time
+//! it reports a position that still quotes.
+//!
+//! Idle quote parked in a lending venue ([`crate::lending`]) is reported with
+//! [`RiskEngine::observe_lent`]: it counts towards the value the drawdown limit watches, and
+//! new deposits are checked against the lending limit with [`RiskEngine::check_lend`].
+//!
+//! Amounts are summed raw across markets, so the base and quote limits are only meaningful
+//! when the markets a process trades share their mints.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use anchor_client::Program;
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::{
+    LiquidityPositionBalances, ProgramPayer,
+    config::env_var,
+    execute_update_flows, fetch_market_state, nearest_reference_index,
+    portfolio::Portfolio,
+    strategy::Action,
+    tx::{SendOptions, TxSender},
+};
+
+/// Aggregate limits. `None` disables a limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RiskLimits {
+    pub max_base_deployed: Option<u64>,
+    pub max_quote_deployed: Option<u64>,
+    pub max_base_flow: Option<u64>,
+    pub max_quote_flow: Option<u64>,
+    /// Largest tolerated fall of total position value from its peak.
+    pub max_drawdown_bps: Option<u64>,
+    /// Largest total quote parked in lending venues.
+    pub max_lent: Option<u64>,
+}
+
+impl RiskLimits {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let limit = |name: &str| -> anyhow::Result<Option<u64>> {
+            env_var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<u64>()
+                        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            max_base_deployed: limit("RISK_MAX_BASE_DEPLOYED")?,
+            max_quote_deployed: limit("RISK_MAX_QUOTE_DEPLOYED")?,
+            max_base_flow: limit("RISK_MAX_BASE_FLOW")?,
+            max_quote_flow: limit("RISK_MAX_QUOTE_FLOW")?,
+            max_drawdown_bps: limit("RISK_MAX_DRAWDOWN_BPS")?,
+            max_lent: limit("RISK_MAX_LENT")?,
+        })
+    }
+}
+
+/// One position as last reported by its runtime.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PositionExposure {
+    pub base_deployed: u64,
+    pub quote_deployed: u64,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+    /// Marked value in whatever unit the process values positions in, e.g. quote tokens.
+    pub value: f64,
+}
+
+impl PositionExposure {
+    pub fn new(
+        balances: &LiquidityPositionBalances,
+        base_flow: u64,
+        quote_flow: u64,
+        value: f64,
+    ) -> Self {
+        Self {
+            base_deployed: balances.base_balance,
+            quote_deployed: balances.quote_balance,
+            base_flow,
+            quote_flow,
+            value,
+        }
+    }
+}
+
+/// Totals across every tracked position.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Exposure {
+    pub positions: usize,
+    pub base_deployed: u64,
+    pub quote_deployed: u64,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+    /// Quote parked in lending venues.
+    pub lent: u64,
+    pub value: f64,
+    pub peak_value: f64,
+    pub drawdown_bps: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RiskViolation {
+    BaseDeployed {
+        total: u64,
+        limit: u64,
+    },
+    QuoteDeployed {
+        total: u64,
+        limit: u64,
+    },
+    BaseFlow {
+        total: u64,
+        limit: u64,
+    },
+    QuoteFlow {
+        total: u64,
+        limit: u64,
+    },
+    Drawdown {
+        drawdown_bps: u64,
+        limit: u64,
+    },
+    Lent {
+        total: u64,
+        limit: u64,
+    },
+    /// The engine was halted by an earlier breach and only risk-reducing actions pass.
+    Halted,
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskViolation::BaseDeployed { total, limit } => {
+                write!(f, "base deployed {} exceeds limit {}", total, limit)
+            }
+            RiskViolation::QuoteDeployed { total, limit } => {
+                write!(f, "quote deployed {} exceeds limit {}", total, limit)
+            }
+            RiskViolation::BaseFlow { total, limit } => {
+                write!(f, "total base flow {} would exceed limit {}", total, limit)
+            }
+            RiskViolation::QuoteFlow { total, limit } => {
+                write!(f, "total quote flow {} would exceed limit {}", total, limit)
+            }
+            RiskViolation::Drawdown {
+                drawdown_bps,
+                limit,
+            } => write!(f, "drawdown {}bps exceeds limit {}bps", drawdown_bps, limit),
+            RiskViolation::Lent { total, limit } => {
+                write!(f, "total lent {} would exceed limit {}", total, limit)
+            }
+            RiskViolation::Halted => f.write_str("risk engine is halted"),
+        }
+    }
+}
+
+impl std::error::Error for RiskViolation {}
+
+#[derive(Debug, Default)]
+struct Inner {
+    positions: BTreeMap<u64, PositionExposure>,
+    /// Raw quote lent and its value, per market.
+    lent: BTreeMap<u64, (u64, f64)>,
+    peak_value: f64,
+    halted: Option<RiskViolation>,
+}
+
+impl Inner {
+    fn exposure(&self) -> Exposure {
+        let mut exposure = Exposure {
+            positions: self.positions.len(),
+            peak_value: self.peak_value,
+            ..Exposure::default()
+        };
+        for position in self.positions.values() {
+            exposure.base_deployed = exposure
+                .base_deployed
+                .saturating_add(position.base_deployed);
+            exposure.quote_deployed = exposure
+                .quote_deployed
+                .saturating_add(position.quote_deployed);
+            exposure.base_flow = exposure.base_flow.saturating_add(position.base_flow);
+            exposure.quote_flow = exposure.quote_flow.saturating_add(position.quote_flow);
+            exposure.value += position.value;
+        }
+        for &(lent, value) in self.lent.values() {
+            exposure.lent = exposure.lent.saturating_add(lent);
+            exposure.value += value;
+        }
+        if self.peak_value > 0.0 && exposure.value < self.peak_value {
+            exposure.drawdown_bps =
+                ((self.peak_value - exposure.value) / self.peak_value * 10_000.0).round() as u64;
+        }
+        exposure
+    }
+}
+
+/// Tracks positions by market id; the bots sharing an engine are expected to run one
+/// position per market between them.
+#[derive(Debug)]
+pub struct RiskEngine {
+    limits: RiskLimits,
+    inner: Mutex<Inner>,
+}
+
+impl RiskEngine {
+    pub fn new(limits: RiskLimits) -> Arc<Self> {
+        Arc::new(Self {
+            limits,
+            inner: Mutex::new(Inner::default()),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // The guarded data is plain numbers, so a poisoned lock is still usable.
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn limits(&self) -> RiskLimits {
+        self.limits
+    }
+
+    pub fn exposure(&self) -> Exposure {
+        self.lock().exposure()
+    }
+
+    /// The value of the position on `market_id` and of the quote it has lent, as last
+    /// reported.
+    pub fn market_value(&self, market_id: u64) -> f64 {
+        let inner = self.lock();
+        let position = inner.positions.get(&market_id).map_or(0.0, |p| p.value);
+        position + inner.lent.get(&market_id).map_or(0.0, |&(_, value)| value)
+    }
+
+    pub fn market_ids(&self) -> Vec<u64> {
+        self.lock().positions.keys().copied().collect()
+    }
+
+    pub fn halted(&self) -> Option<RiskViolation> {
+        self.lock().halted
+    }
+
+    /// Clear a halt once an operator has dealt with it. The drawdown peak restarts from the
+    /// current value so the same loss doesn't halt the engine again immediately.
+    pub fn reset(&self) {
+        let mut inner = self.lock();
+        inner.halted = None;
+        inner.peak_value = inner.exposure().value;
+    }
+
+    /// Record a position's latest state. Returns the breach that halted the engine, once,
+    /// on the observation that caused it; the caller should pull all quotes.
+    pub fn observe(&self, market_id: u64, position: PositionExposure) -> Option<RiskViolation> {
+        let mut inner = self.lock();
+        inner.positions.insert(market_id, position);
+        let value = inner.exposure().value;
+        if value > inner.peak_value {
+            inner.peak_value = value;
+        }
+        if inner.halted.is_some() {
+            return None;
+        }
+
+        let violation = self.breach(&inner.exposure())?;
+        inner.halted = Some(violation);
+        error!(
+            event.name = "risk_limit_breached",
+            market.id = market_id,
+            risk.violation = %violation,
+            monotonic_counter.risk_breaches_total = 1_u64,
+        );
+        Some(violation)
+    }
+
+    /// Record how much of `market_id`'s quote is parked in a lending venue and its value,
+    /// in the same unit as the positions' values. Leaves the drawdown peak to
+    /// [`Self::observe`]: quote lent out of a position counts twice until the position is
+    /// next observed without it.
+    pub fn observe_lent(&self, market_id: u64, lent: u64, value: f64) {
+        let mut inner = self.lock();
+        if lent == 0 {
+            inner.lent.remove(&market_id);
+        } else {
+            inner.lent.insert(market_id, (lent, value));
+        }
+    }
+
+    /// Veto lending `amount` more quote. Recalls always pass; deposits
+    /// don't while the engine is halted.
+    pub fn check_lend(&self, amount: u64) -> Result<(), RiskViolation> {
+        let inner = self.lock();
+        if inner.halted.is_some() {
+            return Err(RiskViolation::Halted);
+        }
+        let total = inner.exposure().lent.saturating_add(amount);
+        match self.limits.max_lent.filter(|l| total > *l) {
+            Some(limit) => Err(RiskViolation::Lent { total, limit }),
+            None => Ok(()),
+        }
+    }
+
+    /// [`Self::observe`] every market's liquidity positions in `portfolio`.
+    pub fn observe_portfolio(&self, portfolio: &Portfolio) -> Option<RiskViolation> {
+        portfolio
+            .position_exposures()
+            .into_iter()
+            .fold(None, |violation, (market_id, exposure)| {
+                violation.or(self.observe(market_id, exposure))
+            })
+    }
+
+    fn breach(&self, exposure: &Exposure) -> Option<RiskViolation> {
+        let limits = &self.limits;
+        if let Some(limit) = limits
+            .max_drawdown_bps
+            .filter(|l| exposure.drawdown_bps > *l)
+        {
+            return Some(RiskViolation::Drawdown {
+                drawdown_bps: exposure.drawdown_bps,
+                limit,
+            });
+        }
+        if let Some(limit) = limits
+            .max_base_deployed
+            .filter(|l| exposure.base_deployed > *l)
+        {
+            return Some(RiskViolation::BaseDeployed {
+                total: exposure.base_deployed,
+                limit,
+            });
+        }
+        if let Some(limit) = limits
+            .max_quote_deployed
+            .filter(|l| exposure.quote_deployed > *l)
+        {
+            return Some(RiskViolation::QuoteDeployed {
+                total: exposure.quote_deployed,
+                limit,
+            });
+        }
+        None
+    }
+
+    /// Veto `action` on `market_id` if it would breach a limit. Stopping, waiting and
+    /// lowering flows always pass, so a position can always be wound down.
+    pub fn check(&self, market_id: u64, action: &Action) -> Result<(), RiskViolation> {
+        let inner = self.lock();
+        match *action {
+            Action::Stop { .. } | Action::Reevaluate { .. } => Ok(()),
+            Action::Rebalance => match inner.halted {
+                Some(_) => Err(RiskViolation::Halted),
+                None => Ok(()),
+            },
+            Action::UpdateFlows {
+                base_flow,
+                quote_flow,
+                ..
+            } => {
+                let current = inner.positions.get(&market_id).copied().unwrap_or_default();
+                let raises_base = base_flow > current.base_flow;
+                let raises_quote = quote_flow > current.quote_flow;
+                if !raises_base && !raises_quote {
+                    return Ok(());
+                }
+                if inner.halted.is_some() {
+                    return Err(RiskViolation::Halted);
+                }
+
+                let exposure = inner.exposure();
+                // `None` if the tracked total lags this market's flow or the new total
+                // overflows, neither of which can be shown to be within a limit.
+                let total_base = exposure
+                    .base_flow
+                    .checked_sub(current.base_flow)
+                    .and_then(|other| other.checked_add(base_flow));
+                let total_quote = exposure
+                    .quote_flow
+                    .checked_sub(current.quote_flow)
+                    .and_then(|other| other.checked_add(quote_flow));
+                if let Some(limit) = self
+                    .limits
+                    .max_base_flow
+                    .filter(|l| raises_base && total_base.is_none_or(|t| t > *l))
+                {
+                    return Err(RiskViolation::BaseFlow {
+                        total: total_base.unwrap_or(u64::MAX),
+                        limit,
+                    });
+                }
+                if let Some(limit) = self
+                    .limits
+                    .max_quote_flow
+                    .filter(|l| raises_quote && total_quote.is_none_or(|t| t > *l))
+                {
+                    return Err(RiskViolation::QuoteFlow {
+                        total: total_quote.unwrap_or(u64::MAX),
+                        limit,
+                    });
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Emergency helper: zero the sender's payer's flows on every market in `market_ids`. Keeps going
+/// past failures so one bad market doesn't leave the others quoting, then reports them.
+pub async fn pull_all_quotes(
+    program: &Program<ProgramPayer>,
+    market_ids: &[u64],
+    sender: &TxSender,
+) -> anyhow::Result<()> {
+    let sender = &sender.with_options(SendOptions::urgent());
+    let mut failed = Vec::new();
+    for &market_id in market_ids {
+        let result = async {
+            let market_state = fetch_market_state(program, market_id).await?;
+            let reference_index = nearest_reference_index(
+                market_state.current_slot,
+                market_state.market.end_slot_interval,
+            );
+            execute_update_flows(program, market_id, 0, 0, reference_index, sender).await?;
+            anyhow::Ok(reference_index)
+        }
+        .await;
+
+        match result {
+            Ok(reference_index) => info!(
+                event.name = "risk_quotes_pulled",
+                market.id = market_id,
+                twob.instruction = "update_liquidity_flows",
+                twob.reference_index = reference_index,
+            ),
+            Err(error) => {
+                error!(
+                    event.name = "risk_pull_quotes_failed",
+                    market.id = market_id,
+                    ?error,
+                );
+                failed.push(market_id);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "Failed to pull quotes on {} of {} markets: {:?}",
+            failed.len(),
+            market_ids.len(),
+            failed
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(base_flow: u64, quote_flow: u64, value: f64) -> PositionExposure {
+        PositionExposure {
+            base_deployed: 1_000,
+            quote_deployed: 1_000,
+            base_flow,
+            quote_flow,
+            value,
+        }
+    }
+
+    fn update(base_flow: u64, quote_flow: u64) -> Action {
+        Action::UpdateFlows {
+            base_flow,
+            quote_flow,
+            reference_index: 0,
+        }
+    }
+
+    #[test]
+    fn flow_limit_counts_other_markets_and_allows_reductions() {
+        let engine = RiskEngine::new(RiskLimits {
+            max_base_flow: Some(100),
+            ..RiskLimits::default()
+        });
+        assert_eq!(engine.observe(1, position(60, 10, 0.0)), None);
+        assert_eq!(engine.observe(2, position(30, 10, 0.0)), None);
+
+        assert_eq!(engine.check(1, &update(70, 10)), Ok(()));
+        assert_eq!(
+            engine.check(1, &update(71, 10)),
+            Err(RiskViolation::BaseFlow {
+                total: 101,
+                limit: 100
+            })
+        );
+        // Unknown markets start from zero flow.
+        assert!(engine.check(3, &update(11, 0)).is_err());
+        assert_eq!(engine.check(2, &update(0, 500)), Ok(()));
+    }
+
+    #[test]
+    fn flow_total_overflow_is_a_breach() {
+        let engine = RiskEngine::new(RiskLimits {
+            max_quote_flow: Some(u64::MAX - 1),
+            ..RiskLimits::default()
+        });
+        // The tracked total saturates at `u64::MAX`, so raising market 2 overflows it.
+        assert_eq!(engine.observe(1, position(0, u64::MAX - 5, 0.0)), None);
+        assert_eq!(engine.observe(2, position(0, 10, 0.0)), None);
+
+        assert_eq!(
+            engine.check(2, &update(0, 20)),
+            Err(RiskViolation::QuoteFlow {
+                total: u64::MAX,
+                limit: u64::MAX - 1
+            })
+        );
+    }
+
+    #[test]
+    fn drawdown_breach_halts_once_and_blocks_risk_increases() {
+        let engine = RiskEngine::new(RiskLimits {
+            max_drawdown_bps: Some(1_000),
+            ..RiskLimits::default()
+        });
+        assert_eq!(engine.observe(1, position(10, 10, 100.0)), None);
+        assert_eq!(engine.observe(1, position(10, 10, 91.0)), None);
+
+        assert_eq!(
+            engine.observe(1, position(10, 10, 89.0)),
+            Some(RiskViolation::Drawdown {
+                drawdown_bps: 1_100,
+                limit: 1_000
+            })
+        );
+        assert_eq!(engine.observe(1, position(10, 10, 80.0)), None);
+        assert_eq!(engine.exposure().drawdown_bps, 2_000);
+
+        assert_eq!(engine.check(1, &update(11, 10)), Err(RiskViolation::Halted));
+        assert_eq!(
+            engine.check(1, &Action::Rebalance),
+            Err(RiskViolation::Halted)
+        );
+        assert_eq!(engine.check(1, &update(0, 0)), Ok(()));
+        assert_eq!(
+            engine.check(1, &Action::Stop { reference_index: 0 }),
+            Ok(())
+        );
+
+        engine.reset();
+        assert_eq!(engine.halted(), None);
+        assert_eq!(engine.exposure().drawdown_bps, 0);
+        assert_eq!(engine.check(1, &update(11, 10)), Ok(()));
+    }
+
+    #[test]
+    fn lent_quote_counts_towards_value_and_the_lending_limit() {
+        let engine = RiskEngine::new(RiskLimits {
+            max_drawdown_bps: Some(1_000),
+            max_lent: Some(500),
+            ..RiskLimits::default()
+        });
+        assert_eq!(engine.observe(1, position(10, 10, 100.0)), None);
+
+        // Moving 40 of value from the position into lending is not a drawdown.
+        engine.observe_lent(1, 400, 40.0);
+        assert_eq!(engine.observe(1, position(10, 10, 60.0)), None);
+        assert_eq!(engine.exposure().lent, 400);
+        assert_eq!(engine.exposure().drawdown_bps, 0);
+
+        assert_eq!(engine.check_lend(100), Ok(()));
+        assert_eq!(
+            engine.check_lend(101),
+            Err(RiskViolation::Lent {
+                total: 501,
+                limit: 500
+            })
+        );
+    }
+}
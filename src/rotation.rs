@@ -0,0 +1,379 @@
+//! Moving a liquidity position from one signing keypair to another.
+//!
+//! A position belongs to the keypair that opened it, so rotating a bot's key means moving
+//! the position with it. [`hand_over_position`] does that in steps, each confirmed before
+//! the next goes out:
+//!
+//! 1. zero the old position's flows, so it stops quoting under the old authority;
+//! 2. withdraw its base and quote balances, read once the zeroing has landed, to the old
+//!    authority's token accounts;
+//! 3. transfer those amounts to the new authority's token accounts;
+//! 4. open the new authority's position with them, at the old position's flows.
+//!
+//! Should a step fail, the error names it, so an operator knows where the funds sit. The
+//! old keypair stays usable throughout; nothing is closed or revoked.
+//!
+//! [`rotate`] then re-arms the bot's [`EmergencyStop`] on the new position, as the one it
+//! signed stops the old.
+
+use std::sync::Arc;
+
+use anchor_client::{
+    Client, Program,
+    solana_sdk::{instruction::Instruction, pubkey::Pubkey},
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    token_2022::spl_token_2022,
+};
+use anyhow::Context;
+use tracing::{error, info};
+
+use crate::{
+    LiquidityPositionBalances, MarketState, ProgramPayer, TwobRpc,
+    alerts::{AlertKind, Alerter},
+    config::CommonConfig,
+    control::{ControlState, KeyRotation, emergency::EmergencyStop},
+    execute_open_position, execute_update_flows, execute_withdraw_liquidity,
+    fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances,
+    get_token_program_id, nearest_reference_index,
+    twob_anchor::{self, client::args},
+    tx::{SendOptions, SignerConfig, TxSender, TxSigner, keypair_from_file},
+};
+
+/// The signer `rotation` asks for: the keypair file it names, or else the bot's configured
+/// signer read afresh, with a local keypair from env var `keypair_var` and the files it
+/// points to.
+pub fn load_signer(rotation: &KeyRotation, keypair_var: &str) -> anyhow::Result<Arc<dyn TxSigner>> {
+    match &rotation.keypair_path {
+        Some(path) => Ok(Arc::new(keypair_from_file(path)?)),
+        None => SignerConfig::from_env()?.connect(keypair_var),
+    }
+}
+
+/// What [`hand_over_position`] moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handover {
+    pub market_id: u64,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+    pub base_flow: u64,
+    pub quote_flow: u64,
+}
+
+/// What a bot goes on with after a key rotation.
+pub struct Rotated {
+    pub client: Arc<Client<ProgramPayer>>,
+    pub sender: TxSender,
+    pub signer: Arc<dyn TxSigner>,
+}
+
+/// Carry out `rotation` for a flow bot on `common`'s market that runs on `program` and
+/// `sender`: connect a client and sender for the new signer the way the bot's own were,
+/// reporting where the old sender did, hand the position over to it and re-arm
+/// `emergency_stop` on the new position.
+///
+/// Returns what the bot goes on with, after updating `control`'s authority. On failure the
+/// bot is paused and alerted on, and goes on with nothing changed, since where the funds
+/// ended up is for an operator to judge. A stop that fails to re-arm is alerted on with
+/// the rotation, and sends nothing until a later refresh signs it.
+pub async fn rotate(
+    common: &CommonConfig,
+    rotation: &KeyRotation,
+    program: &Program<ProgramPayer>,
+    sender: &TxSender,
+    emergency_stop: Option<&EmergencyStop>,
+    alerter: &Alerter,
+    control: &ControlState,
+) -> Option<Rotated> {
+    let market_id = common.market_id;
+    let result = async {
+        let signer = load_signer(rotation, &common.keypair_var)?;
+        let (client, new_sender) = common
+            .connect(signer.clone(), alerter, sender.reporting())
+            .await?;
+        let new_program = client.program(twob_anchor::ID)?;
+        let handover =
+            hand_over_position(program, sender, &new_program, &new_sender, market_id).await?;
+        let rotated = Rotated {
+            client,
+            sender: new_sender,
+            signer,
+        };
+        anyhow::Ok((rotated, new_program, handover))
+    }
+    .await;
+
+    match result {
+        Ok((rotated, new_program, handover)) => {
+            control.set_authority(handover.new_authority);
+            let mut message = format!(
+                "position moved from {} to {}: {} base, {} quote",
+                handover.old_authority,
+                handover.new_authority,
+                handover.base_amount,
+                handover.quote_amount
+            );
+            if let Some(stop) = emergency_stop
+                && let Err(error) = stop.rearm(&new_program, &rotated.sender).await
+            {
+                error!(
+                    event.name = "emergency_stop_rearm_failed",
+                    market.id = market_id,
+                    lp.authority = %handover.new_authority,
+                    ?error,
+                );
+                message.push_str(&format!("; emergency stop not re-armed: {error:#}"));
+            }
+            alerter.notify(AlertKind::KeyRotated, Some(market_id), message);
+            Some(rotated)
+        }
+        Err(rotation_error) => {
+            control.pause();
+            error!(
+                event.name = "key_rotation_failed",
+                market.id = market_id,
+                rotation.old_authority = %sender.payer(),
+                error = ?rotation_error,
+            );
+            alerter.notify(
+                AlertKind::KeyRotationFailed,
+                Some(market_id),
+                format!("{rotation_error:#}; bot paused"),
+            );
+            None
+        }
+    }
+}
+
+/// Move the position `old_sender`'s payer holds on `market_id` to `new_sender`'s payer.
+/// Each program must be the client for its sender's payer.
+pub async fn hand_over_position(
+    old_program: &Program<ProgramPayer>,
+    old_sender: &TxSender,
+    new_program: &Program<ProgramPayer>,
+    new_sender: &TxSender,
+    market_id: u64,
+) -> anyhow::Result<Handover> {
+    let old_authority = old_sender.payer();
+    let new_authority = new_sender.payer();
+    anyhow::ensure!(
+        old_authority != new_authority,
+        "the new keypair is the current one ({old_authority})"
+    );
+    // The new authority pays for its deposit and every update after it.
+    let lamports = new_sender.rpc().get_balance(&new_authority).await?;
+    anyhow::ensure!(
+        lamports > 0,
+        "the new authority {new_authority} has no SOL to pay fees with"
+    );
+
+    // Refuse a position in debt before touching it, and note the flows to reopen with.
+    let position = fetch_liquidity_position(old_program, market_id, &old_authority).await?;
+    withdrawable_balances(old_program, market_id, &old_authority).await?;
+    info!(
+        event.name = "key_rotation_handover_started",
+        market.id = market_id,
+        rotation.old_authority = %old_authority,
+        rotation.new_authority = %new_authority,
+    );
+
+    let urgent = old_sender.with_options(SendOptions::urgent());
+    let reference_index = current_reference_index(old_program, market_id).await?;
+    execute_update_flows(old_program, market_id, 0, 0, reference_index, &urgent)
+        .await
+        .context("zeroing the old position's flows")?;
+
+    // The position traded until the zeroing landed, so its balances are read only now.
+    let (market_state, balances) = withdrawable_balances(old_program, market_id, &old_authority)
+        .await
+        .context("reading the old position's balances; its flows are zero")?;
+    let handover = Handover {
+        market_id,
+        old_authority,
+        new_authority,
+        base_amount: balances.base_balance,
+        quote_amount: balances.quote_balance,
+        base_flow: position.base_flow_u64,
+        quote_flow: position.quote_flow_u64,
+    };
+    execute_withdraw_liquidity(
+        old_program,
+        market_id,
+        handover.base_amount,
+        handover.quote_amount,
+        nearest_reference_index(
+            market_state.current_slot,
+            market_state.market.end_slot_interval,
+        ),
+        &urgent,
+    )
+    .await
+    .context("withdrawing from the old position; its flows are zero")?;
+
+    let mut transfers = Vec::new();
+    for (mint, amount) in [
+        (&market_state.market.base_mint, handover.base_amount),
+        (&market_state.market.quote_mint, handover.quote_amount),
+    ] {
+        if amount == 0 {
+            continue;
+        }
+        let token_program = get_token_program_id(old_program, mint).await?;
+        let decimals = old_program.rpc().get_token_supply(mint).await?.decimals;
+        transfers.extend(transfer_instructions(
+            mint,
+            &token_program,
+            decimals,
+            &old_authority,
+            &new_authority,
+            amount,
+        )?);
+    }
+    if !transfers.is_empty() {
+        old_sender.send(transfers).await.with_context(|| {
+            format!("moving the withdrawn tokens; they are in {old_authority}'s accounts")
+        })?;
+    }
+
+    let reference_index = current_reference_index(new_program, market_id).await?;
+    execute_open_position(
+        new_program,
+        market_id,
+        args::ProvideLiquidity {
+            reference_index,
+            base_deposit_lamports: handover.base_amount,
+            quote_deposit_lamports: handover.quote_amount,
+            base_flow_u64: handover.base_flow,
+            quote_flow_u64: handover.quote_flow,
+        },
+        new_sender,
+    )
+    .await
+    .with_context(|| {
+        format!(
+            "opening the new authority's position; the tokens are in {new_authority}'s accounts"
+        )
+    })?;
+
+    info!(
+        event.name = "key_rotation_handover_completed",
+        market.id = market_id,
+        rotation.old_authority = %old_authority,
+        rotation.new_authority = %new_authority,
+        rotation.base_amount = handover.base_amount,
+        rotation.quote_amount = handover.quote_amount,
+        monotonic_counter.key_rotations_total = 1_u64,
+    );
+    Ok(handover)
+}
+
+/// The market as read now and what `authority`'s position on it holds, refusing a position
+/// in debt, which can't be withdrawn from in full.
+pub async fn withdrawable_balances(
+    rpc: &(impl TwobRpc + ?Sized),
+    market_id: u64,
+    authority: &Pubkey,
+) -> anyhow::Result<(MarketState, LiquidityPositionBalances)> {
+    let market_state = fetch_market_state(rpc, market_id).await?;
+    let position = fetch_liquidity_position(rpc, market_id, authority).await?;
+    let balances = get_liquidity_position_balances(
+        rpc,
+        position,
+        market_state.bookkeeping,
+        market_state.market,
+        market_state.current_slot,
+    )
+    .await?;
+    anyhow::ensure!(
+        balances.base_debt == 0 && balances.quote_debt == 0,
+        "the position is in debt (base {}, quote {}); stop it instead",
+        balances.base_debt,
+        balances.quote_debt
+    );
+    Ok((market_state, balances))
+}
+
+async fn current_reference_index(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+) -> anyhow::Result<u64> {
+    let market_state = fetch_market_state(program, market_id).await?;
+    Ok(nearest_reference_index(
+        market_state.current_slot,
+        market_state.market.end_slot_interval,
+    ))
+}
+
+/// Instructions moving `amount` of `mint`, a `token_program` mint of `decimals`, from
+/// `from`'s associated token account to `to`'s, creating the latter at `from`'s expense if
+/// needed.
+pub fn transfer_instructions(
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    decimals: u8,
+    from: &Pubkey,
+    to: &Pubkey,
+    amount: u64,
+) -> anyhow::Result<Vec<Instruction>> {
+    Ok(vec![
+        create_associated_token_account_idempotent(from, to, mint, token_program),
+        spl_token_2022::instruction::transfer_checked(
+            token_program,
+            &get_associated_token_address_with_program_id(from, mint, token_program),
+            mint,
+            &get_associated_token_address_with_program_id(to, mint, token_program),
+            from,
+            &[],
+            amount,
+            decimals,
+        )?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use spl_token_2022::instruction::TokenInstruction;
+
+    use super::*;
+
+    #[test]
+    fn transfers_into_the_new_authoritys_account_creating_it() {
+        let (mint, from, to) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let token_program = spl_token_2022::ID;
+        let account =
+            |owner| get_associated_token_address_with_program_id(owner, &mint, &token_program);
+
+        let instructions =
+            transfer_instructions(&mint, &token_program, 6, &from, &to, 1_500).unwrap();
+        let [create, transfer] = instructions.as_slice() else {
+            panic!("expected two instructions, got {}", instructions.len());
+        };
+
+        // The old authority pays for the new authority's account.
+        assert_eq!(create.program_id, anchor_spl::associated_token::ID);
+        let created: Vec<_> = create.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(created[..3], [from, account(&to), to]);
+
+        assert_eq!(transfer.program_id, token_program);
+        let moved: Vec<_> = transfer.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(moved, [account(&from), mint, account(&to), from]);
+        assert!(transfer.accounts[3].is_signer);
+        assert_eq!(
+            TokenInstruction::unpack(&transfer.data).unwrap(),
+            TokenInstruction::TransferChecked {
+                amount: 1_500,
+                decimals: 6
+            }
+        );
+    }
+}
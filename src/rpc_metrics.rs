@@ -0,0 +1,233 @@
+//! Latency and error rates per RPC endpoint.
+//!
+//! Every request through a client from [`rpc_client`](crate::rate_limit::rpc_client) is
+//! timed, and failures are sorted by [`failure_kind`] into the endpoint's fault
+//! (timeouts, 429s, 5xx, a node behind the cluster) and the caller's (a transaction that
+//! fails preflight, an account that isn't there), which don't count against it. Fetchers
+//! going through anchor's `Program` wrap their calls in [`timed`] instead.
+//!
+//! Each request emits `histogram.rpc_request_duration_ms` by `rpc.endpoint` and
+//! `rpc.method` at debug level, and each endpoint failure
+//! `monotonic_counter.rpc_request_errors_total` with its `rpc.error_kind` at warn. The
+//! process also keeps a decaying average of both per endpoint, read back through
+//! [`health`], so a caller with more than one endpoint can move off one that has started
+//! to time out ([`healthiest`]).
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use anchor_client::ClientError;
+use solana_rpc_client_api::{
+    client_error::{Error as RpcClientError, ErrorKind},
+    request::RpcError,
+};
+use tracing::{debug, warn};
+
+use crate::rate_limit::endpoint_host;
+
+/// Weight of the newest request in the decaying averages.
+const SMOOTHING: f64 = 0.1;
+
+/// An RPC failure that may or may not be the endpoint's fault.
+pub trait RpcFailure: std::fmt::Debug {
+    /// What went wrong, or `None` when the endpoint did its job, e.g. it reported an
+    /// account missing or a transaction failing preflight.
+    fn failure_kind(&self) -> Option<&'static str>;
+}
+
+impl RpcFailure for RpcClientError {
+    fn failure_kind(&self) -> Option<&'static str> {
+        failure_kind(self)
+    }
+}
+
+impl RpcFailure for ClientError {
+    fn failure_kind(&self) -> Option<&'static str> {
+        match self {
+            ClientError::SolanaClientError(err) => failure_kind(err),
+            _ => None,
+        }
+    }
+}
+
+/// Sort `err` by whose fault it is; see [`RpcFailure::failure_kind`].
+pub fn failure_kind(err: &RpcClientError) -> Option<&'static str> {
+    match err.kind() {
+        ErrorKind::Reqwest(err) => Some(match err.status().map(|status| status.as_u16()) {
+            _ if err.is_timeout() => "timeout",
+            _ if err.is_connect() => "connect",
+            Some(429) => "rate_limited",
+            Some(500..=599) => "server_error",
+            Some(_) => "http_status",
+            None => "transport",
+        }),
+        ErrorKind::Io(_) => Some("transport"),
+        ErrorKind::SerdeJson(_) => Some("decode"),
+        ErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => match code {
+            // Transaction simulation failed: the transaction's problem, not the node's.
+            -32002 => None,
+            -32005 => Some("node_behind"),
+            429 | -32429 => Some("rate_limited"),
+            _ => Some("rpc_error"),
+        },
+        ErrorKind::RpcError(RpcError::ParseError(_)) => Some("decode"),
+        ErrorKind::RpcError(RpcError::ForUser(_)) => None,
+        ErrorKind::RpcError(_) => Some("rpc_error"),
+        ErrorKind::TransactionError(_) | ErrorKind::SigningError(_) => None,
+        _ => Some("other"),
+    }
+}
+
+/// How an endpoint has been doing lately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointHealth {
+    pub endpoint: String,
+    pub requests: u64,
+    pub errors: u64,
+    /// Decaying average over recent requests.
+    pub latency_ms: f64,
+    /// Decaying share of recent requests that failed, from 0 to 1.
+    pub error_rate: f64,
+}
+
+impl EndpointHealth {
+    fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            requests: 0,
+            errors: 0,
+            latency_ms: 0.0,
+            error_rate: 0.0,
+        }
+    }
+
+    fn record(&mut self, elapsed: Duration, failed: bool) {
+        let latency_ms = elapsed.as_secs_f64() * 1_000.0;
+        let failed = if failed { 1.0 } else { 0.0 };
+        if self.requests == 0 {
+            self.latency_ms = latency_ms;
+            self.error_rate = failed;
+        } else {
+            self.latency_ms += SMOOTHING * (latency_ms - self.latency_ms);
+            self.error_rate += SMOOTHING * (failed - self.error_rate);
+        }
+        self.requests += 1;
+        self.errors += failed as u64;
+    }
+}
+
+fn endpoints() -> std::sync::MutexGuard<'static, HashMap<String, EndpointHealth>> {
+    static ENDPOINTS: OnceLock<Mutex<HashMap<String, EndpointHealth>>> = OnceLock::new();
+    ENDPOINTS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Record a request to the endpoint at `url` that took `elapsed`.
+pub fn observe<T, E: RpcFailure>(
+    url: &str,
+    method: &str,
+    elapsed: Duration,
+    result: &Result<T, E>,
+) {
+    let host = endpoint_host(url);
+    let kind = result.as_ref().err().and_then(RpcFailure::failure_kind);
+    endpoints()
+        .entry(host.clone())
+        .or_insert_with(|| EndpointHealth::new(&host))
+        .record(elapsed, kind.is_some());
+
+    let elapsed_ms = elapsed.as_millis() as u64;
+    debug!(
+        event.name = "rpc_request_completed",
+        rpc.endpoint = host.as_str(),
+        rpc.method = method,
+        rpc.ok = kind.is_none(),
+        histogram.rpc_request_duration_ms = elapsed_ms,
+    );
+    if let (Some(kind), Err(error)) = (kind, result) {
+        warn!(
+            event.name = "rpc_request_failed",
+            rpc.endpoint = host.as_str(),
+            rpc.method = method,
+            rpc.error_kind = kind,
+            rpc.duration_ms = elapsed_ms,
+            monotonic_counter.rpc_request_errors_total = 1_u64,
+            ?error,
+        );
+    }
+}
+
+/// Run `request` to the endpoint at `url` and record how it went.
+pub async fn timed<T, E: RpcFailure>(
+    url: &str,
+    method: &str,
+    request: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let start = Instant::now();
+    let result = request.await;
+    observe(url, method, start.elapsed(), &result);
+    result
+}
+
+/// How the endpoint at `url` has been doing, `None` before its first request.
+pub fn health(url: &str) -> Option<EndpointHealth> {
+    endpoints().get(&endpoint_host(url)).cloned()
+}
+
+/// Every endpoint requested so far.
+pub fn snapshot() -> Vec<EndpointHealth> {
+    let mut all: Vec<_> = endpoints().values().cloned().collect();
+    all.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+    all
+}
+
+/// The url among `urls` whose endpoint has been failing least, then answering fastest.
+/// Endpoints not requested yet count as healthy, so they get tried.
+pub fn healthiest(urls: &[String]) -> Option<&str> {
+    let score = |url: &String| {
+        health(url).map_or((0.0, 0.0), |health| (health.error_rate, health.latency_ms))
+    };
+    urls.iter()
+        .min_by(|a, b| {
+            let (a, b) = (score(a), score(b));
+            a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+        })
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_rpc_client_api::request::RpcResponseErrorData;
+
+    use super::*;
+
+    fn response_error(code: i64) -> RpcClientError {
+        ErrorKind::RpcError(RpcError::RpcResponseError {
+            code,
+            message: String::new(),
+            data: RpcResponseErrorData::Empty,
+        })
+        .into()
+    }
+
+    #[test]
+    fn only_the_endpoints_failures_count_against_it() {
+        assert_eq!(failure_kind(&response_error(-32005)), Some("node_behind"));
+        assert_eq!(failure_kind(&response_error(-32429)), Some("rate_limited"));
+        assert_eq!(failure_kind(&response_error(-32002)), None);
+        assert_eq!(ClientError::AccountNotFound.failure_kind(), None);
+
+        let mut health = EndpointHealth::new("rpc.example.com");
+        health.record(Duration::from_millis(100), false);
+        health.record(Duration::from_millis(200), true);
+        assert_eq!((health.requests, health.errors), (2, 1));
+        assert!((health.latency_ms - 110.0).abs() < 1e-9);
+        assert!((health.error_rate - 0.1).abs() < 1e-9);
+    }
+}
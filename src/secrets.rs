@@ -0,0 +1,382 @@
+//! Secrets fetched from a secrets manager instead of sitting in the environment or config
+//! file. Any variable, e.g. `ORACLE_FLOW_KEYPAIR` or `JUPITER_API_KEY`, may hold a
+//! reference to one, and [`resolve_env`] replaces each reference with what it points at
+//! before the configuration is read. The scheme picks the manager:
+//!
+//! - `aws-sm://<secret id or ARN>`: AWS Secrets Manager, in `AWS_REGION`, with the
+//!   `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY` (and `AWS_SESSION_TOKEN`) credentials;
+//! - `gcp-sm://projects/<project>/secrets/<secret>[/versions/<version>]`: GCP Secret
+//!   Manager, the latest version by default, authenticated with `GCP_ACCESS_TOKEN` or else
+//!   the instance's service account;
+//! - `vault://<path>#<field>`: HashiCorp Vault at `VAULT_ADDR` with `VAULT_TOKEN` (and
+//!   `VAULT_NAMESPACE`), reading `<field>` from a KV v1 or v2 secret.
+//!
+//! A `#<field>` on an AWS or GCP reference picks one field of a JSON secret.
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::config::{env_var, env_vars, set_env_var};
+
+const GCP_METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Where a secret lives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    Aws {
+        secret_id: String,
+        field: Option<String>,
+    },
+    Gcp {
+        name: String,
+        field: Option<String>,
+    },
+    Vault {
+        path: String,
+        field: String,
+    },
+}
+
+impl SecretRef {
+    /// The reference `value` holds, or `None` when it's a plain value.
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let value = value.trim();
+        let Some((scheme, rest)) = value.split_once("://") else {
+            return Ok(None);
+        };
+        let (location, field) = match rest.rsplit_once('#') {
+            Some((location, field)) if !field.is_empty() => (location, Some(field.to_string())),
+            _ => (rest, None),
+        };
+        if location.is_empty() {
+            bail!("secret reference `{value}` names no secret");
+        }
+        Ok(Some(match scheme {
+            "aws-sm" => Self::Aws {
+                secret_id: location.to_string(),
+                field,
+            },
+            "gcp-sm" => {
+                let name = if location.contains("/versions/") {
+                    location.to_string()
+                } else {
+                    format!("{location}/versions/latest")
+                };
+                Self::Gcp { name, field }
+            }
+            "vault" => Self::Vault {
+                path: location.trim_start_matches('/').to_string(),
+                field: field.with_context(|| format!("`{value}` needs a #<field>"))?,
+            },
+            _ => return Ok(None),
+        }))
+    }
+
+    pub fn manager(&self) -> &'static str {
+        match self {
+            Self::Aws { .. } => "aws",
+            Self::Gcp { .. } => "gcp",
+            Self::Vault { .. } => "vault",
+        }
+    }
+
+    pub async fn fetch(&self, http: &reqwest::Client) -> Result<String> {
+        match self {
+            Self::Aws { secret_id, field } => {
+                let secret = fetch_aws(http, secret_id).await?;
+                select_field(secret, field.as_deref())
+            }
+            Self::Gcp { name, field } => {
+                let secret = fetch_gcp(http, name).await?;
+                select_field(secret, field.as_deref())
+            }
+            Self::Vault { path, field } => fetch_vault(http, path, field).await,
+        }
+    }
+}
+
+/// Replace every variable holding a [`SecretRef`] with the secret, for the bot alone (see
+/// [`config::vars`](crate::config::vars)). Secrets are fetched once, at start-up; rotating
+/// one takes a restart.
+pub async fn resolve_env() -> Result<()> {
+    let references = env_vars()
+        .into_iter()
+        .map(|(name, value)| Ok((SecretRef::parse(&value)?, name)))
+        .filter_map(|result| match result {
+            Ok((Some(reference), name)) => Some(Ok((name, reference))),
+            Ok((None, _)) => None,
+            Err(error) => Some(Err(error)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if references.is_empty() {
+        return Ok(());
+    }
+    let http = reqwest::Client::new();
+    for (name, reference) in references {
+        let secret = reference
+            .fetch(&http)
+            .await
+            .with_context(|| format!("failed to fetch {name} from {}", reference.manager()))?;
+        info!(
+            event.name = "secret_resolved",
+            secret.variable = %name,
+            secret.manager = reference.manager(),
+        );
+        set_env_var(&name, secret);
+    }
+    Ok(())
+}
+
+fn select_field(secret: String, field: Option<&str>) -> Result<String> {
+    let Some(field) = field else {
+        return Ok(secret);
+    };
+    let value: Value = serde_json::from_str(&secret).context("secret is not a JSON object")?;
+    json_field(&value, field)
+}
+
+fn json_field(value: &Value, field: &str) -> Result<String> {
+    match value.get(field) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(Value::Null) | None => Err(anyhow!("secret has no field `{field}`")),
+        Some(value) => Ok(value.to_string()),
+    }
+}
+
+fn required_var(name: &str) -> Result<String> {
+    env_var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .with_context(|| format!("{name} not set"))
+}
+
+async fn fetch_aws(http: &reqwest::Client, secret_id: &str) -> Result<String> {
+    let region = required_var("AWS_REGION").or_else(|_| required_var("AWS_DEFAULT_REGION"))?;
+    let credentials = AwsCredentials {
+        access_key_id: required_var("AWS_ACCESS_KEY_ID")?,
+        secret_access_key: required_var("AWS_SECRET_ACCESS_KEY")?,
+        session_token: env_var("AWS_SESSION_TOKEN").ok(),
+    };
+    let host = format!("secretsmanager.{region}.amazonaws.com");
+    let body = json!({ "SecretId": secret_id }).to_string();
+    let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let target = "secretsmanager.GetSecretValue";
+
+    let mut headers = vec![
+        ("content-type", "application/x-amz-json-1.1".to_string()),
+        ("host", host.clone()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    headers.push(("x-amz-target", target.to_string()));
+    let authorization = sigv4_authorization(
+        &credentials,
+        &region,
+        "secretsmanager",
+        &amz_date,
+        &headers,
+        &body,
+    );
+
+    let mut request = http.post(format!("https://{host}/")).body(body);
+    for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+        request = request.header(name, value);
+    }
+    let response: Value = request
+        .header("authorization", authorization)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(secret) = response.get("SecretString").and_then(Value::as_str) {
+        return Ok(secret.to_string());
+    }
+    let binary = response
+        .get("SecretBinary")
+        .and_then(Value::as_str)
+        .context("secret has neither SecretString nor SecretBinary")?;
+    Ok(String::from_utf8(STANDARD.decode(binary)?)?)
+}
+
+struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn sigv4_signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{secret_access_key}").as_bytes(), date);
+    let key = hmac_sha256(&key, region);
+    let key = hmac_sha256(&key, service);
+    hmac_sha256(&key, "aws4_request")
+}
+
+/// The `Authorization` header for a `POST /` with `headers`, which must be lower-case and
+/// sorted by name.
+fn sigv4_authorization(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    amz_date: &str,
+    headers: &[(&str, String)],
+    body: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+        hex::encode(Sha256::digest(body.as_bytes()))
+    );
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let signature = hex::encode(hmac_sha256(
+        &sigv4_signing_key(&credentials.secret_access_key, date, region, service),
+        &string_to_sign,
+    ));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, \
+         Signature={signature}",
+        credentials.access_key_id
+    )
+}
+
+async fn fetch_gcp(http: &reqwest::Client, name: &str) -> Result<String> {
+    let token = match required_var("GCP_ACCESS_TOKEN") {
+        Ok(token) => token,
+        Err(_) => {
+            let response: Value = http
+                .get(GCP_METADATA_TOKEN_URL)
+                .header("Metadata-Flavor", "Google")
+                .send()
+                .await
+                .context("no GCP_ACCESS_TOKEN and no metadata server to get one from")?
+                .error_for_status()?
+                .json()
+                .await?;
+            response
+                .get("access_token")
+                .and_then(Value::as_str)
+                .context("metadata server returned no access token")?
+                .to_string()
+        }
+    };
+    let response: Value = http
+        .get(format!(
+            "https://secretmanager.googleapis.com/v1/{name}:access"
+        ))
+        .bearer_auth(token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let data = response
+        .pointer("/payload/data")
+        .and_then(Value::as_str)
+        .context("secret has no payload")?;
+    Ok(String::from_utf8(STANDARD.decode(data)?)?)
+}
+
+async fn fetch_vault(http: &reqwest::Client, path: &str, field: &str) -> Result<String> {
+    let addr = required_var("VAULT_ADDR")?;
+    let mut request = http
+        .get(format!("{}/v1/{path}", addr.trim_end_matches('/')))
+        .header("X-Vault-Token", required_var("VAULT_TOKEN")?);
+    if let Ok(namespace) = required_var("VAULT_NAMESPACE") {
+        request = request.header("X-Vault-Namespace", namespace);
+    }
+    let response: Value = request.send().await?.error_for_status()?.json().await?;
+    vault_field(&response, field)
+}
+
+/// `field` of a Vault read response: under `data.data` for KV v2, `data` for KV v1.
+fn vault_field(response: &Value, field: &str) -> Result<String> {
+    let data = response.get("data").context("Vault returned no data")?;
+    let data = match data.get("data") {
+        Some(inner) if inner.is_object() => inner,
+        _ => data,
+    };
+    json_field(data, field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_secret_references_by_scheme() {
+        assert_eq!(SecretRef::parse("[1,2,3]").unwrap(), None);
+        assert_eq!(SecretRef::parse("https://api.example").unwrap(), None);
+        assert_eq!(
+            SecretRef::parse("aws-sm://prod/oracle-flow#keypair").unwrap(),
+            Some(SecretRef::Aws {
+                secret_id: "prod/oracle-flow".to_string(),
+                field: Some("keypair".to_string()),
+            })
+        );
+        assert_eq!(
+            SecretRef::parse("gcp-sm://projects/mm/secrets/jupiter-key").unwrap(),
+            Some(SecretRef::Gcp {
+                name: "projects/mm/secrets/jupiter-key/versions/latest".to_string(),
+                field: None,
+            })
+        );
+        assert_eq!(
+            SecretRef::parse("vault://secret/data/hedger#api_secret").unwrap(),
+            Some(SecretRef::Vault {
+                path: "secret/data/hedger".to_string(),
+                field: "api_secret".to_string(),
+            })
+        );
+        assert!(SecretRef::parse("vault://secret/data/hedger").is_err());
+    }
+
+    #[test]
+    fn reads_fields_from_kv_v1_and_v2_responses() {
+        let v2 = json!({"data": {"data": {"api_key": "k2"}, "metadata": {}}});
+        let v1 = json!({"data": {"api_key": "k1"}});
+        assert_eq!(vault_field(&v2, "api_key").unwrap(), "k2");
+        assert_eq!(vault_field(&v1, "api_key").unwrap(), "k1");
+        assert!(vault_field(&v1, "missing").is_err());
+    }
+
+    #[test]
+    fn derives_the_documented_sigv4_signing_key() {
+        let key = sigv4_signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+}
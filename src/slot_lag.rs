@@ -0,0 +1,288 @@
+//! How far the primary RPC endpoint's view of the chain trails the cluster.
+//!
+//! Every balance the bots compute is settled up to the slot the endpoint reports, so an
+//! endpoint that falls behind skews the math without failing a single request. The
+//! [`monitor`] compares the endpoint's processed slot with a reference endpoint's, and
+//! with where its own slot should have got to by now had it kept advancing, which catches
+//! an endpoint that has stalled outright even with no reference configured. Lag above
+//! [`max_lag_slots`](SlotLagConfig::max_lag_slots) raises an alert.
+//!
+//! With [`failover`](SlotLagConfig::failover) on, the monitor points the bot's [`Failover`]
+//! at the reference endpoint for as long as the lag lasts, and the bot reads through the
+//! program [`Failover::reads`] gives it. Transactions still go out through the primary.
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anchor_client::{Client, Cluster, Program, solana_sdk::commitment_config::CommitmentConfig};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    config::env_var,
+    program_payer,
+    rate_limit::{self, endpoint_host},
+    telemetry::parse_bool,
+};
+
+/// Nominal slot time, for turning time without a new slot into slots behind.
+pub const SLOT_DURATION: Duration = Duration::from_millis(400);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotLagConfig {
+    /// Endpoint the primary's slot is compared against; without one only a stalled
+    /// primary is caught.
+    pub reference_url: Option<String>,
+    pub max_lag_slots: u64,
+    pub check_interval: Duration,
+    /// Read from the reference endpoint while the primary lags.
+    pub failover: bool,
+}
+
+impl SlotLagConfig {
+    /// `None` unless `SLOT_LAG_MAX_SLOTS` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let var = |name: &str| {
+            env_var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let Some(max_lag_slots) = var("SLOT_LAG_MAX_SLOTS") else {
+            return Ok(None);
+        };
+        let max_lag_slots = max_lag_slots
+            .parse::<u64>()
+            .map_err(|e| anyhow::anyhow!("Invalid SLOT_LAG_MAX_SLOTS: {}", e))?;
+        let check_interval_secs = match var("SLOT_LAG_CHECK_INTERVAL_SECS") {
+            Some(value) => value
+                .parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("Invalid SLOT_LAG_CHECK_INTERVAL_SECS: {}", e))?,
+            None => 10,
+        };
+        let failover = match var("SLOT_LAG_FAILOVER") {
+            Some(value) => parse_bool(&value)
+                .map_err(|e| anyhow::anyhow!("Invalid SLOT_LAG_FAILOVER: {}", e))?,
+            None => false,
+        };
+        let reference_url = var("SLOT_LAG_REFERENCE_RPC_URL");
+        anyhow::ensure!(
+            !failover || reference_url.is_some(),
+            "SLOT_LAG_FAILOVER needs SLOT_LAG_REFERENCE_RPC_URL to fail over to"
+        );
+        Ok(Some(Self {
+            reference_url,
+            max_lag_slots,
+            check_interval: Duration::from_secs(check_interval_secs),
+            failover,
+        }))
+    }
+}
+
+/// Slots the primary endpoint is behind, by each measure.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SlotLag {
+    /// Behind the reference endpoint, when there is one and it answered.
+    pub behind_reference: Option<u64>,
+    /// Slots that should have gone by since the primary's slot last moved.
+    pub stalled: u64,
+}
+
+impl SlotLag {
+    pub fn slots(&self) -> u64 {
+        self.behind_reference.unwrap_or(0).max(self.stalled)
+    }
+}
+
+/// When the primary's slot last moved.
+#[derive(Debug, Default)]
+struct StallTracker {
+    last: Option<(u64, Instant)>,
+}
+
+impl StallTracker {
+    /// Record `slot` read at `now` and return the slots it has been stuck for.
+    fn observe(&mut self, slot: u64, now: Instant) -> u64 {
+        match self.last {
+            Some((last_slot, advanced_at)) if slot <= last_slot => {
+                let stuck = now.saturating_duration_since(advanced_at);
+                (stuck.as_millis() / SLOT_DURATION.as_millis()) as u64
+            }
+            _ => {
+                self.last = Some((slot, now));
+                0
+            }
+        }
+    }
+}
+
+/// Where one bot's reads go while its primary endpoint lags: shared between the bot and
+/// its [`monitor`], which sets it. Each bot has its own, so one bot failing over leaves
+/// the others on their endpoints. Cheap to clone.
+#[derive(Debug, Clone, Default)]
+pub struct Failover(Arc<Mutex<Option<String>>>);
+
+impl Failover {
+    fn url_mut(&self) -> MutexGuard<'_, Option<String>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The endpoint reads should go to instead of the primary, while failed over.
+    pub fn url(&self) -> Option<String> {
+        self.url_mut().clone()
+    }
+
+    /// `program` to read through: as it is, or on the reference endpoint while failed
+    /// over. Only for reads; subscriptions and sends stay on the primary.
+    pub fn reads(&self, program: Program<ProgramPayer>) -> anyhow::Result<Program<ProgramPayer>> {
+        let Some(url) = self.url() else {
+            return Ok(program);
+        };
+        let client = Client::new_with_options(
+            Cluster::Custom(url.clone(), url),
+            program_payer(program.payer()),
+            program.rpc().commitment(),
+        );
+        Ok(client.program(program.id())?)
+    }
+}
+
+/// Check the endpoint at `primary_url` every interval until the task is dropped, failing
+/// `failover` over to the reference endpoint while it lags, if configured to.
+pub async fn monitor(
+    config: SlotLagConfig,
+    primary_url: String,
+    failover: Failover,
+    alerter: Alerter,
+    market_id: u64,
+) {
+    let primary = rate_limit::rpc_client(&primary_url, CommitmentConfig::processed());
+    let reference = config
+        .reference_url
+        .as_deref()
+        .map(|url| rate_limit::rpc_client(url, CommitmentConfig::processed()));
+    let endpoint = endpoint_host(&primary_url);
+    let mut stall = StallTracker::default();
+    info!(
+        event.name = "slot_lag_monitor_started",
+        rpc.endpoint = endpoint.as_str(),
+        slot_lag.reference = ?config.reference_url.as_deref().map(endpoint_host),
+        slot_lag.max_slots = config.max_lag_slots,
+        slot_lag.failover = config.failover,
+    );
+
+    loop {
+        sleep(config.check_interval).await;
+        let primary_slot = match primary.get_slot().await {
+            Ok(slot) => slot,
+            // Reachability is the RPC probe's business.
+            Err(error) => {
+                warn!(
+                    event.name = "slot_lag_check_failed",
+                    rpc.endpoint = endpoint.as_str(),
+                    ?error
+                );
+                continue;
+            }
+        };
+        let reference_slot = match &reference {
+            Some(reference) => reference
+                .get_slot()
+                .await
+                .inspect_err(|error| {
+                    warn!(
+                        event.name = "slot_lag_reference_failed",
+                        rpc.endpoint = endpoint_host(&reference.url()).as_str(),
+                        ?error,
+                    )
+                })
+                .ok(),
+            None => None,
+        };
+        let lag = SlotLag {
+            behind_reference: reference_slot.map(|slot| slot.saturating_sub(primary_slot)),
+            stalled: stall.observe(primary_slot, Instant::now()),
+        };
+        info!(
+            event.name = "rpc_slot_lag",
+            rpc.endpoint = endpoint.as_str(),
+            slot.primary = %primary_slot,
+            slot.reference = ?reference_slot,
+            slot_lag.behind_reference = ?lag.behind_reference,
+            slot_lag.stalled = %lag.stalled,
+            gauge.rpc_slot_lag_slots = lag.slots() as f64,
+        );
+
+        let lagging = lag.slots() > config.max_lag_slots;
+        if lagging {
+            alerter.notify(
+                AlertKind::RpcLagging,
+                Some(market_id),
+                format!(
+                    "{} is {} slots behind (limit {})",
+                    endpoint,
+                    lag.slots(),
+                    config.max_lag_slots
+                ),
+            );
+        }
+        if !config.failover {
+            continue;
+        }
+        let mut failover_url = failover.url_mut();
+        match (lagging, failover_url.is_some()) {
+            (true, false) => {
+                *failover_url = config.reference_url.clone();
+                warn!(
+                    event.name = "rpc_failover_started",
+                    rpc.endpoint = endpoint.as_str(),
+                    slot_lag.slots = %lag.slots(),
+                    monotonic_counter.rpc_failovers_total = 1_u64,
+                );
+            }
+            (false, true) => {
+                *failover_url = None;
+                info!(
+                    event.name = "rpc_failover_ended",
+                    rpc.endpoint = endpoint.as_str()
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lag_counts_slots_behind_the_reference_or_stuck() {
+        let mut stall = StallTracker::default();
+        let start = Instant::now();
+        assert_eq!(stall.observe(100, start), 0);
+        assert_eq!(stall.observe(125, start + Duration::from_secs(10)), 0);
+        // Stuck at 125 since the 10s read: 25 slots' worth 10s later, 50 after 20s.
+        assert_eq!(stall.observe(125, start + Duration::from_secs(20)), 25);
+        assert_eq!(stall.observe(125, start + Duration::from_secs(30)), 50);
+
+        let lag = SlotLag {
+            behind_reference: Some(30),
+            stalled: 50,
+        };
+        assert_eq!(lag.slots(), 50);
+        assert_eq!(
+            SlotLag {
+                behind_reference: Some(80),
+                stalled: 0
+            }
+            .slots(),
+            80
+        );
+        assert_eq!(SlotLag::default().slots(), 0);
+    }
+}
@@ -1,13 +1,12 @@
-use std::sync::Arc;
-
-use anchor_client::{Program, solana_sdk::signature::Keypair};
-use anchor_lang::prelude::Pubkey;
+use anchor_client::ClientError;
+use anchor_lang::{AccountDeserialize, prelude::Pubkey};
 
 use crate::{
-    AccountResolver,
+    AccountResolver, TwobError,
+    state::TwobRpc,
     twob_anchor::{
         self,
-        accounts::{Bookkeeping, LiquidityPosition, Market},
+        accounts::{Bookkeeping, LiquidityPosition, Market, TradePosition},
     },
 };
 
@@ -17,19 +16,61 @@ pub struct MarketState {
     pub current_slot: u64,
 }
 
+/// A snapshot of the market as other tools read it: addresses in base58, and of the
+/// bookkeeping only what says how fresh it is.
+impl serde::Serialize for MarketState {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let market = &self.market;
+        let mut state = serializer.serialize_struct("MarketState", 13)?;
+        state.serialize_field("market_id", &market.id)?;
+        state.serialize_field("base_mint", &market.base_mint.to_string())?;
+        state.serialize_field("quote_mint", &market.quote_mint.to_string())?;
+        state.serialize_field("base_flow", &market.base_flow)?;
+        state.serialize_field("quote_flow", &market.quote_flow)?;
+        state.serialize_field("end_slot_interval", &market.end_slot_interval)?;
+        state.serialize_field("open_positions", &market.open_positions)?;
+        state.serialize_field("fee_bps", &market.fee_bps)?;
+        state.serialize_field("is_paused", &(market.is_paused != 0))?;
+        state.serialize_field(
+            "bookkeeping_last_update_slot",
+            &self.bookkeeping.last_update_slot,
+        )?;
+        state.serialize_field(
+            "bookkeeping_slots_without_trade",
+            &self.bookkeeping.slots_without_trade,
+        )?;
+        state.serialize_field("start_slot", &market.start_slot)?;
+        state.serialize_field("current_slot", &self.current_slot)?;
+        state.end()
+    }
+}
+
+/// The account at `address`, deserialized as `T`.
+pub async fn fetch_account<T: AccountDeserialize>(
+    rpc: &(impl TwobRpc + ?Sized),
+    address: Pubkey,
+) -> Result<T, TwobError> {
+    let data = rpc
+        .get_account_data(address)
+        .await?
+        .ok_or(TwobError::AccountNotFound { pubkey: address })?;
+    T::try_deserialize(&mut data.as_slice())
+        .map_err(|err| TwobError::account(address, ClientError::from(err)))
+}
+
 pub async fn fetch_market_state(
-    program: &Program<Arc<Keypair>>,
+    rpc: &(impl TwobRpc + ?Sized),
     market_id: u64,
-) -> anyhow::Result<MarketState> {
+) -> Result<MarketState, TwobError> {
     let resolver = AccountResolver::new(twob_anchor::ID);
     let market_pda = resolver.market_pda(market_id);
     let bookkeeping_pda = resolver.bookkeeping_pda(&market_pda.address());
 
-    let market = program.account::<Market>(market_pda.address()).await?;
-    let bookkeeping = program
-        .account::<Bookkeeping>(bookkeeping_pda.address())
-        .await?;
-    let current_slot = program.rpc().get_slot().await?;
+    let market = fetch_account::<Market>(rpc, market_pda.address()).await?;
+    let bookkeeping = fetch_account::<Bookkeeping>(rpc, bookkeeping_pda.address()).await?;
+    let current_slot = rpc.get_slot().await?;
 
     Ok(MarketState {
         market,
@@ -39,15 +80,27 @@ pub async fn fetch_market_state(
 }
 
 pub async fn fetch_liquidity_position(
-    program: &Program<Arc<Keypair>>,
+    rpc: &(impl TwobRpc + ?Sized),
     market_id: u64,
     authority: &Pubkey,
-) -> anyhow::Result<LiquidityPosition> {
+) -> Result<LiquidityPosition, TwobError> {
     let resolver = AccountResolver::new(twob_anchor::ID);
     let market_pda = resolver.market_pda(market_id);
     let liquidity_position_pda = resolver.liquidity_position_pda(&market_pda.address(), authority);
 
-    Ok(program
-        .account::<LiquidityPosition>(liquidity_position_pda.address())
-        .await?)
+    fetch_account(rpc, liquidity_position_pda.address()).await
+}
+
+pub async fn fetch_trade_position(
+    rpc: &(impl TwobRpc + ?Sized),
+    market_id: u64,
+    authority: &Pubkey,
+    position_id: u64,
+) -> Result<TradePosition, TwobError> {
+    let resolver = AccountResolver::new(twob_anchor::ID);
+    let market_pda = resolver.market_pda(market_id);
+    let trade_position_pda =
+        resolver.trade_position_pda(&market_pda.address(), authority, position_id);
+
+    fetch_account(rpc, trade_position_pda.address()).await
 }
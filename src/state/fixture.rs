@@ -0,0 +1,173 @@
+//! Recorded accounts of one liquidity position, with the balances
+//! [`get_liquidity_position_balances`] gave for them, for regression tests and benchmarks
+//! of the balance math. The balances are this crate's own, not an independent check.
+//!
+//! A fixture is JSON: the market, bookkeeping, position and exits accounts in the window
+//! the balances walk, each as base64 of the account data the program stores (see
+//! [`accounts::codec`](crate::accounts::codec)), the slot they were read at and the
+//! balances. `cargo run --bin audit` writes one for the audited position with
+//! `AUDIT_FIXTURE` set.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::Context;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AccountResolver, LiquidityPositionBalances,
+    accounts::{Bookkeeping, Exits, LiquidityPosition, Market, TwobAccount, deserialize_account},
+    get_liquidity_position_balances,
+    state::{MockRpc, TwobRpc, fetch_liquidity_position, fetch_market_state},
+    twob_anchor,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionFixture {
+    pub market_id: u64,
+    pub authority: String,
+    pub current_slot: u64,
+    pub market: String,
+    pub bookkeeping: String,
+    pub position: String,
+    /// By index; windows without an exits account are left out.
+    pub exits: BTreeMap<u64, String>,
+    pub expected: LiquidityPositionBalances,
+}
+
+/// The accounts of a [`PositionFixture`], decoded.
+#[derive(Debug, Clone)]
+pub struct FixtureAccounts {
+    pub market: Market,
+    pub bookkeeping: Bookkeeping,
+    pub position: LiquidityPosition,
+    pub exits: BTreeMap<u64, Exits>,
+}
+
+impl PositionFixture {
+    /// Read `authority`'s position on `market_id` and the balances it has now.
+    pub async fn capture(
+        rpc: &(impl TwobRpc + ?Sized),
+        market_id: u64,
+        authority: &Pubkey,
+    ) -> anyhow::Result<Self> {
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(market_id).address();
+        let state = fetch_market_state(rpc, market_id).await?;
+        let position = fetch_liquidity_position(rpc, market_id, authority).await?;
+
+        let mut exits = BTreeMap::new();
+        for index in
+            crate::core::exits_window(&state.bookkeeping, &state.market, state.current_slot)
+        {
+            let address = resolver.exits_pda(&market_pda, index).address();
+            if let Some(data) = rpc.get_account_data(address).await? {
+                exits.insert(index, STANDARD.encode(data));
+            }
+        }
+        // The balances come from the accounts above rather than the node again, so they
+        // match even if the position has moved on since.
+        let mut fixture = Self {
+            market_id,
+            authority: authority.to_string(),
+            current_slot: state.current_slot,
+            market: encode(&state.market),
+            bookkeeping: encode(&state.bookkeeping),
+            position: encode(&position),
+            exits,
+            expected: LiquidityPositionBalances {
+                base_balance: 0,
+                quote_balance: 0,
+                base_debt: 0,
+                quote_debt: 0,
+            },
+        };
+        fixture.expected = fixture.replay().await?;
+        Ok(fixture)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read fixture {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse fixture {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write fixture {}", path.display()))
+    }
+
+    pub fn accounts(&self) -> anyhow::Result<FixtureAccounts> {
+        Ok(FixtureAccounts {
+            market: decode(&self.market, "market")?,
+            bookkeeping: decode(&self.bookkeeping, "bookkeeping")?,
+            position: decode(&self.position, "position")?,
+            exits: self
+                .exits
+                .iter()
+                .map(|(index, data)| Ok((*index, decode(data, "exits")?)))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    /// The fixture's accounts at their addresses, at its slot.
+    pub fn mock_rpc(&self) -> anyhow::Result<MockRpc> {
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(self.market_id).address();
+        let authority = self.authority.parse::<Pubkey>()?;
+        let rpc = MockRpc::new(self.current_slot);
+        let addresses = [
+            (market_pda, &self.market),
+            (
+                resolver.bookkeeping_pda(&market_pda).address(),
+                &self.bookkeeping,
+            ),
+            (
+                resolver
+                    .liquidity_position_pda(&market_pda, &authority)
+                    .address(),
+                &self.position,
+            ),
+        ];
+        for (address, data) in addresses {
+            rpc.set_account_data(address, STANDARD.decode(data)?);
+        }
+        for (index, data) in &self.exits {
+            rpc.set_account_data(
+                resolver.exits_pda(&market_pda, *index).address(),
+                STANDARD.decode(data)?,
+            );
+        }
+        Ok(rpc)
+    }
+
+    /// [`get_liquidity_position_balances`] run on the fixture's accounts.
+    pub async fn replay(&self) -> anyhow::Result<LiquidityPositionBalances> {
+        let rpc = self.mock_rpc()?;
+        let state = fetch_market_state(&rpc, self.market_id).await?;
+        let authority = self.authority.parse::<Pubkey>()?;
+        let position = fetch_liquidity_position(&rpc, self.market_id, &authority).await?;
+        Ok(get_liquidity_position_balances(
+            &rpc,
+            position,
+            state.bookkeeping,
+            state.market,
+            state.current_slot,
+        )
+        .await?)
+    }
+}
+
+fn encode<T: TwobAccount>(account: &T) -> String {
+    STANDARD.encode(crate::accounts::serialize_account(account))
+}
+
+fn decode<T: TwobAccount>(data: &str, name: &str) -> anyhow::Result<T> {
+    let bytes = STANDARD
+        .decode(data)
+        .with_context(|| format!("Invalid base64 in fixture {name}"))?;
+    deserialize_account(&bytes).with_context(|| format!("Invalid fixture {name} account"))
+}
@@ -1,3 +1,9 @@
 pub mod fetchers;
+pub mod fixture;
+pub mod rpc;
+pub mod runway;
 
+pub use crate::core::{runway::*, trade_fill::*};
 pub use fetchers::*;
+pub use rpc::*;
+pub use runway::*;
@@ -0,0 +1,270 @@
+//! The reads the fetchers and the balance math make, behind [`TwobRpc`]: an account's data
+//! and the current slot. A [`Program`] makes them over RPC; a [`MockRpc`] answers them from
+//! memory, so code built on them can be tested without a cluster.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use anchor_client::Program;
+use anchor_lang::prelude::Pubkey;
+use futures::future::BoxFuture;
+
+use crate::{
+    ProgramPayer, TwobError,
+    accounts::{TwobAccount, serialize_account},
+    rate_limit, rpc_metrics,
+};
+
+pub trait TwobRpc: Send + Sync {
+    /// The data of the account at `address`, `None` if there is none.
+    fn get_account_data(
+        &self,
+        address: Pubkey,
+    ) -> BoxFuture<'_, Result<Option<Vec<u8>>, TwobError>>;
+
+    fn get_slot(&self) -> BoxFuture<'_, Result<u64, TwobError>>;
+}
+
+/// Reads from the program's endpoint, within its rate limit. A bot reads through the
+/// program its [`Failover`](crate::slot_lag::Failover) gives it, so its reads move to the
+/// reference endpoint while the primary lags.
+impl TwobRpc for Program<ProgramPayer> {
+    fn get_account_data(
+        &self,
+        address: Pubkey,
+    ) -> BoxFuture<'_, Result<Option<Vec<u8>>, TwobError>> {
+        Box::pin(async move {
+            let rpc = self.rpc();
+            rate_limit::throttle(&rpc, 1).await;
+            let account = rpc_metrics::timed(
+                &rpc.url(),
+                "getAccountInfo",
+                rpc.get_account_with_commitment(&address, rpc.commitment()),
+            )
+            .await?
+            .value;
+            Ok(account.map(|account| account.data))
+        })
+    }
+
+    fn get_slot(&self) -> BoxFuture<'_, Result<u64, TwobError>> {
+        Box::pin(async move {
+            let rpc = self.rpc();
+            rate_limit::throttle(&rpc, 1).await;
+            Ok(rpc_metrics::timed(&rpc.url(), "getSlot", rpc.get_slot()).await?)
+        })
+    }
+}
+
+/// Accounts and a slot held in memory, for tests.
+#[derive(Debug, Default)]
+pub struct MockRpc {
+    accounts: Mutex<HashMap<Pubkey, Vec<u8>>>,
+    slot: AtomicU64,
+}
+
+impl MockRpc {
+    pub fn new(slot: u64) -> Self {
+        Self {
+            slot: AtomicU64::new(slot),
+            ..Self::default()
+        }
+    }
+
+    /// Store `account` at `address` as the program would.
+    pub fn set_account<T: TwobAccount>(&self, address: Pubkey, account: &T) {
+        self.set_account_data(address, serialize_account(account));
+    }
+
+    pub fn set_account_data(&self, address: Pubkey, data: Vec<u8>) {
+        self.accounts.lock().unwrap().insert(address, data);
+    }
+
+    pub fn remove_account(&self, address: &Pubkey) {
+        self.accounts.lock().unwrap().remove(address);
+    }
+
+    pub fn set_slot(&self, slot: u64) {
+        self.slot.store(slot, Ordering::Relaxed);
+    }
+}
+
+impl TwobRpc for MockRpc {
+    fn get_account_data(
+        &self,
+        address: Pubkey,
+    ) -> BoxFuture<'_, Result<Option<Vec<u8>>, TwobError>> {
+        let data = self.accounts.lock().unwrap().get(&address).cloned();
+        Box::pin(async move { Ok(data) })
+    }
+
+    fn get_slot(&self) -> BoxFuture<'_, Result<u64, TwobError>> {
+        let slot = self.slot.load(Ordering::Relaxed);
+        Box::pin(async move { Ok(slot) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        AccountResolver, BOOKKEEPING_PRECISION_FACTOR as P, LiquidityPositionBalances,
+        accounts::{Bookkeeping, Exits, LiquidityPosition, Market, test_market},
+        core::position_balances,
+        fetch_liquidity_position, fetch_market_state, get_liquidity_position_balances, twob_anchor,
+    };
+
+    fn market() -> Market {
+        Market {
+            base_flow: 1_000,
+            quote_flow: 2_000,
+            open_positions: 1,
+            ..test_market()
+        }
+    }
+
+    fn bookkeeping() -> Bookkeeping {
+        Bookkeeping {
+            base_per_quote: 0,
+            previous_base_per_quote: 0,
+            quote_per_base: 0,
+            previous_quote_per_base: 0,
+            slots_without_trade: 0,
+            last_update_slot: 100,
+            previous_update_slot: 0,
+            bump: 255,
+        }
+    }
+
+    fn position(authority: Pubkey) -> LiquidityPosition {
+        LiquidityPosition {
+            authority,
+            base_balance: 120 * P,
+            quote_balance: 100 * P,
+            base_per_quote_snapshot: 0,
+            quote_per_base_snapshot: 0,
+            slots_without_trade_snapshot: 0,
+            base_flow_u64: 3,
+            quote_flow_u64: 2,
+            base_debt: 0,
+            quote_debt: 0,
+            last_update_slot: 100,
+            bump: 255,
+        }
+    }
+
+    /// Market 1 with `authority`'s position on it, read at slot 150.
+    fn mock(authority: Pubkey) -> MockRpc {
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(1).address();
+        let rpc = MockRpc::new(150);
+        rpc.set_account(market_pda, &market());
+        rpc.set_account(
+            resolver.bookkeeping_pda(&market_pda).address(),
+            &bookkeeping(),
+        );
+        rpc.set_account(
+            resolver
+                .liquidity_position_pda(&market_pda, &authority)
+                .address(),
+            &position(authority),
+        );
+        rpc
+    }
+
+    async fn balances(rpc: &MockRpc, authority: Pubkey) -> LiquidityPositionBalances {
+        let state = fetch_market_state(rpc, 1).await.unwrap();
+        get_liquidity_position_balances(
+            rpc,
+            position(authority),
+            state.bookkeeping,
+            state.market,
+            state.current_slot,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetches_market_state_and_position() {
+        let authority = Pubkey::new_unique();
+        let rpc = mock(authority);
+
+        let state = fetch_market_state(&rpc, 1).await.unwrap();
+        assert_eq!(state.market.base_flow, 1_000);
+        assert_eq!(state.bookkeeping.last_update_slot, 100);
+        assert_eq!(state.current_slot, 150);
+
+        let position = fetch_liquidity_position(&rpc, 1, &authority).await.unwrap();
+        assert_eq!(position.authority, authority);
+
+        let missing = fetch_liquidity_position(&rpc, 1, &Pubkey::new_unique()).await;
+        assert!(matches!(missing, Err(TwobError::AccountNotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn balances_read_the_exits_accounts_in_the_window() {
+        let authority = Pubkey::new_unique();
+        let rpc = mock(authority);
+        // No exits account yet counts as a window without exits.
+        assert_eq!(
+            balances(&rpc, authority).await,
+            LiquidityPositionBalances {
+                base_balance: 20,
+                quote_balance: 300,
+                base_debt: 0,
+                quote_debt: 0,
+            }
+        );
+
+        let mut base_exits = [0; 10];
+        base_exits[2] = 500;
+        let exits = Exits {
+            owner: Pubkey::new_unique(),
+            base_exits,
+            quote_exits: [0; 10],
+            open_positions: 0,
+            index: 1,
+            bump: 255,
+        };
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = resolver.market_pda(1).address();
+        rpc.set_account(resolver.exits_pda(&market_pda, 1).address(), &exits);
+
+        let expected = position_balances(
+            &position(authority),
+            &bookkeeping(),
+            &market(),
+            &BTreeMap::from([(1, exits)]),
+            150,
+        )
+        .unwrap();
+        assert_eq!(balances(&rpc, authority).await, expected);
+        assert_ne!(expected.quote_balance, 300);
+    }
+
+    #[tokio::test]
+    async fn a_lagging_node_reads_as_stale() {
+        let authority = Pubkey::new_unique();
+        let rpc = mock(authority);
+        rpc.set_slot(99);
+        let state = fetch_market_state(&rpc, 1).await.unwrap();
+
+        let result = get_liquidity_position_balances(
+            &rpc,
+            position(authority),
+            state.bookkeeping,
+            state.market,
+            state.current_slot,
+        )
+        .await;
+        assert!(matches!(result, Err(TwobError::StaleData(_))));
+    }
+}
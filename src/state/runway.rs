@@ -0,0 +1,28 @@
+use tracing::info;
+
+use crate::{
+    LiquidityPositionBalances,
+    core::slots_until_debt_per_side,
+    twob_anchor::accounts::{LiquidityPosition, Market},
+};
+
+/// Report each side's runway as `gauge.position_runway_slots` by `runway.side`, so an alert
+/// can fire while debt is still some way off. A side that isn't draining reads as
+/// `u64::MAX` rather than going unreported, which would leave its last reading standing.
+pub fn record_runway(
+    market_id: u64,
+    position: &LiquidityPosition,
+    market: &Market,
+    balances: &LiquidityPositionBalances,
+) {
+    let (base, quote) = slots_until_debt_per_side(position, market, balances);
+    for (side, slots) in [("base", base), ("quote", quote)] {
+        info!(
+            event.name = "position_runway",
+            market.id = market_id,
+            runway.side = side,
+            runway.slots = ?slots,
+            gauge.position_runway_slots = slots.unwrap_or(u64::MAX) as f64,
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! The interface between market-making decision logic and the runtime that feeds it.
+//!
+//! A [`Strategy`] only decides: the runtime fetches market and position state, calls the
+//! hook matching what happened (a timer tick, a `MarketUpdateEvent`, a fresh oracle price)
+//! and carries out the returned [`Action`]s. Keeping strategies free of RPC and signing
+//! lets custom strategies plug into the shared transaction sending, alerting and control
+//! plane without reimplementing them, and keeps their logic unit-testable.
+//!
+//! The flow bots drive their strategy, or one plugged into them, through a
+//! [`StrategyRuntime`](crate::bots::runtime::StrategyRuntime), which passes what it
+//! decided to [`audit_decisions`]; [`execute_action`] audits how carrying it out went.
+
+use std::time::{Duration, Instant};
+
+use anchor_client::Program;
+
+use crate::{
+    LiquidityPositionBalances, MarketState, ProgramPayer, TwobError,
+    audit::{self, AuditRecord},
+    control::ThresholdOverrides,
+    execute_stop_position, execute_update_flows,
+    price::PriceData,
+    reporting::Reporting,
+    twob_anchor::{accounts::LiquidityPosition, events::MarketUpdateEvent},
+    tx::{SendOptions, TxSender},
+};
+
+/// What a strategy wants done. Actions are carried out in the order they are returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    UpdateFlows {
+        base_flow: u64,
+        quote_flow: u64,
+        reference_index: u64,
+    },
+    /// Stop the position via `public_stop_liquidity_position`.
+    Stop { reference_index: u64 },
+    /// Swap inventory back towards balance. How is up to the runtime (e.g. Jupiter).
+    Rebalance,
+    /// Call [`Strategy::on_tick`] again after `after`, with fresh state.
+    Reevaluate { after: Duration },
+}
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UpdateFlows { .. } => "update_flows",
+            Self::Stop { .. } => "stop",
+            Self::Rebalance => "rebalance",
+            Self::Reevaluate { .. } => "reevaluate",
+        }
+    }
+
+    /// The action's parameters, as audited.
+    fn params(&self) -> serde_json::Value {
+        match *self {
+            Self::UpdateFlows {
+                base_flow,
+                quote_flow,
+                reference_index,
+            } => serde_json::json!({
+                "base_flow": base_flow,
+                "quote_flow": quote_flow,
+                "reference_index": reference_index,
+            }),
+            Self::Stop { reference_index } => {
+                serde_json::json!({ "reference_index": reference_index })
+            }
+            Self::Rebalance => serde_json::json!({}),
+            Self::Reevaluate { after } => serde_json::json!({ "after_ms": after.as_millis() }),
+        }
+    }
+}
+
+/// Market and position state as of `market_state.current_slot`.
+#[derive(Clone, Copy)]
+pub struct StrategyContext<'a> {
+    pub market_id: u64,
+    pub market_state: &'a MarketState,
+    pub position: &'a LiquidityPosition,
+    pub balances: &'a LiquidityPositionBalances,
+}
+
+impl StrategyContext<'_> {
+    pub fn has_debt(&self) -> bool {
+        self.balances.base_debt > 0 || self.balances.quote_debt > 0
+    }
+
+    /// [`crate::nearest_reference_index`] for the current slot.
+    pub fn nearest_reference_index(&self) -> u64 {
+        crate::nearest_reference_index(
+            self.market_state.current_slot,
+            self.market_state.market.end_slot_interval,
+        )
+    }
+}
+
+pub trait Strategy: Send {
+    fn name(&self) -> &str;
+
+    /// Periodic evaluation.
+    fn on_tick(&mut self, ctx: &StrategyContext<'_>) -> Vec<Action>;
+
+    /// The market's aggregate flows changed.
+    fn on_market_event(
+        &mut self,
+        _event: &MarketUpdateEvent,
+        _ctx: &StrategyContext<'_>,
+    ) -> Vec<Action> {
+        Vec::new()
+    }
+
+    /// A fresh external reference price arrived.
+    fn on_price(&mut self, _price: &PriceData, _ctx: &StrategyContext<'_>) -> Vec<Action> {
+        Vec::new()
+    }
+
+    /// The control plane's threshold overrides, before each evaluation. A `None` field
+    /// puts the configured value back.
+    fn on_overrides(&mut self, _overrides: &ThresholdOverrides) {}
+
+    /// The runtime attempted an [`Action::Rebalance`] this strategy asked for, starting at
+    /// `started_at`, whether or not it went through.
+    fn on_rebalanced(&mut self, _started_at: Instant) {}
+}
+
+/// Carry out an on-chain action with the shared transaction helpers. `Rebalance` and
+/// `Reevaluate` need runtime-specific handling and are rejected.
+pub async fn execute_action(
+    program: &Program<ProgramPayer>,
+    market_id: u64,
+    action: &Action,
+    sender: &TxSender,
+) -> Result<(), TwobError> {
+    let result = match *action {
+        Action::UpdateFlows {
+            base_flow,
+            quote_flow,
+            reference_index,
+        } => {
+            execute_update_flows(
+                program,
+                market_id,
+                base_flow,
+                quote_flow,
+                reference_index,
+                sender,
+            )
+            .await
+        }
+        Action::Stop { reference_index } => {
+            let sender = sender.with_options(SendOptions::urgent());
+            execute_stop_position(program, market_id, reference_index, &sender).await
+        }
+        Action::Rebalance | Action::Reevaluate { .. } => Err(TwobError::Send(anyhow::anyhow!(
+            "{:?} is not an on-chain twob action",
+            action
+        ))),
+    };
+    audit::record(
+        sender.reporting(),
+        AuditRecord {
+            market_id: Some(market_id),
+            inputs: action.params(),
+            error: result.as_ref().err().map(ToString::to_string),
+            ..AuditRecord::new(
+                "strategy",
+                action.name(),
+                if result.is_ok() { "executed" } else { "failed" },
+            )
+        },
+    );
+    result
+}
+
+/// Audit each of the `actions` `strategy` decided on from `ctx`, with the state it
+/// decided on, through the bot's `reporting`, and note them for its
+/// [crash dump](crate::crash_dump).
+pub fn audit_decisions(
+    strategy: &str,
+    ctx: &StrategyContext<'_>,
+    actions: &[Action],
+    reporting: &Reporting,
+) {
+    if let Some(crash_dump) = &reporting.crash_dump {
+        crash_dump.record_actions(
+            actions
+                .iter()
+                .map(|action| {
+                    serde_json::json!({ "action": action.name(), "params": action.params() })
+                })
+                .collect(),
+        );
+    }
+    for action in actions {
+        audit::record(
+            reporting,
+            AuditRecord {
+                slot: Some(ctx.market_state.current_slot),
+                market_id: Some(ctx.market_id),
+                inputs: serde_json::json!({
+                    "strategy": strategy,
+                    "action": action.params(),
+                    "balances": {
+                        "base_balance": ctx.balances.base_balance,
+                        "quote_balance": ctx.balances.quote_balance,
+                        "base_debt": ctx.balances.base_debt,
+                        "quote_debt": ctx.balances.quote_debt,
+                    },
+                    "position": {
+                        "base_flow": ctx.position.base_flow_u64,
+                        "quote_flow": ctx.position.quote_flow_u64,
+                    },
+                }),
+                ..AuditRecord::new("strategy", action.name(), "decided")
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use super::*;
+    use crate::{accounts::test_market, twob_anchor::accounts::Bookkeeping};
+
+    fn market_state(current_slot: u64) -> MarketState {
+        MarketState {
+            market: test_market(),
+            bookkeeping: Bookkeeping {
+                base_per_quote: 0,
+                previous_base_per_quote: 0,
+                quote_per_base: 0,
+                previous_quote_per_base: 0,
+                slots_without_trade: 0,
+                last_update_slot: 0,
+                previous_update_slot: 0,
+                bump: 255,
+            },
+            current_slot,
+        }
+    }
+
+    fn position() -> LiquidityPosition {
+        LiquidityPosition {
+            authority: Pubkey::new_unique(),
+            base_balance: 0,
+            quote_balance: 0,
+            base_per_quote_snapshot: 0,
+            quote_per_base_snapshot: 0,
+            slots_without_trade_snapshot: 0,
+            base_flow_u64: 0,
+            quote_flow_u64: 0,
+            base_debt: 0,
+            quote_debt: 0,
+            last_update_slot: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn nearest_reference_index_looks_half_an_array_ahead() {
+        // Windows are ARRAY_LENGTH * end_slot_interval = 100 slots wide.
+        let state = market_state(260);
+        let position = position();
+        let balances = LiquidityPositionBalances {
+            base_balance: 0,
+            quote_balance: 0,
+            base_debt: 0,
+            quote_debt: 1,
+        };
+        let ctx = StrategyContext {
+            market_id: 1,
+            market_state: &state,
+            position: &position,
+            balances: &balances,
+        };
+
+        assert_eq!(ctx.nearest_reference_index(), 2);
+        assert!(ctx.has_debt());
+
+        let state = market_state(296);
+        let ctx = StrategyContext {
+            market_state: &state,
+            ..ctx
+        };
+        assert_eq!(ctx.nearest_reference_index(), 3);
+    }
+}
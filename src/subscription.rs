@@ -0,0 +1,140 @@
+//! How blind a bot's websocket event subscription has left it.
+//!
+//! A dropped subscription doesn't fail anything: the bot simply stops hearing about the
+//! market until it resubscribes. [`SubscriptionTracker`] counts the drops and
+//! resubscribes by websocket endpoint and times how long each outage lasted, and
+//! [`backfill_market_updates`] recovers the events emitted meanwhile from transaction
+//! history, counting them, so providers can be compared on how much they made a bot
+//! miss.
+
+use std::time::Instant;
+
+use anchor_client::solana_sdk::signature::Signature;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use tracing::{info, warn};
+
+use crate::{
+    AccountResolver,
+    decode::TwobEvent,
+    ingest::{fetch_signatures, fetch_transaction},
+    rate_limit::endpoint_host,
+    twob_anchor::{self, events::MarketUpdateEvent},
+};
+
+#[derive(Debug)]
+pub struct SubscriptionTracker {
+    subscription: &'static str,
+    endpoint: String,
+    market_id: u64,
+    last_event: Option<(Signature, u64)>,
+    down_since: Option<Instant>,
+}
+
+impl SubscriptionTracker {
+    pub fn new(subscription: &'static str, ws_url: &str, market_id: u64) -> Self {
+        Self {
+            subscription,
+            endpoint: endpoint_host(ws_url),
+            market_id,
+            last_event: None,
+            down_since: None,
+        }
+    }
+
+    /// Remember the latest event heard, as the point a backfill resumes from.
+    pub fn on_event(&mut self, signature: Signature, slot: u64) {
+        self.last_event = Some((signature, slot));
+    }
+
+    /// Signature of the latest event heard.
+    pub fn last_signature(&self) -> Option<Signature> {
+        self.last_event.map(|(signature, _)| signature)
+    }
+
+    pub fn disconnected(&mut self) {
+        self.down_since.get_or_insert_with(Instant::now);
+        warn!(
+            event.name = "ws_subscription_dropped",
+            market.id = self.market_id,
+            ws.endpoint = self.endpoint.as_str(),
+            subscription.name = self.subscription,
+            subscription.last_slot = ?self.last_event.map(|(_, slot)| slot),
+            monotonic_counter.ws_disconnects_total = 1_u64,
+        );
+    }
+
+    /// Record the subscription back up after `attempts` tries.
+    pub fn resubscribed(&mut self, attempts: u32) {
+        let blind_ms = self
+            .down_since
+            .take()
+            .map(|since| since.elapsed().as_millis() as u64)
+            .unwrap_or(0);
+        info!(
+            event.name = "ws_subscription_restored",
+            market.id = self.market_id,
+            ws.endpoint = self.endpoint.as_str(),
+            subscription.name = self.subscription,
+            subscription.attempts = %attempts,
+            monotonic_counter.ws_resubscribes_total = 1_u64,
+            histogram.ws_subscription_blind_ms = blind_ms,
+        );
+    }
+
+    /// Count `recovered` events found by a backfill after the last drop.
+    pub fn backfilled(&self, recovered: usize, truncated: bool) {
+        info!(
+            event.name = "ws_subscription_backfilled",
+            market.id = self.market_id,
+            ws.endpoint = self.endpoint.as_str(),
+            subscription.name = self.subscription,
+            subscription.backfill_truncated = truncated,
+            monotonic_counter.ws_events_backfilled_total = recovered as u64,
+        );
+    }
+}
+
+/// `MarketUpdateEvent`s for `market_id` in transactions after `until`, oldest first, looking
+/// at no more than `limit` transactions. Returns whether `limit` cut the history short.
+pub async fn backfill_market_updates(
+    rpc: &RpcClient,
+    market_id: u64,
+    until: Signature,
+    limit: usize,
+) -> anyhow::Result<(Vec<(Signature, u64, MarketUpdateEvent)>, bool)> {
+    let market = AccountResolver::new(twob_anchor::ID)
+        .market_pda(market_id)
+        .address();
+    let (signatures, truncated) = fetch_signatures(rpc, &market, Some(until), limit).await?;
+    let mut recovered = Vec::new();
+    for entry in signatures.into_iter().rev() {
+        if entry.err.is_some() {
+            continue;
+        }
+        let signature = entry.signature.parse::<Signature>()?;
+        let transaction = fetch_transaction(rpc, &signature).await?;
+        let slot = transaction.slot;
+        recovered.extend(
+            transaction
+                .events
+                .into_iter()
+                .filter_map(|event| match event {
+                    TwobEvent::MarketUpdate {
+                        market_id: event_market_id,
+                        base_flow,
+                        quote_flow,
+                    } if event_market_id == market_id => Some((
+                        signature,
+                        slot,
+                        MarketUpdateEvent {
+                            market_id,
+                            base_flow,
+                            quote_flow,
+                        },
+                    )),
+                    _ => None,
+                }),
+        );
+    }
+    Ok((recovered, truncated))
+}
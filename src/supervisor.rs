@@ -0,0 +1,294 @@
+//! Restart-with-backoff supervision for long-running strategy tasks.
+//!
+//! A [`Supervisor`] owns a set of named tasks built from factories. When a task fails or
+//! panics it is rebuilt after an exponential backoff; a task that returns `Ok(())` is
+//! considered finished and is not restarted. A shared [`KillSwitch`] stops every restart
+//! loop at once, and is handed to each task so it can wind down on its own terms. The flow
+//! bots take one in their entry points, so the orchestrator can run them as tasks of its
+//! own process ([`Launch::Task`]).
+
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::{signal, sync::watch, task::JoinSet, time::sleep};
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RestartPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up on a task after this many restarts; `None` retries forever.
+    pub max_restarts: Option<u32>,
+    /// A run lasting at least this long resets the backoff.
+    pub healthy_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5 * 60),
+            max_restarts: None,
+            healthy_after: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before the restart following `consecutive_failures` failed runs (1-based).
+    pub fn backoff(&self, consecutive_failures: u32) -> Duration {
+        let exponent = consecutive_failures.saturating_sub(1).min(31);
+        self.initial_backoff
+            .saturating_mul(1_u32 << exponent)
+            .min(self.max_backoff)
+    }
+}
+
+/// Process-wide stop signal. Cloning shares the same switch; once tripped it stays tripped.
+#[derive(Debug, Clone)]
+pub struct KillSwitch {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for KillSwitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KillSwitch {
+    pub fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::Sender::new(false)),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Resolves once the switch has been tripped.
+    pub async fn triggered(&self) {
+        let mut rx = self.tx.subscribe();
+        // The sender lives in `self`, so the channel cannot close while we wait.
+        let _ = rx.wait_for(|triggered| *triggered).await;
+    }
+
+    /// A switch tripped by ctrl-c, for a strategy running as a process of its own. Must be
+    /// called from within the runtime.
+    pub fn on_ctrl_c() -> Self {
+        let kill = Self::new();
+        let tripped = kill.clone();
+        tokio::spawn(async move {
+            if signal::ctrl_c().await.is_ok() {
+                tripped.trigger();
+            }
+        });
+        kill
+    }
+}
+
+/// How a strategy's entry point was started. As a [`Process`](Self::Process) it is alone in
+/// its process and sets up the process-wide telemetry, crash dump and panic hooks itself; as
+/// a [`Task`](Self::Task) it runs under the orchestrator's [`Supervisor`] next to others and
+/// leaves those to the orchestrator, which restarts it instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Launch {
+    Process,
+    Task,
+}
+
+pub struct Supervisor {
+    policy: RestartPolicy,
+    kill_switch: KillSwitch,
+    tasks: JoinSet<()>,
+}
+
+impl Supervisor {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            kill_switch: KillSwitch::new(),
+            tasks: JoinSet::new(),
+        }
+    }
+
+    pub fn kill_switch(&self) -> KillSwitch {
+        self.kill_switch.clone()
+    }
+
+    /// Run `factory`'s task under supervision. The factory is called again for every restart.
+    pub fn spawn<F, Fut>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: FnMut(KillSwitch) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.tasks.spawn(supervise(
+            name.into(),
+            factory,
+            self.policy,
+            self.kill_switch.clone(),
+        ));
+    }
+
+    /// Wait until every supervised task has finished or been given up on.
+    pub async fn join(mut self) {
+        while self.tasks.join_next().await.is_some() {}
+    }
+}
+
+async fn supervise<F, Fut>(name: String, mut factory: F, policy: RestartPolicy, kill: KillSwitch)
+where
+    F: FnMut(KillSwitch) -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let mut consecutive_failures = 0_u32;
+    let mut restarts = 0_u32;
+
+    loop {
+        if kill.is_triggered() {
+            return;
+        }
+
+        info!(
+            event.name = "supervisor_task_started",
+            task.name = %name,
+            task.restarts = restarts,
+        );
+        let started_at = Instant::now();
+        // Spawned separately so a panic surfaces as a JoinError instead of unwinding us.
+        let failure = match tokio::spawn(factory(kill.clone())).await {
+            Ok(Ok(())) => {
+                info!(event.name = "supervisor_task_finished", task.name = %name);
+                return;
+            }
+            Ok(Err(error)) => format!("{error:#}"),
+            Err(join_error) => format!("task panicked: {join_error}"),
+        };
+
+        if kill.is_triggered() {
+            return;
+        }
+
+        if started_at.elapsed() >= policy.healthy_after {
+            consecutive_failures = 0;
+        }
+        consecutive_failures = consecutive_failures.saturating_add(1);
+        restarts = restarts.saturating_add(1);
+
+        if policy.max_restarts.is_some_and(|max| restarts > max) {
+            error!(
+                event.name = "supervisor_task_abandoned",
+                task.name = %name,
+                task.restarts = restarts - 1,
+                error = %failure,
+            );
+            return;
+        }
+
+        let delay = policy.backoff(consecutive_failures);
+        warn!(
+            event.name = "supervisor_task_failed",
+            task.name = %name,
+            task.restart_in_ms = delay.as_millis() as u64,
+            monotonic_counter.supervisor_restarts_total = 1_u64,
+            error = %failure,
+        );
+
+        tokio::select! {
+            _ = kill.triggered() => return,
+            _ = sleep(delay) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn fast_policy(max_restarts: Option<u32>) -> RestartPolicy {
+        RestartPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            max_restarts,
+            healthy_after: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RestartPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(5), Duration::from_secs(10));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn restarts_failed_and_panicking_tasks_until_they_finish() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let mut supervisor = Supervisor::new(fast_policy(None));
+        supervisor.spawn("flaky", {
+            let runs = runs.clone();
+            move |_kill| {
+                let run = runs.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match run {
+                        0 => anyhow::bail!("rpc down"),
+                        1 => panic!("bad state"),
+                        _ => Ok(()),
+                    }
+                }
+            }
+        });
+
+        supervisor.join().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_restarts() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let mut supervisor = Supervisor::new(fast_policy(Some(2)));
+        supervisor.spawn("broken", {
+            let runs = runs.clone();
+            move |_kill| {
+                runs.fetch_add(1, Ordering::SeqCst);
+                async { anyhow::bail!("always fails") }
+            }
+        });
+
+        supervisor.join().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn kill_switch_stops_running_tasks_and_restarts() {
+        let mut supervisor = Supervisor::new(fast_policy(None));
+        let kill_switch = supervisor.kill_switch();
+        supervisor.spawn("long-running", |kill: KillSwitch| async move {
+            kill.triggered().await;
+            Ok(())
+        });
+        supervisor.spawn("failing", |_kill| async { anyhow::bail!("fails") });
+
+        kill_switch.trigger();
+        tokio::time::timeout(Duration::from_secs(1), supervisor.join())
+            .await
+            .unwrap();
+        assert!(kill_switch.is_triggered());
+    }
+}
@@ -1,3 +1,28 @@
+//! Tracing setup shared by the bots: logs to stdout and, with `OTEL_EXPORTER_OTLP_ENDPOINT`
+//! set, traces, metrics and logs over OTLP.
+//!
+//! Stdout logs are JSON lines or readable console output by `LOG_FORMAT` (`json` or
+//! `pretty`); see [`LogFormat`]. Deployments that ship logs to an aggregator want the
+//! former, a terminal the latter.
+//!
+//! Exported traces break each action down end to end. A bot's cycle span holds its RPC
+//! fetches and strategy math, and every send through a [`TxSender`](crate::tx::TxSender)
+//! adds a `tx.send` span with `tx.build` (pricing, blockhash, signing, simulation),
+//! `tx.broadcast` and `tx.confirm` children for each attempt, so a slow action shows
+//! where its time went.
+//!
+//! Metrics are events carrying a `monotonic_counter.`, `gauge.` or `histogram.` field,
+//! named in snake case with the unit last (`_ms`, `_lamports`, `_raw`); counters end in
+//! `_total`. A metric shared by the bots, e.g. `cycles_total` or `tx_sent_total`, has one
+//! name everywhere rather than one per bin. The event's string, integer and bool fields
+//! become its labels, e.g. `market.id`, `tx.action` or `rpc.endpoint`, while `%` and `?`
+//! fields (signatures, cycle ids, errors) stay out of them, so series counts stay bounded.
+//!
+//! Every series also carries its bot's `market.id`, `pair` (`MARKET_PAIR`, e.g.
+//! `SOL/USDC`) and `strategy` as resource attributes. The collector config in
+//! `deploy/otel-collector.yaml` turns those into `market_id`, `pair` and `strategy`
+//! labels, and `deploy/prometheus/recording-rules.yml` rolls a fleet's series up per pair.
+
 use std::{env, time::Duration};
 
 use anyhow::{Context, Result, anyhow};
@@ -17,48 +42,7 @@ use tracing_subscriber::{
     Layer, filter::EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt,
 };
 
-const DEFAULT_SERVICE_NAME: &str = "twob-market-maker";
-const DEFAULT_BALANCE_SNAPSHOT_INTERVAL_SECS: u64 = 60;
-
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct TelemetryConfig {
-    pub service_name: String,
-    pub stdout_json: bool,
-    pub balance_snapshot_interval_secs: u64,
-}
-
-impl TelemetryConfig {
-    pub fn from_env() -> Result<Self> {
-        Self::from_lookup(|key| env::var(key).ok())
-    }
-
-    fn from_lookup<F>(lookup: F) -> Result<Self>
-    where
-        F: Fn(&str) -> Option<String>,
-    {
-        let service_name = lookup("OTEL_SERVICE_NAME")
-            .filter(|value| !value.trim().is_empty())
-            .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
-        let stdout_json = lookup("TELEMETRY_STDOUT_JSON")
-            .map(|value| parse_bool(&value))
-            .transpose()?
-            .unwrap_or(true);
-        let balance_snapshot_interval_secs = lookup("BALANCE_SNAPSHOT_INTERVAL_SECS")
-            .map(|value| {
-                value.parse::<u64>().with_context(|| {
-                    format!("invalid BALANCE_SNAPSHOT_INTERVAL_SECS value `{value}`")
-                })
-            })
-            .transpose()?
-            .unwrap_or(DEFAULT_BALANCE_SNAPSHOT_INTERVAL_SECS);
-
-        Ok(Self {
-            service_name,
-            stdout_json,
-            balance_snapshot_interval_secs,
-        })
-    }
-}
+use crate::{cluster::ClusterPreset, config::env_var};
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct OtlpExporterConfig {
@@ -68,7 +52,7 @@ struct OtlpExporterConfig {
 
 impl OtlpExporterConfig {
     fn from_env() -> Self {
-        Self::from_lookup(|key| env::var(key).ok())
+        Self::from_lookup(|key| env_var(key).ok())
     }
 
     fn from_lookup<F>(lookup: F) -> Self
@@ -90,10 +74,93 @@ impl OtlpExporterConfig {
     }
 }
 
+/// How stdout logs are written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LogFormat {
+    /// One JSON object per line, with the current span and its parents.
+    Json,
+    /// Human-readable lines.
+    Pretty,
+}
+
+impl LogFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Pretty => "pretty",
+        }
+    }
+
+    /// `LOG_FORMAT`, else the older `TELEMETRY_STDOUT_JSON` flag, else `default`.
+    pub fn from_env(default: Self) -> Result<Self> {
+        Self::from_lookup(|key| env_var(key).ok(), default)
+    }
+
+    pub fn from_lookup<F>(lookup: F, default: Self) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(value) = lookup("LOG_FORMAT").filter(|value| !value.trim().is_empty()) {
+            return value.parse();
+        }
+        Ok(lookup("TELEMETRY_STDOUT_JSON")
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| parse_bool(&value))
+            .transpose()?
+            .map_or(default, |json| if json { Self::Json } else { Self::Pretty }))
+    }
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "pretty" | "text" | "plain" => Ok(Self::Pretty),
+            other => Err(anyhow!(
+                "invalid LOG_FORMAT `{other}`, expected json or pretty"
+            )),
+        }
+    }
+}
+
+fn stdout_layer<S>(format: LogFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    match format {
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_target(true)
+            .boxed(),
+        LogFormat::Pretty => fmt::layer()
+            .with_target(true)
+            .with_thread_ids(true)
+            .with_thread_names(true)
+            .boxed(),
+    }
+}
+
+/// Stdout logging alone, for the tools that don't export over OTLP. Honours `RUST_LOG` and
+/// [`LogFormat::from_env`], defaulting to pretty output.
+pub fn init_logging() -> Result<()> {
+    let env_filter = env_filter();
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(stdout_layer(LogFormat::from_env(LogFormat::Pretty)?))
+        .try_init()
+        .map_err(|error| anyhow!(error))
+}
+
 #[derive(Clone, Debug)]
 pub struct TelemetryInitConfig {
     pub service_name: String,
-    pub stdout_json: bool,
+    /// Reported as the `bot.role` resource attribute, e.g. `oracle-flow`.
+    pub bot_role: &'static str,
+    pub log_format: LogFormat,
     pub market_id: u64,
     pub authority: String,
     pub rpc_url: String,
@@ -131,32 +198,14 @@ fn log_shutdown_result<E: std::fmt::Display>(
 }
 
 pub fn init_telemetry(config: TelemetryInitConfig) -> Result<TelemetryGuard> {
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let env_filter = env_filter();
     let resource = telemetry_resource(&config);
     let otlp_config = OtlpExporterConfig::from_env();
 
-    let fmt_json_layer = fmt::layer()
-        .json()
-        .with_current_span(true)
-        .with_span_list(true)
-        .with_target(true)
-        .boxed();
-    let fmt_pretty_layer = fmt::layer()
-        .with_target(true)
-        .with_thread_ids(true)
-        .with_thread_names(true)
-        .boxed();
-
-    let stdout_layer = if config.stdout_json {
-        fmt_json_layer
-    } else {
-        fmt_pretty_layer
-    };
-
     let base_subscriber = tracing_subscriber::registry()
         .with(env_filter)
         .with(ErrorLayer::default())
-        .with(stdout_layer);
+        .with(stdout_layer(config.log_format));
 
     let Some(endpoint) = otlp_config.endpoint.as_deref() else {
         base_subscriber.try_init().map_err(|error| anyhow!(error))?;
@@ -234,19 +283,6 @@ pub fn init_telemetry(config: TelemetryInitConfig) -> Result<TelemetryGuard> {
     })
 }
 
-pub fn balance_delta(after: u64, before: u64) -> i128 {
-    i128::from(after) - i128::from(before)
-}
-
-pub fn external_wallet_input_estimated(input_consumed: u64, available_budget: u64) -> u64 {
-    input_consumed.saturating_sub(available_budget)
-}
-
-pub fn token_amount_ui(raw_amount: u64, decimals: u8) -> f64 {
-    let scale = 10_u64.checked_pow(u32::from(decimals)).unwrap_or(1) as f64;
-    raw_amount as f64 / scale
-}
-
 pub fn parse_otlp_headers(headers: &str) -> Vec<(String, String)> {
     headers
         .split(',')
@@ -272,8 +308,19 @@ fn telemetry_resource(config: &TelemetryInitConfig) -> Resource {
             KeyValue::new("service.namespace", "twob"),
             KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
             KeyValue::new("deployment.environment.name", deployment_environment()),
-            KeyValue::new("bot.role", "oracle-flow"),
-            KeyValue::new("solana.cluster", solana_cluster(&config.rpc_url)),
+            KeyValue::new("bot.role", config.bot_role),
+            KeyValue::new("strategy", config.bot_role),
+            KeyValue::new(
+                "pair",
+                market_pair(config.market_id, |key| env_var(key).ok()),
+            ),
+            KeyValue::new(
+                "solana.cluster",
+                match ClusterPreset::from_env() {
+                    Ok(Some(preset)) => preset.as_str(),
+                    _ => solana_cluster(&config.rpc_url),
+                },
+            ),
             KeyValue::new("market.id", config.market_id.to_string()),
             KeyValue::new("twob.program_id", config.program_id.clone()),
             KeyValue::new("lp.authority", config.authority.clone()),
@@ -281,12 +328,32 @@ fn telemetry_resource(config: &TelemetryInitConfig) -> Resource {
         .build()
 }
 
+/// `MARKET_PAIR`, upper-cased so `sol/usdc` and `SOL/USDC` land in one series, or
+/// `market-<id>` when it's unset.
+fn market_pair<F>(market_id: u64, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    lookup("MARKET_PAIR")
+        .map(|value| value.trim().to_ascii_uppercase())
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| format!("market-{market_id}"))
+}
+
+/// The `RUST_LOG` filter, read like every other setting, else `info`.
+fn env_filter() -> EnvFilter {
+    env_var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .and_then(|directives| EnvFilter::try_new(directives).ok())
+        .unwrap_or_else(|| EnvFilter::new("info"))
+}
+
 fn deployment_environment() -> String {
-    env::var("DEPLOYMENT_ENVIRONMENT_NAME")
-        .or_else(|_| env::var("DEPLOYMENT_ENVIRONMENT"))
-        .or_else(|_| env::var("RAILWAY_ENVIRONMENT_NAME"))
+    env_var("DEPLOYMENT_ENVIRONMENT_NAME")
+        .or_else(|_| env_var("DEPLOYMENT_ENVIRONMENT"))
+        .or_else(|_| env_var("RAILWAY_ENVIRONMENT_NAME"))
         .or_else(|_| {
-            env::var("OTEL_RESOURCE_ATTRIBUTES")
+            env_var("OTEL_RESOURCE_ATTRIBUTES")
                 .ok()
                 .and_then(|attributes| {
                     resource_attribute_value(&attributes, "deployment.environment.name")
@@ -322,7 +389,7 @@ fn solana_cluster(rpc_url: &str) -> &'static str {
     }
 }
 
-fn parse_bool(value: &str) -> Result<bool> {
+pub fn parse_bool(value: &str) -> Result<bool> {
     match value.trim().to_ascii_lowercase().as_str() {
         "1" | "true" | "yes" | "y" | "on" => Ok(true),
         "0" | "false" | "no" | "n" | "off" => Ok(false),
@@ -335,34 +402,6 @@ mod tests {
     use super::*;
     use std::collections::HashMap;
 
-    #[test]
-    fn parses_telemetry_config_defaults() {
-        let config = TelemetryConfig::from_lookup(|_| None).unwrap();
-
-        assert_eq!(config.service_name, DEFAULT_SERVICE_NAME);
-        assert!(config.stdout_json);
-        assert_eq!(
-            config.balance_snapshot_interval_secs,
-            DEFAULT_BALANCE_SNAPSHOT_INTERVAL_SECS
-        );
-    }
-
-    #[test]
-    fn parses_telemetry_config_from_lookup() {
-        let env = HashMap::from([
-            ("OTEL_SERVICE_NAME", "custom-service"),
-            ("TELEMETRY_STDOUT_JSON", "false"),
-            ("BALANCE_SNAPSHOT_INTERVAL_SECS", "15"),
-        ]);
-        let config =
-            TelemetryConfig::from_lookup(|key| env.get(key).map(|value| value.to_string()))
-                .unwrap();
-
-        assert_eq!(config.service_name, "custom-service");
-        assert!(!config.stdout_json);
-        assert_eq!(config.balance_snapshot_interval_secs, 15);
-    }
-
     #[test]
     fn parses_otlp_headers_without_exposing_values() {
         let headers = parse_otlp_headers("authorization=secret, x-team = market-making ");
@@ -409,31 +448,45 @@ mod tests {
     }
 
     #[test]
-    fn reads_deployment_environment_from_resource_attributes() {
+    fn log_format_prefers_log_format_over_the_json_flag() {
+        let format = |pairs: &[(&str, &str)]| {
+            let env = pairs.iter().copied().collect::<HashMap<_, _>>();
+            LogFormat::from_lookup(
+                |key| env.get(key).map(|value| value.to_string()),
+                LogFormat::Pretty,
+            )
+        };
+
+        assert_eq!(format(&[]).unwrap(), LogFormat::Pretty);
         assert_eq!(
-            resource_attribute_value(
-                "service.namespace=twob,deployment.environment.name=prod,bot.role=oracle-flow",
-                "deployment.environment.name",
-            ),
-            Some("prod".to_string())
+            format(&[("TELEMETRY_STDOUT_JSON", "true")]).unwrap(),
+            LogFormat::Json
         );
+        assert_eq!(
+            format(&[("LOG_FORMAT", "PRETTY"), ("TELEMETRY_STDOUT_JSON", "true")]).unwrap(),
+            LogFormat::Pretty
+        );
+        assert_eq!(format(&[("LOG_FORMAT", "json")]).unwrap(), LogFormat::Json);
+        assert!(format(&[("LOG_FORMAT", "xml")]).is_err());
     }
 
     #[test]
-    fn computes_balance_delta() {
-        assert_eq!(balance_delta(110, 100), 10);
-        assert_eq!(balance_delta(90, 100), -10);
-    }
-
-    #[test]
-    fn estimates_external_wallet_input() {
-        assert_eq!(external_wallet_input_estimated(150, 100), 50);
-        assert_eq!(external_wallet_input_estimated(80, 100), 0);
+    fn labels_the_pair_consistently() {
+        assert_eq!(
+            market_pair(7, |_| Some(" sol/usdc ".to_string())),
+            "SOL/USDC"
+        );
+        assert_eq!(market_pair(7, |_| None), "market-7");
     }
 
     #[test]
-    fn formats_token_amount_ui() {
-        assert_eq!(token_amount_ui(1_000_000, 6), 1.0);
-        assert_eq!(token_amount_ui(1_000_000_000, 9), 1.0);
+    fn reads_deployment_environment_from_resource_attributes() {
+        assert_eq!(
+            resource_attribute_value(
+                "service.namespace=twob,deployment.environment.name=prod,bot.role=oracle-flow",
+                "deployment.environment.name",
+            ),
+            Some("prod".to_string())
+        );
     }
 }
@@ -0,0 +1,151 @@
+//! Execution-quality metrics: how a position's flows change, how much it has streamed,
+//! and how far its quote sits from the oracle over time.
+//!
+//! A configuration that requotes on every tick pays for it in fees without turning over
+//! more inventory, and one quoting wide of the oracle turns over little at a good price.
+//! [`record_flow_change`] reports each update's size and direction along with the
+//! inventory streamed at the flows it replaces, and [`SpreadTracker`] keeps the
+//! slot-weighted average of the quote's spread to the oracle, so the two can be set
+//! against each other per configuration.
+
+use tracing::info;
+
+use crate::{quote::flow_price, twob_anchor::accounts::LiquidityPosition};
+
+/// Which way a flow moved.
+pub fn direction(previous: u64, next: u64) -> &'static str {
+    match next.cmp(&previous) {
+        std::cmp::Ordering::Greater => "increase",
+        std::cmp::Ordering::Less => "decrease",
+        std::cmp::Ordering::Equal => "unchanged",
+    }
+}
+
+/// Size of a change from `previous` to `next`, in bps of `previous`; a flow starting from
+/// zero counts as 10 000.
+pub fn change_bps(previous: u64, next: u64) -> u64 {
+    if previous == 0 {
+        return if next == 0 { 0 } else { 10_000 };
+    }
+    (u128::from(previous.abs_diff(next)) * 10_000 / u128::from(previous)) as u64
+}
+
+/// Report `position`'s flows changing to `base_flow`/`quote_flow` at `current_slot`, with
+/// what the old flows streamed since they were set.
+pub fn record_flow_change(
+    market_id: u64,
+    position: &LiquidityPosition,
+    current_slot: u64,
+    base_flow: u64,
+    quote_flow: u64,
+) {
+    let slots = current_slot.saturating_sub(position.last_update_slot);
+    let streamed = |flow: u64| u128::from(flow) * u128::from(slots);
+    info!(
+        event.name = "flow_changed",
+        market.id = market_id,
+        flow.base_direction = direction(position.base_flow_u64, base_flow),
+        flow.quote_direction = direction(position.quote_flow_u64, quote_flow),
+        flow.base_delta = %(i128::from(base_flow) - i128::from(position.base_flow_u64)),
+        flow.quote_delta = %(i128::from(quote_flow) - i128::from(position.quote_flow_u64)),
+        flow.held_slots = %slots,
+        monotonic_counter.flow_changes_total = 1_u64,
+        histogram.flow_change_base_bps = change_bps(position.base_flow_u64, base_flow),
+        histogram.flow_change_quote_bps = change_bps(position.quote_flow_u64, quote_flow),
+        monotonic_counter.flow_turnover_base_raw = streamed(position.base_flow_u64) as f64,
+        monotonic_counter.flow_turnover_quote_raw = streamed(position.quote_flow_u64) as f64,
+    );
+}
+
+/// Signed distance of `quote_price` from `oracle_price`, in bps of the oracle.
+pub fn spread_bps(quote_price: f64, oracle_price: f64) -> Option<f64> {
+    (oracle_price.is_finite() && oracle_price > 0.0 && quote_price.is_finite())
+        .then(|| (quote_price - oracle_price) / oracle_price * 10_000.0)
+}
+
+/// Slot-weighted average of the quote's spread to the oracle. Each observed spread is held
+/// until the next observation.
+#[derive(Debug, Clone, Default)]
+pub struct SpreadTracker {
+    weighted_bps: f64,
+    slots: u64,
+    last: Option<(u64, f64)>,
+}
+
+impl SpreadTracker {
+    /// Record the spread seen at `slot` and return the average so far, if any slots have
+    /// been covered yet.
+    pub fn observe(&mut self, slot: u64, spread_bps: f64) -> Option<f64> {
+        if let Some((last_slot, last_spread)) = self.last {
+            let held = slot.saturating_sub(last_slot);
+            self.weighted_bps += last_spread.abs() * held as f64;
+            self.slots += held;
+        }
+        if self.last.is_none_or(|(last_slot, _)| slot >= last_slot) {
+            self.last = Some((slot, spread_bps));
+        }
+        self.average()
+    }
+
+    /// Time-weighted average absolute spread, in bps.
+    pub fn average(&self) -> Option<f64> {
+        (self.slots > 0).then(|| self.weighted_bps / self.slots as f64)
+    }
+
+    /// Report `position`'s quote against `oracle_price` at `slot`.
+    pub fn record(
+        &mut self,
+        market_id: u64,
+        position: &LiquidityPosition,
+        oracle_price: f64,
+        slot: u64,
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+    ) {
+        let Some(spread) = flow_price(
+            u128::from(position.base_flow_u64),
+            u128::from(position.quote_flow_u64),
+            base_token_decimals,
+            quote_token_decimals,
+        )
+        .and_then(|quote_price| spread_bps(quote_price, oracle_price)) else {
+            return;
+        };
+        let average = self.observe(slot, spread);
+        info!(
+            event.name = "quote_spread",
+            market.id = market_id,
+            gauge.quote_spread_bps = spread,
+            gauge.quote_spread_twa_bps = average.unwrap_or(spread.abs()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flow_changes_have_a_size_and_direction() {
+        assert_eq!(direction(100, 150), "increase");
+        assert_eq!(direction(100, 50), "decrease");
+        assert_eq!(direction(7, 7), "unchanged");
+        assert_eq!(change_bps(100, 150), 5_000);
+        assert_eq!(change_bps(100, 50), 5_000);
+        assert_eq!(change_bps(0, 5), 10_000);
+        assert_eq!(change_bps(0, 0), 0);
+    }
+
+    #[test]
+    fn spread_average_weights_each_spread_by_the_slots_it_held() {
+        assert_eq!(spread_bps(101.0, 100.0).map(f64::round), Some(100.0));
+        assert_eq!(spread_bps(1.0, 0.0), None);
+
+        let mut tracker = SpreadTracker::default();
+        assert_eq!(tracker.observe(100, 20.0), None);
+        // 20bps held for 100 slots, then -50bps for 300.
+        assert_eq!(tracker.observe(200, -50.0), Some(20.0));
+        let average = tracker.observe(500, 0.0).unwrap();
+        assert!((average - (20.0 * 100.0 + 50.0 * 300.0) / 400.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,190 @@
+//! What a send costs the payer, estimated before it goes out, and the operator's daily
+//! budget for fees.
+//!
+//! A [`CostEstimate`] splits the cost into the base fee per signature, the priority fee on
+//! the requested compute units, a Jito tip when there is one, and the rent of accounts the
+//! transaction creates (e.g. a new exits/prices pair or an ATA). Rent is held in those
+//! accounts rather than burned, so only the rest counts against a [`FeeBudget`].
+
+use std::{fmt, sync::Mutex};
+
+use chrono::{NaiveDate, Utc};
+
+/// Lamports charged per signature.
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Compute units the runtime grants each instruction when no limit is requested.
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+
+/// The most compute units a transaction may use.
+const MAX_COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+
+/// Expected lamport cost of one transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CostEstimate {
+    pub base_fee: u64,
+    pub priority_fee: u64,
+    pub tip: u64,
+    /// Rent-exempt balances of the accounts it creates.
+    pub rent: u64,
+}
+
+impl CostEstimate {
+    /// The fees for a transaction with `signatures` signatures and `instructions`
+    /// instructions of its own, requesting `compute_unit_limit` units (the runtime default
+    /// when `None`) at `compute_unit_price` micro-lamports each.
+    pub fn fees(
+        signatures: usize,
+        instructions: usize,
+        compute_unit_limit: Option<u32>,
+        compute_unit_price: Option<u64>,
+    ) -> Self {
+        let units = compute_unit_limit.map(u64::from).unwrap_or_else(|| {
+            (instructions as u64 * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT)
+                .min(MAX_COMPUTE_UNIT_LIMIT)
+        });
+        let priority_fee = (u128::from(compute_unit_price.unwrap_or(0)) * u128::from(units))
+            .div_ceil(1_000_000) as u64;
+        Self {
+            base_fee: signatures as u64 * LAMPORTS_PER_SIGNATURE,
+            priority_fee,
+            ..Self::default()
+        }
+    }
+
+    /// What counts against a [`FeeBudget`]: everything but rent.
+    pub fn spend(&self) -> u64 {
+        self.base_fee + self.priority_fee + self.tip
+    }
+
+    pub fn total(&self) -> u64 {
+        self.spend() + self.rent
+    }
+}
+
+/// A send was refused because its fees would take the day's spend past the
+/// [`FeeBudget`]. Critical sends are never refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeBudgetExceeded {
+    pub spent: u64,
+    pub estimate: u64,
+    pub budget: u64,
+}
+
+impl fmt::Display for FeeBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} lamports of fees would take today's {} lamports spent past the {} lamport budget",
+            self.estimate, self.spent, self.budget
+        )
+    }
+}
+
+impl std::error::Error for FeeBudgetExceeded {}
+
+/// Lamports spent on fees per UTC day, against a daily limit. Spend is kept in memory, so
+/// a restart starts the day's count over.
+#[derive(Debug)]
+pub struct FeeBudget {
+    daily_lamports: u64,
+    spent: Mutex<(NaiveDate, u64)>,
+}
+
+impl FeeBudget {
+    pub fn new(daily_lamports: u64) -> Self {
+        Self {
+            daily_lamports,
+            spent: Mutex::new((Utc::now().date_naive(), 0)),
+        }
+    }
+
+    pub fn daily_lamports(&self) -> u64 {
+        self.daily_lamports
+    }
+
+    /// Lamports spent so far today.
+    pub fn spent_today(&self) -> u64 {
+        self.spent_on(Utc::now().date_naive())
+    }
+
+    /// Fail if spending `lamports` now would go over today's budget.
+    pub fn check(&self, lamports: u64) -> Result<(), FeeBudgetExceeded> {
+        self.check_on(Utc::now().date_naive(), lamports)
+    }
+
+    /// Count `lamports` against today's budget.
+    pub fn record(&self, lamports: u64) {
+        self.record_on(Utc::now().date_naive(), lamports);
+    }
+
+    fn spent_on(&self, day: NaiveDate) -> u64 {
+        let spent = self.lock();
+        if spent.0 == day { spent.1 } else { 0 }
+    }
+
+    fn check_on(&self, day: NaiveDate, lamports: u64) -> Result<(), FeeBudgetExceeded> {
+        let spent = self.spent_on(day);
+        if spent.saturating_add(lamports) <= self.daily_lamports {
+            return Ok(());
+        }
+        Err(FeeBudgetExceeded {
+            spent,
+            estimate: lamports,
+            budget: self.daily_lamports,
+        })
+    }
+
+    fn record_on(&self, day: NaiveDate, lamports: u64) {
+        let mut spent = self.lock();
+        if spent.0 != day {
+            *spent = (day, 0);
+        }
+        spent.1 = spent.1.saturating_add(lamports);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (NaiveDate, u64)> {
+        self.spent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fees_charge_signatures_and_requested_units() {
+        let estimate = CostEstimate::fees(2, 1, Some(100_000), Some(1_500));
+        assert_eq!(estimate.base_fee, 10_000);
+        assert_eq!(estimate.priority_fee, 150);
+        assert_eq!(estimate.spend(), 10_150);
+
+        // No limit requested: the runtime default per instruction, capped.
+        let estimate = CostEstimate::fees(1, 10, None, Some(1_000_000));
+        assert_eq!(estimate.priority_fee, MAX_COMPUTE_UNIT_LIMIT);
+        assert_eq!(CostEstimate::fees(1, 1, Some(1), Some(1)).priority_fee, 1);
+    }
+
+    #[test]
+    fn budget_refuses_overspend_and_resets_each_day() {
+        let budget = FeeBudget::new(20_000);
+        let day = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let next_day = day.succ_opt().unwrap();
+
+        budget.record_on(day, 15_000);
+        assert!(budget.check_on(day, 5_000).is_ok());
+        assert_eq!(
+            budget.check_on(day, 5_001),
+            Err(FeeBudgetExceeded {
+                spent: 15_000,
+                estimate: 5_001,
+                budget: 20_000,
+            })
+        );
+        assert!(budget.check_on(next_day, 20_000).is_ok());
+        budget.record_on(next_day, 1_000);
+        assert_eq!(budget.spent_on(next_day), 1_000);
+    }
+}
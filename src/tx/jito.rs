@@ -0,0 +1,148 @@
+//! Private submission through a Jito block engine. A transaction sent this way goes to the
+//! block engine alone, never to a public RPC node's mempool forwarding, so nobody can see
+//! a flow update's direction and trade around it before it lands. The block engine only
+//! takes transactions that tip one of its tip accounts.
+
+use std::time::SystemTime;
+
+use anchor_client::solana_sdk::{
+    instruction::Instruction, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+};
+use anyhow::Context;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::{Value, json};
+
+use crate::config::env_var;
+
+/// Jito's mainnet tip accounts, used unless `TX_JITO_TIP_ACCOUNTS` lists others.
+pub const DEFAULT_TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitoConfig {
+    /// Block engine base URL, e.g. `https://mainnet.block-engine.jito.wtf`.
+    pub block_engine_url: String,
+    /// Tip added to every privately sent transaction.
+    pub tip_lamports: u64,
+    pub tip_accounts: Vec<Pubkey>,
+}
+
+impl JitoConfig {
+    /// `None` unless `TX_JITO_BLOCK_ENGINE_URL` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(block_engine_url) = env_var("TX_JITO_BLOCK_ENGINE_URL")
+            .ok()
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty())
+        else {
+            return Ok(None);
+        };
+
+        let tip_lamports = match env_var("TX_JITO_TIP_LAMPORTS") {
+            Ok(value) if !value.trim().is_empty() => value
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("Invalid TX_JITO_TIP_LAMPORTS: {}", e))?,
+            _ => 10_000,
+        };
+        let tip_accounts = env_var("TX_JITO_TIP_ACCOUNTS").unwrap_or_default();
+        let tip_accounts: Vec<&str> = if tip_accounts.trim().is_empty() {
+            DEFAULT_TIP_ACCOUNTS.to_vec()
+        } else {
+            tip_accounts.split(',').map(str::trim).collect()
+        };
+        let tip_accounts = tip_accounts
+            .into_iter()
+            .map(|account| {
+                account
+                    .parse::<Pubkey>()
+                    .map_err(|e| anyhow::anyhow!("Invalid Jito tip account {}: {}", account, e))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Some(Self {
+            block_engine_url,
+            tip_lamports,
+            tip_accounts,
+        }))
+    }
+}
+
+/// Sends transactions to a block engine's `sendTransaction` endpoint.
+pub struct JitoRelay {
+    http: reqwest::Client,
+    config: JitoConfig,
+}
+
+impl JitoRelay {
+    pub fn new(config: JitoConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !config.tip_accounts.is_empty(),
+            "Jito submission needs at least one tip account"
+        );
+        Ok(Self {
+            http: reqwest::Client::new(),
+            config,
+        })
+    }
+
+    pub fn tip_lamports(&self) -> u64 {
+        self.config.tip_lamports
+    }
+
+    /// A transfer of the configured tip from `payer`, to go in the same transaction. The
+    /// tip account is picked per call: tipping one account from every transaction would
+    /// contend on its write lock.
+    pub fn tip_instruction(&self, payer: &Pubkey) -> Instruction {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos() as usize)
+            .unwrap_or_default();
+        let tip_account = self.config.tip_accounts[nanos % self.config.tip_accounts.len()];
+        solana_system_interface::instruction::transfer(
+            payer,
+            &tip_account,
+            self.config.tip_lamports,
+        )
+    }
+
+    pub async fn send(&self, transaction: &Transaction) -> anyhow::Result<Signature> {
+        let encoded = STANDARD.encode(bincode::serialize(transaction)?);
+        let response: Value = self
+            .http
+            .post(format!(
+                "{}/api/v1/transactions",
+                self.config.block_engine_url
+            ))
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "sendTransaction",
+                "params": [encoded, { "encoding": "base64" }],
+            }))
+            .send()
+            .await
+            .context("Failed to reach Jito block engine")?
+            .json()
+            .await
+            .context("Invalid response from Jito block engine")?;
+
+        if let Some(error) = response.get("error") {
+            anyhow::bail!("Jito block engine rejected transaction: {}", error);
+        }
+        response
+            .get("result")
+            .and_then(Value::as_str)
+            .context("Jito block engine returned no signature")?
+            .parse()
+            .context("Jito block engine returned an invalid signature")
+    }
+}
@@ -0,0 +1,372 @@
+//! Every transaction a [`TxSender`](super::TxSender) sends, with what it was for and how
+//! it ended, persisted in sqlite. Read back on restart to settle sends that were still
+//! pending, and after an incident to see what the bot tried.
+
+use std::{sync::Mutex, time::Instant};
+
+use anyhow::Context;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use super::TxPriority;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS tx_journal (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    signature TEXT NOT NULL UNIQUE,
+    payer TEXT NOT NULL,
+    action TEXT NOT NULL,
+    market_id INTEGER,
+    reference_index INTEGER,
+    base_flow INTEGER,
+    quote_flow INTEGER,
+    base_amount INTEGER,
+    quote_amount INTEGER,
+    attempt INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT,
+    updated_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS tx_journal_market_time
+    ON tx_journal (market_id, timestamp);
+CREATE INDEX IF NOT EXISTS tx_journal_status ON tx_journal (status);
+";
+
+/// Columns added since the table was first created, for journals made before them.
+const ADDED_COLUMNS: [&str; 2] = ["base_amount", "quote_amount"];
+
+const COLUMNS: &str = "timestamp, signature, payer, action, market_id, reference_index, \
+                       base_flow, quote_flow, base_amount, quote_amount, attempt, status, error";
+
+/// What a transaction was sent to do.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxIntent {
+    /// The instruction, e.g. `update_liquidity_flows`.
+    pub action: String,
+    pub market_id: Option<u64>,
+    pub reference_index: Option<u64>,
+    pub base_flow: Option<u64>,
+    pub quote_flow: Option<u64>,
+    /// Lamports deposited or withdrawn, for sends that move tokens in or out of a position.
+    pub base_amount: Option<u64>,
+    pub quote_amount: Option<u64>,
+    /// The last slot the transaction can land in and still be right, e.g. the end of its
+    /// reference index's window. Not journaled.
+    pub valid_until_slot: Option<u64>,
+    /// Queue class, when not the one [`TxPriority::of`] would pick. Not journaled.
+    pub priority: Option<TxPriority>,
+    /// Past this the send is abandoned rather than retried; the sender's configured
+    /// deadline for the action when `None`. Not journaled.
+    pub deadline: Option<Instant>,
+}
+
+impl TxIntent {
+    pub fn new(action: &str, market_id: u64) -> Self {
+        Self {
+            action: action.to_string(),
+            market_id: Some(market_id),
+            ..Self::default()
+        }
+    }
+
+    /// For sends made without saying what they are for.
+    pub fn unlabeled() -> Self {
+        Self {
+            action: "unlabeled".to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn reference_index(mut self, reference_index: u64) -> Self {
+        self.reference_index = Some(reference_index);
+        self
+    }
+
+    pub fn flows(mut self, base_flow: u64, quote_flow: u64) -> Self {
+        self.base_flow = Some(base_flow);
+        self.quote_flow = Some(quote_flow);
+        self
+    }
+
+    pub fn amounts(mut self, base_amount: u64, quote_amount: u64) -> Self {
+        self.base_amount = Some(base_amount);
+        self.quote_amount = Some(quote_amount);
+        self
+    }
+
+    pub fn valid_until_slot(mut self, slot: u64) -> Self {
+        self.valid_until_slot = Some(slot);
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn priority(mut self, priority: TxPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalStatus {
+    /// Sent, with no outcome seen yet. Still pending after a restart means the bot went
+    /// down while waiting.
+    Pending,
+    Confirmed,
+    /// Landed and failed on-chain.
+    Failed,
+    /// The blockhash expired without the transaction landing.
+    Expired,
+}
+
+impl JournalStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Confirmed => "confirmed",
+            Self::Failed => "failed",
+            Self::Expired => "expired",
+        }
+    }
+
+    fn parse(value: &str) -> rusqlite::Result<Self> {
+        match value {
+            "pending" => Ok(Self::Pending),
+            "confirmed" => Ok(Self::Confirmed),
+            "failed" => Ok(Self::Failed),
+            "expired" => Ok(Self::Expired),
+            other => Err(rusqlite::Error::InvalidColumnType(
+                11,
+                format!("status `{other}`"),
+                rusqlite::types::Type::Text,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub timestamp: i64,
+    pub signature: String,
+    pub payer: String,
+    pub intent: TxIntent,
+    pub attempt: u32,
+    pub status: JournalStatus,
+    pub error: Option<String>,
+}
+
+pub struct SignatureJournal {
+    conn: Mutex<Connection>,
+}
+
+impl SignatureJournal {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open signature journal at {}", path))?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to initialize signature journal schema")?;
+        add_missing_columns(&conn).context("Failed to migrate signature journal schema")?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn record(&self, entry: &JournalEntry) -> anyhow::Result<()> {
+        self.conn()
+            .execute(
+                "INSERT INTO tx_journal (
+                    timestamp, signature, payer, action, market_id, reference_index,
+                    base_flow, quote_flow, base_amount, quote_amount, attempt, status, error,
+                    updated_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?1)",
+                params![
+                    entry.timestamp,
+                    entry.signature,
+                    entry.payer,
+                    entry.intent.action,
+                    entry.intent.market_id.map(|value| value as i64),
+                    entry.intent.reference_index.map(|value| value as i64),
+                    entry.intent.base_flow.map(|value| value as i64),
+                    entry.intent.quote_flow.map(|value| value as i64),
+                    entry.intent.base_amount.map(|value| value as i64),
+                    entry.intent.quote_amount.map(|value| value as i64),
+                    entry.attempt,
+                    entry.status.as_str(),
+                    entry.error,
+                ],
+            )
+            .context("Failed to record journal entry")?;
+        Ok(())
+    }
+
+    pub fn set_status(
+        &self,
+        signature: &str,
+        status: JournalStatus,
+        error: Option<&str>,
+        timestamp: i64,
+    ) -> anyhow::Result<()> {
+        self.conn()
+            .execute(
+                "UPDATE tx_journal SET status = ?2, error = ?3, updated_at = ?4
+                 WHERE signature = ?1",
+                params![signature, status.as_str(), error, timestamp],
+            )
+            .context("Failed to update journal entry")?;
+        Ok(())
+    }
+
+    /// Entries still waiting on an outcome, oldest first.
+    pub fn pending(&self) -> anyhow::Result<Vec<JournalEntry>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM tx_journal WHERE status = ?1 ORDER BY timestamp ASC, id ASC"
+        ))?;
+        let rows = statement.query_map(params![JournalStatus::Pending.as_str()], read_row)?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read pending journal entries")
+    }
+
+    /// The most recent send for `market_id`: "did my last update land?"
+    pub fn latest(&self, market_id: u64) -> anyhow::Result<Option<JournalEntry>> {
+        self.conn()
+            .query_row(
+                &format!(
+                    "SELECT {COLUMNS} FROM tx_journal WHERE market_id = ?1
+                     ORDER BY timestamp DESC, id DESC LIMIT 1"
+                ),
+                params![market_id as i64],
+                read_row,
+            )
+            .optional()
+            .context("Failed to read latest journal entry")
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn add_missing_columns(conn: &Connection) -> rusqlite::Result<()> {
+    let mut statement = conn.prepare("SELECT name FROM pragma_table_info('tx_journal')")?;
+    let existing = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for column in ADDED_COLUMNS {
+        if !existing.iter().any(|name| name == column) {
+            conn.execute_batch(&format!(
+                "ALTER TABLE tx_journal ADD COLUMN {column} INTEGER"
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn read_row(row: &Row<'_>) -> rusqlite::Result<JournalEntry> {
+    let optional_u64 = |index: usize| -> rusqlite::Result<Option<u64>> {
+        Ok(row.get::<_, Option<i64>>(index)?.map(|value| value as u64))
+    };
+    Ok(JournalEntry {
+        timestamp: row.get(0)?,
+        signature: row.get(1)?,
+        payer: row.get(2)?,
+        intent: TxIntent {
+            action: row.get(3)?,
+            market_id: optional_u64(4)?,
+            reference_index: optional_u64(5)?,
+            base_flow: optional_u64(6)?,
+            quote_flow: optional_u64(7)?,
+            base_amount: optional_u64(8)?,
+            quote_amount: optional_u64(9)?,
+            valid_until_slot: None,
+            priority: None,
+            deadline: None,
+        },
+        attempt: row.get(10)?,
+        status: JournalStatus::parse(&row.get::<_, String>(11)?)?,
+        error: row.get(12)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_sends_until_they_settle() {
+        let journal = SignatureJournal::open(":memory:").unwrap();
+        let entry = |timestamp: i64, signature: &str, market_id: u64| JournalEntry {
+            timestamp,
+            signature: signature.to_string(),
+            payer: "lp".to_string(),
+            intent: TxIntent::new("update_liquidity_flows", market_id)
+                .reference_index(42)
+                .flows(1_000, 150_000),
+            attempt: 1,
+            status: JournalStatus::Pending,
+            error: None,
+        };
+
+        journal.record(&entry(10, "first", 1)).unwrap();
+        journal.record(&entry(20, "second", 1)).unwrap();
+        journal.record(&entry(30, "other", 2)).unwrap();
+        journal
+            .set_status("first", JournalStatus::Expired, None, 15)
+            .unwrap();
+        journal
+            .set_status("other", JournalStatus::Failed, Some("custom 6013"), 35)
+            .unwrap();
+
+        assert_eq!(journal.pending().unwrap(), vec![entry(20, "second", 1)]);
+        assert_eq!(journal.latest(1).unwrap(), Some(entry(20, "second", 1)));
+        assert_eq!(
+            journal.latest(2).unwrap(),
+            Some(JournalEntry {
+                status: JournalStatus::Failed,
+                error: Some("custom 6013".to_string()),
+                ..entry(30, "other", 2)
+            })
+        );
+        assert_eq!(journal.latest(3).unwrap(), None);
+    }
+
+    #[test]
+    fn keeps_deposit_amounts_apart_from_flows_in_an_older_journal() {
+        let path = std::env::temp_dir().join(format!("twob-journal-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        // The table as it was before the amount columns.
+        let old_schema = SCHEMA
+            .replace("base_amount INTEGER,", "")
+            .replace("quote_amount INTEGER,", "");
+        Connection::open(path)
+            .unwrap()
+            .execute_batch(&old_schema)
+            .unwrap();
+
+        let journal = SignatureJournal::open(path).unwrap();
+        let entry = JournalEntry {
+            timestamp: 10,
+            signature: "deposit".to_string(),
+            payer: "lp".to_string(),
+            intent: TxIntent::new("add_liquidity", 1)
+                .reference_index(42)
+                .amounts(5_000_000, 750_000_000),
+            attempt: 1,
+            status: JournalStatus::Pending,
+            error: None,
+        };
+        journal.record(&entry).unwrap();
+
+        let latest = journal.latest(1).unwrap().unwrap();
+        assert_eq!(latest, entry);
+        assert_eq!(latest.intent.base_flow, None);
+        drop(journal);
+        std::fs::remove_file(path).unwrap();
+    }
+}
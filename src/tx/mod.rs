@@ -0,0 +1,2446 @@
+//! Transaction submission shared by every execute helper.
+//!
+//! [`TxSender`] owns what used to be repeated at each `program.request().send()` call: it
+//! keeps a recent blockhash cached, prepends the compute-budget instructions the
+//! [`TxSenderConfig`] asks for (pricing compute from the market's recent fees if told to),
+//! simulates before sending, confirms by polling signature status, and resends a
+//! transaction that isn't confirmed yet. It is re-signed with a fresh blockhash only once
+//! the block height shows the last one expired: until then it may still land, and a
+//! second signature could land with it. Each transaction can also be broadcast to extra
+//! RPC endpoints, so one provider's slow forwarding doesn't hold it back. A transaction that lands and fails on-chain, or fails simulation, is not
+//! retried: resending the same instructions would fail the same way.
+//!
+//! With a [`journal_path`](TxSenderConfig::journal_path) set, every signature sent is
+//! written to a [`SignatureJournal`] with its [`TxIntent`] and, once known, its outcome.
+//! The same goes to the [`audit`](crate::audit) log, when there is one.
+//!
+//! A [`min_sol_balance`](TxSenderConfig::min_sol_balance) keeps the payer able to unwind:
+//! below it only [critical](SendOptions::critical) sends, i.e. stops and quote pulls, go
+//! out, so the SOL left pays for those alone.
+//!
+//! Built with the `tpu` feature and [`tpu`](TxSenderConfig::tpu) set, sends marked
+//! [`tpu`](SendOptions::tpu) also go over QUIC straight to the current and upcoming
+//! leaders, skipping the RPC node's forwarding.
+//!
+//! An intent with a [`valid_until_slot`](TxIntent::valid_until_slot) is held back when the
+//! leader schedule says it would land too late, e.g. past the end of the reference index
+//! window it was built for, where it would fail with the wrong exits account.
+//! [`send_for_window`](TxSender::send_for_window) rebuilds such a send for the next
+//! window instead of giving up on it. An intent's [`deadline`](TxIntent::deadline), or
+//! its action's entry in [`deadlines`](TxSenderConfig::deadlines), stops retries once
+//! the send is no longer worth making.
+//!
+//! With a [`jito`](TxSenderConfig::jito) block engine configured, sends marked
+//! [`private`](SendOptions::private) carry a tip and go to the block engine only.
+//!
+//! With a [`nonce_pool_size`](TxSenderConfig::nonce_pool_size),
+//! [`durable`](SendOptions::durable) sends are signed over a [`NonceManager`] nonce
+//! instead of a blockhash, so they stay valid however long the RPC takes to come back.
+//!
+//! The payer signs through a [`TxSigner`], which may be a local keypair, a Ledger or a
+//! remote signing service.
+//!
+//! In [`dry_run`](TxSenderConfig::dry_run) mode nothing is sent: each transaction is
+//! simulated and logged as `tx_dry_run`, and the send fails with the simulation's error, or
+//! with [`DryRun`] if it would have gone through, so nothing counts it as sent.
+//!
+//! Each send's cost is estimated before it goes out (see [`CostEstimate`]) and, with a
+//! [`daily_fee_budget_lamports`](TxSenderConfig::daily_fee_budget_lamports) set, refused
+//! once the day's fees would exceed it, critical sends excepted.
+//!
+//! With a [`queue`](TxSenderConfig::queue) and [`start_queue`](TxSender::start_queue)
+//! called, sends wait their turn in a [`TxQueue`] by [`TxPriority`] instead of going out
+//! as soon as they are made.
+
+pub mod cost;
+pub mod jito;
+pub mod journal;
+pub mod nonce;
+pub mod queue;
+pub mod signer;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anchor_client::{
+    Program,
+    solana_sdk::{
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        compute_budget::ComputeBudgetInstruction,
+        hash::Hash,
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::{Keypair, Signature},
+        signer::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+};
+use anyhow::Context;
+use futures::future::join_all;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_types::{
+    config::{
+        RpcSendTransactionConfig, RpcSimulateTransactionAccountsConfig,
+        RpcSimulateTransactionConfig,
+    },
+    response::RpcSimulateTransactionResult,
+};
+use solana_transaction_status_client_types::TransactionStatus;
+use tokio::{sync::Mutex, time::sleep};
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::{
+    ARRAY_LENGTH, ProgramPayer,
+    alerts::{AlertKind, Alerter},
+    audit::{self, AuditLog, AuditRecord},
+    config::env_var,
+    error::{ProgramErrorCode, program_error},
+    rate_limit, reference_window_last_slot,
+    reporting::Reporting,
+};
+
+#[cfg(feature = "tpu")]
+use solana_client::{nonblocking::tpu_client::TpuClient, tpu_client::TpuClientConfig};
+#[cfg(feature = "tpu")]
+use solana_quic_client::{QuicConfig, QuicConnectionManager, QuicPool};
+
+#[cfg(feature = "tpu")]
+type QuicTpuClient = TpuClient<QuicPool, QuicConnectionManager, QuicConfig>;
+
+pub use cost::{CostEstimate, FeeBudget, FeeBudgetExceeded};
+pub use jito::{JitoConfig, JitoRelay};
+pub use journal::{JournalEntry, JournalStatus, SignatureJournal, TxIntent};
+pub use nonce::{NonceLease, NonceManager};
+pub use queue::{Superseded, TxPriority, TxQueue, TxQueueConfig};
+#[cfg(feature = "ledger")]
+pub use signer::LedgerSigner;
+pub use signer::{RemoteSigner, SignerConfig, TxSigner, keypair_from_env, keypair_from_file};
+
+/// How often signature status is polled while waiting for confirmation.
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The most compute units a transaction may request.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// `getMultipleAccounts` takes at most this many accounts.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// `getRecentPrioritizationFees` takes at most this many accounts.
+const MAX_PRIORITIZATION_FEE_ACCOUNTS: usize = 128;
+
+/// A journaled send with no status this long after it was sent is taken to have expired.
+const JOURNAL_EXPIRY_SECS: i64 = 120;
+
+/// Consecutive slots each leader is scheduled for.
+const LEADER_ROTATION_SLOTS: u64 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxSenderConfig {
+    /// Priority fee, in micro-lamports per compute unit. `None` prices from recent fees when
+    /// `priority_fee_percentile` is set, and otherwise sends without one.
+    pub compute_unit_price: Option<u64>,
+    /// Price each transaction at this percentile of the recent prioritization fees paid to
+    /// write the accounts it writes, so fees follow contention on this market.
+    pub priority_fee_percentile: Option<u8>,
+    /// Never pay more than this, in micro-lamports per compute unit, when pricing from
+    /// recent fees.
+    pub max_compute_unit_price: u64,
+    /// Compute-unit limit requested per transaction. `None` estimates one when
+    /// `compute_unit_margin_pct` is set, and otherwise keeps the runtime default.
+    pub compute_unit_limit: Option<u32>,
+    /// Estimate each transaction's compute-unit limit by simulating it, and request the
+    /// units it consumed plus this percentage. The priority fee is charged on the requested
+    /// units, so a tight limit makes it cheaper. Estimating simulates even when `simulate`
+    /// is off.
+    pub compute_unit_margin_pct: Option<u32>,
+    /// Commitment for blockhashes, simulation, and the confirmation a send waits for.
+    pub commitment: CommitmentConfig,
+    /// Simulate each transaction before sending it, and skip the RPC's own preflight.
+    pub simulate: bool,
+    /// Attempts per transaction. One re-signs with a fresh blockhash only once the last
+    /// one's has expired; until then it resends the same transaction.
+    pub max_attempts: u32,
+    /// Pause between attempts.
+    pub retry_delay: Duration,
+    /// Give up waiting for a confirmation after this long, even if the blockhash is valid.
+    pub confirm_timeout: Duration,
+    /// Refetch the cached blockhash once it is this old.
+    pub blockhash_max_age: Duration,
+    /// Resend a pending transaction every this many blocks until it confirms or its
+    /// blockhash expires; 0 sends it once per attempt.
+    pub rebroadcast_interval_blocks: u64,
+    /// Submission options for senders that don't override them.
+    pub send_options: SendOptions,
+    /// Endpoints every transaction is also sent to, alongside the main RPC. A send succeeds
+    /// if any endpoint accepts it.
+    pub broadcast_rpc_urls: Vec<String>,
+    /// Sqlite file every sent signature is journaled to.
+    pub journal_path: Option<String>,
+    /// Payer balance, in lamports, below which only critical sends are made.
+    pub min_sol_balance: Option<u64>,
+    /// Connect a TPU client (see [`TxSender::connect_tpu`]) for sends that ask for it.
+    pub tpu: bool,
+    /// How many upcoming leaders, in slots, a TPU send is fanned out to.
+    pub tpu_fanout_slots: u64,
+    /// Slots of slack kept before an intent's `valid_until_slot`, on top of the expected
+    /// landing slot.
+    pub valid_until_guard_slots: u64,
+    /// Block engine for [`private`](SendOptions::private) sends.
+    pub jito: Option<JitoConfig>,
+    /// Durable nonce accounts kept for [`durable`](SendOptions::durable) sends and
+    /// pre-signed transactions; 0 keeps none.
+    pub nonce_pool_size: usize,
+    /// Send through a prioritized queue (see [`TxSender::start_queue`]).
+    pub queue: Option<TxQueueConfig>,
+    /// How long a send for each action, e.g. `update_liquidity_flows`, may take from the
+    /// moment it is made, queueing included, before it is abandoned. Actions not listed
+    /// retry until their attempts run out.
+    pub deadlines: HashMap<String, Duration>,
+    /// How many times [`send_for_window`](TxSender::send_for_window) rebuilds a send for
+    /// a later reference index before giving up.
+    pub max_rebuilds: u32,
+    /// Lamports of fees (base, priority and tips, not rent) non-critical sends may spend
+    /// per UTC day.
+    pub daily_fee_budget_lamports: Option<u64>,
+    /// Include the rent of accounts a send creates in its cost estimate. Costs a
+    /// `getMultipleAccounts` call per send, and a simulation when one is created.
+    pub estimate_rent: bool,
+    /// Simulate and log each send instead of submitting it.
+    pub dry_run: bool,
+}
+
+impl Default for TxSenderConfig {
+    fn default() -> Self {
+        Self {
+            compute_unit_price: None,
+            priority_fee_percentile: None,
+            max_compute_unit_price: 1_000_000,
+            compute_unit_limit: None,
+            compute_unit_margin_pct: Some(20),
+            commitment: CommitmentConfig::confirmed(),
+            simulate: true,
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(500),
+            confirm_timeout: Duration::from_secs(60),
+            blockhash_max_age: Duration::from_secs(20),
+            rebroadcast_interval_blocks: 10,
+            send_options: SendOptions::default(),
+            broadcast_rpc_urls: Vec::new(),
+            journal_path: None,
+            min_sol_balance: None,
+            tpu: false,
+            tpu_fanout_slots: 12,
+            valid_until_guard_slots: 2,
+            jito: None,
+            nonce_pool_size: 0,
+            queue: None,
+            deadlines: HashMap::new(),
+            max_rebuilds: 2,
+            daily_fee_budget_lamports: None,
+            estimate_rent: false,
+            dry_run: false,
+        }
+    }
+}
+
+impl TxSenderConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let defaults = Self::default();
+        let optional = |name: &str| -> anyhow::Result<Option<u64>> {
+            env_var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<u64>()
+                        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        };
+
+        let compute_unit_limit = optional("TX_COMPUTE_UNIT_LIMIT")?
+            .map(u32::try_from)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Invalid TX_COMPUTE_UNIT_LIMIT: {}", e))?;
+        let compute_unit_margin_pct = match env_var("TX_COMPUTE_UNIT_MARGIN_PCT") {
+            // Empty disables estimation.
+            Ok(value) if value.trim().is_empty() => None,
+            Ok(value) => Some(
+                value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|e| anyhow::anyhow!("Invalid TX_COMPUTE_UNIT_MARGIN_PCT: {}", e))?,
+            ),
+            Err(_) => defaults.compute_unit_margin_pct,
+        };
+        let simulate = match env_var("TX_SIMULATE") {
+            Ok(value) => value
+                .trim()
+                .parse::<bool>()
+                .map_err(|e| anyhow::anyhow!("Invalid TX_SIMULATE: {}", e))?,
+            Err(_) => defaults.simulate,
+        };
+        let priority_fee_percentile = optional("TX_PRIORITY_FEE_PERCENTILE")?
+            .map(|percentile| {
+                if percentile > 100 {
+                    anyhow::bail!("TX_PRIORITY_FEE_PERCENTILE must be at most 100");
+                }
+                Ok(percentile as u8)
+            })
+            .transpose()?;
+        let skip_preflight = match env_var("TX_SKIP_PREFLIGHT") {
+            Ok(value) => value
+                .trim()
+                .parse::<bool>()
+                .map_err(|e| anyhow::anyhow!("Invalid TX_SKIP_PREFLIGHT: {}", e))?,
+            Err(_) => defaults.send_options.skip_preflight,
+        };
+        let preflight_commitment = match env_var("TX_PREFLIGHT_COMMITMENT") {
+            Ok(value) if !value.trim().is_empty() => Some(
+                value
+                    .trim()
+                    .parse::<CommitmentLevel>()
+                    .map_err(|e| anyhow::anyhow!("Invalid TX_PREFLIGHT_COMMITMENT: {}", e))?,
+            ),
+            _ => defaults.send_options.preflight_commitment,
+        };
+        let private = match env_var("TX_PRIVATE") {
+            Ok(value) => value
+                .trim()
+                .parse::<bool>()
+                .map_err(|e| anyhow::anyhow!("Invalid TX_PRIVATE: {}", e))?,
+            Err(_) => defaults.send_options.private,
+        };
+        let tpu = match env_var("TX_TPU") {
+            Ok(value) => value
+                .trim()
+                .parse::<bool>()
+                .map_err(|e| anyhow::anyhow!("Invalid TX_TPU: {}", e))?,
+            Err(_) => defaults.tpu,
+        };
+        let estimate_rent = match env_var("TX_ESTIMATE_RENT") {
+            Ok(value) => value
+                .trim()
+                .parse::<bool>()
+                .map_err(|e| anyhow::anyhow!("Invalid TX_ESTIMATE_RENT: {}", e))?,
+            Err(_) => defaults.estimate_rent,
+        };
+        let dry_run = match env_var("TX_DRY_RUN") {
+            Ok(value) if !value.trim().is_empty() => crate::telemetry::parse_bool(&value)
+                .map_err(|e| anyhow::anyhow!("Invalid TX_DRY_RUN: {}", e))?,
+            _ => defaults.dry_run,
+        };
+        let max_attempts = optional("TX_MAX_ATTEMPTS")?
+            .map(|attempts| attempts.max(1) as u32)
+            .unwrap_or(defaults.max_attempts);
+
+        Ok(Self {
+            compute_unit_price: optional("TX_COMPUTE_UNIT_PRICE")?.filter(|&price| price > 0),
+            priority_fee_percentile,
+            max_compute_unit_price: optional("TX_MAX_COMPUTE_UNIT_PRICE")?
+                .unwrap_or(defaults.max_compute_unit_price),
+            compute_unit_limit,
+            compute_unit_margin_pct,
+            commitment: crate::commitment_from_env("TX_COMMITMENT")?,
+            simulate,
+            max_attempts,
+            retry_delay: optional("TX_RETRY_DELAY_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.retry_delay),
+            confirm_timeout: optional("TX_CONFIRM_TIMEOUT_SECS")?
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.confirm_timeout),
+            blockhash_max_age: optional("TX_BLOCKHASH_MAX_AGE_SECS")?
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.blockhash_max_age),
+            rebroadcast_interval_blocks: optional("TX_REBROADCAST_INTERVAL_BLOCKS")?
+                .unwrap_or(defaults.rebroadcast_interval_blocks),
+            send_options: SendOptions {
+                skip_preflight,
+                preflight_commitment,
+                max_retries: optional("TX_MAX_RETRIES")?.map(|retries| retries as usize),
+                private,
+                ..defaults.send_options
+            },
+            broadcast_rpc_urls: env_var("TX_BROADCAST_RPC_URLS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect(),
+            journal_path: env_var("TX_JOURNAL_PATH")
+                .ok()
+                .filter(|path| !path.trim().is_empty()),
+            min_sol_balance: optional("TX_MIN_SOL_BALANCE_LAMPORTS")?,
+            tpu,
+            tpu_fanout_slots: optional("TX_TPU_FANOUT_SLOTS")?.unwrap_or(defaults.tpu_fanout_slots),
+            valid_until_guard_slots: optional("TX_VALID_UNTIL_GUARD_SLOTS")?
+                .unwrap_or(defaults.valid_until_guard_slots),
+            jito: JitoConfig::from_env()?,
+            nonce_pool_size: optional("TX_NONCE_POOL_SIZE")?.unwrap_or(0) as usize,
+            queue: TxQueueConfig::from_env()?,
+            deadlines: parse_deadlines(&env_var("TX_DEADLINES_MS").unwrap_or_default())?,
+            max_rebuilds: optional("TX_MAX_REBUILDS")?
+                .map(|rebuilds| rebuilds as u32)
+                .unwrap_or(defaults.max_rebuilds),
+            daily_fee_budget_lamports: optional("TX_DAILY_FEE_BUDGET_LAMPORTS")?,
+            estimate_rent,
+            dry_run,
+        })
+    }
+
+    /// Compute-budget instructions to put in front of a transaction's own.
+    pub fn compute_budget_instructions(&self) -> Vec<Instruction> {
+        compute_budget_instructions(self.compute_unit_limit, self.compute_unit_price)
+    }
+}
+
+/// Per-action deadlines from `action=milliseconds` pairs separated by commas, e.g.
+/// `update_liquidity_flows=20000,submit_order=45000`.
+fn parse_deadlines(value: &str) -> anyhow::Result<HashMap<String, Duration>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (action, millis) = entry
+                .split_once('=')
+                .with_context(|| format!("Invalid TX_DEADLINES_MS entry {}", entry))?;
+            let millis = millis
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("Invalid TX_DEADLINES_MS entry {}: {}", entry, e))?;
+            Ok((action.trim().to_string(), Duration::from_millis(millis)))
+        })
+        .collect()
+}
+
+/// Instructions requesting `limit` compute units at `price` micro-lamports each.
+pub fn compute_budget_instructions(limit: Option<u32>, price: Option<u64>) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    if let Some(limit) = limit {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(limit));
+    }
+    if let Some(price) = price {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+    }
+    instructions
+}
+
+/// How a transaction is submitted, separate from what is in it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SendOptions {
+    /// Send without simulating first: neither the sender's own simulation (and compute-unit
+    /// estimate) nor the RPC's preflight. Saves a round trip at the cost of paying for
+    /// transactions that fail.
+    pub skip_preflight: bool,
+    /// Commitment the RPC's preflight runs at; the sender's commitment when `None`.
+    pub preflight_commitment: Option<CommitmentLevel>,
+    /// How often the RPC node retries forwarding the transaction; `None` leaves it to the
+    /// node.
+    pub max_retries: Option<usize>,
+    /// Send even when the payer is below
+    /// [`min_sol_balance`](TxSenderConfig::min_sol_balance).
+    pub critical: bool,
+    /// Also send straight to the leaders when the sender has a TPU client.
+    pub tpu: bool,
+    /// Send through the Jito block engine alone, with a tip, when the sender has one.
+    /// Keeps a flow update's direction, and with it the bot's inventory, out of sight
+    /// until it lands.
+    pub private: bool,
+    /// Sign over a durable nonce, when the sender has any, so the transaction can't
+    /// expire while the RPC is down.
+    pub durable: bool,
+}
+
+impl SendOptions {
+    /// For stops and quote pulls, where landing a slot sooner beats catching a failure
+    /// before paying for it, and which may spend the SOL held back below the floor.
+    pub fn urgent() -> Self {
+        Self {
+            skip_preflight: true,
+            critical: true,
+            tpu: true,
+            durable: true,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedBlockhash {
+    hash: Hash,
+    last_valid_block_height: u64,
+    fetched_at: Instant,
+}
+
+/// The block height passed the last attempt's blockhash without the transaction confirming:
+/// it was dropped, not rejected, and may be worth sending again later. Callers that need to
+/// tell the two apart downcast the send error to this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxExpired {
+    /// The last attempt's signature.
+    pub signature: Signature,
+    pub attempts: u32,
+}
+
+impl fmt::Display for TxExpired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "transaction {} expired before confirming after {} attempts",
+            self.signature, self.attempts
+        )
+    }
+}
+
+impl std::error::Error for TxExpired {}
+
+/// The attempts ran out with the transaction neither confirmed nor expired: it may still
+/// land, so sending the same instructions again could make them land twice. It is left
+/// pending in the journal, for [`reconcile_journal`](TxSender::reconcile_journal) to settle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxUnconfirmed {
+    pub signature: Signature,
+    pub attempts: u32,
+    /// The block height it can land until; `None` over a durable nonce, which it can land
+    /// on until the nonce advances.
+    pub last_valid_block_height: Option<u64>,
+}
+
+impl fmt::Display for TxUnconfirmed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "transaction {} not confirmed after {} attempts",
+            self.signature, self.attempts
+        )?;
+        match self.last_valid_block_height {
+            Some(height) => write!(f, "; it may still land until block height {height}"),
+            None => write!(f, "; it may still land until its nonce advances"),
+        }
+    }
+}
+
+impl std::error::Error for TxUnconfirmed {}
+
+/// A transaction failed simulation (no `signature`) or landed and failed on-chain. Not
+/// retried: the same instructions would fail the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionFailed {
+    pub signature: Option<Signature>,
+    pub error: TransactionError,
+    /// The simulation's program logs.
+    pub logs: Option<Vec<String>>,
+}
+
+impl TransactionFailed {
+    /// The twob error behind the failure, if that's what it was.
+    pub fn program_error(&self) -> Option<ProgramErrorCode> {
+        program_error(&self.error, self.logs.as_deref())
+    }
+}
+
+impl fmt::Display for TransactionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.signature, self.program_error()) {
+            (Some(signature), Some(error)) => {
+                write!(f, "transaction {} failed: {}", signature, error)
+            }
+            (Some(signature), None) => {
+                write!(f, "transaction {} failed: {:?}", signature, self.error)
+            }
+            (None, Some(error)) => write!(
+                f,
+                "Transaction simulation failed: {}. logs={:?}",
+                error, self.logs
+            ),
+            (None, None) => write!(
+                f,
+                "Transaction simulation failed. err={:?} logs={:?}",
+                self.error, self.logs
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TransactionFailed {}
+
+/// A non-critical send was refused because the payer's SOL is below
+/// [`min_sol_balance`](TxSenderConfig::min_sol_balance).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeBalanceLow {
+    pub balance: u64,
+    pub floor: u64,
+}
+
+impl fmt::Display for FeeBalanceLow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "payer balance {} lamports is below the {} lamport floor; holding it for stops",
+            self.balance, self.floor
+        )
+    }
+}
+
+impl std::error::Error for FeeBalanceLow {}
+
+/// A send was held back because it would not land by its intent's
+/// [`valid_until_slot`](TxIntent::valid_until_slot). Rebuild it for the next window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LandsTooLate {
+    pub slot: u64,
+    pub expected_landing_slot: u64,
+    pub valid_until_slot: u64,
+}
+
+impl fmt::Display for LandsTooLate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sent at slot {} the transaction would land by slot {}, after its last valid slot {}",
+            self.slot, self.expected_landing_slot, self.valid_until_slot
+        )
+    }
+}
+
+impl std::error::Error for LandsTooLate {}
+
+/// A send was abandoned because its intent's [`deadline`](TxIntent::deadline) passed
+/// before it confirmed. Whatever it was for has moved on; build a fresh one if it is still
+/// wanted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlinePassed {
+    pub action: String,
+    /// How long after the deadline the send was abandoned.
+    pub overdue: Duration,
+}
+
+impl fmt::Display for DeadlinePassed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} send abandoned {}ms past its deadline",
+            self.action,
+            self.overdue.as_millis()
+        )
+    }
+}
+
+impl std::error::Error for DeadlinePassed {}
+
+/// A send made in [`dry_run`](TxSenderConfig::dry_run) mode simulated cleanly and was not
+/// sent. There is no signature: nothing landed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRun {
+    pub action: String,
+    pub units_consumed: Option<u64>,
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dry run: {} simulated and not sent", self.action)
+    }
+}
+
+impl std::error::Error for DryRun {}
+
+/// Whether `error`, or one behind it, is a [`DryRun`] rather than a real failure.
+pub fn is_dry_run(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.is::<DryRun>()
+            || cause
+                .downcast_ref::<crate::TwobError>()
+                .is_some_and(crate::TwobError::is_dry_run)
+    })
+}
+
+/// The result of one send attempt that didn't fail outright.
+enum Attempt {
+    Confirmed {
+        slot: u64,
+    },
+    /// The block height passed the blockhash's last valid one without the transaction
+    /// landing: it never will.
+    Expired,
+    /// The wait timed out with the transaction neither landed nor expired: it may still.
+    Pending,
+}
+
+/// Signs, submits and confirms transactions paid for by one keypair.
+pub struct TxSender {
+    rpc: Arc<RpcClient>,
+    /// The extra endpoints from [`TxSenderConfig::broadcast_rpc_urls`].
+    broadcast_rpcs: Arc<Vec<RpcClient>>,
+    payer: Arc<dyn TxSigner>,
+    config: Arc<TxSenderConfig>,
+    options: SendOptions,
+    blockhash: Arc<Mutex<Option<CachedBlockhash>>>,
+    journal: Option<Arc<SignatureJournal>>,
+    alerter: Option<Alerter>,
+    reporting: Reporting,
+    #[cfg(feature = "tpu")]
+    tpu: Option<Arc<QuicTpuClient>>,
+    jito: Option<Arc<JitoRelay>>,
+    nonces: Option<Arc<NonceManager>>,
+    queue: Option<TxQueue>,
+    fee_budget: Option<Arc<FeeBudget>>,
+}
+
+impl TxSender {
+    pub fn new(
+        rpc: RpcClient,
+        payer: Arc<dyn TxSigner>,
+        config: TxSenderConfig,
+    ) -> anyhow::Result<Self> {
+        let journal = config
+            .journal_path
+            .as_deref()
+            .map(SignatureJournal::open)
+            .transpose()?
+            .map(Arc::new);
+        let broadcast_rpcs = config
+            .broadcast_rpc_urls
+            .iter()
+            .map(|url| rate_limit::rpc_client(url, config.commitment))
+            .collect();
+        let jito = config
+            .jito
+            .clone()
+            .map(JitoRelay::new)
+            .transpose()?
+            .map(Arc::new);
+        let fee_budget = config
+            .daily_fee_budget_lamports
+            .map(|lamports| Arc::new(FeeBudget::new(lamports)));
+        Ok(Self {
+            rpc: Arc::new(rpc),
+            broadcast_rpcs: Arc::new(broadcast_rpcs),
+            payer,
+            options: config.send_options,
+            config: Arc::new(config),
+            blockhash: Arc::new(Mutex::new(None)),
+            journal,
+            alerter: None,
+            reporting: Reporting {
+                audit_log: AuditLog::from_env().map(Arc::new),
+                ..Reporting::default()
+            },
+            #[cfg(feature = "tpu")]
+            tpu: None,
+            jito,
+            nonces: None,
+            queue: None,
+            fee_budget,
+        })
+    }
+
+    /// A sender sharing this one's endpoints, payer, blockhash cache and journal that
+    /// submits with `options`, e.g. [`SendOptions::urgent`] for a stop.
+    pub fn with_options(&self, options: SendOptions) -> Self {
+        Self {
+            rpc: self.rpc.clone(),
+            broadcast_rpcs: self.broadcast_rpcs.clone(),
+            payer: self.payer.clone(),
+            config: self.config.clone(),
+            options,
+            blockhash: self.blockhash.clone(),
+            journal: self.journal.clone(),
+            alerter: self.alerter.clone(),
+            reporting: self.reporting.clone(),
+            #[cfg(feature = "tpu")]
+            tpu: self.tpu.clone(),
+            jito: self.jito.clone(),
+            nonces: self.nonces.clone(),
+            queue: self.queue.clone(),
+            fee_budget: self.fee_budget.clone(),
+        }
+    }
+
+    /// Start the send loop when a [`queue`](TxSenderConfig::queue) is configured. From
+    /// then on this sender, and every one made from it, queues its sends for the loop.
+    /// Call after the other setup, which the loop's own copy of the sender keeps.
+    pub fn start_queue(mut self) -> Self {
+        if let Some(config) = self.config.queue.clone() {
+            self.queue = Some(TxQueue::start(self.with_options(self.options), config));
+        }
+        self
+    }
+
+    /// Set up the [`nonce_pool_size`](TxSenderConfig::nonce_pool_size) nonce accounts,
+    /// creating any that don't exist yet.
+    pub async fn prepare_nonces(mut self) -> anyhow::Result<Self> {
+        if self.config.nonce_pool_size == 0 {
+            return Ok(self);
+        }
+        let nonces = NonceManager::new(self.payer.pubkey(), self.config.nonce_pool_size)?;
+        nonces.create_missing(&self).await?;
+        info!(
+            event.name = "tx_nonce_pool_ready",
+            tx.nonce_accounts = nonces.accounts().len(),
+        );
+        self.nonces = Some(Arc::new(nonces));
+        Ok(self)
+    }
+
+    /// Start a TPU client tracking the leader schedule over `websocket_url`, if
+    /// [`tpu`](TxSenderConfig::tpu) is set.
+    #[cfg(feature = "tpu")]
+    pub async fn connect_tpu(mut self, websocket_url: &str) -> anyhow::Result<Self> {
+        if !self.config.tpu {
+            return Ok(self);
+        }
+        let client = QuicTpuClient::new(
+            "twob-tx-sender",
+            self.rpc.clone(),
+            websocket_url,
+            TpuClientConfig {
+                fanout_slots: self.config.tpu_fanout_slots,
+            },
+        )
+        .await
+        .context("Failed to start TPU client")?;
+        info!(
+            event.name = "tx_tpu_connected",
+            tx.fanout_slots = self.config.tpu_fanout_slots,
+        );
+        self.tpu = Some(Arc::new(client));
+        Ok(self)
+    }
+
+    /// Without the `tpu` feature there is no TPU client to start; a configured one is
+    /// only warned about.
+    #[cfg(not(feature = "tpu"))]
+    pub async fn connect_tpu(self, _websocket_url: &str) -> anyhow::Result<Self> {
+        if self.config.tpu {
+            warn!(
+                event.name = "tx_tpu_unavailable",
+                "TX_TPU is set but this binary was built without the `tpu` feature"
+            );
+        }
+        Ok(self)
+    }
+
+    /// Alert through `alerter` when the payer drops below the fee floor.
+    pub fn with_alerter(mut self, alerter: Alerter) -> Self {
+        self.alerter = Some(alerter);
+        self
+    }
+
+    /// Publish sends, and fire the webhooks of the instructions sent, through the bot's
+    /// `reporting`.
+    pub fn with_reporting(mut self, reporting: Reporting) -> Self {
+        self.reporting = reporting;
+        self
+    }
+
+    /// A sender on `program`'s RPC endpoint, paid for by `payer`, which must be the
+    /// payer `program` builds instructions for.
+    pub fn for_program(
+        program: &Program<ProgramPayer>,
+        payer: Arc<dyn TxSigner>,
+        config: TxSenderConfig,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            payer.pubkey() == program.payer(),
+            "Signer {} is not the program client's payer {}",
+            payer.pubkey(),
+            program.payer()
+        );
+        let rpc = program.rpc();
+        Self::new(
+            rate_limit::rpc_client(&rpc.url(), rpc.commitment()),
+            payer,
+            config,
+        )
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.payer.pubkey()
+    }
+
+    pub fn config(&self) -> &TxSenderConfig {
+        &self.config
+    }
+
+    /// The options this sender submits with.
+    pub fn options(&self) -> SendOptions {
+        self.options
+    }
+
+    pub fn journal(&self) -> Option<&SignatureJournal> {
+        self.journal.as_deref()
+    }
+
+    pub fn nonces(&self) -> Option<&NonceManager> {
+        self.nonces.as_deref()
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    pub fn fee_budget(&self) -> Option<&FeeBudget> {
+        self.fee_budget.as_deref()
+    }
+
+    /// What the bot this sender sends for reports through.
+    pub fn reporting(&self) -> &Reporting {
+        &self.reporting
+    }
+
+    /// What sending `instructions` now would cost the payer: priced as a send would price
+    /// them, with the rent of any accounts they create.
+    pub async fn estimate_cost(
+        &self,
+        instructions: &[Instruction],
+    ) -> anyhow::Result<CostEstimate> {
+        let price = self.compute_unit_price(instructions).await;
+        Ok(CostEstimate {
+            tip: self.tip_lamports(),
+            rent: self.created_account_rent(instructions).await?,
+            ..CostEstimate::fees(1, instructions.len(), self.config.compute_unit_limit, price)
+        })
+    }
+
+    fn tip_lamports(&self) -> u64 {
+        self.private_relay().map_or(0, JitoRelay::tip_lamports)
+    }
+
+    /// The block engine to send through, if these options ask for one and there is one.
+    fn private_relay(&self) -> Option<&JitoRelay> {
+        self.jito.as_deref().filter(|_| self.options.private)
+    }
+
+    fn simulates(&self) -> bool {
+        self.config.simulate && !self.options.skip_preflight
+    }
+
+    fn estimates_compute_units(&self) -> bool {
+        self.config.compute_unit_limit.is_none() && self.config.compute_unit_margin_pct.is_some()
+    }
+
+    /// Send `instructions` in one transaction signed by the payer and wait for it to confirm.
+    pub async fn send(&self, instructions: Vec<Instruction>) -> anyhow::Result<Signature> {
+        self.send_with_signers(instructions, &[]).await
+    }
+
+    /// Like [`send`](Self::send), journaling each attempt under `intent`.
+    pub async fn send_with_intent(
+        &self,
+        intent: &TxIntent,
+        instructions: Vec<Instruction>,
+    ) -> anyhow::Result<Signature> {
+        self.send_inner(intent, instructions, &[]).await
+    }
+
+    /// Like [`send`](Self::send), with `extra_signers` signing alongside the payer, e.g. a
+    /// new account's keypair.
+    pub async fn send_with_signers(
+        &self,
+        instructions: Vec<Instruction>,
+        extra_signers: &[&Keypair],
+    ) -> anyhow::Result<Signature> {
+        self.send_inner(&TxIntent::unlabeled(), instructions, extra_signers)
+            .await
+    }
+
+    /// Send what `build` makes for a reference index, starting with `reference_index`,
+    /// each build valid until the end of its index's window. One held back as
+    /// [`LandsTooLate`] was never sent, so rather than retrying an instruction that can
+    /// only fail, it is rebuilt for the window it would now land in and sent again, up to
+    /// [`max_rebuilds`](TxSenderConfig::max_rebuilds) times.
+    pub async fn send_for_window<F, Fut>(
+        &self,
+        end_slot_interval: u64,
+        mut reference_index: u64,
+        mut build: F,
+    ) -> anyhow::Result<Signature>
+    where
+        F: FnMut(u64) -> Fut,
+        Fut: Future<Output = anyhow::Result<(TxIntent, Vec<Instruction>)>>,
+    {
+        let mut rebuilds = 0;
+        loop {
+            let (intent, instructions) = build(reference_index).await?;
+            let intent = intent.reference_index(reference_index).valid_until_slot(
+                reference_window_last_slot(reference_index, end_slot_interval),
+            );
+            let error = match self.send_inner(&intent, instructions, &[]).await {
+                Ok(signature) => return Ok(signature),
+                Err(error) => error,
+            };
+            let Some(too_late) = error.downcast_ref::<LandsTooLate>() else {
+                return Err(error);
+            };
+            if rebuilds >= self.config.max_rebuilds {
+                return Err(error);
+            }
+
+            let next = next_reference_index(
+                too_late.expected_landing_slot,
+                end_slot_interval,
+                reference_index,
+            );
+            info!(
+                event.name = "tx_rebuilt",
+                tx.action = intent.action.as_str(),
+                tx.market_id = ?intent.market_id,
+                tx.reference_index = reference_index,
+                tx.next_reference_index = next,
+                monotonic_counter.tx_rebuilt_total = 1_u64,
+            );
+            reference_index = next;
+            rebuilds += 1;
+        }
+    }
+
+    async fn send_inner(
+        &self,
+        intent: &TxIntent,
+        instructions: Vec<Instruction>,
+        extra_signers: &[&Keypair],
+    ) -> anyhow::Result<Signature> {
+        // Resolved before queueing, so time spent waiting in the queue counts against it.
+        let intent = &self.with_deadline(intent);
+        self.check_deadline(intent)?;
+        if self.config.dry_run {
+            return self.dry_run(intent, instructions).await;
+        }
+        if let Some(queue) = &self.queue {
+            return queue
+                .send(intent, instructions, extra_signers, self.options)
+                .await;
+        }
+        let span = info_span!(
+            "tx.send",
+            tx.action = intent.action.as_str(),
+            tx.market_id = ?intent.market_id,
+            tx.durable = self.options.durable,
+        );
+        self.send_now(intent, instructions, extra_signers)
+            .instrument(span)
+            .await
+    }
+
+    async fn send_now(
+        &self,
+        intent: &TxIntent,
+        instructions: Vec<Instruction>,
+        extra_signers: &[&Keypair],
+    ) -> anyhow::Result<Signature> {
+        self.check_fee_balance().await?;
+        if let Some(nonces) = self.nonces.as_deref().filter(|_| self.options.durable) {
+            return self
+                .send_durable(intent, instructions, extra_signers, nonces)
+                .await;
+        }
+
+        let tip = self
+            .private_relay()
+            .map(|jito| jito.tip_instruction(&self.payer.pubkey()));
+        let tipped: Vec<Instruction> = instructions.iter().cloned().chain(tip).collect();
+
+        // Estimating simulates the transaction, which stands in for the first attempt's
+        // simulation check.
+        let mut simulated = false;
+        let compute_unit_limit = match self.config.compute_unit_margin_pct {
+            Some(margin_pct)
+                if self.config.compute_unit_limit.is_none() && !self.options.skip_preflight =>
+            {
+                simulated = true;
+                self.estimate_compute_unit_limit(&tipped, margin_pct)
+                    .instrument(info_span!("tx.estimate_compute"))
+                    .await?
+            }
+            _ => self.config.compute_unit_limit,
+        };
+
+        let rent = self.send_rent(&tipped).await;
+
+        // The transaction in flight, kept until its blockhash is seen to expire: until then
+        // it may still land, and signing a second one could land both.
+        let mut in_flight: Option<(Transaction, CachedBlockhash, CostEstimate)> = None;
+        let mut expired = None;
+        for attempt in 1..=self.config.max_attempts {
+            let resend = in_flight.is_some();
+            let (transaction, blockhash, cost) = match in_flight.take() {
+                Some(signed) => signed,
+                None => {
+                    async {
+                        // Repriced for each signing: an expired one may have been outbid.
+                        // Priced on the accounts our own instructions write: tip accounts
+                        // are always contended.
+                        let price = self.compute_unit_price(&instructions).await;
+                        let mut all_instructions =
+                            compute_budget_instructions(compute_unit_limit, price);
+                        all_instructions.extend_from_slice(&tipped);
+                        if attempt > 1 {
+                            self.check_deadline(intent)?;
+                        }
+                        let cost = CostEstimate {
+                            tip: self.tip_lamports(),
+                            rent,
+                            ..CostEstimate::fees(
+                                1 + extra_signers.len(),
+                                tipped.len(),
+                                compute_unit_limit,
+                                price,
+                            )
+                        };
+                        self.check_fee_budget(intent, &cost)?;
+                        if let Some(valid_until_slot) = intent.valid_until_slot {
+                            self.check_landing_slot(valid_until_slot).await?;
+                        }
+                        let blockhash = self.blockhash(attempt > 1).await?;
+                        let transaction = self
+                            .sign(&all_instructions, extra_signers, blockhash.hash)
+                            .await?;
+
+                        if self.simulates() && !(attempt == 1 && simulated) {
+                            self.check_simulation(&transaction).await?;
+                        }
+                        Ok::<_, anyhow::Error>((transaction, blockhash, cost))
+                    }
+                    .instrument(info_span!("tx.build", tx.attempt = attempt))
+                    .await?
+                }
+            };
+            let signature = transaction.signatures[0];
+
+            let started = Instant::now();
+            let outcome = self
+                .submit(&transaction, blockhash, intent, attempt, resend)
+                .await;
+            if matches!(
+                outcome,
+                Ok(Attempt::Confirmed { .. }) | Err(SubmitError::Failed(_))
+            ) {
+                self.record_fees(&cost);
+            }
+            match outcome {
+                Ok(Attempt::Confirmed { .. }) => {
+                    info!(
+                        event.name = "tx_confirmed",
+                        tx.signature = %signature,
+                        tx.attempt = attempt,
+                        histogram.tx_confirm_ms = started.elapsed().as_millis() as u64,
+                        monotonic_counter.tx_confirmed_total = 1_u64,
+                    );
+                    return Ok(signature);
+                }
+                Ok(Attempt::Expired) => {
+                    warn!(
+                        event.name = "tx_expired",
+                        tx.signature = %signature,
+                        tx.attempt = attempt,
+                        monotonic_counter.tx_expired_total = 1_u64,
+                    );
+                    expired = Some(TxExpired {
+                        signature,
+                        attempts: attempt,
+                    });
+                }
+                Ok(Attempt::Pending) => {
+                    warn!(
+                        event.name = "tx_unconfirmed",
+                        tx.signature = %signature,
+                        tx.attempt = attempt,
+                    );
+                    expired = None;
+                    in_flight = Some((transaction, blockhash, cost));
+                }
+                Err(SubmitError::Failed(error)) => return Err(error),
+                Err(SubmitError::Retryable(error)) => {
+                    warn!(
+                        event.name = "tx_send_failed",
+                        tx.signature = %signature,
+                        tx.attempt = attempt,
+                        ?error,
+                    );
+                    expired = None;
+                    in_flight = Some((transaction, blockhash, cost));
+                }
+            }
+            if attempt < self.config.max_attempts {
+                sleep(self.config.retry_delay).await;
+            }
+        }
+
+        if let Some(expired) = expired {
+            return Err(expired.into());
+        }
+        match in_flight {
+            Some((transaction, blockhash, _)) => Err(TxUnconfirmed {
+                signature: transaction.signatures[0],
+                attempts: self.config.max_attempts,
+                last_valid_block_height: Some(blockhash.last_valid_block_height),
+            }
+            .into()),
+            None => Err(anyhow::anyhow!("no send attempts configured")),
+        }
+    }
+
+    /// Send over a nonce from `nonces`. The transaction is signed once and the same one is
+    /// resent every attempt: signing a second over the advanced nonce could land both.
+    /// One still unconfirmed at the end stays valid, so it is left pending in the journal.
+    async fn send_durable(
+        &self,
+        intent: &TxIntent,
+        instructions: Vec<Instruction>,
+        extra_signers: &[&Keypair],
+        nonces: &NonceManager,
+    ) -> anyhow::Result<Signature> {
+        let lease = nonces.acquire()?;
+        let price = self.compute_unit_price(&instructions).await;
+        // The nonce advance is one more instruction on top of ours.
+        let cost = CostEstimate {
+            tip: self.tip_lamports(),
+            rent: self.send_rent(&instructions).await,
+            ..CostEstimate::fees(
+                1 + extra_signers.len(),
+                instructions.len() + 1,
+                self.config.compute_unit_limit,
+                price,
+            )
+        };
+        self.check_fee_budget(intent, &cost)?;
+        let transaction = self
+            .sign_durable(instructions, extra_signers, nonces, lease.account(), price)
+            .instrument(info_span!("tx.build", tx.attempt = 1))
+            .await?;
+        let signature = transaction.signatures[0];
+        self.record_sent(signature, intent, 1);
+
+        for attempt in 1..=self.config.max_attempts {
+            if let Err(error) = self
+                .broadcast(&transaction)
+                .instrument(info_span!("tx.broadcast", tx.attempt = attempt))
+                .await
+            {
+                warn!(
+                    event.name = "tx_send_failed",
+                    tx.signature = %signature,
+                    tx.attempt = attempt,
+                    ?error,
+                );
+            }
+            let outcome = self
+                .confirm(&transaction, signature, None)
+                .instrument(info_span!(
+                    "tx.confirm",
+                    tx.signature = %signature,
+                    tx.attempt = attempt,
+                ))
+                .await;
+            match outcome {
+                Ok(Attempt::Confirmed { .. }) | Err(SubmitError::Failed(_)) => {
+                    self.record_fees(&cost);
+                    if matches!(outcome, Ok(Attempt::Confirmed { .. })) {
+                        info!(
+                            event.name = "tx_confirmed",
+                            tx.signature = %signature,
+                            tx.attempt = attempt,
+                            tx.durable = true,
+                            monotonic_counter.tx_confirmed_total = 1_u64,
+                        );
+                    }
+                    self.record_outcome(signature, intent, &outcome);
+                    return match outcome {
+                        Err(SubmitError::Failed(error)) => Err(error),
+                        _ => Ok(signature),
+                    };
+                }
+                Ok(Attempt::Expired | Attempt::Pending) | Err(SubmitError::Retryable(_)) => {}
+            }
+            if attempt < self.config.max_attempts {
+                sleep(self.config.retry_delay).await;
+            }
+        }
+        Err(TxUnconfirmed {
+            signature,
+            attempts: self.config.max_attempts,
+            last_valid_block_height: None,
+        }
+        .into())
+    }
+
+    /// Sign `instructions` over a nonce taken out of rotation for good, for a transaction
+    /// kept to be sent later, e.g. a stop the watchdog submits if the bot goes silent.
+    pub async fn presign_durable(
+        &self,
+        instructions: Vec<Instruction>,
+    ) -> anyhow::Result<Transaction> {
+        let account = self.reserve_nonce()?;
+        self.presign_durable_over(account, instructions).await
+    }
+
+    /// Take a nonce account out of rotation for good, for [`presign_durable_over`] to sign
+    /// over again and again.
+    ///
+    /// [`presign_durable_over`]: Self::presign_durable_over
+    pub fn reserve_nonce(&self) -> anyhow::Result<Pubkey> {
+        self.nonces
+            .as_deref()
+            .context("Pre-signing needs nonce accounts; set TX_NONCE_POOL_SIZE")?
+            .reserve()
+    }
+
+    /// Sign `instructions` over the current nonce of `account`, from [`reserve_nonce`].
+    /// Until one of them lands, every transaction signed this way stays valid, and the
+    /// first to land invalidates the rest.
+    ///
+    /// [`reserve_nonce`]: Self::reserve_nonce
+    pub async fn presign_durable_over(
+        &self,
+        account: Pubkey,
+        instructions: Vec<Instruction>,
+    ) -> anyhow::Result<Transaction> {
+        let nonces = self
+            .nonces
+            .as_deref()
+            .context("Pre-signing needs nonce accounts; set TX_NONCE_POOL_SIZE")?;
+        let price = self.compute_unit_price(&instructions).await;
+        self.sign_durable(instructions, &[], nonces, account, price)
+            .await
+    }
+
+    /// `instructions` behind the nonce advance and compute-budget instructions, at `price`,
+    /// signed over `account`'s current nonce.
+    async fn sign_durable(
+        &self,
+        instructions: Vec<Instruction>,
+        extra_signers: &[&Keypair],
+        nonces: &NonceManager,
+        account: Pubkey,
+        price: Option<u64>,
+    ) -> anyhow::Result<Transaction> {
+        let nonce = nonce::current_nonce(&self.rpc, &account, self.config.commitment).await?;
+        // The advance must come first for the runtime to accept the nonce.
+        let mut all_instructions = vec![nonces.advance_instruction(&account)];
+        all_instructions.extend(compute_budget_instructions(
+            self.config.compute_unit_limit,
+            price,
+        ));
+        all_instructions.extend(instructions);
+        if let Some(jito) = self.private_relay() {
+            all_instructions.push(jito.tip_instruction(&self.payer.pubkey()));
+        }
+        self.sign(&all_instructions, extra_signers, nonce).await
+    }
+
+    /// `instructions` over `recent_blockhash`, signed by the payer's [`TxSigner`] and by
+    /// `extra_signers`.
+    async fn sign(
+        &self,
+        instructions: &[Instruction],
+        extra_signers: &[&Keypair],
+        recent_blockhash: Hash,
+    ) -> anyhow::Result<Transaction> {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&self.payer.pubkey()));
+        transaction.message.recent_blockhash = recent_blockhash;
+        // The fee payer always signs first.
+        transaction.signatures[0] = self
+            .payer
+            .sign_message(&transaction.message_data())
+            .await
+            .with_context(|| format!("{} signer failed to sign", self.payer.name()))?;
+        let extra_signers: Vec<&dyn Signer> = extra_signers
+            .iter()
+            .map(|signer| *signer as &dyn Signer)
+            .collect();
+        transaction.try_partial_sign(&extra_signers, recent_blockhash)?;
+        Ok(transaction)
+    }
+
+    /// Simulate `instructions` as [`send`](Self::send) would submit them, without sending.
+    /// The caller decides what a failed simulation means.
+    pub async fn simulate(
+        &self,
+        instructions: Vec<Instruction>,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        // An estimated limit isn't known yet, so simulate with room to spare.
+        let limit = if self.estimates_compute_units() {
+            Some(MAX_COMPUTE_UNIT_LIMIT)
+        } else {
+            self.config.compute_unit_limit
+        };
+        let mut all_instructions =
+            compute_budget_instructions(limit, self.config.compute_unit_price);
+        all_instructions.extend(instructions);
+        let transaction =
+            Transaction::new_with_payer(&all_instructions, Some(&self.payer.pubkey()));
+        self.simulate_transaction(&transaction).await
+    }
+
+    async fn dry_run(
+        &self,
+        intent: &TxIntent,
+        instructions: Vec<Instruction>,
+    ) -> anyhow::Result<Signature> {
+        let simulation = self.simulate(instructions).await?;
+        info!(
+            event.name = "tx_dry_run",
+            tx.action = intent.action.as_str(),
+            tx.market_id = ?intent.market_id,
+            tx.units_consumed = ?simulation.units_consumed,
+            tx.simulation_error = ?simulation.err,
+            monotonic_counter.tx_dry_run_total = 1_u64,
+        );
+        match simulation.err {
+            Some(error) => Err(TransactionFailed {
+                signature: None,
+                error,
+                logs: simulation.logs,
+            }
+            .into()),
+            None => Err(DryRun {
+                action: intent.action.clone(),
+                units_consumed: simulation.units_consumed,
+            }
+            .into()),
+        }
+    }
+
+    /// Submit a transaction someone else signed (e.g. a pre-signed stop against a durable
+    /// nonce) and wait for it to confirm. It is broadcast once: its blockhash is not ours
+    /// to replace.
+    pub async fn send_presigned(
+        &self,
+        intent: &TxIntent,
+        transaction: &Transaction,
+    ) -> anyhow::Result<Signature> {
+        let signature = self.broadcast(transaction).await?;
+        self.record_sent(signature, intent, 1);
+        let outcome = self.confirm(transaction, signature, None).await;
+        self.record_outcome(signature, intent, &outcome);
+        match outcome {
+            Ok(Attempt::Confirmed { .. }) => Ok(signature),
+            Ok(Attempt::Expired | Attempt::Pending) => Err(TxUnconfirmed {
+                signature,
+                attempts: 1,
+                last_valid_block_height: None,
+            }
+            .into()),
+            Err(SubmitError::Failed(error) | SubmitError::Retryable(error)) => Err(error),
+        }
+    }
+
+    /// Settle journal entries left pending, e.g. by a restart while waiting on them: each
+    /// takes the status the chain now reports, or becomes expired once it is too old to
+    /// still land. Returns the entries that were pending. Without a journal, does nothing.
+    pub async fn reconcile_journal(&self) -> anyhow::Result<Vec<JournalEntry>> {
+        let Some(journal) = &self.journal else {
+            return Ok(Vec::new());
+        };
+        let pending = journal.pending()?;
+        let now = chrono::Utc::now().timestamp();
+        for entry in &pending {
+            let signature: Signature = entry
+                .signature
+                .parse()
+                .with_context(|| format!("Invalid journaled signature {}", entry.signature))?;
+            let (status, error) = match self.signature_status(&signature).await? {
+                Some(status) => match status.err {
+                    Some(err) => (JournalStatus::Failed, Some(format!("{:?}", err))),
+                    None if status.satisfies_commitment(self.config.commitment) => {
+                        (JournalStatus::Confirmed, None)
+                    }
+                    None => continue,
+                },
+                None if now - entry.timestamp > JOURNAL_EXPIRY_SECS => {
+                    (JournalStatus::Expired, None)
+                }
+                None => continue,
+            };
+            journal.set_status(&entry.signature, status, error.as_deref(), now)?;
+            info!(
+                event.name = "tx_journal_reconciled",
+                tx.signature = %entry.signature,
+                tx.action = entry.intent.action.as_str(),
+                tx.market_id = ?entry.intent.market_id,
+                tx.status = status.as_str(),
+            );
+        }
+        Ok(pending)
+    }
+
+    /// Refuse a non-critical send while the payer is below the fee floor, alerting either
+    /// way. A balance that can't be read doesn't block the send.
+    async fn check_fee_balance(&self) -> anyhow::Result<()> {
+        let Some(floor) = self.config.min_sol_balance else {
+            return Ok(());
+        };
+        let balance = match self
+            .rpc
+            .get_balance_with_commitment(&self.payer.pubkey(), self.config.commitment)
+            .await
+        {
+            Ok(response) => response.value,
+            Err(error) => {
+                warn!(event.name = "tx_fee_balance_query_failed", ?error);
+                return Ok(());
+            }
+        };
+        if balance >= floor {
+            return Ok(());
+        }
+
+        let low = FeeBalanceLow { balance, floor };
+        warn!(
+            event.name = "tx_fee_balance_low",
+            tx.payer = %self.payer.pubkey(),
+            tx.critical = self.options.critical,
+            gauge.tx_payer_balance_lamports = balance,
+            monotonic_counter.tx_fee_balance_blocked_total = u64::from(!self.options.critical),
+        );
+        if let Some(alerter) = &self.alerter {
+            alerter.notify(
+                AlertKind::FeeBalanceLow,
+                None,
+                format!("{}: {}", self.payer.pubkey(), low),
+            );
+        }
+        if self.options.critical {
+            return Ok(());
+        }
+        Err(low.into())
+    }
+
+    /// Log what a send is expected to cost and refuse a non-critical one its fees would
+    /// take past the day's budget, alerting when it does.
+    fn check_fee_budget(&self, intent: &TxIntent, cost: &CostEstimate) -> anyhow::Result<()> {
+        info!(
+            event.name = "tx_cost_estimated",
+            tx.action = intent.action.as_str(),
+            tx.base_fee_lamports = cost.base_fee,
+            tx.priority_fee_lamports = cost.priority_fee,
+            tx.tip_lamports = cost.tip,
+            tx.rent_lamports = cost.rent,
+            histogram.tx_estimated_cost_lamports = cost.total(),
+        );
+        let Some(budget) = &self.fee_budget else {
+            return Ok(());
+        };
+        let Err(exceeded) = budget.check(cost.spend()) else {
+            return Ok(());
+        };
+        warn!(
+            event.name = "tx_fee_budget_exceeded",
+            tx.action = intent.action.as_str(),
+            tx.critical = self.options.critical,
+            tx.spent_today_lamports = exceeded.spent,
+            tx.estimated_fee_lamports = exceeded.estimate,
+            tx.daily_fee_budget_lamports = exceeded.budget,
+            monotonic_counter.tx_fee_budget_blocked_total = u64::from(!self.options.critical),
+        );
+        if let Some(alerter) = &self.alerter {
+            alerter.notify(
+                AlertKind::FeeBudgetExhausted,
+                intent.market_id,
+                format!("{}: {}", self.payer.pubkey(), exceeded),
+            );
+        }
+        if self.options.critical {
+            return Ok(());
+        }
+        Err(exceeded.into())
+    }
+
+    /// Count a landed send's fees against the day's budget.
+    fn record_fees(&self, cost: &CostEstimate) {
+        if let Some(budget) = &self.fee_budget {
+            budget.record(cost.spend());
+            info!(
+                event.name = "tx_fees_spent",
+                monotonic_counter.tx_fees_spent_lamports_total = cost.spend(),
+                gauge.tx_fees_spent_today_lamports = budget.spent_today(),
+            );
+        }
+    }
+
+    /// The rent of the accounts `instructions` create, when
+    /// [`estimate_rent`](TxSenderConfig::estimate_rent) is on. A failed estimate is logged
+    /// and taken as none: it must not stop the send.
+    async fn send_rent(&self, instructions: &[Instruction]) -> u64 {
+        if !self.config.estimate_rent {
+            return 0;
+        }
+        self.created_account_rent(instructions)
+            .await
+            .unwrap_or_else(|error| {
+                warn!(event.name = "tx_rent_estimate_failed", ?error);
+                0
+            })
+    }
+
+    /// Rent for the accounts `instructions` would create: the writable ones that don't
+    /// exist yet, at the balance a simulation leaves them with.
+    async fn created_account_rent(&self, instructions: &[Instruction]) -> anyhow::Result<u64> {
+        let payer = self.payer.pubkey();
+        let mut writable: Vec<Pubkey> = instructions
+            .iter()
+            .flat_map(|instruction| &instruction.accounts)
+            .filter(|meta| meta.is_writable && meta.pubkey != payer)
+            .map(|meta| meta.pubkey)
+            .collect();
+        writable.sort();
+        writable.dedup();
+
+        let mut missing = Vec::new();
+        for chunk in writable.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let accounts = self
+                .rpc
+                .get_multiple_accounts_with_commitment(chunk, self.config.commitment)
+                .await?
+                .value;
+            missing.extend(
+                chunk
+                    .iter()
+                    .zip(accounts)
+                    .filter(|(_, account)| account.is_none())
+                    .map(|(pubkey, _)| pubkey.to_string()),
+            );
+        }
+        if missing.is_empty() {
+            return Ok(0);
+        }
+
+        let transaction = Transaction::new_with_payer(instructions, Some(&payer));
+        let simulation = self
+            .rpc
+            .simulate_transaction_with_config(
+                &transaction,
+                RpcSimulateTransactionConfig {
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.config.commitment),
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: None,
+                        addresses: missing,
+                    }),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )
+            .await?
+            .value;
+        if let Some(err) = simulation.err {
+            anyhow::bail!("Simulation to estimate rent failed: {:?}", err);
+        }
+        Ok(simulation
+            .accounts
+            .unwrap_or_default()
+            .iter()
+            .flatten()
+            .map(|account| account.lamports)
+            .sum())
+    }
+
+    /// `intent` with its action's configured deadline, counted from now, unless it carries
+    /// its own.
+    fn with_deadline(&self, intent: &TxIntent) -> TxIntent {
+        let mut intent = intent.clone();
+        if intent.deadline.is_none() {
+            intent.deadline = self
+                .config
+                .deadlines
+                .get(&intent.action)
+                .map(|deadline| Instant::now() + *deadline);
+        }
+        intent
+    }
+
+    /// Fail with [`DeadlinePassed`] once `intent`'s deadline is behind us.
+    fn check_deadline(&self, intent: &TxIntent) -> anyhow::Result<()> {
+        let Some(overdue) = intent
+            .deadline
+            .and_then(|deadline| Instant::now().checked_duration_since(deadline))
+        else {
+            return Ok(());
+        };
+        warn!(
+            event.name = "tx_deadline_passed",
+            tx.action = intent.action.as_str(),
+            tx.market_id = ?intent.market_id,
+            tx.overdue_ms = overdue.as_millis() as u64,
+            monotonic_counter.tx_deadline_passed_total = 1_u64,
+        );
+        Err(DeadlinePassed {
+            action: intent.action.clone(),
+            overdue,
+        }
+        .into())
+    }
+
+    /// Fail with [`LandsTooLate`] if a transaction sent now is not expected to land by
+    /// `valid_until_slot`. RPC nodes and the TPU client forward to the current and next
+    /// leaders, so it should land by the end of the next leader's rotation.
+    async fn check_landing_slot(&self, valid_until_slot: u64) -> anyhow::Result<()> {
+        let slot = self
+            .rpc
+            .get_slot_with_commitment(CommitmentConfig::processed())
+            .await?;
+        let expected_landing_slot = match self
+            .rpc
+            .get_slot_leaders(slot, 3 * LEADER_ROTATION_SLOTS)
+            .await
+        {
+            Ok(leaders) => next_leader_last_slot(slot, &leaders),
+            Err(error) => {
+                warn!(event.name = "tx_leader_schedule_query_failed", ?error);
+                (slot / LEADER_ROTATION_SLOTS + 2) * LEADER_ROTATION_SLOTS - 1
+            }
+        };
+        if expected_landing_slot + self.config.valid_until_guard_slots <= valid_until_slot {
+            return Ok(());
+        }
+
+        warn!(
+            event.name = "tx_held_lands_too_late",
+            tx.slot = slot,
+            tx.expected_landing_slot = expected_landing_slot,
+            tx.valid_until_slot = valid_until_slot,
+            monotonic_counter.tx_held_lands_too_late_total = 1_u64,
+        );
+        Err(LandsTooLate {
+            slot,
+            expected_landing_slot,
+            valid_until_slot,
+        }
+        .into())
+    }
+
+    async fn blockhash(&self, refresh: bool) -> anyhow::Result<CachedBlockhash> {
+        let mut cached = self.blockhash.lock().await;
+        if let Some(blockhash) = *cached
+            && !refresh
+            && blockhash.fetched_at.elapsed() < self.config.blockhash_max_age
+        {
+            return Ok(blockhash);
+        }
+
+        let (hash, last_valid_block_height) = self
+            .rpc
+            .get_latest_blockhash_with_commitment(self.config.commitment)
+            .await?;
+        let blockhash = CachedBlockhash {
+            hash,
+            last_valid_block_height,
+            fetched_at: Instant::now(),
+        };
+        *cached = Some(blockhash);
+        Ok(blockhash)
+    }
+
+    /// The configured compute-unit price, or one read from the recent prioritization fees
+    /// for the accounts `instructions` write. A failed fee query sends without a price
+    /// rather than not at all.
+    async fn compute_unit_price(&self, instructions: &[Instruction]) -> Option<u64> {
+        if self.config.compute_unit_price.is_some() {
+            return self.config.compute_unit_price;
+        }
+        let percentile = self.config.priority_fee_percentile?;
+
+        let mut writable = Vec::new();
+        for meta in instructions
+            .iter()
+            .flat_map(|instruction| &instruction.accounts)
+        {
+            if meta.is_writable && !writable.contains(&meta.pubkey) {
+                writable.push(meta.pubkey);
+            }
+        }
+        writable.truncate(MAX_PRIORITIZATION_FEE_ACCOUNTS);
+
+        match self.rpc.get_recent_prioritization_fees(&writable).await {
+            Ok(fees) => {
+                let mut fees: Vec<u64> = fees.iter().map(|fee| fee.prioritization_fee).collect();
+                let price =
+                    fee_percentile(&mut fees, percentile).min(self.config.max_compute_unit_price);
+                info!(
+                    event.name = "tx_priority_fee_priced",
+                    tx.fee_percentile = percentile,
+                    tx.fee_samples = fees.len(),
+                    gauge.tx_compute_unit_price = price,
+                );
+                (price > 0).then_some(price)
+            }
+            Err(error) => {
+                warn!(event.name = "tx_priority_fee_query_failed", ?error);
+                None
+            }
+        }
+    }
+
+    /// Simulate `instructions` with the maximum limit and return the units consumed plus
+    /// `margin_pct`. Fails if the simulation does; `None` if the node didn't report usage.
+    async fn estimate_compute_unit_limit(
+        &self,
+        instructions: &[Instruction],
+        margin_pct: u32,
+    ) -> anyhow::Result<Option<u32>> {
+        let mut all_instructions = compute_budget_instructions(
+            Some(MAX_COMPUTE_UNIT_LIMIT),
+            self.config.compute_unit_price,
+        );
+        all_instructions.extend_from_slice(instructions);
+        // Simulation doesn't check signatures, so the signer isn't asked for one.
+        let transaction =
+            Transaction::new_with_payer(&all_instructions, Some(&self.payer.pubkey()));
+
+        let simulation = self.simulate_transaction(&transaction).await?;
+        if let Some(error) = simulation.err {
+            return Err(TransactionFailed {
+                signature: None,
+                error,
+                logs: simulation.logs,
+            }
+            .into());
+        }
+        let Some(units_consumed) = simulation.units_consumed else {
+            return Ok(None);
+        };
+        let limit = limit_with_margin(units_consumed, margin_pct);
+        info!(
+            event.name = "tx_compute_units_estimated",
+            tx.units_consumed = units_consumed,
+            tx.compute_unit_limit = limit,
+            histogram.tx_compute_units_consumed = units_consumed,
+        );
+        Ok(Some(limit))
+    }
+
+    async fn check_simulation(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        let simulation = self.simulate_transaction(transaction).await?;
+        if let Some(error) = simulation.err {
+            return Err(TransactionFailed {
+                signature: None,
+                error,
+                logs: simulation.logs,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Simulate against the node's latest blockhash, so a hash that hasn't reached the
+    /// simulating node yet doesn't read as a failure.
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        let simulation = self
+            .rpc
+            .simulate_transaction_with_config(
+                transaction,
+                RpcSimulateTransactionConfig {
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.config.commitment),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )
+            .await?;
+        Ok(simulation.value)
+    }
+
+    /// Send `transaction` to the main RPC and every broadcast endpoint at once. Succeeds if
+    /// any of them accepts it; otherwise returns the main RPC's error. A private send goes
+    /// to the block engine and nowhere else.
+    async fn broadcast(&self, transaction: &Transaction) -> anyhow::Result<Signature> {
+        if let Some(jito) = self.private_relay() {
+            let signature = jito.send(transaction).await?;
+            info!(
+                event.name = "tx_sent_private",
+                tx.signature = %signature,
+                monotonic_counter.tx_private_sent_total = 1_u64,
+            );
+            return Ok(signature);
+        }
+
+        let config = RpcSendTransactionConfig {
+            // Our own simulation stands in for the RPC's preflight.
+            skip_preflight: self.config.simulate || self.options.skip_preflight,
+            preflight_commitment: Some(
+                self.options
+                    .preflight_commitment
+                    .unwrap_or(self.config.commitment.commitment),
+            ),
+            max_retries: self.options.max_retries,
+            ..RpcSendTransactionConfig::default()
+        };
+        let (primary, extras, via_tpu) = tokio::join!(
+            self.rpc.send_transaction_with_config(transaction, config),
+            join_all(
+                self.broadcast_rpcs
+                    .iter()
+                    .map(|rpc| rpc.send_transaction_with_config(transaction, config)),
+            ),
+            self.send_via_tpu(transaction),
+        );
+
+        let mut accepted = via_tpu.then(|| transaction.signatures[0]);
+        for (rpc, result) in self.broadcast_rpcs.iter().zip(extras) {
+            match result {
+                Ok(signature) => accepted = Some(signature),
+                Err(error) => warn!(
+                    event.name = "tx_broadcast_failed",
+                    tx.rpc_url = %rpc.url(),
+                    monotonic_counter.tx_broadcast_failures_total = 1_u64,
+                    ?error,
+                ),
+            }
+        }
+        match (primary, accepted) {
+            (Ok(signature), _) => Ok(signature),
+            (Err(error), Some(signature)) => {
+                warn!(
+                    event.name = "tx_broadcast_failed",
+                    tx.rpc_url = %self.rpc.url(),
+                    monotonic_counter.tx_broadcast_failures_total = 1_u64,
+                    ?error,
+                );
+                Ok(signature)
+            }
+            (Err(error), None) => Err(error.into()),
+        }
+    }
+
+    /// Send `transaction` to the leaders over QUIC if these options ask for it. Returns
+    /// whether a leader connection took it.
+    #[cfg(feature = "tpu")]
+    async fn send_via_tpu(&self, transaction: &Transaction) -> bool {
+        let Some(tpu) = self.tpu.as_ref().filter(|_| self.options.tpu) else {
+            return false;
+        };
+        match tpu.try_send_transaction(transaction).await {
+            Ok(()) => {
+                info!(
+                    event.name = "tx_tpu_sent",
+                    tx.signature = %transaction.signatures[0],
+                    monotonic_counter.tx_tpu_sent_total = 1_u64,
+                );
+                true
+            }
+            Err(error) => {
+                warn!(
+                    event.name = "tx_tpu_send_failed",
+                    tx.signature = %transaction.signatures[0],
+                    monotonic_counter.tx_tpu_failures_total = 1_u64,
+                    ?error,
+                );
+                false
+            }
+        }
+    }
+
+    #[cfg(not(feature = "tpu"))]
+    async fn send_via_tpu(&self, _transaction: &Transaction) -> bool {
+        false
+    }
+
+    /// The first status any endpoint reports for `signature`, asking the main RPC first.
+    /// Fails only if every endpoint does.
+    async fn signature_status(
+        &self,
+        signature: &Signature,
+    ) -> anyhow::Result<Option<TransactionStatus>> {
+        let mut answered = false;
+        let mut last_error = None;
+        for rpc in std::iter::once(&*self.rpc).chain(self.broadcast_rpcs.iter()) {
+            match rpc.get_signature_statuses(&[*signature]).await {
+                Ok(statuses) => {
+                    if let Some(Some(status)) = statuses.value.into_iter().next() {
+                        return Ok(Some(status));
+                    }
+                    answered = true;
+                }
+                Err(error) => last_error = Some(error),
+            }
+        }
+        match last_error {
+            Some(error) if !answered => Err(error.into()),
+            _ => Ok(None),
+        }
+    }
+
+    async fn submit(
+        &self,
+        transaction: &Transaction,
+        blockhash: CachedBlockhash,
+        intent: &TxIntent,
+        attempt: u32,
+        resend: bool,
+    ) -> Result<Attempt, SubmitError> {
+        let signature = self
+            .broadcast(transaction)
+            .instrument(info_span!("tx.broadcast", tx.attempt = attempt))
+            .await
+            .map_err(SubmitError::Retryable)?;
+        if resend {
+            info!(
+                event.name = "tx_resent",
+                tx.signature = %signature,
+                tx.attempt = attempt,
+                monotonic_counter.tx_rebroadcasts_total = 1_u64,
+            );
+        } else {
+            info!(
+                event.name = "tx_sent",
+                tx.signature = %signature,
+                tx.action = intent.action.as_str(),
+                monotonic_counter.tx_sent_total = 1_u64,
+            );
+            self.record_sent(signature, intent, attempt);
+        }
+        let outcome = self
+            .confirm(
+                transaction,
+                signature,
+                Some(blockhash.last_valid_block_height),
+            )
+            .instrument(info_span!(
+                "tx.confirm",
+                tx.signature = %signature,
+                tx.attempt = attempt,
+            ))
+            .await;
+        self.record_outcome(signature, intent, &outcome);
+        outcome
+    }
+
+    /// Journal `signature` as pending and audit the send. A journal that can't be written
+    /// is logged, never allowed to stop a send.
+    fn record_sent(&self, signature: Signature, intent: &TxIntent, attempt: u32) {
+        audit::record(
+            &self.reporting,
+            AuditRecord {
+                market_id: intent.market_id,
+                inputs: serde_json::json!({
+                    "payer": self.payer.pubkey().to_string(),
+                    "reference_index": intent.reference_index,
+                    "base_flow": intent.base_flow,
+                    "quote_flow": intent.quote_flow,
+                    "base_amount": intent.base_amount,
+                    "quote_amount": intent.quote_amount,
+                    "attempt": attempt,
+                }),
+                signature: Some(signature.to_string()),
+                ..AuditRecord::new("tx", &intent.action, JournalStatus::Pending.as_str())
+            },
+        );
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let entry = JournalEntry {
+            timestamp: chrono::Utc::now().timestamp(),
+            signature: signature.to_string(),
+            payer: self.payer.pubkey().to_string(),
+            intent: intent.clone(),
+            attempt,
+            status: JournalStatus::Pending,
+            error: None,
+        };
+        if let Err(error) = journal.record(&entry) {
+            warn!(event.name = "tx_journal_write_failed", tx.signature = %signature, ?error);
+        }
+    }
+
+    /// Journal and audit how waiting on `signature` ended. An RPC error leaves it pending,
+    /// for [`reconcile_journal`](Self::reconcile_journal) to settle.
+    fn record_outcome(
+        &self,
+        signature: Signature,
+        intent: &TxIntent,
+        outcome: &Result<Attempt, SubmitError>,
+    ) {
+        let (status, slot, error) = match outcome {
+            Ok(Attempt::Confirmed { slot }) => (JournalStatus::Confirmed, Some(*slot), None),
+            Ok(Attempt::Expired) => (JournalStatus::Expired, None, None),
+            Err(SubmitError::Failed(error)) => {
+                (JournalStatus::Failed, None, Some(error.to_string()))
+            }
+            Ok(Attempt::Pending) | Err(SubmitError::Retryable(_)) => return,
+        };
+        audit::record(
+            &self.reporting,
+            AuditRecord {
+                slot,
+                market_id: intent.market_id,
+                signature: Some(signature.to_string()),
+                error: error.clone(),
+                ..AuditRecord::new("tx", &intent.action, status.as_str())
+            },
+        );
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let result = journal.set_status(
+            &signature.to_string(),
+            status,
+            error.as_deref(),
+            chrono::Utc::now().timestamp(),
+        );
+        if let Err(error) = result {
+            warn!(event.name = "tx_journal_write_failed", tx.signature = %signature, ?error);
+        }
+    }
+
+    /// Poll `signature` until it confirms, fails, or expires, resending `transaction` every
+    /// [`rebroadcast_interval_blocks`](TxSenderConfig::rebroadcast_interval_blocks) while it
+    /// is pending. It has expired only once the block height is past
+    /// `last_valid_block_height`; a wait that times out first leaves it
+    /// [`Pending`](Attempt::Pending), as does one without a `last_valid_block_height` (a
+    /// durable-nonce transaction).
+    async fn confirm(
+        &self,
+        transaction: &Transaction,
+        signature: Signature,
+        last_valid_block_height: Option<u64>,
+    ) -> Result<Attempt, SubmitError> {
+        let mut last_sent_height = None;
+        let deadline = Instant::now() + self.config.confirm_timeout;
+        while Instant::now() < deadline {
+            sleep(CONFIRM_POLL_INTERVAL).await;
+
+            let status = self
+                .signature_status(&signature)
+                .await
+                .map_err(SubmitError::Retryable)?;
+            if let Some(status) = status {
+                if let Some(error) = &status.err {
+                    return Err(SubmitError::Failed(
+                        TransactionFailed {
+                            signature: Some(signature),
+                            error: error.clone(),
+                            logs: None,
+                        }
+                        .into(),
+                    ));
+                }
+                if status.satisfies_commitment(self.config.commitment) {
+                    return Ok(Attempt::Confirmed { slot: status.slot });
+                }
+                continue;
+            }
+
+            let block_height = self
+                .rpc
+                .get_block_height()
+                .await
+                .map_err(|error| SubmitError::Retryable(error.into()))?;
+            if last_valid_block_height.is_some_and(|last_valid| block_height > last_valid) {
+                return Ok(Attempt::Expired);
+            }
+            let interval = self.config.rebroadcast_interval_blocks;
+            match last_sent_height {
+                None => last_sent_height = Some(block_height),
+                Some(sent) if interval > 0 && block_height >= sent + interval => {
+                    last_sent_height = Some(block_height);
+                    match self.broadcast(transaction).await {
+                        Ok(_) => info!(
+                            event.name = "tx_rebroadcast",
+                            tx.signature = %signature,
+                            tx.block_height = block_height,
+                            monotonic_counter.tx_rebroadcasts_total = 1_u64,
+                        ),
+                        Err(error) => warn!(
+                            event.name = "tx_rebroadcast_failed",
+                            tx.signature = %signature,
+                            ?error,
+                        ),
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(Attempt::Pending)
+    }
+}
+
+/// The `percentile`th of `fees` (nearest rank), or 0 without any.
+fn fee_percentile(fees: &mut [u64], percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let rank = (fees.len() * usize::from(percentile)).div_ceil(100);
+    fees[rank.saturating_sub(1)]
+}
+
+/// The last slot of the leader after the one scheduled at `slot`, given the leaders of
+/// the slots from `slot` on. The end of the schedule when it shows no later leader.
+fn next_leader_last_slot(slot: u64, leaders: &[Pubkey]) -> u64 {
+    let Some(current) = leaders.first() else {
+        return slot;
+    };
+    let next_start = leaders
+        .iter()
+        .position(|leader| leader != current)
+        .unwrap_or(leaders.len());
+    let next_end = leaders[next_start..]
+        .iter()
+        .position(|leader| leader != &leaders[next_start])
+        .map_or(leaders.len(), |offset| next_start + offset);
+    slot + next_end as u64 - 1
+}
+
+/// The reference index to rebuild a send for after one built for `current` was held back
+/// because it would land at `expected_landing_slot`: that slot's window, and never
+/// `current` again.
+fn next_reference_index(expected_landing_slot: u64, end_slot_interval: u64, current: u64) -> u64 {
+    (expected_landing_slot / ARRAY_LENGTH / end_slot_interval).max(current + 1)
+}
+
+/// `units_consumed` plus `margin_pct` percent, capped at what a transaction may request.
+fn limit_with_margin(units_consumed: u64, margin_pct: u32) -> u32 {
+    let limit = units_consumed.saturating_mul(100 + u64::from(margin_pct)) / 100;
+    limit.min(u64::from(MAX_COMPUTE_UNIT_LIMIT)) as u32
+}
+
+enum SubmitError {
+    /// Landed and failed on-chain; resending won't help.
+    Failed(anyhow::Error),
+    /// The RPC couldn't be reached or rejected the submission.
+    Retryable(anyhow::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use base64::prelude::{BASE64_STANDARD, Engine};
+    use serde_json::{Value, json};
+    use solana_rpc_client::{
+        rpc_client::RpcClientConfig,
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use solana_rpc_client_api::{client_error::Result as ClientResult, request::RpcRequest};
+
+    use super::*;
+
+    #[test]
+    fn compute_budget_instructions_follow_config() {
+        let config = TxSenderConfig::default();
+        assert!(config.compute_budget_instructions().is_empty());
+
+        let config = TxSenderConfig {
+            compute_unit_price: Some(5_000),
+            compute_unit_limit: Some(200_000),
+            ..TxSenderConfig::default()
+        };
+        assert_eq!(
+            config.compute_budget_instructions(),
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+                ComputeBudgetInstruction::set_compute_unit_price(5_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn fee_percentile_uses_nearest_rank() {
+        assert_eq!(fee_percentile(&mut [], 75), 0);
+        let mut fees = [400, 0, 100, 300, 200];
+        assert_eq!(fee_percentile(&mut fees, 0), 0);
+        assert_eq!(fee_percentile(&mut fees, 50), 200);
+        assert_eq!(fee_percentile(&mut fees, 75), 300);
+        assert_eq!(fee_percentile(&mut fees, 100), 400);
+    }
+
+    #[test]
+    fn next_leader_last_slot_skips_the_current_rotation() {
+        let (a, b, c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        // Two slots left of a's rotation, then b for four.
+        assert_eq!(next_leader_last_slot(100, &[a, a, b, b, b, b, c, c]), 105);
+        assert_eq!(next_leader_last_slot(100, &[a, b, b, b, b, a]), 104);
+        assert_eq!(next_leader_last_slot(100, &[a, a, b, b]), 103);
+        assert_eq!(next_leader_last_slot(100, &[a, a, a]), 102);
+        assert_eq!(next_leader_last_slot(100, &[]), 100);
+    }
+
+    #[test]
+    fn rebuilds_target_a_later_window() {
+        let window = ARRAY_LENGTH * 2;
+        // Landing in window 4: rebuild for it.
+        assert_eq!(next_reference_index(4 * window + 3, 2, 3), 4);
+        // Held by the guard slots while still landing in window 3: move on to 4.
+        assert_eq!(next_reference_index(4 * window - 1, 2, 3), 4);
+        // Landing two windows on skips the one in between.
+        assert_eq!(next_reference_index(5 * window, 2, 3), 5);
+    }
+
+    #[test]
+    fn deadlines_parse_per_action() {
+        let deadlines =
+            parse_deadlines(" update_liquidity_flows=20000, submit_order=45000,").unwrap();
+        assert_eq!(
+            deadlines.get("update_liquidity_flows"),
+            Some(&Duration::from_secs(20))
+        );
+        assert_eq!(
+            deadlines.get("submit_order"),
+            Some(&Duration::from_secs(45))
+        );
+        assert!(parse_deadlines("").unwrap().is_empty());
+        assert!(parse_deadlines("submit_order").is_err());
+        assert!(parse_deadlines("submit_order=soon").is_err());
+    }
+
+    #[test]
+    fn compute_unit_limit_adds_margin_and_caps() {
+        assert_eq!(limit_with_margin(50_000, 20), 60_000);
+        assert_eq!(limit_with_margin(50_000, 0), 50_000);
+        assert_eq!(limit_with_margin(1_300_000, 50), MAX_COMPUTE_UNIT_LIMIT);
+    }
+
+    /// An RPC where a signature goes unseen for its first `unseen_polls` status checks and
+    /// has landed after, at a block height stuck at `block_height`. Keeps the signature of
+    /// every transaction sent to it in `sent`.
+    struct LandsLate {
+        unseen_polls: AtomicU32,
+        block_height: u64,
+        sent: Arc<std::sync::Mutex<Vec<Signature>>>,
+    }
+
+    impl LandsLate {
+        /// A sender over this RPC whose waits time out after the first status check.
+        fn sender(self, max_attempts: u32) -> TxSender {
+            let config = TxSenderConfig {
+                compute_unit_margin_pct: None,
+                simulate: false,
+                max_attempts,
+                retry_delay: Duration::ZERO,
+                confirm_timeout: Duration::from_millis(1),
+                rebroadcast_interval_blocks: 0,
+                ..TxSenderConfig::default()
+            };
+            let rpc =
+                RpcClient::new_sender(self, RpcClientConfig::with_commitment(config.commitment));
+            TxSender::new(rpc, Arc::new(Keypair::new()), config).unwrap()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl RpcSender for LandsLate {
+        async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+            let context = json!({ "slot": 1 });
+            Ok(match request {
+                RpcRequest::GetLatestBlockhash => json!({
+                    "context": context,
+                    "value": {
+                        "blockhash": Hash::new_unique().to_string(),
+                        "lastValidBlockHeight": LAST_VALID_BLOCK_HEIGHT,
+                    },
+                }),
+                RpcRequest::SendTransaction => {
+                    let encoded = BASE64_STANDARD.decode(params[0].as_str().unwrap()).unwrap();
+                    let transaction: Transaction = bincode::deserialize(&encoded).unwrap();
+                    self.sent.lock().unwrap().push(transaction.signatures[0]);
+                    json!(transaction.signatures[0].to_string())
+                }
+                RpcRequest::GetSignatureStatuses => {
+                    let unseen = self
+                        .unseen_polls
+                        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |polls| {
+                            polls.checked_sub(1)
+                        })
+                        .is_ok();
+                    let status = (!unseen).then(|| {
+                        json!({
+                            "slot": 5,
+                            "confirmations": null,
+                            "err": null,
+                            "status": { "Ok": null },
+                            "confirmationStatus": "confirmed",
+                        })
+                    });
+                    json!({ "context": context, "value": [status] })
+                }
+                RpcRequest::GetBlockHeight => json!(self.block_height),
+                other => panic!("unexpected {other} request"),
+            })
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "lands-late".to_string()
+        }
+    }
+
+    const LAST_VALID_BLOCK_HEIGHT: u64 = 100;
+
+    fn test_instructions() -> Vec<Instruction> {
+        vec![Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[1],
+            Vec::new(),
+        )]
+    }
+
+    #[tokio::test]
+    async fn send_that_times_out_resends_the_same_transaction_until_it_lands() {
+        let sent = Arc::default();
+        let sender = LandsLate {
+            unseen_polls: AtomicU32::new(1),
+            block_height: LAST_VALID_BLOCK_HEIGHT - 50,
+            sent: Arc::clone(&sent),
+        }
+        .sender(3);
+
+        let signature = sender.send(test_instructions()).await.unwrap();
+
+        assert_eq!(*sent.lock().unwrap(), vec![signature, signature]);
+    }
+
+    #[tokio::test]
+    async fn send_is_expired_only_once_the_block_height_passes_its_blockhash() {
+        let sent = Arc::<std::sync::Mutex<Vec<Signature>>>::default();
+        let error = LandsLate {
+            unseen_polls: AtomicU32::new(u32::MAX),
+            block_height: LAST_VALID_BLOCK_HEIGHT,
+            sent: Arc::clone(&sent),
+        }
+        .sender(2)
+        .send(test_instructions())
+        .await
+        .unwrap_err();
+        let unconfirmed = error.downcast_ref::<TxUnconfirmed>().unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![unconfirmed.signature; 2]);
+        assert_eq!(
+            unconfirmed.last_valid_block_height,
+            Some(LAST_VALID_BLOCK_HEIGHT)
+        );
+        assert!(!crate::TwobError::from(error).is_retryable());
+
+        let sent = Arc::<std::sync::Mutex<Vec<Signature>>>::default();
+        let error = LandsLate {
+            unseen_polls: AtomicU32::new(u32::MAX),
+            block_height: LAST_VALID_BLOCK_HEIGHT + 1,
+            sent: Arc::clone(&sent),
+        }
+        .sender(2)
+        .send(test_instructions())
+        .await
+        .unwrap_err();
+        let expired = error.downcast_ref::<TxExpired>().unwrap();
+        // Each expired attempt was signed anew.
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_ne!(sent[0], sent[1]);
+        assert_eq!(expired.signature, sent[1]);
+        assert!(crate::TwobError::from(error).is_retryable());
+    }
+}
@@ -0,0 +1,214 @@
+//! A pool of durable nonce accounts owned by the payer. A transaction whose blockhash is a
+//! nonce doesn't expire: it stays valid until the nonce is advanced, which the transaction
+//! itself does first thing. That is what a pre-signed emergency stop needs, and what lets
+//! a send ride out an RPC outage longer than a blockhash lives.
+//!
+//! Pool accounts are derived from the payer with a seed, so the same pool is found again
+//! after a restart and no extra keypairs need keeping.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use anchor_client::solana_sdk::{
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::Instruction,
+    nonce::state::{State, Versions},
+    pubkey::Pubkey,
+};
+use anyhow::Context;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_system_interface::instruction as system_instruction;
+use tracing::info;
+
+use super::{TxIntent, TxSender};
+
+const SEED_PREFIX: &str = "twob-nonce-";
+
+/// The payer's nonce accounts, handed out one at a time and round robin, so concurrent
+/// sends never share (and race to advance) a nonce.
+pub struct NonceManager {
+    authority: Pubkey,
+    accounts: Vec<Pubkey>,
+    pool: Arc<Mutex<Pool>>,
+}
+
+#[derive(Default)]
+struct Pool {
+    next: usize,
+    busy: HashSet<Pubkey>,
+    /// Taken for good by pre-signed transactions.
+    reserved: HashSet<Pubkey>,
+}
+
+impl NonceManager {
+    /// The pool of `size` accounts derived from `authority`, which pays for them and is the
+    /// only key that can advance them.
+    pub fn new(authority: Pubkey, size: usize) -> anyhow::Result<Self> {
+        let accounts = (0..size)
+            .map(|index| {
+                Pubkey::create_with_seed(
+                    &authority,
+                    &format!("{SEED_PREFIX}{index}"),
+                    &solana_system_interface::program::ID,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            authority,
+            accounts,
+            pool: Arc::new(Mutex::new(Pool::default())),
+        })
+    }
+
+    pub fn accounts(&self) -> &[Pubkey] {
+        &self.accounts
+    }
+
+    /// Create whichever pool accounts don't exist yet, through `sender`.
+    pub async fn create_missing(&self, sender: &TxSender) -> anyhow::Result<()> {
+        let rpc = sender.rpc();
+        let rent = rpc
+            .get_minimum_balance_for_rent_exemption(State::size())
+            .await?;
+        let existing = rpc.get_multiple_accounts(&self.accounts).await?;
+        for (index, (account, existing)) in self.accounts.iter().zip(existing).enumerate() {
+            if existing.is_some() {
+                continue;
+            }
+            let instructions = system_instruction::create_nonce_account_with_seed(
+                &self.authority,
+                account,
+                &self.authority,
+                &format!("{SEED_PREFIX}{index}"),
+                &self.authority,
+                rent,
+            );
+            let intent = TxIntent {
+                action: "create_nonce_account".to_string(),
+                ..TxIntent::default()
+            };
+            sender
+                .send_with_intent(&intent, instructions)
+                .await
+                .with_context(|| format!("Failed to create nonce account {}", account))?;
+            info!(event.name = "nonce_account_created", nonce.account = %account);
+        }
+        Ok(())
+    }
+
+    /// Take the next free account until the lease is dropped.
+    pub fn acquire(&self) -> anyhow::Result<NonceLease> {
+        let mut pool = self.lock();
+        let account = next_free(&self.accounts, &mut pool)
+            .context("Every nonce account is in use; raise TX_NONCE_POOL_SIZE")?;
+        pool.busy.insert(account);
+        Ok(NonceLease {
+            account,
+            pool: self.pool.clone(),
+        })
+    }
+
+    /// Take the next free account out of rotation for good, for a transaction signed now
+    /// and sent who knows when.
+    pub fn reserve(&self) -> anyhow::Result<Pubkey> {
+        let mut pool = self.lock();
+        let account = next_free(&self.accounts, &mut pool)
+            .context("Every nonce account is in use; raise TX_NONCE_POOL_SIZE")?;
+        pool.reserved.insert(account);
+        Ok(account)
+    }
+
+    /// The instruction a transaction using `account` must start with.
+    pub fn advance_instruction(&self, account: &Pubkey) -> Instruction {
+        system_instruction::advance_nonce_account(account, &self.authority)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Pool> {
+        self.pool
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The blockhash stored in nonce `account`: what a transaction using it is signed over.
+pub async fn current_nonce(
+    rpc: &RpcClient,
+    account: &Pubkey,
+    commitment: CommitmentConfig,
+) -> anyhow::Result<Hash> {
+    let data = rpc
+        .get_account_with_commitment(account, commitment)
+        .await?
+        .value
+        .with_context(|| format!("Nonce account {} does not exist", account))?
+        .data;
+    let versions: Versions = bincode::deserialize(&data)
+        .with_context(|| format!("Account {} is not a nonce account", account))?;
+    match versions.state() {
+        State::Initialized(data) => Ok(data.blockhash()),
+        State::Uninitialized => anyhow::bail!("Nonce account {} is not initialized", account),
+    }
+}
+
+/// A nonce account held for one send; back in the pool when dropped.
+pub struct NonceLease {
+    account: Pubkey,
+    pool: Arc<Mutex<Pool>>,
+}
+
+impl NonceLease {
+    pub fn account(&self) -> Pubkey {
+        self.account
+    }
+}
+
+impl Drop for NonceLease {
+    fn drop(&mut self) {
+        self.pool
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .busy
+            .remove(&self.account);
+    }
+}
+
+/// The first account from the rotation point on that is neither busy nor reserved, moving
+/// the rotation point past it.
+fn next_free(accounts: &[Pubkey], pool: &mut Pool) -> Option<Pubkey> {
+    (0..accounts.len())
+        .map(|offset| (pool.next + offset) % accounts.len())
+        .find(|&index| {
+            !pool.busy.contains(&accounts[index]) && !pool.reserved.contains(&accounts[index])
+        })
+        .map(|index| {
+            pool.next = index + 1;
+            accounts[index]
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_rotates_and_skips_held_accounts() {
+        let manager = NonceManager::new(Pubkey::new_unique(), 3).unwrap();
+        let [a, b, c] = [0, 1, 2].map(|index| manager.accounts()[index]);
+
+        let first = manager.acquire().unwrap();
+        assert_eq!(first.account(), a);
+        assert_eq!(manager.reserve().unwrap(), b);
+        let second = manager.acquire().unwrap();
+        assert_eq!(second.account(), c);
+        assert!(manager.acquire().is_err());
+
+        drop(first);
+        assert_eq!(manager.acquire().unwrap().account(), a);
+        // Derived, so the same pool comes back after a restart.
+        let again = NonceManager::new(manager.authority, 3).unwrap();
+        assert_eq!(again.accounts(), manager.accounts());
+    }
+}
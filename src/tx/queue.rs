@@ -0,0 +1,454 @@
+//! A queue in front of a [`TxSender`]. Sends are queued by [`TxPriority`] and a single
+//! loop drains the queue one transaction at a time, so a stop is never left bidding for
+//! the same blockhash and compute budget as a crank that happened to be built first.
+//! Lower classes are rate limited, and a send queued for the same action and market as
+//! one still waiting replaces it: only the newest flow update matters.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anchor_client::solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signature},
+};
+use anyhow::Context;
+use tokio::{
+    sync::{Notify, oneshot},
+    time::sleep_until,
+};
+use tracing::{Instrument, Span, info, warn};
+
+use crate::config::{env_var, inherit};
+
+use super::{SendOptions, TxIntent, TxSender};
+
+/// Send classes, most urgent first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TxPriority {
+    /// Stops and quote pulls: anything sent [`critical`](SendOptions::critical).
+    Stop,
+    /// Flow updates and the orders and liquidity moves that go with them.
+    UpdateFlows,
+    /// Keeping market state moving for everyone, e.g. book updates.
+    Crank,
+    /// Setup and cleanup nobody is waiting on.
+    Housekeeping,
+}
+
+impl TxPriority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::UpdateFlows => "update_flows",
+            Self::Crank => "crank",
+            Self::Housekeeping => "housekeeping",
+        }
+    }
+
+    /// The class of a send: the intent's own when it names one, else from how urgently
+    /// it is sent and what it does.
+    pub fn of(intent: &TxIntent, options: &SendOptions) -> Self {
+        if let Some(priority) = intent.priority {
+            return priority;
+        }
+        if options.critical {
+            return Self::Stop;
+        }
+        match intent.action.as_str() {
+            "update_liquidity_flows"
+            | "open_next_window"
+            | "submit_order"
+            | "authority_close_position"
+            | "public_close_position"
+            | "provide_liquidity"
+            | "add_liquidity"
+            | "withdraw_liquidity" => Self::UpdateFlows,
+            _ => Self::Housekeeping,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxQueueConfig {
+    /// Least time between two crank sends.
+    pub crank_min_interval: Duration,
+    /// Least time between two housekeeping sends.
+    pub housekeeping_min_interval: Duration,
+}
+
+impl Default for TxQueueConfig {
+    fn default() -> Self {
+        Self {
+            crank_min_interval: Duration::from_secs(2),
+            housekeeping_min_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl TxQueueConfig {
+    /// `None` unless `TX_QUEUE` is true.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let enabled = match env_var("TX_QUEUE") {
+            Ok(value) if !value.trim().is_empty() => value
+                .trim()
+                .parse::<bool>()
+                .map_err(|e| anyhow::anyhow!("Invalid TX_QUEUE: {}", e))?,
+            _ => false,
+        };
+        if !enabled {
+            return Ok(None);
+        }
+
+        let defaults = Self::default();
+        let interval = |name: &str, default: Duration| -> anyhow::Result<Duration> {
+            match env_var(name) {
+                Ok(value) if !value.trim().is_empty() => value
+                    .trim()
+                    .parse::<u64>()
+                    .map(Duration::from_millis)
+                    .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e)),
+                _ => Ok(default),
+            }
+        };
+        Ok(Some(Self {
+            crank_min_interval: interval(
+                "TX_QUEUE_CRANK_INTERVAL_MS",
+                defaults.crank_min_interval,
+            )?,
+            housekeeping_min_interval: interval(
+                "TX_QUEUE_HOUSEKEEPING_INTERVAL_MS",
+                defaults.housekeeping_min_interval,
+            )?,
+        }))
+    }
+
+    fn min_interval(&self, priority: TxPriority) -> Duration {
+        match priority {
+            TxPriority::Stop | TxPriority::UpdateFlows => Duration::ZERO,
+            TxPriority::Crank => self.crank_min_interval,
+            TxPriority::Housekeeping => self.housekeeping_min_interval,
+        }
+    }
+}
+
+/// A queued send was replaced by a newer one for the same action and market before it
+/// went out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Superseded {
+    pub action: String,
+    pub market_id: Option<u64>,
+}
+
+impl std::fmt::Display for Superseded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} superseded by a newer one", self.action)?;
+        if let Some(market_id) = self.market_id {
+            write!(f, " for market {}", market_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Superseded {}
+
+struct Job {
+    priority: TxPriority,
+    intent: TxIntent,
+    instructions: Vec<Instruction>,
+    extra_signers: Vec<Keypair>,
+    options: SendOptions,
+    queued_at: Instant,
+    /// The caller's span, so the send is traced as part of the action that made it.
+    span: Span,
+    reply: oneshot::Sender<anyhow::Result<Signature>>,
+}
+
+impl Job {
+    /// Whether `other` sends the same thing, newer. Sends that don't name a market never
+    /// replace each other.
+    fn replaced_by(&self, other: &Job) -> bool {
+        self.intent.market_id.is_some()
+            && self.intent.market_id == other.intent.market_id
+            && self.intent.action == other.intent.action
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Oldest first.
+    jobs: Vec<Job>,
+    last_sent: [Option<Instant>; 4],
+}
+
+/// Handle to a running send loop. Cloned into every sender sharing it.
+#[derive(Clone)]
+pub struct TxQueue {
+    state: Arc<Mutex<QueueState>>,
+    notify: Arc<Notify>,
+}
+
+impl TxQueue {
+    /// Spawn the loop that sends everything queued through `sender`, which must not
+    /// itself have a queue, with the settings of the bot starting it.
+    pub fn start(sender: TxSender, config: TxQueueConfig) -> Self {
+        let queue = Self {
+            state: Arc::new(Mutex::new(QueueState::default())),
+            notify: Arc::new(Notify::new()),
+        };
+        tokio::spawn(inherit(queue.clone().run(sender, config)));
+        queue
+    }
+
+    /// Queue a send and wait for the loop to carry it out.
+    pub async fn send(
+        &self,
+        intent: &TxIntent,
+        instructions: Vec<Instruction>,
+        extra_signers: &[&Keypair],
+        options: SendOptions,
+    ) -> anyhow::Result<Signature> {
+        let (reply, outcome) = oneshot::channel();
+        let job = Job {
+            priority: TxPriority::of(intent, &options),
+            intent: intent.clone(),
+            instructions,
+            extra_signers: extra_signers
+                .iter()
+                .map(|signer| signer.insecure_clone())
+                .collect(),
+            options,
+            queued_at: Instant::now(),
+            span: Span::current(),
+            reply,
+        };
+        let priority = job.priority;
+        let (superseded, depth) = {
+            let mut state = self.lock();
+            let superseded = push(&mut state.jobs, job);
+            (superseded, state.jobs.len())
+        };
+        if let Some(old) = superseded {
+            info!(
+                event.name = "tx_superseded",
+                tx.action = old.intent.action.as_str(),
+                tx.market_id = old.intent.market_id,
+                monotonic_counter.tx_superseded_total = 1_u64,
+            );
+            let _ = old.reply.send(Err(Superseded {
+                action: old.intent.action,
+                market_id: old.intent.market_id,
+            }
+            .into()));
+        }
+        info!(
+            event.name = "tx_queued",
+            tx.action = intent.action.as_str(),
+            tx.priority = priority.as_str(),
+            gauge.tx_queue_depth = depth as u64,
+        );
+        self.notify.notify_one();
+
+        outcome
+            .await
+            .context("Transaction send loop stopped before sending")?
+    }
+
+    async fn run(self, sender: TxSender, config: TxQueueConfig) {
+        loop {
+            let now = Instant::now();
+            let next = {
+                let mut state = self.lock();
+                let priorities: Vec<TxPriority> =
+                    state.jobs.iter().map(|job| job.priority).collect();
+                match next_ready(&priorities, &state.last_sent, &config, now) {
+                    Ok(index) => {
+                        let job = state.jobs.remove(index);
+                        state.last_sent[job.priority as usize] = Some(now);
+                        Ok(job)
+                    }
+                    Err(wait_until) => Err(wait_until),
+                }
+            };
+
+            let job = match next {
+                Ok(job) => job,
+                Err(Some(wait_until)) => {
+                    tokio::select! {
+                        _ = self.notify.notified() => {}
+                        _ = sleep_until(wait_until.into()) => {}
+                    }
+                    continue;
+                }
+                Err(None) => {
+                    self.notify.notified().await;
+                    continue;
+                }
+            };
+
+            info!(
+                event.name = "tx_dequeued",
+                tx.action = job.intent.action.as_str(),
+                tx.priority = job.priority.as_str(),
+                histogram.tx_queue_wait_ms = job.queued_at.elapsed().as_millis() as u64,
+            );
+            let signers: Vec<&Keypair> = job.extra_signers.iter().collect();
+            let outcome = sender
+                .with_options(job.options)
+                .send_inner(&job.intent, job.instructions, &signers)
+                .instrument(job.span)
+                .await;
+            if job.reply.send(outcome).is_err() {
+                warn!(
+                    event.name = "tx_queue_caller_gone",
+                    tx.action = job.intent.action.as_str()
+                );
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Add `job` to the queue, in place of a waiting job it replaces, which is returned.
+fn push(jobs: &mut Vec<Job>, job: Job) -> Option<Job> {
+    match jobs.iter().position(|queued| queued.replaced_by(&job)) {
+        Some(index) => Some(std::mem::replace(&mut jobs[index], job)),
+        None => {
+            jobs.push(job);
+            None
+        }
+    }
+}
+
+/// The index of the oldest job of the most urgent class not held back by its rate limit,
+/// or, when every queued job is held back, when the first of them may go.
+fn next_ready(
+    priorities: &[TxPriority],
+    last_sent: &[Option<Instant>; 4],
+    config: &TxQueueConfig,
+    now: Instant,
+) -> Result<usize, Option<Instant>> {
+    let ready_at = |priority: TxPriority| {
+        last_sent[priority as usize].map(|sent| sent + config.min_interval(priority))
+    };
+    priorities
+        .iter()
+        .enumerate()
+        .filter(|(_, priority)| ready_at(**priority).is_none_or(|ready| ready <= now))
+        .min_by_key(|(index, priority)| (**priority, *index))
+        .map(|(index, _)| index)
+        .ok_or_else(|| {
+            priorities
+                .iter()
+                .filter_map(|priority| ready_at(*priority))
+                .min()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(
+        action: &str,
+        market_id: Option<u64>,
+    ) -> (Job, oneshot::Receiver<anyhow::Result<Signature>>) {
+        let (reply, outcome) = oneshot::channel();
+        let job = Job {
+            priority: TxPriority::Housekeeping,
+            intent: TxIntent {
+                action: action.to_string(),
+                market_id,
+                ..TxIntent::default()
+            },
+            instructions: Vec::new(),
+            extra_signers: Vec::new(),
+            options: SendOptions::default(),
+            queued_at: Instant::now(),
+            span: Span::current(),
+            reply,
+        };
+        (job, outcome)
+    }
+
+    #[test]
+    fn stops_jump_the_queue_and_cranks_wait_their_turn() {
+        use TxPriority::*;
+        let config = TxQueueConfig::default();
+        let now = Instant::now();
+        let never = [None; 4];
+
+        assert_eq!(
+            next_ready(&[Crank, UpdateFlows, Stop, Stop], &never, &config, now),
+            Ok(2)
+        );
+        assert_eq!(
+            next_ready(&[Housekeeping, Crank], &never, &config, now),
+            Ok(1)
+        );
+        assert_eq!(next_ready(&[], &never, &config, now), Err(None));
+
+        let mut last_sent = never;
+        last_sent[Crank as usize] = Some(now);
+        assert_eq!(
+            next_ready(&[Crank, Housekeeping], &last_sent, &config, now),
+            Ok(1)
+        );
+        assert_eq!(
+            next_ready(&[Crank], &last_sent, &config, now),
+            Err(Some(now + config.crank_min_interval))
+        );
+        // Flow updates are never rate limited.
+        last_sent[UpdateFlows as usize] = Some(now);
+        assert_eq!(
+            next_ready(&[Crank, UpdateFlows], &last_sent, &config, now),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn newer_send_for_the_same_market_replaces_the_waiting_one() {
+        let mut jobs = Vec::new();
+        assert!(push(&mut jobs, job("update_liquidity_flows", Some(1)).0).is_none());
+        assert!(push(&mut jobs, job("update_liquidity_flows", Some(2)).0).is_none());
+        assert!(push(&mut jobs, job("unlabeled", None).0).is_none());
+        assert!(push(&mut jobs, job("unlabeled", None).0).is_none());
+
+        let (mut newer, _) = job("update_liquidity_flows", Some(1));
+        newer.intent.reference_index = Some(7);
+        let replaced = push(&mut jobs, newer).unwrap();
+        assert_eq!(replaced.intent.market_id, Some(1));
+        assert_eq!(replaced.intent.reference_index, None);
+        assert_eq!(jobs.len(), 4);
+        // The replacement keeps the old one's place in line.
+        assert_eq!(jobs[0].intent.reference_index, Some(7));
+    }
+
+    #[test]
+    fn critical_sends_are_stops() {
+        let intent = TxIntent::new("update_liquidity_flows", 1);
+        assert_eq!(
+            TxPriority::of(&intent, &SendOptions::default()),
+            TxPriority::UpdateFlows
+        );
+        assert_eq!(
+            TxPriority::of(&intent, &SendOptions::urgent()),
+            TxPriority::Stop
+        );
+        let crank = TxIntent::new("update_books", 1).priority(TxPriority::Crank);
+        assert_eq!(
+            TxPriority::of(&crank, &SendOptions::urgent()),
+            TxPriority::Crank
+        );
+        assert_eq!(
+            TxPriority::of(&TxIntent::unlabeled(), &SendOptions::default()),
+            TxPriority::Housekeeping
+        );
+    }
+}
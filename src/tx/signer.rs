@@ -0,0 +1,491 @@
+//! Who signs for the payer. A [`TxSender`](super::TxSender) only asks its [`TxSigner`] for
+//! a signature over each message, so the key can be a local [`Keypair`], a Ledger (built
+//! with the `ledger` feature) or a remote signing service reached over HTTP, e.g. one
+//! fronting a cloud KMS key. With the latter two, no raw private key has to sit in the
+//! bot's environment.
+//!
+//! A local keypair file may be [age](https://age-encryption.org)-encrypted, to a
+//! passphrase or to an age identity (built with the `encrypted-keypair` feature), so a hot
+//! key isn't left in plaintext on disk. See [`keypair_from_file`].
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anchor_client::solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+use anyhow::Context;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures::future::BoxFuture;
+use serde_json::{Value, json};
+
+use crate::config::env_var;
+
+/// Signs transaction messages for one pubkey.
+pub trait TxSigner: Send + Sync {
+    /// Where signatures come from, for logs: `local`, `ledger` or `remote`.
+    fn name(&self) -> &str;
+
+    fn pubkey(&self) -> Pubkey;
+
+    /// Sign a serialized transaction message.
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, anyhow::Result<Signature>>;
+}
+
+impl TxSigner for Keypair {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn pubkey(&self) -> Pubkey {
+        Signer::pubkey(self)
+    }
+
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, anyhow::Result<Signature>> {
+        Box::pin(async move { Ok(Signer::try_sign_message(self, message)?) })
+    }
+}
+
+/// A signing service that holds the key and signs over HTTP. It is sent
+/// `{"pubkey", "message"}` (the message base64-encoded) at `POST {url}/sign` and answers
+/// `{"signature"}` in base58. Every signature it returns is checked against the pubkey
+/// before use.
+pub struct RemoteSigner {
+    http: reqwest::Client,
+    url: String,
+    pubkey: Pubkey,
+    auth_token: Option<String>,
+}
+
+impl RemoteSigner {
+    pub fn new(url: &str, pubkey: Pubkey, auth_token: Option<String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            url: url.trim_end_matches('/').to_string(),
+            pubkey,
+            auth_token,
+        })
+    }
+}
+
+impl TxSigner for RemoteSigner {
+    fn name(&self) -> &str {
+        "remote"
+    }
+
+    fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, anyhow::Result<Signature>> {
+        Box::pin(async move {
+            let mut request = self.http.post(format!("{}/sign", self.url)).json(&json!({
+                "pubkey": self.pubkey.to_string(),
+                "message": STANDARD.encode(message),
+            }));
+            if let Some(token) = &self.auth_token {
+                request = request.bearer_auth(token);
+            }
+            let response: Value = request
+                .send()
+                .await
+                .context("Failed to reach remote signer")?
+                .error_for_status()
+                .context("Remote signer refused to sign")?
+                .json()
+                .await
+                .context("Invalid response from remote signer")?;
+
+            let signature: Signature = response
+                .get("signature")
+                .and_then(Value::as_str)
+                .context("Remote signer returned no signature")?
+                .parse()
+                .context("Remote signer returned an invalid signature")?;
+            anyhow::ensure!(
+                signature.verify(self.pubkey.as_ref(), message),
+                "Remote signer returned a signature that doesn't verify for {}",
+                self.pubkey
+            );
+            Ok(signature)
+        })
+    }
+}
+
+/// A key on a Ledger. Every signature has to be approved on the device, so this suits
+/// rare, deliberate sends (a manual stop, a treasury move) more than a bot's flow updates.
+///
+/// The device handle can't leave the thread that opened it, so that thread stays up for
+/// the signer's lifetime and signs each message sent to it.
+#[cfg(feature = "ledger")]
+pub struct LedgerSigner {
+    pubkey: Pubkey,
+    requests: std::sync::mpsc::Sender<LedgerRequest>,
+}
+
+#[cfg(feature = "ledger")]
+type LedgerRequest = (
+    Vec<u8>,
+    tokio::sync::oneshot::Sender<anyhow::Result<Signature>>,
+);
+
+#[cfg(feature = "ledger")]
+impl LedgerSigner {
+    /// The key at `derivation_path` (e.g. `0/0`; the device's default key when `None`) on
+    /// the wallet at `locator` (e.g. `usb://ledger`).
+    pub fn connect(locator: &str, derivation_path: Option<&str>) -> anyhow::Result<Self> {
+        use solana_derivation_path::DerivationPath;
+        use solana_remote_wallet::{
+            locator::Locator, remote_keypair::generate_remote_keypair,
+            remote_wallet::maybe_wallet_manager,
+        };
+
+        let locator = Locator::new_from_path(locator)?;
+        let derivation_path = derivation_path
+            .map(DerivationPath::from_key_str)
+            .transpose()?
+            .unwrap_or_default();
+        let (connected, connection) = std::sync::mpsc::channel();
+        let (requests, pending) = std::sync::mpsc::channel::<LedgerRequest>();
+        std::thread::Builder::new()
+            .name("ledger-signer".to_string())
+            .spawn(move || {
+                let keypair = (|| -> anyhow::Result<_> {
+                    let manager = maybe_wallet_manager()?.context("No hardware wallet found")?;
+                    Ok(generate_remote_keypair(
+                        locator,
+                        derivation_path,
+                        &manager,
+                        false,
+                        "payer",
+                    )?)
+                })();
+                let keypair = match keypair {
+                    Ok(keypair) => {
+                        let _ = connected.send(Ok(Signer::pubkey(&keypair)));
+                        keypair
+                    }
+                    Err(error) => {
+                        let _ = connected.send(Err(error));
+                        return;
+                    }
+                };
+                // Blocks until the device answers, which waits on a person.
+                for (message, reply) in pending {
+                    let _ = reply.send(keypair.try_sign_message(&message).map_err(Into::into));
+                }
+            })?;
+        let pubkey = connection
+            .recv()
+            .context("Ledger signer thread exited before connecting")??;
+        Ok(Self { pubkey, requests })
+    }
+}
+
+#[cfg(feature = "ledger")]
+impl TxSigner for LedgerSigner {
+    fn name(&self) -> &str {
+        "ledger"
+    }
+
+    fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, anyhow::Result<Signature>> {
+        Box::pin(async move {
+            let (reply, signature) = tokio::sync::oneshot::channel();
+            self.requests
+                .send((message.to_vec(), reply))
+                .context("Ledger signer thread has exited")?;
+            signature.await.context("Ledger signer thread has exited")?
+        })
+    }
+}
+
+/// Which [`TxSigner`] a bot signs with, from `TX_SIGNER`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignerConfig {
+    /// A keypair from the bot's own keypair env var. The default.
+    Local,
+    /// `TX_REMOTE_SIGNER_URL`, signing for `TX_REMOTE_SIGNER_PUBKEY`, authenticated with
+    /// `TX_REMOTE_SIGNER_TOKEN` when set.
+    Remote {
+        url: String,
+        pubkey: Pubkey,
+        auth_token: Option<String>,
+    },
+    /// `TX_LEDGER_LOCATOR` (default `usb://ledger`) at `TX_LEDGER_DERIVATION_PATH`.
+    Ledger {
+        locator: String,
+        derivation_path: Option<String>,
+    },
+}
+
+impl SignerConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str| {
+            env_var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        match var("TX_SIGNER").as_deref().unwrap_or("local") {
+            "local" => Ok(Self::Local),
+            "remote" => Ok(Self::Remote {
+                url: var("TX_REMOTE_SIGNER_URL")
+                    .context("TX_SIGNER=remote needs TX_REMOTE_SIGNER_URL")?,
+                pubkey: var("TX_REMOTE_SIGNER_PUBKEY")
+                    .context("TX_SIGNER=remote needs TX_REMOTE_SIGNER_PUBKEY")?
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid TX_REMOTE_SIGNER_PUBKEY: {}", e))?,
+                auth_token: var("TX_REMOTE_SIGNER_TOKEN"),
+            }),
+            "ledger" => Ok(Self::Ledger {
+                locator: var("TX_LEDGER_LOCATOR").unwrap_or_else(|| "usb://ledger".to_string()),
+                derivation_path: var("TX_LEDGER_DERIVATION_PATH"),
+            }),
+            other => anyhow::bail!(
+                "Invalid TX_SIGNER: {} (expected local, remote or ledger)",
+                other
+            ),
+        }
+    }
+
+    /// The configured signer. A local one reads its keypair from env var `keypair_var`,
+    /// which the others don't need set.
+    pub fn connect(&self, keypair_var: &str) -> anyhow::Result<Arc<dyn TxSigner>> {
+        match self {
+            Self::Local => Ok(Arc::new(keypair_from_env(keypair_var)?)),
+            Self::Remote {
+                url,
+                pubkey,
+                auth_token,
+            } => Ok(Arc::new(RemoteSigner::new(
+                url,
+                *pubkey,
+                auth_token.clone(),
+            )?)),
+            #[cfg(feature = "ledger")]
+            Self::Ledger {
+                locator,
+                derivation_path,
+            } => Ok(Arc::new(LedgerSigner::connect(
+                locator,
+                derivation_path.as_deref(),
+            )?)),
+            #[cfg(not(feature = "ledger"))]
+            Self::Ledger { .. } => {
+                anyhow::bail!("TX_SIGNER=ledger needs a build with the `ledger` feature")
+            }
+        }
+    }
+}
+
+/// The keypair for env var `name`, as the JSON byte array `solana-keygen` writes. Taken
+/// from the first of:
+///
+/// - `name` itself, holding the array;
+/// - a file at `<name>_PATH`, then at `KEYPAIR_PATH`;
+/// - the `keypair_path` in the Solana CLI config (`~/.config/solana/cli/config.yml`);
+/// - `~/.config/solana/id.json`.
+///
+/// A path keeps the key out of the environment, where it would show up in shell history
+/// and to anyone who can list the process's environment.
+pub fn keypair_from_env(name: &str) -> anyhow::Result<Keypair> {
+    let var = |name: &str| {
+        env_var(name)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    if let Some(value) = var(name) {
+        return keypair_from_json(&value).with_context(|| format!("Invalid {name}"));
+    }
+    let path = var(&format!("{name}_PATH"))
+        .or_else(|| var("KEYPAIR_PATH"))
+        .map(|path| expand_home(&path))
+        .or_else(solana_cli_keypair_path)
+        .with_context(|| {
+            format!(
+                "{name} not set: set it, {name}_PATH or KEYPAIR_PATH, or create a Solana CLI \
+                 keypair"
+            )
+        })?;
+    keypair_from_file(&path)
+}
+
+/// The keypair in the JSON byte array file at `path`.
+///
+/// The file may be age-encrypted, binary or armored. One encrypted to a passphrase is
+/// decrypted with the contents of the file at `KEYPAIR_PASSPHRASE_FILE` (e.g. a mounted
+/// secret), else `KEYPAIR_PASSPHRASE`, else one typed at a prompt when stdin is a
+/// terminal. One encrypted to a recipient needs the identity file at
+/// `KEYPAIR_AGE_IDENTITY`.
+pub fn keypair_from_file(path: &Path) -> anyhow::Result<Keypair> {
+    let contents = std::fs::read(path)
+        .with_context(|| format!("Failed to read keypair file {}", path.display()))?;
+    let json = if is_age_encrypted(&contents) {
+        decrypt_keypair_file(path, &contents)?
+    } else {
+        contents
+    };
+    let json = std::str::from_utf8(&json)
+        .with_context(|| format!("Invalid keypair file {}", path.display()))?;
+    keypair_from_json(json).with_context(|| format!("Invalid keypair file {}", path.display()))
+}
+
+fn keypair_from_json(json: &str) -> anyhow::Result<Keypair> {
+    let keypair_bytes: Vec<u8> = serde_json::from_str(json)?;
+    Keypair::try_from(keypair_bytes.as_slice())
+        .map_err(|e| anyhow::anyhow!("Invalid keypair: {}", e))
+}
+
+fn is_age_encrypted(contents: &[u8]) -> bool {
+    contents.starts_with(b"age-encryption.org/")
+        || contents.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+}
+
+#[cfg(feature = "encrypted-keypair")]
+fn decrypt_keypair_file(path: &Path, contents: &[u8]) -> anyhow::Result<Vec<u8>> {
+    use std::io::Read;
+
+    use age::{Identity, armor::ArmoredReader, secrecy::SecretString};
+
+    let decryptor = age::Decryptor::new(ArmoredReader::new(contents))
+        .with_context(|| format!("Invalid encrypted keypair file {}", path.display()))?;
+    let mut reader = if decryptor.is_scrypt() {
+        let identity = age::scrypt::Identity::new(SecretString::from(keypair_passphrase(path)?));
+        decryptor.decrypt(std::iter::once(&identity as &dyn Identity))
+    } else {
+        let identity_path = env_var("KEYPAIR_AGE_IDENTITY")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .with_context(|| {
+                format!(
+                    "{} is encrypted to an age recipient; set KEYPAIR_AGE_IDENTITY",
+                    path.display()
+                )
+            })?;
+        let identities = age::IdentityFile::from_file(identity_path.clone())
+            .with_context(|| format!("Failed to read age identity {identity_path}"))?
+            .into_identities()?;
+        decryptor.decrypt(
+            identities
+                .iter()
+                .map(|identity| identity.as_ref() as &dyn Identity),
+        )
+    }
+    .with_context(|| format!("Failed to decrypt keypair file {}", path.display()))?;
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+#[cfg(not(feature = "encrypted-keypair"))]
+fn decrypt_keypair_file(path: &Path, _contents: &[u8]) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!(
+        "{} is encrypted; decrypting it needs a build with the `encrypted-keypair` feature",
+        path.display()
+    )
+}
+
+/// The passphrase for the encrypted keypair file at `path`.
+#[cfg(feature = "encrypted-keypair")]
+fn keypair_passphrase(path: &Path) -> anyhow::Result<String> {
+    use std::io::IsTerminal;
+
+    if let Some(file) = env_var("KEYPAIR_PASSPHRASE_FILE")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+    {
+        let passphrase = std::fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read KEYPAIR_PASSPHRASE_FILE {file}"))?;
+        return Ok(passphrase.trim_end_matches(['\r', '\n']).to_string());
+    }
+    if let Ok(passphrase) = env_var("KEYPAIR_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    anyhow::ensure!(
+        std::io::stdin().is_terminal(),
+        "{} is encrypted; set KEYPAIR_PASSPHRASE_FILE or run from a terminal to be prompted",
+        path.display()
+    );
+    Ok(rpassword::prompt_password(format!(
+        "Passphrase for {}: ",
+        path.display()
+    ))?)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The Solana CLI's keypair: its config's `keypair_path`, else its default `id.json`, if
+/// the file exists.
+fn solana_cli_keypair_path() -> Option<PathBuf> {
+    let solana_dir = PathBuf::from(env::var_os("HOME")?).join(".config/solana");
+    std::fs::read_to_string(solana_dir.join("cli/config.yml"))
+        .ok()
+        .and_then(|config| cli_config_keypair_path(&config))
+        .map(|path| expand_home(&path))
+        .into_iter()
+        .chain([solana_dir.join("id.json")])
+        .find(|path| path.is_file())
+}
+
+/// `keypair_path` from a Solana CLI config file.
+fn cli_config_keypair_path(config: &str) -> Option<String> {
+    config.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("keypair_path:")?.trim();
+        let value = value.trim_matches(|c| c == '"' || c == '\'');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn local_signer_signs_for_its_pubkey() {
+        let keypair = Keypair::new();
+        let signer: Arc<dyn TxSigner> = Arc::new(keypair.insecure_clone());
+        let signature = signer.sign_message(b"message").await.unwrap();
+
+        assert_eq!(signer.pubkey(), Signer::pubkey(&keypair));
+        assert!(signature.verify(signer.pubkey().as_ref(), b"message"));
+    }
+
+    #[test]
+    fn recognizes_age_encrypted_files() {
+        assert!(is_age_encrypted(b"age-encryption.org/v1\n-> scrypt"));
+        assert!(is_age_encrypted(
+            b"-----BEGIN AGE ENCRYPTED FILE-----\nYWdlLWVuY3J5cHRpb24ub3JnL3YxCi0+"
+        ));
+        assert!(!is_age_encrypted(b"[1,2,3]"));
+    }
+
+    #[test]
+    fn reads_keypair_path_from_solana_cli_config() {
+        let config = "---\njson_rpc_url: https://api.devnet.solana.com\n\
+                      keypair_path: \"/home/mm/.config/solana/bot.json\"\ncommitment: confirmed\n";
+
+        assert_eq!(
+            cli_config_keypair_path(config).as_deref(),
+            Some("/home/mm/.config/solana/bot.json")
+        );
+        assert_eq!(cli_config_keypair_path("commitment: confirmed\n"), None);
+    }
+}
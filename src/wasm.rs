@@ -0,0 +1,204 @@
+//! JavaScript bindings for web frontends, built with the `wasm` feature for
+//! `wasm32-unknown-unknown`: the PDA [`accounts`](crate::accounts) resolvers and the
+//! balance and runway math of [`core`](crate::core), so a frontend shows the numbers the
+//! bots act on rather than a TypeScript port of them.
+//!
+//! Addresses go in and out as base58 strings and account data as the raw bytes an RPC
+//! returns, so nothing here reaches the network. `u64`s are `BigInt`s on the JavaScript
+//! side.
+//!
+//! ```sh
+//! wasm-pack build --target web -- --no-default-features --features wasm
+//! ```
+
+use std::collections::BTreeMap;
+
+use anchor_lang::{AccountDeserialize, prelude::Pubkey};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    accounts::AccountResolver,
+    core::{LiquidityPositionBalances, exits_window, position_balances, slots_until_debt},
+    twob_anchor::{
+        self,
+        accounts::{Bookkeeping, Exits, LiquidityPosition, Market},
+    },
+};
+
+/// Derives the program's addresses; `programId` defaults to the TwoB program.
+#[wasm_bindgen]
+pub struct Resolver {
+    inner: AccountResolver,
+}
+
+#[wasm_bindgen]
+impl Resolver {
+    #[wasm_bindgen(constructor)]
+    pub fn new(program_id: Option<String>) -> Result<Resolver, JsError> {
+        let program_id = match program_id {
+            Some(program_id) => pubkey(&program_id)?,
+            None => twob_anchor::ID,
+        };
+        Ok(Self {
+            inner: AccountResolver::new(program_id),
+        })
+    }
+
+    #[wasm_bindgen(js_name = programConfig)]
+    pub fn program_config(&self) -> String {
+        self.inner.program_config_pda().address().to_string()
+    }
+
+    pub fn market(&self, market_id: u64) -> String {
+        self.inner.market_pda(market_id).address().to_string()
+    }
+
+    pub fn bookkeeping(&self, market: &str) -> Result<String, JsError> {
+        Ok(self
+            .inner
+            .bookkeeping_pda(&pubkey(market)?)
+            .address()
+            .to_string())
+    }
+
+    #[wasm_bindgen(js_name = liquidityPosition)]
+    pub fn liquidity_position(&self, market: &str, authority: &str) -> Result<String, JsError> {
+        Ok(self
+            .inner
+            .liquidity_position_pda(&pubkey(market)?, &pubkey(authority)?)
+            .address()
+            .to_string())
+    }
+
+    #[wasm_bindgen(js_name = tradePosition)]
+    pub fn trade_position(
+        &self,
+        market: &str,
+        authority: &str,
+        position_id: u64,
+    ) -> Result<String, JsError> {
+        Ok(self
+            .inner
+            .trade_position_pda(&pubkey(market)?, &pubkey(authority)?, position_id)
+            .address()
+            .to_string())
+    }
+
+    pub fn exits(&self, market: &str, index: u64) -> Result<String, JsError> {
+        Ok(self
+            .inner
+            .exits_pda(&pubkey(market)?, index)
+            .address()
+            .to_string())
+    }
+
+    pub fn prices(&self, market: &str, index: u64) -> Result<String, JsError> {
+        Ok(self
+            .inner
+            .prices_pda(&pubkey(market)?, index)
+            .address()
+            .to_string())
+    }
+
+    #[wasm_bindgen(js_name = associatedTokenAccount)]
+    pub fn associated_token_account(&self, wallet: &str, mint: &str) -> Result<String, JsError> {
+        Ok(self
+            .inner
+            .associated_token_account(&pubkey(wallet)?, &pubkey(mint)?)
+            .to_string())
+    }
+}
+
+/// A liquidity position with the market accounts its balances are brought forward from.
+///
+/// The exits accounts to add are those whose indexes [`exitsIndexes`](Self::exits_indexes)
+/// returns for the slot asked about; one left out is read as having no exits.
+#[wasm_bindgen]
+pub struct Position {
+    position: LiquidityPosition,
+    bookkeeping: Bookkeeping,
+    market: Market,
+    exits: BTreeMap<u64, Exits>,
+}
+
+#[wasm_bindgen]
+impl Position {
+    #[wasm_bindgen(constructor)]
+    pub fn new(position: &[u8], bookkeeping: &[u8], market: &[u8]) -> Result<Position, JsError> {
+        Ok(Self {
+            position: account(position, "liquidity position")?,
+            bookkeeping: account(bookkeeping, "bookkeeping")?,
+            market: account(market, "market")?,
+            exits: BTreeMap::new(),
+        })
+    }
+
+    /// Indexes of the exits accounts balances at `currentSlot` read.
+    #[wasm_bindgen(js_name = exitsIndexes)]
+    pub fn exits_indexes(&self, current_slot: u64) -> Vec<u64> {
+        exits_window(&self.bookkeeping, &self.market, current_slot).collect()
+    }
+
+    #[wasm_bindgen(js_name = addExits)]
+    pub fn add_exits(&mut self, index: u64, data: &[u8]) -> Result<(), JsError> {
+        self.exits.insert(index, account(data, "exits")?);
+        Ok(())
+    }
+
+    pub fn balances(&self, current_slot: u64) -> Result<Balances, JsError> {
+        position_balances(
+            &self.position,
+            &self.bookkeeping,
+            &self.market,
+            &self.exits,
+            current_slot,
+        )
+        .map(Balances)
+        .map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    /// Slots until the position runs into debt at the market's current flows; `undefined`
+    /// when it isn't draining either side.
+    #[wasm_bindgen(js_name = slotsUntilDebt)]
+    pub fn slots_until_debt(&self, current_slot: u64) -> Result<Option<u64>, JsError> {
+        let balances = self.balances(current_slot)?;
+        Ok(slots_until_debt(&self.position, &self.market, &balances.0))
+    }
+}
+
+/// [`LiquidityPositionBalances`], in the token's smallest units.
+#[wasm_bindgen]
+pub struct Balances(LiquidityPositionBalances);
+
+#[wasm_bindgen]
+impl Balances {
+    #[wasm_bindgen(getter, js_name = baseBalance)]
+    pub fn base_balance(&self) -> u64 {
+        self.0.base_balance
+    }
+
+    #[wasm_bindgen(getter, js_name = quoteBalance)]
+    pub fn quote_balance(&self) -> u64 {
+        self.0.quote_balance
+    }
+
+    #[wasm_bindgen(getter, js_name = baseDebt)]
+    pub fn base_debt(&self) -> u64 {
+        self.0.base_debt
+    }
+
+    #[wasm_bindgen(getter, js_name = quoteDebt)]
+    pub fn quote_debt(&self) -> u64 {
+        self.0.quote_debt
+    }
+}
+
+fn pubkey(value: &str) -> Result<Pubkey, JsError> {
+    value
+        .parse()
+        .map_err(|error| JsError::new(&format!("invalid address `{value}`: {error}")))
+}
+
+fn account<T: AccountDeserialize>(mut data: &[u8], name: &str) -> Result<T, JsError> {
+    T::try_deserialize(&mut data).map_err(|error| JsError::new(&format!("invalid {name}: {error}")))
+}
@@ -0,0 +1,351 @@
+//! Outbound webhooks on a position's lifecycle: deposits, withdrawals, flow updates, stops,
+//! debt showing up and rebalances, for operators wiring the bots into their own incident
+//! and workflow tooling.
+//!
+//! With `WEBHOOK_URLS` set, each [`LifecycleEvent`] a bot [`fire`](Webhooks::fire)s
+//! through its [`Webhooks`] is POSTed as JSON to every URL. Requests carry the event name in `X-Twob-Event`, a per-event
+//! `X-Twob-Delivery` id that stays the same across retries, the unix time in
+//! `X-Twob-Timestamp` and, with `WEBHOOK_SECRET` set, `X-Twob-Signature: sha256=<hex>`, an
+//! HMAC-SHA256 of `<timestamp>.<body>` under the secret, so receivers can check where a
+//! call came from and reject replays.
+//!
+//! Delivery happens in the background. Network errors, 429s and 5xx are retried with
+//! doubling backoff up to `WEBHOOK_MAX_ATTEMPTS` times; any other 4xx is given up on at once.
+//! `WEBHOOK_EVENTS` limits which events are sent.
+
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+
+use anchor_lang::prelude::Pubkey;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{alerts::webhook::http_client, config::env_var};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LifecycleEvent {
+    /// Tokens added to the position with `provide_liquidity` or `add_liquidity`.
+    Deposit {
+        base_lamports: u64,
+        quote_lamports: u64,
+        reference_index: u64,
+    },
+    /// Tokens taken out of the position with `withdraw_liquidity`.
+    Withdraw {
+        base_lamports: u64,
+        quote_lamports: u64,
+        reference_index: u64,
+    },
+    /// The position's flows set with `update_liquidity_flows`.
+    FlowUpdate { base_flow: u64, quote_flow: u64 },
+    /// The position stopped with `public_stop_liquidity_position`.
+    Stop { reference_index: u64 },
+    /// The position's balances show debt.
+    DebtDetected { base_debt: u64, quote_debt: u64 },
+    /// Inventory swapped back towards balance.
+    Rebalance,
+}
+
+impl LifecycleEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Deposit { .. } => "deposit",
+            Self::Withdraw { .. } => "withdraw",
+            Self::FlowUpdate { .. } => "flow_update",
+            Self::Stop { .. } => "stop",
+            Self::DebtDetected { .. } => "debt_detected",
+            Self::Rebalance => "rebalance",
+        }
+    }
+}
+
+const EVENT_NAMES: &[&str] = &[
+    "deposit",
+    "withdraw",
+    "flow_update",
+    "stop",
+    "debt_detected",
+    "rebalance",
+];
+
+/// The body of a webhook call.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WebhookPayload {
+    pub delivery_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub bot: &'static str,
+    pub market_id: u64,
+    /// The position's authority.
+    pub authority: String,
+    #[serde(flatten)]
+    pub event: LifecycleEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    pub urls: Vec<String>,
+    /// Signs each request when set.
+    pub secret: Option<String>,
+    /// Only these events are sent; empty sends all of them.
+    pub events: BTreeSet<String>,
+    pub max_attempts: u32,
+}
+
+impl WebhookConfig {
+    /// `None` unless `WEBHOOK_URLS` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let var = |name: &str| {
+            env_var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let list = |name: &str| -> Vec<String> {
+            var(name)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let urls = list("WEBHOOK_URLS");
+        if urls.is_empty() {
+            return Ok(None);
+        }
+        for url in &urls {
+            anyhow::ensure!(
+                url.starts_with("http://") || url.starts_with("https://"),
+                "WEBHOOK_URLS must hold http(s) URLs, got `{url}`"
+            );
+        }
+        let events: BTreeSet<String> = list("WEBHOOK_EVENTS").into_iter().collect();
+        if let Some(unknown) = events
+            .iter()
+            .find(|event| !EVENT_NAMES.contains(&event.as_str()))
+        {
+            anyhow::bail!(
+                "unknown WEBHOOK_EVENTS entry `{unknown}`; expected any of {}",
+                EVENT_NAMES.join(", ")
+            );
+        }
+        let max_attempts = var("WEBHOOK_MAX_ATTEMPTS")
+            .map(|value| value.parse::<u32>())
+            .transpose()?
+            .unwrap_or(5);
+        anyhow::ensure!(max_attempts > 0, "WEBHOOK_MAX_ATTEMPTS must be at least 1");
+
+        Ok(Some(Self {
+            urls,
+            secret: var("WEBHOOK_SECRET"),
+            events,
+            max_attempts,
+        }))
+    }
+
+    fn wants(&self, event: &LifecycleEvent) -> bool {
+        self.events.is_empty() || self.events.contains(event.name())
+    }
+}
+
+/// `sha256=<hex>` of `<timestamp>.<body>` under `secret`.
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// One bot's webhooks. Each bot instance has its own, so instances sharing a process
+/// deliver under their own bot name and settings. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct Webhooks(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    config: WebhookConfig,
+    bot: &'static str,
+    client: reqwest::Client,
+}
+
+impl Webhooks {
+    /// Send webhooks for `bot` as `config` says.
+    pub fn new(config: WebhookConfig, bot: &'static str) -> anyhow::Result<Self> {
+        info!(
+            event.name = "webhooks_installed",
+            webhook.urls = config.urls.len()
+        );
+        Ok(Self(Arc::new(Inner {
+            config,
+            bot,
+            client: http_client()?,
+        })))
+    }
+
+    /// Send `event` on `authority`'s position on `market_id` to every webhook. Returns
+    /// immediately; delivery happens on background tasks.
+    pub fn fire(&self, market_id: u64, authority: &Pubkey, event: LifecycleEvent) {
+        if !self.0.config.wants(&event) {
+            return;
+        }
+        let payload = WebhookPayload {
+            delivery_id: format!("{:032x}", rand::thread_rng().r#gen::<u128>()),
+            timestamp: Utc::now(),
+            bot: self.0.bot,
+            market_id,
+            authority: authority.to_string(),
+            event,
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(error) => {
+                warn!(event.name = "webhook_encode_failed", ?error);
+                return;
+            }
+        };
+        for url in &self.0.config.urls {
+            tokio::spawn(deliver(
+                self.0.clone(),
+                url.clone(),
+                payload.clone(),
+                body.clone(),
+            ));
+        }
+    }
+}
+
+async fn deliver(webhooks: Arc<Inner>, url: String, payload: WebhookPayload, body: Vec<u8>) {
+    let event = payload.event.name();
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 1..=webhooks.config.max_attempts {
+        let timestamp = Utc::now().timestamp();
+        let mut request = webhooks
+            .client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Twob-Event", event)
+            .header("X-Twob-Delivery", &payload.delivery_id)
+            .header("X-Twob-Timestamp", timestamp.to_string());
+        if let Some(secret) = &webhooks.config.secret {
+            request = request.header("X-Twob-Signature", signature(secret, timestamp, &body));
+        }
+        let retryable = match request.body(body.clone()).send().await {
+            Ok(response) if response.status().is_success() => {
+                info!(
+                    event.name = "webhook_delivered",
+                    webhook.event = event,
+                    webhook.url = %url,
+                    webhook.attempts = attempt,
+                    monotonic_counter.webhooks_delivered_total = 1_u64,
+                );
+                return;
+            }
+            Ok(response) => {
+                let status = response.status();
+                warn!(
+                    event.name = "webhook_rejected",
+                    webhook.event = event,
+                    webhook.url = %url,
+                    http.status_code = status.as_u16(),
+                    retry.attempt = attempt,
+                );
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(error) => {
+                warn!(
+                    event.name = "webhook_send_failed",
+                    webhook.event = event,
+                    webhook.url = %url,
+                    retry.attempt = attempt,
+                    ?error,
+                );
+                true
+            }
+        };
+        if !retryable || attempt == webhooks.config.max_attempts {
+            break;
+        }
+        sleep(backoff).await;
+        backoff *= 2;
+    }
+    warn!(
+        event.name = "webhook_abandoned",
+        webhook.event = event,
+        webhook.url = %url,
+        webhook.delivery_id = %payload.delivery_id,
+        monotonic_counter.webhooks_failed_total = 1_u64,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        let signature = signature("secret", 1_700_000_000, br#"{"event":"stop"}"#);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_ne!(
+            signature,
+            super::signature("secret", 1_700_000_001, br#"{"event":"stop"}"#)
+        );
+        assert_ne!(
+            signature,
+            super::signature("other", 1_700_000_000, br#"{"event":"stop"}"#)
+        );
+    }
+
+    #[test]
+    fn payload_names_the_event_and_flattens_its_fields() {
+        let payload = WebhookPayload {
+            delivery_id: "abc".to_string(),
+            timestamp: Utc::now(),
+            bot: "oracle-flow",
+            market_id: 1,
+            authority: Pubkey::default().to_string(),
+            event: LifecycleEvent::DebtDetected {
+                base_debt: 0,
+                quote_debt: 7,
+            },
+        };
+        let json = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["event"], "debt_detected");
+        assert_eq!(json["quote_debt"], 7);
+        assert_eq!(json["market_id"], 1);
+    }
+
+    #[test]
+    fn event_filter_keeps_listed_events() {
+        let config = WebhookConfig {
+            urls: vec!["https://example.com/hook".to_string()],
+            secret: None,
+            events: BTreeSet::from(["stop".to_string()]),
+            max_attempts: 1,
+        };
+        assert!(config.wants(&LifecycleEvent::Stop { reference_index: 1 }));
+        assert!(!config.wants(&LifecycleEvent::Rebalance));
+        assert!(
+            EVENT_NAMES.contains(
+                &LifecycleEvent::FlowUpdate {
+                    base_flow: 0,
+                    quote_flow: 0
+                }
+                .name()
+            )
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! End-to-end tests of the send path and a key rotation's handover against a local
+//! validator running the twob program.
+//!
+//! They need `solana-test-validator` on `PATH` and the program in `TWOB_PROGRAM_SO` (see
+//! [`support`]), so they are ignored by default:
+//!
+//! ```sh
+//! TWOB_PROGRAM_SO=twob_anchor.so cargo test --test e2e -- --ignored --test-threads 1
+//! ```
+#![cfg(feature = "client")]
+
+mod support;
+
+use std::time::{Duration, Instant};
+
+use anchor_client::solana_sdk::signer::Signer;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id, token::spl_token,
+};
+use support::{DEPOSIT, MINT_AMOUNT, TestMarket, TestValidator, test_sender_config};
+use tokio::time::sleep;
+use twob_market_making::{
+    control::emergency::{EmergencyStop, EmergencyStopConfig},
+    execute_add_liquidity, execute_stop_position, execute_update_flows, fetch_liquidity_position,
+    rotation::hand_over_position,
+    tx::{TxSender, TxSenderConfig},
+};
+
+#[tokio::test]
+#[ignore = "needs solana-test-validator and TWOB_PROGRAM_SO"]
+async fn deposit_update_flows_and_stop() -> anyhow::Result<()> {
+    let validator = TestValidator::start().await?;
+    let market = TestMarket::create(&validator, 1, 1_000).await?;
+    let authority = market.authority.pubkey();
+    let rpc = validator.rpc();
+
+    let position = fetch_liquidity_position(&market.program, market.market_id, &authority).await?;
+    assert_eq!(position.base_flow_u64, DEPOSIT / 1_000);
+    assert_eq!(position.quote_flow_u64, DEPOSIT / 1_000);
+
+    // Deposit: the tokens leave the authority's accounts.
+    let deposit = 1_000;
+    execute_add_liquidity(
+        &market.program,
+        market.market_id,
+        deposit,
+        2 * deposit,
+        market.reference_index().await?,
+        &market.sender,
+    )
+    .await?;
+    let token_balance = |mint| {
+        let account =
+            get_associated_token_address_with_program_id(&authority, &mint, &spl_token::ID);
+        let rpc = &rpc;
+        async move {
+            anyhow::Ok(
+                rpc.get_token_account_balance(&account)
+                    .await?
+                    .amount
+                    .parse::<u64>()?,
+            )
+        }
+    };
+    assert_eq!(
+        token_balance(market.base_mint).await?,
+        MINT_AMOUNT - DEPOSIT - deposit
+    );
+    assert_eq!(
+        token_balance(market.quote_mint).await?,
+        MINT_AMOUNT - DEPOSIT - 2 * deposit
+    );
+
+    // Update flows: the position holds what was sent.
+    execute_update_flows(
+        &market.program,
+        market.market_id,
+        7_000,
+        11_000,
+        market.reference_index().await?,
+        &market.sender,
+    )
+    .await?;
+    let position = fetch_liquidity_position(&market.program, market.market_id, &authority).await?;
+    assert_eq!(position.base_flow_u64, 7_000);
+    assert_eq!(position.quote_flow_u64, 11_000);
+
+    // Stop: the flows are zeroed.
+    execute_stop_position(
+        &market.program,
+        market.market_id,
+        market.reference_index().await?,
+        &market.sender,
+    )
+    .await?;
+    let position = fetch_liquidity_position(&market.program, market.market_id, &authority).await?;
+    assert_eq!(position.base_flow_u64, 0);
+    assert_eq!(position.quote_flow_u64, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs solana-test-validator and TWOB_PROGRAM_SO"]
+async fn hand_over_position_and_rearm_the_emergency_stop() -> anyhow::Result<()> {
+    let validator = TestValidator::start().await?;
+    let market = TestMarket::create(&validator, 1, 1_000).await?;
+    // The stop is signed over a nonce account of its authority's.
+    let with_nonces = TxSenderConfig {
+        nonce_pool_size: 1,
+        ..test_sender_config()
+    };
+    let old_sender = TxSender::for_program(
+        &market.program,
+        market.authority.clone(),
+        with_nonces.clone(),
+    )?
+    .prepare_nonces()
+    .await?;
+    let stop_config = EmergencyStopConfig {
+        timeout: Duration::from_secs(10),
+    };
+    let stop =
+        EmergencyStop::prepare(stop_config, &market.program, market.market_id, &old_sender).await?;
+
+    let new_authority = validator.funded_wallet(10).await?;
+    let new_program = validator.program(&new_authority)?;
+    let new_sender = TxSender::for_program(&new_program, new_authority.clone(), with_nonces)?
+        .prepare_nonces()
+        .await?;
+    let handover = hand_over_position(
+        &market.program,
+        &old_sender,
+        &new_program,
+        &new_sender,
+        market.market_id,
+    )
+    .await?;
+    assert_eq!(handover.old_authority, market.authority.pubkey());
+    assert_eq!(handover.new_authority, new_authority.pubkey());
+    assert_eq!(handover.base_flow, DEPOSIT / 1_000);
+    assert_eq!(handover.quote_flow, DEPOSIT / 1_000);
+    assert!(handover.base_amount > 0 && handover.quote_amount > 0);
+
+    // The old position is left quoting nothing; the new one quotes what it did.
+    let old = fetch_liquidity_position(&market.program, market.market_id, &handover.old_authority)
+        .await?;
+    assert_eq!((old.base_flow_u64, old.quote_flow_u64), (0, 0));
+    let new =
+        fetch_liquidity_position(&new_program, market.market_id, &handover.new_authority).await?;
+    assert_eq!(
+        (new.base_flow_u64, new.quote_flow_u64),
+        (handover.base_flow, handover.quote_flow)
+    );
+
+    // Re-armed, the stop zeroes the new position's flows.
+    stop.rearm(&new_program, &new_sender).await?;
+    assert!(stop.trigger("test".to_string()).await);
+    let started = Instant::now();
+    loop {
+        let new = fetch_liquidity_position(&new_program, market.market_id, &handover.new_authority)
+            .await?;
+        if (new.base_flow_u64, new.quote_flow_u64) == (0, 0) {
+            break;
+        }
+        anyhow::ensure!(
+            started.elapsed() < Duration::from_secs(30),
+            "the emergency stop didn't land"
+        );
+        sleep(Duration::from_millis(250)).await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+#!/usr/bin/env sh
+# Record mainnet liquidity positions as golden fixtures through the audit's capture path.
+# Reads one `<name> <market id> <authority>` per line from stdin:
+#
+#   echo "sol-usdc 1 <authority pubkey>" | tests/fixtures/record.sh
+#
+# Each position is written to tests/fixtures/<name>.json with the balances it has now as
+# its expected output, so recording a fixture again regenerates its golden output. Those
+# balances come from this crate's own math: check them against an independent source
+# (e.g. what a withdrawal that empties the position pays out) before checking a recording
+# in as golden. Set RPC_URL to read through a provider other than the public mainnet
+# endpoint.
+set -eu
+
+cd "$(dirname "$0")/../.."
+cargo build --release --bin audit
+
+while read -r name market_id authority; do
+    [ -n "$name" ] || continue
+    CLUSTER=mainnet-beta \
+        MARKET_ID="$market_id" \
+        AUDIT_AUTHORITY="$authority" \
+        AUDIT_FIXTURE="tests/fixtures/$name.json" \
+        target/release/audit
+done
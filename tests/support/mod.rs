@@ -0,0 +1,359 @@
+//! A `solana-test-validator` with the twob program loaded, and the accounts a test needs
+//! on it: funded wallets, two mints, the program config, a market and a liquidity position.
+//!
+//! The program comes from `TWOB_PROGRAM_SO`, e.g. dumped from mainnet with
+//! `solana program dump -u m CCAmAqvza37EWzou7LoYCaGKzdJsCu1CLPMp3Wvx3Bc5 twob_anchor.so`.
+//! `solana-test-validator` must be on `PATH`.
+
+use std::{
+    env,
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anchor_client::{
+    Client, Cluster, Program,
+    solana_sdk::{
+        commitment_config::CommitmentConfig, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+        signature::Keypair, signer::Signer,
+    },
+};
+use anchor_lang::{solana_program::program_pack::Pack, system_program};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    token::spl_token,
+};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use tokio::time::sleep;
+use twob_market_making::{
+    ARRAY_LENGTH, AccountResolver, ProgramPayer, fetch_market_state, program_payer,
+    twob_anchor::{
+        self,
+        client::{accounts, args},
+    },
+    tx::{TxSender, TxSenderConfig},
+};
+
+/// Short windows, so a fresh validator reaches a usable reference index within seconds.
+pub const END_SLOT_INTERVAL: u64 = 2;
+pub const DECIMALS: u8 = 6;
+/// Minted to the liquidity provider, in raw units.
+pub const MINT_AMOUNT: u64 = 1_000_000 * 10u64.pow(DECIMALS as u32);
+/// Deposited on each side when the position is opened, in raw units.
+pub const DEPOSIT: u64 = 10_000 * 10u64.pow(DECIMALS as u32);
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A running validator, killed when dropped.
+pub struct TestValidator {
+    process: Child,
+    ledger: PathBuf,
+    pub rpc_url: String,
+    pub ws_url: String,
+}
+
+impl TestValidator {
+    /// Start a validator with the program at `TWOB_PROGRAM_SO` deployed at the twob ID.
+    pub async fn start() -> anyhow::Result<Self> {
+        let program_so = env::var("TWOB_PROGRAM_SO")
+            .map_err(|_| anyhow::anyhow!("TWOB_PROGRAM_SO env var not set"))?;
+        let rpc_port = free_port()?;
+        let faucet_port = free_port()?;
+        let ledger = env::temp_dir().join(format!(
+            "twob-test-ledger-{}-{rpc_port}",
+            std::process::id()
+        ));
+        let process = Command::new("solana-test-validator")
+            .arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(&ledger)
+            .args(["--rpc-port", &rpc_port.to_string()])
+            .args(["--faucet-port", &faucet_port.to_string()])
+            .arg("--bpf-program")
+            .arg(twob_anchor::ID.to_string())
+            .arg(&program_so)
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("failed to start solana-test-validator: {e}"))?;
+        let validator = Self {
+            process,
+            ledger,
+            rpc_url: format!("http://127.0.0.1:{rpc_port}"),
+            ws_url: format!("ws://127.0.0.1:{}", rpc_port + 1),
+        };
+
+        let rpc = validator.rpc();
+        let started = Instant::now();
+        while rpc.get_health().await.is_err() {
+            anyhow::ensure!(
+                started.elapsed() < STARTUP_TIMEOUT,
+                "solana-test-validator didn't become healthy within {STARTUP_TIMEOUT:?}"
+            );
+            sleep(Duration::from_millis(250)).await;
+        }
+        Ok(validator)
+    }
+
+    pub fn rpc(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+    }
+
+    /// A client building instructions paid for by `payer`.
+    pub fn program(&self, payer: &Keypair) -> anyhow::Result<Program<ProgramPayer>> {
+        let client = Client::new_with_options(
+            Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone()),
+            program_payer(payer.pubkey()),
+            CommitmentConfig::confirmed(),
+        );
+        Ok(client.program(twob_anchor::ID)?)
+    }
+
+    /// A new wallet holding `sol` SOL.
+    pub async fn funded_wallet(&self, sol: u64) -> anyhow::Result<Arc<Keypair>> {
+        let wallet = Keypair::new();
+        let rpc = self.rpc();
+        let signature = rpc
+            .request_airdrop(&wallet.pubkey(), sol * LAMPORTS_PER_SOL)
+            .await?;
+        let started = Instant::now();
+        while !rpc.confirm_transaction(&signature).await? {
+            anyhow::ensure!(started.elapsed() < STARTUP_TIMEOUT, "airdrop not confirmed");
+            sleep(Duration::from_millis(250)).await;
+        }
+        Ok(Arc::new(wallet))
+    }
+
+    /// Wait until the reference index is at least `index`, so its previous window exists.
+    pub async fn wait_for_reference_index(&self, index: u64) -> anyhow::Result<()> {
+        let rpc = self.rpc();
+        let target = index * ARRAY_LENGTH * END_SLOT_INTERVAL;
+        while rpc.get_slot().await? < target {
+            sleep(Duration::from_millis(200)).await;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = std::fs::remove_dir_all(&self.ledger);
+    }
+}
+
+fn free_port() -> anyhow::Result<u16> {
+    // The validator also binds the port above its RPC port for websockets; good enough for
+    // a test that runs alone.
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// A market with an open liquidity position, owned by one wallet.
+pub struct TestMarket {
+    pub market_id: u64,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub authority: Arc<Keypair>,
+    pub program: Program<ProgramPayer>,
+    pub sender: TxSender,
+}
+
+impl TestMarket {
+    /// Create the mints, the program config, market `market_id` and `authority`'s position
+    /// on it, depositing [`DEPOSIT`] of each token with flows of `flow_divisor`ths of it.
+    pub async fn create(
+        validator: &TestValidator,
+        market_id: u64,
+        flow_divisor: u64,
+    ) -> anyhow::Result<Self> {
+        let authority = validator.funded_wallet(10).await?;
+        let program = validator.program(&authority)?;
+        let sender = TxSender::for_program(&program, authority.clone(), test_sender_config())?;
+
+        let base_mint = create_mint(&program, &sender).await?;
+        let quote_mint = create_mint(&program, &sender).await?;
+        let market = Self {
+            market_id,
+            base_mint,
+            quote_mint,
+            authority,
+            program,
+            sender,
+        };
+        market.initialize_program_config().await?;
+        market.initialize_market().await?;
+        validator.wait_for_reference_index(2).await?;
+        market.provide_liquidity(flow_divisor).await?;
+        Ok(market)
+    }
+
+    pub fn market_pda(&self) -> Pubkey {
+        AccountResolver::new(twob_anchor::ID)
+            .market_pda(self.market_id)
+            .address()
+    }
+
+    /// The reference index an instruction sent now should carry.
+    pub async fn reference_index(&self) -> anyhow::Result<u64> {
+        let slot = self.program.rpc().get_slot().await?;
+        Ok((slot + ARRAY_LENGTH / 2) / ARRAY_LENGTH / END_SLOT_INTERVAL)
+    }
+
+    async fn initialize_program_config(&self) -> anyhow::Result<()> {
+        let admin = self.authority.pubkey();
+        let instruction = self
+            .program
+            .request()
+            .accounts(accounts::InitializeProgramConfig {
+                authority: admin,
+                payer: admin,
+                program_config: AccountResolver::new(twob_anchor::ID)
+                    .program_config_pda()
+                    .address(),
+                system_program: system_program::ID,
+            })
+            .args(args::InitializeProgramConfig {})
+            .instructions()?
+            .remove(0);
+        self.sender.send(vec![instruction]).await?;
+        Ok(())
+    }
+
+    async fn initialize_market(&self) -> anyhow::Result<()> {
+        let admin = self.authority.pubkey();
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market = self.market_pda();
+        let instruction = self
+            .program
+            .request()
+            .accounts(accounts::InitializeMarket {
+                authority: admin,
+                payer: admin,
+                program_config: resolver.program_config_pda().address(),
+                base_mint: self.base_mint,
+                quote_mint: self.quote_mint,
+                market,
+                base_vault: vault(&market, &self.base_mint),
+                quote_vault: vault(&market, &self.quote_mint),
+                bookkeeping: resolver.bookkeeping_pda(&market).address(),
+                base_token_program: spl_token::ID,
+                quote_token_program: spl_token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+                system_program: system_program::ID,
+            })
+            .args(args::InitializeMarket {
+                id: self.market_id,
+                start_slot: self.program.rpc().get_slot().await?,
+                end_slot_interval: END_SLOT_INTERVAL,
+                fee_bps: 30,
+                unhealthy_liquidity_fee_bps: 100,
+            })
+            .instructions()?
+            .remove(0);
+        self.sender.send(vec![instruction]).await?;
+        Ok(())
+    }
+
+    async fn provide_liquidity(&self, flow_divisor: u64) -> anyhow::Result<()> {
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let state = fetch_market_state(&self.program, self.market_id).await?;
+        let market = self.market_pda();
+        let authority = self.authority.pubkey();
+        let reference_index = self.reference_index().await?;
+        let instruction = self
+            .program
+            .request()
+            .accounts(accounts::ProvideLiquidity {
+                authority,
+                base_mint: state.market.base_mint,
+                quote_mint: state.market.quote_mint,
+                authority_base_token_account: vault(&authority, &state.market.base_mint),
+                authority_quote_token_account: vault(&authority, &state.market.quote_mint),
+                market,
+                liquidity_position: resolver
+                    .liquidity_position_pda(&market, &authority)
+                    .address(),
+                base_vault: vault(&market, &state.market.base_mint),
+                quote_vault: vault(&market, &state.market.quote_mint),
+                bookkeeping: resolver.bookkeeping_pda(&market).address(),
+                current_exits: resolver.exits_pda(&market, reference_index).address(),
+                previous_exits: resolver.exits_pda(&market, reference_index - 1).address(),
+                current_prices: resolver.prices_pda(&market, reference_index).address(),
+                previous_prices: resolver.prices_pda(&market, reference_index - 1).address(),
+                base_token_program: spl_token::ID,
+                quote_token_program: spl_token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+                system_program: system_program::ID,
+            })
+            .args(args::ProvideLiquidity {
+                reference_index,
+                base_deposit_lamports: DEPOSIT,
+                quote_deposit_lamports: DEPOSIT,
+                base_flow_u64: DEPOSIT / flow_divisor,
+                quote_flow_u64: DEPOSIT / flow_divisor,
+            })
+            .instructions()?
+            .remove(0);
+        self.sender.send(vec![instruction]).await?;
+        Ok(())
+    }
+}
+
+/// Sends that fail fast, as a test wants them.
+pub fn test_sender_config() -> TxSenderConfig {
+    TxSenderConfig {
+        compute_unit_margin_pct: None,
+        max_attempts: 2,
+        confirm_timeout: Duration::from_secs(30),
+        ..TxSenderConfig::default()
+    }
+}
+
+fn vault(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, &spl_token::ID)
+}
+
+/// A [`DECIMALS`]-decimal SPL Token mint with [`MINT_AMOUNT`] minted to the sender's payer.
+async fn create_mint(program: &Program<ProgramPayer>, sender: &TxSender) -> anyhow::Result<Pubkey> {
+    let payer = sender.payer();
+    let mint = Keypair::new();
+    let rent = program
+        .rpc()
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .await?;
+    let instructions = vec![
+        solana_system_interface::instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            rent,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &payer,
+            None,
+            DECIMALS,
+        )?,
+        create_associated_token_account_idempotent(&payer, &payer, &mint.pubkey(), &spl_token::ID),
+        spl_token::instruction::mint_to(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &vault(&payer, &mint.pubkey()),
+            &payer,
+            &[],
+            MINT_AMOUNT,
+        )?,
+    ];
+    sender.send_with_signers(instructions, &[&mint]).await?;
+    Ok(mint.pubkey())
+}
@@ -0,0 +1,562 @@
+//! In-process tests of the instruction builders on LiteSVM: no validator, no RPC, and the
+//! clock moved by hand, so reference-index edge cases (index 0 and the first usable one, a
+//! window rolling over, an index gone stale, a market left idle past its windows, an order
+//! past its end) and a key rotation's handover take milliseconds.
+//!
+//! They load the program from `TWOB_PROGRAM_SO` (see `tests/support`) and are ignored
+//! without it:
+//!
+//! ```sh
+//! TWOB_PROGRAM_SO=twob_anchor.so cargo test --test svm -- --ignored
+//! ```
+#![cfg(feature = "client")]
+
+use std::env;
+
+use anchor_client::solana_sdk::{
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use anchor_lang::{
+    InstructionData, ToAccountMetas, solana_program::program_pack::Pack, system_program,
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    token::spl_token,
+};
+use litesvm::LiteSVM;
+use twob_market_making::{
+    ARRAY_LENGTH, AccountResolver, BuildError, OrderSide, TokenPrograms,
+    accounts::{LiquidityPosition, Market, TwobAccount, deserialize_account},
+    add_liquidity_instruction, nearest_reference_index, open_liquidity_position_instructions,
+    provide_liquidity_instruction,
+    rotation::{transfer_instructions, withdrawable_balances},
+    state::MockRpc,
+    submit_order_instruction,
+    twob_anchor::{
+        self,
+        client::{accounts, args},
+    },
+    update_liquidity_flows_instruction, withdraw_liquidity_instruction,
+};
+
+const END_SLOT_INTERVAL: u64 = 1;
+const WINDOW_SLOTS: u64 = ARRAY_LENGTH * END_SLOT_INTERVAL;
+const MARKET_ID: u64 = 1;
+const MINT_AMOUNT: u64 = 1_000_000_000;
+const DEPOSIT: u64 = 100_000_000;
+/// `EndSlotAlreadyPassed` in the program's error codes.
+const END_SLOT_ALREADY_PASSED: u32 = 6002;
+
+/// A market with an open liquidity position, owned by one wallet.
+struct TestMarket {
+    svm: LiteSVM,
+    authority: Keypair,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+}
+
+impl TestMarket {
+    /// The program config, a market starting at slot 0, and a position opened at
+    /// reference index `open_at`.
+    fn create(open_at: u64) -> Self {
+        let program_so = env::var("TWOB_PROGRAM_SO").expect("TWOB_PROGRAM_SO env var not set");
+        let mut svm = LiteSVM::new();
+        svm.add_program_from_file(twob_anchor::ID, program_so)
+            .expect("loading the twob program");
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 100 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let mut market = Self {
+            svm,
+            authority,
+            base_mint: Pubkey::default(),
+            quote_mint: Pubkey::default(),
+        };
+        market.base_mint = market.create_mint();
+        market.quote_mint = market.create_mint();
+
+        let admin = market.authority.pubkey();
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = market.market_pda();
+        market
+            .send(vec![twob_ix(
+                accounts::InitializeProgramConfig {
+                    authority: admin,
+                    payer: admin,
+                    program_config: resolver.program_config_pda().address(),
+                    system_program: system_program::ID,
+                },
+                args::InitializeProgramConfig {},
+            )])
+            .unwrap();
+        market
+            .send(vec![twob_ix(
+                accounts::InitializeMarket {
+                    authority: admin,
+                    payer: admin,
+                    program_config: resolver.program_config_pda().address(),
+                    base_mint: market.base_mint,
+                    quote_mint: market.quote_mint,
+                    market: market_pda,
+                    base_vault: ata(&market_pda, &market.base_mint),
+                    quote_vault: ata(&market_pda, &market.quote_mint),
+                    bookkeeping: resolver.bookkeeping_pda(&market_pda).address(),
+                    base_token_program: spl_token::ID,
+                    quote_token_program: spl_token::ID,
+                    associated_token_program: anchor_spl::associated_token::ID,
+                    system_program: system_program::ID,
+                },
+                args::InitializeMarket {
+                    id: MARKET_ID,
+                    start_slot: 0,
+                    end_slot_interval: END_SLOT_INTERVAL,
+                    fee_bps: 30,
+                    unhealthy_liquidity_fee_bps: 100,
+                },
+            )])
+            .unwrap();
+
+        market.warp_to_index(open_at);
+        let instruction = provide_liquidity_instruction(
+            admin,
+            &market.market(),
+            token_programs(),
+            args::ProvideLiquidity {
+                reference_index: open_at,
+                base_deposit_lamports: DEPOSIT,
+                quote_deposit_lamports: DEPOSIT,
+                base_flow_u64: DEPOSIT / 1_000,
+                quote_flow_u64: DEPOSIT / 1_000,
+            },
+        )
+        .unwrap();
+        market.send(vec![instruction]).unwrap();
+        market
+    }
+
+    fn market_pda(&self) -> Pubkey {
+        AccountResolver::new(twob_anchor::ID)
+            .market_pda(MARKET_ID)
+            .address()
+    }
+
+    fn account<T: TwobAccount>(&self, address: &Pubkey) -> Option<T> {
+        let account = self.svm.get_account(address)?;
+        Some(deserialize_account(&account.data).expect("decoding a twob account"))
+    }
+
+    fn market(&self) -> Market {
+        self.account(&self.market_pda()).expect("market exists")
+    }
+
+    fn position(&self) -> LiquidityPosition {
+        self.position_of(&self.authority.pubkey())
+            .expect("position exists")
+    }
+
+    fn position_of(&self, authority: &Pubkey) -> Option<LiquidityPosition> {
+        let address = AccountResolver::new(twob_anchor::ID)
+            .liquidity_position_pda(&self.market_pda(), authority)
+            .address();
+        self.account(&address)
+    }
+
+    fn token_balance(&self, owner: &Pubkey, mint: &Pubkey) -> u64 {
+        let account = self.svm.get_account(&ata(owner, mint)).unwrap();
+        spl_token::state::Account::unpack(&account.data)
+            .unwrap()
+            .amount
+    }
+
+    /// The market's accounts and `authorities`' positions as they stand, for code that
+    /// reads them over RPC.
+    fn rpc(&self, authorities: &[Pubkey]) -> MockRpc {
+        let slot = self.svm.get_sysvar::<Clock>().slot;
+        let rpc = MockRpc::new(slot);
+        let resolver = AccountResolver::new(twob_anchor::ID);
+        let market_pda = self.market_pda();
+        let mut addresses = vec![market_pda, resolver.bookkeeping_pda(&market_pda).address()];
+        addresses.extend(authorities.iter().map(|authority| {
+            resolver
+                .liquidity_position_pda(&market_pda, authority)
+                .address()
+        }));
+        addresses.extend(
+            (0..=slot / WINDOW_SLOTS + 1)
+                .map(|index| resolver.exits_pda(&market_pda, index).address()),
+        );
+        for address in addresses {
+            if let Some(account) = self.svm.get_account(&address) {
+                rpc.set_account_data(address, account.data);
+            }
+        }
+        rpc
+    }
+
+    /// Move the clock to the first slot of window `index`.
+    fn warp_to_index(&mut self, index: u64) {
+        self.svm.warp_to_slot(index * WINDOW_SLOTS);
+        self.svm.expire_blockhash();
+    }
+
+    fn send(&mut self, instructions: Vec<Instruction>) -> Result<(), TransactionError> {
+        self.send_with_signers(instructions, &[])
+    }
+
+    /// Send `instructions` paid for and signed by `payer` alone.
+    fn send_as(
+        &mut self,
+        payer: &Keypair,
+        instructions: Vec<Instruction>,
+    ) -> Result<(), TransactionError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[payer],
+            self.svm.latest_blockhash(),
+        );
+        self.svm
+            .send_transaction(transaction)
+            .map(|_| ())
+            .map_err(|failed| failed.err)
+    }
+
+    fn send_with_signers(
+        &mut self,
+        instructions: Vec<Instruction>,
+        extra_signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        let mut signers = vec![&self.authority];
+        signers.extend_from_slice(extra_signers);
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&self.authority.pubkey()),
+            &signers,
+            self.svm.latest_blockhash(),
+        );
+        self.svm
+            .send_transaction(transaction)
+            .map(|_| ())
+            .map_err(|failed| failed.err)
+    }
+
+    fn update_flows(
+        &mut self,
+        reference_index: u64,
+        base_flow: u64,
+        quote_flow: u64,
+    ) -> Result<(), TransactionError> {
+        let instruction = update_liquidity_flows_instruction(
+            self.authority.pubkey(),
+            MARKET_ID,
+            args::UpdateLiquidityFlows {
+                reference_index,
+                base_flow_u64: base_flow,
+                quote_flow_u64: quote_flow,
+            },
+        )
+        .expect("building update_liquidity_flows");
+        self.send(vec![instruction])
+    }
+
+    /// A mint with [`MINT_AMOUNT`] minted to the authority.
+    fn create_mint(&mut self) -> Pubkey {
+        let payer = self.authority.pubkey();
+        let mint = Keypair::new();
+        let rent = self
+            .svm
+            .minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN);
+        let instructions = vec![
+            solana_system_interface::instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent,
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &payer,
+                None,
+                6,
+            )
+            .unwrap(),
+            create_associated_token_account_idempotent(
+                &payer,
+                &payer,
+                &mint.pubkey(),
+                &spl_token::ID,
+            ),
+            spl_token::instruction::mint_to(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &ata(&payer, &mint.pubkey()),
+                &payer,
+                &[],
+                MINT_AMOUNT,
+            )
+            .unwrap(),
+        ];
+        self.send_with_signers(instructions, &[&mint]).unwrap();
+        mint.pubkey()
+    }
+}
+
+fn twob_ix(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: twob_anchor::ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+fn ata(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, &spl_token::ID)
+}
+
+fn token_programs() -> TokenPrograms {
+    TokenPrograms {
+        base: spl_token::ID,
+        quote: spl_token::ID,
+    }
+}
+
+#[test]
+#[ignore = "needs TWOB_PROGRAM_SO"]
+fn index_zero_is_refused_and_one_is_the_first_usable() {
+    // Index 0 has no previous window to name, so 1 is the first a position can open at.
+    let mut market = TestMarket::create(1);
+    let args = args::UpdateLiquidityFlows {
+        reference_index: 0,
+        base_flow_u64: 5_000,
+        quote_flow_u64: 6_000,
+    };
+    assert_eq!(
+        update_liquidity_flows_instruction(market.authority.pubkey(), MARKET_ID, args),
+        Err(BuildError::ReferenceIndexZero)
+    );
+    let state = market.market();
+    let args = args::AddLiquidity {
+        reference_index: 0,
+        base_lamports: 1_000,
+        quote_lamports: 1_000,
+    };
+    assert_eq!(
+        add_liquidity_instruction(market.authority.pubkey(), &state, token_programs(), args),
+        Err(BuildError::ReferenceIndexZero)
+    );
+
+    market.update_flows(1, 5_000, 6_000).unwrap();
+    let position = market.position();
+    assert_eq!(position.base_flow_u64, 5_000);
+    assert_eq!(position.quote_flow_u64, 6_000);
+}
+
+#[test]
+#[ignore = "needs TWOB_PROGRAM_SO"]
+fn flows_carry_across_a_window_rollover() {
+    let mut market = TestMarket::create(3);
+    market.update_flows(3, 5_000, 5_000).unwrap();
+
+    // The last slot of window 3, then the first of window 4.
+    market.svm.warp_to_slot(4 * WINDOW_SLOTS - 1);
+    market.svm.expire_blockhash();
+    market.update_flows(3, 6_000, 6_000).unwrap();
+    market.warp_to_index(4);
+    market.update_flows(4, 7_000, 7_000).unwrap();
+
+    assert_eq!(market.position().base_flow_u64, 7_000);
+    let exits = AccountResolver::new(twob_anchor::ID).exits_pda(&market.market_pda(), 4);
+    assert!(market.svm.get_account(&exits.address()).is_some());
+}
+
+#[test]
+#[ignore = "needs TWOB_PROGRAM_SO"]
+fn stale_reference_index_is_rejected() {
+    let mut market = TestMarket::create(2);
+    market.warp_to_index(5);
+
+    assert!(market.update_flows(2, 1_000, 1_000).is_err());
+    assert_eq!(market.position().base_flow_u64, DEPOSIT / 1_000);
+}
+
+#[test]
+#[ignore = "needs TWOB_PROGRAM_SO"]
+fn market_left_idle_past_its_windows_takes_only_the_current_index() {
+    // Nobody touches the market for longer than an array of windows, so every window the
+    // position last named has expired.
+    let mut market = TestMarket::create(2);
+    let idle_until = 2 + 3 * ARRAY_LENGTH;
+    market.warp_to_index(idle_until);
+
+    assert!(market.update_flows(2, 1_000, 1_000).is_err());
+    assert!(market.update_flows(idle_until - 1, 1_000, 1_000).is_err());
+    market.update_flows(idle_until, 2_000, 3_000).unwrap();
+    let position = market.position();
+    assert_eq!(position.base_flow_u64, 2_000);
+    assert_eq!(position.quote_flow_u64, 3_000);
+}
+
+#[test]
+#[ignore = "needs TWOB_PROGRAM_SO"]
+fn deposit_moves_tokens_into_the_vaults() {
+    let mut market = TestMarket::create(1);
+    let state = market.market();
+    let instruction = add_liquidity_instruction(
+        market.authority.pubkey(),
+        &state,
+        token_programs(),
+        args::AddLiquidity {
+            reference_index: 1,
+            base_lamports: 1_000,
+            quote_lamports: 2_000,
+        },
+    )
+    .unwrap();
+    market.send(vec![instruction]).unwrap();
+
+    let market_pda = market.market_pda();
+    assert_eq!(
+        market.token_balance(&market_pda, &market.base_mint),
+        DEPOSIT + 1_000
+    );
+    assert_eq!(
+        market.token_balance(&market_pda, &market.quote_mint),
+        DEPOSIT + 2_000
+    );
+}
+
+#[test]
+#[ignore = "needs TWOB_PROGRAM_SO"]
+fn order_ending_in_the_past_is_rejected() {
+    let mut market = TestMarket::create(3);
+    let state = market.market();
+    let end_slot = 2 * WINDOW_SLOTS;
+    let instruction = submit_order_instruction(
+        market.authority.pubkey(),
+        &state,
+        OrderSide::Buy,
+        spl_token::ID,
+        args::SubmitOrder {
+            id: 1,
+            future_index: twob_market_making::future_index(end_slot, END_SLOT_INTERVAL),
+            reference_index: 3,
+            amount: 1_000,
+            end_slot,
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        market.send(vec![instruction]),
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(END_SLOT_ALREADY_PASSED)
+        ))
+    );
+}
+
+/// The steps `rotation::hand_over_position` sends, built the same way without RPC.
+#[tokio::test]
+#[ignore = "needs TWOB_PROGRAM_SO"]
+async fn handover_moves_the_position_to_the_new_authority() {
+    let mut market = TestMarket::create(1);
+    let old_authority = market.authority.pubkey();
+    let new_signer = Keypair::new();
+    let new_authority = new_signer.pubkey();
+    market
+        .svm
+        .airdrop(&new_authority, 10 * LAMPORTS_PER_SOL)
+        .unwrap();
+    let flows = market.position();
+
+    // Let the position trade for a window, then zero its flows.
+    market.warp_to_index(2);
+    market.update_flows(2, 0, 0).unwrap();
+
+    let (state, balances) =
+        withdrawable_balances(&market.rpc(&[old_authority]), MARKET_ID, &old_authority)
+            .await
+            .unwrap();
+    let reference_index = nearest_reference_index(state.current_slot, END_SLOT_INTERVAL);
+    let withdraw = withdraw_liquidity_instruction(
+        old_authority,
+        &state.market,
+        token_programs(),
+        args::WithdrawLiquidity {
+            reference_index,
+            base_lamports: balances.base_balance,
+            quote_lamports: balances.quote_balance,
+        },
+    )
+    .unwrap();
+    market.send(vec![withdraw]).unwrap();
+
+    let mut transfers = Vec::new();
+    for (mint, amount) in [
+        (market.base_mint, balances.base_balance),
+        (market.quote_mint, balances.quote_balance),
+    ] {
+        transfers.extend(
+            transfer_instructions(
+                &mint,
+                &spl_token::ID,
+                6,
+                &old_authority,
+                &new_authority,
+                amount,
+            )
+            .unwrap(),
+        );
+    }
+    market.send(transfers).unwrap();
+
+    let open = open_liquidity_position_instructions(
+        new_authority,
+        &state.market,
+        token_programs(),
+        args::ProvideLiquidity {
+            reference_index,
+            base_deposit_lamports: balances.base_balance,
+            quote_deposit_lamports: balances.quote_balance,
+            base_flow_u64: flows.base_flow_u64,
+            quote_flow_u64: flows.quote_flow_u64,
+        },
+    )
+    .unwrap();
+    market.send_as(&new_signer, open).unwrap();
+
+    let rpc = market.rpc(&[old_authority, new_authority]);
+    let (_, old_balances) = withdrawable_balances(&rpc, MARKET_ID, &old_authority)
+        .await
+        .unwrap();
+    assert_eq!(
+        (old_balances.base_balance, old_balances.quote_balance),
+        (0, 0)
+    );
+    let (_, new_balances) = withdrawable_balances(&rpc, MARKET_ID, &new_authority)
+        .await
+        .unwrap();
+    assert_eq!(new_balances, balances);
+
+    let position = market
+        .position_of(&new_authority)
+        .expect("new position exists");
+    assert_eq!(
+        (position.base_flow_u64, position.quote_flow_u64),
+        (flows.base_flow_u64, flows.quote_flow_u64)
+    );
+    assert_eq!(market.token_balance(&new_authority, &market.base_mint), 0);
+    assert_eq!(market.token_balance(&new_authority, &market.quote_mint), 0);
+}